@@ -0,0 +1,74 @@
+//! Generates a single server with one application account and two users.
+//!
+//! ```sh
+//! cargo run --example basic -- [output-dir]
+//! nats-server -c <output-dir>/nats.conf
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use natsforge::{
+    config::{AccountConfig, BackendKind, NatsConfig, OperatorConfig, ServerConfig, SetupResult, UserConfig},
+    NatsForge,
+};
+
+pub fn config(output_dir: &Path) -> NatsConfig {
+    NatsConfig {
+        name: Some("basic".to_string()),
+        operator: OperatorConfig {
+            name: "example-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "basic-server".to_string(),
+            port: 4222,
+            output_dir: output_dir.to_path_buf(),
+            accounts: vec![AccountConfig {
+                name: "APP".to_string(),
+                max_connections: Some(100),
+                users: vec![
+                    UserConfig {
+                        name: "publisher".to_string(),
+                        allow_pub: vec!["orders.>".to_string()],
+                        ..Default::default()
+                    },
+                    UserConfig {
+                        name: "subscriber".to_string(),
+                        allow_sub: vec!["orders.>".to_string()],
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        // Signs everything in-process, so the example runs without nsc installed.
+        backend: BackendKind::Native,
+        ..Default::default()
+    }
+}
+
+pub async fn run(output_dir: &Path) -> natsforge::Result<SetupResult> {
+    NatsForge::from_config(config(output_dir))?.initialize().await
+}
+
+#[tokio::main]
+async fn main() -> natsforge::Result<()> {
+    let output_dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("example-output/basic"));
+    let result = run(&output_dir).await?;
+
+    println!(
+        "Server config: {}",
+        result.server_config("basic-server").unwrap().display()
+    );
+    for user in ["publisher", "subscriber"] {
+        println!("Credentials:   {}", result.creds("APP", user).unwrap().display());
+    }
+    println!("Connect to:    {}", result.connections[0].url);
+    Ok(())
+}
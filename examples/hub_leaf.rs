@@ -0,0 +1,91 @@
+//! Generates a hub and a leaf server; the leaf connects to the hub with the credentials of an
+//! account that only exists on the hub.
+//!
+//! ```sh
+//! cargo run --example hub_leaf -- [output-dir]
+//! nats-server -c <output-dir>/hub/nats.conf
+//! nats-server -c <output-dir>/leaf/nats.conf
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use natsforge::{
+    config::{
+        AccountConfig, BackendKind, ExportConfig, LeafNodeConfig, NatsConfig, OperatorConfig, RemoteConfig,
+        ServerConfig, SetupResult, UserConfig,
+    },
+    NatsForge,
+};
+
+pub fn config(output_dir: &Path) -> NatsConfig {
+    NatsConfig {
+        name: Some("hub-leaf".to_string()),
+        operator: OperatorConfig {
+            name: "example-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![
+            ServerConfig {
+                name: "hub".to_string(),
+                port: 4222,
+                output_dir: output_dir.join("hub"),
+                leafnodes: LeafNodeConfig {
+                    port: Some(7422),
+                    ..Default::default()
+                },
+                accounts: vec![AccountConfig {
+                    name: "EDGE".to_string(),
+                    users: vec![UserConfig {
+                        name: "leaf".to_string(),
+                        ..Default::default()
+                    }],
+                    exports: vec![ExportConfig {
+                        subject: "telemetry.>".to_string(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ServerConfig {
+                name: "leaf".to_string(),
+                port: 4223,
+                output_dir: output_dir.join("leaf"),
+                leafnodes: LeafNodeConfig {
+                    remotes: vec![RemoteConfig {
+                        url: "nats://localhost:7422".to_string(),
+                        account: "EDGE".to_string(),
+                        // Copied from the hub's output directory.
+                        credentials: "EDGE-leaf.creds".to_string(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ],
+        backend: BackendKind::Native,
+        ..Default::default()
+    }
+}
+
+pub async fn run(output_dir: &Path) -> natsforge::Result<SetupResult> {
+    NatsForge::from_config(config(output_dir))?.initialize().await
+}
+
+#[tokio::main]
+async fn main() -> natsforge::Result<()> {
+    let output_dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("example-output/hub-leaf"));
+    let result = run(&output_dir).await?;
+
+    for connection in &result.connections {
+        let path = result.server_config(&connection.server_name).unwrap();
+        println!("{}: {} ({})", connection.server_name, path.display(), connection.url);
+    }
+    Ok(())
+}
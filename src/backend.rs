@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use crate::{
+    config::{AccountConfig, ImportConfig, OperatorConfig, PushReport, UserConfig},
+    error::{NatsForgeError, Result},
+    timing::PhaseTiming,
+};
+
+/// Issues the operator, account and user JWTs that make up a setup.
+///
+/// Implementations keep their own key material; `store_dir` is the forge's private scratch
+/// directory and may be ignored by backends that don't need one.
+#[async_trait]
+pub trait CredentialBackend: Send + Sync {
+    /// Checks that the backend is usable before anything is generated.
+    async fn preflight(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Creates the operator together with its default SYS account and returns the operator JWT.
+    async fn create_operator(&self, operator: &OperatorConfig, store_dir: &Path) -> Result<String>;
+
+    /// Whether an earlier run left an operator of this name in the store.
+    async fn operator_exists(&self, _operator_name: &str, _store_dir: &Path) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Whether the operator already has an account stored under `account`'s unique name.
+    async fn account_exists(&self, _account: &AccountConfig, _operator_name: &str, _store_dir: &Path) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns the JWT of the SYS account created alongside the operator.
+    async fn system_account_jwt(&self, operator_name: &str, store_dir: &Path) -> Result<String>;
+
+    /// Makes `account`, created with [`create_account`](Self::create_account), the operator's
+    /// system account in place of the SYS account, and returns the reissued operator JWT.
+    async fn set_system_account(
+        &self,
+        account: &AccountConfig,
+        operator_name: &str,
+        _store_dir: &Path,
+    ) -> Result<String> {
+        Err(NatsForgeError::Backend(format!(
+            "This backend can't make {} the system account of operator {}",
+            account.name, operator_name
+        )))
+    }
+
+    /// Creates an account with its limits and exports and returns the account JWT.
+    async fn create_account(&self, account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<String>;
+
+    /// Issues the token that activates `import` of a private export of `src_unique_name` for the
+    /// account with public key `target_account_id`, and returns the activation JWT.
+    async fn generate_activation(
+        &self,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        target_account_id: &str,
+        store_dir: &Path,
+    ) -> Result<String>;
+
+    /// Adds an import from `src_unique_name` to `account`, carrying `activation` when the export
+    /// is private.
+    async fn add_import(
+        &self,
+        account: &AccountConfig,
+        import_name: &str,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        activation: Option<&str>,
+        store_dir: &Path,
+    ) -> Result<()>;
+
+    /// Revokes `user` of `account`, given by name or public key, in the account's JWT. Credentials
+    /// of the user issued up to now stop working once servers have the updated JWT.
+    async fn revoke_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> Result<()>;
+
+    /// Removes `user` from `account`, so a user of the same name can be issued again. Credentials
+    /// already issued to it keep working unless it is also revoked.
+    async fn delete_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> Result<()>;
+
+    /// Returns the current JWT of an account, reflecting any imports added after creation.
+    async fn account_jwt(&self, account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<String>;
+
+    /// Issues a user and returns the contents of its `.creds` file.
+    async fn create_user(&self, account: &AccountConfig, user: &UserConfig, store_dir: &Path) -> Result<String>;
+
+    /// Pushes every account JWT to `url`: an account server for URL resolvers, or a `nats://` URL
+    /// of a running server with a full resolver. Accounts the server rejects are reported, not
+    /// returned as errors.
+    async fn push_accounts(&self, _operator_name: &str, url: &str, _store_dir: &Path) -> Result<PushReport> {
+        Err(NatsForgeError::Backend(format!(
+            "This backend can't push account JWTs to {}",
+            url
+        )))
+    }
+
+    /// Time spent in external processes so far, by command. Backends that don't spawn anything
+    /// report nothing.
+    fn process_times(&self) -> Vec<PhaseTiming> {
+        Vec::new()
+    }
+
+    /// Time spent so far in the steps a call is made of, by step and summed over calls, e.g. the
+    /// [`PHASE_EDITS`] and [`PHASE_EXPORTS`] of `create_account`. Backends that issue everything
+    /// in one step report nothing.
+    ///
+    /// [`PHASE_EDITS`]: crate::timing::PHASE_EDITS
+    /// [`PHASE_EXPORTS`]: crate::timing::PHASE_EXPORTS
+    fn step_times(&self) -> Vec<PhaseTiming> {
+        Vec::new()
+    }
+}
+
+/// The name an account is stored under: the system account keeps its name, every other account
+/// uses its unique name.
+pub(crate) fn store_account_name(account: &AccountConfig) -> &str {
+    if account.is_system_account {
+        &account.name
+    } else {
+        &account.unique_name
+    }
+}
+
+/// Whether `user` is a user's public key rather than its name.
+pub(crate) fn is_user_public_key(user: &str) -> bool {
+    user.starts_with('U') && nkeys::KeyPair::from_public_key(user).is_ok()
+}
+
+/// The user JWT in a `.creds` file.
+pub(crate) fn creds_jwt(creds: &str) -> Option<&str> {
+    let mut lines = creds.lines().map(str::trim);
+    lines.find(|line| line.contains("BEGIN NATS USER JWT"))?;
+    lines.next().filter(|jwt| !jwt.is_empty())
+}
+
+pub(crate) fn format_creds(jwt: &str, seed: &str) -> String {
+    format!(
+        "-----BEGIN NATS USER JWT-----\n{}\n------END NATS USER JWT------\n\n************************* IMPORTANT \
+         *************************\nNKEY Seed printed below can be used to sign and prove identity.\nNKEYs are \
+         sensitive and should be treated as secrets.\n\n-----BEGIN USER NKEY SEED-----\n{}\n------END USER NKEY \
+         SEED------\n\n*************************************************************\n",
+        jwt.trim(),
+        seed.trim()
+    )
+}
@@ -0,0 +1,125 @@
+//! Connecting to the generated servers with the generated credentials.
+//!
+//! Needs the `client` feature, which is on by default.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{
+    config::SetupResult,
+    error::{NatsForgeError, Result},
+};
+
+/// How long [`SetupResult::connect`] keeps trying while a server starts up.
+pub const DEFAULT_CONNECT_RETRY: Duration = Duration::from_secs(10);
+
+/// The first delay between attempts, doubled after each failure up to [`MAX_BACKOFF`].
+const INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+impl SetupResult {
+    /// Connects to `server` as `user` of `account`, retrying for [`DEFAULT_CONNECT_RETRY`] while
+    /// the server comes up.
+    pub async fn connect(&self, server: &str, account: &str, user: &str) -> Result<async_nats::Client> {
+        self.connect_within(server, account, user, DEFAULT_CONNECT_RETRY).await
+    }
+
+    /// Like [`SetupResult::connect`], retrying for `retry_for` instead. A zero duration tries once.
+    pub async fn connect_within(
+        &self,
+        server: &str,
+        account: &str,
+        user: &str,
+        retry_for: Duration,
+    ) -> Result<async_nats::Client> {
+        let url = self.client_url(server)?;
+        let creds = self.creds_contents(account, user)?;
+        connect_with_retry(server, &url, &creds, retry_for).await
+    }
+
+    /// Connects to `server` as [`NatsConfig::system_user`](crate::config::NatsConfig::system_user),
+    /// or otherwise as the first user, by name, of the configured system account.
+    pub async fn connect_sys(&self, server: &str) -> Result<async_nats::Client> {
+        if let (None, Some(path)) = (&self.artifacts, self.sys_creds(server)) {
+            if self.creds_encrypted {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "The system user's creds are encrypted; decrypt {} first",
+                    path.display()
+                )));
+            }
+            let creds = std::fs::read_to_string(path).map_err(NatsForgeError::io(path))?;
+            let url = self.client_url(server)?;
+            return connect_with_retry(server, &url, &creds, DEFAULT_CONNECT_RETRY).await;
+        }
+        let user = self.system_user().ok_or_else(|| {
+            NatsForgeError::InvalidConfig(format!(
+                "The {} account has no users to connect as",
+                self.system_account
+            ))
+        })?;
+        self.connect(server, &self.system_account, &user).await
+    }
+
+    /// Asks the running `server` to reload its config through the system account's
+    /// `$SYS.REQ.SERVER.<id>.RELOAD` API, connected as in [`SetupResult::connect_sys`].
+    pub async fn reload_server(&self, server: &str) -> Result<()> {
+        let failed = |message: String| NatsForgeError::Reload {
+            server: server.to_string(),
+            message,
+        };
+        let client = self.connect_sys(server).await?;
+        let subject = format!("$SYS.REQ.SERVER.{}.RELOAD", client.server_info().server_id);
+        let reply = client
+            .request(subject, "".into())
+            .await
+            .map_err(|err| failed(err.to_string()))?;
+        let reply: serde_json::Value =
+            serde_json::from_slice(&reply.payload).map_err(|err| failed(format!("unreadable reply: {}", err)))?;
+        match reply.get("error") {
+            Some(error) => Err(failed(
+                error["description"].as_str().unwrap_or("reload refused").to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The URL clients reach `server` at.
+    pub(crate) fn client_url(&self, server: &str) -> Result<String> {
+        self.connections
+            .iter()
+            .find(|connection| connection.server_name == server)
+            .map(|connection| connection.url.clone())
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown server: {}", server)))
+    }
+
+    fn system_user(&self) -> Option<String> {
+        let users: Vec<&String> = match &self.artifacts {
+            Some(artifacts) => artifacts.creds.get(&self.system_account)?.keys().collect(),
+            None => self.account(&self.system_account)?.creds.keys().collect(),
+        };
+        users.into_iter().min().cloned()
+    }
+}
+
+async fn connect_with_retry(server: &str, url: &str, creds: &str, retry_for: Duration) -> Result<async_nats::Client> {
+    let failed = |message: String| NatsForgeError::Connect {
+        server: server.to_string(),
+        message,
+    };
+    let deadline = Instant::now() + retry_for;
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let options = async_nats::ConnectOptions::with_credentials(creds).map_err(|err| failed(err.to_string()))?;
+        match options.connect(url).await {
+            Ok(client) => return Ok(client),
+            Err(err) if Instant::now() + backoff > deadline => {
+                return Err(failed(format!("{} at {}", err, url)));
+            }
+            Err(_) => {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    config::{ClusterConfig, ClusterTemplate, NatsConfig, ServerConfig},
+    error::{NatsForgeError, Result},
+    net,
+};
+
+impl NatsConfig {
+    /// Replaces every entry of `clusters` with its nodes, appended to `servers` in order.
+    /// [`NatsForge::from_config`](crate::NatsForge::from_config) does this before anything else.
+    pub fn expand_cluster_templates(&mut self) -> Result<()> {
+        for template in std::mem::take(&mut self.clusters) {
+            self.servers.extend(expand(template)?);
+        }
+        Ok(())
+    }
+}
+
+fn expand(template: ClusterTemplate) -> Result<Vec<ServerConfig>> {
+    if template.size == 0 {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "Cluster {} needs at least one node",
+            template.name
+        )));
+    }
+    let port = |base: u16, node: u16| {
+        base.checked_add(node).ok_or_else(|| {
+            NatsForgeError::InvalidConfig(format!(
+                "Cluster {} has {} nodes, too many for ports from {}",
+                template.name, template.size, base
+            ))
+        })
+    };
+    let route_host = net::client_host(template.host.as_deref());
+    let cluster_ports = (0..template.size)
+        .map(|node| port(template.base_cluster_port, node))
+        .collect::<Result<Vec<u16>>>()?;
+
+    let mut accounts = Some(template.accounts);
+    let mut servers = Vec::new();
+    for node in 0..template.size {
+        let name = format!("{}-{}", template.name, node + 1);
+        let output_dir = template.output_dir.join(&name);
+        let mut jetstream = template.jetstream.clone();
+        // With absolute paths, the default store_dir already lands below each node's output_dir.
+        if jetstream.store_dir.is_none() && !jetstream.absolute_paths {
+            jetstream.store_dir = Some(output_dir.join("jetstream").to_string_lossy().into_owned());
+        }
+        let routes = cluster_ports
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != usize::from(node))
+            .map(|(_, port)| format!("nats-route://{}", net::host_port(&route_host, *port)))
+            .collect();
+        servers.push(ServerConfig {
+            port: port(template.base_client_port, node)?,
+            host: template.host.clone(),
+            jetstream,
+            accounts: accounts.take().unwrap_or_default(),
+            cluster: Some(ClusterConfig {
+                name: template.name.clone(),
+                port: cluster_ports[usize::from(node)],
+                routes,
+                advertise: None,
+                auth: template.auth.clone(),
+            }),
+            name,
+            output_dir,
+            ..Default::default()
+        });
+    }
+    Ok(servers)
+}
@@ -0,0 +1,160 @@
+//! A `docker-compose.yml` that runs the generated servers in containers.
+//!
+//! Each server becomes a service running the official `nats` image. Its output directory is
+//! mounted at the same path inside the container, so the paths in `nats.conf` stay valid, and a
+//! second config, `nats.compose.conf`, is written next to `nats.conf` with the server listening
+//! on all interfaces and its leafnode remotes pointing at the other services.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{config::ServerConfig, net, prometheus::ExporterOptions, yaml};
+
+/// The image services run unless [`ComposeOptions::image`] says otherwise.
+pub const DEFAULT_IMAGE: &str = "nats:2.10-alpine";
+/// The config written next to each `nats.conf` for the container.
+pub const COMPOSE_CONFIG_FILE: &str = "nats.compose.conf";
+/// Where the container's config is mounted.
+pub const CONTAINER_CONFIG: &str = "/etc/nats/nats.conf";
+
+/// How [`crate::NatsForge::emit_docker_compose`] lays out the services.
+#[derive(Debug, Clone)]
+pub struct ComposeOptions {
+    pub image: String,
+    /// Hostnames to use in leafnode remote URLs instead of the hosts they name, such as
+    /// `hub.example.com` → `hub`. Remotes to `localhost` and the loopback addresses are pointed at
+    /// the service listening for leaf nodes on their port without an entry here.
+    pub hostnames: HashMap<String, String>,
+    /// Adds a `<service>-exporter` service scraping every server with a monitoring `http_port`.
+    /// Its metrics port isn't published, since the exporters would all claim the same one; scrape
+    /// it from the compose network.
+    pub exporter: Option<ExporterOptions>,
+}
+
+impl Default for ComposeOptions {
+    fn default() -> Self {
+        ComposeOptions {
+            image: DEFAULT_IMAGE.to_string(),
+            hostnames: HashMap::new(),
+            exporter: None,
+        }
+    }
+}
+
+/// The compose service name of a server: its name in lowercase, with every character compose
+/// doesn't allow replaced by `-`.
+pub fn service_name(server_name: &str) -> String {
+    server_name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '.' | '_' | '-' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect()
+}
+
+/// `server` as it runs in a container: with its files below `output_dir`, listening on all
+/// interfaces and with leafnode remotes rewritten to the hosts `host_of` names for the servers
+/// they connect to. Also returns those servers.
+pub(crate) fn container_server<'a>(
+    servers: &'a [ServerConfig],
+    server: &ServerConfig,
+    output_dir: &Path,
+    hostnames: &HashMap<String, String>,
+    host_of: impl Fn(&ServerConfig) -> String,
+) -> (ServerConfig, Vec<&'a ServerConfig>) {
+    let mut container = server.clone();
+    container.output_dir = output_dir.to_path_buf();
+    container.host = None;
+    let mut upstreams: Vec<&ServerConfig> = Vec::new();
+    for remote in &mut container.leafnodes.remotes {
+        let Some((host, port)) = net::url_host_port(&remote.url) else {
+            continue;
+        };
+        let upstream = servers
+            .iter()
+            .filter(|other| other.name != server.name && other.leafnodes.port == Some(port))
+            .find(|other| match hostnames.get(&host) {
+                Some(mapped) => *mapped == host_of(other),
+                None => is_local(&host),
+            });
+        let new_host = match (hostnames.get(&host), upstream) {
+            (Some(mapped), _) => mapped.clone(),
+            (None, Some(upstream)) => host_of(upstream),
+            (None, None) => continue,
+        };
+        remote.url = replace_host(&remote.url, &new_host, port);
+        if let Some(upstream) = upstream {
+            if !upstreams.iter().any(|known| known.name == upstream.name) {
+                upstreams.push(upstream);
+            }
+        }
+    }
+    (container, upstreams)
+}
+
+fn is_local(host: &str) -> bool {
+    matches!(host, "localhost" | "127.0.0.1" | "::1" | "0.0.0.0" | "::")
+}
+
+/// `url` with its host and port replaced by `host` and `port`.
+fn replace_host(url: &str, host: &str, port: u16) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (format!("{}://", scheme), rest),
+        None => (String::new(), url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    let userinfo = authority
+        .rsplit_once('@')
+        .map_or(String::new(), |(userinfo, _)| format!("{}@", userinfo));
+    format!("{}{}{}{}", scheme, userinfo, net::host_port(host, port), path)
+}
+
+/// One service of the compose file.
+pub(crate) struct ComposeService {
+    pub(crate) name: String,
+    pub(crate) image: String,
+    pub(crate) command: Vec<String>,
+    /// `host:container` paths.
+    pub(crate) volumes: Vec<(PathBuf, PathBuf)>,
+    pub(crate) ports: Vec<u16>,
+    pub(crate) depends_on: Vec<String>,
+}
+
+/// Renders the compose file.
+pub(crate) fn render_compose(services: &[ComposeService]) -> String {
+    let services: serde_json::Map<String, Value> = services
+        .iter()
+        .map(|service| {
+            let mut spec = json!({
+                "image": service.image,
+                "command": service.command,
+            });
+            if !service.volumes.is_empty() {
+                let volumes: Vec<String> = service
+                    .volumes
+                    .iter()
+                    .map(|(host, container)| format!("{}:{}", host.to_string_lossy(), container.to_string_lossy()))
+                    .collect();
+                spec["volumes"] = json!(volumes);
+            }
+            if !service.ports.is_empty() {
+                let ports: Vec<String> = service.ports.iter().map(|port| format!("{}:{}", port, port)).collect();
+                spec["ports"] = json!(ports);
+            }
+            if !service.depends_on.is_empty() {
+                spec["depends_on"] = json!(service.depends_on);
+            }
+            (service.name.clone(), spec)
+        })
+        .collect();
+    yaml::to_yaml(&json!({ "services": services }))
+}
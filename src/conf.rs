@@ -0,0 +1,404 @@
+//! nats-server configs as a tree.
+//!
+//! A [`ConfDocument`] is what a server's `nats.conf` is rendered from: objects of ordered keys
+//! holding strings, numbers, durations, arrays and nested objects. Building the tree first lets
+//! a generated config be inspected or adjusted before it is written, and keeps the layout of the
+//! rendered text in one place.
+//!
+//! The text is written the same way for every block:
+//!
+//! - `key: value` for scalars and arrays, `key { ... }` for objects, indented by four spaces;
+//! - arrays of scalars on one line, arrays holding objects with one element per line;
+//! - objects in an array on one line when all their values are scalars;
+//! - keys quoted unless they are plain words such as `port` or an account ID;
+//! - blocks at the top level set apart by blank lines;
+//! - blocks moved to files of their own as `include "file"`.
+
+use crate::error::{NatsForgeError, Result};
+
+/// A value in a nats-server config.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfValue {
+    /// Written double-quoted, with backslashes, quotes and control characters escaped.
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    /// A Go duration such as `2m` or `1h30m`, written quoted.
+    Duration(String),
+    /// Written as is, for values nats-server reads unquoted: resolver types, `URL(...)`,
+    /// `MEMORY` and mapping weights such as `90%`.
+    Raw(String),
+    Array(Vec<ConfValue>),
+    Object(ConfObject),
+    /// Written as `include "path"` in place of the key, for a block moved to a file of its own
+    /// with [`ConfDocument::split`]. nats-server resolves `path` relative to the including file.
+    Include(String),
+}
+
+impl ConfValue {
+    /// The text of a string, duration or raw value, or the path of an include.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfValue::String(text) | ConfValue::Duration(text) | ConfValue::Raw(text) | ConfValue::Include(text) => {
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ConfValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[ConfValue]> {
+        match self {
+            ConfValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&ConfObject> {
+        match self {
+            ConfValue::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut ConfObject> {
+        match self {
+            ConfValue::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    fn is_scalar(&self) -> bool {
+        !matches!(self, ConfValue::Array(_) | ConfValue::Object(_))
+    }
+
+    /// Whether the value is written over several lines, or includes a file that is.
+    fn is_block(&self) -> bool {
+        match self {
+            ConfValue::Object(_) | ConfValue::Include(_) => true,
+            ConfValue::Array(items) => items.iter().any(|item| matches!(item, ConfValue::Object(_))),
+            _ => false,
+        }
+    }
+}
+
+impl From<&str> for ConfValue {
+    fn from(value: &str) -> Self {
+        ConfValue::String(value.to_string())
+    }
+}
+
+impl From<String> for ConfValue {
+    fn from(value: String) -> Self {
+        ConfValue::String(value)
+    }
+}
+
+impl From<&String> for ConfValue {
+    fn from(value: &String) -> Self {
+        ConfValue::String(value.clone())
+    }
+}
+
+impl From<bool> for ConfValue {
+    fn from(value: bool) -> Self {
+        ConfValue::Bool(value)
+    }
+}
+
+impl From<u16> for ConfValue {
+    fn from(value: u16) -> Self {
+        ConfValue::Integer(value.into())
+    }
+}
+
+impl From<u32> for ConfValue {
+    fn from(value: u32) -> Self {
+        ConfValue::Integer(value.into())
+    }
+}
+
+impl From<i64> for ConfValue {
+    fn from(value: i64) -> Self {
+        ConfValue::Integer(value)
+    }
+}
+
+impl From<f64> for ConfValue {
+    fn from(value: f64) -> Self {
+        ConfValue::Float(value)
+    }
+}
+
+impl From<ConfObject> for ConfValue {
+    fn from(value: ConfObject) -> Self {
+        ConfValue::Object(value)
+    }
+}
+
+impl<T: Into<ConfValue>> From<Vec<T>> for ConfValue {
+    fn from(items: Vec<T>) -> Self {
+        ConfValue::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// JSON as it is written in a nats.conf: strings quoted, and integers, floats, bools, arrays and
+/// objects as such. `null` has no counterpart.
+impl TryFrom<&serde_json::Value> for ConfValue {
+    type Error = NatsForgeError;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self> {
+        Ok(match value {
+            serde_json::Value::Null => {
+                return Err(NatsForgeError::InvalidConfig(
+                    "null has no nats.conf equivalent".to_string(),
+                ))
+            }
+            serde_json::Value::Bool(flag) => ConfValue::Bool(*flag),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(integer) => ConfValue::Integer(integer),
+                None => ConfValue::Float(number.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(text) => ConfValue::String(text.clone()),
+            serde_json::Value::Array(items) => {
+                ConfValue::Array(items.iter().map(ConfValue::try_from).collect::<Result<_>>()?)
+            }
+            serde_json::Value::Object(entries) => ConfValue::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), ConfValue::try_from(value)?)))
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .collect(),
+            ),
+        })
+    }
+}
+
+/// The keys of a block, in the order they are written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfObject {
+    entries: Vec<(String, ConfValue)>,
+}
+
+impl ConfObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `key`, keeping its place when it is already set.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<ConfValue>) -> &mut Self {
+        let key = key.into();
+        let value = value.into();
+        match self.entries.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.entries.push((key, value)),
+        }
+        self
+    }
+
+    /// Like [`set`](Self::set), for building an object in one expression.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<ConfValue>) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ConfValue> {
+        self.entries
+            .iter()
+            .find(|(existing, _)| existing == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut ConfValue> {
+        self.entries
+            .iter_mut()
+            .find(|(existing, _)| existing == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The object under `key`, added empty at the end when it isn't set yet.
+    pub fn object_mut(&mut self, key: &str) -> &mut ConfObject {
+        if !matches!(self.get(key), Some(ConfValue::Object(_))) {
+            self.set(key, ConfObject::new());
+        }
+        self.get_mut(key)
+            .and_then(ConfValue::as_object_mut)
+            .expect("object was just set")
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<ConfValue> {
+        let index = self.entries.iter().position(|(existing, _)| existing == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ConfValue)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K: Into<String>, V: Into<ConfValue>> FromIterator<(K, V)> for ConfObject {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        let mut object = ConfObject::new();
+        for (key, value) in entries {
+            object.set(key, value);
+        }
+        object
+    }
+}
+
+/// A whole config file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfDocument {
+    pub root: ConfObject,
+}
+
+impl ConfDocument {
+    pub fn new(root: ConfObject) -> Self {
+        ConfDocument { root }
+    }
+
+    /// Moves `key` to a document of its own, leaving an include of `path` in its place. `None`
+    /// when the document doesn't set `key`.
+    pub fn split(&mut self, key: &str, path: &str) -> Option<ConfDocument> {
+        let value = self.root.get_mut(key)?;
+        let value = std::mem::replace(value, ConfValue::Include(path.to_string()));
+        Some(ConfDocument::new(ConfObject::new().with(key, value)))
+    }
+
+    /// The config as nats-server reads it.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        let mut previous_block = false;
+        for (index, (key, value)) in self.root.iter().enumerate() {
+            if index > 0 && (previous_block || value.is_block()) {
+                text.push('\n');
+            }
+            render_entry(&mut text, key, value, 0);
+            previous_block = value.is_block();
+        }
+        text
+    }
+}
+
+fn indent(text: &mut String, depth: usize) {
+    text.push_str(&"    ".repeat(depth));
+}
+
+fn render_entry(text: &mut String, key: &str, value: &ConfValue, depth: usize) {
+    indent(text, depth);
+    if let ConfValue::Include(path) = value {
+        text.push_str(&format!("include {}\n", quote(path)));
+        return;
+    }
+    text.push_str(&render_key(key));
+    match value {
+        ConfValue::Object(object) => {
+            text.push_str(" {\n");
+            for (key, value) in object.iter() {
+                render_entry(text, key, value, depth + 1);
+            }
+            indent(text, depth);
+            text.push_str("}\n");
+        }
+        ConfValue::Array(items) if value.is_block() => {
+            text.push_str(": [\n");
+            for item in items {
+                indent(text, depth + 1);
+                render_element(text, item, depth + 1);
+                text.push('\n');
+            }
+            indent(text, depth);
+            text.push_str("]\n");
+        }
+        value => {
+            text.push_str(": ");
+            text.push_str(&render_inline(value));
+            text.push('\n');
+        }
+    }
+}
+
+/// An element of an array written one element per line.
+fn render_element(text: &mut String, value: &ConfValue, depth: usize) {
+    match value {
+        ConfValue::Object(object) if !object.iter().all(|(_, value)| value.is_scalar()) => {
+            text.push_str("{\n");
+            for (key, value) in object.iter() {
+                render_entry(text, key, value, depth + 1);
+            }
+            indent(text, depth);
+            text.push('}');
+        }
+        value => text.push_str(&render_inline(value)),
+    }
+}
+
+fn render_inline(value: &ConfValue) -> String {
+    match value {
+        ConfValue::String(text) | ConfValue::Duration(text) => quote(text),
+        ConfValue::Include(path) => format!("include {}", quote(path)),
+        ConfValue::Integer(number) => number.to_string(),
+        ConfValue::Float(number) => number.to_string(),
+        ConfValue::Bool(flag) => flag.to_string(),
+        ConfValue::Raw(text) => text.clone(),
+        ConfValue::Array(items) => {
+            let items: Vec<String> = items.iter().map(render_inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        ConfValue::Object(object) if object.is_empty() => "{}".to_string(),
+        ConfValue::Object(object) => {
+            let entries: Vec<String> = object
+                .iter()
+                .map(|(key, value)| format!("{}: {}", render_key(key), render_inline(value)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+    }
+}
+
+/// Keys such as subjects are quoted; plain words are not.
+fn render_key(key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// `value` as a double-quoted nats.conf string. Inside quotes nats-server reads `\\`, `\"`, `\t`,
+/// `\n`, `\r` and `\xHH` as escapes, so backslashes (as in Windows paths), quotes and control
+/// characters are escaped and everything else is written as is.
+pub(crate) fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\x{:02x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,127 @@
+//! Users of conf-mode servers, which `nats.conf` defines with a generated nkey, password or token
+//! instead of a JWT.
+
+use std::collections::HashMap;
+
+use nkeys::{KeyPair, XKey};
+use uuid::Uuid;
+
+use crate::{
+    config::{ConfCredential, Permissions, ServerConfig, SubjectPermissions, UserConfig},
+    error::{NatsForgeError, Result},
+};
+
+/// A generated credential of a conf-mode user.
+pub(crate) enum Credential {
+    Nkey { public_key: String, seed: String },
+    Password(String),
+    Token(String),
+}
+
+/// Credentials by account name, then user name.
+pub(crate) type Credentials = HashMap<String, HashMap<String, Credential>>;
+
+impl Credential {
+    fn generate(kind: ConfCredential) -> Result<Self> {
+        Ok(match kind {
+            ConfCredential::Nkey => {
+                let key = KeyPair::new_user();
+                let seed = key
+                    .seed()
+                    .map_err(|err| NatsForgeError::Backend(format!("Failed to read nkey seed: {}", err)))?;
+                Credential::Nkey {
+                    public_key: key.public_key(),
+                    seed,
+                }
+            }
+            ConfCredential::Password => Credential::Password(Uuid::new_v4().simple().to_string()),
+            ConfCredential::Token => Credential::Token(Uuid::new_v4().simple().to_string()),
+        })
+    }
+
+    /// What the client connects with: the seed, password or token.
+    pub(crate) fn secret(&self) -> &str {
+        match self {
+            Credential::Nkey { seed, .. } => seed,
+            Credential::Password(secret) | Credential::Token(secret) => secret,
+        }
+    }
+
+    /// What the secret's file name ends in.
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            Credential::Nkey { .. } => "nk",
+            Credential::Password(_) => "password",
+            Credential::Token(_) => "token",
+        }
+    }
+
+    /// The file in the server's output directory the secret is written to.
+    pub(crate) fn file_name(&self, account: &str, user: &str) -> String {
+        format!("{}.{}", username(account, user), self.extension())
+    }
+}
+
+/// The keys of a server's auth callout: the issuer account key the service signs user JWTs with,
+/// and the curve key nats-server encrypts requests to it with.
+pub(crate) struct CalloutKeys {
+    pub(crate) issuer: KeyPair,
+    pub(crate) xkey: Option<XKey>,
+}
+
+impl CalloutKeys {
+    pub(crate) const ISSUER_FILE: &'static str = "auth-callout-issuer.nk";
+    pub(crate) const XKEY_FILE: &'static str = "auth-callout.xk";
+
+    /// Generates the keys of `server`'s auth callout, if it has one.
+    pub(crate) fn generate(server: &ServerConfig) -> Option<Self> {
+        let callout = server.auth_callout.as_ref()?;
+        Some(CalloutKeys {
+            issuer: KeyPair::new_account(),
+            xkey: callout.xkey.then(XKey::new),
+        })
+    }
+
+    /// The seeds the service needs, by the file in the server's output directory they go to.
+    pub(crate) fn seed_files(&self) -> Result<Vec<(&'static str, String)>> {
+        let seed_error =
+            |err: nkeys::error::Error| NatsForgeError::Backend(format!("Failed to read nkey seed: {}", err));
+        let mut files = vec![(Self::ISSUER_FILE, self.issuer.seed().map_err(seed_error)?)];
+        if let Some(xkey) = &self.xkey {
+            files.push((Self::XKEY_FILE, xkey.seed().map_err(seed_error)?));
+        }
+        Ok(files)
+    }
+}
+
+/// Generates a credential for every user of `server`.
+pub(crate) fn issue(server: &ServerConfig) -> Result<Credentials> {
+    let mut credentials = Credentials::new();
+    for account in &server.accounts {
+        for user in &account.users {
+            credentials
+                .entry(account.name.clone())
+                .or_default()
+                .insert(user.name.clone(), Credential::generate(user.conf_credential)?);
+        }
+    }
+    Ok(credentials)
+}
+
+/// The name a password user logs in with, which is unique on its server and also how
+/// `no_auth_user` refers to the user.
+pub(crate) fn username(account: &str, user: &str) -> String {
+    format!("{}-{}", account, user)
+}
+
+/// The subjects `user` may publish and subscribe to, with its pub-sub lists applied to both.
+pub(crate) fn permissions(user: &UserConfig) -> Permissions {
+    let direction = |allow: &[String], deny: &[String]| SubjectPermissions {
+        allow: user.allow_pubsub.iter().chain(allow).cloned().collect(),
+        deny: user.deny_pubsub.iter().chain(deny).cloned().collect(),
+    };
+    Permissions {
+        publish: direction(&user.allow_pub, &user.deny_pub),
+        subscribe: direction(&user.allow_sub, &user.deny_sub),
+    }
+}
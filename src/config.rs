@@ -1,42 +1,750 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::{
+    error::{NatsForgeError, Result},
+    graph::AccountGraph,
+    manifest::SetupManifest,
+    subject::covers,
+    timing::SetupTimings,
+};
+
+/// The name of the system account `nsc init` and the native backend create with every operator.
+pub const DEFAULT_SYSTEM_ACCOUNT: &str = "SYS";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct NatsConfig {
     pub name: Option<String>,
+    /// The operator servers trust unless they pick one of `operators`.
+    #[serde(default)]
     pub operator: OperatorConfig,
+    /// Operators besides `operator`, one per security domain, e.g. for the two sides of a
+    /// leafnode link. A config may also list every operator here and leave `operator` out, in
+    /// which case the first of them is the default.
+    #[serde(default)]
+    pub operators: Vec<OperatorConfig>,
     #[serde(default)]
     pub servers: Vec<ServerConfig>,
+    /// Clusters given as one definition each, expanded into `servers` by
+    /// [`NatsConfig::expand_cluster_templates`].
+    #[serde(default)]
+    pub clusters: Vec<ClusterTemplate>,
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// nsc binary used by the nsc backend; defaults to `nsc` on PATH.
+    #[serde(default)]
+    pub nsc_path: Option<PathBuf>,
+    /// How long a single nsc invocation may run before it is killed, e.g. `1m`; defaults to
+    /// [`DEFAULT_NSC_TIMEOUT`](crate::DEFAULT_NSC_TIMEOUT).
+    #[serde(default)]
+    pub nsc_timeout: Option<String>,
+    /// How often an nsc command that only reads the store, like `generate creds`, is run again
+    /// after failing or timing out; defaults to
+    /// [`DEFAULT_NSC_RETRIES`](crate::DEFAULT_NSC_RETRIES).
+    #[serde(default)]
+    pub nsc_retries: Option<u32>,
+    /// nats-server binary [`NatsForge::check`](crate::NatsForge::check) runs; defaults to
+    /// `nats-server` on PATH.
+    #[serde(default)]
+    pub nats_server_path: Option<PathBuf>,
+    /// Whether `initialize()` runs [`NatsForge::check`](crate::NatsForge::check) on the configs it
+    /// wrote.
+    #[serde(default)]
+    pub verify_configs: VerifyMode,
+    /// Directory the nsc backend keeps its store in, so a later run with `reuse_existing` finds
+    /// the same operator and accounts. Defaults to a temporary directory removed with the forge.
+    /// With several operators, each gets a store in a subdirectory named after it.
+    #[serde(default)]
+    pub nsc_store_dir: Option<PathBuf>,
+    /// Whether operator and account names get a unique suffix in the backend's store.
+    #[serde(default)]
+    pub naming: NamingMode,
+    #[serde(default)]
+    pub durability: Durability,
+    /// Whether artifacts are written to `output_dir` or only returned in the [`SetupResult`].
+    #[serde(default)]
+    pub artifact_mode: ArtifactMode,
+    /// Create output directories with mode 0700 on Unix. `.creds` files are always 0600.
+    #[serde(default)]
+    pub private_output_dirs: bool,
+    /// Write a [`SetupManifest`](crate::manifest::SetupManifest) as `manifest.json` into every
+    /// output directory.
+    #[serde(default)]
+    pub write_manifest: bool,
+    /// Encrypt the `.creds` files and bearer JWTs that are written, so no plaintext credentials
+    /// end up in the output directories.
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    /// A user issued under the system account, for monitoring through `$SYS` subjects. Its creds
+    /// are written as `<system account>-<name>.creds` into every output directory; see
+    /// [`SetupResult::sys_creds`].
+    #[serde(default)]
+    pub system_user: Option<UserConfig>,
+    /// A system account of its own, created as a regular account and made the operator's
+    /// system account instead of the `SYS` account the operator is created with. It is defined on
+    /// the first server.
+    #[serde(default)]
+    pub system_account: Option<SystemAccountConfig>,
+    /// Variables set on child processes (nsc, nats-server). Seeds and tokens are rejected.
+    #[serde(default)]
+    pub process_env: HashMap<String, String>,
+    /// Variables passed through from the forge's own environment to child processes, in addition
+    /// to [`BASE_INHERITED_ENV`](crate::env::BASE_INHERITED_ENV). Everything else is scrubbed.
+    #[serde(default)]
+    pub inherit_env: Vec<String>,
+    /// How many accounts, or users, are issued at once; defaults to
+    /// [`DEFAULT_CONCURRENCY`](crate::DEFAULT_CONCURRENCY).
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// Streams that mirror a stream of another JetStream domain, e.g. a hub collecting what its
+    /// leaf nodes buffered. Created by [`NatsForge::provision`](crate::NatsForge::provision).
+    #[serde(default)]
+    pub mirrors: Vec<MirrorSpec>,
+    /// Config files this one is laid over, relative to it, merged in order by
+    /// [`NatsConfig::from_json_file`]. Empty once loaded.
+    #[serde(default)]
+    pub extends: Vec<PathBuf>,
+    /// Overrides for one environment each, by name. [`NatsConfig::from_json_file`] applies the
+    /// one it is asked for; the others, and all of them without a profile, change nothing.
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileOverride>,
+}
+
+/// The overrides of one profile, merged over the config like the file of an `extends` overlay.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ProfileOverride {
+    /// Overrides by server name; each has to name one of the config's servers.
+    #[serde(default)]
+    pub servers: HashMap<String, ServerOverride>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerOverride {
+    /// `false` leaves the server out of the config.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    /// Replaces the server's TLS settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jetstream: Option<JetStreamLimits>,
+}
+
+impl Default for ServerOverride {
+    fn default() -> Self {
+        ServerOverride {
+            enabled: true,
+            port: None,
+            output_dir: None,
+            tls: None,
+            jetstream: None,
+        }
+    }
+}
+
+/// The storage limits of [`JetStreamConfig`], for overriding them alone.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct JetStreamLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<i64>, // Bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_storage: Option<i64>, // Bytes
+}
+
+/// The system account of [`NatsConfig::system_account`].
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SystemAccountConfig {
+    pub name: String,
+    #[serde(default)]
+    pub users: Vec<UserConfig>,
+    /// E.g. a service export of `$SYS.REQ.ACCOUNT.*.CLAIMS.LOOKUP` for accounts that look up
+    /// account JWTs themselves.
+    #[serde(default)]
+    pub exports: Vec<ExportConfig>,
+}
+
+/// How hard the forge tries to make generated artifacts durable before `initialize()` returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Durability {
+    /// Atomic rename only; contents may still sit in the page cache.
+    Fast,
+    /// Fsync every artifact and, on Unix, the directory it was renamed into.
+    #[default]
+    Fsync,
+}
+
+/// Where `initialize()` puts the generated artifacts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactMode {
+    /// Write everything below each server's `output_dir`.
+    #[default]
+    Files,
+    /// Write nothing and return the contents in [`SetupResult::artifacts`]. Paths in the rendered
+    /// `nats.conf` still point below `output_dir`, where the caller is expected to place the files
+    /// listed in [`SetupArtifacts::server_files`].
+    InMemory,
+}
+
+/// Whether generated configs are checked with `nats-server -t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyMode {
+    #[default]
+    Off,
+    /// Check when nats-server can be found, and warn when it can't.
+    IfAvailable,
+    /// Fail when nats-server can't be found.
+    Required,
+}
+
+/// How the operator and accounts are named in the backend's store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NamingMode {
+    /// Append a UUID to the operator name and to accounts without a `unique_name`, so runs never
+    /// collide in a shared store.
+    #[default]
+    Unique,
+    /// Use the names as configured. Running again against a persistent store needs
+    /// `reuse_existing`.
+    Exact,
+}
+
+/// How a server authenticates clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// Trust an operator and give every user a `.creds` file with its JWT.
+    #[default]
+    Operator,
+    /// Define the accounts and their users in `nats.conf`, without any JWTs or credential backend.
+    /// Settings that only exist in JWTs, such as signing keys or user limits, are rejected.
+    Conf,
+}
+
+/// How a user of a conf-mode server authenticates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfCredential {
+    /// A generated nkey, whose seed is written to `<account>-<user>.nk`.
+    #[default]
+    Nkey,
+    /// The username `<account>-<user>` with a generated password, written to
+    /// `<account>-<user>.password`.
+    Password,
+    /// A generated token, written to `<account>-<user>.token`. nats-server logs token clients in
+    /// to its global account, so a token user has to be the server's only user.
+    Token,
+}
+
+/// How a server's `nats.conf` sets the operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OperatorRef {
+    /// Inline the operator JWT.
+    #[default]
+    Inline,
+    /// Refer to the absolute path of the `operator.jwt` written to the server's output directory,
+    /// which keeps the JWT out of `nats.conf` and lets it be replaced on its own.
+    File,
+}
+
+/// Which credential backend issues the operator, account and user JWTs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Shell out to the `nsc` binary.
+    #[default]
+    Nsc,
+    /// Generate keys and JWTs in-process.
+    Native,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ServerConfig {
     pub name: String,
     pub port: u16,
+    /// Listen address; IPv6 literals may be given bare (`::`) or bracketed (`[::1]`).
+    #[serde(default)]
+    pub host: Option<String>,
+    /// The `host:port` clients are told to reconnect to, when it differs from the listen address,
+    /// e.g. behind NAT.
+    #[serde(default)]
+    pub client_advertise: Option<String>,
     #[serde(default)]
     pub jetstream: JetStreamConfig,
     #[serde(default)]
     pub leafnodes: LeafNodeConfig,
     #[serde(default)]
     pub accounts: Vec<AccountConfig>,
+    /// Accounts defined once and stamped out per tenant, added to `accounts` by
+    /// [`NatsConfig::expand_account_templates`].
+    #[serde(default)]
+    pub account_templates: Vec<AccountTemplate>,
     pub output_dir: PathBuf,
     #[serde(default)]
     pub tls: Option<TlsConfig>,
+    /// Subject mappings for the server's global account, by source subject.
+    #[serde(default)]
+    pub mappings: HashMap<String, MappingTarget>,
+    /// Server-wide cap on client connections, across all accounts.
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    /// Server-wide cap on subscriptions per connection.
+    #[serde(default)]
+    pub max_subscriptions: Option<u32>,
+    /// Largest message payload, in bytes, the server accepts.
+    #[serde(default)]
+    pub max_payload: Option<u32>,
+    /// Longest protocol line, in bytes, the server accepts from a client.
+    #[serde(default)]
+    pub max_control_line: Option<u32>,
+    /// How long a write to a slow client may block before it is disconnected, e.g. `10s`.
+    #[serde(default)]
+    pub write_deadline: Option<String>,
+    /// How often the server pings clients, e.g. `2m`.
+    #[serde(default)]
+    pub ping_interval: Option<String>,
+    /// Unanswered pings after which a client is disconnected.
+    #[serde(default)]
+    pub ping_max: Option<u32>,
+    #[serde(default)]
+    pub cluster: Option<ClusterConfig>,
+    #[serde(default)]
+    pub gateway: Option<GatewayConfig>,
     #[serde(default)]
-    pub mappings: HashMap<String, String>,
+    pub monitoring: Option<MonitoringConfig>,
+    /// File the server writes its process ID to.
+    #[serde(default)]
+    pub pid_file: Option<String>,
+    /// Directory the server writes a file listing the ports it listens on to.
+    #[serde(default)]
+    pub ports_file_dir: Option<String>,
+    /// Tags for JetStream placement, e.g. `["region:eu", "az:1"]`.
+    #[serde(default)]
+    pub server_tags: Vec<String>,
+    #[serde(default)]
+    pub logging: Option<LoggingConfig>,
+    /// How long a server in lame duck mode takes to close its clients, e.g. `2m`.
+    #[serde(default)]
+    pub lame_duck_duration: Option<String>,
+    /// How long a server in lame duck mode waits before it starts closing clients.
+    #[serde(default)]
+    pub lame_duck_grace_period: Option<String>,
+    /// An MQTT listener. MQTT keeps its sessions in JetStream, so the server needs it enabled.
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    #[serde(default)]
+    pub resolver: ResolverType,
+    /// Preloads and copies every issued account JWT, not only those of the server's own accounts
+    /// and of the accounts its leafnode remotes and imports refer to.
+    #[serde(default)]
+    pub include_all_accounts: bool,
+    /// Pushes every account JWT to the server's URL resolver at the end of `initialize()`. Needs
+    /// the nsc backend and a running account server.
+    #[serde(default)]
+    pub push_accounts: bool,
+    /// Streams [`NatsForge::provision`](crate::NatsForge::provision) creates on the running server.
+    #[serde(default)]
+    pub streams: Vec<StreamSpec>,
+    /// Durable consumers on the server's `streams`, provisioned after them.
+    #[serde(default)]
+    pub consumers: Vec<ConsumerSpec>,
+    #[serde(default)]
+    pub kv_buckets: Vec<KvSpec>,
+    /// Blocks for server options natsforge doesn't cover, by name, e.g.
+    /// `{ "websocket": { "port": 8080, "no_tls": true } }`. They are rendered after everything
+    /// natsforge generates, with the keys of objects in alphabetical order, and can't set an
+    /// option it generates itself.
+    #[serde(default)]
+    pub extra_blocks: HashMap<String, serde_json::Value>,
+    /// Text appended to the end of `nats.conf` as is, after `extra_blocks`. Like those, it can't
+    /// set a top-level option natsforge generates.
+    #[serde(default)]
+    pub extra_config: Option<String>,
+    /// Writes the `resolver_preload` map to `resolver_preload.conf` and includes it, which keeps
+    /// `nats.conf` reviewable with many accounts. Short for listing it in `split_includes`.
+    #[serde(default)]
+    pub split_resolver_preload: bool,
+    /// Top-level blocks written to `<name>.conf` next to `nats.conf` and included from there, e.g.
+    /// `["tls", "resolver_preload"]`. Any generated block or one of `extra_blocks` can be named.
+    /// The configs of the Docker Compose and Kubernetes emitters keep them inline.
+    #[serde(default)]
+    pub split_includes: Vec<String>,
+    /// Whether `nats.conf` inlines the operator JWT or refers to `operator.jwt`. Kubernetes
+    /// configs always inline it.
+    #[serde(default)]
+    pub operator_reference: OperatorRef,
+    /// Which of the config's operators signs the server's accounts and is trusted by it, by name;
+    /// defaults to `operator`.
+    #[serde(default)]
+    pub operator: Option<String>,
+    /// The user clients connecting without credentials are logged in as, by `<account>-<user>`.
+    /// Only for local development: anyone who can reach the server gets that user's permissions.
+    #[serde(default)]
+    pub no_auth_user: Option<String>,
+    /// Permissions of users defined in `nats.conf` that don't set their own. Operator-mode users
+    /// carry theirs in their JWTs.
+    #[serde(default)]
+    pub default_permissions: Option<Permissions>,
+    #[serde(default)]
+    pub auth_mode: AuthMode,
+    /// Hands the authentication of clients to an external service. Needs conf mode: with an
+    /// operator, nats-server only takes the callout from the issuer account's JWT.
+    #[serde(default)]
+    pub auth_callout: Option<AuthCalloutConfig>,
+    /// Account JWT files issued elsewhere, e.g. by a partner team, that the server hosts next to
+    /// the accounts natsforge generates. They are preloaded or put in the resolver directory as
+    /// they are, and each has to be issued by the operator, one of its signing keys, or one of
+    /// `external_account_issuers`.
+    #[serde(default)]
+    pub external_accounts: Vec<PathBuf>,
+    /// Public keys besides the operator's that may have issued `external_accounts`. nats-server
+    /// still only accepts accounts of an operator it trusts.
+    #[serde(default)]
+    pub external_account_issuers: Vec<String>,
+}
+
+impl NatsConfig {
+    /// `operator` followed by the other `operators`, without `operator` when only `operators` is
+    /// set.
+    pub fn all_operators(&self) -> impl Iterator<Item = &OperatorConfig> {
+        let shorthand = (!self.operator.name.is_empty() || self.operators.is_empty()).then_some(&self.operator);
+        shorthand.into_iter().chain(&self.operators)
+    }
+
+    /// The name of the operators' system account: that of `system_account`, or `SYS`, the one
+    /// operators are created with.
+    pub fn system_account_name(&self) -> &str {
+        self.system_account
+            .as_ref()
+            .map_or(DEFAULT_SYSTEM_ACCOUNT, |account| account.name.as_str())
+    }
+
+    /// The operator servers trust unless they pick another one.
+    pub fn default_operator(&self) -> &OperatorConfig {
+        self.all_operators().next().unwrap_or(&self.operator)
+    }
+
+    /// The operator `server` trusts. Falls back to the default operator for an unknown name,
+    /// which validation rejects.
+    pub fn server_operator(&self, server: &ServerConfig) -> &OperatorConfig {
+        server
+            .operator
+            .as_ref()
+            .and_then(|name| self.all_operators().find(|operator| &operator.name == name))
+            .unwrap_or_else(|| self.default_operator())
+    }
+
+    /// The operator of the first server that defines `account`.
+    pub fn account_operator(&self, account: &str) -> Option<&OperatorConfig> {
+        self.servers
+            .iter()
+            .find(|server| server.accounts.iter().any(|a| a.name == account))
+            .map(|server| self.server_operator(server))
+    }
+
+    /// The account an import of `account` by an account on `server` resolves to, with the server
+    /// defining it: the server's own account of that name, or else that of the first server of the
+    /// same operator.
+    pub fn import_exporter<'a>(
+        &'a self,
+        server: &'a ServerConfig,
+        account: &str,
+    ) -> Option<(&'a ServerConfig, &'a AccountConfig)> {
+        let operator = &self.server_operator(server).name;
+        let others = self
+            .servers
+            .iter()
+            .filter(|other| other.name != server.name && &self.server_operator(other).name == operator);
+        std::iter::once(server)
+            .chain(others)
+            .find_map(|candidate| Some((candidate, candidate.accounts.iter().find(|a| a.name == account)?)))
+    }
+
+    /// The error for an import of `account` by `importer` on `server` that resolves to nothing,
+    /// suggesting the closest account name.
+    pub(crate) fn unknown_import(&self, server: &ServerConfig, importer: &str, account: &str) -> NatsForgeError {
+        let names = self.servers.iter().flat_map(|s| &s.accounts).map(|a| a.name.as_str());
+        NatsForgeError::UnknownImportAccount {
+            account: account.to_string(),
+            importer: importer.to_string(),
+            server: server.name.clone(),
+            suggestion: closest_name(account, names).map(str::to_string),
+        }
+    }
+
+    /// Whether `server` accepts `account`'s JWT, which only servers of the same operator do.
+    /// Accounts no server defines, like the system account, are taken to be the server's own.
+    pub fn trusts_account(&self, server: &ServerConfig, account: &str) -> bool {
+        self.account_operator(account)
+            .is_none_or(|operator| operator.name == self.server_operator(server).name)
+    }
+
+    /// Names of the accounts whose JWTs `server` gets, or `None` with `include_all_accounts`: its
+    /// own accounts and those of the servers it shares a cluster or gateways with, the accounts
+    /// these import from, and the accounts its leafnode remotes and authorization refer to.
+    pub fn scoped_accounts<'a>(&'a self, server: &'a ServerConfig) -> Option<HashSet<&'a str>> {
+        if server.include_all_accounts {
+            return None;
+        }
+        let cluster = server.cluster.as_ref().map(|cluster| &cluster.name);
+        let peers = self.servers.iter().filter(|other| {
+            other.name == server.name
+                || (cluster.is_some() && other.cluster.as_ref().map(|cluster| &cluster.name) == cluster)
+                || (server.gateway.is_some() && other.gateway.is_some())
+        });
+        let mut scope = HashSet::new();
+        for account in peers.flat_map(|peer| &peer.accounts) {
+            scope.insert(account.name.as_str());
+            scope.extend(account.imports.iter().map(|import| import.account.as_str()));
+        }
+        scope.extend(server.leafnodes.remotes.iter().map(|remote| remote.account.as_str()));
+        scope.extend(
+            server
+                .leafnodes
+                .authorization
+                .iter()
+                .filter_map(|authorization| authorization.account.as_deref()),
+        );
+        Some(scope)
+    }
+}
+
+impl ServerConfig {
+    /// Where the server keeps its JetStream store, before `initialize()` makes it absolute: the
+    /// `store_dir` below the output directory, or as given with `absolute_paths` off.
+    pub fn jetstream_store_dir(&self) -> PathBuf {
+        let store_dir = Path::new(self.jetstream.store_dir.as_deref().unwrap_or("jetstream"));
+        if self.jetstream.absolute_paths {
+            self.output_dir.join(store_dir)
+        } else {
+            store_dir.to_path_buf()
+        }
+    }
+
+    /// The directory a full or cache resolver keeps its account JWTs in.
+    pub fn resolver_dir(&self) -> Option<PathBuf> {
+        match &self.resolver {
+            ResolverType::Full { dir, .. } | ResolverType::Cache { dir, .. } => Some(self.output_dir.join(dir)),
+            ResolverType::Memory | ResolverType::Url(_) => None,
+        }
+    }
+
+    /// The hosts dev TLS certificates are issued for: the listen host unless it is a wildcard,
+    /// `localhost` and the loopback addresses.
+    pub fn dev_cert_hosts(&self) -> Vec<String> {
+        let mut hosts = Vec::new();
+        if let Some(host) = self.host.as_deref().map(crate::net::strip_brackets) {
+            if !["0.0.0.0", "::"].contains(&host) {
+                hosts.push(host.to_string());
+            }
+        }
+        for host in ["localhost", "127.0.0.1", "::1"] {
+            if !hosts.iter().any(|h| h == host) {
+                hosts.push(host.to_string());
+            }
+        }
+        hosts
+    }
+
+    /// The file the server logs to; relative paths are resolved against `output_dir`.
+    pub fn logfile(&self) -> Option<PathBuf> {
+        let logfile = self.logging.as_ref()?.logfile.as_ref()?;
+        Some(self.output_dir.join(logfile))
+    }
+}
+
+/// Routes between the servers of a cluster.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ClusterConfig {
+    pub name: String,
+    pub port: u16,
+    /// Route URLs of the other cluster members, e.g. `nats-route://10.0.0.2:6222`. The
+    /// `nats-route://` scheme is added when missing.
+    #[serde(default)]
+    pub routes: Vec<String>,
+    /// The `host:port` other members should route to, when it differs from what they see.
+    #[serde(default)]
+    pub advertise: Option<String>,
+    /// Credentials routes must present. They are added to routes that don't carry their own.
+    #[serde(default)]
+    pub auth: Option<ClusterAuth>,
+}
+
+/// Gateway connections to other clusters, for super-cluster topologies.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GatewayConfig {
+    /// This cluster's gateway name; must match the cluster name when the server has a cluster.
+    pub name: String,
+    pub port: u16,
+    #[serde(default)]
+    pub gateways: Vec<RemoteGateway>,
+    /// Rejects gateway connections from clusters not listed in `gateways`.
+    #[serde(default)]
+    pub reject_unknown_cluster: bool,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteGateway {
+    pub name: String,
+    pub urls: Vec<String>,
+}
+
+/// The HTTP(S) monitoring endpoints, such as `/varz`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MonitoringConfig {
+    #[serde(default)]
+    pub http_port: Option<u16>,
+    #[serde(default)]
+    pub https_port: Option<u16>,
+    /// Certificate for `https_port`. nats-server serves HTTPS with the server's `tls` block, so this
+    /// is rendered as that block and also applies to client connections.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// Where and how verbosely the server logs. Without a `logfile` it logs to stderr.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub logfile: Option<PathBuf>,
+    /// Size in bytes at which the logfile is rotated.
+    #[serde(default)]
+    pub size_limit: Option<i64>,
+    #[serde(default)]
+    pub debug: bool,
+    #[serde(default)]
+    pub trace: bool,
+    #[serde(default = "default_true")]
+    pub logtime: bool,
+    /// nats-server always prefixes log lines with its process ID and has no setting to turn that
+    /// off, so `false` only produces a warning.
+    #[serde(default = "default_true")]
+    pub pid_in_log: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            logfile: None,
+            size_limit: None,
+            debug: false,
+            trace: false,
+            logtime: true,
+            pid_in_log: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MqttConfig {
+    pub port: u16,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// How long the server waits for a QoS 1 acknowledgement before redelivering, e.g. `30s`.
+    #[serde(default)]
+    pub ack_wait: Option<String>,
+    /// QoS 1 messages a subscription may have unacknowledged at once.
+    #[serde(default)]
+    pub max_ack_pending: Option<u32>,
+    /// JetStream domain MQTT state is kept in.
+    #[serde(default)]
+    pub jetstream_domain: Option<String>,
+}
+
+/// A cluster of `size` identical nodes. Node `i` (from 1) is named `<name>-<i>`, listens on
+/// `base_client_port + i - 1` and `base_cluster_port + i - 1`, routes to every other node and
+/// writes to `<output_dir>/<name>-<i>`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ClusterTemplate {
+    pub name: String,
+    pub size: u16,
+    pub base_client_port: u16,
+    pub base_cluster_port: u16,
+    pub output_dir: PathBuf,
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Accounts served by the cluster. They are defined on the first node; every node loads them.
+    #[serde(default)]
+    pub accounts: Vec<AccountConfig>,
+    /// JetStream settings for every node. Each node keeps its own `jetstream` store below its
+    /// output directory unless `store_dir` is set.
+    #[serde(default)]
+    pub jetstream: JetStreamConfig,
+    #[serde(default)]
+    pub auth: Option<ClusterAuth>,
+}
+
+/// An account repeated for each tenant. In every string of `template`, including subjects,
+/// exports, imports and mapping sources, `{tenant}` is replaced with the tenant and `{name}` with
+/// the tenant's account name, which is the template's `name` with `{tenant}` replaced.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AccountTemplate {
+    pub template: AccountConfig,
+    /// The tenants to instantiate the template for.
+    #[serde(default)]
+    pub instances: Vec<String>,
+    /// Instead of `instances`, tenants `1` to `count`.
+    #[serde(default)]
+    pub count: Option<u32>,
+    /// Another placeholder replaced with the tenant, for templates whose subjects name it
+    /// themselves, e.g. `customer` for `{customer}.orders.>`.
+    #[serde(default)]
+    pub subject_prefix_var: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterAuth {
+    pub user: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TlsConfig {
+    #[serde(default)]
     pub cert_file: String,
+    #[serde(default)]
     pub key_file: String,
     pub ca_file: Option<String>,
+    /// Generates a self-signed CA and server certificate into the server's `output_dir` during
+    /// `initialize()` and uses them instead of the files above. See [`TlsConfig::dev`].
+    #[serde(default)]
+    pub dev: bool,
+    /// Requires peers to present a certificate signed by `ca_file`.
+    #[serde(default)]
+    pub verify: bool,
+    /// Like `verify`, and also maps the certificate's subject to a user.
+    #[serde(default)]
+    pub verify_and_map: bool,
+    /// TLS handshake timeout in seconds.
+    #[serde(default)]
+    pub timeout: Option<f64>,
+    /// Go cipher suite names, e.g. `TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384`. Empty keeps nats-server's defaults.
+    #[serde(default)]
+    pub cipher_suites: Vec<String>,
+    /// Go curve names, e.g. `X25519`. Empty keeps nats-server's defaults.
+    #[serde(default)]
+    pub curve_preferences: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JetStreamConfig {
     pub enabled: bool,
+    /// Defaults to `jetstream`. Relative paths are taken from the server's output directory.
     pub store_dir: Option<String>,
     pub domain: Option<String>,
     #[serde(default)]
@@ -47,6 +755,26 @@ pub struct JetStreamConfig {
     pub subject_transform: Option<SubjectTransform>,
     #[serde(default)]
     pub republish: Vec<RepublishConfig>,
+    /// Renders `store_dir` as an absolute path below the output directory, created during
+    /// `initialize()`. Off, a relative `store_dir` is rendered as given and resolved against
+    /// nats-server's working directory.
+    #[serde(default = "default_true")]
+    pub absolute_paths: bool,
+}
+
+impl Default for JetStreamConfig {
+    fn default() -> Self {
+        JetStreamConfig {
+            enabled: false,
+            store_dir: None,
+            domain: None,
+            max_memory: None,
+            max_storage: None,
+            subject_transform: None,
+            republish: Vec::new(),
+            absolute_paths: true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,67 +789,596 @@ pub struct RepublishConfig {
     pub dest: String,
 }
 
+/// A JetStream stream to create, or bring up to date, once the server runs.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct StreamSpec {
+    pub name: String,
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub retention: StreamRetention,
+    #[serde(default)]
+    pub storage: StreamStorage,
+    /// Unlimited when unset.
+    #[serde(default)]
+    pub max_msgs: Option<i64>,
+    /// Unlimited when unset.
+    #[serde(default)]
+    pub max_bytes: Option<i64>,
+    /// How long messages are kept, as a Go duration such as `24h`. Forever when unset.
+    #[serde(default)]
+    pub max_age: Option<String>,
+    #[serde(default)]
+    pub replicas: Option<usize>,
+    /// The account the stream belongs to. Its first user creates it, and the account needs
+    /// `jetstream` enabled.
+    pub account: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamRetention {
+    #[default]
+    Limits,
+    Interest,
+    WorkQueue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamStorage {
+    #[default]
+    File,
+    Memory,
+}
+
+/// A durable pull consumer on one of the server's `streams`, created as the stream's account.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ConsumerSpec {
+    pub stream: String,
+    pub name: String,
+    /// Only deliver messages on this subject of the stream. All of them when unset.
+    #[serde(default)]
+    pub filter_subject: Option<String>,
+    #[serde(default)]
+    pub ack_policy: ConsumerAckPolicy,
+    /// How often a message is delivered before the consumer gives up on it. Unlimited when unset.
+    #[serde(default)]
+    pub max_deliver: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsumerAckPolicy {
+    #[default]
+    Explicit,
+    All,
+    None,
+}
+
+/// A stream in `dest_domain` mirroring `source_stream` of `source_domain`. Both domains are
+/// those of servers in the config, and the account has to reach the source over a leafnode
+/// connection.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MirrorSpec {
+    pub name: String,
+    pub source_stream: String,
+    pub source_domain: String,
+    pub dest_domain: String,
+    pub account: String,
+}
+
+/// A JetStream key-value bucket.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct KvSpec {
+    pub bucket: String,
+    /// Values kept per key, up to 64. One when unset.
+    #[serde(default)]
+    pub history: Option<i64>,
+    /// How long values are kept, as a Go duration such as `1h`. Forever when unset.
+    #[serde(default)]
+    pub ttl: Option<String>,
+    /// Largest value in bytes. Unlimited when unset.
+    #[serde(default)]
+    pub max_value_size: Option<i32>,
+    #[serde(default)]
+    pub replicas: Option<usize>,
+    /// The account the bucket belongs to, as for a stream.
+    pub account: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct LeafNodeConfig {
     #[serde(default)]
     pub port: Option<u16>,
+    /// The `host:port` remote leaf nodes should connect to, when it differs from what they see.
+    #[serde(default)]
+    pub advertise: Option<String>,
+    /// TLS for the leafnode listener, separate from the client `tls` block.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Username/password logins leaf nodes must use instead of their account credentials.
+    #[serde(default)]
+    pub authorization: Option<LeafAuth>,
+    /// Oldest nats-server version allowed to connect as a leaf node, e.g. `2.10.0`; at least `2.8.0`.
+    #[serde(default)]
+    pub min_version: Option<String>,
     #[serde(default)]
     pub remotes: Vec<RemoteConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeafAuth {
+    pub users: Vec<LeafUser>,
+    /// Account the users are bound to on the hub.
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeafUser {
+    pub user: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RemoteConfig {
     pub url: String,
     pub account: String,
+    /// File name of the creds the leaf connects with, `<account>-<user>.creds` of a user generated
+    /// in this run. May be left empty when `user` is set.
+    #[serde(default)]
     pub credentials: String,
+    /// User of `account` whose creds the leaf connects with, instead of naming the file.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Server whose instance of `account` the creds come from, when several servers define an
+    /// account of that name. Defaults to this server, then to the only server defining it.
+    #[serde(default)]
+    pub server: Option<String>,
+    /// TLS for the connection to the hub. A `nats://` URL is switched to `tls://` when set.
+    #[serde(default)]
+    pub tls: Option<RemoteTls>,
+    /// Subjects the hub's messages are not let through on.
+    #[serde(default)]
+    pub deny_imports: Vec<String>,
+    /// Subjects the leaf's messages are not sent to the hub on.
+    #[serde(default)]
+    pub deny_exports: Vec<String>,
+    /// Where the decrypted creds will be when the server runs, required with
+    /// [`NatsConfig::encryption`]. The config refers to this path, and only the encrypted copy is
+    /// written to the output directory, which has to be decrypted here at deploy time.
+    #[serde(default)]
+    pub decrypted_credentials: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How written credentials are encrypted with [age](https://age-encryption.org). Each file gets an
+/// `.age` suffix, e.g. `APP-app-user.creds.age`, and is ASCII-armored. Exactly one of `recipients`
+/// and `passphrase_env` is set.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EncryptionConfig {
+    /// X25519 recipients (`age1...`); any of their identities decrypts the files.
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    /// Environment variable holding a passphrase to encrypt with instead. The variable is read
+    /// when `initialize()` runs, so the passphrase stays out of the config.
+    #[serde(default)]
+    pub passphrase_env: Option<String>,
+}
+
+impl RemoteConfig {
+    /// The creds file name, relative to the server's output directory.
+    pub fn credentials_file(&self) -> String {
+        match &self.user {
+            Some(user) if self.credentials.is_empty() => format!("{}-{}.creds", self.account, user),
+            _ => self.credentials.clone(),
+        }
+    }
+
+    /// Where the server's config expects the creds: `decrypted_credentials` when set, the file in
+    /// `output_dir` otherwise.
+    pub fn credentials_path(&self, output_dir: &Path) -> PathBuf {
+        self.decrypted_credentials
+            .clone()
+            .unwrap_or_else(|| output_dir.join(self.credentials_file()))
+    }
+}
+
+impl TlsConfig {
+    /// Client TLS with certificates generated by [`crate::tls::generate_dev_certs`] for the
+    /// server's host, `localhost` and the loopback addresses. For development and tests only.
+    pub fn dev() -> Self {
+        TlsConfig {
+            dev: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// TLS settings a leaf node uses when connecting to its hub.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RemoteTls {
+    /// CA to verify the hub's certificate with.
+    #[serde(default)]
+    pub ca_file: Option<String>,
+    /// Client certificate for hubs that verify leaf nodes; needs `key_file`.
+    #[serde(default)]
+    pub cert_file: Option<String>,
+    #[serde(default)]
+    pub key_file: Option<String>,
+    /// Skips verifying the hub's certificate. Only for testing.
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+/// Where a subject mapping sends messages: a single subject, or one of several picked by weight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MappingTarget {
+    Subject(String),
+    /// Weights are percentages and add up to 100.
+    Weighted(Vec<WeightedDestination>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedDestination {
+    pub dest: String,
+    pub weight: u8,
+}
+
+impl MappingTarget {
+    /// The destination subjects with their weights; a single subject gets all of them.
+    pub fn destinations(&self) -> Vec<(&str, u8)> {
+        match self {
+            MappingTarget::Subject(dest) => vec![(dest, 100)],
+            MappingTarget::Weighted(dests) => dests.iter().map(|d| (d.dest.as_str(), d.weight)).collect(),
+        }
+    }
+}
+
+impl From<&str> for MappingTarget {
+    fn from(dest: &str) -> Self {
+        MappingTarget::Subject(dest.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct OperatorConfig {
     pub name: String,
     #[serde(default)]
     pub reuse_existing: bool,
+    /// The operator JWT's `account_server_url`, where `nsc push` and the nats CLI send account
+    /// JWTs, e.g. `nats://hub:4222`.
+    #[serde(default)]
+    pub account_server_url: Option<String>, // Maps to --account-jwt-server-url
+    /// The operator JWT's `operator_service_urls`, the `nats://` or `tls://` URLs tools connect
+    /// to.
+    #[serde(default)]
+    pub service_urls: Vec<String>, // Maps to --service-url
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AccountConfig {
     pub name: String,
     #[serde(default)]
     pub users: Vec<UserConfig>,
+    /// Numbered users added to `users` by [`NatsConfig::expand_user_templates`].
+    #[serde(default)]
+    pub user_template: Option<UserTemplate>,
     #[serde(default)]
     pub is_system_account: bool,
     #[serde(default)]
     pub unique_name: String,
     #[serde(default)]
     pub max_connections: Option<i32>,
+    /// The total data the account may hold in flight, in bytes; despite the name, not a per-message
+    /// limit.
     #[serde(default)]
-    pub max_payload: Option<i64>,
+    pub max_payload: Option<i64>, // Maps to --data
+    /// Largest message payload, in bytes, the account's clients may publish; -1 is unlimited.
+    #[serde(default)]
+    pub max_message_payload: Option<i64>, // Maps to --payload
+    /// How many leafnode connections the account accepts; -1 is unlimited.
+    #[serde(default)]
+    pub max_leaf_connections: Option<i32>, // Maps to --leaf-conns
+    /// How many exports the account may have; -1 is unlimited.
+    #[serde(default)]
+    pub max_exports: Option<i32>, // Maps to --exports
+    /// How many imports the account may have; -1 is unlimited.
+    #[serde(default)]
+    pub max_imports: Option<i32>, // Maps to --imports
+    /// Whether the account may export wildcard subjects, which JWTs allow by default.
+    #[serde(default)]
+    pub allow_wildcard_exports: Option<bool>, // Maps to --wildcard-exports
     #[serde(default)]
     pub exports: Vec<ExportConfig>,
     #[serde(default)]
     pub imports: Vec<ImportConfig>,
+    /// Subject mappings in the account's JWT, by source subject.
+    #[serde(default)]
+    pub mappings: HashMap<String, MappingTarget>,
+    /// How many signing keys to generate for the account. Users are then issued with the first of
+    /// them, so the account's identity key can be kept offline.
+    #[serde(default)]
+    pub signing_keys: Option<u8>,
+    /// Scoped signing keys, one per role. Users with a `role` are issued with the role's key and get
+    /// its permissions and limits instead of their own.
+    #[serde(default)]
+    pub roles: Vec<RoleConfig>,
+    /// When the account stops working, in the forms a user's `expiry` takes.
+    #[serde(default)]
+    pub expiry: Option<String>, // Maps to --expiry
+    /// Rejects bearer users, so every connection has to prove it holds the user's seed.
+    #[serde(default)]
+    pub disallow_bearer: bool, // Maps to --disallow-bearer
+    /// Users to revoke, by name or public key. Their credentials stop working once the servers
+    /// load the account JWT.
+    #[serde(default)]
+    pub revocations: Vec<String>, // Maps to nsc revocations add-user
+    /// Lets the account use JetStream, without storage limits of its own.
     #[serde(default)]
-    pub mappings: HashMap<String, String>,
+    pub jetstream: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AccountConfig {
+    /// The private export of this account that `import` draws on, if it needs an activation token.
+    pub(crate) fn private_export(&self, import: &ImportConfig) -> Option<&ExportConfig> {
+        self.exports
+            .iter()
+            .find(|e| e.private && e.is_service == import.service && covers(&e.subject, &import.subject))
+    }
+}
+
+/// A scoped signing key: every user it issues is held to these permissions and limits.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RoleConfig {
+    pub name: String,
+    pub allowed_publishes: Option<Vec<String>>,
+    pub allowed_subscribes: Option<Vec<String>>,
+    pub max_payload: Option<i64>,
+}
+
+/// `count` users alike but for their names, e.g. for load tests. User `i` (from 1) is named
+/// `name_pattern` with `{i}` replaced by `i`, or `{i:03}` by `i` zero-padded to three digits, and
+/// gets the permissions and limits of `base`, whose strings may use the same placeholders.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UserTemplate {
+    pub name_pattern: String,
+    pub count: u32,
+    /// The users' settings; its `name` is ignored and may be left out.
+    #[serde(default, deserialize_with = "unnamed_user")]
+    pub base: UserConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct UserConfig {
     pub name: String,
-    pub allowed_pubsub: Option<Vec<String>>,    // Maps to --allow-pubsub
-    pub allowed_publishes: Option<Vec<String>>, // Maps to --allow-pub
-    pub allowed_subjects: Option<Vec<String>>,  // Maps to --allow-sub
-    pub denied_pubsub: Option<Vec<String>>,     // Maps to --deny-pubsub
-    pub denied_publishes: Option<Vec<String>>,  // Maps to --deny-pub
-    pub denied_subjects: Option<Vec<String>>,   // Maps to --deny-sub
-    pub allow_pub_response: Option<bool>,       // Maps to --allow-pub-response
+    // Empty lists leave the direction unrestricted. The aliases are the names used before publish
+    // and subscribe permissions were split.
+    #[serde(default, alias = "allowed_publishes")]
+    pub allow_pub: Vec<String>, // Maps to --allow-pub
+    #[serde(default, alias = "allowed_subjects")]
+    pub allow_sub: Vec<String>, // Maps to --allow-sub
+    #[serde(default, alias = "allowed_pubsub")]
+    pub allow_pubsub: Vec<String>, // Maps to --allow-pubsub
+    #[serde(default, alias = "denied_publishes")]
+    pub deny_pub: Vec<String>, // Maps to --deny-pub
+    #[serde(default, alias = "denied_subjects")]
+    pub deny_sub: Vec<String>, // Maps to --deny-sub
+    #[serde(default, alias = "denied_pubsub")]
+    pub deny_pubsub: Vec<String>, // Maps to --deny-pubsub
+    /// How many responses the user may publish to a request it received, on the request's reply
+    /// subject. `true` in a config means one.
+    #[serde(default, deserialize_with = "response_limit")]
+    pub allow_pub_response: Option<u32>, // Maps to --allow-pub-response=<n>
+    /// How long after a request the responses may be sent, as a Go duration such as `5s`.
+    #[serde(default)]
+    pub response_ttl: Option<String>, // Maps to --response-ttl
+    /// Networks, in CIDR notation, the user may connect from. Empty allows any.
+    #[serde(default)]
+    pub source_networks: Vec<String>, // Maps to --source-network
+    // Per-user limits; -1 is unlimited.
+    #[serde(default)]
+    pub max_subscriptions: Option<i64>, // Maps to --subs
+    #[serde(default)]
+    pub max_data: Option<i64>, // Maps to --data
+    #[serde(default)]
+    pub max_payload: Option<i64>, // Maps to --payload
+    /// When the credentials stop working: an RFC 3339 timestamp, a date (the start of that day in
+    /// UTC) or a duration from now such as `30d` or `12h`.
     pub expiry: Option<String>,
+    /// When the credentials start working, in the same forms as `expiry`.
+    #[serde(default)]
+    pub start: Option<String>, // Maps to --start
+    /// Times of day the user may connect. Empty allows any time.
+    #[serde(default)]
+    pub times: Vec<TimeRange>, // Maps to --time
+    /// The time zone `times` are in, such as `Europe/Berlin`. The server's local time when unset.
+    #[serde(default)]
+    pub locale: Option<String>, // Maps to --locale
+    /// Lets the user connect with the JWT alone, without the nkey challenge, for clients such as
+    /// browsers that can't sign it. The raw JWT is written next to the `.creds` file.
+    #[serde(default)]
+    pub bearer: bool,
+    /// One of the account's `roles`. The user is issued with the role's scoped signing key, so the
+    /// permissions above are not applied.
+    #[serde(default)]
+    pub role: Option<String>,
+    /// How the user authenticates when its server is in conf mode.
+    #[serde(default)]
+    pub conf_credential: ConfCredential,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An auth callout service, which mints a user JWT for every client that connects. natsforge
+/// generates the issuer key the service signs those with, and writes its seed to
+/// `auth-callout-issuer.nk` next to `nats.conf`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuthCalloutConfig {
+    /// The account of the `auth_users`.
+    pub issuer_account: String,
+    /// Users of `issuer_account` that log in as usual rather than through the callout; the
+    /// service connects as one of them.
+    pub auth_users: Vec<String>,
+    /// Accounts the service may place clients in.
+    #[serde(default)]
+    pub allowed_accounts: Vec<String>,
+    /// Encrypts the requests to the service with a generated curve key, whose seed is written to
+    /// `auth-callout.xk`.
+    #[serde(default)]
+    pub xkey: bool,
+}
+
+/// Subjects a user may and may not publish and subscribe to, as nats-server's `permissions`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Permissions {
+    #[serde(default)]
+    pub publish: SubjectPermissions,
+    #[serde(default)]
+    pub subscribe: SubjectPermissions,
+}
+
+/// One direction of [`Permissions`]. Empty `allow` leaves the direction unrestricted.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct SubjectPermissions {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// A daily window, from `start` to `end`, both `HH:MM:SS`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: String,
+    pub end: String,
+}
+
+/// Reads a user template's `base`, which needs no `name` of its own.
+fn unnamed_user<'de, D>(deserializer: D) -> Result<UserConfig, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut base = serde_json::Value::deserialize(deserializer)?;
+    if let Some(object) = base.as_object_mut() {
+        object.entry("name").or_insert_with(|| "".into());
+    }
+    serde_json::from_value(base).map_err(serde::de::Error::custom)
+}
+
+/// Reads `allow_pub_response` as either a flag, which configs used before it took a count, or a
+/// number of responses.
+fn response_limit<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ResponseLimit {
+        Flag(bool),
+        Max(u32),
+    }
+    Ok(match Option::<ResponseLimit>::deserialize(deserializer)? {
+        Some(ResponseLimit::Flag(true)) => Some(1),
+        Some(ResponseLimit::Flag(false)) | None => None,
+        Some(ResponseLimit::Max(max)) => Some(max),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ExportConfig {
     pub subject: String,
     #[serde(default)]
     pub is_service: bool,
+    /// For service exports: derive the response and publish permissions that serving and calling
+    /// the service needs (see [`NatsConfig::derive_grants`]).
+    #[serde(default)]
+    pub grant_responses: bool,
+    /// For service exports: how many responses a request gets. Singleton when unset.
+    #[serde(default)]
+    pub response_type: Option<ResponseType>, // Maps to --response-type
+    #[serde(default)]
+    pub description: Option<String>, // Maps to --description
+    /// The 1-based position of the `*` token in `subject` that must hold the importing account's
+    /// public key, for subjects such as `service.*.requests` that are private to each importer.
+    #[serde(default)]
+    pub account_token_position: Option<u32>, // Maps to --account-token-position
+    /// Importers need an activation token; `initialize()` generates one for every configured
+    /// import of the export.
+    #[serde(default)]
+    pub private: bool, // Maps to --private
+    /// For service exports: where latency measurements are published.
+    #[serde(default)]
+    pub latency_subject: Option<String>, // Maps to --latency
+    /// How many requests are measured; 100 when only `latency_subject` is set.
+    #[serde(default)]
+    pub latency_sampling: Option<LatencySampling>, // Maps to --sampling
+}
+
+/// The share of service requests whose latency is measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencySampling {
+    /// A percentage of requests, 1 to 100.
+    Percent(u8),
+    /// Requests whose callers set tracing headers.
+    Headers,
+}
+
+impl LatencySampling {
+    /// The value nsc's `--sampling` takes.
+    pub fn as_arg(&self) -> String {
+        match self {
+            LatencySampling::Percent(percent) => percent.to_string(),
+            LatencySampling::Headers => "headers".to_string(),
+        }
+    }
+}
+
+impl Serialize for LatencySampling {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LatencySampling::Percent(percent) => serializer.serialize_u8(*percent),
+            LatencySampling::Headers => serializer.serialize_str("headers"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LatencySampling {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Sampling {
+            Percent(u8),
+            Mode(String),
+        }
+        match Sampling::deserialize(deserializer)? {
+            Sampling::Percent(percent) => Ok(LatencySampling::Percent(percent)),
+            Sampling::Mode(mode) if mode == "headers" => Ok(LatencySampling::Headers),
+            Sampling::Mode(mode) => Err(serde::de::Error::custom(format!(
+                "latency_sampling must be 1-100 or \"headers\", not {:?}",
+                mode
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResponseType {
+    #[serde(alias = "singleton")]
+    Singleton,
+    #[serde(alias = "stream")]
+    Stream,
+    #[serde(alias = "chunked")]
+    Chunked,
+}
+
+impl ResponseType {
+    /// The name nsc and the account JWT use.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseType::Singleton => "Singleton",
+            ResponseType::Stream => "Stream",
+            ResponseType::Chunked => "Chunked",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,12 +1389,77 @@ pub struct ImportConfig {
     pub local_subject: Option<String>,
     #[serde(default)]
     pub service: bool,
+    /// Shares the importing account's client details with the exporter's latency measurements.
+    #[serde(default)]
+    pub share: bool, // Maps to --share
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How a server looks up account JWTs.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum ResolverType {
+    /// Every account JWT is preloaded into `nats.conf`; changes need a restart.
+    #[default]
     Memory,
+    /// Account JWTs are looked up from an account server such as nats-account-server, e.g.
+    /// `http://localhost:9090/jwt/v1/accounts/`. Only the system account is preloaded.
     Url(String),
+    /// Account JWTs are kept as `<account-id>.jwt` files in `dir`, relative to the server's
+    /// `output_dir`, and only the system account is preloaded. The server picks up changed files on
+    /// reload, and accepts pushed updates.
+    Full {
+        #[serde(default = "default_resolver_dir")]
+        dir: PathBuf,
+        /// Lets the operator delete accounts from the resolver.
+        #[serde(default)]
+        allow_delete: bool,
+        /// How often servers sync their JWTs with each other, e.g. `2m`.
+        #[serde(default)]
+        interval: Option<String>,
+    },
+    /// Like `Full`, but the directory only caches JWTs fetched from other servers, each for `ttl`
+    /// (e.g. `1h`). Suits edge servers.
+    Cache {
+        #[serde(default = "default_resolver_dir")]
+        dir: PathBuf,
+        #[serde(default)]
+        ttl: Option<String>,
+    },
+}
+
+fn default_resolver_dir() -> PathBuf {
+    PathBuf::from("jwt")
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The candidate closest to `name` by edit distance, if it is within a third of `name`'s length,
+/// so that only likely typos are suggested.
+fn closest_name<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between `a` and `b`, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -148,11 +1470,383 @@ pub struct ServerOptions {
     pub resolver: ResolverType,
 }
 
+/// Where a client can reach one of the generated servers.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub server_name: String,
+    pub url: String,
+}
+
+impl ConnectionInfo {
+    pub fn for_server(server: &ServerConfig) -> Self {
+        let host = crate::net::client_host(server.host.as_deref());
+        ConnectionInfo {
+            server_name: server.name.clone(),
+            url: format!("nats://{}", crate::net::host_port(&host, server.port)),
+        }
+    }
+}
+
+/// What was generated for one account.
+#[derive(Debug, Clone)]
+pub struct AccountArtifacts {
+    /// The account's public key.
+    pub id: String,
+    /// The account JWT in the output directory of the server that defines the account.
+    pub jwt_path: PathBuf,
+    /// The account's public signing keys, listed in its JWT.
+    pub signing_keys: Vec<String>,
+    /// `.creds` files by user name.
+    pub creds: HashMap<String, PathBuf>,
+    /// The raw JWT files of bearer users, by user name.
+    pub bearer_jwts: HashMap<String, PathBuf>,
+    /// Activation tokens of the account's imports from private exports, by imported subject.
+    pub activations: HashMap<String, PathBuf>,
+}
+
+/// What [`NatsForge::push_accounts`](crate::NatsForge::push_accounts) pushed, account by account.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushReport {
+    pub accounts: Vec<PushedAccount>,
+}
+
+impl PushReport {
+    /// The accounts the server rejected.
+    pub fn failures(&self) -> impl Iterator<Item = &PushedAccount> {
+        self.accounts.iter().filter(|pushed| pushed.error.is_some())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushedAccount {
+    /// The account's name in the config, or its store name for accounts the config doesn't name.
+    pub account: String,
+    /// The server's error text, when the push was rejected.
+    pub error: Option<String>,
+}
+
+/// What [`NatsForge::reload`](crate::NatsForge::reload) reloaded, server by server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    pub servers: Vec<ReloadedServer>,
+}
+
+impl ReloadReport {
+    /// The servers that couldn't be reached or refused to reload.
+    pub fn failures(&self) -> impl Iterator<Item = &ReloadedServer> {
+        self.servers.iter().filter(|reloaded| reloaded.error.is_some())
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadedServer {
+    pub server: String,
+    /// Why the server didn't reload, when it didn't.
+    pub error: Option<String>,
+}
+
+/// What [`NatsForge::cleanup`](crate::NatsForge::cleanup) removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// The removed files and directories, deepest first.
+    pub removed: Vec<PathBuf>,
+    /// Paths of the result that no longer existed.
+    pub missing: Vec<PathBuf>,
+}
+
+/// The outcome of [`NatsForge::rotate_user`](crate::NatsForge::rotate_user).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotatedUser {
+    /// The replaced `.creds` file, now holding the new key.
+    pub creds_path: PathBuf,
+    /// The user's previous public key, when it was revoked.
+    pub revoked_key: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct SetupResult {
+    /// `operator.jwt` of the first server; see `operator_jwt_paths`.
     pub operator_jwt_path: PathBuf,
+    /// `operator.jwt` in every server's output directory, in server order.
+    pub operator_jwt_paths: Vec<PathBuf>,
+    #[deprecated(note = "use `account_jwts` or `SetupResult::account`")]
     pub account_jwt_paths: Vec<PathBuf>,
+    #[deprecated(note = "use `SetupResult::creds`")]
     pub user_creds_paths: Vec<PathBuf>,
+    #[deprecated(note = "use `servers` or `SetupResult::server_config`")]
     pub server_config_path: PathBuf,
+    #[deprecated(note = "use `servers` or `SetupResult::server_config`")]
     pub server_config_paths: Option<Vec<PathBuf>>,
+    /// Artifacts by account name. Of an account several servers define, the first defining
+    /// server's; see `server_accounts`.
+    pub account_jwts: HashMap<String, AccountArtifacts>,
+    /// Artifacts by server name, then account name. Unlike `account_jwts`, tells apart accounts of
+    /// the same name on different servers.
+    pub server_accounts: HashMap<String, HashMap<String, AccountArtifacts>>,
+    /// `nats.conf` paths by server name.
+    pub servers: HashMap<String, PathBuf>,
+    /// Full and cache resolver JWT directories by server name.
+    pub resolver_dirs: HashMap<String, PathBuf>,
+    /// JetStream store directories by server name, for servers with JetStream enabled.
+    pub jetstream_store_dirs: HashMap<String, PathBuf>,
+    /// The directory the backend keeps its keys in; see [`NatsConfig::nsc_store_dir`]. With several
+    /// operators, each has a subdirectory named after it.
+    pub store_dir: PathBuf,
+    /// Account public keys by account name, including the system account `SYS`.
+    pub account_ids: HashMap<String, String>,
+    /// The accounts and their imports, with IDs filled in.
+    pub graph: AccountGraph,
+    pub connections: Vec<ConnectionInfo>,
+    pub timings: SetupTimings,
+    /// Problems that didn't stop generation but likely need attention.
+    pub warnings: Vec<String>,
+    /// The artifact contents, in [`ArtifactMode::InMemory`]. The path fields above then name
+    /// where the files would have been written.
+    pub artifacts: Option<SetupArtifacts>,
+    /// Whether the written `.creds` files and bearer JWTs are encrypted, see
+    /// [`NatsConfig::encryption`]. The paths in `account_jwts` then name the `.age` files, which
+    /// have to be decrypted before use. In-memory artifacts are never encrypted.
+    pub creds_encrypted: bool,
+    /// The creds of [`NatsConfig::system_user`] in every server's output directory, by server
+    /// name. In memory the contents are under the system account in [`SetupArtifacts::creds`].
+    pub sys_creds_paths: HashMap<String, PathBuf>,
+    /// The name of the system account, see [`NatsConfig::system_account_name`].
+    pub system_account: String,
+    /// In conf mode, which issues no `.creds`: the nkey seed, password or token file of every user
+    /// by account name, then user name.
+    pub user_secrets: HashMap<String, HashMap<String, PathBuf>>,
+    /// The seeds generated for [`ServerConfig::auth_callout`], by server name.
+    pub auth_callout_keys: HashMap<String, AuthCalloutKeys>,
+}
+
+/// The seed files an auth callout service signs and decrypts with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthCalloutKeys {
+    /// The account seed of the issuer, `auth-callout-issuer.nk`.
+    pub issuer_seed: PathBuf,
+    /// The curve seed, `auth-callout.xk`, when the callout sets `xkey`.
+    pub xkey_seed: Option<PathBuf>,
+}
+
+/// Generated artifacts returned as strings instead of being written.
+#[derive(Clone, Default)]
+pub struct SetupArtifacts {
+    /// The default operator's JWT; see `operator_jwts`.
+    pub operator_jwt: String,
+    /// Operator JWTs by operator name.
+    pub operator_jwts: HashMap<String, String>,
+    /// Account JWTs by account name; the first defining server's where several define one.
+    pub account_jwts: HashMap<String, String>,
+    /// `.creds` file contents by account name, then user name; the first defining server's where
+    /// several define the account.
+    pub creds: HashMap<String, HashMap<String, String>>,
+    /// `.creds` file contents by server name, account name and then user name.
+    pub server_creds: HashMap<String, HashMap<String, HashMap<String, String>>>,
+    /// The JWTs of bearer users by account name, then user name.
+    pub bearer_jwts: HashMap<String, HashMap<String, String>>,
+    /// Activation tokens by importing account name, then imported subject.
+    pub activations: HashMap<String, HashMap<String, String>>,
+    /// Rendered `nats.conf` by server name.
+    pub server_configs: HashMap<String, String>,
+    /// Files a server's `nats.conf` refers to, such as leafnode remote credentials, by server
+    /// name and then the path the config expects them at.
+    pub server_files: HashMap<String, HashMap<PathBuf, String>>,
+    /// In conf mode: the nkey seed, password or token of every user by account name, then user
+    /// name.
+    pub user_secrets: HashMap<String, HashMap<String, String>>,
+}
+
+impl SetupArtifacts {
+    /// The `.creds` file contents of `user` in `account`.
+    pub fn creds(&self, account: &str, user: &str) -> Option<&str> {
+        self.creds.get(account)?.get(user).map(String::as_str)
+    }
+
+    /// The `.creds` file contents of `user` in the `account` defined on `server`.
+    pub fn server_creds(&self, server: &str, account: &str, user: &str) -> Option<&str> {
+        self.server_creds
+            .get(server)?
+            .get(account)?
+            .get(user)
+            .map(String::as_str)
+    }
+
+    /// The JWT of bearer `user` in `account`, which is all a client needs to connect.
+    pub fn bearer_jwt(&self, account: &str, user: &str) -> Option<&str> {
+        self.bearer_jwts.get(account)?.get(user).map(String::as_str)
+    }
+
+    /// The nkey seed, password or token of `user` in `account` on a conf-mode server.
+    pub fn user_secret(&self, account: &str, user: &str) -> Option<&str> {
+        self.user_secrets.get(account)?.get(user).map(String::as_str)
+    }
+
+    /// The activation token `account` imports `subject` with.
+    pub fn activation(&self, account: &str, subject: &str) -> Option<&str> {
+        self.activations.get(account)?.get(subject).map(String::as_str)
+    }
+}
+
+// Keeps seeds out of logs; `SetupResult` is commonly printed with `{:?}`.
+impl fmt::Debug for SetupArtifacts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let creds: HashMap<&String, Vec<&String>> = self
+            .creds
+            .iter()
+            .map(|(account, users)| (account, users.keys().collect()))
+            .collect();
+        let server_creds: HashMap<&String, HashMap<&String, Vec<&String>>> = self
+            .server_creds
+            .iter()
+            .map(|(server, accounts)| {
+                let accounts = accounts
+                    .iter()
+                    .map(|(account, users)| (account, users.keys().collect()))
+                    .collect();
+                (server, accounts)
+            })
+            .collect();
+        // Bearer JWTs are credentials on their own.
+        let bearer_jwts: HashMap<&String, Vec<&String>> = self
+            .bearer_jwts
+            .iter()
+            .map(|(account, users)| (account, users.keys().collect()))
+            .collect();
+        let server_files: HashMap<&String, Vec<&PathBuf>> = self
+            .server_files
+            .iter()
+            .map(|(server, files)| (server, files.keys().collect()))
+            .collect();
+        f.debug_struct("SetupArtifacts")
+            .field("operator_jwt", &self.operator_jwt)
+            .field("operator_jwts", &self.operator_jwts)
+            .field("account_jwts", &self.account_jwts)
+            .field("creds", &creds)
+            .field("server_creds", &server_creds)
+            .field("bearer_jwts", &bearer_jwts)
+            .field("activations", &self.activations)
+            .field("server_configs", &self.server_configs.keys().collect::<Vec<_>>())
+            .field("server_files", &server_files)
+            .finish()
+    }
+}
+
+impl SetupResult {
+    pub fn account(&self, account: &str) -> Option<&AccountArtifacts> {
+        self.account_jwts.get(account)
+    }
+
+    /// The artifacts of the `account` defined on `server`.
+    pub fn server_account(&self, server: &str, account: &str) -> Option<&AccountArtifacts> {
+        self.server_accounts.get(server)?.get(account)
+    }
+
+    /// The public key of an account.
+    pub fn account_id(&self, account: &str) -> Option<&str> {
+        self.account_ids.get(account).map(String::as_str)
+    }
+
+    pub fn account_graph(&self) -> &AccountGraph {
+        &self.graph
+    }
+
+    /// The `.creds` file of `user` in `account`.
+    pub fn creds(&self, account: &str, user: &str) -> Option<&Path> {
+        self.account(account)?.creds.get(user).map(PathBuf::as_path)
+    }
+
+    /// The nkey seed, password or token file of `user` in `account` on a conf-mode server.
+    pub fn user_secret(&self, account: &str, user: &str) -> Option<&Path> {
+        self.user_secrets.get(account)?.get(user).map(PathBuf::as_path)
+    }
+
+    /// The creds of [`NatsConfig::system_user`] in the output directory of `server`.
+    pub fn sys_creds(&self, server: &str) -> Option<&Path> {
+        self.sys_creds_paths.get(server).map(PathBuf::as_path)
+    }
+
+    /// The contents of the `.creds` file of `user` in `account`, read from disk unless the setup
+    /// kept artifacts in memory.
+    pub(crate) fn creds_contents(&self, account: &str, user: &str) -> Result<String> {
+        let missing = || NatsForgeError::InvalidConfig(format!("No creds for user {} in account {}", user, account));
+        match &self.artifacts {
+            Some(artifacts) => artifacts.creds(account, user).map(str::to_string).ok_or_else(missing),
+            None => {
+                let path = self.creds(account, user).ok_or_else(missing)?;
+                if self.creds_encrypted {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "The creds of user {} in account {} are encrypted; decrypt {} first",
+                        user,
+                        account,
+                        path.display()
+                    )));
+                }
+                std::fs::read_to_string(path).map_err(NatsForgeError::io(path))
+            }
+        }
+    }
+
+    /// Like [`Self::creds_contents`], for the `account` defined on `server`.
+    pub(crate) fn server_creds_contents(&self, server: &str, account: &str, user: &str) -> Result<String> {
+        let missing = || {
+            NatsForgeError::InvalidConfig(format!(
+                "No creds for user {} in account {} of server {}",
+                user, account, server
+            ))
+        };
+        match &self.artifacts {
+            Some(artifacts) => artifacts
+                .server_creds(server, account, user)
+                .map(str::to_string)
+                .ok_or_else(missing),
+            None => {
+                let path = self
+                    .server_account(server, account)
+                    .and_then(|artifacts| artifacts.creds.get(user))
+                    .ok_or_else(missing)?;
+                if self.creds_encrypted {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "The creds of user {} in account {} are encrypted; decrypt {} first",
+                        user,
+                        account,
+                        path.display()
+                    )));
+                }
+                std::fs::read_to_string(path).map_err(NatsForgeError::io(path))
+            }
+        }
+    }
+
+    /// The raw JWT file of bearer `user` in `account`.
+    pub fn bearer_jwt(&self, account: &str, user: &str) -> Option<&Path> {
+        self.account(account)?.bearer_jwts.get(user).map(PathBuf::as_path)
+    }
+
+    /// The activation token file `account` imports `subject` with.
+    pub fn activation(&self, account: &str, subject: &str) -> Option<&Path> {
+        self.account(account)?.activations.get(subject).map(PathBuf::as_path)
+    }
+
+    /// The generated `nats.conf` of a server.
+    pub fn server_config(&self, server: &str) -> Option<&Path> {
+        self.servers.get(server).map(PathBuf::as_path)
+    }
+
+    /// The directory a server's full or cache resolver reads account JWTs from.
+    pub fn resolver_dir(&self, server: &str) -> Option<&Path> {
+        self.resolver_dirs.get(server).map(PathBuf::as_path)
+    }
+
+    /// The paths and IDs of the run, in a form that serializes.
+    pub fn manifest(&self) -> SetupManifest {
+        SetupManifest::new(self)
+    }
 }
@@ -0,0 +1,649 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    backend::creds_jwt,
+    compose::COMPOSE_CONFIG_FILE,
+    config::{NatsConfig, SetupArtifacts},
+    error::{NatsForgeError, Result},
+    jwt::{decode_claims, decode_operator_claims, extract_account_id},
+    ENCRYPTED_SUFFIX,
+};
+
+/// Claims that change every time a JWT is signed, or with the store name of unique naming.
+const VOLATILE_CLAIMS: &[&str] = &["iat", "jti", "name"];
+
+/// How deep [`removed_servers`] looks for the output directories of servers that are gone.
+const MAX_SEARCH_DEPTH: usize = 3;
+
+/// How many unchanged lines surround each change in a [`FileDiff`].
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+impl Change {
+    fn marker(self) -> char {
+        match self {
+            Change::Added => '+',
+            Change::Removed => '-',
+            Change::Modified => '~',
+        }
+    }
+}
+
+/// What [`NatsForge::diff`](crate::NatsForge::diff) found would change. Keys and JWTs are compared
+/// by what they belong to, and JWTs by their claims without `iat`, `jti` and `name`, so issuing
+/// everything again with new keys is not a change in itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiffReport {
+    /// Configured servers without a `nats.conf` on disk.
+    pub added_servers: Vec<String>,
+    /// Servers with a `nats.conf` on disk that the config no longer has.
+    pub removed_servers: Vec<String>,
+    pub accounts: Vec<AccountChange>,
+    pub users: Vec<UserChange>,
+    /// The config files of servers in both that would change.
+    pub files: Vec<FileDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountChange {
+    pub account: String,
+    pub change: Change,
+    /// For modified accounts, the claims that differ, such as `nats.limits`.
+    pub claims: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserChange {
+    pub account: String,
+    pub user: String,
+    pub change: Change,
+    /// For modified users, the claims that differ, such as `nats.pub`. Encrypted creds on disk
+    /// can't be read, so their users only show up as added or removed.
+    pub claims: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    pub server: String,
+    /// The file on disk, which may not exist yet.
+    pub path: PathBuf,
+    /// A unified diff from the file on disk to the generated one.
+    pub diff: String,
+}
+
+impl DiffReport {
+    /// Whether nothing would change.
+    pub fn is_empty(&self) -> bool {
+        self.added_servers.is_empty()
+            && self.removed_servers.is_empty()
+            && self.accounts.is_empty()
+            && self.users.is_empty()
+            && self.files.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("diff report serializes")
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        if !self.added_servers.is_empty() || !self.removed_servers.is_empty() {
+            writeln!(f, "Servers:")?;
+            for server in &self.added_servers {
+                writeln!(f, "  + {}", server)?;
+            }
+            for server in &self.removed_servers {
+                writeln!(f, "  - {}", server)?;
+            }
+        }
+        if !self.accounts.is_empty() {
+            writeln!(f, "Accounts:")?;
+            for account in &self.accounts {
+                write!(f, "  {} {}", account.change.marker(), account.account)?;
+                write_claims(f, &account.claims)?;
+            }
+        }
+        if !self.users.is_empty() {
+            writeln!(f, "Users:")?;
+            for user in &self.users {
+                write!(f, "  {} {}/{}", user.change.marker(), user.account, user.user)?;
+                write_claims(f, &user.claims)?;
+            }
+        }
+        for file in &self.files {
+            write!(f, "{}", file.diff)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_claims(f: &mut fmt::Formatter<'_>, claims: &[String]) -> fmt::Result {
+    if claims.is_empty() {
+        writeln!(f)
+    } else {
+        writeln!(f, " ({})", claims.join(", "))
+    }
+}
+
+/// The accounts, users and config files of a deployment, with keys and JWTs replaced by the
+/// names of what they belong to.
+#[derive(Default)]
+pub(crate) struct Snapshot {
+    accounts: BTreeMap<String, Value>,
+    /// By account and user name; `None` when the creds are encrypted.
+    users: BTreeMap<(String, String), Option<Value>>,
+    /// Config files by server name, then file name.
+    servers: BTreeMap<String, ServerFiles>,
+}
+
+struct ServerFiles {
+    dir: PathBuf,
+    files: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// What an in-memory run generated for `config`.
+    pub(crate) fn generated(config: &NatsConfig, artifacts: &SetupArtifacts) -> Result<Self> {
+        let mut labels = Labels::default();
+        for operator_jwt in artifacts.operator_jwts.values() {
+            labels.operator(operator_jwt)?;
+        }
+        for (name, account_jwt) in &artifacts.account_jwts {
+            labels.0.insert(extract_account_id(account_jwt)?, name.clone());
+        }
+
+        let mut snapshot = Snapshot::default();
+        for (name, account_jwt) in &artifacts.account_jwts {
+            snapshot.accounts.insert(name.clone(), labels.claims(account_jwt)?);
+        }
+        for (account, users) in &artifacts.creds {
+            for (user, creds) in users {
+                let claims = creds_jwt(creds).map(|jwt| labels.claims(jwt)).transpose()?;
+                snapshot.users.insert((account.clone(), user.clone()), claims);
+            }
+        }
+        for server in &config.servers {
+            let mut files = BTreeMap::new();
+            if let Some(server_config) = artifacts.server_configs.get(&server.name) {
+                files.insert("nats.conf".to_string(), labels.conf(server_config));
+            }
+            for (path, contents) in artifacts.server_files.get(&server.name).into_iter().flatten() {
+                if let Some(file_name) = config_file_name(path) {
+                    files.insert(file_name, labels.conf(contents));
+                }
+            }
+            let dir = server.output_dir.clone();
+            snapshot.servers.insert(server.name.clone(), ServerFiles { dir, files });
+        }
+        Ok(snapshot)
+    }
+
+    /// What the configured servers have in their output directories under `against_dir`. Servers
+    /// without a `nats.conf` there are left out.
+    pub(crate) fn read(config: &NatsConfig, against_dir: &Path) -> Result<Self> {
+        let mut labels = Labels::default();
+        let mut account_jwts = BTreeMap::new();
+        let mut creds_files = Vec::new();
+        let mut conf_files = Vec::new();
+        // Paths are written absolute but rendered as configured in memory.
+        let mut prefixes = Vec::new();
+        for server in &config.servers {
+            let dir = against_dir.join(&server.output_dir);
+            if !dir.join("nats.conf").is_file() {
+                continue;
+            }
+            for output_dir in [&dir, &server.output_dir] {
+                if let Ok(absolute) = std::fs::canonicalize(output_dir) {
+                    prefixes.push((
+                        absolute.to_string_lossy().into_owned(),
+                        server.output_dir.to_string_lossy(),
+                    ));
+                }
+            }
+            for entry in std::fs::read_dir(&dir).map_err(NatsForgeError::io(&dir))? {
+                let path = entry.map_err(NatsForgeError::io(&dir))?.path();
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                if file_name == "operator.jwt" {
+                    labels.operator(&read_trimmed(&path)?)?;
+                } else if path.extension() == Some(OsStr::new("jwt")) {
+                    let jwt = read_trimmed(&path)?;
+                    // Bearer user JWTs and activation tokens sit next to the account JWTs.
+                    if claims_of(&jwt, &path)?["nats"]["type"] == "account" {
+                        let name = file_name.trim_end_matches(".jwt").to_string();
+                        account_jwts.entry(name).or_insert((jwt, path));
+                    }
+                } else if let Some(stem) = creds_stem(&file_name) {
+                    creds_files.push((stem.to_string(), path));
+                } else if let Some(file_name) = config_file_name(&path) {
+                    conf_files.push((server, dir.clone(), file_name, path));
+                }
+            }
+        }
+        // Longest first, so an output directory inside another is replaced as a whole.
+        prefixes.sort_by_key(|(absolute, _)| std::cmp::Reverse(absolute.len()));
+        for (name, (jwt, path)) in &account_jwts {
+            labels.0.insert(
+                claims_of(jwt, path)?["sub"].as_str().unwrap_or_default().to_string(),
+                name.clone(),
+            );
+        }
+
+        let mut snapshot = Snapshot::default();
+        for (name, (jwt, _)) in &account_jwts {
+            snapshot.accounts.insert(name.clone(), labels.claims(jwt)?);
+        }
+        let mut account_names: BTreeSet<&str> = account_jwts.keys().map(String::as_str).collect();
+        account_names.extend(
+            config
+                .servers
+                .iter()
+                .flat_map(|server| &server.accounts)
+                .map(|a| a.name.as_str()),
+        );
+        account_names.insert(config.system_account_name());
+        for (stem, path) in creds_files {
+            let Some((account, user)) = split_creds_stem(&stem, &account_names) else {
+                continue;
+            };
+            let claims = if path.to_string_lossy().ends_with(ENCRYPTED_SUFFIX) {
+                None
+            } else {
+                let creds = std::fs::read_to_string(&path).map_err(NatsForgeError::io(&path))?;
+                creds_jwt(&creds).map(|jwt| labels.claims(jwt)).transpose()?
+            };
+            snapshot.users.insert((account, user), claims);
+        }
+        for (server, dir, file_name, path) in conf_files {
+            let mut contents = std::fs::read_to_string(&path).map_err(NatsForgeError::io(&path))?;
+            for (absolute, configured) in &prefixes {
+                contents = contents.replace(absolute.as_str(), configured);
+            }
+            snapshot
+                .servers
+                .entry(server.name.clone())
+                .or_insert_with(|| ServerFiles {
+                    dir,
+                    files: BTreeMap::new(),
+                })
+                .files
+                .insert(file_name, labels.conf(&contents));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// The servers with a `nats.conf` in a directory under `against_dir` that isn't the output
+/// directory of a configured server, by their `server_name`.
+pub(crate) fn removed_servers(config: &NatsConfig, against_dir: &Path) -> Result<Vec<String>> {
+    let configured_dirs: Vec<PathBuf> = config
+        .servers
+        .iter()
+        .filter_map(|server| std::fs::canonicalize(against_dir.join(&server.output_dir)).ok())
+        .collect();
+    let mut removed = Vec::new();
+    let mut pending = vec![(against_dir.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let canonical = std::fs::canonicalize(&dir).map_err(NatsForgeError::io(&dir))?;
+        if configured_dirs.contains(&canonical) {
+            continue;
+        }
+        let server_config = dir.join("nats.conf");
+        if server_config.is_file() {
+            let contents = std::fs::read_to_string(&server_config).map_err(NatsForgeError::io(&server_config))?;
+            let name = contents
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("server_name:"))
+                .map(|name| name.trim().trim_matches('"').to_string())
+                .unwrap_or_else(|| dir.file_name().unwrap_or_default().to_string_lossy().into_owned());
+            if !config.servers.iter().any(|server| server.name == name) {
+                removed.push(name);
+            }
+            continue;
+        }
+        if depth == MAX_SEARCH_DEPTH {
+            continue;
+        }
+        for entry in std::fs::read_dir(&dir).map_err(NatsForgeError::io(&dir))? {
+            let path = entry.map_err(NatsForgeError::io(&dir))?.path();
+            if path.is_dir() {
+                pending.push((path, depth + 1));
+            }
+        }
+    }
+    removed.sort();
+    Ok(removed)
+}
+
+/// Compares the deployment `on_disk` with the one `generated` for the config.
+pub(crate) fn compare(on_disk: &Snapshot, generated: &Snapshot, removed_servers: Vec<String>) -> DiffReport {
+    let mut report = DiffReport {
+        removed_servers,
+        ..Default::default()
+    };
+    for (name, server) in &generated.servers {
+        let Some(existing) = on_disk.servers.get(name) else {
+            report.added_servers.push(name.clone());
+            continue;
+        };
+        let file_names: BTreeSet<&String> = existing.files.keys().chain(server.files.keys()).collect();
+        for file_name in file_names {
+            let old = existing.files.get(file_name).map_or("", String::as_str);
+            let new = server.files.get(file_name).map_or("", String::as_str);
+            if old != new {
+                let path = existing.dir.join(file_name);
+                report.files.push(FileDiff {
+                    server: name.clone(),
+                    diff: unified_diff(&path.to_string_lossy(), old, new),
+                    path,
+                });
+            }
+        }
+    }
+
+    for (account, change, claims) in changes(&on_disk.accounts, &generated.accounts, |old, new| {
+        (old != new).then(|| changed_claims(old, new))
+    }) {
+        report.accounts.push(AccountChange {
+            account: account.clone(),
+            change,
+            claims,
+        });
+    }
+    for ((account, user), change, claims) in changes(&on_disk.users, &generated.users, |old, new| match (old, new) {
+        (Some(old), Some(new)) if old != new => Some(changed_claims(old, new)),
+        _ => None,
+    }) {
+        report.users.push(UserChange {
+            account: account.clone(),
+            user: user.clone(),
+            change,
+            claims,
+        });
+    }
+    report
+}
+
+/// The keys only in `old`, only in `new`, and in both where `modified` reports the differences.
+fn changes<'a, K: Ord, V>(
+    old: &'a BTreeMap<K, V>,
+    new: &'a BTreeMap<K, V>,
+    modified: impl Fn(&V, &V) -> Option<Vec<String>>,
+) -> Vec<(&'a K, Change, Vec<String>)> {
+    let keys: BTreeSet<&K> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| match (old.get(key), new.get(key)) {
+            (None, _) => Some((key, Change::Added, Vec::new())),
+            (_, None) => Some((key, Change::Removed, Vec::new())),
+            (Some(old), Some(new)) => modified(old, new).map(|claims| (key, Change::Modified, claims)),
+        })
+        .collect()
+}
+
+/// The top-level claims, and those under `nats` one by one, that differ.
+fn changed_claims(old: &Value, new: &Value) -> Vec<String> {
+    let keys = |value: &Value| -> BTreeSet<String> {
+        value
+            .as_object()
+            .map(|object| object.keys().cloned().collect())
+            .unwrap_or_default()
+    };
+    let mut changed = Vec::new();
+    for key in keys(old).union(&keys(new)) {
+        if key == "nats" {
+            for nats_key in keys(&old["nats"]).union(&keys(&new["nats"])) {
+                if old["nats"][nats_key] != new["nats"][nats_key] {
+                    changed.push(format!("nats.{}", nats_key));
+                }
+            }
+        } else if old[key] != new[key] {
+            changed.push(key.clone());
+        }
+    }
+    changed
+}
+
+/// Names for the public keys of a deployment, so keys and JWTs issued in different runs compare
+/// equal when they belong to the same operator or account.
+#[derive(Default)]
+struct Labels(HashMap<String, String>);
+
+impl Labels {
+    /// Names the operator's key and the system account created with it.
+    fn operator(&mut self, operator_jwt: &str) -> Result<()> {
+        let claims = decode_operator_claims(operator_jwt)?;
+        self.0.insert(claims.sub, "operator".to_string());
+        if let Some(system_account_id) = claims.nats.system_account {
+            self.0.insert(system_account_id, "SYS".to_string());
+        }
+        Ok(())
+    }
+
+    /// The claims of `jwt` without the volatile ones, with every key and embedded JWT named.
+    fn claims(&self, jwt: &str) -> Result<Value> {
+        let mut claims = self.value(decode_claims(jwt)?);
+        if let Some(object) = claims.as_object_mut() {
+            for claim in VOLATILE_CLAIMS {
+                object.remove(*claim);
+            }
+        }
+        Ok(claims)
+    }
+
+    fn value(&self, value: Value) -> Value {
+        match value {
+            Value::String(text) => Value::String(self.text(&text)),
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.value(item)).collect()),
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (self.text(&key), self.value(value)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    /// A config file with every key and JWT named. Preload entries are keyed by account ID, so
+    /// they are put in the order of the names that replaced the IDs.
+    fn conf(&self, contents: &str) -> String {
+        let mut lines: Vec<String> = contents.lines().map(|line| self.text(line)).collect();
+        let mut start = 0;
+        while start < lines.len() {
+            let end = (start..lines.len())
+                .find(|&line| !is_preload_entry(&lines[line]))
+                .unwrap_or(lines.len());
+            lines[start..end].sort();
+            start = end + 1;
+        }
+        let mut text = lines.join("\n");
+        if contents.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// `text` with public keys and JWTs replaced by `<name>` and `<name JWT>`.
+    fn text(&self, text: &str) -> String {
+        let mut named = String::with_capacity(text.len());
+        let mut token = String::new();
+        for c in text.chars().chain(std::iter::once('\n')) {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                token.push(c);
+                continue;
+            }
+            named.push_str(&self.token(&token));
+            token.clear();
+            named.push(c);
+        }
+        named.pop();
+        named
+    }
+
+    fn token(&self, token: &str) -> String {
+        if is_public_key(token) {
+            return format!("<{}>", self.0.get(token).map_or("key", String::as_str));
+        }
+        if token.starts_with("eyJ") && token.matches('.').count() == 2 {
+            if let Ok(claims) = decode_claims::<Value>(token) {
+                let owner = claims["sub"].as_str().and_then(|id| self.0.get(id));
+                return format!("<{} JWT>", owner.map_or("unknown", String::as_str));
+            }
+        }
+        token.to_string()
+    }
+}
+
+/// Whether `token` has the shape of an nkey public key: 56 base32 characters.
+fn is_public_key(token: &str) -> bool {
+    token.len() == 56
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || (b'2'..=b'7').contains(&b))
+}
+
+/// A named `resolver_preload` entry, `<APP>: "<APP JWT>"`.
+fn is_preload_entry(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with('<') && line.ends_with(" JWT>\"")
+}
+
+/// The file name of a config file natsforge writes next to `nats.conf`, including `nats.conf`
+/// itself.
+fn config_file_name(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    (path.extension() == Some(OsStr::new("conf")) && file_name != COMPOSE_CONFIG_FILE).then(|| file_name.to_string())
+}
+
+/// `ACCOUNT-user` of `ACCOUNT-user.creds`, encrypted or not.
+fn creds_stem(file_name: &str) -> Option<&str> {
+    let file_name = file_name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(file_name);
+    file_name.strip_suffix(".creds")
+}
+
+/// The account and user of a creds file, taking the longest account name that fits since both
+/// may contain dashes.
+fn split_creds_stem(stem: &str, account_names: &BTreeSet<&str>) -> Option<(String, String)> {
+    account_names
+        .iter()
+        .filter_map(|account| {
+            let user = stem.strip_prefix(account)?.strip_prefix('-')?;
+            Some((account.to_string(), user.to_string()))
+        })
+        .max_by_key(|(account, _)| account.len())
+}
+
+fn read_trimmed(path: &Path) -> Result<String> {
+    Ok(std::fs::read_to_string(path)
+        .map_err(NatsForgeError::io(path))?
+        .trim()
+        .to_string())
+}
+
+fn claims_of(jwt: &str, path: &Path) -> Result<Value> {
+    decode_claims(jwt).map_err(|err| NatsForgeError::Jwt(format!("{}: {}", path.display(), err)))
+}
+
+/// A unified diff from `old` to `new`, both labelled `path`, or nothing when they are equal.
+fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&old, &new);
+    if ops.iter().all(|op| matches!(op, Op::Same(..))) {
+        return String::new();
+    }
+
+    let mut text = format!("--- {}\t(on disk)\n+++ {}\t(generated)\n", path, path);
+    let changed: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], Op::Same(..))).collect();
+    let mut hunk_start = 0;
+    while hunk_start < changed.len() {
+        // Changes closer together than twice the context share a hunk.
+        let mut hunk_end = hunk_start;
+        while hunk_end + 1 < changed.len() && changed[hunk_end + 1] - changed[hunk_end] <= 2 * CONTEXT_LINES {
+            hunk_end += 1;
+        }
+        let first = changed[hunk_start].saturating_sub(CONTEXT_LINES);
+        let last = (changed[hunk_end] + CONTEXT_LINES).min(ops.len() - 1);
+        let hunk = &ops[first..=last];
+        let (old_start, new_start) = ops[..first].iter().fold((0, 0), |(o, n), op| match op {
+            Op::Same(..) => (o + 1, n + 1),
+            Op::Removed(_) => (o + 1, n),
+            Op::Added(_) => (o, n + 1),
+        });
+        let old_len = hunk.iter().filter(|op| !matches!(op, Op::Added(_))).count();
+        let new_len = hunk.iter().filter(|op| !matches!(op, Op::Removed(_))).count();
+        // An empty side starts at the line before it, as in diff -u.
+        let start = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start(old_start, old_len),
+            old_len,
+            start(new_start, new_len),
+            new_len
+        ));
+        for op in hunk {
+            match op {
+                Op::Same(line) => text.push_str(&format!(" {}\n", line)),
+                Op::Removed(line) => text.push_str(&format!("-{}\n", line)),
+                Op::Added(line) => text.push_str(&format!("+{}\n", line)),
+            }
+        }
+        hunk_start = hunk_end + 1;
+    }
+    text
+}
+
+enum Op<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// The edit from `old` to `new` along their longest common subsequence of lines.
+fn line_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Op<'a>> {
+    // lcs[i][j]: the longest common subsequence of old[i..] and new[j..].
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(Op::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(Op::Removed(old[i]));
+            i += 1;
+        } else {
+            ops.push(Op::Added(new[j]));
+            j += 1;
+        }
+    }
+    ops
+}
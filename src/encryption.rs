@@ -0,0 +1,122 @@
+//! [age](https://age-encryption.org) encryption of the credentials `initialize()` writes, set up
+//! with [`NatsConfig::encryption`](crate::config::NatsConfig::encryption).
+//!
+//! Files are ASCII-armored, so the `age` command line tool decrypts them as well as
+//! [`decrypt_with_identity`] and [`decrypt_with_passphrase`] do.
+
+use std::{
+    io::{Read, Write},
+    iter,
+    str::FromStr,
+};
+
+use age::{
+    armor::{ArmoredReader, ArmoredWriter, Format},
+    scrypt,
+    secrecy::{ExposeSecret, SecretString},
+    x25519, Decryptor, Encryptor, Identity, Recipient,
+};
+
+use crate::{
+    config::EncryptionConfig,
+    error::{NatsForgeError, Result},
+};
+
+/// Appended to the name of every encrypted file.
+pub const ENCRYPTED_SUFFIX: &str = ".age";
+
+/// The recipients or passphrase of an [`EncryptionConfig`], ready to encrypt with.
+pub(crate) struct Encrypter {
+    recipients: Vec<x25519::Recipient>,
+    passphrase: Option<SecretString>,
+}
+
+impl Encrypter {
+    pub(crate) fn new(config: &EncryptionConfig) -> Result<Self> {
+        match (&config.passphrase_env, config.recipients.is_empty()) {
+            (None, false) => {
+                let recipients = config
+                    .recipients
+                    .iter()
+                    .map(|recipient| {
+                        x25519::Recipient::from_str(recipient).map_err(|err| {
+                            NatsForgeError::InvalidConfig(format!("Invalid age recipient {}: {}", recipient, err))
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+                Ok(Encrypter {
+                    recipients,
+                    passphrase: None,
+                })
+            }
+            (Some(var), true) => {
+                let passphrase = std::env::var(var).map_err(|_| {
+                    NatsForgeError::InvalidConfig(format!("Encryption passphrase variable {} is not set", var))
+                })?;
+                if passphrase.is_empty() {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Encryption passphrase variable {} is empty",
+                        var
+                    )));
+                }
+                Ok(Encrypter {
+                    recipients: Vec::new(),
+                    passphrase: Some(SecretString::from(passphrase)),
+                })
+            }
+            _ => Err(NatsForgeError::InvalidConfig(
+                "encryption needs either recipients or passphrase_env".to_string(),
+            )),
+        }
+    }
+
+    /// `plaintext` as an armored age file.
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+        let failed = |err: &dyn std::fmt::Display| NatsForgeError::Backend(format!("age encryption failed: {}", err));
+        let encryptor = match &self.passphrase {
+            Some(passphrase) => Encryptor::with_user_passphrase(passphrase.clone()),
+            None => Encryptor::with_recipients(self.recipients.iter().map(|recipient| recipient as &dyn Recipient))
+                .map_err(|err| failed(&err))?,
+        };
+        let mut armored = Vec::new();
+        let output = ArmoredWriter::wrap_output(&mut armored, Format::AsciiArmor).map_err(|err| failed(&err))?;
+        let mut writer = encryptor.wrap_output(output).map_err(|err| failed(&err))?;
+        writer.write_all(plaintext).map_err(|err| failed(&err))?;
+        writer
+            .finish()
+            .and_then(|output| output.finish())
+            .map_err(|err| failed(&err))?;
+        Ok(String::from_utf8(armored).expect("armored age output is ASCII"))
+    }
+}
+
+/// A new X25519 identity (`AGE-SECRET-KEY-1...`) and the recipient (`age1...`) that encrypts to it.
+pub fn generate_identity() -> (String, String) {
+    let identity = x25519::Identity::generate();
+    let recipient = identity.to_public().to_string();
+    (identity.to_string().expose_secret().to_string(), recipient)
+}
+
+/// Decrypts an encrypted file's contents with an X25519 identity (`AGE-SECRET-KEY-1...`).
+pub fn decrypt_with_identity(ciphertext: &[u8], identity: &str) -> Result<String> {
+    let identity = x25519::Identity::from_str(identity)
+        .map_err(|err| NatsForgeError::InvalidConfig(format!("Invalid age identity: {}", err)))?;
+    decrypt(ciphertext, &identity)
+}
+
+/// Decrypts an encrypted file's contents with the passphrase it was encrypted with.
+pub fn decrypt_with_passphrase(ciphertext: &[u8], passphrase: &str) -> Result<String> {
+    decrypt(
+        ciphertext,
+        &scrypt::Identity::new(SecretString::from(passphrase.to_string())),
+    )
+}
+
+fn decrypt(ciphertext: &[u8], identity: &dyn Identity) -> Result<String> {
+    let failed = |err: &dyn std::fmt::Display| NatsForgeError::Backend(format!("age decryption failed: {}", err));
+    let decryptor = Decryptor::new(ArmoredReader::new(ciphertext)).map_err(|err| failed(&err))?;
+    let mut reader = decryptor.decrypt(iter::once(identity)).map_err(|err| failed(&err))?;
+    let mut plaintext = String::new();
+    reader.read_to_string(&mut plaintext).map_err(|err| failed(&err))?;
+    Ok(plaintext)
+}
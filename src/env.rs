@@ -0,0 +1,91 @@
+use std::ffi::OsString;
+
+use tokio::process::Command;
+
+use crate::{
+    config::NatsConfig,
+    error::{NatsForgeError, Result},
+};
+
+/// Variables every child process gets from the forge's environment, on top of `inherit_env`;
+/// without them nsc can't locate its home directory and shims can't find their interpreter.
+pub const BASE_INHERITED_ENV: &[&str] = &["PATH", "HOME", "USERPROFILE", "SYSTEMROOT", "TMPDIR", "TEMP", "TMP"];
+
+/// The exact environment child processes (nsc, nats-server) are started with.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessEnv {
+    vars: Vec<(String, OsString)>,
+}
+
+impl ProcessEnv {
+    /// Resolves `inherit_env` against the current environment and layers `process_env` on top.
+    pub fn from_config(config: &NatsConfig) -> Self {
+        let mut env = ProcessEnv::default();
+        let inherited = BASE_INHERITED_ENV
+            .iter()
+            .copied()
+            .chain(config.inherit_env.iter().map(String::as_str));
+        for name in inherited {
+            if let Some(value) = std::env::var_os(name) {
+                env.set(name, value);
+            }
+        }
+        let mut explicit: Vec<_> = config.process_env.iter().collect();
+        explicit.sort();
+        for (name, value) in explicit {
+            env.set(name, value.into());
+        }
+        env
+    }
+
+    pub fn set(&mut self, name: &str, value: OsString) {
+        self.vars.retain(|(n, _)| n != name);
+        self.vars.push((name.to_string(), value));
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &OsString)> {
+        self.vars.iter().map(|(n, v)| (n.as_str(), v))
+    }
+
+    /// Replaces the command's environment with this one.
+    pub fn apply(&self, command: &mut Command) {
+        command.env_clear().envs(self.vars.iter().map(|(n, v)| (n, v)));
+    }
+}
+
+/// Rejects `process_env`/`inherit_env` entries that would hand credentials to child processes.
+pub(crate) fn validate(config: &NatsConfig) -> Result<()> {
+    for name in &config.inherit_env {
+        if is_secret_name(name) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "inherit_env must not pass secrets to child processes: {}",
+                name
+            )));
+        }
+    }
+    for (name, value) in &config.process_env {
+        if is_secret_name(name) || is_secret_value(value) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "process_env must not set seeds or tokens: {}",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    ["SEED", "TOKEN", "CREDS", "PASSWORD"].iter().any(|s| name.contains(s))
+}
+
+pub(crate) fn is_secret_value(value: &str) -> bool {
+    let value = value.trim();
+    let nkey_seed = value.len() == 58
+        && value.starts_with('S')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || ('2'..='7').contains(&c));
+    let jwt = value.starts_with("eyJ") && value.split('.').count() == 3;
+    nkey_seed || jwt || value.contains("NKEY SEED")
+}
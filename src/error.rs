@@ -0,0 +1,209 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use crate::progress::SetupEvent;
+
+pub type Result<T, E = NatsForgeError> = std::result::Result<T, E>;
+
+/// Everything that can go wrong while loading a configuration or generating a setup.
+#[derive(Debug, Error)]
+pub enum NatsForgeError {
+    #[error("nsc not found at {resolution}: {source}")]
+    NscNotFound {
+        /// The path that was tried and where it came from.
+        resolution: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("nsc {version} at {resolution} is older than the minimum supported version {minimum}")]
+    NscVersionUnsupported {
+        version: String,
+        minimum: String,
+        resolution: String,
+    },
+
+    #[error(
+        "nsc {command} failed{}: {stderr}{}",
+        exit_code.map(|code| format!(" with exit code {}", code)).unwrap_or_default(),
+        invocation_details(argv, stdout)
+    )]
+    NscCommandFailed {
+        command: String,
+        /// The full command line, with secrets redacted. Empty when nsc ran fine but its output
+        /// couldn't be used.
+        argv: Vec<String>,
+        exit_code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+
+    /// An nsc invocation ran longer than `nsc_timeout` and was killed.
+    #[error("nsc {command} timed out after {timeout:?}; command: {}", argv.join(" "))]
+    NscTimedOut {
+        command: String,
+        /// The full command line, with secrets redacted.
+        argv: Vec<String>,
+        timeout: Duration,
+    },
+
+    #[error("nats-server not found at {resolution}: {source}")]
+    NatsServerNotFound {
+        /// The path that was tried and where it came from.
+        resolution: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// `nats-server -t` rejected a generated config.
+    #[error("nats-server rejected the config of server {server}: {output}")]
+    ConfigCheckFailed {
+        server: String,
+        /// What nats-server printed.
+        output: String,
+    },
+
+    /// Neither the importer's server nor another server of its operator defines the account.
+    #[error(
+        "account {importer} on server {server} imports from unknown account {account}{}",
+        suggestion.as_ref().map(|name| format!("; did you mean {}?", name)).unwrap_or_default()
+    )]
+    UnknownImportAccount {
+        account: String,
+        importer: String,
+        server: String,
+        /// The closest account name, when one is close enough to be a typo.
+        suggestion: Option<String>,
+    },
+
+    /// A cycle of imports through a private export, by account name.
+    #[error("circular import dependency: {}", cycle.join(" -> "))]
+    CircularDependency { cycle: Vec<String> },
+
+    /// With exact naming, the store already holds an operator or account of that name and
+    /// `reuse_existing` is off.
+    #[error("{kind} {name} already exists in the store; set operator.reuse_existing to reuse it")]
+    AlreadyInStore { kind: &'static str, name: String },
+
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("failed to parse config {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A config's `extends` lead back to a file that is still being loaded. `chain` runs from that
+    /// file to itself, as canonical paths.
+    #[error("config extends itself: {}", display_chain(chain))]
+    ExtendsCycle { chain: Vec<PathBuf> },
+
+    /// The profile asked for isn't among the config's `profiles`, listed sorted in `available`.
+    #[error("unknown profile {name}; {}", available_profiles(available))]
+    UnknownProfile { name: String, available: Vec<String> },
+
+    #[error("invalid JWT: {0}")]
+    Jwt(String),
+
+    /// One of a server's `external_accounts` doesn't hold an account JWT.
+    #[error("{} is not an account JWT: {reason}", path.display())]
+    NotAnAccountJwt { path: PathBuf, reason: String },
+
+    /// One of a server's `external_accounts` was issued by a key the setup doesn't accept.
+    #[error(
+        "account JWT {} is issued by {issuer}, which is neither the operator nor one of the server's \
+         external_account_issuers",
+        path.display()
+    )]
+    ExternalIssuerMismatch { path: PathBuf, issuer: String },
+
+    /// A credential backend could not issue what was asked of it.
+    #[error("{0}")]
+    Backend(String),
+
+    /// No connection to a running server could be made in time.
+    #[error("failed to connect to server {server}: {message}")]
+    Connect { server: String, message: String },
+
+    /// A running server couldn't be asked to reload its config, or refused to.
+    #[error("server {server} did not reload its config: {message}")]
+    Reload { server: String, message: String },
+
+    /// Creating a JetStream resource on a running server failed.
+    #[error("provisioning {resource} on server {server} failed: {message}")]
+    Provision {
+        server: String,
+        /// What was being created, e.g. `stream ORDERS`.
+        resource: String,
+        message: String,
+    },
+
+    /// [`NatsForge::cleanup`](crate::NatsForge::cleanup) was asked to remove a path outside every
+    /// server's `output_dir`, and removed nothing.
+    #[error("refusing to remove {}, which is outside every configured output_dir", path.display())]
+    OutsideOutputDirs { path: PathBuf },
+
+    /// The setup was cancelled through its
+    /// [`CancellationToken`](crate::progress::CancellationToken) before it finished. `created`
+    /// is what it had created by then, in order.
+    #[error("setup cancelled{}", cancelled_details(created))]
+    Cancelled { created: Vec<SetupEvent> },
+
+    #[error("I/O error on {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl NatsForgeError {
+    /// Adapter for `map_err` that attaches `path` to an I/O error.
+    pub(crate) fn io(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| NatsForgeError::Io { path, source }
+    }
+}
+
+/// The command line and stdout of a failed nsc invocation, for
+/// [`NatsForgeError::NscCommandFailed`].
+fn invocation_details(argv: &[String], stdout: &str) -> String {
+    let mut details = String::new();
+    if !argv.is_empty() {
+        details.push_str(&format!("; command: {}", argv.join(" ")));
+    }
+    if !stdout.is_empty() {
+        details.push_str(&format!("; stdout: {}", stdout));
+    }
+    details
+}
+
+/// The files of an extends cycle, for [`NatsForgeError::ExtendsCycle`].
+fn display_chain(chain: &[PathBuf]) -> String {
+    let chain: Vec<String> = chain.iter().map(|path| path.display().to_string()).collect();
+    chain.join(" -> ")
+}
+
+/// The profiles a config defines, for [`NatsForgeError::UnknownProfile`].
+fn available_profiles(available: &[String]) -> String {
+    if available.is_empty() {
+        return "the config defines no profiles".to_string();
+    }
+    format!("available: {}", available.join(", "))
+}
+
+/// What a cancelled setup left behind, for [`NatsForgeError::Cancelled`].
+fn cancelled_details(created: &[SetupEvent]) -> String {
+    if created.is_empty() {
+        return " before creating anything".to_string();
+    }
+    let created: Vec<String> = created.iter().map(SetupEvent::to_string).collect();
+    format!(" after creating {}", created.join(", "))
+}
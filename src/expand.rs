@@ -0,0 +1,179 @@
+use std::fmt;
+
+use crate::{
+    config::{AccountConfig, NatsConfig, UserConfig},
+    subject::{any_covers, covers},
+};
+
+/// Replies to requests made through a service import arrive on the requester's inbox.
+pub(crate) const INBOX: &str = "_INBOX.>";
+
+/// A permission added to a user on top of what the config states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedGrant {
+    pub account: String,
+    pub user: String,
+    pub grant: Grant,
+    /// The export or import the grant was derived from.
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grant {
+    /// The user may publish responses to requests it received (`allow_pub_response`).
+    PubResponse,
+    /// The user may publish on the subject.
+    Publish(String),
+}
+
+impl fmt::Display for DerivedGrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.grant {
+            Grant::PubResponse => write!(
+                f,
+                "{}/{}: allow_pub_response ({})",
+                self.account, self.user, self.reason
+            ),
+            Grant::Publish(subject) => write!(
+                f,
+                "{}/{}: allow publish {} ({})",
+                self.account, self.user, subject, self.reason
+            ),
+        }
+    }
+}
+
+/// The grants derived from a config, and the problems found while deriving them.
+#[derive(Debug, Clone, Default)]
+pub struct Expansion {
+    pub grants: Vec<DerivedGrant>,
+    pub warnings: Vec<String>,
+}
+
+impl Expansion {
+    /// Returns `user` with the grants derived for it applied.
+    pub fn apply(&self, account: &AccountConfig, user: &UserConfig) -> UserConfig {
+        let mut user = user.clone();
+        for derived in &self.grants {
+            if derived.account != account.name || derived.user != user.name {
+                continue;
+            }
+            match &derived.grant {
+                Grant::PubResponse => {
+                    user.allow_pub_response.get_or_insert(1);
+                }
+                Grant::Publish(subject) => user.allow_pub.push(subject.clone()),
+            }
+        }
+        user
+    }
+
+    fn grant(&mut self, account: &AccountConfig, user: &UserConfig, grant: Grant, reason: String) {
+        let duplicate = self
+            .grants
+            .iter()
+            .any(|g| g.account == account.name && g.user == user.name && g.grant == grant);
+        if !duplicate {
+            self.grants.push(DerivedGrant {
+                account: account.name.clone(),
+                user: user.name.clone(),
+                grant,
+                reason,
+            });
+        }
+    }
+}
+
+impl NatsConfig {
+    /// Derives the permissions users need to serve and call service exports marked with
+    /// `grant_responses`.
+    ///
+    /// Users of the exporting account that can subscribe on the exported subject get
+    /// `allow_pub_response`; users of importing accounts get the import's local subject added to
+    /// their publish allows. Only users whose permissions are restricted are touched.
+    pub fn derive_grants(&self) -> Expansion {
+        let mut expansion = Expansion::default();
+        let accounts: Vec<&AccountConfig> = self.servers.iter().flat_map(|s| &s.accounts).collect();
+
+        for account in &accounts {
+            for export in account.exports.iter().filter(|e| e.is_service && e.grant_responses) {
+                for user in &account.users {
+                    if user.allow_pub_response.is_some()
+                        || !publish_restricted(user)
+                        || !can_subscribe(user, &export.subject)
+                    {
+                        continue;
+                    }
+                    let reason = format!("serves service export {}", export.subject);
+                    expansion.grant(account, user, Grant::PubResponse, reason);
+                }
+            }
+        }
+
+        for account in &accounts {
+            for import in account.imports.iter().filter(|i| i.service) {
+                let Some(exporter) = accounts.iter().find(|a| a.name == import.account) else {
+                    continue;
+                };
+                let exported = exporter
+                    .exports
+                    .iter()
+                    .any(|e| e.is_service && e.grant_responses && covers(&e.subject, &import.subject));
+                if !exported {
+                    continue;
+                }
+
+                let local_subject = import.local_subject.as_deref().unwrap_or(&import.subject);
+                for user in &account.users {
+                    if publish_denied(user, local_subject) {
+                        expansion.warnings.push(format!(
+                            "User {} in account {} is denied publishing to {} and cannot use the service imported \
+                             from {}",
+                            user.name, account.name, local_subject, exporter.name
+                        ));
+                        continue;
+                    }
+                    if publish_restricted(user) && !publish_allowed(user, local_subject) {
+                        let reason = format!("calls service {} imported from {}", local_subject, exporter.name);
+                        expansion.grant(account, user, Grant::Publish(local_subject.to_string()), reason);
+                    }
+                    if !can_subscribe(user, INBOX) {
+                        expansion.warnings.push(format!(
+                            "User {} in account {} cannot subscribe to {} and will not receive replies from the \
+                             service imported from {}",
+                            user.name, account.name, INBOX, exporter.name
+                        ));
+                    }
+                }
+            }
+        }
+
+        expansion
+    }
+}
+
+fn publish_restricted(user: &UserConfig) -> bool {
+    !user.allow_pubsub.is_empty() || !user.allow_pub.is_empty()
+}
+
+fn publish_allowed(user: &UserConfig, subject: &str) -> bool {
+    let allows = user.allow_pubsub.iter().chain(&user.allow_pub);
+    any_covers(allows, subject)
+}
+
+fn publish_denied(user: &UserConfig, subject: &str) -> bool {
+    let denies = user.deny_pubsub.iter().chain(&user.deny_pub);
+    any_covers(denies, subject)
+}
+
+fn can_subscribe(user: &UserConfig, subject: &str) -> bool {
+    let denies = user.deny_pubsub.iter().chain(&user.deny_sub);
+    if any_covers(denies, subject) {
+        return false;
+    }
+    if user.allow_pubsub.is_empty() && user.allow_sub.is_empty() {
+        return true;
+    }
+    let allows = user.allow_pubsub.iter().chain(&user.allow_sub);
+    any_covers(allows, subject)
+}
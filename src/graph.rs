@@ -0,0 +1,200 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use serde::Serialize;
+
+use crate::config::NatsConfig;
+
+/// Accounts and the imports between them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccountGraph {
+    pub nodes: Vec<AccountNode>,
+    pub edges: Vec<ImportEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountNode {
+    pub name: String,
+    /// The account's public key; unknown until the account has been issued.
+    pub id: Option<String>,
+    /// The servers the account is configured on.
+    pub servers: Vec<String>,
+}
+
+/// An import, pointing from the exporting account to the importing one.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportEdge {
+    pub exporter: String,
+    pub importer: String,
+    pub subject: String,
+    pub local_subject: Option<String>,
+    pub kind: ImportKind,
+    /// Whether the export is private, so the import carries an activation token.
+    pub private: bool,
+    /// The accounts are configured on different servers.
+    pub cross_server: bool,
+    /// The edge is part of an import cycle, which `initialize()` rejects if one of its imports
+    /// is of a private export.
+    pub in_cycle: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportKind {
+    Stream,
+    Service,
+}
+
+impl NatsConfig {
+    /// Builds the import graph of the configured accounts, without issuing anything.
+    pub fn account_graph(&self) -> AccountGraph {
+        let mut graph = AccountGraph::default();
+        for server in &self.servers {
+            for account in &server.accounts {
+                match graph.nodes.iter_mut().find(|n| n.name == account.name) {
+                    Some(node) => node.servers.push(server.name.clone()),
+                    None => graph.nodes.push(AccountNode {
+                        name: account.name.clone(),
+                        id: None,
+                        servers: vec![server.name.clone()],
+                    }),
+                }
+            }
+        }
+
+        for server in &self.servers {
+            for account in &server.accounts {
+                for import in &account.imports {
+                    let private = self
+                        .import_exporter(server, &import.account)
+                        .is_some_and(|(_, exporter)| exporter.private_export(import).is_some());
+                    graph.edges.push(ImportEdge {
+                        exporter: import.account.clone(),
+                        importer: account.name.clone(),
+                        subject: import.subject.clone(),
+                        local_subject: import.local_subject.clone(),
+                        kind: if import.service {
+                            ImportKind::Service
+                        } else {
+                            ImportKind::Stream
+                        },
+                        private,
+                        cross_server: false,
+                        in_cycle: false,
+                    });
+                }
+            }
+        }
+
+        let placement: HashMap<&str, &[String]> = graph
+            .nodes
+            .iter()
+            .map(|n| (n.name.as_str(), n.servers.as_slice()))
+            .collect();
+        let cross_server: Vec<bool> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                match (
+                    placement.get(edge.exporter.as_str()),
+                    placement.get(edge.importer.as_str()),
+                ) {
+                    (Some(exporter), Some(importer)) => !exporter.iter().any(|s| importer.contains(s)),
+                    _ => false,
+                }
+            })
+            .collect();
+        let in_cycle: Vec<bool> = graph
+            .edges
+            .iter()
+            .map(|edge| graph.reaches(&edge.importer, &edge.exporter))
+            .collect();
+        for (edge, (cross_server, in_cycle)) in graph.edges.iter_mut().zip(cross_server.into_iter().zip(in_cycle)) {
+            edge.cross_server = cross_server;
+            edge.in_cycle = in_cycle;
+        }
+        graph
+    }
+}
+
+impl AccountGraph {
+    /// Fills in the account IDs from an account name → public key map.
+    pub fn with_ids(mut self, ids: &HashMap<String, String>) -> Self {
+        for node in &mut self.nodes {
+            node.id = ids.get(&node.name).cloned();
+        }
+        self
+    }
+
+    /// Whether `to` can be reached from `from` by following edges.
+    fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(account) = stack.pop() {
+            if account == to {
+                return true;
+            }
+            if seen.insert(account) {
+                stack.extend(
+                    self.edges
+                        .iter()
+                        .filter(|e| e.exporter == account)
+                        .map(|e| e.importer.as_str()),
+                );
+            }
+        }
+        false
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("account graph serializes")
+    }
+
+    /// Renders the graph in Graphviz DOT. Cross-server imports are dashed, imports that are part of
+    /// a cycle are red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph accounts {\n    rankdir=LR;\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let mut label = node.name.clone();
+            if let Some(id) = &node.id {
+                let _ = write!(label, "\\n{}", id);
+            }
+            let _ = write!(label, "\\n@ {}", node.servers.join(", "));
+            let _ = writeln!(dot, "    {} [label={}];", quote(&node.name), quote(&label));
+        }
+        for edge in &self.edges {
+            let kind = match edge.kind {
+                ImportKind::Stream => "stream",
+                ImportKind::Service => "service",
+            };
+            let mut label = format!("{} ({})", edge.subject, kind);
+            let mut attributes = Vec::new();
+            if edge.cross_server {
+                label.push_str("\\ncross-server");
+                attributes.push("style=dashed".to_string());
+            }
+            if edge.in_cycle {
+                label.push_str("\\ncycle");
+                attributes.push("color=red".to_string());
+                attributes.push("fontcolor=red".to_string());
+            }
+            attributes.insert(0, format!("label={}", quote(&label)));
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [{}];",
+                quote(&edge.exporter),
+                quote(&edge.importer),
+                attributes.join(", ")
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quotes a DOT identifier. `\n` sequences already in the text are kept as line breaks.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\\\""))
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use base64::{
+    engine::general_purpose::{STANDARD_NO_PAD as BASE64, URL_SAFE_NO_PAD as BASE64_URL},
+    Engine,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{NatsForgeError, Result};
+
+/// The claims every NATS JWT has, with those under `nats` typed as `N`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims<N> {
+    #[serde(default)]
+    pub jti: String,
+    #[serde(default)]
+    pub iat: i64,
+    /// The public key of the signer: the operator (or one of its signing keys) for accounts, the
+    /// account (or one of its signing keys) for users.
+    pub iss: String,
+    /// The public key the JWT is about.
+    pub sub: String,
+    #[serde(default)]
+    pub name: String,
+    /// Seconds since the epoch.
+    #[serde(default)]
+    pub exp: Option<i64>,
+    pub nats: N,
+    /// All claims as decoded, including those without a field here.
+    #[serde(skip)]
+    pub raw: Value,
+}
+
+pub type OperatorClaims = Claims<OperatorNats>;
+pub type AccountClaims = Claims<AccountNats>;
+pub type UserClaims = Claims<UserNats>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OperatorNats {
+    pub signing_keys: Vec<String>,
+    /// The public key of the operator's system account.
+    pub system_account: Option<String>,
+    pub account_server_url: Option<String>,
+    pub operator_service_urls: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountNats {
+    pub limits: AccountLimits,
+    pub exports: Vec<ExportClaim>,
+    pub imports: Vec<ImportClaim>,
+    pub signing_keys: Vec<SigningKey>,
+    /// The revoked user keys, with the time they were revoked in seconds since the epoch.
+    pub revocations: HashMap<String, i64>,
+    pub default_permissions: Permissions,
+}
+
+/// Account limits; `-1` is unlimited, and a missing one is the server's default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountLimits {
+    pub subs: Option<i64>,
+    pub data: Option<i64>,
+    pub payload: Option<i64>,
+    pub imports: Option<i64>,
+    pub exports: Option<i64>,
+    pub wildcards: Option<bool>,
+    pub conn: Option<i64>,
+    pub leaf: Option<i64>,
+    pub mem_storage: Option<i64>,
+    pub disk_storage: Option<i64>,
+    pub streams: Option<i64>,
+    pub consumer: Option<i64>,
+    pub disallow_bearer: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportClaim {
+    pub name: String,
+    pub subject: String,
+    /// `stream` or `service`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub token_req: bool,
+    pub response_type: Option<String>,
+    pub account_token_position: Option<u32>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportClaim {
+    pub name: String,
+    pub subject: String,
+    /// The public key of the exporting account.
+    pub account: String,
+    /// `stream` or `service`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub local_subject: Option<String>,
+    /// The activation JWT of a private export.
+    pub token: Option<String>,
+    pub share: bool,
+}
+
+/// An account signing key: a plain public key, or a scoped one whose users get the permissions
+/// and limits of its template.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SigningKey {
+    Plain(String),
+    Scoped {
+        key: String,
+        #[serde(default)]
+        role: String,
+        #[serde(default)]
+        template: Box<UserNats>,
+    },
+}
+
+impl SigningKey {
+    /// The public key, scoped or not.
+    pub fn key(&self) -> &str {
+        match self {
+            SigningKey::Plain(key) | SigningKey::Scoped { key, .. } => key,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserNats {
+    #[serde(flatten)]
+    pub permissions: Permissions,
+    /// Subscription, data and payload limits; `-1` is unlimited.
+    pub subs: Option<i64>,
+    pub data: Option<i64>,
+    pub payload: Option<i64>,
+    pub bearer_token: bool,
+    /// The account the user belongs to when it was signed by one of its signing keys.
+    pub issuer_account: Option<String>,
+    pub allowed_connection_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Permissions {
+    #[serde(rename = "pub")]
+    pub publish: SubjectRules,
+    #[serde(rename = "sub")]
+    pub subscribe: SubjectRules,
+    #[serde(rename = "resp")]
+    pub response: Option<ResponsePermission>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubjectRules {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResponsePermission {
+    pub max: i64,
+    /// Nanoseconds.
+    pub ttl: i64,
+}
+
+/// Decodes the claims of a JWT into `T` without verifying its signature. The payload may be
+/// base64url or standard base64, padded or not.
+pub fn decode_claims<T: DeserializeOwned>(jwt: &str) -> Result<T> {
+    let parts: Vec<&str> = jwt.trim().split('.').collect();
+    if parts.len() != 3 {
+        return Err(NatsForgeError::Jwt(format!("expected 3 parts, found {}", parts.len())));
+    }
+    let payload = parts[1].trim_end_matches('=');
+    let payload = BASE64_URL
+        .decode(payload)
+        .or_else(|_| BASE64.decode(payload))
+        .map_err(|e| NatsForgeError::Jwt(format!("Failed to decode payload: {}", e)))?;
+    serde_json::from_slice(&payload).map_err(|e| NatsForgeError::Jwt(format!("Failed to parse payload: {}", e)))
+}
+
+pub fn decode_operator_claims(jwt: &str) -> Result<OperatorClaims> {
+    decode_typed(jwt, "operator")
+}
+
+pub fn decode_account_claims(jwt: &str) -> Result<AccountClaims> {
+    decode_typed(jwt, "account")
+}
+
+pub fn decode_user_claims(jwt: &str) -> Result<UserClaims> {
+    decode_typed(jwt, "user")
+}
+
+/// The public key an account JWT is about.
+pub fn extract_account_id(jwt: &str) -> Result<String> {
+    decode_claims::<Value>(jwt)?["sub"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| NatsForgeError::Jwt("No 'sub' field".to_string()))
+}
+
+/// Decodes a JWT of the given `type`, keeping the raw claims alongside the typed ones.
+fn decode_typed<N: DeserializeOwned>(jwt: &str, kind: &str) -> Result<Claims<N>> {
+    let raw: Value = decode_claims(jwt)?;
+    // Version 1 JWTs have the type at the top level.
+    let found = raw["nats"]["type"]
+        .as_str()
+        .or(raw["type"].as_str())
+        .unwrap_or_default();
+    if found != kind {
+        return Err(NatsForgeError::Jwt(format!(
+            "expected a JWT of type {}, found {:?}",
+            kind, found
+        )));
+    }
+    let mut claims: Claims<N> = serde_json::from_value(raw.clone())
+        .map_err(|e| NatsForgeError::Jwt(format!("Failed to parse {} claims: {}", kind, e)))?;
+    claims.raw = raw;
+    Ok(claims)
+}
@@ -0,0 +1,262 @@
+//! Kubernetes manifests for the generated servers, as plain YAML.
+//!
+//! Every server gets a ConfigMap with its `nats.conf`, a Secret with the account JWTs and creds
+//! the server reads, a StatefulSet running it and a Service in front of it. In the pod the Secret
+//! is mounted at [`SECRETS_DIR`], which takes the place of the output directory, and whatever the
+//! server writes, such as its JetStream store, goes below [`DATA_DIR`].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+
+use crate::{
+    compose::{CONTAINER_CONFIG, DEFAULT_IMAGE},
+    config::{ResolverType, ServerConfig},
+    prometheus::ExporterOptions,
+    yaml,
+};
+
+/// Where the Secret is mounted, and where the server's config expects its output directory.
+pub const SECRETS_DIR: &str = "/etc/nats/secrets";
+/// The server's writable directory, a volume claim when it keeps state and an `emptyDir` otherwise.
+pub const DATA_DIR: &str = "/data";
+
+/// How [`crate::NatsForge::emit_kubernetes`] writes the manifests.
+#[derive(Debug, Clone)]
+pub struct K8sOptions {
+    /// Where the `<server>.yaml` files are written. They hold Secrets, so they are only readable by
+    /// the current user.
+    pub output_dir: PathBuf,
+    pub namespace: String,
+    /// Added to every resource next to the `app.kubernetes.io/name` and `instance` labels.
+    pub labels: BTreeMap<String, String>,
+    /// Needs a shell to load resolver JWTs, as the alpine variants have.
+    pub image: String,
+    /// Hostnames to use in leafnode remote URLs instead of the hosts they name, such as
+    /// `hub.example.com` → `hub.nats.svc.cluster.local`. Remotes to `localhost` and the loopback
+    /// addresses are pointed at the Service of the server listening for leaf nodes on their port.
+    pub hostnames: HashMap<String, String>,
+    pub cluster_domain: String,
+    /// Size of the volume claim of servers with JetStream or a full or cache resolver.
+    pub storage_size: String,
+    /// Adds an exporter container to the pod of every server with a monitoring `http_port`, with
+    /// its metrics port named `metrics` on the Service.
+    pub exporter: Option<ExporterOptions>,
+}
+
+impl Default for K8sOptions {
+    fn default() -> Self {
+        K8sOptions {
+            output_dir: PathBuf::from("k8s"),
+            namespace: "default".to_string(),
+            labels: BTreeMap::new(),
+            image: DEFAULT_IMAGE.to_string(),
+            hostnames: HashMap::new(),
+            cluster_domain: "cluster.local".to_string(),
+            storage_size: "1Gi".to_string(),
+            exporter: None,
+        }
+    }
+}
+
+/// The name of a server's resources: its name in lowercase, with every character a DNS label
+/// doesn't allow replaced by `-`.
+pub fn resource_name(server_name: &str) -> String {
+    let name: String = server_name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '-',
+        })
+        .collect();
+    name.trim_matches('-').to_string()
+}
+
+/// The cluster DNS name of a server's Service.
+pub(crate) fn service_host(server: &ServerConfig, options: &K8sOptions) -> String {
+    format!(
+        "{}.{}.svc.{}",
+        resource_name(&server.name),
+        options.namespace,
+        options.cluster_domain
+    )
+}
+
+/// Moves what the server writes from its output directory to [`DATA_DIR`]: the JetStream store,
+/// the resolver directory and the logfile. Absolute paths are kept.
+pub(crate) fn move_writable_paths(pod: &mut ServerConfig) {
+    let data = Path::new(DATA_DIR);
+    let store_dir = pod.jetstream.store_dir.as_deref().unwrap_or("jetstream");
+    pod.jetstream.store_dir = Some(data.join(store_dir).to_string_lossy().into_owned());
+    if let ResolverType::Full { dir, .. } | ResolverType::Cache { dir, .. } = &mut pod.resolver {
+        *dir = data.join(&*dir);
+    }
+    if let Some(logfile) = pod.logging.as_mut().and_then(|logging| logging.logfile.as_mut()) {
+        *logfile = data.join(&*logfile);
+    }
+}
+
+/// What the manifests of one server are made of.
+pub(crate) struct ServerManifests<'a> {
+    pub(crate) server: &'a ServerConfig,
+    /// The rendered `nats.conf` for the pod.
+    pub(crate) config: String,
+    /// Secret contents by file name in [`SECRETS_DIR`].
+    pub(crate) secrets: BTreeMap<String, String>,
+    /// The resolver directory in the pod, and the Secret keys of the JWTs it starts with.
+    pub(crate) resolver: Option<(PathBuf, Vec<String>)>,
+}
+
+/// Renders the ConfigMap, Secret, StatefulSet and Service of a server as one YAML stream.
+pub(crate) fn render_manifests(manifests: &ServerManifests, options: &K8sOptions) -> String {
+    let server = manifests.server;
+    let name = resource_name(&server.name);
+    let selector = json!({
+        "app.kubernetes.io/name": "nats",
+        "app.kubernetes.io/instance": name,
+    });
+    let mut labels = selector.clone();
+    for (key, value) in &options.labels {
+        labels[key] = json!(value);
+    }
+    let metadata = |name: &str| {
+        json!({
+            "name": name,
+            "namespace": options.namespace,
+            "labels": labels,
+        })
+    };
+    let config_map_name = format!("{}-config", name);
+    let secret_name = format!("{}-secrets", name);
+
+    let monitoring = server.monitoring.as_ref();
+    let ports: Vec<(&str, u16)> = [
+        ("client", Some(server.port)),
+        ("leafnodes", server.leafnodes.port),
+        ("monitor", monitoring.and_then(|monitoring| monitoring.http_port)),
+        ("monitor-https", monitoring.and_then(|monitoring| monitoring.https_port)),
+    ]
+    .into_iter()
+    .filter_map(|(port_name, port)| Some((port_name, port?)))
+    .collect();
+
+    let config_map = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": metadata(&config_map_name),
+        "data": { "nats.conf": manifests.config },
+    });
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": metadata(&secret_name),
+        "type": "Opaque",
+        "stringData": manifests.secrets,
+    });
+
+    let persistent = server.jetstream.enabled || manifests.resolver.is_some();
+    let mounts = json!([
+        { "name": "config", "mountPath": CONTAINER_CONFIG, "subPath": "nats.conf" },
+        { "name": "secrets", "mountPath": SECRETS_DIR, "readOnly": true },
+        { "name": "data", "mountPath": DATA_DIR },
+    ]);
+    let mut volumes = vec![
+        json!({ "name": "config", "configMap": { "name": config_map_name } }),
+        json!({ "name": "secrets", "secret": { "secretName": secret_name } }),
+    ];
+    if !persistent {
+        volumes.push(json!({ "name": "data", "emptyDir": {} }));
+    }
+    let container_ports = |ports: &[(&str, u16)]| {
+        ports
+            .iter()
+            .map(|(port_name, port)| json!({ "name": port_name, "containerPort": port }))
+            .collect::<Vec<_>>()
+    };
+    let mut containers = vec![json!({
+        "name": "nats",
+        "image": options.image,
+        "args": ["-c", CONTAINER_CONFIG],
+        "ports": container_ports(&ports),
+        "volumeMounts": mounts,
+    })];
+    let mut service_ports = ports.clone();
+    let http_port = monitoring.and_then(|monitoring| monitoring.http_port);
+    if let (Some(exporter), Some(http_port)) = (&options.exporter, http_port) {
+        let metrics = [("metrics", exporter.port)];
+        containers.push(json!({
+            "name": "exporter",
+            "image": exporter.image,
+            "args": exporter.args(server, &format!("http://localhost:{}", http_port)),
+            "ports": container_ports(&metrics),
+        }));
+        service_ports.extend(metrics);
+    }
+    let mut pod_spec = json!({
+        "containers": containers,
+        "volumes": volumes,
+    });
+    if let Some((dir, keys)) = &manifests.resolver {
+        let sources: Vec<String> = keys
+            .iter()
+            .map(|key| sh_quote(&Path::new(SECRETS_DIR).join(key).to_string_lossy()))
+            .collect();
+        let dir = sh_quote(&dir.to_string_lossy());
+        pod_spec["initContainers"] = json!([{
+            "name": "load-resolver",
+            "image": options.image,
+            "command": ["sh", "-c", format!("mkdir -p {0} && cp {1} {0}/", dir, sources.join(" "))],
+            "volumeMounts": mounts,
+        }]);
+    }
+    let mut stateful_set = json!({
+        "apiVersion": "apps/v1",
+        "kind": "StatefulSet",
+        "metadata": metadata(&name),
+        "spec": {
+            "serviceName": name,
+            "replicas": 1,
+            "selector": { "matchLabels": selector },
+            "template": {
+                "metadata": { "labels": labels },
+                "spec": pod_spec,
+            },
+        },
+    });
+    if persistent {
+        stateful_set["spec"]["volumeClaimTemplates"] = json!([{
+            "metadata": { "name": "data" },
+            "spec": {
+                "accessModes": ["ReadWriteOnce"],
+                "resources": { "requests": { "storage": options.storage_size } },
+            },
+        }]);
+    }
+
+    let service = json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": metadata(&name),
+        "spec": {
+            "selector": selector,
+            "ports": service_ports
+                .iter()
+                .map(|(port_name, port)| json!({ "name": port_name, "port": port, "targetPort": port_name }))
+                .collect::<Vec<_>>(),
+        },
+    });
+
+    [config_map, secret, stateful_set, service]
+        .iter()
+        .map(yaml::to_yaml)
+        .collect::<Vec<_>>()
+        .join("---\n")
+}
+
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
@@ -1,301 +1,3054 @@
+//! Generates decentralized-auth NATS deployments: an operator, accounts and users with their JWTs
+//! and `.creds` files, and a `nats.conf` per server with the account JWTs preloaded.
+//!
+//! ```
+//! use natsforge::{
+//!     config::{AccountConfig, BackendKind, NatsConfig, OperatorConfig, ServerConfig, UserConfig},
+//!     NatsForge,
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() -> natsforge::Result<()> {
+//! let output_dir = tempfile::tempdir().unwrap();
+//! let config = NatsConfig {
+//!     operator: OperatorConfig {
+//!         name: "my-operator".to_string(),
+//!         reuse_existing: false,
+//!         account_server_url: None,
+//!         service_urls: vec![],
+//!     },
+//!     servers: vec![ServerConfig {
+//!         name: "my-server".to_string(),
+//!         port: 4222,
+//!         output_dir: output_dir.path().to_path_buf(),
+//!         accounts: vec![AccountConfig {
+//!             name: "APP".to_string(),
+//!             users: vec![UserConfig {
+//!                 name: "app-user".to_string(),
+//!                 allow_pubsub: vec!["app.>".to_string()],
+//!                 ..Default::default()
+//!             }],
+//!             ..Default::default()
+//!         }],
+//!         ..Default::default()
+//!     }],
+//!     // No nsc needed: keys are generated and JWTs signed in-process.
+//!     backend: BackendKind::Native,
+//!     ..Default::default()
+//! };
+//!
+//! let result = NatsForge::from_config(config)?.initialize().await?;
+//! assert!(result.server_config("my-server").unwrap().ends_with("nats.conf"));
+//! assert!(result.creds("APP", "app-user").unwrap().ends_with("APP-app-user.creds"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! See `examples/` for a hub/leaf setup and JSON configurations for the CLI.
+//!
+//! [`prelude`] re-exports the types most programs need. `tests/public-api.txt` records the public
+//! API and which modules are considered stable.
+
 use std::{
-    collections::{HashMap, HashSet},
-    path::PathBuf,
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
 };
 
-use anyhow::{Context, Result};
+use futures_util::{stream, StreamExt, TryStreamExt};
 use tempfile::TempDir;
-use tokio::process::Command;
+use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 
+#[cfg(feature = "client")]
+use crate::config::{ReloadReport, ReloadedServer};
+#[cfg(feature = "encryption")]
+use crate::encryption::{Encrypter, ENCRYPTED_SUFFIX};
 use crate::{
-    config::{AccountConfig, NatsConfig, SetupResult},
-    nsc::{create_account, create_operator, create_user},
-    server::generate_server_config,
+    compose::ComposeOptions,
+    conf::{ConfDocument, ConfObject},
+    config::{
+        AccountArtifacts, AccountConfig, ArtifactMode, AuthCalloutKeys, AuthMode, BackendKind, CleanupReport,
+        ConnectionInfo, NamingMode, NatsConfig, OperatorConfig, OperatorRef, PushReport, RemoteConfig, ResolverType,
+        RotatedUser, ServerConfig, SetupArtifacts, SetupResult, UserConfig, VerifyMode,
+    },
+    diff::{DiffReport, Snapshot},
+    env::ProcessEnv,
+    kubernetes::K8sOptions,
+    manifest::MANIFEST_FILE,
+    nsc::ProcessRunner,
+    progress::{CancellationToken, Progress, SetupEvent},
+    secrets::SecretSink,
+    server::ServerAuth,
+    timing::{TimingRecorder, PHASE_ACCOUNTS, PHASE_IMPORTS, PHASE_OPERATOR, PHASE_RENDER, PHASE_USERS, PHASE_WRITES},
+    validate::parse_duration,
+    writer::{create_output_dir, ArtifactWriter, FsSink, OutputSink},
 };
 
+pub mod backend;
+#[cfg(feature = "client")]
+pub mod client;
+mod cluster;
+pub mod compose;
+pub mod conf;
+mod conf_auth;
 pub mod config;
+pub mod diff;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod env;
+pub mod error;
+pub mod expand;
+pub mod graph;
+pub mod jwt;
+pub mod kubernetes;
+pub mod manifest;
+pub mod native;
+pub mod net;
 mod nsc;
+mod overlay;
+pub mod prelude;
+pub mod progress;
+pub mod prometheus;
+#[cfg(feature = "client")]
+mod provision;
+pub mod secrets;
 mod server;
-pub use nsc::extract_account_id;
+pub mod subject;
+mod tenant;
+pub mod timing;
+pub mod tls;
+mod user_template;
+mod validate;
+pub mod writer;
+mod yaml;
+pub use backend::CredentialBackend;
+pub use error::{NatsForgeError, Result};
+pub use jwt::extract_account_id;
+pub use native::NativeBackend;
+pub use nsc::{NscBackend, NscRunner};
+
+/// How many accounts or users are issued at once unless `concurrency` says otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// How long an nsc invocation may run unless `nsc_timeout` says otherwise.
+pub const DEFAULT_NSC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a failed read-only nsc command is retried unless `nsc_retries` says otherwise.
+pub const DEFAULT_NSC_RETRIES: u32 = 1;
 
 pub struct NatsForge {
     config: NatsConfig,
-    store_dir: TempDir,
+    /// Created with the forge by `from_config`, and by the first `initialize()` after `new`.
+    store_dir: OnceLock<StoreDir>,
+    /// Whether `new` kept the config as given because its templates don't expand, for
+    /// `initialize()` to report why.
+    unexpanded: bool,
+    backend: Box<dyn CredentialBackend>,
+    sink: Box<dyn OutputSink>,
+    secret_sink: Option<Box<dyn SecretSink>>,
+    cancel: Option<CancellationToken>,
+    /// What the last `initialize()` issued, for rendering the server configs again.
+    issued: Mutex<Option<Issued>>,
 }
 
-impl NatsForge {
-    pub fn new(mut config: NatsConfig) -> Self {
-        let store_dir = TempDir::new().expect("Failed to create temp store dir");
-        let unique_operator_name = format!("{}-{}", config.operator.name, Uuid::new_v4());
-        config.operator.name = unique_operator_name;
-
-        for server in &mut config.servers {
-            for account in &mut server.accounts {
-                if account.unique_name.is_empty() {
-                    account.unique_name = format!("{}-{}", account.name, Uuid::new_v4());
-                }
+/// Where the backend keeps its store.
+enum StoreDir {
+    /// Removed together with the forge.
+    Temp(TempDir),
+    /// `nsc_store_dir`, kept across runs.
+    Persistent(PathBuf),
+}
+
+impl StoreDir {
+    fn new(config: &NatsConfig) -> Result<Self> {
+        match &config.nsc_store_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir).map_err(NatsForgeError::io(dir))?;
+                Ok(StoreDir::Persistent(dir.clone()))
             }
+            None => Ok(StoreDir::Temp(
+                TempDir::new().map_err(NatsForgeError::io(std::env::temp_dir()))?,
+            )),
         }
+    }
 
-        NatsForge { config, store_dir }
+    fn path(&self) -> &Path {
+        match self {
+            StoreDir::Temp(dir) => dir.path(),
+            StoreDir::Persistent(dir) => dir,
+        }
     }
+}
 
-    pub fn from_config(mut config: NatsConfig) -> Result<Self> {
-        let store_dir = TempDir::new().context("Failed to create temp store dir")?;
-        let unique_operator_name = format!("{}-{}", config.operator.name, Uuid::new_v4());
-        config.operator.name = unique_operator_name;
-
-        for server in &mut config.servers {
-            for account in &mut server.accounts {
-                if account.unique_name.is_empty() {
-                    account.unique_name = format!("{}-{}", account.name, Uuid::new_v4());
-                }
-            }
+/// An operator's JWT and its system account: the one created with it, or
+/// [`NatsConfig::system_account`].
+struct IssuedOperator {
+    jwt: String,
+    system_id: String,
+    system_jwt: String,
+}
+
+/// The JWTs a server config is rendered from.
+struct Issued {
+    /// By operator name.
+    operators: HashMap<String, IssuedOperator>,
+    /// By account unique name.
+    account_jwts: HashMap<String, String>,
+    /// `.creds` files by account name, then user name, including users added afterwards. An
+    /// account several servers define is the first one's.
+    creds: HashMap<String, HashMap<String, PathBuf>>,
+    /// Users added with `add_user`, with the name of their account.
+    added_users: Vec<(String, UserConfig)>,
+    /// JetStream store directories as rendered, by server name.
+    jetstream_store_dirs: HashMap<String, PathBuf>,
+    /// The `operator.jwt` of servers that refer to it by path, as rendered, by server name.
+    operator_jwt_paths: HashMap<String, PathBuf>,
+    /// The users of conf-mode servers, by server name. Those servers have nothing else issued.
+    conf_credentials: HashMap<String, conf_auth::Credentials>,
+    /// The auth callout keys of conf-mode servers, by server name.
+    callout_keys: HashMap<String, conf_auth::CalloutKeys>,
+    /// The `external_accounts` of each server as account name and JWT, by server name.
+    external_accounts: HashMap<String, Vec<(String, String)>>,
+    output_dirs: OutputDirs,
+}
+
+/// Every server's output directory, created and made absolute once before anything is written,
+/// so no write depends on another server's directory having been created first.
+#[derive(Clone)]
+struct OutputDirs(HashMap<String, PathBuf>);
+
+impl OutputDirs {
+    /// Creates the output directories. In memory nothing is created, so the paths stay as
+    /// configured instead of being made absolute.
+    fn prepare(config: &NatsConfig) -> Result<Self> {
+        let mut dirs = HashMap::new();
+        for server in &config.servers {
+            let dir = if config.artifact_mode == ArtifactMode::InMemory {
+                server.output_dir.clone()
+            } else {
+                create_output_dir(&server.output_dir, config.private_output_dirs)
+                    .map_err(NatsForgeError::io(&server.output_dir))?;
+                std::fs::canonicalize(&server.output_dir).map_err(NatsForgeError::io(&server.output_dir))?
+            };
+            dirs.insert(server.name.clone(), dir);
         }
+        Ok(OutputDirs(dirs))
+    }
 
-        Ok(NatsForge { config, store_dir })
+    /// The output directory of `server`.
+    fn get(&self, server: &ServerConfig) -> &Path {
+        &self.0[&server.name]
     }
+}
 
-    pub fn from_json_file(path: &str) -> Result<Self> {
-        let file = std::fs::File::open(path).context("Failed to open JSON config")?;
-        let mut config: NatsConfig = serde_json::from_reader(file).context("Failed to parse JSON config")?;
-        let store_dir = TempDir::new().context("Failed to create temp store dir")?;
-        let unique_operator_name = format!("{}-{}", config.operator.name, Uuid::new_v4());
-        config.operator.name = unique_operator_name;
-        for server in &mut config.servers {
-            for account in &mut server.accounts {
-                if account.unique_name.is_empty() {
-                    account.unique_name = format!("{}-{}", account.name, Uuid::new_v4());
-                }
+impl Issued {
+    /// What was issued for the operator `server` trusts.
+    fn operator(&self, config: &NatsConfig, server: &ServerConfig) -> &IssuedOperator {
+        &self.operators[&config.server_operator(server).name]
+    }
+}
+
+/// Gives the operator and accounts without a `unique_name` their names in the backend's store.
+fn apply_naming(config: &mut NatsConfig) {
+    let naming = config.naming;
+    let store_name = |name: &str| match naming {
+        NamingMode::Unique => format!("{}-{}", name, Uuid::new_v4()),
+        NamingMode::Exact => name.to_string(),
+    };
+    let shorthand = config.operators.is_empty() || !config.operator.name.is_empty();
+    let operators = shorthand
+        .then_some(&mut config.operator)
+        .into_iter()
+        .chain(&mut config.operators);
+    let mut renamed = HashMap::new();
+    for operator in operators {
+        let name = store_name(&operator.name);
+        renamed.insert(std::mem::replace(&mut operator.name, name.clone()), name);
+    }
+    for server in &mut config.servers {
+        if let Some(operator) = &mut server.operator {
+            if let Some(name) = renamed.get(operator) {
+                *operator = name.clone();
+            }
+        }
+        for account in &mut server.accounts {
+            if account.unique_name.is_empty() {
+                account.unique_name = store_name(&account.name);
             }
         }
-        Ok(NatsForge { config, store_dir })
     }
+}
+
+fn default_backend(config: &NatsConfig) -> Box<dyn CredentialBackend> {
+    match config.backend {
+        BackendKind::Nsc => {
+            // An invalid nsc_timeout fails validate() before nsc ever runs.
+            let timeout = config
+                .nsc_timeout
+                .as_deref()
+                .and_then(|timeout| parse_duration(timeout).ok())
+                .unwrap_or(DEFAULT_NSC_TIMEOUT);
+            Box::new(
+                NscBackend::new(config.nsc_path.clone())
+                    .with_env(ProcessEnv::from_config(config))
+                    .with_timeout(timeout)
+                    .with_retries(config.nsc_retries.unwrap_or(DEFAULT_NSC_RETRIES)),
+            )
+        }
+        BackendKind::Native => Box::new(NativeBackend::new()),
+    }
+}
+
+impl NatsForge {
+    /// Like [`NatsForge::from_config`], but never fails: templates that don't expand and a store
+    /// directory that can't be created fail `initialize()` instead.
+    pub fn new(config: NatsConfig) -> Self {
+        let mut expanded = config.clone();
+        match expand_templates(&mut expanded) {
+            Ok(()) => Self::prepare(expanded, OnceLock::new(), false),
+            Err(_) => Self::prepare(config, OnceLock::new(), true),
+        }
+    }
+
+    pub fn from_config(mut config: NatsConfig) -> Result<Self> {
+        expand_templates(&mut config)?;
+        let store_dir = StoreDir::new(&config)?;
+        Ok(Self::prepare(config, OnceLock::from(store_dir), false))
+    }
+
+    fn prepare(mut config: NatsConfig, store_dir: OnceLock<StoreDir>, unexpanded: bool) -> Self {
+        declare_system_account(&mut config);
+        apply_naming(&mut config);
+
+        let backend = default_backend(&config);
+        NatsForge {
+            config,
+            store_dir,
+            unexpanded,
+            backend,
+            sink: Box::new(FsSink),
+            secret_sink: None,
+            cancel: None,
+            issued: Mutex::default(),
+        }
+    }
+
+    /// Like [`NatsForge::from_config`], but issues credentials through a caller-supplied backend
+    /// instead of the one selected by `config.backend`.
+    pub fn with_backend(config: NatsConfig, backend: Box<dyn CredentialBackend>) -> Result<Self> {
+        let mut forge = Self::from_config(config)?;
+        forge.backend = backend;
+        Ok(forge)
+    }
+
+    /// Routes all artifact writes through `sink` instead of straight to the filesystem.
+    pub fn with_output_sink(mut self, sink: Box<dyn OutputSink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Also delivers every account JWT and user `.creds` file to `sink`, under the keys of
+    /// [`secrets::account_jwt_key`] and [`secrets::creds_key`]. Users added or rotated later, and
+    /// account JWTs reissued with revocations, are delivered as well.
+    pub fn with_secret_sink(mut self, sink: Box<dyn SecretSink>) -> Self {
+        self.secret_sink = Some(sink);
+        self
+    }
+
+    /// Makes `initialize()` stop before its next nsc call once `token` is cancelled, failing with
+    /// [`NatsForgeError::Cancelled`].
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Loads the config with [`NatsConfig::from_json_file`], which merges the files it `extends`
+    /// and applies `profile`, if given.
+    pub fn from_json_file(path: &str, profile: Option<&str>) -> Result<Self> {
+        Self::from_config(NatsConfig::from_json_file(path, profile)?)
+    }
+
+    pub fn config(&self) -> &NatsConfig {
+        &self.config
+    }
+
     pub async fn initialize(&self) -> Result<SetupResult> {
-        let operator_jwt = create_operator(&self.config.operator, &self.store_dir.path().to_path_buf()).await?;
-        let operator_jwt_path = self.config.servers[0].output_dir.join("operator.jwt");
-        println!("Writing operator JWT to: {}", operator_jwt_path.display());
-        std::fs::create_dir_all(operator_jwt_path.parent().unwrap())?;
-        std::fs::write(&operator_jwt_path, &operator_jwt)?;
-        if !operator_jwt_path.exists() {
-            println!("Operator JWT write failed: {}", operator_jwt_path.display());
-            return Err(anyhow::anyhow!("Failed to write operator JWT"));
-        }
-        println!("Operator JWT written successfully");
-
-        let default_sys_jwt_path = self
-            .store_dir
-            .path()
-            .join(&self.config.operator.name)
-            .join("accounts")
-            .join("SYS")
-            .join("SYS.jwt");
-        let default_sys_jwt = std::fs::read_to_string(&default_sys_jwt_path)?;
-        let default_sys_id = extract_account_id(&default_sys_jwt)?;
+        self.setup(Progress::new(None, self.cancel.clone())).await
+    }
+
+    /// Like [`NatsForge::initialize`], but sends a [`SetupEvent`] to `tx` for everything created,
+    /// as it is created. The run waits for the receiver when the channel is full.
+    pub async fn initialize_with_progress(&self, tx: Sender<SetupEvent>) -> Result<SetupResult> {
+        self.setup(Progress::new(Some(tx), self.cancel.clone())).await
+    }
+
+    async fn setup(&self, progress: Progress) -> Result<SetupResult> {
+        match self.run_setup(&progress).await {
+            // Steps that were in flight when the run was cancelled may have finished since.
+            Err(NatsForgeError::Cancelled { .. }) => Err(NatsForgeError::Cancelled {
+                created: progress.created(),
+            }),
+            result => result,
+        }
+    }
+
+    async fn run_setup(&self, progress: &Progress) -> Result<SetupResult> {
+        if self.unexpanded {
+            // The expansion `new` gave up on fails the same way again.
+            expand_templates(&mut self.config.clone())?;
+        }
+        let started = Instant::now();
+        let timings = TimingRecorder::default();
+        let in_memory = self.config.artifact_mode == ArtifactMode::InMemory;
+        let writer = if in_memory {
+            ArtifactWriter::discarding()
+        } else {
+            ArtifactWriter::new(self.sink.as_ref(), self.config.durability)
+        };
+        self.config.validate()?;
+        // Nothing is written in memory, so there is nothing to encrypt.
+        let encrypter = match &self.config.encryption {
+            Some(encryption) if !in_memory => Some(Encrypter::new(encryption)?),
+            _ => None,
+        };
+        let expansion = self.config.derive_grants();
+        let mut warnings = self.config.warnings();
+        warnings.extend(expansion.warnings.iter().cloned());
+        for warning in &warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        // Validation keeps every server in the same mode.
+        if self
+            .config
+            .servers
+            .iter()
+            .any(|server| server.auth_mode == AuthMode::Conf)
+        {
+            return self
+                .initialize_conf(started, timings, writer, encrypter.as_ref(), warnings, progress)
+                .await;
+        }
+        let output_dirs = OutputDirs::prepare(&self.config)?;
+        self.backend.preflight().await?;
+        let store_dir = self.store_dir()?;
+        let exact = self.config.naming == NamingMode::Exact;
+        let phase_start = Instant::now();
+        let mut operators = HashMap::new();
+        for operator in self.config.all_operators() {
+            let operator_store_dir = self.operator_store_dir(operator)?;
+            if exact
+                && !operator.reuse_existing
+                && self
+                    .backend
+                    .operator_exists(&operator.name, &operator_store_dir)
+                    .await?
+            {
+                return Err(NatsForgeError::AlreadyInStore {
+                    kind: "operator",
+                    name: operator.name.clone(),
+                });
+            }
+            progress.checkpoint().await?;
+            let jwt = timings
+                .timed(
+                    PHASE_OPERATOR,
+                    &operator.name,
+                    self.backend.create_operator(operator, &operator_store_dir),
+                )
+                .await?;
+            progress
+                .emit(SetupEvent::OperatorCreated {
+                    operator: operator.name.clone(),
+                })
+                .await;
+            let (jwt, system_jwt) = match &self.config.system_account {
+                None => {
+                    let system_jwt = self
+                        .backend
+                        .system_account_jwt(&operator.name, &operator_store_dir)
+                        .await?;
+                    (jwt, system_jwt)
+                }
+                Some(_) => {
+                    let account = system_account(&self.config);
+                    let system_jwt = if operator.reuse_existing
+                        && self
+                            .backend
+                            .account_exists(&account, &operator.name, &operator_store_dir)
+                            .await?
+                    {
+                        self.backend
+                            .account_jwt(&account, &operator.name, &operator_store_dir)
+                            .await?
+                    } else {
+                        self.backend
+                            .create_account(&account, &operator.name, &operator_store_dir)
+                            .await?
+                    };
+                    let jwt = self
+                        .backend
+                        .set_system_account(&account, &operator.name, &operator_store_dir)
+                        .await?;
+                    (jwt, system_jwt)
+                }
+            };
+            let issued_operator = IssuedOperator {
+                jwt,
+                system_id: extract_account_id(&system_jwt)?,
+                system_jwt,
+            };
+            operators.insert(operator.name.clone(), issued_operator);
+        }
+        timings.record(PHASE_OPERATOR, phase_start.elapsed());
+        let server_operator = |server: &ServerConfig| &operators[&self.config.server_operator(server).name];
+        let operator_jwt_paths: Vec<PathBuf> = self
+            .config
+            .servers
+            .iter()
+            .map(|server| server.output_dir.join("operator.jwt"))
+            .collect();
+        if !in_memory {
+            for (server, operator_jwt_path) in self.config.servers.iter().zip(&operator_jwt_paths) {
+                eprintln!("Writing operator JWT to: {}", operator_jwt_path.display());
+                let operator_jwt = &server_operator(server).jwt;
+                timings
+                    .time(PHASE_WRITES, || writer.write(operator_jwt_path, operator_jwt))
+                    .map_err(NatsForgeError::io(operator_jwt_path))?;
+            }
+            eprintln!("Operator JWT written successfully");
+        }
+
+        // Read before anything is issued, so a bad file fails the run early.
+        let mut external_accounts = HashMap::new();
+        for server in &self.config.servers {
+            let accounts = read_external_accounts(server, &server_operator(server).jwt)?;
+            for (account_name, _) in &accounts {
+                let generated = account_name == self.config.system_account_name()
+                    || self
+                        .config
+                        .servers
+                        .iter()
+                        .any(|other| other.accounts.iter().any(|account| account.name == *account_name));
+                if generated {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "External account {} of server {} has the name of an account natsforge generates",
+                        account_name, server.name
+                    )));
+                }
+            }
+            external_accounts.insert(server.name.clone(), accounts);
+        }
 
         let mut account_jwt_paths = Vec::new();
         let mut user_creds_paths = Vec::new();
         let mut server_config_paths = Vec::new();
+        // Account JWTs by unique name, and artifacts by server and then account name, so servers
+        // defining accounts of the same name each keep their own. The maps keyed by account name
+        // alone hold the first defining server's.
         let mut account_jwts = HashMap::new();
-        let mut creds_map: HashMap<String, Vec<(PathBuf, PathBuf)>> = HashMap::new();
-        let mut name_to_unique: HashMap<String, String> = HashMap::new();
+        let mut server_accounts: HashMap<String, HashMap<String, AccountArtifacts>> = HashMap::new();
+        let mut servers = HashMap::new();
+        // Creds by (server, account unique name, user), with the account's configured name.
+        let mut creds_map: HashMap<(String, String, String), (String, String)> = HashMap::new();
+        let mut creds_contents: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut bearer_jwts: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut activations: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut server_configs = HashMap::new();
+        let mut server_files: HashMap<String, HashMap<PathBuf, String>> = HashMap::new();
+        let account_operator = |server_idx: usize| self.config.server_operator(&self.config.servers[server_idx]);
 
         let mut all_accounts: Vec<(usize, usize, &AccountConfig)> = Vec::new();
         for (server_idx, server) in self.config.servers.iter().enumerate() {
-            std::fs::create_dir_all(&server.output_dir)?;
             for (account_idx, account) in server.accounts.iter().enumerate() {
                 all_accounts.push((server_idx, account_idx, account));
-                name_to_unique.insert(account.name.clone(), account.unique_name.clone());
             }
         }
 
         let mut dependencies: HashMap<String, HashSet<String>> = HashMap::new();
-        for (_, _, account) in &all_accounts {
+        // Imports of private exports, as (exporter, importer) unique names.
+        let mut private_imports = HashSet::new();
+        for (server_idx, _, account) in &all_accounts {
             let account_unique_name = &account.unique_name;
             dependencies.entry(account_unique_name.clone()).or_default();
             for import in &account.imports {
-                let src_unique_name = name_to_unique
-                    .get(&import.account)
-                    .ok_or_else(|| anyhow::anyhow!("Unknown import account: {}", import.account))?;
+                let server = &self.config.servers[*server_idx];
+                let (_, exporter) = self
+                    .config
+                    .import_exporter(server, &import.account)
+                    .ok_or_else(|| self.config.unknown_import(server, &account.name, &import.account))?;
+                let src_unique_name = &exporter.unique_name;
                 dependencies
-                    .entry(src_unique_name.clone())
+                    .entry(src_unique_name.to_string())
                     .or_default()
                     .insert(account_unique_name.clone());
+                if exporter.private_export(import).is_some() {
+                    private_imports.insert((src_unique_name.clone(), account_unique_name.clone()));
+                }
             }
         }
 
-        let sorted_accounts = topological_sort(&dependencies)?;
+        let declaration_order: Vec<&str> = all_accounts.iter().map(|(_, _, a)| a.unique_name.as_str()).collect();
+        let dependencies =
+            acyclic_dependencies(&dependencies, &private_imports, &declaration_order).map_err(|err| match err {
+                NatsForgeError::CircularDependency { cycle } => {
+                    let display_name = |unique: &String| {
+                        all_accounts
+                            .iter()
+                            .find(|(_, _, a)| &a.unique_name == unique)
+                            .map(|(_, _, a)| a.name.clone())
+                            .unwrap_or_else(|| unique.clone())
+                    };
+                    NatsForgeError::CircularDependency {
+                        cycle: cycle.iter().map(display_name).collect(),
+                    }
+                }
+                other => other,
+            })?;
 
-        for account_unique_name in &sorted_accounts {
-            if let Some((server_idx, _, account)) = all_accounts
+        // With exact names, accounts left in the store by an earlier run are reused as they are:
+        // same keys and JWT, with their users issued again.
+        let mut reused = HashSet::new();
+        if exact {
+            for (server_idx, _, account) in &all_accounts {
+                if account.is_system_account {
+                    continue;
+                }
+                let operator = account_operator(*server_idx);
+                if !self
+                    .backend
+                    .account_exists(account, &operator.name, &self.operator_store_dir(operator)?)
+                    .await?
+                {
+                    continue;
+                }
+                if !operator.reuse_existing {
+                    return Err(NatsForgeError::AlreadyInStore {
+                        kind: "account",
+                        name: account.unique_name.clone(),
+                    });
+                }
+                reused.insert(account.unique_name.clone());
+            }
+        }
+
+        // Accounts in a layer only import from earlier layers, so each layer can be issued
+        // concurrently. `buffered` keeps results in config order, which keeps the output stable.
+        let concurrency = self.config.concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+        for layer in dependency_layers(&dependencies) {
+            let layer_accounts: Vec<(usize, &AccountConfig)> = all_accounts
                 .iter()
-                .find(|(_, _, a)| a.unique_name == *account_unique_name)
-            {
+                .filter(|(_, _, a)| layer.contains(&a.unique_name))
+                .map(|(server_idx, _, a)| (*server_idx, *a))
+                .collect();
+
+            let phase_start = Instant::now();
+            let created: Vec<String> = stream::iter(layer_accounts.iter().map(|(server_idx, account)| {
+                let operator = account_operator(*server_idx);
+                let system_jwt = &operators[&operator.name].system_jwt;
+                let (reused, timings) = (&reused, &timings);
+                async move {
+                    let operator_store_dir = self.operator_store_dir(operator)?;
+                    if account.is_system_account {
+                        Ok(system_jwt.clone())
+                    } else if reused.contains(&account.unique_name) {
+                        self.backend
+                            .account_jwt(account, &operator.name, &operator_store_dir)
+                            .await
+                    } else {
+                        progress.checkpoint().await?;
+                        let jwt = timings
+                            .timed(
+                                PHASE_ACCOUNTS,
+                                &account.name,
+                                self.backend
+                                    .create_account(account, &operator.name, &operator_store_dir),
+                            )
+                            .await?;
+                        progress
+                            .emit(SetupEvent::AccountCreated {
+                                name: account.name.clone(),
+                            })
+                            .await;
+                        Ok(jwt)
+                    }
+                }
+            }))
+            .buffered(concurrency)
+            .try_collect()
+            .await?;
+            timings.record(PHASE_ACCOUNTS, phase_start.elapsed());
+
+            let phase_start = Instant::now();
+            let layer_users = layer_accounts
+                .iter()
+                .flat_map(|(server_idx, account)| account.users.iter().map(move |user| (*server_idx, *account, user)));
+            let issued: Vec<String> = stream::iter(layer_users.map(|(server_idx, account, user)| {
+                let effective_user = expansion.apply(account, user);
+                let reissue = reused.contains(&account.unique_name);
+                let operator = account_operator(server_idx);
+                let timings = &timings;
+                async move {
+                    let operator_store_dir = self.operator_store_dir(operator)?;
+                    progress.checkpoint().await?;
+                    if reissue {
+                        // The earlier run may not have issued this user yet.
+                        let _ = self.backend.delete_user(account, &user.name, &operator_store_dir).await;
+                    }
+                    let creds = timings
+                        .timed(
+                            PHASE_USERS,
+                            format!("{}/{}", account.name, user.name),
+                            self.backend.create_user(account, &effective_user, &operator_store_dir),
+                        )
+                        .await?;
+                    progress
+                        .emit(SetupEvent::UserCreated {
+                            account: account.name.clone(),
+                            user: user.name.clone(),
+                        })
+                        .await;
+                    Ok::<_, NatsForgeError>(creds)
+                }
+            }))
+            .buffered(concurrency)
+            .try_collect()
+            .await?;
+            timings.record(PHASE_USERS, phase_start.elapsed());
+
+            let mut issued = issued.into_iter();
+            for ((server_idx, account), account_jwt) in layer_accounts.iter().zip(created) {
                 let server = &self.config.servers[*server_idx];
-                let abs_output_dir = std::fs::canonicalize(&server.output_dir)?;
-                std::fs::create_dir_all(&abs_output_dir)?;
+                let abs_output_dir = output_dirs.get(server);
 
-                let account_jwt = if account.name == "SYS" && account.is_system_account {
-                    default_sys_jwt.clone()
-                } else {
-                    create_account(account, &self.config.operator.name, self.store_dir.path()).await?
-                };
                 let account_jwt_path = abs_output_dir.join(format!("{}.jwt", account.name));
-                std::fs::write(&account_jwt_path, &account_jwt)?;
+                timings
+                    .time(PHASE_WRITES, || writer.write(&account_jwt_path, &account_jwt))
+                    .map_err(NatsForgeError::io(&account_jwt_path))?;
                 account_jwt_paths.push(account_jwt_path.clone());
-                account_jwts.insert(account.name.clone(), account_jwt);
-
-                for user in &account.users {
-                    let creds_path = create_user(account, user, &abs_output_dir, self.store_dir.path()).await?;
-                    let filename = creds_path.file_name().unwrap().to_string_lossy().to_string();
-                    creds_map
-                        .entry(filename.clone())
-                        .or_insert_with(Vec::new)
-                        .push((creds_path.clone(), server.output_dir.clone()));
+                let first_defined = self.first_defines(server, account);
+                let artifacts = server_accounts
+                    .entry(server.name.clone())
+                    .or_default()
+                    .entry(account.name.clone())
+                    .or_insert(AccountArtifacts {
+                        id: extract_account_id(&account_jwt)?,
+                        jwt_path: account_jwt_path.clone(),
+                        signing_keys: nsc::extract_signing_keys(&account_jwt)?,
+                        creds: HashMap::new(),
+                        bearer_jwts: HashMap::new(),
+                        activations: HashMap::new(),
+                    });
+                account_jwts.insert(account.unique_name.clone(), account_jwt);
+
+                for (user, creds) in account.users.iter().zip(issued.by_ref()) {
+                    let filename = format!("{}-{}.creds", account.name, user.name);
+                    let creds_path = timings.time(PHASE_WRITES, || {
+                        write_credential(&writer, encrypter.as_ref(), &abs_output_dir.join(&filename), &creds)
+                    })?;
+                    if !in_memory {
+                        eprintln!("Wrote creds for {} to {}", user.name, creds_path.display());
+                    }
+                    if user.bearer {
+                        let jwt = backend::creds_jwt(&creds).ok_or_else(|| {
+                            NatsForgeError::Backend(format!("No JWT in the creds of user {}", user.name))
+                        })?;
+                        let jwt_path = abs_output_dir.join(format!("{}-{}.jwt", account.name, user.name));
+                        let jwt_path = timings.time(PHASE_WRITES, || {
+                            write_credential(&writer, encrypter.as_ref(), &jwt_path, jwt)
+                        })?;
+                        if first_defined {
+                            bearer_jwts
+                                .entry(account.name.clone())
+                                .or_default()
+                                .insert(user.name.clone(), jwt.to_string());
+                        }
+                        artifacts.bearer_jwts.insert(user.name.clone(), jwt_path);
+                    }
+                    if first_defined {
+                        creds_contents
+                            .entry(account.name.clone())
+                            .or_default()
+                            .insert(user.name.clone(), creds.clone());
+                    }
+                    creds_map.insert(
+                        (server.name.clone(), account.unique_name.clone(), user.name.clone()),
+                        (account.name.clone(), creds),
+                    );
+                    artifacts.creds.insert(user.name.clone(), creds_path.clone());
                     user_creds_paths.push(creds_path);
                 }
             }
         }
 
-        for (_, _, account) in &all_accounts {
-            for (i, import) in account.imports.iter().enumerate() {
+        let phase_start = Instant::now();
+        for (server_idx, _, account) in &all_accounts {
+            // A reused account still has the imports it was given when it was created.
+            let imports = if reused.contains(&account.unique_name) {
+                &[][..]
+            } else {
+                &account.imports[..]
+            };
+            let operator_store_dir = self.operator_store_dir(account_operator(*server_idx))?;
+            for (i, import) in imports.iter().enumerate() {
                 let import_name = format!("import-{}", i);
-                let src_unique_name = name_to_unique
-                    .get(&import.account)
-                    .ok_or_else(|| anyhow::anyhow!("Unknown import account: {}", import.account))?;
-                let mut import_args = vec![
-                    "add".to_string(),
-                    "import".to_string(),
-                    "--name".to_string(),
-                    import_name,
-                    "--src-account".to_string(),
-                    src_unique_name.clone(),
-                    "--remote-subject".to_string(),
-                    import.subject.clone(),
-                    "--account".to_string(),
-                    account.unique_name.clone(),
-                    "--data-dir".to_string(),
-                    self.store_dir.path().to_str().unwrap().to_string(),
-                ];
-                if let Some(local_subject) = &import.local_subject {
-                    import_args.push("--local-subject".to_string());
-                    import_args.push(local_subject.clone());
-                }
-                if import.service {
-                    import_args.push("--service".to_string());
+                let server = &self.config.servers[*server_idx];
+                let (_, exporter) = self
+                    .config
+                    .import_exporter(server, &import.account)
+                    .ok_or_else(|| self.config.unknown_import(server, &account.name, &import.account))?;
+                let src_unique_name = &exporter.unique_name;
+
+                progress.checkpoint().await?;
+                // Every account exists by now, so the importer's public key is known.
+                let private = exporter.private_export(import).is_some();
+                let artifacts = server_accounts
+                    .get_mut(&server.name)
+                    .and_then(|accounts| accounts.get_mut(&account.name));
+                let activation = match artifacts.filter(|_| private) {
+                    Some(artifacts) => {
+                        let token = self
+                            .backend
+                            .generate_activation(import, src_unique_name, &artifacts.id, &operator_store_dir)
+                            .await?;
+                        let abs_output_dir = output_dirs.get(&self.config.servers[*server_idx]);
+                        let token_path =
+                            abs_output_dir.join(format!("{}-{}.activation.jwt", account.name, import_name));
+                        // Part of the imports phase; timing it as a write too would count it twice.
+                        writer
+                            .write(&token_path, &token)
+                            .map_err(NatsForgeError::io(&token_path))?;
+                        artifacts.activations.insert(import.subject.clone(), token_path);
+                        if self.first_defines(server, account) {
+                            activations
+                                .entry(account.name.clone())
+                                .or_default()
+                                .insert(import.subject.clone(), token.clone());
+                        }
+                        Some(token)
+                    }
+                    None => None,
+                };
+                timings
+                    .timed(
+                        PHASE_IMPORTS,
+                        format!("{}: {} from {}", account.name, import.subject, exporter.name),
+                        self.backend.add_import(
+                            account,
+                            &import_name,
+                            import,
+                            src_unique_name,
+                            activation.as_deref(),
+                            &operator_store_dir,
+                        ),
+                    )
+                    .await?;
+                progress
+                    .emit(SetupEvent::ImportAdded {
+                        account: account.name.clone(),
+                        from: exporter.name.clone(),
+                        subject: import.subject.clone(),
+                    })
+                    .await;
+            }
+            for user in &account.revocations {
+                progress.checkpoint().await?;
+                self.backend.revoke_user(account, user, &operator_store_dir).await?;
+            }
+        }
+
+        // Imports and revocations are added after the accounts were first issued, so pick up the
+        // updated JWTs.
+        for (server_idx, _, account) in &all_accounts {
+            if account_jwts.contains_key(&account.unique_name) {
+                let operator = account_operator(*server_idx);
+                let account_jwt = self
+                    .backend
+                    .account_jwt(account, &operator.name, &self.operator_store_dir(operator)?)
+                    .await?;
+                account_jwts.insert(account.unique_name.clone(), account_jwt);
+            }
+        }
+        timings.record(PHASE_IMPORTS, phase_start.elapsed());
+
+        // The system account belongs to the operator, so its user is issued once per operator and
+        // valid on every server trusting it. The in-memory creds are the default operator's.
+        let mut sys_creds = HashMap::new();
+        let mut sys_creds_paths = HashMap::new();
+        if let Some(user) = &self.config.system_user {
+            let sys_account = system_account(&self.config);
+            for operator in self.config.all_operators() {
+                let phase_start = Instant::now();
+                let operator_store_dir = self.operator_store_dir(operator)?;
+                progress.checkpoint().await?;
+                if operator.reuse_existing {
+                    // An earlier run may have issued it already.
+                    let _ = self
+                        .backend
+                        .delete_user(&sys_account, &user.name, &operator_store_dir)
+                        .await;
                 }
-                let import_output = Command::new("nsc")
-                    .args(&import_args)
-                    .output()
-                    .await
-                    .context(format!("Failed to add import {}", import.subject))?;
-                if !import_output.status.success() {
-                    return Err(anyhow::anyhow!(
-                        "nsc add import failed: {}",
-                        String::from_utf8_lossy(&import_output.stderr)
-                    ));
+                let creds = timings
+                    .timed(
+                        PHASE_USERS,
+                        format!("{}/{}", sys_account.name, user.name),
+                        self.backend.create_user(&sys_account, user, &operator_store_dir),
+                    )
+                    .await?;
+                progress
+                    .emit(SetupEvent::UserCreated {
+                        account: sys_account.name.clone(),
+                        user: user.name.clone(),
+                    })
+                    .await;
+                timings.record(PHASE_USERS, phase_start.elapsed());
+                let filename = format!("{}-{}.creds", sys_account.name, user.name);
+                let trusting = self
+                    .config
+                    .servers
+                    .iter()
+                    .filter(|server| self.config.server_operator(server).name == operator.name);
+                for server in trusting {
+                    let creds_path = output_dirs.get(server).join(&filename);
+                    let creds_path = timings.time(PHASE_WRITES, || {
+                        write_credential(&writer, encrypter.as_ref(), &creds_path, &creds)
+                    })?;
+                    if !in_memory {
+                        eprintln!("Wrote creds for {} to {}", user.name, creds_path.display());
+                    }
+                    sys_creds_paths.insert(server.name.clone(), creds_path);
+                    sys_creds.insert(server.name.clone(), creds.clone());
                 }
+                creds_contents
+                    .entry(sys_account.name.clone())
+                    .or_default()
+                    .entry(user.name.clone())
+                    .or_insert(creds);
             }
         }
 
         for server in &self.config.servers {
-            let abs_output_dir = std::fs::canonicalize(&server.output_dir)?;
+            let abs_output_dir = output_dirs.get(server);
             for remote in &server.leafnodes.remotes {
-                if let Some(creds_entries) = creds_map.get(&remote.credentials) {
-                    let (source_path, _) = creds_entries
-                        .iter()
-                        .find(|(path, _)| path.exists())
-                        .ok_or_else(|| anyhow::anyhow!("No existing creds file for {}", remote.credentials))?;
+                let source_content = remote_creds(&creds_map, server, remote)?;
+                let filename = remote.credentials_file();
+                let abs_dest = abs_output_dir.join(&filename);
+                timings.time(PHASE_WRITES, || {
+                    write_credential(&writer, encrypter.as_ref(), &abs_dest, source_content)
+                })?;
+                // The rendered remote refers to the creds relative to the configured output_dir.
+                server_files
+                    .entry(server.name.clone())
+                    .or_default()
+                    .insert(server.output_dir.join(&filename), source_content.clone());
+            }
 
-                    let source_content = std::fs::read_to_string(source_path)?;
-                    let abs_dest = abs_output_dir.join(&remote.credentials);
-                    std::fs::write(&abs_dest, &source_content)?;
+            let scope = self.config.scoped_accounts(server);
+            for (account_name, account_jwt) in named_account_jwts(&self.config, server, &account_jwts) {
+                if scope.as_ref().is_some_and(|scope| !scope.contains(account_name)) {
+                    continue;
+                }
+                let dest_jwt_path = abs_output_dir.join(format!("{}.jwt", account_name));
+                timings
+                    .time(PHASE_WRITES, || writer.write(&dest_jwt_path, account_jwt))
+                    .map_err(NatsForgeError::io(&dest_jwt_path))?;
+            }
+            for (account_name, account_jwt) in &external_accounts[&server.name] {
+                let dest_jwt_path = abs_output_dir.join(format!("{}.jwt", account_name));
+                timings
+                    .time(PHASE_WRITES, || writer.write(&dest_jwt_path, account_jwt))
+                    .map_err(NatsForgeError::io(&dest_jwt_path))?;
+            }
+        }
+
+        if let Some(sink) = &self.secret_sink {
+            let phase_start = Instant::now();
+            for server in &self.config.servers {
+                for account in &server.accounts {
+                    if let Some(account_jwt) = account_jwts.get(&account.unique_name) {
+                        let key = secrets::account_jwt_key(&server.name, &account.name);
+                        sink.store(&key, account_jwt.as_bytes()).await?;
+                    }
+                    for user in &account.users {
+                        let issued_key = (server.name.clone(), account.unique_name.clone(), user.name.clone());
+                        if let Some((_, creds)) = creds_map.get(&issued_key) {
+                            let key = secrets::creds_key(&server.name, &account.name, &user.name);
+                            sink.store(&key, creds.as_bytes()).await?;
+                        }
+                    }
+                }
+                if let (Some(user), Some(creds)) = (&self.config.system_user, sys_creds.get(&server.name)) {
+                    let key = secrets::creds_key(&server.name, self.config.system_account_name(), &user.name);
+                    sink.store(&key, creds.as_bytes()).await?;
+                }
+            }
+            timings.record(PHASE_WRITES, phase_start.elapsed());
+        }
+
+        let jetstream_store_dirs = self.jetstream_store_dirs(in_memory)?;
+        let mut operator_files = HashMap::new();
+        for server in self.config.servers.iter() {
+            if server.operator_reference == OperatorRef::File {
+                operator_files.insert(server.name.clone(), output_dirs.get(server).join("operator.jwt"));
+                server_files.entry(server.name.clone()).or_default().insert(
+                    server.output_dir.join("operator.jwt"),
+                    server_operator(server).jwt.clone(),
+                );
+            }
+        }
+        let issued = Issued {
+            operators,
+            account_jwts,
+            creds: by_account_name(&self.config, &server_accounts)
+                .into_iter()
+                .map(|(name, artifacts)| (name, artifacts.creds))
+                .collect(),
+            added_users: Vec::new(),
+            jetstream_store_dirs,
+            operator_jwt_paths: operator_files,
+            conf_credentials: HashMap::new(),
+            callout_keys: HashMap::new(),
+            external_accounts,
+            output_dirs: output_dirs.clone(),
+        };
+        let mut resolver_dirs = HashMap::new();
+        for server in &self.config.servers {
+            let abs_output_dir = output_dirs.get(server);
+            if let Some(resolver_dir) = server.resolver_dir() {
+                let abs_resolver_dir = if in_memory {
+                    resolver_dir.clone()
                 } else {
-                    return Err(anyhow::anyhow!("No creds entry found for {}", remote.credentials));
+                    create_output_dir(&resolver_dir, self.config.private_output_dirs)
+                        .map_err(NatsForgeError::io(&resolver_dir))?;
+                    std::fs::canonicalize(&resolver_dir).map_err(NatsForgeError::io(&resolver_dir))?
+                };
+                for (path, account_jwt) in resolver_files(&self.config, &resolver_dir, server, &issued)? {
+                    let abs_path = abs_resolver_dir.join(path.file_name().unwrap());
+                    timings
+                        .time(PHASE_WRITES, || writer.write(&abs_path, &account_jwt))
+                        .map_err(NatsForgeError::io(&abs_path))?;
+                    server_files
+                        .entry(server.name.clone())
+                        .or_default()
+                        .insert(path, account_jwt);
                 }
+                resolver_dirs.insert(server.name.clone(), abs_resolver_dir);
             }
+            let (server_config_path, server_config) =
+                self.write_server_config(server, &issued, abs_output_dir, &writer, &timings, &mut server_files)?;
+            progress
+                .emit(SetupEvent::ServerConfigWritten {
+                    server: server.name.clone(),
+                })
+                .await;
+            servers.insert(server.name.clone(), server_config_path.clone());
+            server_configs.insert(server.name.clone(), server_config);
+            server_config_paths.push(server_config_path);
+        }
 
-            for (account_name, account_jwt) in &account_jwts {
-                let dest_jwt_path = abs_output_dir.join(format!("{}.jwt", account_name));
-                std::fs::write(&dest_jwt_path, account_jwt)?;
+        let mut pushed = HashSet::new();
+        for server in self.config.servers.iter().filter(|server| server.push_accounts) {
+            let ResolverType::Url(url) = &server.resolver else {
+                continue;
+            };
+            if pushed.insert(url) {
+                progress.checkpoint().await?;
+                let report = self.push_to(self.config.server_operator(server), url).await?;
+                let failure = report.failures().next().cloned();
+                if let Some(failure) = failure {
+                    return Err(NatsForgeError::Backend(format!(
+                        "{} rejected account {}: {}",
+                        url,
+                        failure.account,
+                        failure.error.as_deref().unwrap_or_default()
+                    )));
+                }
             }
         }
 
+        let account_artifacts = by_account_name(&self.config, &server_accounts);
+        let mut account_ids: HashMap<String, String> = account_artifacts
+            .iter()
+            .map(|(name, artifacts)| (name.clone(), artifacts.id.clone()))
+            .collect();
+        let default_operator = &issued.operators[&self.config.default_operator().name];
+        account_ids
+            .entry(self.config.system_account_name().to_string())
+            .or_insert(default_operator.system_id.clone());
+        for (account_name, account_jwt) in issued.external_accounts.values().flatten() {
+            account_ids.insert(account_name.clone(), extract_account_id(account_jwt)?);
+        }
+
+        let artifacts = in_memory.then(|| SetupArtifacts {
+            operator_jwt: default_operator.jwt.clone(),
+            operator_jwts: issued
+                .operators
+                .iter()
+                .map(|(name, operator)| (name.clone(), operator.jwt.clone()))
+                .collect(),
+            account_jwts: account_artifacts
+                .keys()
+                .filter_map(|name| {
+                    let (_, account) = self.defining_server(name).ok()?;
+                    Some((name.clone(), issued.account_jwts.get(&account.unique_name)?.clone()))
+                })
+                .collect(),
+            creds: creds_contents,
+            server_creds: server_creds(&creds_map),
+            bearer_jwts,
+            activations,
+            server_configs,
+            server_files,
+            user_secrets: HashMap::new(),
+        });
+
+        let jetstream_store_dirs = issued.jetstream_store_dirs.clone();
+        *self.issued.lock().expect("issued JWTs poisoned") = Some(issued);
+
+        #[allow(deprecated)]
+        let result = SetupResult {
+            operator_jwt_path: operator_jwt_paths[0].clone(),
+            operator_jwt_paths,
+            account_jwt_paths,
+            user_creds_paths,
+            server_config_path: self.config.servers[0].output_dir.join("nats.conf"),
+            server_config_paths: Some(server_config_paths),
+            account_jwts: account_artifacts,
+            server_accounts,
+            servers,
+            resolver_dirs,
+            jetstream_store_dirs,
+            store_dir: store_dir.to_path_buf(),
+            graph: self.config.account_graph().with_ids(&account_ids),
+            account_ids,
+            connections: self.config.servers.iter().map(ConnectionInfo::for_server).collect(),
+            timings: timings.finish(
+                started.elapsed(),
+                self.backend.process_times(),
+                self.backend.step_times(),
+            ),
+            warnings,
+            artifacts,
+            creds_encrypted: encrypter.is_some(),
+            sys_creds_paths,
+            system_account: self.config.system_account_name().to_string(),
+            user_secrets: HashMap::new(),
+            auth_callout_keys: HashMap::new(),
+        };
+        self.finish_setup(&writer, result).await
+    }
+
+    /// Sets up conf-mode servers, which need neither the backend nor an operator: every user gets a
+    /// generated nkey, password or token, written next to `nats.conf` in place of `.creds`.
+    async fn initialize_conf(
+        &self,
+        started: Instant,
+        timings: TimingRecorder,
+        writer: ArtifactWriter<'_>,
+        encrypter: Option<&Encrypter>,
+        warnings: Vec<String>,
+        progress: &Progress,
+    ) -> Result<SetupResult> {
+        let in_memory = self.config.artifact_mode == ArtifactMode::InMemory;
+        let output_dirs = OutputDirs::prepare(&self.config)?;
+
+        let phase_start = Instant::now();
+        let conf_credentials = self
+            .config
+            .servers
+            .iter()
+            .map(|server| Ok((server.name.clone(), conf_auth::issue(server)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let callout_keys: HashMap<String, conf_auth::CalloutKeys> = self
+            .config
+            .servers
+            .iter()
+            .filter_map(|server| Some((server.name.clone(), conf_auth::CalloutKeys::generate(server)?)))
+            .collect();
+        timings.record(PHASE_USERS, phase_start.elapsed());
+
+        let mut user_secrets: HashMap<String, HashMap<String, PathBuf>> = HashMap::new();
+        let mut secret_contents: HashMap<String, HashMap<String, String>> = HashMap::new();
         for server in &self.config.servers {
-            let abs_output_dir = std::fs::canonicalize(&server.output_dir)?;
-            let mut resolver_preload = Vec::new();
-            let mut system_account_id = None;
-
-            for (account_name, account_jwt) in &account_jwts {
-                let account_id = extract_account_id(account_jwt)?;
-                if account_name == "SYS" && server.accounts.iter().any(|a| a.name == "SYS" && a.is_system_account) {
-                    system_account_id = Some(account_id.clone());
+            let abs_output_dir = output_dirs.get(server);
+            for (account, users) in &conf_credentials[&server.name] {
+                for (user, credential) in users {
+                    let path = abs_output_dir.join(credential.file_name(account, user));
+                    let path = timings.time(PHASE_WRITES, || {
+                        write_credential(&writer, encrypter, &path, credential.secret())
+                    })?;
+                    if !in_memory {
+                        eprintln!("Wrote the secret of {} to {}", user, path.display());
+                    }
+                    if let Some(sink) = &self.secret_sink {
+                        let key = secrets::user_secret_key(&server.name, account, user, credential.extension());
+                        sink.store(&key, credential.secret().as_bytes()).await?;
+                    }
+                    user_secrets
+                        .entry(account.clone())
+                        .or_default()
+                        .insert(user.clone(), path);
+                    secret_contents
+                        .entry(account.clone())
+                        .or_default()
+                        .insert(user.clone(), credential.secret().to_string());
                 }
-                resolver_preload.push(format!("    {}: \"{}\"", account_id, account_jwt));
             }
+        }
 
-            let system_account_id = system_account_id.unwrap_or(default_sys_id.clone());
-            if !resolver_preload.iter().any(|entry| entry.contains(&default_sys_id)) {
-                resolver_preload.push(format!("    {}: \"{}\"", default_sys_id, default_sys_jwt));
+        let mut server_files: HashMap<String, HashMap<PathBuf, String>> = HashMap::new();
+        let mut auth_callout_keys = HashMap::new();
+        for server in &self.config.servers {
+            let Some(keys) = callout_keys.get(&server.name) else {
+                continue;
+            };
+            let abs_output_dir = output_dirs.get(server);
+            let mut paths = HashMap::new();
+            for (file_name, seed) in keys.seed_files()? {
+                let path = abs_output_dir.join(file_name);
+                let path = timings.time(PHASE_WRITES, || write_credential(&writer, encrypter, &path, &seed))?;
+                if !in_memory {
+                    eprintln!("Wrote the auth callout seed {} to {}", file_name, path.display());
+                }
+                if let Some(sink) = &self.secret_sink {
+                    sink.store(&secrets::server_secret_key(&server.name, file_name), seed.as_bytes())
+                        .await?;
+                }
+                if in_memory {
+                    server_files
+                        .entry(server.name.clone())
+                        .or_default()
+                        .insert(path.clone(), seed);
+                }
+                paths.insert(file_name, path);
             }
+            auth_callout_keys.insert(
+                server.name.clone(),
+                AuthCalloutKeys {
+                    issuer_seed: paths.remove(conf_auth::CalloutKeys::ISSUER_FILE).unwrap_or_default(),
+                    xkey_seed: paths.remove(conf_auth::CalloutKeys::XKEY_FILE),
+                },
+            );
+        }
 
-            let server_config = generate_server_config(
+        let issued = Issued {
+            operators: HashMap::new(),
+            account_jwts: HashMap::new(),
+            creds: HashMap::new(),
+            added_users: Vec::new(),
+            jetstream_store_dirs: self.jetstream_store_dirs(in_memory)?,
+            operator_jwt_paths: HashMap::new(),
+            conf_credentials,
+            callout_keys,
+            external_accounts: HashMap::new(),
+            output_dirs: output_dirs.clone(),
+        };
+        let mut servers = HashMap::new();
+        let mut server_configs = HashMap::new();
+        let mut server_config_paths = Vec::new();
+        for server in &self.config.servers {
+            let (server_config_path, server_config) = self.write_server_config(
                 server,
-                &operator_jwt,
-                &system_account_id,
-                &resolver_preload.join("\n"),
-                &account_jwts,
+                &issued,
+                output_dirs.get(server),
+                &writer,
+                &timings,
+                &mut server_files,
+            )?;
+            progress
+                .emit(SetupEvent::ServerConfigWritten {
+                    server: server.name.clone(),
+                })
+                .await;
+            servers.insert(server.name.clone(), server_config_path.clone());
+            server_configs.insert(server.name.clone(), server_config);
+            server_config_paths.push(server_config_path);
+        }
+
+        let artifacts = in_memory.then(|| SetupArtifacts {
+            server_configs,
+            server_files,
+            user_secrets: secret_contents,
+            ..Default::default()
+        });
+        let jetstream_store_dirs = issued.jetstream_store_dirs.clone();
+        *self.issued.lock().expect("issued JWTs poisoned") = Some(issued);
+
+        #[allow(deprecated)]
+        let result = SetupResult {
+            operator_jwt_path: PathBuf::new(),
+            operator_jwt_paths: Vec::new(),
+            account_jwt_paths: Vec::new(),
+            user_creds_paths: Vec::new(),
+            server_config_path: self.config.servers[0].output_dir.join("nats.conf"),
+            server_config_paths: Some(server_config_paths),
+            account_jwts: HashMap::new(),
+            server_accounts: HashMap::new(),
+            servers,
+            resolver_dirs: HashMap::new(),
+            jetstream_store_dirs,
+            store_dir: self.store_dir()?.to_path_buf(),
+            account_ids: HashMap::new(),
+            graph: self.config.account_graph(),
+            connections: self.config.servers.iter().map(ConnectionInfo::for_server).collect(),
+            timings: timings.finish(
+                started.elapsed(),
+                self.backend.process_times(),
+                self.backend.step_times(),
+            ),
+            warnings,
+            artifacts,
+            creds_encrypted: encrypter.is_some(),
+            sys_creds_paths: HashMap::new(),
+            system_account: self.config.system_account_name().to_string(),
+            user_secrets,
+            auth_callout_keys,
+        };
+        self.finish_setup(&writer, result).await
+    }
+
+    /// The JetStream store directory of every server with JetStream enabled, by server name. With
+    /// `absolute_paths` it is created and made absolute, unless nothing is written.
+    fn jetstream_store_dirs(&self, in_memory: bool) -> Result<HashMap<String, PathBuf>> {
+        let mut jetstream_store_dirs = HashMap::new();
+        for server in self.config.servers.iter().filter(|server| server.jetstream.enabled) {
+            let mut store_dir = server.jetstream_store_dir();
+            if server.jetstream.absolute_paths && !in_memory {
+                create_output_dir(&store_dir, self.config.private_output_dirs)
+                    .map_err(NatsForgeError::io(&store_dir))?;
+                store_dir = std::fs::canonicalize(&store_dir).map_err(NatsForgeError::io(&store_dir))?;
+            }
+            jetstream_store_dirs.insert(server.name.clone(), store_dir);
+        }
+        Ok(jetstream_store_dirs)
+    }
+
+    /// Writes the dev TLS certificates, split includes and `nats.conf` of `server` to
+    /// `abs_output_dir`, adding them to `files` under the paths the config expects them at.
+    /// Returns the path and text of `nats.conf`.
+    fn write_server_config(
+        &self,
+        server: &ServerConfig,
+        issued: &Issued,
+        abs_output_dir: &Path,
+        writer: &ArtifactWriter,
+        timings: &TimingRecorder,
+        server_files: &mut HashMap<String, HashMap<PathBuf, String>>,
+    ) -> Result<(PathBuf, String)> {
+        if server.tls.as_ref().is_some_and(|tls| tls.dev) {
+            for file in tls::dev_cert_files(&server.dev_cert_hosts())? {
+                let abs_path = abs_output_dir.join(file.name);
+                timings
+                    .time(PHASE_WRITES, || {
+                        if file.private {
+                            writer.write_private(&abs_path, &file.contents)
+                        } else {
+                            writer.write(&abs_path, &file.contents)
+                        }
+                    })
+                    .map_err(NatsForgeError::io(&abs_path))?;
+                server_files
+                    .entry(server.name.clone())
+                    .or_default()
+                    .insert(server.output_dir.join(file.name), file.contents);
+            }
+        }
+        let (server_config, includes) = timings.time(PHASE_RENDER, || {
+            render_split_server_config(&self.config, server, issued)
+        })?;
+        for (file_name, contents) in includes {
+            let abs_path = abs_output_dir.join(&file_name);
+            timings
+                .time(PHASE_WRITES, || writer.write(&abs_path, &contents))
+                .map_err(NatsForgeError::io(&abs_path))?;
+            server_files
+                .entry(server.name.clone())
+                .or_default()
+                .insert(server.output_dir.join(file_name), contents);
+        }
+        let server_config_path = abs_output_dir.join("nats.conf");
+        timings
+            .time(PHASE_WRITES, || writer.write(&server_config_path, &server_config))
+            .map_err(NatsForgeError::io(&server_config_path))?;
+        Ok((server_config_path, server_config))
+    }
+
+    /// Writes the manifests and checks the written configs, as configured.
+    async fn finish_setup(&self, writer: &ArtifactWriter<'_>, mut result: SetupResult) -> Result<SetupResult> {
+        if self.config.write_manifest {
+            let manifest = result.manifest().to_json();
+            for server in &self.config.servers {
+                let manifest_path = result.servers[&server.name].with_file_name(MANIFEST_FILE);
+                writer
+                    .write(&manifest_path, &manifest)
+                    .map_err(NatsForgeError::io(&manifest_path))?;
+            }
+        }
+        if self.config.verify_configs != VerifyMode::Off {
+            let strict = self.config.verify_configs == VerifyMode::Required;
+            for warning in self.check(&result, strict).await? {
+                eprintln!("Warning: {}", warning);
+                result.warnings.push(warning);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Runs `nats-server -t` on every written `nats.conf`, which parses it the way starting the
+    /// server would, and fails with the output of the first server it rejects. When nats-server
+    /// can't be found the configs go unchecked with a warning, which is returned, unless `strict`
+    /// is set.
+    pub async fn check(&self, result: &SetupResult, strict: bool) -> Result<Vec<String>> {
+        self.check_writes_files("check")?;
+        let (program, resolution) = match &self.config.nats_server_path {
+            Some(path) => (
+                path.clone(),
+                format!("{} (configured via nats_server_path)", path.display()),
+            ),
+            None => (
+                PathBuf::from("nats-server"),
+                "nats-server (resolved from PATH)".to_string(),
+            ),
+        };
+        let runner = ProcessRunner::new(&program).with_env(ProcessEnv::from_config(&self.config));
+        for server in &self.config.servers {
+            let config_path = result
+                .server_config(&server.name)
+                .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown server: {}", server.name)))?;
+            let args = [OsStr::new("-t"), OsStr::new("-c"), config_path.as_os_str()];
+            let output = match runner.output(args).await {
+                Ok(output) => output,
+                Err(source) if source.kind() == std::io::ErrorKind::NotFound => {
+                    if strict {
+                        return Err(NatsForgeError::NatsServerNotFound { resolution, source });
+                    }
+                    return Ok(vec![format!(
+                        "nats-server not found at {}; the generated configs were not checked",
+                        resolution
+                    )]);
+                }
+                Err(source) => return Err(NatsForgeError::io(&program)(source)),
+            };
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+                let output = if stderr.is_empty() {
+                    String::from_utf8_lossy(&output.stdout).trim().to_string()
+                } else {
+                    stderr
+                };
+                return Err(NatsForgeError::ConfigCheckFailed {
+                    server: server.name.clone(),
+                    output,
+                });
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Removes what `initialize()` wrote for `result`: the operator, account and activation JWTs,
+    /// creds and user secrets, server configs and manifests, and the resolver and JetStream store
+    /// directories it created. With `remove_output_dirs` the servers' output directories go as
+    /// well, with anything else in them. Every path is checked first: if one lies outside the
+    /// servers' output directories, nothing is removed and [`NatsForgeError::OutsideOutputDirs`]
+    /// is returned. Directories a server is still writing to are retried a few times.
+    pub async fn cleanup(&self, result: &SetupResult, remove_output_dirs: bool) -> Result<CleanupReport> {
+        let mut report = CleanupReport::default();
+        // Nothing was written in memory.
+        if result.artifacts.is_some() {
+            return Ok(report);
+        }
+        let output_dirs: Vec<PathBuf> = self
+            .config
+            .servers
+            .iter()
+            .filter_map(|server| std::fs::canonicalize(&server.output_dir).ok())
+            .collect();
+        let mut paths = generated_paths(&self.config, result);
+        if remove_output_dirs {
+            paths.extend(self.config.servers.iter().map(|server| server.output_dir.clone()));
+        }
+
+        let mut targets: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            let Ok(canonical) = std::fs::canonicalize(&path) else {
+                report.missing.push(path);
+                continue;
+            };
+            let inside = output_dirs
+                .iter()
+                .any(|dir| canonical.starts_with(dir) && (canonical != *dir || remove_output_dirs));
+            if !inside {
+                return Err(NatsForgeError::OutsideOutputDirs { path });
+            }
+            if !targets.contains(&canonical) {
+                targets.push(canonical);
+            }
+        }
+        // Contents before the directories holding them, so each is removed exactly once.
+        targets.sort_by_key(|path| Reverse(path.components().count()));
+        for target in targets {
+            match remove_path(&target).await {
+                Ok(()) => report.removed.push(target),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => report.missing.push(target),
+                Err(err) => return Err(NatsForgeError::io(&target)(err)),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Compares what `initialize()` would generate for the current config with what an earlier run
+    /// left on disk, without writing anything: added and removed servers, accounts and users, and a
+    /// unified diff of every config file that would change. Each server's files are looked up at
+    /// `against_dir` joined with its `output_dir`, so `.` compares against the configured output
+    /// directories themselves. The preview is generated in memory, with throwaway keys from the
+    /// backend `config.backend` selects; [`DiffReport`] disregards the keys themselves.
+    pub async fn diff(&self, against_dir: &Path) -> Result<DiffReport> {
+        let mut config = self.config.clone();
+        config.artifact_mode = ArtifactMode::InMemory;
+        // Nothing of the preview may end up in a persistent store.
+        config.nsc_store_dir = None;
+        config.verify_configs = VerifyMode::Off;
+        let preview = NatsForge::from_config(config)?.initialize().await?;
+        let artifacts = preview
+            .artifacts
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("in-memory setup returned no artifacts".to_string()))?;
+        let generated = Snapshot::generated(&self.config, artifacts)?;
+        let on_disk = Snapshot::read(&self.config, against_dir)?;
+        let removed_servers = diff::removed_servers(&self.config, against_dir)?;
+        Ok(diff::compare(&on_disk, &generated, removed_servers))
+    }
+
+    /// Rewrites every server's `nats.conf` (and split includes) for the current config without
+    /// issuing anything: the operator and account JWTs are the ones `previous` left in the output
+    /// directories, and the resolver preload is rebuilt from them. Meant for server-level changes
+    /// such as ports, TLS paths or JetStream limits. Fails with [`NatsForgeError::InvalidConfig`]
+    /// when the config's servers or accounts no longer match `previous`; those need `initialize()`.
+    /// The returned result lists the same JWTs and creds as `previous`.
+    pub async fn regenerate_configs(&self, previous: &SetupResult) -> Result<SetupResult> {
+        let started = Instant::now();
+        self.check_writes_files("regenerate_configs")?;
+        if previous.artifacts.is_some() {
+            return Err(NatsForgeError::InvalidConfig(
+                "regenerate_configs reads the JWTs in output_dir, which the in-memory previous setup didn't write"
+                    .to_string(),
+            ));
+        }
+        if self
+            .config
+            .servers
+            .iter()
+            .any(|server| server.auth_mode == AuthMode::Conf)
+        {
+            return Err(NatsForgeError::InvalidConfig(
+                "regenerate_configs reuses issued JWTs, which conf-mode servers don't use".to_string(),
+            ));
+        }
+        self.config.validate()?;
+        let previous_dirs = self.previous_output_dirs(previous)?;
+        self.check_same_accounts(previous)?;
+
+        let mut operators = HashMap::new();
+        for operator in self.config.all_operators() {
+            let Some(server) = self
+                .config
+                .servers
+                .iter()
+                .find(|server| self.config.server_operator(server).name == operator.name)
+            else {
+                continue;
+            };
+            let previous_dir = &previous_dirs[&server.name];
+            let operator_jwt_path = previous_dir.join("operator.jwt");
+            let jwt = std::fs::read_to_string(&operator_jwt_path)
+                .map_err(NatsForgeError::io(&operator_jwt_path))?
+                .trim()
+                .to_string();
+            let system_id = jwt::decode_operator_claims(&jwt)?
+                .nats
+                .system_account
+                .ok_or_else(|| NatsForgeError::Jwt(format!("No system account in {}", operator_jwt_path.display())))?;
+            let system_jwt = self
+                .previous_system_account_jwt(operator, &system_id, previous, previous_dir)
+                .await?;
+            operators.insert(
+                operator.name.clone(),
+                IssuedOperator {
+                    jwt,
+                    system_id,
+                    system_jwt,
+                },
             );
-            let server_config_path = abs_output_dir.join("nats.conf");
-            std::fs::write(&server_config_path, &server_config)?;
+        }
+        let mut account_jwts = HashMap::new();
+        for server in &self.config.servers {
+            for account in &server.accounts {
+                let Some(artifacts) = previous.server_account(&server.name, &account.name) else {
+                    continue;
+                };
+                let account_jwt = std::fs::read_to_string(&artifacts.jwt_path)
+                    .map_err(NatsForgeError::io(&artifacts.jwt_path))?
+                    .trim()
+                    .to_string();
+                account_jwts.insert(account.unique_name.clone(), account_jwt);
+            }
+        }
+        let mut external_accounts = HashMap::new();
+        for server in &self.config.servers {
+            let operator_jwt = &operators[&self.config.server_operator(server).name].jwt;
+            external_accounts.insert(server.name.clone(), read_external_accounts(server, operator_jwt)?);
+        }
+
+        let output_dirs = OutputDirs::prepare(&self.config)?;
+        let operator_jwt_paths = self
+            .config
+            .servers
+            .iter()
+            .filter(|server| server.operator_reference == OperatorRef::File)
+            .map(|server| (server.name.clone(), output_dirs.get(server).join("operator.jwt")))
+            .collect();
+        let issued = Issued {
+            operators,
+            account_jwts,
+            creds: previous
+                .account_jwts
+                .iter()
+                .map(|(name, artifacts)| (name.clone(), artifacts.creds.clone()))
+                .collect(),
+            added_users: Vec::new(),
+            jetstream_store_dirs: self.jetstream_store_dirs(false)?,
+            operator_jwt_paths,
+            conf_credentials: HashMap::new(),
+            callout_keys: HashMap::new(),
+            external_accounts,
+            output_dirs: output_dirs.clone(),
+        };
+
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+        let timings = TimingRecorder::default();
+        let mut server_files = HashMap::new();
+        let mut servers = HashMap::new();
+        let mut server_config_paths = Vec::new();
+        for server in &self.config.servers {
+            let (server_config_path, _) = self.write_server_config(
+                server,
+                &issued,
+                output_dirs.get(server),
+                &writer,
+                &timings,
+                &mut server_files,
+            )?;
+            servers.insert(server.name.clone(), server_config_path.clone());
             server_config_paths.push(server_config_path);
         }
+        let jetstream_store_dirs = issued.jetstream_store_dirs.clone();
+        *self.issued.lock().expect("issued JWTs poisoned") = Some(issued);
 
-        Ok(SetupResult {
-            operator_jwt_path,
-            account_jwt_paths,
-            user_creds_paths,
+        #[allow(deprecated)]
+        let result = SetupResult {
+            operator_jwt_path: previous.operator_jwt_path.clone(),
+            operator_jwt_paths: previous.operator_jwt_paths.clone(),
+            account_jwt_paths: previous.account_jwt_paths.clone(),
+            user_creds_paths: previous.user_creds_paths.clone(),
             server_config_path: self.config.servers[0].output_dir.join("nats.conf"),
             server_config_paths: Some(server_config_paths),
+            account_jwts: previous.account_jwts.clone(),
+            server_accounts: previous.server_accounts.clone(),
+            servers,
+            resolver_dirs: previous.resolver_dirs.clone(),
+            jetstream_store_dirs,
+            store_dir: previous.store_dir.clone(),
+            graph: self.config.account_graph().with_ids(&previous.account_ids),
+            account_ids: previous.account_ids.clone(),
+            connections: self.config.servers.iter().map(ConnectionInfo::for_server).collect(),
+            timings: timings.finish(
+                started.elapsed(),
+                self.backend.process_times(),
+                self.backend.step_times(),
+            ),
+            warnings: self.config.warnings(),
+            artifacts: None,
+            creds_encrypted: previous.creds_encrypted,
+            sys_creds_paths: previous.sys_creds_paths.clone(),
+            system_account: previous.system_account.clone(),
+            user_secrets: HashMap::new(),
+            auth_callout_keys: HashMap::new(),
+        };
+        self.finish_setup(&writer, result).await
+    }
+
+    /// The output directory `previous` wrote each configured server's files to. Fails when a
+    /// server is new, or its `output_dir` now points somewhere else.
+    fn previous_output_dirs(&self, previous: &SetupResult) -> Result<HashMap<String, PathBuf>> {
+        let mut dirs = HashMap::new();
+        for server in &self.config.servers {
+            let previous_dir = previous
+                .servers
+                .get(&server.name)
+                .and_then(|server_config| server_config.parent())
+                .ok_or_else(|| {
+                    NatsForgeError::InvalidConfig(format!(
+                        "Server {} is not part of the previous setup; run initialize() to set it up",
+                        server.name
+                    ))
+                })?;
+            let moved = std::fs::canonicalize(&server.output_dir).map_or(true, |dir| dir != previous_dir);
+            if moved {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "The output_dir of server {} changed from {}; run initialize() to set it up there",
+                    server.name,
+                    previous_dir.display()
+                )));
+            }
+            dirs.insert(server.name.clone(), previous_dir.to_path_buf());
+        }
+        Ok(dirs)
+    }
+
+    /// Fails, naming the differences, unless the config defines exactly the accounts `previous`
+    /// issued.
+    fn check_same_accounts(&self, previous: &SetupResult) -> Result<()> {
+        let configured: BTreeSet<&str> = self
+            .config
+            .servers
+            .iter()
+            .flat_map(|server| &server.accounts)
+            .map(|account| account.name.as_str())
+            .collect();
+        let issued: BTreeSet<&str> = previous.account_jwts.keys().map(String::as_str).collect();
+        if configured == issued {
+            return Ok(());
+        }
+        let mut differences = Vec::new();
+        let added: Vec<&str> = configured.difference(&issued).copied().collect();
+        if !added.is_empty() {
+            differences.push(format!("not issued yet: {}", added.join(", ")));
+        }
+        let removed: Vec<&str> = issued.difference(&configured).copied().collect();
+        if !removed.is_empty() {
+            differences.push(format!("no longer configured: {}", removed.join(", ")));
+        }
+        Err(NatsForgeError::InvalidConfig(format!(
+            "The accounts in the config no longer match the previous setup ({}); run initialize() instead",
+            differences.join("; ")
+        )))
+    }
+
+    /// The JWT of the system account of `operator`: the configured system account's when it is
+    /// that account, else the entry in the resolver preload `previous` wrote, else the one in the
+    /// store.
+    async fn previous_system_account_jwt(
+        &self,
+        operator: &OperatorConfig,
+        system_id: &str,
+        previous: &SetupResult,
+        previous_dir: &Path,
+    ) -> Result<String> {
+        if let Some(artifacts) = previous
+            .account_jwts
+            .get(self.config.system_account_name())
+            .filter(|sys| sys.id == system_id)
+        {
+            return Ok(std::fs::read_to_string(&artifacts.jwt_path)
+                .map_err(NatsForgeError::io(&artifacts.jwt_path))?
+                .trim()
+                .to_string());
+        }
+        if let Some(jwt) = preloaded_jwt(previous_dir, system_id)? {
+            return Ok(jwt);
+        }
+        self.backend
+            .system_account_jwt(&operator.name, &self.operator_store_dir(operator)?)
+            .await
+            .ok()
+            .filter(|jwt| extract_account_id(jwt).is_ok_and(|id| id == system_id))
+            .ok_or_else(|| {
+                NatsForgeError::InvalidConfig(format!(
+                    "The JWT of system account {} is neither preloaded in {} nor in the store",
+                    system_id,
+                    previous_dir.display()
+                ))
+            })
+    }
+
+    /// Issues one more user for `account_name` after [`NatsForge::initialize`], without
+    /// regenerating anything else, and writes its `.creds` file (and for bearer users the raw JWT)
+    /// to the output directory of the server that defines the account. Returns the creds path,
+    /// which [`NatsForge::creds`] reports from then on.
+    pub async fn add_user(&self, account_name: &str, user: &UserConfig) -> Result<PathBuf> {
+        let (server, account) = self.defining_server(account_name)?;
+        self.check_operator_mode("add_user")?;
+        self.check_writes_files("add_user")?;
+        match &*self.issued.lock().expect("issued JWTs poisoned") {
+            None => {
+                return Err(NatsForgeError::Backend(
+                    "No account to add a user to yet; call initialize() first".to_string(),
+                ))
+            }
+            Some(issued) => {
+                if issued
+                    .creds
+                    .get(account_name)
+                    .is_some_and(|users| users.contains_key(&user.name))
+                {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Account {} already has a user {}",
+                        account_name, user.name
+                    )));
+                }
+            }
+        }
+
+        let creds = self
+            .backend
+            .create_user(account, user, &self.server_store_dir(server)?)
+            .await?;
+        let creds_path = self.write_user(server, account, user, &creds)?;
+        self.store_creds(server, account, user, &creds).await?;
+        if let Some(issued) = self.issued.lock().expect("issued JWTs poisoned").as_mut() {
+            issued
+                .creds
+                .entry(account_name.to_string())
+                .or_default()
+                .insert(user.name.clone(), creds_path.clone());
+            issued.added_users.push((account_name.to_string(), user.clone()));
+        }
+        Ok(creds_path)
+    }
+
+    /// The `.creds` file of `user` in `account`, including users added with
+    /// [`NatsForge::add_user`]. `None` before [`NatsForge::initialize`].
+    pub fn creds(&self, account: &str, user: &str) -> Option<PathBuf> {
+        let issued = self.issued.lock().expect("issued JWTs poisoned");
+        issued.as_ref()?.creds.get(account)?.get(user).cloned()
+    }
+
+    /// Reissues `user` of `account` with a new nkey, keeping its name and permissions, and replaces
+    /// its `.creds` file. With `revoke_old`, the old public key is also revoked in the account JWT
+    /// and the servers' files are rewritten as by [`NatsForge::revoke_user`], so copies of the old
+    /// creds stop working once the servers reload.
+    pub async fn rotate_user(&self, account_name: &str, user_name: &str, revoke_old: bool) -> Result<RotatedUser> {
+        let (server, account) = self.defining_server(account_name)?;
+        self.check_operator_mode("rotate_user")?;
+        self.check_writes_files("rotate_user")?;
+        // The old key is read from the old creds.
+        self.check_plaintext_creds("rotate_user")?;
+        let (old_creds_path, user) = match &*self.issued.lock().expect("issued JWTs poisoned") {
+            None => {
+                return Err(NatsForgeError::Backend(
+                    "No user to rotate yet; call initialize() first".to_string(),
+                ))
+            }
+            Some(issued) => {
+                let unknown =
+                    || NatsForgeError::InvalidConfig(format!("Account {} has no user {}", account_name, user_name));
+                let path = issued
+                    .creds
+                    .get(account_name)
+                    .and_then(|users| users.get(user_name))
+                    .cloned()
+                    .ok_or_else(unknown)?;
+                let added = issued
+                    .added_users
+                    .iter()
+                    .filter(|(account, _)| account == account_name)
+                    .map(|(_, user)| user);
+                let user = account
+                    .users
+                    .iter()
+                    .chain(added)
+                    .find(|u| u.name == user_name)
+                    .cloned()
+                    .ok_or_else(unknown)?;
+                (path, user)
+            }
+        };
+
+        let old_creds = std::fs::read_to_string(&old_creds_path).map_err(NatsForgeError::io(&old_creds_path))?;
+        let old_jwt = backend::creds_jwt(&old_creds)
+            .ok_or_else(|| NatsForgeError::Backend(format!("No JWT in the creds of user {}", user_name)))?;
+        let old_key = jwt::decode_user_claims(old_jwt)?.sub;
+
+        let store_dir = self.server_store_dir(server)?;
+        if revoke_old {
+            self.backend.revoke_user(account, &old_key, &store_dir).await?;
+        }
+        self.backend.delete_user(account, user_name, &store_dir).await?;
+        let effective_user = self.config.derive_grants().apply(account, &user);
+        let creds = self.backend.create_user(account, &effective_user, &store_dir).await?;
+        // The creds are replaced by rename, so a reader never sees a half-written file.
+        let creds_path = self.write_user(server, account, &user, &creds)?;
+        self.store_creds(server, account, &user, &creds).await?;
+        if revoke_old {
+            self.publish_account_jwt(account).await?;
+        }
+        Ok(RotatedUser {
+            creds_path,
+            revoked_key: revoke_old.then_some(old_key),
         })
     }
-}
 
-fn topological_sort(deps: &HashMap<String, HashSet<String>>) -> Result<Vec<String>> {
-    let mut result = Vec::new();
-    let mut visited = HashSet::new();
-    let mut temp = HashSet::new();
+    /// Revokes `user` of `account`, given by name or public key, after [`NatsForge::initialize`].
+    ///
+    /// The account JWT is reissued with the user in its revocations, and every server's
+    /// `<account>.jwt` and `nats.conf` are rewritten with it, so a server picks the revocation up on
+    /// its next reload. Returns the new account JWT. The keys of a setup only live as long as the
+    /// forge, so this has to be called on the one that ran `initialize()`.
+    pub async fn revoke_user(&self, account: &str, user: &str) -> Result<String> {
+        let (server, account_config) = self.defining_server(account)?;
+        self.check_operator_mode("revoke_user")?;
+        match &*self.issued.lock().expect("issued JWTs poisoned") {
+            None => {
+                return Err(NatsForgeError::Backend(
+                    "Nothing to revoke from yet; call initialize() first".to_string(),
+                ))
+            }
+            Some(issued) => {
+                let known = issued.creds.get(account).is_some_and(|users| users.contains_key(user));
+                if !backend::is_user_public_key(user) && !known {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Account {} has no user {}",
+                        account, user
+                    )));
+                }
+            }
+        }
+
+        self.backend
+            .revoke_user(account_config, user, &self.server_store_dir(server)?)
+            .await?;
+        self.publish_account_jwt(account_config).await
+    }
+
+    /// Pushes every account JWT to a running server with a full resolver, e.g. after
+    /// [`NatsForge::revoke_user`], so the change applies without a reload. `system_url` is a
+    /// `nats://` URL of the server; nsc authenticates with the system account's user. Accounts the
+    /// server rejects are listed in [`PushReport::failures`] with its error text. With several
+    /// operators, the accounts of the default one are pushed.
+    pub async fn push_accounts(&self, system_url: &str) -> Result<PushReport> {
+        let system_url = net::normalize_url(system_url)?;
+        self.check_operator_mode("push_accounts")?;
+        if self.issued.lock().expect("issued JWTs poisoned").is_none() {
+            return Err(NatsForgeError::Backend(
+                "Nothing to push yet; call initialize() first".to_string(),
+            ));
+        }
+        self.push_to(self.config.default_operator(), &system_url).await
+    }
+
+    /// Renders every server's `nats.conf` again from the account JWTs now in the store, e.g. after
+    /// [`NatsForge::revoke_user`] or limits edited in a persistent store, without issuing anything,
+    /// and asks each running server to reload it with [`SetupResult::reload_server`]. Servers that
+    /// couldn't be reached or refused are listed in [`ReloadReport::failures`] with the reason.
+    /// Needs the `client` feature.
+    #[cfg(feature = "client")]
+    pub async fn reload(&self, result: &SetupResult) -> Result<ReloadReport> {
+        self.rewrite_server_configs().await?;
+        let mut report = ReloadReport::default();
+        for server in &self.config.servers {
+            let error = result.reload_server(&server.name).await.err();
+            report.servers.push(ReloadedServer {
+                server: server.name.clone(),
+                error: error.map(|err| err.to_string()),
+            });
+        }
+        Ok(report)
+    }
+
+    /// Creates the `streams`, `consumers` and `kv_buckets` of every server and the config's
+    /// `mirrors` on the running servers of `result`, or updates those that already exist to
+    /// match. Safe to run again after every `initialize()`. Needs the `client` feature.
+    #[cfg(feature = "client")]
+    pub async fn provision(&self, result: &SetupResult) -> Result<()> {
+        provision::provision(&self.config, result).await
+    }
+
+    /// The `nats.conf` of `server` as [`initialize`](Self::initialize) rendered it, before its
+    /// `split_includes` were moved to files of their own, as a tree that can be inspected or
+    /// changed before [`ConfDocument::render`] turns it back into text.
+    pub fn server_document(&self, server: &str) -> Result<ConfDocument> {
+        let issued = self.issued.lock().expect("issued JWTs poisoned");
+        let issued = issued
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("Nothing to render yet; call initialize() first".to_string()))?;
+        let server = self
+            .config
+            .servers
+            .iter()
+            .find(|candidate| candidate.name == server)
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown server: {}", server)))?;
+        server_document(&self.config, server, issued)
+    }
+
+    /// Writes a `docker-compose.yml` to `path` with one service per server of `result`, and next
+    /// to each server's `nats.conf` the `nats.compose.conf` its container runs with. Output
+    /// directories are mounted at the same path in the containers, and leaf nodes start after the
+    /// hubs their remotes connect to.
+    pub fn emit_docker_compose(&self, result: &SetupResult, path: &Path, options: &ComposeOptions) -> Result<()> {
+        self.check_writes_files("emit_docker_compose")?;
+        self.check_plaintext_creds("emit_docker_compose")?;
+        let issued = self.issued.lock().expect("issued JWTs poisoned");
+        let issued = issued
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("Nothing to run yet; call initialize() first".to_string()))?;
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+        let host_of = |server: &ServerConfig| compose::service_name(&server.name);
+
+        let mut services = Vec::new();
+        for server in &self.config.servers {
+            let output_dir = result
+                .server_config(&server.name)
+                .and_then(Path::parent)
+                .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown server: {}", server.name)))?;
+            let (container, upstreams) =
+                compose::container_server(&self.config.servers, server, output_dir, &options.hostnames, host_of);
+            let config_path = output_dir.join(compose::COMPOSE_CONFIG_FILE);
+            writer
+                .write(&config_path, &render_server_config(&self.config, &container, issued)?)
+                .map_err(NatsForgeError::io(&config_path))?;
+
+            let mut volumes = vec![
+                (output_dir.to_path_buf(), output_dir.to_path_buf()),
+                (config_path, PathBuf::from(compose::CONTAINER_CONFIG)),
+            ];
+            if let Some(store_dir) = issued.jetstream_store_dirs.get(&server.name) {
+                if store_dir.is_absolute() && !store_dir.starts_with(output_dir) {
+                    volumes.push((store_dir.clone(), store_dir.clone()));
+                }
+            }
+            let monitoring = server.monitoring.as_ref();
+            let ports = [
+                Some(server.port),
+                server.leafnodes.port,
+                monitoring.and_then(|monitoring| monitoring.http_port),
+                monitoring.and_then(|monitoring| monitoring.https_port),
+            ];
+            services.push(compose::ComposeService {
+                name: host_of(server),
+                image: options.image.clone(),
+                command: vec!["-c".to_string(), compose::CONTAINER_CONFIG.to_string()],
+                volumes,
+                ports: ports.into_iter().flatten().collect(),
+                depends_on: upstreams.into_iter().map(host_of).collect(),
+            });
+            let http_port = monitoring.and_then(|monitoring| monitoring.http_port);
+            if let (Some(exporter), Some(http_port)) = (&options.exporter, http_port) {
+                let monitoring_url = format!("http://{}", net::host_port(&host_of(server), http_port));
+                services.push(compose::ComposeService {
+                    name: format!("{}-exporter", host_of(server)),
+                    image: exporter.image.clone(),
+                    command: exporter.args(server, &monitoring_url),
+                    volumes: Vec::new(),
+                    ports: Vec::new(),
+                    depends_on: vec![host_of(server)],
+                });
+            }
+        }
+        writer
+            .write(path, compose::render_compose(&services))
+            .map_err(NatsForgeError::io(path))
+    }
+
+    /// Writes `<server>.yaml` to `opts.output_dir` for every server of `result`: a ConfigMap with
+    /// the `nats.conf` for the pod, a Secret with the account JWTs and `.creds` files the server
+    /// reads and those of its accounts' users, a StatefulSet and a Service. Returns the written
+    /// paths.
+    pub fn emit_kubernetes(&self, result: &SetupResult, opts: K8sOptions) -> Result<Vec<PathBuf>> {
+        self.check_plaintext_creds("emit_kubernetes")?;
+        let issued = self.issued.lock().expect("issued JWTs poisoned");
+        let issued = issued
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("Nothing to run yet; call initialize() first".to_string()))?;
+        create_output_dir(&opts.output_dir, self.config.private_output_dirs)
+            .map_err(NatsForgeError::io(&opts.output_dir))?;
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+
+        let mut paths = Vec::new();
+        for server in &self.config.servers {
+            let (mut pod, _) = compose::container_server(
+                &self.config.servers,
+                server,
+                Path::new(kubernetes::SECRETS_DIR),
+                &opts.hostnames,
+                |upstream| kubernetes::service_host(upstream, &opts),
+            );
+            kubernetes::move_writable_paths(&mut pod);
+            // The Secret doesn't carry operator.jwt.
+            pod.operator_reference = OperatorRef::Inline;
+            let config = render_server_config_in(&self.config, &pod, issued, &pod.jetstream_store_dir())?;
+
+            let mut secrets = BTreeMap::new();
+            let scope = self.config.scoped_accounts(server);
+            for (account_name, account_jwt) in named_account_jwts(&self.config, server, &issued.account_jwts) {
+                if scope.as_ref().is_none_or(|scope| scope.contains(account_name)) {
+                    secrets.insert(format!("{}.jwt", account_name), account_jwt.clone());
+                }
+            }
+            for account in &server.accounts {
+                // Users added afterwards are only known for the first server defining the account.
+                let first_defined = self.first_defines(server, account);
+                let users = match first_defined {
+                    true => issued.creds.get(&account.name),
+                    false => result.server_account(&server.name, &account.name).map(|a| &a.creds),
+                };
+                for user in users.into_iter().flat_map(HashMap::keys) {
+                    let contents = match first_defined {
+                        true => result.creds_contents(&account.name, user)?,
+                        false => result.server_creds_contents(&server.name, &account.name, user)?,
+                    };
+                    secrets.insert(format!("{}-{}.creds", account.name, user), contents);
+                }
+            }
+            let mut files: Vec<String> = server
+                .leafnodes
+                .remotes
+                .iter()
+                .map(RemoteConfig::credentials_file)
+                .collect();
+            if server.tls.as_ref().is_some_and(|tls| tls.dev) {
+                files.extend(
+                    [
+                        tls::CA_FILE,
+                        tls::SERVER_CERT_FILE,
+                        tls::SERVER_KEY_FILE,
+                        tls::CLIENT_CERT_FILE,
+                        tls::CLIENT_KEY_FILE,
+                    ]
+                    .map(str::to_string),
+                );
+            }
+            for file in files {
+                let contents = output_file(result, server, &file)?;
+                secrets.insert(file, contents);
+            }
+            let resolver = match pod.resolver_dir() {
+                Some(resolver_dir) => {
+                    let mut keys = Vec::new();
+                    for (path, account_jwt) in resolver_files(&self.config, &resolver_dir, server, issued)? {
+                        let key = path.file_name().unwrap().to_string_lossy().into_owned();
+                        secrets.insert(key.clone(), account_jwt);
+                        keys.push(key);
+                    }
+                    Some((resolver_dir, keys))
+                }
+                None => None,
+            };
+
+            let manifests = kubernetes::ServerManifests {
+                server,
+                config,
+                secrets,
+                resolver,
+            };
+            let path = opts
+                .output_dir
+                .join(format!("{}.yaml", kubernetes::resource_name(&server.name)));
+            writer
+                .write_private(&path, kubernetes::render_manifests(&manifests, &opts))
+                .map_err(NatsForgeError::io(&path))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Writes a Prometheus `file_sd_configs` targets file to `path`, with the monitoring endpoint
+    /// of every server that has one at the host clients reach the server at.
+    pub fn emit_prometheus_targets(&self, result: &SetupResult, path: &Path) -> Result<()> {
+        let mut groups = Vec::new();
+        for server in &self.config.servers {
+            let host = result
+                .connections
+                .iter()
+                .find(|connection| connection.server_name == server.name)
+                .and_then(|connection| net::url_host_port(&connection.url))
+                .map(|(host, _)| host)
+                .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown server: {}", server.name)))?;
+            groups.extend(prometheus::target_group(server, &host));
+        }
+        if groups.is_empty() {
+            return Err(NatsForgeError::InvalidConfig(
+                "emit_prometheus_targets needs a server with a monitoring http_port or https_port".to_string(),
+            ));
+        }
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+        writer
+            .write(path, prometheus::render_targets(&groups))
+            .map_err(NatsForgeError::io(path))
+    }
+
+    /// Runs the backend's push of `operator`'s accounts and reports them by their config names.
+    async fn push_to(&self, operator: &OperatorConfig, url: &str) -> Result<PushReport> {
+        let mut report = self
+            .backend
+            .push_accounts(&operator.name, url, &self.operator_store_dir(operator)?)
+            .await?;
+        for pushed in &mut report.accounts {
+            let account = self
+                .config
+                .servers
+                .iter()
+                .flat_map(|server| &server.accounts)
+                .find(|account| account.unique_name == pushed.account);
+            if let Some(account) = account {
+                pushed.account = account.name.clone();
+            }
+        }
+        Ok(report)
+    }
+
+    /// Where the backend keeps `operator`'s keys: the store directory itself, or a subdirectory
+    /// named after the operator when the config has several, so that each store holds one.
+    fn operator_store_dir(&self, operator: &OperatorConfig) -> Result<PathBuf> {
+        let store_dir = self.store_dir()?;
+        if self.config.all_operators().nth(1).is_some() {
+            Ok(store_dir.join(&operator.name))
+        } else {
+            Ok(store_dir.to_path_buf())
+        }
+    }
+
+    /// The backend's store, created here for a forge made with [`NatsForge::new`].
+    fn store_dir(&self) -> Result<&Path> {
+        if let Some(store_dir) = self.store_dir.get() {
+            return Ok(store_dir.path());
+        }
+        let store_dir = StoreDir::new(&self.config)?;
+        Ok(self.store_dir.get_or_init(|| store_dir).path())
+    }
+
+    /// The store of the operator `server` trusts.
+    fn server_store_dir(&self, server: &ServerConfig) -> Result<PathBuf> {
+        self.operator_store_dir(self.config.server_operator(server))
+    }
+
+    /// The first server that defines `account_name`, and the account there.
+    fn defining_server(&self, account_name: &str) -> Result<(&ServerConfig, &AccountConfig)> {
+        self.config
+            .servers
+            .iter()
+            .flat_map(|server| server.accounts.iter().map(move |account| (server, account)))
+            .find(|(_, account)| account.name == account_name)
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown account: {}", account_name)))
+    }
+
+    /// Whether `server` is the first to define an account of `account`'s name, whose artifacts
+    /// lookups by account name alone return.
+    fn first_defines(&self, server: &ServerConfig, account: &AccountConfig) -> bool {
+        self.defining_server(&account.name)
+            .is_ok_and(|(first, _)| first.name == server.name)
+    }
+
+    fn check_plaintext_creds(&self, operation: &str) -> Result<()> {
+        if self.config.encryption.is_some() {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "{} needs plaintext creds, which encryption doesn't leave",
+                operation
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_operator_mode(&self, operation: &str) -> Result<()> {
+        if self
+            .config
+            .servers
+            .iter()
+            .any(|server| server.auth_mode == AuthMode::Conf)
+        {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "{} issues JWTs, which conf-mode servers don't use",
+                operation
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_writes_files(&self, operation: &str) -> Result<()> {
+        if self.config.artifact_mode == ArtifactMode::InMemory {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "{} writes creds to output_dir, which in-memory mode doesn't use",
+                operation
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes a user's `.creds` file, and for bearer users the raw JWT, to the server's output
+    /// directory.
+    fn write_user(
+        &self,
+        server: &ServerConfig,
+        account: &AccountConfig,
+        user: &UserConfig,
+        creds: &str,
+    ) -> Result<PathBuf> {
+        let output_dir = self
+            .issued
+            .lock()
+            .expect("issued JWTs poisoned")
+            .as_ref()
+            .map(|issued| issued.output_dirs.get(server).to_path_buf())
+            .ok_or_else(|| NatsForgeError::Backend("call initialize() first".to_string()))?;
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+        let encrypter = self.config.encryption.as_ref().map(Encrypter::new).transpose()?;
+        let creds_path = output_dir.join(format!("{}-{}.creds", account.name, user.name));
+        let creds_path = write_credential(&writer, encrypter.as_ref(), &creds_path, creds)?;
+        if user.bearer {
+            let jwt = backend::creds_jwt(creds)
+                .ok_or_else(|| NatsForgeError::Backend(format!("No JWT in the creds of user {}", user.name)))?;
+            let jwt_path = output_dir.join(format!("{}-{}.jwt", account.name, user.name));
+            write_credential(&writer, encrypter.as_ref(), &jwt_path, jwt)?;
+        }
+        Ok(creds_path)
+    }
 
-    fn visit(
-        node: &str,
-        deps: &HashMap<String, HashSet<String>>,
-        visited: &mut HashSet<String>,
-        temp: &mut HashSet<String>,
-        result: &mut Vec<String>,
+    /// Hands a user's creds to the secret sink, if there is one.
+    async fn store_creds(
+        &self,
+        server: &ServerConfig,
+        account: &AccountConfig,
+        user: &UserConfig,
+        creds: &str,
     ) -> Result<()> {
-        if temp.contains(node) {
-            return Err(anyhow::anyhow!("Circular dependency detected at {}", node));
+        match &self.secret_sink {
+            Some(sink) => {
+                sink.store(
+                    &secrets::creds_key(&server.name, &account.name, &user.name),
+                    creds.as_bytes(),
+                )
+                .await
+            }
+            None => Ok(()),
         }
-        if visited.contains(node) {
-            return Ok(());
+    }
+
+    /// Picks up the account's current JWT from the backend and rewrites the `<account>.jwt` and
+    /// `nats.conf` of every server trusting its operator with it. Returns the JWT.
+    async fn publish_account_jwt(&self, account: &AccountConfig) -> Result<String> {
+        let defining_server = self
+            .config
+            .servers
+            .iter()
+            .find(|server| server.accounts.iter().any(|a| a.unique_name == account.unique_name))
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unknown account: {}", account.name)))?;
+        let operator = self.config.server_operator(defining_server);
+        let account_jwt = self
+            .backend
+            .account_jwt(account, &operator.name, &self.operator_store_dir(operator)?)
+            .await?;
+        if let Some(sink) = &self.secret_sink {
+            sink.store(
+                &secrets::account_jwt_key(&defining_server.name, &account.name),
+                account_jwt.as_bytes(),
+            )
+            .await?;
+        }
+
+        let mut issued = self.issued.lock().expect("issued JWTs poisoned");
+        let issued = issued
+            .as_mut()
+            .ok_or_else(|| NatsForgeError::Backend("call initialize() first".to_string()))?;
+        issued
+            .account_jwts
+            .insert(account.unique_name.clone(), account_jwt.clone());
+        if self.config.artifact_mode == ArtifactMode::InMemory {
+            return Ok(account_jwt);
+        }
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+        let trusting = self
+            .config
+            .servers
+            .iter()
+            .filter(|server| self.config.server_operator(server).name == operator.name);
+        for server in trusting {
+            let output_dir = issued.output_dirs.get(server);
+            // A server knowing another account by this name keeps that one's `<account>.jwt`.
+            let known = self.config.import_exporter(server, &account.name);
+            if known.is_some_and(|(_, known)| known.unique_name == account.unique_name) {
+                let jwt_path = output_dir.join(format!("{}.jwt", account.name));
+                writer
+                    .write(&jwt_path, &account_jwt)
+                    .map_err(NatsForgeError::io(&jwt_path))?;
+            }
+            if let Some(resolver_dir) = server.resolver_dir() {
+                let resolver_jwt_path = resolver_dir.join(format!("{}.jwt", extract_account_id(&account_jwt)?));
+                writer
+                    .write(&resolver_jwt_path, &account_jwt)
+                    .map_err(NatsForgeError::io(&resolver_jwt_path))?;
+            }
+            rewrite_server_config(&self.config, server, issued, &writer)?;
+        }
+        Ok(account_jwt)
+    }
+
+    /// Fetches the JWT of every issued account from the store again and rewrites every server's
+    /// `nats.conf` with them.
+    #[cfg(feature = "client")]
+    async fn rewrite_server_configs(&self) -> Result<()> {
+        if self.config.artifact_mode == ArtifactMode::InMemory {
+            return Err(NatsForgeError::InvalidConfig(
+                "reload rewrites the configs in output_dir, which in-memory mode doesn't use".to_string(),
+            ));
         }
-        temp.insert(node.to_string());
-        if let Some(children) = deps.get(node) {
-            for child in children {
-                visit(child, deps, visited, temp, result)?;
+        let issued_accounts: Vec<String> = match &*self.issued.lock().expect("issued JWTs poisoned") {
+            None => {
+                return Err(NatsForgeError::Backend(
+                    "Nothing to reload yet; call initialize() first".to_string(),
+                ))
+            }
+            Some(issued) => issued.account_jwts.keys().cloned().collect(),
+        };
+        let mut account_jwts = Vec::new();
+        for server in &self.config.servers {
+            let operator = self.config.server_operator(server);
+            // The system account keeps the JWT it was issued with along with the operator.
+            let accounts = server
+                .accounts
+                .iter()
+                .filter(|account| issued_accounts.contains(&account.unique_name) && !account.is_system_account);
+            for account in accounts {
+                let account_jwt = self
+                    .backend
+                    .account_jwt(account, &operator.name, &self.operator_store_dir(operator)?)
+                    .await?;
+                account_jwts.push((account.unique_name.clone(), account_jwt));
             }
         }
-        temp.remove(node);
-        visited.insert(node.to_string());
-        result.push(node.to_string());
+
+        let mut issued = self.issued.lock().expect("issued JWTs poisoned");
+        let issued = issued
+            .as_mut()
+            .ok_or_else(|| NatsForgeError::Backend("call initialize() first".to_string()))?;
+        issued.account_jwts.extend(account_jwts);
+        let writer = ArtifactWriter::new(self.sink.as_ref(), self.config.durability);
+        for server in &self.config.servers {
+            rewrite_server_config(&self.config, server, issued, &writer)?;
+        }
         Ok(())
     }
+}
+
+/// Renders the `nats.conf` and split includes of `server` from `issued` again and writes them to
+/// its output directory.
+fn rewrite_server_config(
+    config: &NatsConfig,
+    server: &ServerConfig,
+    issued: &Issued,
+    writer: &ArtifactWriter,
+) -> Result<()> {
+    let output_dir = issued.output_dirs.get(server);
+    let (server_config, includes) = render_split_server_config(config, server, issued)?;
+    for (file_name, contents) in includes {
+        let path = output_dir.join(file_name);
+        writer.write(&path, &contents).map_err(NatsForgeError::io(&path))?;
+    }
+    let server_config_path = output_dir.join("nats.conf");
+    writer
+        .write(&server_config_path, &server_config)
+        .map_err(NatsForgeError::io(&server_config_path))
+}
+
+/// Every file and directory `initialize()` wrote for `result` that the result records. JetStream
+/// store directories only count with `absolute_paths`; otherwise nats-server creates them relative
+/// to where it runs.
+#[allow(deprecated)]
+fn generated_paths(config: &NatsConfig, result: &SetupResult) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    paths.extend(result.operator_jwt_paths.iter().cloned());
+    paths.extend(result.account_jwt_paths.iter().cloned());
+    paths.extend(result.user_creds_paths.iter().cloned());
+    for artifacts in result.account_jwts.values() {
+        paths.push(artifacts.jwt_path.clone());
+        paths.extend(artifacts.creds.values().cloned());
+        paths.extend(artifacts.bearer_jwts.values().cloned());
+        paths.extend(artifacts.activations.values().cloned());
+    }
+    paths.extend(result.sys_creds_paths.values().cloned());
+    paths.extend(result.user_secrets.values().flat_map(|users| users.values().cloned()));
+    for keys in result.auth_callout_keys.values() {
+        paths.push(keys.issuer_seed.clone());
+        paths.extend(keys.xkey_seed.clone());
+    }
+    for server_config in result.servers.values() {
+        paths.push(server_config.clone());
+        if config.write_manifest {
+            paths.push(server_config.with_file_name(MANIFEST_FILE));
+        }
+    }
+    paths.extend(result.resolver_dirs.values().cloned());
+    paths.extend(
+        result
+            .jetstream_store_dirs
+            .values()
+            .filter(|dir| dir.is_absolute())
+            .cloned(),
+    );
+    paths
+}
+
+/// The JWT preloaded for `account_id` in any `.conf` file in `dir`, where the resolver preload
+/// is either inline in `nats.conf` or split off into an include.
+fn preloaded_jwt(dir: &Path, account_id: &str) -> Result<Option<String>> {
+    let entries = std::fs::read_dir(dir).map_err(NatsForgeError::io(dir))?;
+    for entry in entries {
+        let path = entry.map_err(NatsForgeError::io(dir))?.path();
+        if path.extension() != Some(OsStr::new("conf")) {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path).map_err(NatsForgeError::io(&path))?;
+        let jwt = text.lines().find_map(|line| {
+            let value = line.trim().strip_prefix(account_id)?.strip_prefix(':')?;
+            Some(value.trim().trim_matches('"').to_string())
+        });
+        if jwt.is_some() {
+            return Ok(jwt);
+        }
+    }
+    Ok(None)
+}
+
+/// How often removing a directory is tried while something keeps adding files to it.
+const REMOVE_ATTEMPTS: u32 = 3;
+
+/// Removes a file, or a directory with its contents, retrying when a directory fills up again
+/// while it is being removed, e.g. because a server is still shutting down.
+async fn remove_path(path: &Path) -> std::io::Result<()> {
+    if !path.is_dir() {
+        return std::fs::remove_file(path);
+    }
+    let mut attempt = 1;
+    loop {
+        match std::fs::remove_dir_all(path) {
+            Err(err) if err.kind() == std::io::ErrorKind::DirectoryNotEmpty && attempt < REMOVE_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The operators' system account: [`NatsConfig::system_account`], or else the SYS account `nsc
+/// init` and the native backend create along with the operator. The backends store either under
+/// its plain name.
+fn system_account(config: &NatsConfig) -> AccountConfig {
+    let name = config.system_account_name().to_string();
+    let (users, exports) = config
+        .system_account
+        .as_ref()
+        .map(|account| (account.users.clone(), account.exports.clone()))
+        .unwrap_or_default();
+    AccountConfig {
+        unique_name: name.clone(),
+        name,
+        users,
+        exports,
+        is_system_account: true,
+        ..Default::default()
+    }
+}
+
+/// Expands the cluster, account and user templates of `config` into servers, accounts and users.
+fn expand_templates(config: &mut NatsConfig) -> Result<()> {
+    config.expand_cluster_templates()?;
+    config.expand_account_templates()?;
+    config.expand_user_templates()
+}
+
+/// Defines [`NatsConfig::system_account`] on the first server, so its users are issued and its
+/// JWT is written like any other account's. A server that already has an account of its name
+/// marked `is_system_account` defines it instead.
+fn declare_system_account(config: &mut NatsConfig) {
+    if config.system_account.is_none() {
+        return;
+    }
+    let account = system_account(config);
+    let declared = config
+        .servers
+        .iter()
+        .flat_map(|server| &server.accounts)
+        .any(|existing| existing.is_system_account && existing.name == account.name);
+    if let (false, Some(first)) = (declared, config.servers.first_mut()) {
+        first.accounts.push(account);
+    }
+}
+
+/// Renders a server's `nats.conf` with the JWTs of its scoped accounts preloaded.
+fn render_server_config(config: &NatsConfig, server: &ServerConfig, issued: &Issued) -> Result<String> {
+    render_server_config_in(config, server, issued, &jetstream_store_dir(server, issued))
+}
+
+/// Like [`render_server_config`], with the blocks the server splits off moved to the files
+/// returned by name, which go next to its `nats.conf`.
+fn render_split_server_config(
+    config: &NatsConfig,
+    server: &ServerConfig,
+    issued: &Issued,
+) -> Result<(String, Vec<(String, String)>)> {
+    let mut document = server_document(config, server, issued)?;
+    let includes = server::split_includes(server, &mut document);
+    Ok((server::render(server, &document), includes))
+}
+
+/// Like [`render_server_config`], with the JetStream store in `jetstream_store_dir`.
+fn render_server_config_in(
+    config: &NatsConfig,
+    server: &ServerConfig,
+    issued: &Issued,
+    jetstream_store_dir: &Path,
+) -> Result<String> {
+    let document = server_document_in(config, server, issued, jetstream_store_dir)?;
+    Ok(server::render(server, &document))
+}
+
+/// The tree [`render_server_config`] renders.
+fn server_document(config: &NatsConfig, server: &ServerConfig, issued: &Issued) -> Result<ConfDocument> {
+    server_document_in(config, server, issued, &jetstream_store_dir(server, issued))
+}
+
+/// The tree [`render_server_config_in`] renders.
+fn server_document_in(
+    config: &NatsConfig,
+    server: &ServerConfig,
+    issued: &Issued,
+    jetstream_store_dir: &Path,
+) -> Result<ConfDocument> {
+    if let Some(credentials) = issued.conf_credentials.get(&server.name) {
+        let auth = ServerAuth::Conf {
+            credentials,
+            grants: &config.derive_grants(),
+            callout: issued.callout_keys.get(&server.name),
+        };
+        return Ok(server::server_document(server, jetstream_store_dir, &auth));
+    }
+    let (system_account_id, resolver_preload) = resolver_preload(config, server, issued)?;
+    let auth = ServerAuth::Operator {
+        operator: &operator(config, server, issued),
+        system_account_id: &system_account_id,
+        resolver_preload: &resolver_preload,
+        account_jwts: &named_account_jwts(config, server, &issued.account_jwts),
+    };
+    Ok(server::server_document(server, jetstream_store_dir, &auth))
+}
+
+/// What the server's `operator` is set to: the operator JWT, or the path of the file holding it.
+fn operator(config: &NatsConfig, server: &ServerConfig, issued: &Issued) -> String {
+    match server.operator_reference {
+        OperatorRef::Inline => issued.operator(config, server).jwt.clone(),
+        OperatorRef::File => issued
+            .operator_jwt_paths
+            .get(&server.name)
+            .cloned()
+            .unwrap_or_else(|| server.output_dir.join("operator.jwt"))
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+fn jetstream_store_dir(server: &ServerConfig, issued: &Issued) -> PathBuf {
+    issued
+        .jetstream_store_dirs
+        .get(&server.name)
+        .cloned()
+        .unwrap_or_else(|| server.jetstream_store_dir())
+}
+
+/// The issued accounts of `server`'s operator with their JWTs, each once. Accounts of the same
+/// name on several servers are told apart by their unique names.
+fn trusted_account_jwts<'a>(
+    config: &'a NatsConfig,
+    server: &ServerConfig,
+    issued: &'a Issued,
+) -> impl Iterator<Item = (&'a AccountConfig, &'a String)> {
+    let operator = config.server_operator(server).name.clone();
+    let mut seen = HashSet::new();
+    config
+        .servers
+        .iter()
+        .filter(move |other| config.server_operator(other).name == operator)
+        .flat_map(|other| &other.accounts)
+        .filter(move |account| seen.insert(&account.unique_name))
+        .filter_map(|account| Some((account, issued.account_jwts.get(&account.unique_name)?)))
+}
+
+/// The issued JWTs `server` refers to accounts by name with: of each name, the JWT of the account
+/// an import on `server` resolves to, its own account of that name or else the first of its
+/// operator's.
+fn named_account_jwts<'a>(
+    config: &'a NatsConfig,
+    server: &'a ServerConfig,
+    account_jwts: &'a HashMap<String, String>,
+) -> HashMap<&'a str, &'a String> {
+    let mut named = HashMap::new();
+    for account in config.servers.iter().flat_map(|other| &other.accounts) {
+        if named.contains_key(account.name.as_str()) {
+            continue;
+        }
+        let Some((_, known)) = config.import_exporter(server, &account.name) else {
+            continue;
+        };
+        if let Some(account_jwt) = account_jwts.get(&known.unique_name) {
+            named.insert(known.name.as_str(), account_jwt);
+        }
+    }
+    named
+}
+
+/// The artifacts of every account by name alone: the first defining server's, where several
+/// servers define an account of the same name.
+fn by_account_name(
+    config: &NatsConfig,
+    server_accounts: &HashMap<String, HashMap<String, AccountArtifacts>>,
+) -> HashMap<String, AccountArtifacts> {
+    let mut accounts = HashMap::new();
+    for server in &config.servers {
+        for (name, artifacts) in server_accounts.get(&server.name).into_iter().flatten() {
+            accounts.entry(name.clone()).or_insert_with(|| artifacts.clone());
+        }
+    }
+    accounts
+}
+
+/// The contents of `creds_map` by server name, account name and then user name.
+fn server_creds(
+    creds_map: &HashMap<(String, String, String), (String, String)>,
+) -> HashMap<String, HashMap<String, HashMap<String, String>>> {
+    let mut creds: HashMap<String, HashMap<String, HashMap<String, String>>> = HashMap::new();
+    for ((server, _, user), (account, contents)) in creds_map {
+        creds
+            .entry(server.clone())
+            .or_default()
+            .entry(account.clone())
+            .or_default()
+            .insert(user.clone(), contents.clone());
+    }
+    creds
+}
+
+/// The ID of the server's system account, and the account JWTs it preloads by ID: those of its
+/// scoped accounts under its operator for the memory resolver, only the system account's
+/// otherwise.
+fn resolver_preload(config: &NatsConfig, server: &ServerConfig, issued: &Issued) -> Result<(String, ConfObject)> {
+    let mut preload = Vec::new();
+    let scope = config.scoped_accounts(server);
+    for (account, account_jwt) in trusted_account_jwts(config, server, issued) {
+        if scope
+            .as_ref()
+            .is_some_and(|scope| !scope.contains(account.name.as_str()))
+        {
+            continue;
+        }
+        preload.push((extract_account_id(account_jwt)?, account_jwt));
+    }
+    for (_, account_jwt) in issued.external_accounts.get(&server.name).into_iter().flatten() {
+        preload.push((extract_account_id(account_jwt)?, account_jwt));
+    }
+
+    let operator = issued.operator(config, server);
+    if !preload.iter().any(|(id, _)| id == &operator.system_id) {
+        preload.push((operator.system_id.clone(), &operator.system_jwt));
+    }
+    // Other resolvers look the remaining accounts up themselves.
+    if server.resolver != ResolverType::Memory {
+        preload.retain(|(id, _)| id == &operator.system_id);
+    }
+    // In a stable order, so rendering the same JWTs again leaves the config unchanged.
+    preload.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok((operator.system_id.clone(), preload.into_iter().collect()))
+}
+
+/// The creds a leafnode remote of `server` connects with. Picks the account instance on `server`
+/// (or on `remote.server`) when several servers define the account, and fails rather than
+/// guessing when that leaves more than one candidate.
+fn remote_creds<'a>(
+    creds_map: &'a HashMap<(String, String, String), (String, String)>,
+    server: &ServerConfig,
+    remote: &RemoteConfig,
+) -> Result<&'a String> {
+    let mut candidates: Vec<_> = creds_map
+        .iter()
+        .filter(|((_, _, user), (account, _))| match &remote.user {
+            Some(wanted) => account == &remote.account && user == wanted,
+            None => format!("{}-{}.creds", account, user) == remote.credentials,
+        })
+        .filter(|((server_name, _, _), _)| remote.server.as_ref().is_none_or(|wanted| server_name == wanted))
+        .collect();
+    if candidates.len() > 1 && candidates.iter().any(|((name, _, _), _)| name == &server.name) {
+        candidates.retain(|((name, _, _), _)| name == &server.name);
+    }
+    match candidates.as_slice() {
+        [(_, (_, creds))] => Ok(creds),
+        [] => Err(NatsForgeError::InvalidConfig(format!(
+            "No creds entry found for {}",
+            remote.credentials_file()
+        ))),
+        _ => {
+            let mut names: Vec<String> = candidates
+                .iter()
+                .map(|((server_name, unique_name, user), _)| format!("{}/{}/{}", server_name, unique_name, user))
+                .collect();
+            names.sort();
+            Err(NatsForgeError::InvalidConfig(format!(
+                "Creds {} for leafnode remote {} of server {} are ambiguous, set the remote's server to pick one of: \
+                 {}",
+                remote.credentials_file(),
+                remote.url,
+                server.name,
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// Writes a `.creds` file or bearer JWT readable only by the current user, encrypted and with
+/// `.age` appended to `path` when there is an encrypter. Returns the written path.
+fn write_credential(
+    writer: &ArtifactWriter,
+    encrypter: Option<&Encrypter>,
+    path: &Path,
+    contents: &str,
+) -> Result<PathBuf> {
+    let (path, contents) = match encrypter {
+        Some(encrypter) => {
+            let mut encrypted_path = path.as_os_str().to_owned();
+            encrypted_path.push(ENCRYPTED_SUFFIX);
+            (PathBuf::from(encrypted_path), encrypter.encrypt(contents.as_bytes())?)
+        }
+        None => (path.to_path_buf(), contents.to_string()),
+    };
+    writer
+        .write_private(&path, contents)
+        .map_err(NatsForgeError::io(&path))?;
+    Ok(path)
+}
+
+/// Stands in for the encrypter without the `encryption` feature. Validation rejects `encryption`
+/// then, so none is ever made.
+#[cfg(not(feature = "encryption"))]
+enum Encrypter {}
+
+#[cfg(not(feature = "encryption"))]
+const ENCRYPTED_SUFFIX: &str = ".age";
+
+#[cfg(not(feature = "encryption"))]
+impl Encrypter {
+    fn new(_encryption: &config::EncryptionConfig) -> Result<Self> {
+        Err(NatsForgeError::InvalidConfig(
+            "encryption needs natsforge built with the encryption feature".to_string(),
+        ))
+    }
+
+    fn encrypt(&self, _plaintext: &[u8]) -> Result<String> {
+        match *self {}
+    }
+}
+
+/// A file `initialize()` put in the output directory of `server`, such as the creds of a leafnode
+/// remote, read from disk unless the setup kept artifacts in memory.
+fn output_file(result: &SetupResult, server: &ServerConfig, name: &str) -> Result<String> {
+    let missing = || NatsForgeError::InvalidConfig(format!("No file {} for server {}", name, server.name));
+    match &result.artifacts {
+        Some(artifacts) => artifacts
+            .server_files
+            .get(&server.name)
+            .and_then(|files| files.get(&server.output_dir.join(name)))
+            .cloned()
+            .ok_or_else(missing),
+        None => {
+            let path = result
+                .server_config(&server.name)
+                .and_then(Path::parent)
+                .ok_or_else(missing)?
+                .join(name);
+            std::fs::read_to_string(&path).map_err(NatsForgeError::io(&path))
+        }
+    }
+}
+
+/// Reads the `external_accounts` of `server` as account name and JWT. Each has to be an account
+/// JWT issued by the operator, one of its signing keys, or one of the server's
+/// `external_account_issuers`.
+fn read_external_accounts(server: &ServerConfig, operator_jwt: &str) -> Result<Vec<(String, String)>> {
+    let operator = jwt::decode_operator_claims(operator_jwt)?;
+    let mut issuers = vec![operator.sub.as_str()];
+    issuers.extend(operator.nats.signing_keys.iter().map(String::as_str));
+    issuers.extend(server.external_account_issuers.iter().map(String::as_str));
+
+    let mut accounts = Vec::new();
+    for path in &server.external_accounts {
+        let contents = std::fs::read_to_string(path).map_err(NatsForgeError::io(path))?;
+        let account_jwt = contents.trim();
+        let not_an_account = |reason: String| NatsForgeError::NotAnAccountJwt {
+            path: path.clone(),
+            reason,
+        };
+        let claims: serde_json::Value =
+            jwt::decode_claims(account_jwt).map_err(|err| not_an_account(err.to_string()))?;
+        match claims["nats"]["type"].as_str() {
+            Some("account") => {}
+            Some(other) => return Err(not_an_account(format!("it is a {} JWT", other))),
+            None => return Err(not_an_account("it has no type".to_string())),
+        }
+        let Some(account_id) = claims["sub"].as_str() else {
+            return Err(not_an_account("it has no subject".to_string()));
+        };
+        let issuer = claims["iss"].as_str().unwrap_or_default();
+        if !issuers.contains(&issuer) {
+            return Err(NatsForgeError::ExternalIssuerMismatch {
+                path: path.clone(),
+                issuer: issuer.to_string(),
+            });
+        }
+        let account_name = claims["name"].as_str().unwrap_or(account_id);
+        accounts.push((account_name.to_string(), account_jwt.to_string()));
+    }
+    Ok(accounts)
+}
+
+/// The files a full resolver reads, `<account-id>.jwt` for every issued account of the server's
+/// operator and the external accounts of `server`, by path.
+fn resolver_files(
+    config: &NatsConfig,
+    resolver_dir: &Path,
+    server: &ServerConfig,
+    issued: &Issued,
+) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    let mut has_system = false;
+    let operator = issued.operator(config, server);
+    let account_jwts = trusted_account_jwts(config, server, issued).map(|(_, account_jwt)| account_jwt);
+    let external_accounts = issued.external_accounts.get(&server.name).into_iter().flatten();
+    for account_jwt in account_jwts.chain(external_accounts.map(|(_, account_jwt)| account_jwt)) {
+        let account_id = extract_account_id(account_jwt)?;
+        has_system |= account_id == operator.system_id;
+        files.push((resolver_dir.join(format!("{}.jwt", account_id)), account_jwt.clone()));
+    }
+    if !has_system {
+        files.push((
+            resolver_dir.join(format!("{}.jwt", operator.system_id)),
+            operator.system_jwt.clone(),
+        ));
+    }
+    Ok(files)
+}
+
+/// Groups accounts so that every account only depends on accounts in earlier layers.
+///
+/// Expects an acyclic graph, as `acyclic_dependencies` leaves it.
+fn dependency_layers(deps: &HashMap<String, HashSet<String>>) -> Vec<HashSet<String>> {
+    let mut pending: HashMap<&str, usize> = deps.keys().map(|k| (k.as_str(), 0)).collect();
+    for dependents in deps.values() {
+        for dependent in dependents {
+            *pending.entry(dependent.as_str()).or_default() += 1;
+        }
+    }
+
+    let mut layers = Vec::new();
+    while !pending.is_empty() {
+        let layer: HashSet<String> = pending
+            .iter()
+            .filter(|(_, &count)| count == 0)
+            .map(|(node, _)| node.to_string())
+            .collect();
+        if layer.is_empty() {
+            break;
+        }
+        for node in &layer {
+            pending.remove(node.as_str());
+            for dependent in deps.get(node).into_iter().flatten() {
+                if let Some(count) = pending.get_mut(dependent.as_str()) {
+                    *count -= 1;
+                }
+            }
+        }
+        layers.push(layer);
+    }
+    layers
+}
+
+/// `deps` without the imports that close a cycle, so that `dependency_layers` can order the rest.
+///
+/// Import cycles are allowed, as nsc allows them, unless one of their imports is of a private
+/// export; that is reported as the cycle starting at the private export's account. Accounts are
+/// visited in `order`, the config's declaration order, so the same config always drops the same
+/// imports and creates accounts in the same order.
+fn acyclic_dependencies(
+    deps: &HashMap<String, HashSet<String>>,
+    private_imports: &HashSet<(String, String)>,
+    order: &[&str],
+) -> Result<HashMap<String, HashSet<String>>> {
+    let rank: HashMap<&str, usize> = order.iter().enumerate().map(|(i, node)| (*node, i)).collect();
+    let rank = |node: &str| rank.get(node).copied().unwrap_or(usize::MAX);
+    let children = |node: &str| {
+        let mut children: Vec<&String> = deps.get(node).into_iter().flatten().collect();
+        children.sort_by_key(|child| (rank(child), child.as_str()));
+        children
+    };
+
+    let mut private_imports: Vec<_> = private_imports.iter().collect();
+    private_imports.sort_by_key(|(exporter, importer)| (rank(exporter), rank(importer)));
+    for (exporter, importer) in private_imports {
+        if let Some(path) = dependency_path(importer, exporter, children) {
+            let mut cycle = vec![exporter.clone()];
+            cycle.extend(path);
+            return Err(NatsForgeError::CircularDependency { cycle });
+        }
+    }
 
-    for node in deps.keys() {
-        if !visited.contains(node) {
-            visit(node, deps, &mut visited, &mut temp, &mut result)?;
+    // Depth-first with an explicit stack, so long import chains can't overflow the call stack.
+    // Each entry is an account on the current path with the children it has left to visit.
+    let mut acyclic: HashMap<String, HashSet<String>> = deps.keys().map(|k| (k.clone(), HashSet::new())).collect();
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut nodes: Vec<&str> = deps.keys().map(String::as_str).collect();
+    nodes.sort_by_key(|node| (rank(node), *node));
+    for root in nodes {
+        if !visited.insert(root) {
+            continue;
+        }
+        let mut path: Vec<(&str, std::vec::IntoIter<&String>)> = vec![(root, children(root).into_iter())];
+        while let Some((node, remaining)) = path.last_mut() {
+            let node = *node;
+            let Some(child) = remaining.next() else {
+                path.pop();
+                continue;
+            };
+            // An edge back into the path closes a cycle.
+            if path.iter().any(|(on_path, _)| on_path == child) {
+                continue;
+            }
+            acyclic.entry(node.to_string()).or_default().insert(child.clone());
+            if visited.insert(child) {
+                path.push((child, children(child).into_iter()));
+            }
+        }
+    }
+    Ok(acyclic)
+}
+
+/// The shortest chain of dependents from `from` to `to`, both included, following `children`.
+fn dependency_path<'a>(from: &'a str, to: &str, children: impl Fn(&str) -> Vec<&'a String>) -> Option<Vec<String>> {
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(node) = queue.pop_front() {
+        if node == to {
+            let mut path = vec![to.to_string()];
+            let mut node = to;
+            while let Some(prev) = previous.get(node) {
+                path.push(prev.to_string());
+                node = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for child in children(node) {
+            if child != from && !previous.contains_key(child.as_str()) {
+                previous.insert(child, node);
+                queue.push_back(child);
+            }
         }
     }
-    Ok(result)
+    None
 }
@@ -1,18 +1,160 @@
-use clap::Parser;
-use natsforge::NatsForge;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
+use natsforge::{progress::CancellationToken, NatsForge};
+use tokio::sync::mpsc;
 
 #[derive(Parser)]
 #[command(about = "NATS configuration generator")]
 struct Cli {
-    #[arg(short, long, default_value = "config.json")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, default_value = "config.json", global = true)]
     config: String,
+
+    /// Apply this entry of the config's `profiles`, e.g. `prod`.
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Print the permissions and limits derived from the config and exit without generating
+    /// anything.
+    #[arg(long)]
+    explain: bool,
+
+    /// Print a breakdown of where generation time went, by phase and slowest item.
+    #[arg(long)]
+    timings: bool,
+
+    /// Write the setup manifest, the generated paths and account IDs as JSON, to this file.
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+
+    /// Print the setup manifest to stdout instead of a summary of the result, or with `diff` the
+    /// report as JSON.
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the account import graph of the config without generating anything.
+    Graph {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// Show what generating would change compared with an earlier run, without writing anything.
+    Diff {
+        /// Where the earlier run's output is; each server's output_dir is resolved against it.
+        #[arg(long, default_value = ".")]
+        against: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Json,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    let forge = NatsForge::from_json_file(&cli.config)?;
-    let result = forge.initialize().await?;
-    println!("Configuration generated: {:?}", result);
+    let forge = NatsForge::from_json_file(&cli.config, cli.profile.as_deref())?;
+    match cli.command {
+        Some(Command::Graph { format }) => {
+            let graph = forge.config().account_graph();
+            match format {
+                GraphFormat::Dot => print!("{}", graph.to_dot()),
+                GraphFormat::Json => println!("{}", graph.to_json()),
+            }
+            return Ok(());
+        }
+        Some(Command::Diff { against }) => {
+            let report = forge.diff(&against).await?;
+            if cli.json {
+                println!("{}", report.to_json());
+            } else {
+                print!("{}", report);
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+    if cli.explain {
+        let expansion = forge.config().derive_grants();
+        println!("Derived grants:");
+        if expansion.grants.is_empty() {
+            println!("  (none)");
+        }
+        for grant in &expansion.grants {
+            println!("  {}", grant);
+        }
+        println!("Limits:");
+        for server in &forge.config().servers {
+            println!(
+                "  server {}: max_connections {}, max_subscriptions {}, max_payload {}",
+                server.name,
+                limit(server.max_connections, "default"),
+                limit(server.max_subscriptions, "default"),
+                limit(server.max_payload, "default")
+            );
+            for account in &server.accounts {
+                println!(
+                    "    account {}: max_connections {}, max_payload {}",
+                    account.name,
+                    limit(account.max_connections, "unlimited"),
+                    limit(account.max_payload, "unlimited")
+                );
+            }
+        }
+        for warning in forge.config().warnings().iter().chain(&expansion.warnings) {
+            println!("Warning: {}", warning);
+        }
+        return Ok(());
+    }
+    // Ctrl-C stops the run before its next nsc call instead of killing it halfway through one.
+    let cancel = CancellationToken::new();
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        }
+    });
+    let (tx, mut rx) = mpsc::channel(16);
+    let progress = tokio::spawn(async move {
+        let mut created = 0;
+        while let Some(event) = rx.recv().await {
+            created += 1;
+            eprintln!("[{}] Created {}", created, event);
+        }
+    });
+    let result = forge.with_cancellation(cancel).initialize_with_progress(tx).await;
+    progress.await?;
+    let result = result?;
+    let manifest = result.manifest().to_json();
+    if let Some(path) = &cli.manifest {
+        std::fs::write(path, &manifest).with_context(|| format!("writing {}", path.display()))?;
+    }
+    if cli.json {
+        println!("{}", manifest);
+    } else {
+        println!("Configuration generated: {:?}", result);
+    }
+    if cli.timings {
+        // Keeps stdout parseable with --json.
+        if cli.json {
+            eprintln!("\n{}", result.timings.breakdown_table());
+        } else {
+            println!("\n{}", result.timings.breakdown_table());
+        }
+    }
     Ok(())
 }
+
+fn limit(value: Option<impl std::fmt::Display>, unset: &str) -> String {
+    value.map_or_else(|| unset.to_string(), |v| v.to_string())
+}
@@ -0,0 +1,116 @@
+//! A serializable summary of what [`NatsForge::initialize`](crate::NatsForge::initialize)
+//! generated, for tools that consume a run without linking the crate.
+//!
+//! The manifest only names files, never their contents, so it holds no seeds. Fields are only
+//! added in a backwards compatible way; anything else bumps [`MANIFEST_VERSION`].
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::SetupResult;
+
+/// The schema version written to [`SetupManifest::version`].
+pub const MANIFEST_VERSION: u32 = 1;
+/// The file name `write_manifest` uses in every output directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupManifest {
+    pub version: u32,
+    /// `operator.jwt` of the first server; every server has its own copy.
+    pub operator_jwt_path: PathBuf,
+    /// The directory the backend keeps the operator and account keys in. Unless `nsc_store_dir`
+    /// is set it is removed together with the forge.
+    pub store_dir: PathBuf,
+    /// By server name.
+    pub servers: BTreeMap<String, ServerManifest>,
+    /// By account name, including the system account `SYS`.
+    pub accounts: BTreeMap<String, AccountManifest>,
+    /// Whether the creds and bearer JWT files are age-encrypted and need decrypting before use.
+    #[serde(default)]
+    pub creds_encrypted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerManifest {
+    pub config_path: PathBuf,
+    pub operator_jwt_path: PathBuf,
+    /// Where clients connect.
+    pub url: String,
+    /// The JWT directory of a full or cache resolver.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolver_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jetstream_store_dir: Option<PathBuf>,
+    /// The creds of the configured system user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sys_creds_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountManifest {
+    /// The account's public key.
+    pub id: String,
+    /// Not set for a system account the backend created rather than the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt_path: Option<PathBuf>,
+    /// `.creds` files by user name.
+    #[serde(default)]
+    pub creds: BTreeMap<String, PathBuf>,
+    /// The raw JWT files of bearer users, by user name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bearer_jwts: BTreeMap<String, PathBuf>,
+}
+
+impl SetupManifest {
+    pub fn new(result: &SetupResult) -> Self {
+        let servers = result
+            .connections
+            .iter()
+            .zip(&result.operator_jwt_paths)
+            .filter_map(|(connection, operator_jwt_path)| {
+                let name = &connection.server_name;
+                let server = ServerManifest {
+                    config_path: result.server_config(name)?.to_path_buf(),
+                    operator_jwt_path: operator_jwt_path.clone(),
+                    url: connection.url.clone(),
+                    resolver_dir: result.resolver_dir(name).map(PathBuf::from),
+                    jetstream_store_dir: result.jetstream_store_dirs.get(name).cloned(),
+                    sys_creds_path: result.sys_creds(name).map(PathBuf::from),
+                };
+                Some((name.clone(), server))
+            })
+            .collect();
+        let accounts = result
+            .account_ids
+            .iter()
+            .map(|(name, id)| {
+                let artifacts = result.account(name);
+                let account = AccountManifest {
+                    id: id.clone(),
+                    jwt_path: artifacts.map(|artifacts| artifacts.jwt_path.clone()),
+                    creds: artifacts
+                        .map(|artifacts| artifacts.creds.clone().into_iter().collect())
+                        .unwrap_or_default(),
+                    bearer_jwts: artifacts
+                        .map(|artifacts| artifacts.bearer_jwts.clone().into_iter().collect())
+                        .unwrap_or_default(),
+                };
+                (name.clone(), account)
+            })
+            .collect();
+        SetupManifest {
+            version: MANIFEST_VERSION,
+            operator_jwt_path: result.operator_jwt_path.clone(),
+            store_dir: result.store_dir.clone(),
+            servers,
+            accounts,
+            creds_encrypted: result.creds_encrypted,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("setup manifest serializes")
+    }
+}
@@ -0,0 +1,569 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use nkeys::KeyPair;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    backend::{format_creds, is_user_public_key, store_account_name, CredentialBackend},
+    config::{AccountConfig, ImportConfig, LatencySampling, OperatorConfig, UserConfig, DEFAULT_SYSTEM_ACCOUNT},
+    error::{NatsForgeError, Result},
+    validate::{parse_duration, parse_instant},
+};
+
+/// Backend that generates nkeys and signs JWTs in-process, so no `nsc` binary is required.
+///
+/// Key material only lives in memory for the lifetime of the backend, with a store per
+/// `store_dir`, so each operator of a config keeps its own accounts.
+#[derive(Default)]
+pub struct NativeBackend {
+    stores: Mutex<HashMap<PathBuf, NativeState>>,
+}
+
+#[derive(Default)]
+struct NativeState {
+    operator: Option<NativeOperator>,
+    accounts: HashMap<String, NativeAccount>,
+}
+
+struct NativeOperator {
+    name: String,
+    key: KeyPair,
+    claims: Value,
+    jwt: String,
+}
+
+struct NativeAccount {
+    key: KeyPair,
+    /// Users are issued with the first of these when there are any.
+    signing_keys: Vec<KeyPair>,
+    /// Scoped signing keys by role name.
+    roles: HashMap<String, KeyPair>,
+    /// Public keys of the issued users by name, for revoking them.
+    users: HashMap<String, String>,
+    claims: Value,
+    jwt: String,
+}
+
+impl NativeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CredentialBackend for NativeBackend {
+    async fn create_operator(&self, operator: &OperatorConfig, store_dir: &Path) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        if operator.reuse_existing {
+            return state.operator.as_ref().map(|op| op.jwt.clone()).ok_or_else(|| {
+                NatsForgeError::Backend("reuse_existing set, but no operator exists in the native backend".to_string())
+            });
+        }
+
+        let operator_key = KeyPair::new_operator();
+        let sys_key = KeyPair::new_account();
+        let sys_claims = account_claims(DEFAULT_SYSTEM_ACCOUNT, &sys_key);
+        let sys_jwt = encode_jwt(&sys_claims, &operator_key)?;
+
+        let mut operator_claims = json!({
+            "name": operator.name,
+            "sub": operator_key.public_key(),
+            "nats": {
+                "system_account": sys_key.public_key(),
+                "type": "operator",
+                "version": 2,
+            },
+        });
+        if let Some(url) = &operator.account_server_url {
+            operator_claims["nats"]["account_server_url"] = json!(url);
+        }
+        if !operator.service_urls.is_empty() {
+            operator_claims["nats"]["operator_service_urls"] = json!(operator.service_urls);
+        }
+        let operator_jwt = encode_jwt(&operator_claims, &operator_key)?;
+
+        state.accounts.clear();
+        state.accounts.insert(
+            DEFAULT_SYSTEM_ACCOUNT.to_string(),
+            NativeAccount {
+                key: sys_key,
+                signing_keys: Vec::new(),
+                roles: HashMap::new(),
+                users: HashMap::new(),
+                claims: sys_claims,
+                jwt: sys_jwt,
+            },
+        );
+        state.operator = Some(NativeOperator {
+            name: operator.name.clone(),
+            key: operator_key,
+            claims: operator_claims,
+            jwt: operator_jwt.clone(),
+        });
+        Ok(operator_jwt)
+    }
+
+    async fn operator_exists(&self, operator_name: &str, store_dir: &Path) -> Result<bool> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        Ok(state.operator.as_ref().is_some_and(|op| op.name == operator_name))
+    }
+
+    async fn account_exists(&self, account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<bool> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        Ok(state.operator.as_ref().is_some_and(|op| op.name == operator_name)
+            && state.accounts.contains_key(store_account_name(account)))
+    }
+
+    async fn system_account_jwt(&self, _operator_name: &str, store_dir: &Path) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        state
+            .accounts
+            .get(DEFAULT_SYSTEM_ACCOUNT)
+            .map(|sys| sys.jwt.clone())
+            .ok_or_else(|| NatsForgeError::Backend("Operator has not been created".to_string()))
+    }
+
+    async fn set_system_account(
+        &self,
+        account: &AccountConfig,
+        _operator_name: &str,
+        store_dir: &Path,
+    ) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let account_id = state
+            .accounts
+            .get(store_account_name(account))
+            .map(|system| system.key.public_key())
+            .ok_or_else(|| NatsForgeError::Backend(format!("Account {} has not been created", account.name)))?;
+        let operator = state
+            .operator
+            .as_mut()
+            .ok_or_else(|| NatsForgeError::Backend("Operator has not been created".to_string()))?;
+        operator.claims["nats"]["system_account"] = json!(account_id);
+        operator.jwt = encode_jwt(&operator.claims, &operator.key)?;
+        Ok(operator.jwt.clone())
+    }
+
+    async fn create_account(&self, account: &AccountConfig, _operator_name: &str, store_dir: &Path) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let key = KeyPair::new_account();
+        let mut claims = account_claims(&account.unique_name, &key);
+
+        if let Some(max_conn) = account.max_connections {
+            claims["nats"]["limits"]["conn"] = json!(max_conn);
+        }
+        // Mirrors the nsc backend, which passes max_payload as `--data`.
+        if let Some(max_payload) = account.max_payload {
+            claims["nats"]["limits"]["data"] = json!(max_payload);
+        }
+        if let Some(max_message_payload) = account.max_message_payload {
+            claims["nats"]["limits"]["payload"] = json!(max_message_payload);
+        }
+        for (limit, value) in [
+            ("leaf", account.max_leaf_connections),
+            ("exports", account.max_exports),
+            ("imports", account.max_imports),
+        ] {
+            if let Some(value) = value {
+                claims["nats"]["limits"][limit] = json!(value);
+            }
+        }
+        if let Some(allow) = account.allow_wildcard_exports {
+            claims["nats"]["limits"]["wildcards"] = json!(allow);
+        }
+        if account.disallow_bearer {
+            claims["nats"]["limits"]["disallow_bearer"] = json!(true);
+        }
+        if account.jetstream {
+            for limit in ["mem_storage", "disk_storage", "streams", "consumer"] {
+                claims["nats"]["limits"][limit] = json!(-1);
+            }
+        }
+        if let Some(expiry) = &account.expiry {
+            claims["exp"] = json!(parse_instant("expiry", expiry)?.timestamp());
+        }
+
+        let signing_keys: Vec<KeyPair> = (0..account.signing_keys.unwrap_or(0))
+            .map(|_| KeyPair::new_account())
+            .collect();
+        let roles: HashMap<String, KeyPair> = account
+            .roles
+            .iter()
+            .map(|role| (role.name.clone(), KeyPair::new_account()))
+            .collect();
+        let mut signing_key_claims: Vec<Value> = signing_keys.iter().map(|k| json!(k.public_key())).collect();
+        for role in &account.roles {
+            let mut template = json!({ "pub": {}, "sub": {}, "subs": -1, "data": -1, "payload": -1 });
+            add_permissions(
+                &mut template,
+                &["pub"],
+                "allow",
+                role.allowed_publishes.as_deref().unwrap_or_default(),
+            );
+            add_permissions(
+                &mut template,
+                &["sub"],
+                "allow",
+                role.allowed_subscribes.as_deref().unwrap_or_default(),
+            );
+            if let Some(max_payload) = role.max_payload {
+                template["payload"] = json!(max_payload);
+            }
+            signing_key_claims.push(json!({
+                "kind": "user_scope",
+                "key": roles[&role.name].public_key(),
+                "role": role.name,
+                "template": template,
+            }));
+        }
+        if !signing_key_claims.is_empty() {
+            claims["nats"]["signing_keys"] = Value::Array(signing_key_claims);
+        }
+
+        let exports: Vec<Value> = account
+            .exports
+            .iter()
+            .enumerate()
+            .map(|(i, export)| {
+                let mut claim = json!({
+                    "name": format!("export-{}", i),
+                    "subject": export.subject,
+                    "type": if export.is_service { "service" } else { "stream" },
+                });
+                if let Some(response_type) = export.response_type {
+                    claim["response_type"] = json!(response_type.as_str());
+                }
+                if let Some(description) = &export.description {
+                    claim["description"] = json!(description);
+                }
+                if let Some(position) = export.account_token_position {
+                    claim["account_token_position"] = json!(position);
+                }
+                if export.private {
+                    claim["token_req"] = json!(true);
+                }
+                if let Some(results) = &export.latency_subject {
+                    let sampling = export.latency_sampling.unwrap_or(LatencySampling::Percent(100));
+                    claim["service_latency"] = json!({ "sampling": sampling, "results": results });
+                }
+                claim
+            })
+            .collect();
+        if !exports.is_empty() {
+            claims["nats"]["exports"] = Value::Array(exports);
+        }
+        for (source, target) in &account.mappings {
+            let destinations: Vec<Value> = target
+                .destinations()
+                .into_iter()
+                .map(|(subject, weight)| json!({ "subject": subject, "weight": weight }))
+                .collect();
+            claims["nats"]["mappings"][source] = Value::Array(destinations);
+        }
+
+        let operator = state
+            .operator
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("Operator has not been created".to_string()))?;
+        let jwt = encode_jwt(&claims, &operator.key)?;
+        state.accounts.insert(
+            account.unique_name.clone(),
+            NativeAccount {
+                key,
+                signing_keys,
+                roles,
+                users: HashMap::new(),
+                claims,
+                jwt: jwt.clone(),
+            },
+        );
+        Ok(jwt)
+    }
+
+    async fn generate_activation(
+        &self,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        target_account_id: &str,
+        store_dir: &Path,
+    ) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let exporter = state
+            .accounts
+            .get(src_unique_name)
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", src_unique_name)))?;
+        let claims = json!({
+            "name": import.subject,
+            "sub": target_account_id,
+            "nats": {
+                "subject": import.subject,
+                "kind": if import.service { "service" } else { "stream" },
+                "type": "activation",
+                "version": 2,
+            },
+        });
+        encode_jwt(&claims, &exporter.key)
+    }
+
+    async fn add_import(
+        &self,
+        account: &AccountConfig,
+        import_name: &str,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        activation: Option<&str>,
+        store_dir: &Path,
+    ) -> Result<()> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let src_id = state
+            .accounts
+            .get(src_unique_name)
+            .map(|src| src.key.public_key())
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", src_unique_name)))?;
+
+        let mut entry = json!({
+            "name": import_name,
+            "subject": import.subject,
+            "account": src_id,
+            "type": if import.service { "service" } else { "stream" },
+        });
+        if let Some(local_subject) = &import.local_subject {
+            entry["local_subject"] = json!(local_subject);
+        }
+        if let Some(activation) = activation {
+            entry["token"] = json!(activation);
+        }
+        if import.share {
+            entry["share"] = json!(true);
+        }
+
+        let NativeState { operator, accounts } = state;
+        let operator = operator
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("Operator has not been created".to_string()))?;
+        let target = accounts
+            .get_mut(store_account_name(account))
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", account.unique_name)))?;
+        match target.claims["nats"]["imports"].as_array_mut() {
+            Some(imports) => imports.push(entry),
+            None => target.claims["nats"]["imports"] = json!([entry]),
+        }
+        target.jwt = encode_jwt(&target.claims, &operator.key)?;
+        Ok(())
+    }
+
+    async fn revoke_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> Result<()> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let NativeState { operator, accounts } = state;
+        let operator = operator
+            .as_ref()
+            .ok_or_else(|| NatsForgeError::Backend("Operator has not been created".to_string()))?;
+        let target = accounts
+            .get_mut(store_account_name(account))
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", account.unique_name)))?;
+        let user_key = if is_user_public_key(user) {
+            user.to_string()
+        } else {
+            target
+                .users
+                .get(user)
+                .cloned()
+                .ok_or_else(|| NatsForgeError::Backend(format!("Account {} has no user {}", account.name, user)))?
+        };
+        // Every user JWT of the key issued up to this second is revoked.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| NatsForgeError::Jwt(e.to_string()))?
+            .as_secs();
+        target.claims["nats"]["revocations"][user_key.as_str()] = json!(now);
+        target.jwt = encode_jwt(&target.claims, &operator.key)?;
+        Ok(())
+    }
+
+    async fn delete_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> Result<()> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let target = state
+            .accounts
+            .get_mut(store_account_name(account))
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", account.unique_name)))?;
+        target
+            .users
+            .remove(user)
+            .ok_or_else(|| NatsForgeError::Backend(format!("Account {} has no user {}", account.name, user)))?;
+        Ok(())
+    }
+
+    async fn account_jwt(&self, account: &AccountConfig, _operator_name: &str, store_dir: &Path) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        state
+            .accounts
+            .get(store_account_name(account))
+            .map(|a| a.jwt.clone())
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", account.unique_name)))
+    }
+
+    async fn create_user(&self, account: &AccountConfig, user: &UserConfig, store_dir: &Path) -> Result<String> {
+        let mut stores = self.stores.lock().expect("native backend state poisoned");
+        let state = stores.entry(store_dir.to_path_buf()).or_default();
+        let issuer = state
+            .accounts
+            .get_mut(store_account_name(account))
+            .ok_or_else(|| NatsForgeError::Backend(format!("Unknown account: {}", account.unique_name)))?;
+
+        let user_key = KeyPair::new_user();
+        let mut nats = json!({
+            "pub": {},
+            "sub": {},
+            "subs": -1,
+            "data": -1,
+            "payload": -1,
+            "type": "user",
+            "version": 2,
+        });
+        // Users of a scoped signing key get their permissions from the key's template.
+        if user.role.is_none() {
+            add_permissions(&mut nats, &["pub", "sub"], "allow", &user.allow_pubsub);
+            add_permissions(&mut nats, &["pub"], "allow", &user.allow_pub);
+            add_permissions(&mut nats, &["sub"], "allow", &user.allow_sub);
+            add_permissions(&mut nats, &["pub", "sub"], "deny", &user.deny_pubsub);
+            add_permissions(&mut nats, &["pub"], "deny", &user.deny_pub);
+            add_permissions(&mut nats, &["sub"], "deny", &user.deny_sub);
+            if let Some(max) = user.allow_pub_response {
+                let ttl = match &user.response_ttl {
+                    Some(ttl) => parse_duration(ttl)?.as_nanos(),
+                    None => 0,
+                };
+                nats["resp"] = json!({ "max": max, "ttl": ttl });
+            }
+            for (claim, limit) in [
+                ("subs", user.max_subscriptions),
+                ("data", user.max_data),
+                ("payload", user.max_payload),
+            ] {
+                if let Some(limit) = limit {
+                    nats[claim] = json!(limit);
+                }
+            }
+        }
+        if user.bearer {
+            nats["bearer_token"] = json!(true);
+        }
+        if !user.source_networks.is_empty() {
+            nats["src"] = json!(user.source_networks);
+        }
+        if !user.times.is_empty() {
+            nats["times"] = json!(user.times);
+        }
+        if let Some(locale) = &user.locale {
+            nats["times_location"] = json!(locale);
+        }
+
+        let mut claims = json!({
+            "name": user.name,
+            "sub": user_key.public_key(),
+            "nats": nats,
+        });
+        if let Some(expiry) = &user.expiry {
+            claims["exp"] = json!(parse_instant("expiry", expiry)?.timestamp());
+        }
+        if let Some(start) = &user.start {
+            claims["nbf"] = json!(parse_instant("start", start)?.timestamp());
+        }
+
+        let signing_key =
+            match &user.role {
+                Some(role) => Some(issuer.roles.get(role).ok_or_else(|| {
+                    NatsForgeError::Backend(format!("Account {} has no role {}", account.name, role))
+                })?),
+                None => issuer.signing_keys.first(),
+            };
+        let signer = match signing_key {
+            Some(signing_key) => {
+                claims["nats"]["issuer_account"] = json!(issuer.key.public_key());
+                signing_key
+            }
+            None => &issuer.key,
+        };
+        let jwt = encode_jwt(&claims, signer)?;
+        let seed = user_key
+            .seed()
+            .map_err(|e| NatsForgeError::Backend(format!("Failed to encode seed for user {}: {}", user.name, e)))?;
+        issuer.users.insert(user.name.clone(), user_key.public_key());
+        Ok(format_creds(&jwt, &seed))
+    }
+}
+
+fn account_claims(name: &str, key: &KeyPair) -> Value {
+    json!({
+        "name": name,
+        "sub": key.public_key(),
+        "nats": {
+            "limits": {
+                "subs": -1,
+                "data": -1,
+                "payload": -1,
+                "imports": -1,
+                "exports": -1,
+                "wildcards": true,
+                "conn": -1,
+                "leaf": -1,
+            },
+            "default_permissions": { "pub": {}, "sub": {} },
+            "type": "account",
+            "version": 2,
+        },
+    })
+}
+
+fn add_permissions(nats: &mut Value, directions: &[&str], kind: &str, subjects: &[String]) {
+    if subjects.is_empty() {
+        return;
+    }
+    for direction in directions {
+        let list = &mut nats[*direction][kind];
+        if list.is_null() {
+            *list = json!([]);
+        }
+        if let Some(list) = list.as_array_mut() {
+            list.extend(subjects.iter().map(|s| json!(s)));
+        }
+    }
+}
+
+fn encode_jwt(claims: &Value, signer: &KeyPair) -> Result<String> {
+    let mut claims = claims.clone();
+    let iat = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| NatsForgeError::Jwt(e.to_string()))?
+        .as_secs();
+    claims["iat"] = json!(iat);
+    claims["iss"] = json!(signer.public_key());
+    claims["jti"] = json!(Uuid::new_v4().simple().to_string().to_uppercase());
+
+    let header = BASE64_URL.encode(r#"{"typ":"JWT","alg":"ed25519-nkey"}"#);
+    let payload = BASE64_URL.encode(serde_json::to_vec(&claims).map_err(|e| NatsForgeError::Jwt(e.to_string()))?);
+    let signing_input = format!("{}.{}", header, payload);
+    let signature = signer
+        .sign(signing_input.as_bytes())
+        .map_err(|e| NatsForgeError::Jwt(format!("Failed to sign JWT: {}", e)))?;
+    Ok(format!("{}.{}", signing_input, BASE64_URL.encode(signature)))
+}
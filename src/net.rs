@@ -0,0 +1,203 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::{
+    config::ClusterAuth,
+    error::{NatsForgeError, Result},
+};
+
+/// Strips the surrounding brackets from an IPv6 literal such as `[::1]`.
+pub(crate) fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host)
+}
+
+/// Returns true when `host` is an IPv6 literal, bracketed or bare.
+pub fn is_ipv6_literal(host: &str) -> bool {
+    strip_brackets(host).parse::<Ipv6Addr>().is_ok()
+}
+
+/// Checks that a listen host is either a hostname, an IPv4 address or a well-formed IPv6 literal.
+pub fn validate_host(host: &str) -> Result<()> {
+    if host.is_empty() {
+        return Err(NatsForgeError::InvalidConfig("Host must not be empty".to_string()));
+    }
+    let bare = strip_brackets(host);
+    if host.contains(':') || host.starts_with('[') {
+        return bare
+            .parse::<Ipv6Addr>()
+            .map(|_| ())
+            .map_err(|_| NatsForgeError::InvalidConfig(format!("Malformed IPv6 literal: {}", host)));
+    }
+    // Anything made of digits and dots has to be an IPv4 address, so `10.0.0.256` is not taken
+    // for a hostname.
+    if host.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return host
+            .parse::<Ipv4Addr>()
+            .map(|_| ())
+            .map_err(|_| NatsForgeError::InvalidConfig(format!("Malformed IPv4 address: {}", host)));
+    }
+    let valid_label = |label: &str| {
+        (1..=63).contains(&label.len())
+            && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+    if host.len() > 253 || !host.trim_end_matches('.').split('.').all(valid_label) {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "Not an IP address or hostname: {}",
+            host
+        )));
+    }
+    Ok(())
+}
+
+/// Checks an advertised address: a host as accepted by [`validate_host`], optionally followed by
+/// `:port`. IPv6 literals need brackets when a port is given, e.g. `[2001:db8::1]:4222`.
+pub fn validate_advertise(advertise: &str) -> Result<()> {
+    let (host, port) = if let Some(bracketed) = advertise.strip_prefix('[') {
+        let (host, after) = bracketed
+            .split_once(']')
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Malformed IPv6 literal: {}", advertise)))?;
+        match after {
+            "" => (host, None),
+            _ => (
+                host,
+                Some(after.strip_prefix(':').ok_or_else(|| {
+                    NatsForgeError::InvalidConfig(format!("Unexpected {:?} after the host in {}", after, advertise))
+                })?),
+            ),
+        }
+    } else if advertise.matches(':').count() > 1 {
+        (advertise, None)
+    } else {
+        match advertise.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (advertise, None),
+        }
+    };
+    validate_host(host)?;
+    if let Some(port) = port {
+        if !port.bytes().all(|b| b.is_ascii_digit()) || port.parse::<u16>().map_or(true, |port| port == 0) {
+            return Err(NatsForgeError::InvalidConfig(format!("Invalid port in {}", advertise)));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `cidr` is an IPv4 or IPv6 network in CIDR notation, such as `10.8.0.0/16`.
+pub fn validate_cidr(cidr: &str) -> Result<()> {
+    let invalid = || NatsForgeError::InvalidConfig(format!("Invalid CIDR: {}", cidr));
+    let (address, prefix) = cidr.split_once('/').ok_or_else(invalid)?;
+    let max_prefix = match address.parse::<IpAddr>().map_err(|_| invalid())? {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    if !prefix.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    match prefix.parse::<u8>() {
+        Ok(prefix) if prefix <= max_prefix => Ok(()),
+        _ => Err(invalid()),
+    }
+}
+
+/// Renders a host for nats.conf, where IPv6 literals are written without brackets.
+pub fn conf_host(host: &str) -> &str {
+    strip_brackets(host)
+}
+
+/// Joins a host and port, bracketing IPv6 literals as URLs require.
+pub fn host_port(host: &str, port: u16) -> String {
+    let bare = strip_brackets(host);
+    if bare.parse::<Ipv6Addr>().is_ok() {
+        format!("[{}]:{}", bare, port)
+    } else {
+        format!("{}:{}", bare, port)
+    }
+}
+
+/// Returns the host a local client should dial for a server listening on `host`.
+pub(crate) fn client_host(host: Option<&str>) -> String {
+    match host.map(strip_brackets) {
+        None => "localhost".to_string(),
+        Some(h) => match h.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) if ip.is_unspecified() => "127.0.0.1".to_string(),
+            Ok(IpAddr::V6(ip)) if ip.is_unspecified() => "::1".to_string(),
+            _ => h.to_string(),
+        },
+    }
+}
+
+/// Normalizes a NATS URL: adds the `nats://` scheme when missing and brackets bare IPv6 hosts.
+///
+/// `[::1]:4222`, `nats://[::1]:4222` and `::1` are all accepted. A bare IPv6 literal is always read
+/// as an address without a port, so ports must be given with the bracketed form.
+pub fn normalize_url(url: &str) -> Result<String> {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (scheme, rest),
+        None => ("nats", url),
+    };
+    if scheme.is_empty() || rest.is_empty() {
+        return Err(NatsForgeError::InvalidConfig(format!("Invalid URL: {}", url)));
+    }
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    let (userinfo, hostport) = match authority.rsplit_once('@') {
+        Some((userinfo, hostport)) => (format!("{}@", userinfo), hostport),
+        None => (String::new(), authority),
+    };
+
+    let hostport = if let Some(bracketed) = hostport.strip_prefix('[') {
+        let (host, after) = bracketed
+            .split_once(']')
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("Unterminated IPv6 literal in URL: {}", url)))?;
+        host.parse::<Ipv6Addr>()
+            .map_err(|_| NatsForgeError::InvalidConfig(format!("Malformed IPv6 literal in URL: {}", url)))?;
+        if !after.is_empty() {
+            parse_port(after.strip_prefix(':').unwrap_or("x"), url)?;
+        }
+        format!("[{}]{}", host, after)
+    } else if hostport.matches(':').count() > 1 {
+        hostport
+            .parse::<Ipv6Addr>()
+            .map_err(|_| NatsForgeError::InvalidConfig(format!("Malformed IPv6 literal in URL: {}", url)))?;
+        format!("[{}]", hostport)
+    } else {
+        if let Some((host, port)) = hostport.split_once(':') {
+            if host.is_empty() {
+                return Err(NatsForgeError::InvalidConfig(format!("Missing host in URL: {}", url)));
+            }
+            parse_port(port, url)?;
+        }
+        hostport.to_string()
+    };
+
+    Ok(format!("{}://{}{}{}", scheme, userinfo, hostport, path))
+}
+
+/// A route URL as nats.conf takes it: with the `nats-route://` scheme, and with `auth` as its
+/// credentials unless it has its own.
+pub(crate) fn route_url(route: &str, auth: Option<&ClusterAuth>) -> String {
+    let (scheme, rest) = route.split_once("://").unwrap_or(("nats-route", route));
+    match auth {
+        Some(auth) if !rest.split('/').next().unwrap_or_default().contains('@') => {
+            format!("{}://{}:{}@{}", scheme, auth.user, auth.password, rest)
+        }
+        _ => format!("{}://{}", scheme, rest),
+    }
+}
+
+/// The host and port of a URL, with IPv6 hosts unbracketed. `None` without a port.
+pub(crate) fn url_host_port(url: &str) -> Option<(String, u16)> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next()?;
+    let hostport = authority.rsplit_once('@').map_or(authority, |(_, hostport)| hostport);
+    let (host, port) = hostport.rsplit_once(':')?;
+    Some((strip_brackets(host).to_string(), port.parse().ok()?))
+}
+
+fn parse_port(port: &str, url: &str) -> Result<u16> {
+    port.parse::<u16>()
+        .map_err(|_| NatsForgeError::InvalidConfig(format!("Invalid port in URL: {}", url)))
+}
@@ -1,57 +1,731 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Output,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
-use anyhow::{Context, Result};
-use base64::{engine::general_purpose::STANDARD_NO_PAD as BASE64, Engine};
+use async_trait::async_trait;
 use tokio::process::Command;
 
-use crate::config::{AccountConfig, OperatorConfig, UserConfig};
+use crate::{
+    backend::{format_creds, is_user_public_key, store_account_name, CredentialBackend},
+    config::{
+        AccountConfig, ImportConfig, LatencySampling, OperatorConfig, PushReport, PushedAccount, RoleConfig,
+        UserConfig, DEFAULT_SYSTEM_ACCOUNT,
+    },
+    env::{self, ProcessEnv},
+    error::{NatsForgeError, Result},
+    jwt::{decode_account_claims, SigningKey},
+    timing::{PhaseTiming, TimingRecorder, PHASE_EDITS, PHASE_EXPORTS},
+    DEFAULT_NSC_RETRIES, DEFAULT_NSC_TIMEOUT,
+};
 
-pub async fn create_operator(operator: &OperatorConfig, store_dir: &PathBuf) -> Result<String> {
+/// Oldest nsc release whose flags (`--remote-subject`, `--local-subject`, ...) we rely on.
+pub(crate) const MIN_NSC_VERSION: (u32, u32, u32) = (2, 8, 0);
+
+/// How long to wait before retrying a failed read-only nsc command.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Flags whose value may be a seed; their values are left out of error messages.
+const SECRET_FLAGS: &[&str] = &["-K", "--private-key"];
+
+/// Runs nsc. [`NscBackend`] shells out to the nsc binary unless it is given another runner with
+/// [`NscBackend::with_runner`], e.g. one returning canned output in tests.
+#[async_trait]
+pub trait NscRunner: Send + Sync {
+    /// Runs nsc once with `args` and returns how it exited. A non-zero exit is not an error here;
+    /// only failing to run nsc at all is.
+    async fn run(&self, args: &[String]) -> Result<Output>;
+}
+
+/// Runs an external program with a controlled environment.
+pub(crate) struct ProcessRunner {
+    program: PathBuf,
+    env: Option<ProcessEnv>,
+}
+
+impl ProcessRunner {
+    /// The program inherits the forge's full environment until [`ProcessRunner::with_env`] is used.
+    pub(crate) fn new(program: impl Into<PathBuf>) -> Self {
+        ProcessRunner {
+            program: program.into(),
+            env: None,
+        }
+    }
+
+    pub(crate) fn with_env(mut self, env: ProcessEnv) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Runs the program with `args`. The process is killed when the returned future is dropped,
+    /// e.g. by a timeout.
+    pub(crate) async fn output<I, S>(&self, args: I) -> std::io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command(args).output().await
+    }
+
+    fn command<I, S>(&self, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(&self.program);
+        command.args(args).kill_on_drop(true);
+        if let Some(env) = &self.env {
+            env.apply(&mut command);
+        }
+        command
+    }
+}
+
+#[async_trait]
+impl NscRunner for ProcessRunner {
+    /// Also moves nsc's own directories to the [`NscHome`] of the `--keystore-dir` in `args`.
+    async fn run(&self, args: &[String]) -> Result<Output> {
+        let mut command = self.command(args);
+        let keystore_dir = args
+            .iter()
+            .position(|arg| arg == "--keystore-dir")
+            .and_then(|i| args.get(i + 1));
+        if let Some(keystore_dir) = keystore_dir {
+            NscHome::of_keystore(Path::new(keystore_dir)).apply(&mut command)?;
+        }
+        command.output().await.map_err(|source| {
+            if source.kind() == std::io::ErrorKind::NotFound {
+                NatsForgeError::NscNotFound {
+                    resolution: self.program.display().to_string(),
+                    source,
+                }
+            } else {
+                NatsForgeError::Io {
+                    path: self.program.clone(),
+                    source,
+                }
+            }
+        })
+    }
+}
+
+/// Runs nsc commands through an [`NscRunner`]: keeps track of how long they took, per label,
+/// applies the timeout and retries, and turns failed runs into errors.
+pub(crate) struct Nsc {
+    runner: Box<dyn NscRunner>,
+    /// The program, as it is named in error messages.
+    program: PathBuf,
+    timeout: Option<Duration>,
+    retries: u32,
+    times: TimingRecorder,
+    /// Time spent in the steps of `create_account`, by step.
+    steps: TimingRecorder,
+    exclusive: tokio::sync::Mutex<()>,
+}
+
+impl Nsc {
+    fn new(runner: Box<dyn NscRunner>, program: PathBuf) -> Self {
+        Nsc {
+            runner,
+            program,
+            timeout: None,
+            retries: 0,
+            times: TimingRecorder::default(),
+            steps: TimingRecorder::default(),
+            exclusive: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Runs nsc with `args`. Given the `store_dir` nsc works on, nsc's own directories are moved
+    /// under it as well; see [`NscHome`]. A non-zero exit is not an error.
+    async fn output<I, S>(&self, label: &str, store_dir: Option<&Path>, args: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args = command_args(store_dir, args);
+        let start = Instant::now();
+        let output = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.runner.run(&args))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(NatsForgeError::NscTimedOut {
+                        command: label.to_string(),
+                        argv: self.argv(&args),
+                        timeout,
+                    })
+                }),
+            None => self.runner.run(&args).await,
+        };
+        self.times.record(label, start.elapsed());
+        output
+    }
+
+    /// Like [`Nsc::output`], but treats a non-zero exit as an error.
+    ///
+    /// Only for commands that don't modify the store, like `generate creds`: a run that fails or
+    /// times out is retried, and these don't need to wait for exclusive commands either.
+    async fn nsc<I, S>(&self, label: &str, store_dir: &Path, args: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().to_string()).collect();
+        let mut attempt = 0;
+        loop {
+            match self.nsc_once(label, store_dir, &args).await {
+                Err(err @ (NatsForgeError::NscCommandFailed { .. } | NatsForgeError::NscTimedOut { .. }))
+                    if attempt < self.retries =>
+                {
+                    attempt += 1;
+                    eprintln!("Retrying nsc {} ({}/{}) after: {}", label, attempt, self.retries, err);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn nsc_once(&self, label: &str, store_dir: &Path, args: &[String]) -> Result<Output> {
+        let output = self.output(label, Some(store_dir), args).await?;
+        if !output.status.success() {
+            return Err(self.failure(label, &command_args(Some(store_dir), args), &output));
+        }
+        Ok(output)
+    }
+
+    /// Runs a command that modifies the store (and nsc's current-context config). These race
+    /// when run in parallel, so they never run concurrently with another exclusive command; they
+    /// aren't retried either, since a failed run may have changed the store halfway.
+    async fn nsc_exclusive<I, S>(&self, label: &str, store_dir: &Path, args: I) -> Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().to_string()).collect();
+        let _guard = self.exclusive.lock().await;
+        self.nsc_once(label, store_dir, &args).await
+    }
+
+    /// The command line of an invocation with `args`, for error messages, with seeds redacted.
+    fn argv(&self, args: &[String]) -> Vec<String> {
+        let mut argv = vec![self.program.display().to_string()];
+        let mut secret_value = false;
+        for arg in args {
+            let (flag, inline_value) = arg.split_once('=').unwrap_or((arg, ""));
+            argv.push(if secret_value || env::is_secret_value(arg) {
+                "<redacted>".to_string()
+            } else if SECRET_FLAGS.contains(&flag) && !inline_value.is_empty() {
+                format!("{}=<redacted>", flag)
+            } else {
+                arg.clone()
+            });
+            secret_value = SECRET_FLAGS.contains(&arg.as_str());
+        }
+        argv
+    }
+
+    /// The error for an invocation with `args` that exited unsuccessfully.
+    fn failure(&self, label: &str, args: &[String], output: &Output) -> NatsForgeError {
+        NatsForgeError::NscCommandFailed {
+            command: label.to_string(),
+            argv: self.argv(args),
+            exit_code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+    }
+
+    /// Time spent in nsc so far, by label.
+    fn times(&self) -> Vec<PhaseTiming> {
+        self.times.snapshot()
+    }
+
+    /// Time spent in account edits and exports so far, by step.
+    fn step_times(&self) -> Vec<PhaseTiming> {
+        self.steps.snapshot()
+    }
+}
+
+/// The arguments nsc is run with: `args`, and when it works on `store_dir` the keystore of its
+/// [`NscHome`].
+fn command_args<I, S>(store_dir: Option<&Path>, args: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut command_args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().to_string()).collect();
+    if let Some(store_dir) = store_dir {
+        command_args.push("--keystore-dir".to_string());
+        command_args.push(NscHome::new(store_dir).keystore_dir().display().to_string());
+    }
+    command_args
+}
+
+/// The error for nsc output that ran fine but can't be used.
+fn unusable_output(command: &str, message: String) -> NatsForgeError {
+    NatsForgeError::NscCommandFailed {
+        command: command.to_string(),
+        argv: Vec::new(),
+        exit_code: None,
+        stdout: String::new(),
+        stderr: message,
+    }
+}
+
+/// Where nsc keeps its own state for a store: its home with the current context, its keystore,
+/// and the XDG directories it falls back to. All of it lives under the store, so nsc never writes
+/// into the user's `~/.local/share/nats/nsc`, and forges with different stores don't share a
+/// keystore.
+pub(crate) struct NscHome {
+    root: PathBuf,
+}
+
+impl NscHome {
+    pub(crate) fn new(store_dir: &Path) -> Self {
+        NscHome {
+            root: store_dir.join(".nsc-home"),
+        }
+    }
+
+    /// The home whose keystore is `keystore_dir`.
+    fn of_keystore(keystore_dir: &Path) -> Self {
+        NscHome {
+            root: keystore_dir.parent().unwrap_or(keystore_dir).to_path_buf(),
+        }
+    }
+
+    pub(crate) fn keystore_dir(&self) -> PathBuf {
+        self.root.join("keys")
+    }
+
+    /// Points the command's nsc at these directories through its environment, creating them
+    /// first. The keystore is passed as `--keystore-dir` too; see [`command_args`].
+    fn apply(&self, command: &mut Command) -> Result<()> {
+        let dirs = [
+            ("NSC_HOME", self.root.join("home")),
+            ("NKEYS_PATH", self.keystore_dir()),
+            ("XDG_DATA_HOME", self.root.join("data")),
+            ("XDG_CONFIG_HOME", self.root.join("config")),
+        ];
+        for (name, dir) in dirs {
+            std::fs::create_dir_all(&dir).map_err(NatsForgeError::io(&dir))?;
+            command.env(name, dir);
+        }
+        Ok(())
+    }
+}
+
+/// Backend that shells out to the `nsc` binary.
+pub struct NscBackend {
+    runner: Nsc,
+    configured: bool,
+    /// The signing key users of an account are issued with, by the account's store name.
+    signing_keys: Mutex<HashMap<String, String>>,
+}
+
+impl NscBackend {
+    /// Uses `nsc_path` when given, otherwise whatever `nsc` resolves to on PATH, with the
+    /// default timeout and retries.
+    pub fn new(nsc_path: Option<PathBuf>) -> Self {
+        let (program, configured) = match nsc_path {
+            Some(nsc) => (nsc, true),
+            None => (PathBuf::from("nsc"), false),
+        };
+        let mut runner = Nsc::new(Box::new(ProcessRunner::new(&program)), program);
+        runner.timeout = Some(DEFAULT_NSC_TIMEOUT);
+        runner.retries = DEFAULT_NSC_RETRIES;
+        NscBackend {
+            runner,
+            configured,
+            signing_keys: Mutex::default(),
+        }
+    }
+
+    /// Runs nsc with exactly `env` instead of the forge's full environment.
+    pub fn with_env(mut self, env: ProcessEnv) -> Self {
+        self.runner.runner = Box::new(ProcessRunner::new(&self.runner.program).with_env(env));
+        self
+    }
+
+    /// Runs nsc commands through `runner` instead of the nsc binary, e.g. to test what the
+    /// backend asks of nsc without nsc installed. Replaces the environment set by
+    /// [`NscBackend::with_env`].
+    pub fn with_runner(mut self, runner: Box<dyn NscRunner>) -> Self {
+        self.runner.runner = runner;
+        self
+    }
+
+    /// Kills any nsc invocation that runs longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.runner.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs commands that only read the store again, up to `retries` times, after they failed or
+    /// timed out.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.runner.retries = retries;
+        self
+    }
+
+    fn resolution(&self) -> String {
+        if self.configured {
+            format!("{} (configured via nsc_path)", self.runner.program.display())
+        } else {
+            format!("{} (resolved from PATH)", self.runner.program.display())
+        }
+    }
+
+    /// Runs `nsc --version` and checks it against [`MIN_NSC_VERSION`], returning the version string.
+    pub async fn check_version(&self) -> Result<String> {
+        let args = ["--version"];
+        let output = self
+            .runner
+            .output("version", None, args)
+            .await
+            .map_err(|err| match err {
+                NatsForgeError::NscNotFound { source, .. } | NatsForgeError::Io { source, .. } => {
+                    NatsForgeError::NscNotFound {
+                        resolution: self.resolution(),
+                        source,
+                    }
+                }
+                err => err,
+            })?;
+        if !output.status.success() {
+            return Err(NatsForgeError::NscCommandFailed {
+                command: "--version".to_string(),
+                argv: self.runner.argv(&command_args(None, args)),
+                exit_code: output.status.code(),
+                stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                stderr: format!(
+                    "{} ({})",
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                    self.resolution()
+                ),
+            });
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = parse_version(&stdout).ok_or_else(|| {
+            unusable_output(
+                "--version",
+                format!(
+                    "could not parse version from {:?} ({})",
+                    stdout.trim(),
+                    self.resolution()
+                ),
+            )
+        })?;
+        if version < MIN_NSC_VERSION {
+            return Err(NatsForgeError::NscVersionUnsupported {
+                version: format_version(version),
+                minimum: format_version(MIN_NSC_VERSION),
+                resolution: self.resolution(),
+            });
+        }
+        Ok(format_version(version))
+    }
+}
+
+fn format_version((major, minor, patch): (u32, u32, u32)) -> String {
+    format!("{}.{}.{}", major, minor, patch)
+}
+
+/// Extracts `major.minor.patch` from output like `nsc version 2.10.2` or `nsc version v2.8.6`.
+fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    let token = output
+        .split_whitespace()
+        .find(|t| t.trim_start_matches('v').starts_with(|c: char| c.is_ascii_digit()))?;
+    let mut parts = token.trim_start_matches('v').split('.').map(|p| {
+        p.chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse::<u32>()
+    });
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    let patch = parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+#[async_trait]
+impl CredentialBackend for NscBackend {
+    async fn preflight(&self) -> Result<()> {
+        let version = self.check_version().await?;
+        eprintln!("Using nsc {} at {}", version, self.resolution());
+        Ok(())
+    }
+
+    fn process_times(&self) -> Vec<PhaseTiming> {
+        self.runner
+            .times()
+            .into_iter()
+            .map(|t| PhaseTiming {
+                name: format!("nsc {}", t.name),
+                duration: t.duration,
+            })
+            .collect()
+    }
+
+    fn step_times(&self) -> Vec<PhaseTiming> {
+        self.runner.step_times()
+    }
+
+    async fn create_operator(&self, operator: &OperatorConfig, store_dir: &Path) -> Result<String> {
+        create_operator(&self.runner, operator, &store_dir.to_path_buf()).await
+    }
+
+    async fn operator_exists(&self, operator_name: &str, store_dir: &Path) -> Result<bool> {
+        Ok(store_dir
+            .join(operator_name)
+            .join(format!("{}.jwt", operator_name))
+            .exists())
+    }
+
+    async fn account_exists(&self, account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<bool> {
+        let name = store_account_name(account);
+        Ok(store_dir
+            .join(operator_name)
+            .join("accounts")
+            .join(name)
+            .join(format!("{}.jwt", name))
+            .exists())
+    }
+
+    async fn system_account_jwt(&self, operator_name: &str, store_dir: &Path) -> Result<String> {
+        let sys_jwt_path = store_dir
+            .join(operator_name)
+            .join("accounts")
+            .join(DEFAULT_SYSTEM_ACCOUNT)
+            .join(format!("{}.jwt", DEFAULT_SYSTEM_ACCOUNT));
+        std::fs::read_to_string(&sys_jwt_path).map_err(NatsForgeError::io(&sys_jwt_path))
+    }
+
+    async fn set_system_account(
+        &self,
+        account: &AccountConfig,
+        operator_name: &str,
+        store_dir: &Path,
+    ) -> Result<String> {
+        let store_path = store_dir.to_str().unwrap();
+        let args = [
+            "edit",
+            "operator",
+            "--system-account",
+            store_account_name(account),
+            "--data-dir",
+            store_path,
+        ];
+        self.runner.nsc_exclusive("edit operator", store_dir, args).await?;
+        let operator_jwt_path = store_dir.join(operator_name).join(format!("{}.jwt", operator_name));
+        std::fs::read_to_string(&operator_jwt_path).map_err(NatsForgeError::io(&operator_jwt_path))
+    }
+
+    async fn create_account(&self, account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<String> {
+        let jwt = create_account(&self.runner, account, operator_name, store_dir).await?;
+        // Scoped keys are listed as objects and only used for users with their role.
+        let claims = decode_account_claims(&jwt)?;
+        let unscoped = claims.nats.signing_keys.into_iter().find_map(|key| match key {
+            SigningKey::Plain(key) => Some(key),
+            SigningKey::Scoped { .. } => None,
+        });
+        if let Some(key) = unscoped {
+            self.signing_keys
+                .lock()
+                .expect("signing keys poisoned")
+                .insert(account.unique_name.clone(), key);
+        }
+        Ok(jwt)
+    }
+
+    async fn generate_activation(
+        &self,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        target_account_id: &str,
+        store_dir: &Path,
+    ) -> Result<String> {
+        generate_activation(&self.runner, import, src_unique_name, target_account_id, store_dir).await
+    }
+
+    async fn add_import(
+        &self,
+        account: &AccountConfig,
+        import_name: &str,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        activation: Option<&str>,
+        store_dir: &Path,
+    ) -> Result<()> {
+        add_import(
+            &self.runner,
+            account,
+            import_name,
+            import,
+            src_unique_name,
+            activation,
+            store_dir,
+        )
+        .await
+    }
+
+    async fn revoke_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> Result<()> {
+        let user_flag = if is_user_public_key(user) {
+            "--user-public-key"
+        } else {
+            "--name"
+        };
+        let revoke_args = [
+            "revocations",
+            "add-user",
+            "--account",
+            store_account_name(account),
+            user_flag,
+            user,
+            "--data-dir",
+            store_dir.to_str().unwrap(),
+        ];
+        self.runner
+            .nsc_exclusive("revocations add-user", store_dir, revoke_args)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> Result<()> {
+        // The old nkey and creds go too, so they can't be picked up from the store later.
+        let delete_args = [
+            "delete",
+            "user",
+            "--account",
+            store_account_name(account),
+            "--name",
+            user,
+            "--rm-nkey",
+            "--rm-creds",
+            "--data-dir",
+            store_dir.to_str().unwrap(),
+        ];
+        self.runner.nsc_exclusive("delete user", store_dir, delete_args).await?;
+        Ok(())
+    }
+
+    async fn push_accounts(&self, _operator_name: &str, url: &str, store_dir: &Path) -> Result<PushReport> {
+        let push_args = [
+            "push",
+            "--all",
+            "--account-jwt-server-url",
+            url,
+            "--data-dir",
+            store_dir.to_str().unwrap(),
+        ];
+        // nsc exits non-zero when any account is rejected, but still reports every account.
+        let output = self.runner.output("push", Some(store_dir), push_args).await?;
+        let text = format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let report = parse_push_output(&text);
+        if report.accounts.is_empty() && !output.status.success() {
+            return Err(self
+                .runner
+                .failure("push", &command_args(Some(store_dir), push_args), &output));
+        }
+        Ok(report)
+    }
+
+    async fn account_jwt(&self, account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<String> {
+        let name = store_account_name(account);
+        let account_jwt_path = store_dir
+            .join(operator_name)
+            .join("accounts")
+            .join(name)
+            .join(format!("{}.jwt", name));
+        std::fs::read_to_string(&account_jwt_path).map_err(NatsForgeError::io(&account_jwt_path))
+    }
+
+    async fn create_user(&self, account: &AccountConfig, user: &UserConfig, store_dir: &Path) -> Result<String> {
+        let signing_key = user.role.clone().or_else(|| {
+            self.signing_keys
+                .lock()
+                .expect("signing keys poisoned")
+                .get(store_account_name(account))
+                .cloned()
+        });
+        create_user(&self.runner, account, user, signing_key.as_deref(), store_dir).await
+    }
+}
+
+pub(crate) async fn create_operator(runner: &Nsc, operator: &OperatorConfig, store_dir: &PathBuf) -> Result<String> {
     if operator.reuse_existing {
         let operator_jwt_path = store_dir.join(&operator.name).join(format!("{}.jwt", &operator.name));
         if operator_jwt_path.exists() {
-            return std::fs::read_to_string(&operator_jwt_path).context("Failed to read existing operator JWT");
+            return std::fs::read_to_string(&operator_jwt_path).map_err(NatsForgeError::io(&operator_jwt_path));
         } else {
-            return Err(anyhow::anyhow!(
+            return Err(NatsForgeError::Backend(format!(
                 "reuse_existing set, but no operator JWT found at {}",
                 operator_jwt_path.display()
-            ));
+            )));
         }
     }
 
     let store_path = store_dir.to_str().unwrap();
-    std::fs::create_dir_all(store_dir).context("Failed to create store directory")?;
+    std::fs::create_dir_all(store_dir).map_err(NatsForgeError::io(store_dir))?;
 
-    let output = Command::new("nsc")
-        .args([
+    runner
+        .nsc_exclusive(
             "init",
-            "--name",
-            &operator.name,
-            "--dir",
-            store_path,
-            "--data-dir",
-            store_path,
-        ])
-        .output()
-        .await
-        .context("Failed to run nsc init")?;
+            store_dir,
+            [
+                "init",
+                "--name",
+                &operator.name,
+                "--dir",
+                store_path,
+                "--data-dir",
+                store_path,
+            ],
+        )
+        .await?;
 
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "nsc init failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
+    if operator.account_server_url.is_some() || !operator.service_urls.is_empty() {
+        let mut edit_args = vec![
+            "edit".to_string(),
+            "operator".to_string(),
+            "--data-dir".to_string(),
+            store_path.to_string(),
+        ];
+        if let Some(url) = &operator.account_server_url {
+            edit_args.push("--account-jwt-server-url".to_string());
+            edit_args.push(url.clone());
+        }
+        for url in &operator.service_urls {
+            edit_args.push("--service-url".to_string());
+            edit_args.push(url.clone());
+        }
+        runner.nsc_exclusive("edit operator", store_dir, &edit_args).await?;
     }
 
     let operator_jwt_path = store_dir.join(&operator.name).join(format!("{}.jwt", &operator.name));
     if let Some(parent) = operator_jwt_path.parent() {
-        std::fs::create_dir_all(parent).context("Failed to create operator JWT directory")?;
+        std::fs::create_dir_all(parent).map_err(NatsForgeError::io(parent))?;
     }
 
-    std::fs::read_to_string(&operator_jwt_path).context("Failed to read operator JWT")
+    std::fs::read_to_string(&operator_jwt_path).map_err(NatsForgeError::io(&operator_jwt_path))
 }
 
-pub async fn create_account(account: &AccountConfig, operator_name: &str, store_dir: &Path) -> Result<String> {
+pub(crate) async fn create_account(
+    runner: &Nsc,
+    account: &AccountConfig,
+    operator_name: &str,
+    store_dir: &Path,
+) -> Result<String> {
     let store_path = store_dir.to_str().unwrap();
     let args = vec![
         "add".to_string(),
@@ -62,18 +736,7 @@ pub async fn create_account(account: &AccountConfig, operator_name: &str, store_
         store_path.to_string(),
     ];
 
-    let output = Command::new("nsc")
-        .args(&args)
-        .output()
-        .await
-        .context(format!("Failed to run nsc add account {}", account.unique_name))?;
-
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "nsc add account failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
-    }
+    runner.nsc_exclusive("add account", store_dir, &args).await?;
 
     let mut edit_args = vec![
         "edit".to_string(),
@@ -97,18 +760,66 @@ pub async fn create_account(account: &AccountConfig, operator_name: &str, store_
         should_edit = true;
     }
 
-    if should_edit {
-        let edit_output = Command::new("nsc")
-            .args(&edit_args)
-            .output()
-            .await
-            .context(format!("Failed to run nsc edit account {}", account.unique_name))?;
-        if !edit_output.status.success() {
-            return Err(anyhow::anyhow!(
-                "nsc edit account failed: {}",
-                String::from_utf8_lossy(&edit_output.stderr)
-            ));
+    if let Some(max_message_payload) = account.max_message_payload {
+        edit_args.push("--payload".to_string());
+        edit_args.push(max_message_payload.to_string());
+        should_edit = true;
+    }
+
+    for (flag, limit) in [
+        ("--leaf-conns", account.max_leaf_connections),
+        ("--exports", account.max_exports),
+        ("--imports", account.max_imports),
+    ] {
+        if let Some(limit) = limit {
+            edit_args.push(flag.to_string());
+            edit_args.push(limit.to_string());
+            should_edit = true;
+        }
+    }
+
+    if let Some(allow) = account.allow_wildcard_exports {
+        edit_args.push(format!("--wildcard-exports={}", allow));
+        should_edit = true;
+    }
+
+    if let Some(expiry) = &account.expiry {
+        edit_args.push("--expiry".to_string());
+        edit_args.push(nsc_instant(expiry));
+        should_edit = true;
+    }
+
+    if account.disallow_bearer {
+        edit_args.push("--disallow-bearer".to_string());
+        should_edit = true;
+    }
+
+    if account.jetstream {
+        for flag in ["--js-mem-storage", "--js-disk-storage", "--js-streams", "--js-consumer"] {
+            edit_args.push(flag.to_string());
+            edit_args.push("-1".to_string());
         }
+        should_edit = true;
+    }
+
+    for _ in 0..account.signing_keys.unwrap_or(0) {
+        edit_args.push("--sk".to_string());
+        edit_args.push("generate".to_string());
+        should_edit = true;
+    }
+
+    if should_edit {
+        runner
+            .steps
+            .time_async(PHASE_EDITS, runner.nsc_exclusive("edit account", store_dir, &edit_args))
+            .await?;
+    }
+
+    for role in &account.roles {
+        runner
+            .steps
+            .time_async(PHASE_EDITS, add_role(runner, account, role, store_dir))
+            .await?;
     }
 
     for (i, export) in account.exports.iter().enumerate() {
@@ -128,16 +839,62 @@ pub async fn create_account(account: &AccountConfig, operator_name: &str, store_
         if export.is_service {
             export_args.push("--service".to_string());
         }
-        let export_output = Command::new("nsc")
-            .args(&export_args)
-            .output()
-            .await
-            .context(format!("Failed to add export {}", export.subject))?;
-        if !export_output.status.success() {
-            return Err(anyhow::anyhow!(
-                "nsc add export failed: {}",
-                String::from_utf8_lossy(&export_output.stderr)
-            ));
+        if let Some(response_type) = export.response_type {
+            export_args.push("--response-type".to_string());
+            export_args.push(response_type.as_str().to_string());
+        }
+        if let Some(description) = &export.description {
+            export_args.push("--description".to_string());
+            export_args.push(description.clone());
+        }
+        if let Some(position) = export.account_token_position {
+            export_args.push("--account-token-position".to_string());
+            export_args.push(position.to_string());
+        }
+        if export.private {
+            export_args.push("--private".to_string());
+        }
+        if let Some(latency_subject) = &export.latency_subject {
+            let sampling = export.latency_sampling.unwrap_or(LatencySampling::Percent(100));
+            export_args.push("--latency".to_string());
+            export_args.push(latency_subject.clone());
+            export_args.push("--sampling".to_string());
+            export_args.push(sampling.as_arg());
+        }
+        runner
+            .steps
+            .time_async(
+                PHASE_EXPORTS,
+                runner.nsc_exclusive("add export", store_dir, &export_args),
+            )
+            .await?;
+    }
+
+    let mut mappings: Vec<_> = account.mappings.iter().collect();
+    mappings.sort_by_key(|(source, _)| *source);
+    for (source, target) in mappings {
+        for (destination, weight) in target.destinations() {
+            let weight = weight.to_string();
+            runner
+                .nsc_exclusive(
+                    "add mapping",
+                    store_dir,
+                    [
+                        "add",
+                        "mapping",
+                        "--account",
+                        &account.unique_name,
+                        "--from",
+                        source,
+                        "--to",
+                        destination,
+                        "--weight",
+                        &weight,
+                        "--data-dir",
+                        store_path,
+                    ],
+                )
+                .await?;
         }
     }
 
@@ -147,24 +904,220 @@ pub async fn create_account(account: &AccountConfig, operator_name: &str, store_
         .join(&account.unique_name)
         .join(format!("{}.jwt", &account.unique_name));
 
-    std::fs::read_to_string(&account_jwt_path)
-        .context(format!("Failed to read JWT for account {}", account.unique_name))
+    std::fs::read_to_string(&account_jwt_path).map_err(NatsForgeError::io(&account_jwt_path))
+}
+
+/// Adds a scoped signing key for `role` to the account. Users are issued with it as `-K <role>`.
+async fn add_role(runner: &Nsc, account: &AccountConfig, role: &RoleConfig, store_dir: &Path) -> Result<()> {
+    let store_path = store_dir.to_str().unwrap();
+    let output = runner
+        .nsc_exclusive("generate nkey", store_dir, ["generate", "nkey", "--account", "--store"])
+        .await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let key = stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with('A') && line.len() == 56)
+        .ok_or_else(|| {
+            unusable_output(
+                "generate nkey",
+                format!("no account public key in output: {}", stdout.trim()),
+            )
+        })?
+        .to_string();
+
+    let edit_args = [
+        "edit",
+        "account",
+        "--name",
+        &account.unique_name,
+        "--sk",
+        &key,
+        "--data-dir",
+        store_path,
+    ];
+    runner.nsc_exclusive("edit account", store_dir, edit_args).await?;
+
+    let mut scope_args = vec![
+        "edit".to_string(),
+        "signing-key".to_string(),
+        "--account".to_string(),
+        account.unique_name.clone(),
+        "--sk".to_string(),
+        key,
+        "--role".to_string(),
+        role.name.clone(),
+        "--data-dir".to_string(),
+        store_path.to_string(),
+    ];
+    if let Some(publishes) = role.allowed_publishes.as_ref().filter(|p| !p.is_empty()) {
+        scope_args.push("--allow-pub".to_string());
+        scope_args.push(publishes.join(","));
+    }
+    if let Some(subscribes) = role.allowed_subscribes.as_ref().filter(|s| !s.is_empty()) {
+        scope_args.push("--allow-sub".to_string());
+        scope_args.push(subscribes.join(","));
+    }
+    if let Some(max_payload) = role.max_payload {
+        scope_args.push("--payload".to_string());
+        scope_args.push(max_payload.to_string());
+    }
+    runner.nsc_exclusive("edit signing-key", store_dir, &scope_args).await?;
+    Ok(())
+}
+
+/// Generates the activation token for `import` into the store and returns the JWT.
+async fn generate_activation(
+    runner: &Nsc,
+    import: &ImportConfig,
+    src_unique_name: &str,
+    target_account_id: &str,
+    store_dir: &Path,
+) -> Result<String> {
+    let token_path = store_dir.join(format!("{}-{}.activation.jwt", src_unique_name, target_account_id));
+    let activation_args = [
+        "generate".to_string(),
+        "activation".to_string(),
+        "--account".to_string(),
+        src_unique_name.to_string(),
+        "--target-account".to_string(),
+        target_account_id.to_string(),
+        "--subject".to_string(),
+        import.subject.clone(),
+        "--output-file".to_string(),
+        token_path.to_str().unwrap().to_string(),
+        "--data-dir".to_string(),
+        store_dir.to_str().unwrap().to_string(),
+    ];
+    runner.nsc("generate activation", store_dir, &activation_args).await?;
+
+    // The file is decorated with BEGIN/END lines around the JWT.
+    let contents = std::fs::read_to_string(&token_path).map_err(NatsForgeError::io(&token_path))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('-'))
+        .map(String::from)
+        .ok_or_else(|| {
+            unusable_output(
+                "generate activation",
+                format!("no activation token in {}", token_path.display()),
+            )
+        })
 }
 
-pub async fn create_user(
+pub(crate) async fn add_import(
+    runner: &Nsc,
     account: &AccountConfig,
-    user: &UserConfig,
-    output_dir: &Path,
+    import_name: &str,
+    import: &ImportConfig,
+    src_unique_name: &str,
+    activation: Option<&str>,
     store_dir: &Path,
-) -> Result<PathBuf> {
-    let creds_filename = format!("{}-{}.creds", account.name, user.name);
-    let creds_path = output_dir.join(&creds_filename);
+) -> Result<()> {
+    let mut import_args = vec![
+        "add".to_string(),
+        "import".to_string(),
+        "--name".to_string(),
+        import_name.to_string(),
+        "--src-account".to_string(),
+        src_unique_name.to_string(),
+        "--remote-subject".to_string(),
+        import.subject.clone(),
+        "--account".to_string(),
+        account.unique_name.clone(),
+        "--data-dir".to_string(),
+        store_dir.to_str().unwrap().to_string(),
+    ];
+    if let Some(local_subject) = &import.local_subject {
+        import_args.push("--local-subject".to_string());
+        import_args.push(local_subject.clone());
+    }
+    if import.service {
+        import_args.push("--service".to_string());
+    }
+    if import.share {
+        import_args.push("--share".to_string());
+    }
+    if let Some(activation) = activation {
+        // nsc reads the token from a file.
+        let token_path = store_dir.join(format!("{}-{}.activation.jwt", account.unique_name, import_name));
+        std::fs::write(&token_path, activation).map_err(NatsForgeError::io(&token_path))?;
+        import_args.push("--token".to_string());
+        import_args.push(token_path.to_str().unwrap().to_string());
+    }
+    runner.nsc_exclusive("add import", store_dir, &import_args).await?;
+    Ok(())
+}
+
+/// Renders an `expiry` or `start` for nsc. Timestamps are converted to UTC in the
+/// `YYYY-MM-DD HH:MM:SS` form nsc reads to the second; dates and durations are passed as is.
+const PUSH_FAILED: &str = "push failed";
+
+/// Reads the per-account results from `nsc push` output, which has lines like
+/// `[ OK ] pushed "APP" to nats-server NDX...: jwt updated` and `[ERR ] pushed "APP" to ...: <error>`.
+pub(crate) fn parse_push_output(output: &str) -> PushReport {
+    let mut report = PushReport::default();
+    for line in output.lines().map(str::trim) {
+        let (ok, rest) = if let Some(rest) = line.strip_prefix("[ OK ]") {
+            (true, rest)
+        } else if let Some(rest) = line.strip_prefix("[ERR ]").or_else(|| line.strip_prefix("[ X ]")) {
+            (false, rest)
+        } else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let Some(rest) = rest.strip_prefix("pushed ").or_else(|| rest.strip_prefix("push ")) else {
+            continue;
+        };
+        let Some((account, target)) = rest.split_once(" to ") else {
+            continue;
+        };
+        let account = account.trim_matches('"');
+        let message = target
+            .split_once(": ")
+            .map(|(_, message)| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+        let index = match report.accounts.iter().position(|pushed| pushed.account == account) {
+            Some(index) => index,
+            None => {
+                report.accounts.push(PushedAccount {
+                    account: account.to_string(),
+                    error: None,
+                });
+                report.accounts.len() - 1
+            }
+        };
+        // A failed account has a summary line and, usually, a more specific one with the error.
+        let pushed = &mut report.accounts[index];
+        if !ok && (pushed.error.is_none() || pushed.error.as_deref() == Some(PUSH_FAILED)) {
+            pushed.error = Some(message.unwrap_or_else(|| PUSH_FAILED.to_string()));
+        }
+    }
+    report
+}
+
+fn nsc_instant(text: &str) -> String {
+    let text = text.trim();
+    match chrono::DateTime::parse_from_rfc3339(text) {
+        Ok(instant) => instant
+            .with_timezone(&chrono::Utc)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        Err(_) => text.to_string(),
+    }
+}
 
-    let account_name = if account.name == "SYS" && account.is_system_account {
-        "SYS".to_string()
-    } else {
-        account.unique_name.clone()
-    };
+/// Issues a user, signed by `signing_key` (a public key or role name) when given instead of the
+/// account's identity key.
+pub(crate) async fn create_user(
+    runner: &Nsc,
+    account: &AccountConfig,
+    user: &UserConfig,
+    signing_key: Option<&str>,
+    store_dir: &Path,
+) -> Result<String> {
+    let account_name = store_account_name(account).to_string();
 
     let mut add_args = vec![
         "add".to_string(),
@@ -177,95 +1130,82 @@ pub async fn create_user(
         store_dir.to_str().unwrap().to_string(),
     ];
 
-    if let Some(pubsub) = &user.allowed_pubsub {
-        if !pubsub.is_empty() {
-            add_args.push("--allow-pubsub".to_string());
-            add_args.push(pubsub.join(","));
+    // A role's scoped signing key sets the permissions; nsc rejects individual ones alongside it.
+    if user.role.is_none() {
+        let permissions = [
+            ("--allow-pub", &user.allow_pub),
+            ("--allow-sub", &user.allow_sub),
+            ("--allow-pubsub", &user.allow_pubsub),
+            ("--deny-pub", &user.deny_pub),
+            ("--deny-sub", &user.deny_sub),
+            ("--deny-pubsub", &user.deny_pubsub),
+        ];
+        for (flag, subjects) in permissions {
+            if !subjects.is_empty() {
+                add_args.push(flag.to_string());
+                add_args.push(subjects.join(","));
+            }
         }
-    }
 
-    if let Some(publishes) = &user.allowed_publishes {
-        if !publishes.is_empty() {
-            add_args.push("--allow-pub".to_string());
-            add_args.push(publishes.join(","));
+        if let Some(max) = user.allow_pub_response {
+            add_args.push(format!("--allow-pub-response={}", max));
         }
-    }
 
-    if let Some(subjects) = &user.allowed_subjects {
-        if !subjects.is_empty() {
-            add_args.push("--allow-sub".to_string());
-            add_args.push(subjects.join(","));
+        if let Some(ttl) = &user.response_ttl {
+            add_args.push("--response-ttl".to_string());
+            add_args.push(ttl.clone());
         }
-    }
 
-    if let Some(pubsub) = &user.denied_pubsub {
-        if !pubsub.is_empty() {
-            add_args.push("--deny-pubsub".to_string());
-            add_args.push(pubsub.join(","));
+        let limits = [
+            ("--subs", user.max_subscriptions),
+            ("--data", user.max_data),
+            ("--payload", user.max_payload),
+        ];
+        for (flag, limit) in limits {
+            if let Some(limit) = limit {
+                add_args.push(flag.to_string());
+                add_args.push(limit.to_string());
+            }
         }
     }
 
-    if let Some(publishes) = &user.denied_publishes {
-        if !publishes.is_empty() {
-            add_args.push("--deny-pub".to_string());
-            add_args.push(publishes.join(","));
-        }
+    if user.bearer {
+        add_args.push("--bearer".to_string());
     }
 
-    if let Some(subjects) = &user.denied_subjects {
-        if !subjects.is_empty() {
-            add_args.push("--deny-sub".to_string());
-            add_args.push(subjects.join(","));
-        }
+    if !user.source_networks.is_empty() {
+        add_args.push("--source-network".to_string());
+        add_args.push(user.source_networks.join(","));
     }
 
-    if let Some(true) = user.allow_pub_response {
-        add_args.push("--allow-pub-response".to_string());
+    if let Some(signing_key) = signing_key {
+        add_args.push("-K".to_string());
+        add_args.push(signing_key.to_string());
     }
 
     if let Some(expiry) = &user.expiry {
-        let nsc_expiry = if expiry.contains('T') {
-            expiry.split('T').next().unwrap_or(expiry).to_string()
-        } else {
-            expiry.clone()
-        };
         add_args.push("--expiry".to_string());
-        add_args.push(nsc_expiry);
+        add_args.push(nsc_instant(expiry));
     }
 
-    println!("Running nsc add user command for {}: {:?}", user.name, add_args);
-    let add_output = Command::new("nsc")
-        .args(&add_args)
-        .output()
-        .await
-        .context(format!("Failed to run nsc add user {}", user.name))?;
-
-    if !add_output.status.success() {
-        println!("nsc add user stderr: {}", String::from_utf8_lossy(&add_output.stderr));
-        return Err(anyhow::anyhow!(
-            "nsc add user failed: {}",
-            String::from_utf8_lossy(&add_output.stderr)
-        ));
+    if let Some(start) = &user.start {
+        add_args.push("--start".to_string());
+        add_args.push(nsc_instant(start));
     }
 
-    // Paranoid dir check
-    println!("Ensuring output dir for {}: {}", user.name, output_dir.display());
-    std::fs::create_dir_all(output_dir)
-        .context(format!("Failed to ensure output dir exists: {}", output_dir.display()))?;
-    if !output_dir.exists() {
-        return Err(anyhow::anyhow!(
-            "Output dir {} still doesn't exist!",
-            output_dir.display()
-        ));
+    for range in &user.times {
+        add_args.push("--time".to_string());
+        add_args.push(format!("{}-{}", range.start, range.end));
     }
-    let test_file = output_dir.join(format!("test-write-{}.txt", user.name));
-    std::fs::write(&test_file, "test").context("Failed to verify write permissions")?;
-    std::fs::remove_file(&test_file)?;
 
-    if creds_path.exists() {
-        std::fs::remove_file(&creds_path)?;
+    if let Some(locale) = &user.locale {
+        add_args.push("--locale".to_string());
+        add_args.push(locale.clone());
     }
 
+    eprintln!("Running nsc add user command for {}: {:?}", user.name, add_args);
+    runner.nsc_exclusive("add user", store_dir, &add_args).await?;
+
     let generate_args = vec![
         "generate".to_string(),
         "creds".to_string(),
@@ -273,47 +1213,17 @@ pub async fn create_user(
         account_name,
         "--name".to_string(),
         user.name.clone(),
-        "--output-file".to_string(),
-        creds_path.to_str().unwrap().to_string(),
         "--data-dir".to_string(),
         store_dir.to_str().unwrap().to_string(),
     ];
 
-    println!(
+    eprintln!(
         "Running nsc generate creds command for {}: {:?}",
         user.name, generate_args
     );
-    let mut attempts = 0;
-    let max_attempts = 2;
-    let generate_output = loop {
-        attempts += 1;
-        let output = Command::new("nsc").args(&generate_args).output().await?;
-        if output.status.success() || attempts >= max_attempts {
-            break output;
-        }
-        println!(
-            "Retry attempt {}/{} for {} due to failure: {}",
-            attempts,
-            max_attempts,
-            user.name,
-            String::from_utf8_lossy(&output.stderr)
-        );
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    };
+    let generate_output = runner.nsc("generate creds", store_dir, &generate_args).await?;
 
-    if !generate_output.status.success() {
-        println!(
-            "nsc generate creds stderr: {}",
-            String::from_utf8_lossy(&generate_output.stderr)
-        );
-        return Err(anyhow::anyhow!(
-            "nsc generate creds failed after {} attempts: {}",
-            max_attempts,
-            String::from_utf8_lossy(&generate_output.stderr)
-        ));
-    }
-
-    let creds_content = std::fs::read_to_string(&creds_path)?;
+    let creds_content = String::from_utf8_lossy(&generate_output.stdout);
     let jwt = creds_content
         .lines()
         .skip_while(|line| !line.contains("-----BEGIN NATS USER JWT-----"))
@@ -329,31 +1239,15 @@ pub async fn create_user(
         .collect::<Vec<_>>()
         .join("\n");
 
-    let formatted_creds = format!(
-        "-----BEGIN NATS USER JWT-----\n{}\n------END NATS USER JWT------\n\n************************* IMPORTANT \
-         *************************\nNKEY Seed printed below can be used to sign and prove identity.\nNKEYs are \
-         sensitive and should be treated as secrets.\n\n-----BEGIN USER NKEY SEED-----\n{}\n------END USER NKEY \
-         SEED------\n\n*************************************************************\n",
-        jwt.trim(),
-        seed.trim()
-    );
-
-    std::fs::write(&creds_path, &formatted_creds)?;
-    println!("Wrote creds to {}: content=\n{}", creds_path.display(), formatted_creds);
-
-    Ok(creds_path)
+    Ok(format_creds(&jwt, &seed))
 }
 
-pub fn extract_account_id(jwt: &str) -> Result<String> {
-    let parts: Vec<&str> = jwt.split('.').collect();
-    if parts.len() != 3 {
-        return Err(anyhow::anyhow!("Invalid JWT format: {} parts", parts.len()));
-    }
-    let payload = BASE64.decode(parts[1]).context("Failed to decode JWT payload")?;
-    let payload_str = String::from_utf8(payload).context("JWT payload is not UTF-8")?;
-    let json: serde_json::Value = serde_json::from_str(&payload_str).context("Failed to parse JWT JSON")?;
-    json["sub"]
-        .as_str()
-        .map(String::from)
-        .ok_or_else(|| anyhow::anyhow!("No 'sub' field in JWT"))
+/// The public signing keys listed in an account JWT, plain and scoped alike.
+pub(crate) fn extract_signing_keys(jwt: &str) -> Result<Vec<String>> {
+    Ok(decode_account_claims(jwt)?
+        .nats
+        .signing_keys
+        .iter()
+        .map(|key| key.key().to_string())
+        .collect())
 }
@@ -0,0 +1,140 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    config::{NatsConfig, ProfileOverride},
+    error::{NatsForgeError, Result},
+};
+
+impl NatsConfig {
+    /// Loads a JSON config, laid over the files its `extends` lists: those are loaded the same
+    /// way, merged in order, and then this file is merged over them, so later files win. Objects
+    /// merge key by key, servers, accounts, users and other lists of named items merge by name,
+    /// and anything else is replaced. The loaded config's `extends` is empty.
+    ///
+    /// With a `profile`, the overrides of that entry of `profiles` are merged over the result the
+    /// same way, and servers they disable are removed.
+    pub fn from_json_file(path: impl AsRef<Path>, profile: Option<&str>) -> Result<NatsConfig> {
+        let path = path.as_ref();
+        let parse_error = |source| NatsForgeError::Parse {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut value = load(path, &mut Vec::new())?;
+        if let Some(profile) = profile {
+            let profiles: HashMap<String, ProfileOverride> = match value.get("profiles") {
+                Some(profiles) => serde_json::from_value(profiles.clone()).map_err(parse_error)?,
+                None => HashMap::new(),
+            };
+            apply_profile(&mut value, profile, profiles)?;
+        }
+        serde_json::from_value(value).map_err(parse_error)
+    }
+}
+
+/// Merges the overrides of profile `name` over `config`.
+fn apply_profile(config: &mut Value, name: &str, mut profiles: HashMap<String, ProfileOverride>) -> Result<()> {
+    let Some(profile) = profiles.remove(name) else {
+        let mut available: Vec<String> = profiles.into_keys().collect();
+        available.sort();
+        return Err(NatsForgeError::UnknownProfile {
+            name: name.to_string(),
+            available,
+        });
+    };
+    let mut no_servers = Vec::new();
+    let servers = config
+        .get_mut("servers")
+        .and_then(Value::as_array_mut)
+        .unwrap_or(&mut no_servers);
+    // Sorted, so that of several unknown servers the same one is reported every time.
+    for (server, overrides) in BTreeMap::from_iter(profile.servers) {
+        let position = servers
+            .iter()
+            .position(|candidate| candidate["name"] == server.as_str())
+            .ok_or_else(|| {
+                NatsForgeError::InvalidConfig(format!(
+                    "Profile {} overrides server {}, which isn't defined",
+                    name, server
+                ))
+            })?;
+        if !overrides.enabled {
+            servers.remove(position);
+            continue;
+        }
+        let mut overlay = serde_json::to_value(&overrides).expect("ServerOverride serializes");
+        overlay
+            .as_object_mut()
+            .expect("ServerOverride serializes to an object")
+            .remove("enabled");
+        merge(&mut servers[position], overlay);
+    }
+    Ok(())
+}
+
+/// The config at `path` with everything it extends merged under it. `chain` holds the files being
+/// loaded that led to this one, to catch cycles.
+fn load(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Value> {
+    let canonical = std::fs::canonicalize(path).map_err(NatsForgeError::io(path))?;
+    if let Some(start) = chain.iter().position(|loading| *loading == canonical) {
+        let mut cycle = chain[start..].to_vec();
+        cycle.push(canonical);
+        return Err(NatsForgeError::ExtendsCycle { chain: cycle });
+    }
+    let parse_error = |source| NatsForgeError::Parse {
+        path: path.to_path_buf(),
+        source,
+    };
+    let text = std::fs::read_to_string(path).map_err(NatsForgeError::io(path))?;
+    let mut value: Value = serde_json::from_str(&text).map_err(parse_error)?;
+    let extends: Vec<PathBuf> = match value.as_object_mut().and_then(|object| object.remove("extends")) {
+        Some(extends) => serde_json::from_value(extends).map_err(parse_error)?,
+        None => Vec::new(),
+    };
+
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut merged = Value::Object(Default::default());
+    for base in extends {
+        merge(&mut merged, load(&dir.join(base), chain)?);
+    }
+    chain.pop();
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Merges `overlay` into `base`. Objects merge key by key. Arrays whose items all have a string
+/// `name`, such as servers, accounts and users, merge item by item: an overlay item merges into
+/// the base item of the same name, or is appended when there is none. Any other value, including
+/// an empty or unnamed array, replaces the base value.
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(overlay)) if !overlay.is_empty() && named(base) && named(&overlay) => {
+            for item in overlay {
+                match base.iter_mut().find(|existing| existing["name"] == item["name"]) {
+                    Some(existing) => merge(existing, item),
+                    None => base.push(item),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn named(items: &[Value]) -> bool {
+    items.iter().all(|item| item.get("name").is_some_and(Value::is_string))
+}
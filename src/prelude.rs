@@ -0,0 +1,61 @@
+//! The types needed to describe a deployment, generate it and use the result.
+//!
+//! ```
+//! use natsforge::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), NatsForgeError> {
+//! let output_dir = tempfile::tempdir().unwrap();
+//! let config = NatsConfig {
+//!     operator: OperatorConfig {
+//!         name: "prelude-operator".to_string(),
+//!         reuse_existing: false,
+//!         account_server_url: None,
+//!         service_urls: vec![],
+//!     },
+//!     servers: vec![ServerConfig {
+//!         name: "prelude-server".to_string(),
+//!         port: 4222,
+//!         output_dir: output_dir.path().to_path_buf(),
+//!         accounts: vec![AccountConfig {
+//!             name: "APP".to_string(),
+//!             users: vec![UserConfig {
+//!                 name: "app-user".to_string(),
+//!                 ..Default::default()
+//!             }],
+//!             ..Default::default()
+//!         }],
+//!         ..Default::default()
+//!     }],
+//!     backend: BackendKind::Native,
+//!     durability: Durability::Fast,
+//!     ..Default::default()
+//! };
+//!
+//! let result: SetupResult = NatsForge::from_config(config)?.initialize().await?;
+//! assert!(result.creds("APP", "app-user").is_some());
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::{
+    compose::ComposeOptions,
+    config::{
+        AccountArtifacts, AccountConfig, AccountTemplate, ArtifactMode, AuthCalloutConfig, AuthCalloutKeys, AuthMode,
+        BackendKind, CleanupReport, ClusterAuth, ClusterConfig, ClusterTemplate, ConfCredential, ConnectionInfo,
+        ConsumerAckPolicy, ConsumerSpec, Durability, EncryptionConfig, ExportConfig, GatewayConfig, ImportConfig,
+        JetStreamConfig, JetStreamLimits, KvSpec, LatencySampling, LeafAuth, LeafNodeConfig, LeafUser, LoggingConfig,
+        MappingTarget, MirrorSpec, MonitoringConfig, MqttConfig, NamingMode, NatsConfig, OperatorConfig, OperatorRef,
+        Permissions, ProfileOverride, PushReport, PushedAccount, ReloadReport, ReloadedServer, RemoteConfig,
+        RemoteGateway, RemoteTls, ResolverType, ResponseType, RoleConfig, RotatedUser, ServerConfig, ServerOverride,
+        SetupArtifacts, SetupResult, StreamRetention, StreamSpec, StreamStorage, SubjectPermissions,
+        SystemAccountConfig, TimeRange, TlsConfig, UserConfig, UserTemplate, VerifyMode, WeightedDestination,
+    },
+    diff::DiffReport,
+    error::NatsForgeError,
+    kubernetes::K8sOptions,
+    manifest::{AccountManifest, ServerManifest, SetupManifest},
+    prometheus::{ExporterOptions, TargetGroup},
+    secrets::{EnvDirSink, FileSink, SecretSink},
+    NatsForge,
+};
@@ -0,0 +1,89 @@
+use std::{fmt, sync::Mutex};
+
+use tokio::sync::mpsc::Sender;
+pub use tokio_util::sync::CancellationToken;
+
+use crate::error::{NatsForgeError, Result};
+
+/// Something `initialize()` created, reported as soon as it exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetupEvent {
+    OperatorCreated {
+        operator: String,
+    },
+    AccountCreated {
+        name: String,
+    },
+    UserCreated {
+        account: String,
+        user: String,
+    },
+    /// An import of `subject` from account `from` was added to `account`.
+    ImportAdded {
+        account: String,
+        from: String,
+        subject: String,
+    },
+    ServerConfigWritten {
+        server: String,
+    },
+}
+
+impl fmt::Display for SetupEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupEvent::OperatorCreated { operator } => write!(f, "operator {}", operator),
+            SetupEvent::AccountCreated { name } => write!(f, "account {}", name),
+            SetupEvent::UserCreated { account, user } => write!(f, "user {}/{}", account, user),
+            SetupEvent::ImportAdded { account, from, subject } => {
+                write!(f, "import of {} from {} into {}", subject, from, account)
+            }
+            SetupEvent::ServerConfigWritten { server } => write!(f, "config of server {}", server),
+        }
+    }
+}
+
+/// Reports the events of one `initialize()` run and stops it once `cancel` is cancelled.
+pub(crate) struct Progress {
+    tx: Option<Sender<SetupEvent>>,
+    cancel: Option<CancellationToken>,
+    created: Mutex<Vec<SetupEvent>>,
+}
+
+impl Progress {
+    pub(crate) fn new(tx: Option<Sender<SetupEvent>>, cancel: Option<CancellationToken>) -> Self {
+        Progress {
+            tx,
+            cancel,
+            created: Mutex::default(),
+        }
+    }
+
+    /// Called before every step that creates something. Waits until the receiver has room for
+    /// another event, so the run never gets further ahead of it than the channel's capacity, then
+    /// fails with [`NatsForgeError::Cancelled`] if the run was cancelled.
+    pub(crate) async fn checkpoint(&self) -> Result<()> {
+        if let Some(tx) = &self.tx {
+            // A dropped receiver only means nobody is listening.
+            let _ = tx.reserve().await;
+        }
+        if self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(NatsForgeError::Cancelled {
+                created: self.created(),
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn emit(&self, event: SetupEvent) {
+        self.created.lock().expect("setup events poisoned").push(event.clone());
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(event).await;
+        }
+    }
+
+    /// Everything created so far, in the order it was reported.
+    pub(crate) fn created(&self) -> Vec<SetupEvent> {
+        self.created.lock().expect("setup events poisoned").clone()
+    }
+}
@@ -0,0 +1,102 @@
+//! Scraping the generated servers with Prometheus.
+//!
+//! nats-server's monitoring endpoints speak JSON rather than the Prometheus text format, so they
+//! are scraped through [prometheus-nats-exporter](https://github.com/nats-io/prometheus-nats-exporter).
+//! [`NatsForge::emit_prometheus_targets`](crate::NatsForge::emit_prometheus_targets) writes a
+//! `file_sd_configs` targets file of the monitoring endpoints, and [`ExporterOptions`] on the
+//! compose and Kubernetes emitters runs an exporter next to every server that has one.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ServerConfig, net};
+
+/// The exporter image unless [`ExporterOptions::image`] says otherwise.
+pub const DEFAULT_EXPORTER_IMAGE: &str = "natsio/prometheus-nats-exporter:0.15.0";
+/// The port the exporter serves metrics on by default.
+pub const DEFAULT_EXPORTER_PORT: u16 = 7777;
+
+/// One entry of a `file_sd_configs` targets file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetGroup {
+    /// `host:port` of the monitoring endpoint.
+    pub targets: Vec<String>,
+    /// `server_name`, and `cluster` and `jetstream_domain` where the server has them.
+    /// `__scheme__` is `https` for servers only monitored over HTTPS.
+    pub labels: BTreeMap<String, String>,
+}
+
+/// A prometheus-nats-exporter run alongside every server with an `http_port`.
+#[derive(Debug, Clone)]
+pub struct ExporterOptions {
+    pub image: String,
+    pub port: u16,
+}
+
+impl Default for ExporterOptions {
+    fn default() -> Self {
+        ExporterOptions {
+            image: DEFAULT_EXPORTER_IMAGE.to_string(),
+            port: DEFAULT_EXPORTER_PORT,
+        }
+    }
+}
+
+impl ExporterOptions {
+    /// The exporter's arguments for scraping the monitoring endpoint at `monitoring_url`, with
+    /// JetStream and leafnode metrics for servers that have them.
+    pub fn args(&self, server: &ServerConfig, monitoring_url: &str) -> Vec<String> {
+        let mut args: Vec<String> = ["-varz", "-connz", "-subz", "-healthz"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        if server.cluster.is_some() {
+            args.push("-routez".to_string());
+        }
+        if server.gateway.is_some() {
+            args.push("-gatewayz".to_string());
+        }
+        if server.leafnodes.port.is_some() || !server.leafnodes.remotes.is_empty() {
+            args.push("-leafz".to_string());
+        }
+        if server.jetstream.enabled {
+            args.push("-jsz=all".to_string());
+        }
+        args.extend(["-port".to_string(), self.port.to_string(), monitoring_url.to_string()]);
+        args
+    }
+}
+
+/// The target group of `server`, reached at `host`, or `None` when it isn't monitored.
+pub(crate) fn target_group(server: &ServerConfig, host: &str) -> Option<TargetGroup> {
+    let monitoring = server.monitoring.as_ref()?;
+    let mut labels = BTreeMap::from([("server_name".to_string(), server.name.clone())]);
+    let port = match (monitoring.http_port, monitoring.https_port) {
+        (Some(port), _) => port,
+        (None, Some(port)) => {
+            labels.insert("__scheme__".to_string(), "https".to_string());
+            port
+        }
+        (None, None) => return None,
+    };
+    if let Some(cluster) = &server.cluster {
+        labels.insert("cluster".to_string(), cluster.name.clone());
+    }
+    if server.jetstream.enabled {
+        // nats-server is configured with the same default domain.
+        let domain = server.jetstream.domain.as_deref().unwrap_or("core");
+        labels.insert("jetstream_domain".to_string(), domain.to_string());
+    }
+    Some(TargetGroup {
+        targets: vec![net::host_port(host, port)],
+        labels,
+    })
+}
+
+/// Renders the targets file.
+pub(crate) fn render_targets(groups: &[TargetGroup]) -> String {
+    let mut targets = serde_json::to_string_pretty(groups).expect("target groups serialize");
+    targets.push('\n');
+    targets
+}
@@ -0,0 +1,253 @@
+//! Creates the JetStream resources a config declares on the running servers.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use async_nats::jetstream::{self, consumer, context::GetStreamErrorKind, kv, stream, ErrorCode};
+
+use crate::{
+    config::{
+        ConnectionInfo, ConsumerAckPolicy, ConsumerSpec, KvSpec, MirrorSpec, NatsConfig, ServerConfig, SetupResult,
+        StreamRetention, StreamSpec, StreamStorage,
+    },
+    error::{NatsForgeError, Result},
+    validate::parse_duration,
+};
+
+/// Creates the streams, consumers and KV buckets of every server, then the mirrors, or updates
+/// them to match their spec. Consumers and mirrors come after the streams they read from.
+pub(crate) async fn provision(config: &NatsConfig, result: &SetupResult) -> Result<()> {
+    for server in &config.servers {
+        let mut connections = Connections {
+            config,
+            result,
+            server,
+            contexts: HashMap::new(),
+        };
+        for spec in &server.streams {
+            let failed = failure(server, format!("stream {}", spec.name));
+            let context = connections.context(&spec.account).await.map_err(&failed)?;
+            let stream_config = stream_config(spec).map_err(|err| failed(err.to_string()))?;
+            context
+                .create_or_update_stream(stream_config)
+                .await
+                .map_err(|err| failed(err.to_string()))?;
+        }
+        for spec in &server.consumers {
+            let failed = failure(server, format!("consumer {} on stream {}", spec.name, spec.stream));
+            let account = server
+                .streams
+                .iter()
+                .find(|stream| stream.name == spec.stream)
+                .map(|stream| stream.account.as_str())
+                .ok_or_else(|| failed(format!("stream {} isn't declared on the server", spec.stream)))?;
+            let context = connections.context(account).await.map_err(&failed)?;
+            context
+                .create_consumer_on_stream(consumer_config(spec), spec.stream.as_str())
+                .await
+                .map_err(|err| failed(err.to_string()))?;
+        }
+        for spec in &server.kv_buckets {
+            let failed = failure(server, format!("KV bucket {}", spec.bucket));
+            let context = connections.context(&spec.account).await.map_err(&failed)?;
+            provision_bucket(context, spec).await.map_err(&failed)?;
+        }
+    }
+
+    for spec in &config.mirrors {
+        let server = config
+            .servers
+            .iter()
+            .find(|server| server.jetstream.enabled && server.jetstream.domain.as_deref() == Some(&spec.dest_domain))
+            .ok_or_else(|| {
+                NatsForgeError::InvalidConfig(format!(
+                    "Mirror {}: no server with JetStream enabled has the destination domain {}",
+                    spec.name, spec.dest_domain
+                ))
+            })?;
+        let failed = failure(server, format!("mirror {}", spec.name));
+        let mut connections = Connections {
+            config,
+            result,
+            server,
+            contexts: HashMap::new(),
+        };
+        let context = connections.context(&spec.account).await.map_err(&failed)?;
+        context
+            .create_or_update_stream(mirror_config(spec))
+            .await
+            .map_err(|err| failed(err.to_string()))?;
+    }
+    Ok(())
+}
+
+fn failure(server: &ServerConfig, resource: String) -> impl Fn(String) -> NatsForgeError + '_ {
+    move |message| NatsForgeError::Provision {
+        server: server.name.clone(),
+        resource: resource.clone(),
+        message,
+    }
+}
+
+/// One JetStream context per account on a server, opened as the account's first user.
+struct Connections<'a> {
+    config: &'a NatsConfig,
+    result: &'a SetupResult,
+    server: &'a ServerConfig,
+    contexts: HashMap<String, jetstream::Context>,
+}
+
+impl Connections<'_> {
+    async fn context(&mut self, account: &str) -> std::result::Result<&jetstream::Context, String> {
+        match self.contexts.entry(account.to_string()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(connect(self.config, self.result, self.server, account).await?)),
+        }
+    }
+}
+
+/// A JetStream context on `server` as the first user of `account_name`, in the server's
+/// JetStream domain if it has one.
+async fn connect(
+    config: &NatsConfig,
+    result: &SetupResult,
+    server: &ServerConfig,
+    account_name: &str,
+) -> std::result::Result<jetstream::Context, String> {
+    let account = config
+        .servers
+        .iter()
+        .flat_map(|server| &server.accounts)
+        .find(|account| account.name == account_name)
+        .ok_or_else(|| format!("unknown account {}", account_name))?;
+    let user = account
+        .users
+        .first()
+        .ok_or_else(|| format!("account {} has no users", account_name))?;
+    let creds = result
+        .creds_contents(account_name, &user.name)
+        .map_err(|err| err.to_string())?;
+    let url = result
+        .client_url(&server.name)
+        .unwrap_or_else(|_| ConnectionInfo::for_server(server).url);
+
+    let client = async_nats::ConnectOptions::with_credentials(&creds)
+        .map_err(|err| {
+            format!(
+                "invalid creds for user {} in account {}: {}",
+                user.name, account_name, err
+            )
+        })?
+        .connect(&url)
+        .await
+        .map_err(|err| format!("failed to connect to {}: {}", url, err))?;
+    Ok(match &server.jetstream.domain {
+        Some(domain) => jetstream::with_domain(client, domain),
+        None => jetstream::new(client),
+    })
+}
+
+fn stream_config(spec: &StreamSpec) -> Result<stream::Config> {
+    Ok(stream::Config {
+        name: spec.name.clone(),
+        subjects: spec.subjects.clone(),
+        retention: match spec.retention {
+            StreamRetention::Limits => stream::RetentionPolicy::Limits,
+            StreamRetention::Interest => stream::RetentionPolicy::Interest,
+            StreamRetention::WorkQueue => stream::RetentionPolicy::WorkQueue,
+        },
+        storage: match spec.storage {
+            StreamStorage::File => stream::StorageType::File,
+            StreamStorage::Memory => stream::StorageType::Memory,
+        },
+        max_messages: spec.max_msgs.unwrap_or(-1),
+        max_bytes: spec.max_bytes.unwrap_or(-1),
+        max_age: spec
+            .max_age
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?
+            .unwrap_or_default(),
+        num_replicas: spec.replicas.unwrap_or(1),
+        ..Default::default()
+    })
+}
+
+/// Reads the source through the JetStream API its domain exports over the leafnode connection.
+fn mirror_config(spec: &MirrorSpec) -> stream::Config {
+    stream::Config {
+        name: spec.name.clone(),
+        mirror: Some(stream::Source {
+            name: spec.source_stream.clone(),
+            external: Some(stream::External {
+                api_prefix: format!("$JS.{}.API", spec.source_domain),
+                delivery_prefix: None,
+            }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn consumer_config(spec: &ConsumerSpec) -> consumer::pull::Config {
+    consumer::pull::Config {
+        durable_name: Some(spec.name.clone()),
+        filter_subject: spec.filter_subject.clone().unwrap_or_default(),
+        ack_policy: match spec.ack_policy {
+            ConsumerAckPolicy::Explicit => consumer::AckPolicy::Explicit,
+            ConsumerAckPolicy::All => consumer::AckPolicy::All,
+            ConsumerAckPolicy::None => consumer::AckPolicy::None,
+        },
+        max_deliver: spec.max_deliver.unwrap_or(-1),
+        ..Default::default()
+    }
+}
+
+/// Creates a bucket, or updates the limits of the stream behind an existing one. Checked by
+/// hand, as `create_key_value` fails on a bucket whose settings changed.
+async fn provision_bucket(context: &jetstream::Context, spec: &KvSpec) -> std::result::Result<(), String> {
+    let history = spec.history.unwrap_or(1);
+    let max_age = spec
+        .ttl
+        .as_deref()
+        .map(parse_duration)
+        .transpose()
+        .map_err(|err| err.to_string())?
+        .unwrap_or_default();
+    let max_value_size = spec.max_value_size.unwrap_or(-1);
+    let num_replicas = spec.replicas.unwrap_or(1);
+
+    match context.get_stream(format!("KV_{}", spec.bucket)).await {
+        Ok(mut stream) => {
+            let current = stream.info().await.map_err(|err| err.to_string())?.config.clone();
+            let wanted = stream::Config {
+                max_messages_per_subject: history,
+                max_age,
+                max_message_size: max_value_size,
+                num_replicas,
+                ..current.clone()
+            };
+            if wanted != current {
+                context.update_stream(wanted).await.map_err(|err| err.to_string())?;
+            }
+        }
+        Err(err) if is_stream_not_found(&err.kind()) => {
+            context
+                .create_key_value(kv::Config {
+                    bucket: spec.bucket.clone(),
+                    history,
+                    max_age,
+                    max_value_size,
+                    num_replicas,
+                    ..Default::default()
+                })
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        Err(err) => return Err(err.to_string()),
+    }
+    Ok(())
+}
+
+fn is_stream_not_found(kind: &GetStreamErrorKind) -> bool {
+    matches!(kind, GetStreamErrorKind::JetStream(err) if err.error_code() == ErrorCode::STREAM_NOT_FOUND)
+}
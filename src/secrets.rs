@@ -0,0 +1,150 @@
+//! Delivery of the generated credentials to a secret store, next to the output directories.
+//!
+//! [`NatsForge::initialize`](crate::NatsForge::initialize) hands every account JWT and user
+//! `.creds` file to the forge's [`SecretSink`], under the keys [`account_jwt_key`] and
+//! [`creds_key`] return. Stores such as Vault or AWS Secrets Manager are supported by
+//! implementing the trait.
+
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    config::Durability,
+    error::{NatsForgeError, Result},
+    writer::{create_output_dir, ArtifactWriter, FsSink},
+};
+
+/// Where generated credentials are delivered, set with
+/// [`NatsForge::with_secret_sink`](crate::NatsForge::with_secret_sink).
+#[async_trait]
+pub trait SecretSink: Send + Sync {
+    /// Stores `value` under `key`, replacing what an earlier run stored there.
+    async fn store(&self, key: &str, value: &[u8]) -> Result<()>;
+}
+
+/// `nats/<server>/<account>.jwt`, where `server` defines the account.
+pub fn account_jwt_key(server: &str, account: &str) -> String {
+    format!("nats/{}/{}.jwt", server, account)
+}
+
+/// `nats/<server>/<account>/<user>.creds`.
+pub fn creds_key(server: &str, account: &str, user: &str) -> String {
+    format!("nats/{}/{}/{}.creds", server, account, user)
+}
+
+/// `nats/<server>/<account>/<user>.<extension>`, for the nkey seed (`nk`), password or token of a
+/// conf-mode user.
+pub fn user_secret_key(server: &str, account: &str, user: &str, extension: &str) -> String {
+    format!("nats/{}/{}/{}.{}", server, account, user, extension)
+}
+
+/// `nats/<server>/<file_name>`, for a secret of the server itself, such as an auth callout seed.
+pub fn server_secret_key(server: &str, file_name: &str) -> String {
+    format!("nats/{}/{}", server, file_name)
+}
+
+/// Writes every secret to the file its key names below a directory, readable only by the
+/// current user.
+#[derive(Debug, Clone)]
+pub struct FileSink {
+    dir: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileSink { dir: dir.into() }
+    }
+
+    /// The file `key` is stored in.
+    pub fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let valid = !key.is_empty()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !valid {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "Secret key {:?} is not a relative path",
+                key
+            )));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[async_trait]
+impl SecretSink for FileSink {
+    async fn store(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            create_output_dir(parent, true).map_err(NatsForgeError::io(parent))?;
+        }
+        ArtifactWriter::new(&FsSink, Durability::Fast)
+            .write_private(&path, value)
+            .map_err(NatsForgeError::io(&path))
+    }
+}
+
+/// Stores secrets like [`FileSink`] and keeps a `.env` file in the same directory that maps a
+/// variable per key to the secret's path, such as `NATS_HUB_APP_APP_USER_CREDS="/secrets/nats/hub/APP/app-user.creds"`.
+#[derive(Debug)]
+pub struct EnvDirSink {
+    files: FileSink,
+    env_file: PathBuf,
+    entries: Mutex<BTreeMap<String, PathBuf>>,
+}
+
+impl EnvDirSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        EnvDirSink {
+            env_file: dir.join(".env"),
+            files: FileSink::new(dir),
+            entries: Mutex::default(),
+        }
+    }
+
+    pub fn env_file(&self) -> &Path {
+        &self.env_file
+    }
+}
+
+/// The variable name of a key: uppercase, with `_` for anything but letters and digits.
+pub fn env_var_name(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl SecretSink for EnvDirSink {
+    async fn store(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.files.store(key, value).await?;
+        let path = self.files.path(key)?;
+        // Written under the lock, so concurrent stores can't leave an older list behind. The file
+        // is rewritten whole, so variables of an earlier run don't linger.
+        let mut entries = self.entries.lock().expect("env entries poisoned");
+        entries.insert(env_var_name(key), path);
+        let env: String = entries
+            .iter()
+            .map(|(name, path)| {
+                let path = path.to_string_lossy().replace('\\', r"\\").replace('"', "\\\"");
+                format!("{}=\"{}\"\n", name, path)
+            })
+            .collect();
+        ArtifactWriter::new(&FsSink, Durability::Fast)
+            .write_private(&self.env_file, env)
+            .map_err(NatsForgeError::io(&self.env_file))
+    }
+}
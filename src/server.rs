@@ -1,92 +1,717 @@
-use std::collections::HashMap;
-
-use crate::{config::ServerConfig, extract_account_id};
-
-pub fn generate_server_config(
-    server: &ServerConfig,
-    operator_jwt: &str,
-    system_account_id: &str,
-    resolver_preload: &str,
-    account_jwts: &HashMap<String, String>,
-) -> String {
-    let mut config = format!("port: {}\nserver_name: \"{}\"\n\n", server.port, server.name);
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    conf::{ConfDocument, ConfObject, ConfValue},
+    conf_auth::{self, CalloutKeys, Credential, Credentials},
+    config::{
+        AccountConfig, ExportConfig, ImportConfig, LatencySampling, MappingTarget, Permissions, RemoteTls,
+        ResolverType, ServerConfig, TlsConfig, UserConfig,
+    },
+    expand::Expansion,
+    extract_account_id, net,
+    tls::DevCerts,
+};
+
+/// The top-level options natsforge renders, which `extra_blocks` and `extra_config` can't set.
+pub(crate) const GENERATED_KEYS: &[&str] = &[
+    "host",
+    "port",
+    "server_name",
+    "client_advertise",
+    "max_connections",
+    "max_subscriptions",
+    "max_payload",
+    "max_control_line",
+    "write_deadline",
+    "ping_interval",
+    "ping_max",
+    "pid_file",
+    "ports_file_dir",
+    "server_tags",
+    "logfile",
+    "logfile_size_limit",
+    "debug",
+    "trace",
+    "logtime",
+    "lame_duck_duration",
+    "lame_duck_grace_period",
+    "http_port",
+    "https_port",
+    "jetstream",
+    "cluster",
+    "gateway",
+    "mqtt",
+    "tls",
+    "mappings",
+    "leafnodes",
+    "no_auth_user",
+    "default_permissions",
+    "authorization",
+    "accounts",
+    "operator",
+    "system_account",
+    "resolver",
+    "resolver_preload",
+];
+
+/// How a server's `nats.conf` authenticates clients.
+pub(crate) enum ServerAuth<'a> {
+    /// Trusts the operator and preloads account JWTs. `operator` is the operator JWT or the path
+    /// of a file holding it; nats-server accepts either.
+    Operator {
+        operator: &'a str,
+        system_account_id: &'a str,
+        resolver_preload: &'a ConfObject,
+        /// By the name the server knows each account by.
+        account_jwts: &'a HashMap<&'a str, &'a String>,
+    },
+    /// Defines the server's accounts with their users' credentials, and their permissions with the
+    /// derived `grants` applied. `callout` holds the keys of the server's auth callout, if any.
+    Conf {
+        credentials: &'a Credentials,
+        grants: &'a Expansion,
+        callout: Option<&'a CalloutKeys>,
+    },
+}
+
+impl ServerAuth<'_> {
+    /// How the config refers to `account`: by ID in operator mode, by name in conf mode.
+    fn account(&self, account: &str) -> String {
+        match self {
+            ServerAuth::Operator { account_jwts, .. } => account_id(account_jwts, account),
+            ServerAuth::Conf { .. } => account.to_string(),
+        }
+    }
+}
+
+/// `document` as text, followed by the server's `extra_config`.
+pub(crate) fn render(server: &ServerConfig, document: &ConfDocument) -> String {
+    let mut config = document.render();
+    if let Some(extra_config) = &server.extra_config {
+        config.push('\n');
+        config.push_str(extra_config);
+        if !extra_config.ends_with('\n') {
+            config.push('\n');
+        }
+    }
+    config
+}
+
+/// The blocks the server's `split_includes` and `split_resolver_preload` move out of `nats.conf`.
+pub(crate) fn split_blocks(server: &ServerConfig) -> Vec<&str> {
+    let mut blocks: Vec<&str> = server.split_includes.iter().map(String::as_str).collect();
+    if server.split_resolver_preload {
+        blocks.push("resolver_preload");
+    }
+    blocks.sort_unstable();
+    blocks.dedup();
+    blocks
+}
+
+/// Moves the server's split blocks out of `document`, leaving includes in their place, and returns
+/// the files they go to by name. The files sit next to `nats.conf`, so the includes are their
+/// bare names.
+pub(crate) fn split_includes(server: &ServerConfig, document: &mut ConfDocument) -> Vec<(String, String)> {
+    split_blocks(server)
+        .into_iter()
+        .filter_map(|block| {
+            let file_name = format!("{}.conf", block);
+            let included = document.split(block, &file_name)?;
+            Some((file_name, included.render()))
+        })
+        .collect()
+}
+
+/// The tree of the server's `nats.conf`, which has its `extra_blocks` but not its `extra_config`;
+/// see [`render`].
+pub(crate) fn server_document(server: &ServerConfig, jetstream_store_dir: &Path, auth: &ServerAuth) -> ConfDocument {
+    let mut config = ConfObject::new();
+    if let Some(host) = &server.host {
+        config.set("host", net::conf_host(host));
+    }
+    config.set("port", server.port).set("server_name", &server.name);
+    if let Some(client_advertise) = &server.client_advertise {
+        config.set("client_advertise", client_advertise);
+    }
+    for (key, limit) in [
+        ("max_connections", server.max_connections),
+        ("max_subscriptions", server.max_subscriptions),
+        ("max_payload", server.max_payload),
+        ("max_control_line", server.max_control_line),
+    ] {
+        if let Some(limit) = limit {
+            config.set(key, limit);
+        }
+    }
+    if let Some(write_deadline) = &server.write_deadline {
+        config.set("write_deadline", duration(write_deadline));
+    }
+    if let Some(ping_interval) = &server.ping_interval {
+        config.set("ping_interval", duration(ping_interval));
+    }
+    if let Some(ping_max) = server.ping_max {
+        config.set("ping_max", ping_max);
+    }
+    if let Some(pid_file) = &server.pid_file {
+        config.set("pid_file", pid_file);
+    }
+    if let Some(ports_file_dir) = &server.ports_file_dir {
+        config.set("ports_file_dir", ports_file_dir);
+    }
+    if !server.server_tags.is_empty() {
+        config.set("server_tags", server.server_tags.clone());
+    }
+    if let Some(logging) = &server.logging {
+        if let Some(logfile) = server.logfile() {
+            config.set("logfile", logfile.to_string_lossy().into_owned());
+        }
+        if let Some(size_limit) = logging.size_limit {
+            config.set("logfile_size_limit", size_limit);
+        }
+        config
+            .set("debug", logging.debug)
+            .set("trace", logging.trace)
+            .set("logtime", logging.logtime);
+    }
+    if let Some(lame_duck_duration) = &server.lame_duck_duration {
+        config.set("lame_duck_duration", duration(lame_duck_duration));
+    }
+    if let Some(lame_duck_grace_period) = &server.lame_duck_grace_period {
+        config.set("lame_duck_grace_period", duration(lame_duck_grace_period));
+    }
+    if let Some(monitoring) = &server.monitoring {
+        if let Some(http_port) = monitoring.http_port {
+            config.set("http_port", http_port);
+        }
+        if let Some(https_port) = monitoring.https_port {
+            config.set("https_port", https_port);
+        }
+    }
     if server.jetstream.enabled {
-        config.push_str("jetstream {\n");
-        config.push_str(&format!(
-            "    store_dir: \"{}\"\n    domain: \"{}\"\n",
-            server.jetstream.store_dir.as_ref().unwrap_or(&"jetstream".to_string()),
-            server.jetstream.domain.as_ref().unwrap_or(&"core".to_string())
-        ));
+        let jetstream = config.object_mut("jetstream");
+        jetstream
+            .set("store_dir", jetstream_store_dir.to_string_lossy().into_owned())
+            .set("domain", server.jetstream.domain.as_deref().unwrap_or("core"));
         if let Some(max_mem) = server.jetstream.max_memory {
-            config.push_str(&format!("    max_memory_store: {}\n", max_mem));
+            jetstream.set("max_memory_store", max_mem);
         }
         if let Some(max_store) = server.jetstream.max_storage {
-            config.push_str(&format!("    max_file_store: {}\n", max_store));
+            jetstream.set("max_file_store", max_store);
         }
         if let Some(transform) = &server.jetstream.subject_transform {
-            config.push_str(&format!(
-                "    subject_transform {{ src: \"{}\", dest: \"{}\" }}\n",
-                transform.src, transform.dest
-            ));
+            jetstream.set(
+                "subject_transform",
+                ConfObject::new()
+                    .with("src", &transform.src)
+                    .with("dest", &transform.dest),
+            );
         }
         if !server.jetstream.republish.is_empty() {
-            config.push_str("    republish = [\n");
-            for repub in &server.jetstream.republish {
-                config.push_str(&format!(
-                    "        {{ src: \"{}\", dest: \"{}\" }},\n",
-                    repub.src, repub.dest
-                ));
-            }
-            config.push_str("    ]\n");
+            let republish: Vec<ConfObject> = server
+                .jetstream
+                .republish
+                .iter()
+                .map(|repub| ConfObject::new().with("src", &repub.src).with("dest", &repub.dest))
+                .collect();
+            jetstream.set("republish", republish);
         }
-        config.push_str("}\n\n");
     }
-    if let Some(tls) = &server.tls {
-        config.push_str(&format!(
-            "tls {{\n    cert_file: \"{}\"\n    key_file: \"{}\"\n",
-            tls.cert_file, tls.key_file
-        ));
-        if let Some(ca_file) = &tls.ca_file {
-            config.push_str(&format!("    ca_file: \"{}\"\n", ca_file));
+    if let Some(cluster) = &server.cluster {
+        let block = config.object_mut("cluster");
+        block.set("name", &cluster.name).set("port", cluster.port);
+        if let Some(advertise) = &cluster.advertise {
+            block.set("advertise", advertise);
+        }
+        if let Some(auth) = &cluster.auth {
+            block.set(
+                "authorization",
+                ConfObject::new()
+                    .with("user", &auth.user)
+                    .with("password", &auth.password),
+            );
+        }
+        if !cluster.routes.is_empty() {
+            let routes: Vec<String> = cluster
+                .routes
+                .iter()
+                .map(|route| net::route_url(route, cluster.auth.as_ref()))
+                .collect();
+            block.set("routes", routes);
         }
-        config.push_str("}\n\n");
     }
-    if !server.mappings.is_empty() {
-        config.push_str("mappings: {\n");
-        for (src, dest) in &server.mappings {
-            config.push_str(&format!("    \"{}\": \"{}\",\n", src, dest));
+    if let Some(gateway) = &server.gateway {
+        let block = config.object_mut("gateway");
+        block.set("name", &gateway.name).set("port", gateway.port);
+        if gateway.reject_unknown_cluster {
+            block.set("reject_unknown_cluster", true);
+        }
+        if let Some(tls) = &gateway.tls {
+            block.set("tls", tls_block(tls));
+        }
+        if !gateway.gateways.is_empty() {
+            let gateways: Vec<ConfObject> = gateway
+                .gateways
+                .iter()
+                .map(|remote| {
+                    let urls: Vec<String> = remote
+                        .urls
+                        .iter()
+                        .map(|url| net::normalize_url(url).unwrap_or_else(|_| url.clone()))
+                        .collect();
+                    ConfObject::new().with("name", &remote.name).with("urls", urls)
+                })
+                .collect();
+            block.set("gateways", gateways);
+        }
+    }
+    if let Some(mqtt) = &server.mqtt {
+        let block = config.object_mut("mqtt");
+        block.set("port", mqtt.port);
+        if let Some(tls) = &mqtt.tls {
+            block.set("tls", tls_block(tls));
+        }
+        if let Some(ack_wait) = &mqtt.ack_wait {
+            block.set("ack_wait", duration(ack_wait));
+        }
+        if let Some(max_ack_pending) = mqtt.max_ack_pending {
+            block.set("max_ack_pending", max_ack_pending);
+        }
+        if let Some(domain) = &mqtt.jetstream_domain {
+            block.set("js_domain", domain);
+        }
+    }
+    let monitoring_tls = server
+        .monitoring
+        .as_ref()
+        .and_then(|monitoring| monitoring.tls.as_ref());
+    if let Some(tls) = server.tls.as_ref().or(monitoring_tls) {
+        if tls.dev {
+            let certs = DevCerts::in_dir(&server.output_dir).server_tls();
+            let tls = TlsConfig {
+                cert_file: certs.cert_file,
+                key_file: certs.key_file,
+                ca_file: certs.ca_file,
+                ..tls.clone()
+            };
+            config.set("tls", tls_block(&tls));
+        } else {
+            config.set("tls", tls_block(tls));
         }
-        config.push_str("}\n\n");
+    }
+    if !server.mappings.is_empty() {
+        config.set("mappings", mappings_block(&server.mappings));
     }
     if let Some(port) = server.leafnodes.port {
-        config.push_str(&format!("leafnodes {{\n    port: {}\n}}\n\n", port));
+        let leafnodes = config.object_mut("leafnodes");
+        leafnodes.set("port", port);
+        if let Some(advertise) = &server.leafnodes.advertise {
+            leafnodes.set("advertise", advertise);
+        }
+        if let Some(tls) = &server.leafnodes.tls {
+            leafnodes.set("tls", tls_block(tls));
+        }
+        if let Some(min_version) = &server.leafnodes.min_version {
+            leafnodes.set("min_version", min_version);
+        }
+        if let Some(authorization) = &server.leafnodes.authorization {
+            let account_id = authorization.account.as_ref().map(|account| auth.account(account));
+            let users: Vec<ConfObject> = authorization
+                .users
+                .iter()
+                .map(|user| {
+                    let mut entry = ConfObject::new()
+                        .with("user", &user.user)
+                        .with("password", &user.password);
+                    if let Some(account_id) = &account_id {
+                        entry.set("account", account_id);
+                    }
+                    entry
+                })
+                .collect();
+            leafnodes.set("authorization", ConfObject::new().with("users", users));
+        }
     }
     if !server.leafnodes.remotes.is_empty() {
-        config.push_str("leafnodes {\n    remotes = [\n");
-        for remote in &server.leafnodes.remotes {
-            let account_jwt = account_jwts
-                .get(&remote.account)
-                .unwrap_or_else(|| panic!("Missing JWT for {}", remote.account));
-            let account_id = extract_account_id(account_jwt)
-                .unwrap_or_else(|_| panic!("Failed to extract ID for {}", remote.account));
-            let creds_path = server.output_dir.join(&remote.credentials);
-            config.push_str(&format!(
-                "        {{ url: \"{}\", account: \"{}\", credentials: \"{}\" }},\n",
-                remote.url,
-                account_id,
-                creds_path.to_string_lossy()
-            ));
-        }
-        config.push_str("    ]\n}\n\n");
-    }
-    config.push_str(&format!(
-        "operator: \"{}\"\nsystem_account: \"{}\"\nresolver: MEMORY\n",
-        operator_jwt, system_account_id
-    ));
-    if !resolver_preload.is_empty() {
-        config.push_str("resolver_preload: {\n");
-        config.push_str(resolver_preload);
-        config.push_str("\n}\n");
+        let remotes: Vec<ConfObject> = server
+            .leafnodes
+            .remotes
+            .iter()
+            .map(|remote| {
+                let creds_path = remote.credentials_path(&server.output_dir);
+                let url = net::normalize_url(&remote.url).unwrap_or_else(|_| remote.url.clone());
+                let url = match (&remote.tls, url.strip_prefix("nats://")) {
+                    (Some(_), Some(rest)) => format!("tls://{}", rest),
+                    _ => url,
+                };
+                let mut entry = ConfObject::new()
+                    .with("url", url)
+                    .with("account", auth.account(&remote.account))
+                    .with("credentials", creds_path.to_string_lossy().into_owned());
+                for (key, subjects) in [
+                    ("deny_imports", &remote.deny_imports),
+                    ("deny_exports", &remote.deny_exports),
+                ] {
+                    if !subjects.is_empty() {
+                        entry.set(key, subjects.clone());
+                    }
+                }
+                if let Some(tls) = &remote.tls {
+                    entry.set("tls", remote_tls_block(tls));
+                }
+                entry
+            })
+            .collect();
+        config.object_mut("leafnodes").set("remotes", remotes);
     }
-    config
+    match auth {
+        ServerAuth::Operator {
+            operator,
+            system_account_id,
+            resolver_preload,
+            ..
+        } => {
+            config
+                .set("operator", *operator)
+                .set("system_account", *system_account_id);
+            set_resolver(&mut config, server);
+            if !resolver_preload.is_empty() {
+                config.set("resolver_preload", (*resolver_preload).clone());
+            }
+        }
+        ServerAuth::Conf {
+            credentials,
+            grants,
+            callout,
+        } => {
+            set_conf_auth(&mut config, server, credentials, grants);
+            if let Some(keys) = callout {
+                set_auth_callout(&mut config, server, credentials, keys);
+            }
+        }
+    }
+    let mut extra_blocks: Vec<_> = server.extra_blocks.iter().collect();
+    extra_blocks.sort_by_key(|(name, _)| *name);
+    for (name, value) in extra_blocks {
+        config.set(name, ConfValue::try_from(value).expect("extra_blocks are validated"));
+    }
+    ConfDocument::new(config)
+}
+
+/// `mappings` sorted by source subject.
+fn mappings_block(mappings: &HashMap<String, MappingTarget>) -> ConfObject {
+    let mut mappings: Vec<_> = mappings.iter().collect();
+    mappings.sort_by_key(|(src, _)| *src);
+    mappings
+        .into_iter()
+        .map(|(src, target)| {
+            let target = match target {
+                MappingTarget::Subject(dest) => ConfValue::from(dest),
+                MappingTarget::Weighted(dests) => dests
+                    .iter()
+                    .map(|dest| {
+                        ConfObject::new()
+                            .with("destination", &dest.dest)
+                            .with("weight", ConfValue::Raw(format!("{}%", dest.weight)))
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            };
+            (src.clone(), target)
+        })
+        .collect()
+}
+
+fn set_resolver(config: &mut ConfObject, server: &ServerConfig) {
+    match &server.resolver {
+        ResolverType::Full {
+            allow_delete, interval, ..
+        } => {
+            let dir = server.resolver_dir().unwrap_or_default();
+            let mut resolver = ConfObject::new()
+                .with("type", ConfValue::Raw("full".to_string()))
+                .with("dir", dir.to_string_lossy().into_owned())
+                .with("allow_delete", *allow_delete);
+            if let Some(interval) = interval {
+                resolver.set("interval", duration(interval));
+            }
+            config.set("resolver", resolver);
+        }
+        ResolverType::Cache { ttl, .. } => {
+            let dir = server.resolver_dir().unwrap_or_default();
+            let mut resolver = ConfObject::new()
+                .with("type", ConfValue::Raw("cache".to_string()))
+                .with("dir", dir.to_string_lossy().into_owned());
+            if let Some(ttl) = ttl {
+                resolver.set("ttl", duration(ttl));
+            }
+            config.set("resolver", resolver);
+        }
+        ResolverType::Url(url) => {
+            config.set("resolver", ConfValue::Raw(format!("URL({})", url)));
+        }
+        ResolverType::Memory => {
+            config.set("resolver", ConfValue::Raw("MEMORY".to_string()));
+        }
+    }
+}
+
+/// Sets the accounts of a conf-mode server with their users, or the `authorization` of its token
+/// user, along with `no_auth_user` and `default_permissions`.
+fn set_conf_auth(config: &mut ConfObject, server: &ServerConfig, credentials: &Credentials, grants: &Expansion) {
+    let credential = |account: &AccountConfig, user: &UserConfig| {
+        credentials
+            .get(&account.name)
+            .and_then(|users| users.get(&user.name))
+            .unwrap_or_else(|| panic!("No credential for user {} in account {}", user.name, account.name))
+    };
+    if let Some(no_auth_user) = &server.no_auth_user {
+        let referenced = server.accounts.iter().find_map(|account| {
+            account
+                .users
+                .iter()
+                .find(|user| conf_auth::username(&account.name, &user.name) == *no_auth_user)
+                .map(|user| credential(account, user))
+        });
+        // nats-server names nkey users by their public key.
+        if let Some(Credential::Nkey { public_key, .. }) = referenced {
+            config.set("no_auth_user", public_key);
+        } else {
+            config.set("no_auth_user", no_auth_user);
+        }
+    }
+    if let Some(permissions) = &server.default_permissions {
+        config.set("default_permissions", permissions_block(permissions));
+    }
+
+    let mut accounts = ConfObject::new();
+    for account in &server.accounts {
+        let mut block = ConfObject::new();
+        let mut users = Vec::new();
+        for user in &account.users {
+            let effective_user = grants.apply(account, user);
+            let user = &effective_user;
+            let mut entry = match credential(account, user) {
+                Credential::Nkey { public_key, .. } => ConfObject::new().with("nkey", public_key),
+                Credential::Password(password) => ConfObject::new()
+                    .with("user", conf_auth::username(&account.name, &user.name))
+                    .with("password", password),
+                Credential::Token(token) => {
+                    // Validation leaves the token user alone on its server.
+                    let mut authorization = ConfObject::new().with("token", token);
+                    set_user_permissions(&mut authorization, user);
+                    config.set("authorization", authorization);
+                    continue;
+                }
+            };
+            set_user_permissions(&mut entry, user);
+            users.push(entry);
+        }
+        if !users.is_empty() {
+            block.set("users", users);
+        }
+        if account.jetstream {
+            block.set("jetstream", ConfValue::Raw("enabled".to_string()));
+        }
+        if !account.exports.is_empty() {
+            let exports: Vec<ConfObject> = account
+                .exports
+                .iter()
+                .map(|export| export_entry(server, account, export))
+                .collect();
+            block.set("exports", exports);
+        }
+        if !account.imports.is_empty() {
+            block.set("imports", account.imports.iter().map(import_entry).collect::<Vec<_>>());
+        }
+        if !account.mappings.is_empty() {
+            block.set("mappings", mappings_block(&account.mappings));
+        }
+        accounts.set(&account.name, block);
+        if account.is_system_account {
+            config.set("system_account", &account.name);
+        }
+    }
+    if !accounts.is_empty() {
+        config.set("accounts", accounts);
+    }
+}
+
+/// Sets `authorization.auth_callout`, which names the auth users as nats-server knows them: nkey
+/// users by their public key, password users by their username.
+fn set_auth_callout(config: &mut ConfObject, server: &ServerConfig, credentials: &Credentials, keys: &CalloutKeys) {
+    let Some(callout) = &server.auth_callout else {
+        return;
+    };
+    let auth_users: Vec<String> = callout
+        .auth_users
+        .iter()
+        .map(|user| {
+            match credentials
+                .get(&callout.issuer_account)
+                .and_then(|users| users.get(user))
+            {
+                Some(Credential::Nkey { public_key, .. }) => public_key.clone(),
+                _ => conf_auth::username(&callout.issuer_account, user),
+            }
+        })
+        .collect();
+    let mut block = ConfObject::new()
+        .with("issuer", keys.issuer.public_key())
+        .with("account", &callout.issuer_account)
+        .with("auth_users", auth_users);
+    if !callout.allowed_accounts.is_empty() {
+        block.set("allowed_accounts", callout.allowed_accounts.clone());
+    }
+    if let Some(xkey) = &keys.xkey {
+        block.set("xkey", xkey.public_key());
+    }
+    config.object_mut("authorization").set("auth_callout", block);
+}
+
+/// Sets `permissions` on a user entry, unless the user is unrestricted.
+fn set_user_permissions(entry: &mut ConfObject, user: &UserConfig) {
+    let mut permissions = permissions_block(&conf_auth::permissions(user));
+    if let Some(max) = user.allow_pub_response {
+        let mut responses = ConfObject::new().with("max", max);
+        if let Some(ttl) = &user.response_ttl {
+            responses.set("expires", duration(ttl));
+        }
+        permissions.set("allow_responses", responses);
+    }
+    if !permissions.is_empty() {
+        entry.set("permissions", permissions);
+    }
+}
+
+/// An `exports` entry. Private exports list the accounts on the server that import them.
+fn export_entry(server: &ServerConfig, account: &AccountConfig, export: &ExportConfig) -> ConfObject {
+    let mut entry = if export.is_service {
+        let mut entry = ConfObject::new().with("service", &export.subject);
+        if let Some(response_type) = &export.response_type {
+            entry.set("response_type", response_type.as_str().to_ascii_lowercase());
+        }
+        if let Some(subject) = &export.latency_subject {
+            let sampling = match export.latency_sampling.unwrap_or(LatencySampling::Percent(100)) {
+                LatencySampling::Percent(percent) => ConfValue::Raw(format!("{}%", percent)),
+                LatencySampling::Headers => ConfValue::from("headers"),
+            };
+            entry.set(
+                "latency",
+                ConfObject::new().with("sampling", sampling).with("subject", subject),
+            );
+        }
+        entry
+    } else {
+        ConfObject::new().with("stream", &export.subject)
+    };
+    if let Some(position) = export.account_token_position {
+        entry.set("account_token_position", position);
+    }
+    if export.private {
+        let importers: Vec<&str> = server
+            .accounts
+            .iter()
+            .filter(|importer| {
+                importer.imports.iter().any(|import| {
+                    import.account == account.name
+                        && account
+                            .private_export(import)
+                            .is_some_and(|private| private.subject == export.subject)
+                })
+            })
+            .map(|importer| importer.name.as_str())
+            .collect();
+        entry.set("accounts", importers);
+    }
+    entry
+}
+
+fn import_entry(import: &ImportConfig) -> ConfObject {
+    let kind = if import.service { "service" } else { "stream" };
+    let mut entry = ConfObject::new().with(
+        kind,
+        ConfObject::new()
+            .with("account", &import.account)
+            .with("subject", &import.subject),
+    );
+    if let Some(local_subject) = &import.local_subject {
+        entry.set("to", local_subject);
+    }
+    if import.share {
+        entry.set("share", true);
+    }
+    entry
+}
+
+fn duration(value: &str) -> ConfValue {
+    ConfValue::Duration(value.to_string())
+}
+
+/// The ID of `account`, whose JWT has been issued by the time configs are rendered.
+fn account_id(account_jwts: &HashMap<&str, &String>, account: &str) -> String {
+    let account_jwt = account_jwts
+        .get(account)
+        .unwrap_or_else(|| panic!("Missing JWT for {}", account));
+    extract_account_id(account_jwt).unwrap_or_else(|_| panic!("Failed to extract ID for {}", account))
+}
+
+fn remote_tls_block(tls: &RemoteTls) -> ConfObject {
+    let mut block = ConfObject::new();
+    for (key, file) in [
+        ("ca_file", &tls.ca_file),
+        ("cert_file", &tls.cert_file),
+        ("key_file", &tls.key_file),
+    ] {
+        if let Some(file) = file {
+            block.set(key, file);
+        }
+    }
+    if tls.insecure {
+        block.set("insecure", true);
+    }
+    block
+}
+
+/// `permissions` with the empty lists left out.
+fn permissions_block(permissions: &Permissions) -> ConfObject {
+    let mut block = ConfObject::new();
+    for (direction, subjects) in [("publish", &permissions.publish), ("subscribe", &permissions.subscribe)] {
+        let mut rules = ConfObject::new();
+        for (key, list) in [("allow", &subjects.allow), ("deny", &subjects.deny)] {
+            if !list.is_empty() {
+                rules.set(key, list.clone());
+            }
+        }
+        if !rules.is_empty() {
+            block.set(direction, rules);
+        }
+    }
+    block
+}
+
+fn tls_block(tls: &TlsConfig) -> ConfObject {
+    let mut block = ConfObject::new()
+        .with("cert_file", &tls.cert_file)
+        .with("key_file", &tls.key_file);
+    if let Some(ca_file) = &tls.ca_file {
+        block.set("ca_file", ca_file);
+    }
+    if tls.verify {
+        block.set("verify", true);
+    }
+    if tls.verify_and_map {
+        block.set("verify_and_map", true);
+    }
+    if let Some(timeout) = tls.timeout {
+        block.set("timeout", timeout);
+    }
+    for (key, names) in [
+        ("cipher_suites", &tls.cipher_suites),
+        ("curve_preferences", &tls.curve_preferences),
+    ] {
+        if !names.is_empty() {
+            block.set(key, names.clone());
+        }
+    }
+    block
 }
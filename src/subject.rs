@@ -0,0 +1,157 @@
+use crate::error::{NatsForgeError, Result};
+
+/// Checks NATS subject grammar: non-empty tokens separated by dots, without whitespace, where `*`
+/// may only be a whole token and `>` only the whole last token.
+pub fn validate_subject(subject: &str) -> Result<()> {
+    grammar_error(subject).map_or(Ok(()), |reason| Err(invalid(subject, reason)))
+}
+
+/// Checks the destination of a subject mapping, whose tokens may also be mapping functions such as
+/// `{{wildcard(1)}}`.
+pub fn validate_mapping_destination(destination: &str) -> Result<()> {
+    let mut plain = String::new();
+    let mut rest = destination;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| invalid(destination, "unclosed {{"))?;
+        plain.push_str(&rest[..start]);
+        plain.push('_');
+        rest = &rest[start + end + 2..];
+    }
+    plain.push_str(rest);
+    grammar_error(&plain).map_or(Ok(()), |reason| Err(invalid(destination, reason)))
+}
+
+/// Checks that a transform's `destination` keeps the wildcards of `source`: a `>` in one needs a
+/// `>` in the other, bare `*` tokens must match in number, and mapping functions may only refer to
+/// `*` tokens that exist, by their 1-based position.
+pub fn validate_transform(source: &str, destination: &str) -> Result<()> {
+    validate_subject(source)?;
+    validate_mapping_destination(destination)?;
+    let invalid = |reason: String| {
+        NatsForgeError::InvalidConfig(format!(
+            "Invalid transform {:?} -> {:?}: {}",
+            source, destination, reason
+        ))
+    };
+    let source_tokens: Vec<&str> = source.split('.').collect();
+    let destination_tokens: Vec<&str> = destination.split('.').collect();
+    let wildcards = source_tokens.iter().filter(|token| **token == "*").count();
+    if source_tokens.last() == Some(&">") && destination_tokens.last() != Some(&">") {
+        return Err(invalid("the destination drops the trailing >".to_string()));
+    }
+    if destination_tokens.last() == Some(&">") && source_tokens.last() != Some(&">") {
+        return Err(invalid("the destination has a > the source doesn't".to_string()));
+    }
+
+    let references = wildcard_references(destination);
+    let bare = destination_tokens.iter().filter(|token| **token == "*").count();
+    if bare > 0 && !references.is_empty() {
+        return Err(invalid("mixes * tokens with mapping functions".to_string()));
+    }
+    if bare > 0 && bare != wildcards {
+        return Err(invalid(format!(
+            "{} * tokens for the {} of the source",
+            bare, wildcards
+        )));
+    }
+    if let Some(index) = references.iter().find(|index| **index == 0 || **index > wildcards) {
+        return Err(invalid(format!(
+            "refers to wildcard {}, but the source has {}",
+            index, wildcards
+        )));
+    }
+    Ok(())
+}
+
+/// The source wildcard positions a destination refers to through `{{wildcard(n)}}`, `$n` and the
+/// other mapping functions.
+fn wildcard_references(destination: &str) -> Vec<usize> {
+    let mut references = Vec::new();
+    for token in destination.split('.') {
+        if let Some(index) = token.strip_prefix('$') {
+            references.extend(index.parse::<usize>().ok());
+        }
+    }
+    let mut rest = destination;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let function = &rest[start + 2..start + end];
+        rest = &rest[start + end + 2..];
+        let Some((name, args)) = function.trim().trim_end_matches(')').split_once('(') else {
+            continue;
+        };
+        let args: Vec<&str> = args.split(',').map(str::trim).collect();
+        // partition(n, i, j, ...) hashes wildcards i, j, ... into n partitions; the other
+        // functions take the wildcard as their first argument.
+        let indices = match name.trim().to_ascii_lowercase().as_str() {
+            "partition" => args.get(1..).unwrap_or_default(),
+            _ => args.get(..1).unwrap_or_default(),
+        };
+        references.extend(indices.iter().filter_map(|index| index.parse::<usize>().ok()));
+    }
+    references
+}
+
+fn grammar_error(subject: &str) -> Option<&'static str> {
+    if subject.chars().any(char::is_whitespace) {
+        return Some("subjects can't contain whitespace");
+    }
+    let tokens: Vec<&str> = subject.split('.').collect();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_empty() {
+            return Some("empty token");
+        }
+        if *token == ">" && i + 1 != tokens.len() {
+            return Some("> must be the last token");
+        }
+        if token.len() > 1 && (token.contains('*') || token.contains('>')) {
+            return Some("wildcards must be a whole token");
+        }
+    }
+    None
+}
+
+fn invalid(subject: &str, reason: &str) -> NatsForgeError {
+    NatsForgeError::InvalidConfig(format!("Invalid subject {:?}: {}", subject, reason))
+}
+
+/// Whether every subject matched by `subject` is also matched by `pattern`.
+///
+/// Both sides may contain wildcards: `*` matches a single token, a trailing `>` one or more.
+pub fn covers(pattern: &str, subject: &str) -> bool {
+    let mut pattern_tokens = pattern.split('.');
+    let mut subject_tokens = subject.split('.');
+    loop {
+        match (pattern_tokens.next(), subject_tokens.next()) {
+            (Some(">"), Some(_)) => return true,
+            (Some("*"), Some(s)) if s != ">" => {}
+            (Some(p), Some(s)) if p == s => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Whether any of `patterns` covers `subject`.
+pub fn any_covers<'a>(patterns: impl IntoIterator<Item = &'a String>, subject: &str) -> bool {
+    patterns.into_iter().any(|p| covers(p, subject))
+}
+
+/// Whether some subject is matched by both `a` and `b`.
+pub fn overlaps(a: &str, b: &str) -> bool {
+    let mut a_tokens = a.split('.');
+    let mut b_tokens = b.split('.');
+    loop {
+        match (a_tokens.next(), b_tokens.next()) {
+            (Some(">"), Some(_)) | (Some(_), Some(">")) => return true,
+            (Some("*"), Some(_)) | (Some(_), Some("*")) => {}
+            (Some(x), Some(y)) if x == y => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::{
+    config::{AccountConfig, AccountTemplate, NatsConfig},
+    error::{NatsForgeError, Result},
+};
+
+impl NatsConfig {
+    /// Replaces every server's `account_templates` with one account per tenant, appended to its
+    /// `accounts`. An instantiated account may not share its name with any other account.
+    /// [`NatsForge::from_config`](crate::NatsForge::from_config) does this right after expanding
+    /// `clusters`.
+    pub fn expand_account_templates(&mut self) -> Result<()> {
+        let mut names: HashSet<String> = self
+            .servers
+            .iter()
+            .flat_map(|server| &server.accounts)
+            .map(|account| account.name.clone())
+            .collect();
+        for server in &mut self.servers {
+            for template in std::mem::take(&mut server.account_templates) {
+                for account in expand(&template)? {
+                    if !names.insert(account.name.clone()) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Account {} from template {} is already defined",
+                            account.name, template.template.name
+                        )));
+                    }
+                    server.accounts.push(account);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn expand(template: &AccountTemplate) -> Result<Vec<AccountConfig>> {
+    let invalid = |reason: String| {
+        NatsForgeError::InvalidConfig(format!("Account template {} {}", template.template.name, reason))
+    };
+    let tenants: Vec<String> = match (template.instances.is_empty(), template.count) {
+        (false, None) => template.instances.clone(),
+        (true, Some(count)) if count > 0 => (1..=count).map(|tenant| tenant.to_string()).collect(),
+        (false, Some(_)) => return Err(invalid("sets both instances and count".to_string())),
+        (true, _) => return Err(invalid("has no instances".to_string())),
+    };
+    if let Some(var) = &template.subject_prefix_var {
+        if matches!(var.as_str(), "tenant" | "name") || var.is_empty() || var.contains(['{', '}']) {
+            return Err(invalid(format!("can't use {:?} as its subject_prefix_var", var)));
+        }
+    }
+    let template_json = serde_json::to_value(&template.template).expect("AccountConfig serializes");
+
+    let mut accounts = Vec::new();
+    for tenant in tenants {
+        // The tenant becomes a subject token, so it has to be one.
+        if tenant.is_empty() || tenant.contains(|c: char| c == '.' || c == '*' || c == '>' || c.is_whitespace()) {
+            return Err(invalid(format!(
+                "has tenant {:?}, which can't be a subject token",
+                tenant
+            )));
+        }
+        let mut placeholders = vec![("{tenant}".to_string(), tenant.clone())];
+        if let Some(var) = &template.subject_prefix_var {
+            placeholders.push((format!("{{{}}}", var), tenant.clone()));
+        }
+        let name = substitute(&template.template.name, &placeholders);
+        placeholders.push(("{name}".to_string(), name));
+
+        let mut account = template_json.clone();
+        substitute_strings(&mut account, &|text| substitute(text, &placeholders));
+        accounts.push(serde_json::from_value(account).expect("an instantiated AccountConfig deserializes"));
+    }
+    Ok(accounts)
+}
+
+/// Rewrites every string and object key of `value` with `substitute`.
+pub(crate) fn substitute_strings(value: &mut Value, substitute: &impl Fn(&str) -> String) {
+    match value {
+        Value::String(text) => *text = substitute(text),
+        Value::Array(items) => items.iter_mut().for_each(|item| substitute_strings(item, substitute)),
+        Value::Object(object) => {
+            *object = std::mem::take(object)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    substitute_strings(&mut value, substitute);
+                    (substitute(&key), value)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+fn substitute(text: &str, placeholders: &[(String, String)]) -> String {
+    placeholders
+        .iter()
+        .fold(text.to_string(), |text, (placeholder, value)| {
+            text.replace(placeholder, value)
+        })
+}
@@ -0,0 +1,221 @@
+use std::{
+    cmp::Reverse,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+pub const PHASE_OPERATOR: &str = "operator creation";
+pub const PHASE_ACCOUNTS: &str = "account creation";
+pub const PHASE_EDITS: &str = "account edits";
+pub const PHASE_EXPORTS: &str = "exports";
+pub const PHASE_IMPORTS: &str = "imports";
+pub const PHASE_USERS: &str = "user creds";
+pub const PHASE_RENDER: &str = "config rendering";
+pub const PHASE_WRITES: &str = "file writes";
+pub const PHASE_OTHER: &str = "other";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// How long one operator, account, user or import took to issue, within its phase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemTiming {
+    pub phase: String,
+    /// The operator or account name, `account/user` for users and `account: subject from exporter`
+    /// for imports.
+    pub item: String,
+    pub duration: Duration,
+}
+
+/// Where the time of one `initialize()` run went.
+///
+/// `phases` always sums to `total`; anything not attributed to a named phase is reported as
+/// `other`. `processes` breaks down the time spent waiting on external processes (e.g. nsc) by
+/// command and overlaps with the phases they ran in. `steps` does the same for the steps an
+/// account is created in, such as [`PHASE_EDITS`] and [`PHASE_EXPORTS`]: they are summed over
+/// accounts created concurrently, so they overlap each other as well as account (or, for the
+/// system account, operator) creation, and are left out of `phases`. `items` lists what each
+/// phase issued, in the order it finished; items issued concurrently overlap, so they can add up
+/// to more than their phase.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SetupTimings {
+    pub total: Duration,
+    pub phases: Vec<PhaseTiming>,
+    pub processes: Vec<PhaseTiming>,
+    #[serde(default)]
+    pub steps: Vec<PhaseTiming>,
+    #[serde(default)]
+    pub items: Vec<ItemTiming>,
+}
+
+impl SetupTimings {
+    pub fn phase(&self, name: &str) -> Option<Duration> {
+        self.phases.iter().find(|p| p.name == name).map(|p| p.duration)
+    }
+
+    /// How long the `name` step took, summed over accounts, e.g. `step(PHASE_EDITS)`.
+    pub fn step(&self, name: &str) -> Option<Duration> {
+        self.steps.iter().find(|s| s.name == name).map(|s| s.duration)
+    }
+
+    /// How long `item` of `phase` took, e.g. `item(PHASE_ACCOUNTS, "APP")`.
+    pub fn item(&self, phase: &str, item: &str) -> Option<Duration> {
+        self.items
+            .iter()
+            .find(|i| i.phase == phase && i.item == item)
+            .map(|i| i.duration)
+    }
+
+    pub fn external_total(&self) -> Duration {
+        self.processes.iter().map(|p| p.duration).sum()
+    }
+
+    /// Renders the phases and process times as a table, slowest first.
+    pub fn breakdown_table(&self) -> String {
+        let mut out = String::new();
+        let mut phases: Vec<&PhaseTiming> = self.phases.iter().collect();
+        phases.sort_by_key(|p| Reverse(p.duration));
+        out.push_str(&format!("{:<24} {:>12} {:>7}\n", "phase", "ms", "%"));
+        for phase in phases {
+            out.push_str(&format_row(&phase.name, phase.duration, self.total));
+        }
+        out.push_str(&format_row("total", self.total, self.total));
+
+        if !self.processes.is_empty() {
+            let mut processes: Vec<&PhaseTiming> = self.processes.iter().collect();
+            processes.sort_by_key(|p| Reverse(p.duration));
+            out.push_str(&format!("\n{:<24} {:>12} {:>7}\n", "external process", "ms", "%"));
+            for process in processes {
+                out.push_str(&format_row(&process.name, process.duration, self.total));
+            }
+            out.push_str(&format_row("external total", self.external_total(), self.total));
+        }
+
+        if !self.steps.is_empty() {
+            let mut steps: Vec<&PhaseTiming> = self.steps.iter().collect();
+            steps.sort_by_key(|s| Reverse(s.duration));
+            out.push_str(&format!("\n{:<24} {:>12} {:>7}\n", "account step", "ms", "%"));
+            for step in steps {
+                out.push_str(&format_row(&step.name, step.duration, self.total));
+            }
+        }
+
+        if !self.items.is_empty() {
+            let mut items: Vec<&ItemTiming> = self.items.iter().collect();
+            items.sort_by_key(|i| Reverse(i.duration));
+            out.push_str(&format!("\n{:<24} {:>12} {:>7}\n", "slowest items", "ms", "%"));
+            for item in items.into_iter().take(SLOWEST_ITEMS) {
+                out.push_str(&format_row(
+                    &format!("{} {}", item_kind(&item.phase), item.item),
+                    item.duration,
+                    self.total,
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// How many items `breakdown_table` lists.
+const SLOWEST_ITEMS: usize = 10;
+
+/// What an item of `phase` is, for labelling it in `breakdown_table`.
+fn item_kind(phase: &str) -> &str {
+    match phase {
+        PHASE_OPERATOR => "operator",
+        PHASE_ACCOUNTS => "account",
+        PHASE_USERS => "user",
+        PHASE_IMPORTS => "import",
+        other => other,
+    }
+}
+
+fn format_row(name: &str, duration: Duration, total: Duration) -> String {
+    let percent = if total.is_zero() {
+        0.0
+    } else {
+        duration.as_secs_f64() / total.as_secs_f64() * 100.0
+    };
+    format!(
+        "{:<24} {:>12.3} {:>6.1}%\n",
+        name,
+        duration.as_secs_f64() * 1000.0,
+        percent
+    )
+}
+
+/// Accumulates durations by name, keeping first-seen order, and the durations of single items.
+#[derive(Debug, Default)]
+pub(crate) struct TimingRecorder {
+    entries: Mutex<Vec<PhaseTiming>>,
+    items: Mutex<Vec<ItemTiming>>,
+}
+
+impl TimingRecorder {
+    pub(crate) fn record(&self, name: &str, duration: Duration) {
+        let mut entries = self.entries.lock().expect("timing recorder poisoned");
+        match entries.iter_mut().find(|e| e.name == name) {
+            Some(entry) => entry.duration += duration,
+            None => entries.push(PhaseTiming {
+                name: name.to_string(),
+                duration,
+            }),
+        }
+    }
+
+    pub(crate) fn time<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start.elapsed());
+        result
+    }
+
+    /// Awaits `fut`, recording how long it took as `name`.
+    pub(crate) async fn time_async<F: Future>(&self, name: &str, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.await;
+        self.record(name, start.elapsed());
+        output
+    }
+
+    /// Awaits `fut`, recording how long it took as `item` of `phase`. The phase itself is recorded
+    /// separately, since items of one phase may run concurrently.
+    pub(crate) async fn timed<F: Future>(&self, phase: &str, item: impl Into<String>, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.await;
+        self.items.lock().expect("timing recorder poisoned").push(ItemTiming {
+            phase: phase.to_string(),
+            item: item.into(),
+            duration: start.elapsed(),
+        });
+        output
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<PhaseTiming> {
+        self.entries.lock().expect("timing recorder poisoned").clone()
+    }
+
+    /// Closes the run: fills in `other` so that the phases add up to `total`. The phases are timed
+    /// one after another within the run, so they never add up to more.
+    pub(crate) fn finish(&self, total: Duration, processes: Vec<PhaseTiming>, steps: Vec<PhaseTiming>) -> SetupTimings {
+        let mut phases = self.snapshot();
+        let accounted: Duration = phases.iter().map(|p| p.duration).sum();
+        phases.push(PhaseTiming {
+            name: PHASE_OTHER.to_string(),
+            duration: total.saturating_sub(accounted),
+        });
+        SetupTimings {
+            total,
+            phases,
+            processes,
+            steps,
+            items: self.items.lock().expect("timing recorder poisoned").clone(),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Self-signed certificates for development and test setups.
+//!
+//! [`generate_dev_certs`] creates a CA, a server certificate for a list of hosts and a client
+//! certificate, all signed by that CA with ECDSA P-256. They are meant for local clusters and
+//! tests; nothing here is suitable for production PKI.
+
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use base64::Engine;
+use chrono::{TimeDelta, Utc};
+use p256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    pkcs8::{EncodePrivateKey, LineEnding},
+    SecretKey,
+};
+use rand::{rngs::OsRng, RngCore};
+use yasna::{models::ObjectIdentifier, tags::TAG_UTCTIME, DERWriter, Tag};
+
+use crate::{
+    config::{Durability, RemoteTls, TlsConfig},
+    error::{NatsForgeError, Result},
+    net,
+    writer::{create_output_dir, ArtifactWriter, FsSink},
+};
+
+pub const CA_FILE: &str = "ca.pem";
+pub const SERVER_CERT_FILE: &str = "server.pem";
+pub const SERVER_KEY_FILE: &str = "server-key.pem";
+pub const CLIENT_CERT_FILE: &str = "client.pem";
+pub const CLIENT_KEY_FILE: &str = "client-key.pem";
+
+/// How long generated certificates stay valid.
+const VALIDITY_DAYS: i64 = 365;
+
+const OID_COMMON_NAME: &[u64] = &[2, 5, 4, 3];
+const OID_EC_PUBLIC_KEY: &[u64] = &[1, 2, 840, 10045, 2, 1];
+const OID_PRIME256V1: &[u64] = &[1, 2, 840, 10045, 3, 1, 7];
+const OID_ECDSA_WITH_SHA256: &[u64] = &[1, 2, 840, 10045, 4, 3, 2];
+const OID_BASIC_CONSTRAINTS: &[u64] = &[2, 5, 29, 19];
+const OID_KEY_USAGE: &[u64] = &[2, 5, 29, 15];
+const OID_EXT_KEY_USAGE: &[u64] = &[2, 5, 29, 37];
+const OID_SUBJECT_ALT_NAME: &[u64] = &[2, 5, 29, 17];
+const OID_SERVER_AUTH: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 3, 1];
+const OID_CLIENT_AUTH: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 3, 2];
+
+/// The files written by [`generate_dev_certs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevCerts {
+    pub ca_file: PathBuf,
+    pub server_cert_file: PathBuf,
+    pub server_key_file: PathBuf,
+    pub client_cert_file: PathBuf,
+    pub client_key_file: PathBuf,
+}
+
+impl DevCerts {
+    /// Where [`generate_dev_certs`] puts its files in `dir`.
+    pub fn in_dir(dir: &Path) -> Self {
+        DevCerts {
+            ca_file: dir.join(CA_FILE),
+            server_cert_file: dir.join(SERVER_CERT_FILE),
+            server_key_file: dir.join(SERVER_KEY_FILE),
+            client_cert_file: dir.join(CLIENT_CERT_FILE),
+            client_key_file: dir.join(CLIENT_KEY_FILE),
+        }
+    }
+
+    /// A server `tls` block using the server certificate, with the CA for verifying clients.
+    pub fn server_tls(&self) -> TlsConfig {
+        TlsConfig {
+            cert_file: self.server_cert_file.to_string_lossy().into_owned(),
+            key_file: self.server_key_file.to_string_lossy().into_owned(),
+            ca_file: Some(self.ca_file.to_string_lossy().into_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// TLS for a leafnode remote, presenting the client certificate and trusting the CA.
+    pub fn remote_tls(&self) -> RemoteTls {
+        RemoteTls {
+            ca_file: Some(self.ca_file.to_string_lossy().into_owned()),
+            cert_file: Some(self.client_cert_file.to_string_lossy().into_owned()),
+            key_file: Some(self.client_key_file.to_string_lossy().into_owned()),
+            insecure: false,
+        }
+    }
+}
+
+/// Generates a CA, a server certificate valid for `hosts` (hostnames or IP addresses) and a client
+/// certificate into `output_dir`, creating it if needed. Private keys are only readable by the
+/// current user. The CA's own key is not kept.
+pub fn generate_dev_certs(output_dir: &Path, hosts: &[String]) -> Result<DevCerts> {
+    create_output_dir(output_dir, false).map_err(NatsForgeError::io(output_dir))?;
+    let writer = ArtifactWriter::new(&FsSink, Durability::Fast);
+    for file in dev_cert_files(hosts)? {
+        let path = output_dir.join(file.name);
+        if file.private {
+            writer.write_private(&path, &file.contents)
+        } else {
+            writer.write(&path, &file.contents)
+        }
+        .map_err(NatsForgeError::io(&path))?;
+    }
+    Ok(DevCerts::in_dir(output_dir))
+}
+
+/// One PEM file of a dev certificate set.
+pub(crate) struct DevCertFile {
+    pub(crate) name: &'static str,
+    pub(crate) contents: String,
+    pub(crate) private: bool,
+}
+
+/// The contents of the files [`generate_dev_certs`] writes.
+pub(crate) fn dev_cert_files(hosts: &[String]) -> Result<Vec<DevCertFile>> {
+    if hosts.is_empty() {
+        return Err(NatsForgeError::InvalidConfig(
+            "Dev certificates need at least one host".to_string(),
+        ));
+    }
+    for host in hosts {
+        net::validate_host(host)?;
+    }
+
+    let ca_key = SecretKey::random(&mut OsRng);
+    let ca_name = "natsforge dev CA";
+    let ca = certificate(ca_name, &ca_key, ca_name, &ca_key, |w| {
+        extension(w.next(), OID_BASIC_CONSTRAINTS, true, |w| {
+            w.write_sequence(|w| w.next().write_bool(true))
+        });
+        // keyCertSign and cRLSign.
+        extension(w.next(), OID_KEY_USAGE, true, |w| w.write_bitvec_bytes(&[0x06], 7));
+    });
+
+    let server_key = SecretKey::random(&mut OsRng);
+    let server = certificate(&hosts[0], &server_key, ca_name, &ca_key, |w| {
+        leaf_extensions(w, OID_SERVER_AUTH);
+        extension(w.next(), OID_SUBJECT_ALT_NAME, false, |w| {
+            w.write_sequence(|w| {
+                for host in hosts {
+                    match net::strip_brackets(host).parse::<IpAddr>() {
+                        Ok(IpAddr::V4(ip)) => w
+                            .next()
+                            .write_tagged_implicit(Tag::context(7), |w| w.write_bytes(&ip.octets())),
+                        Ok(IpAddr::V6(ip)) => w
+                            .next()
+                            .write_tagged_implicit(Tag::context(7), |w| w.write_bytes(&ip.octets())),
+                        Err(_) => w
+                            .next()
+                            .write_tagged_implicit(Tag::context(2), |w| w.write_ia5_string(host)),
+                    }
+                }
+            })
+        });
+    });
+
+    let client_key = SecretKey::random(&mut OsRng);
+    let client = certificate("natsforge dev client", &client_key, ca_name, &ca_key, |w| {
+        leaf_extensions(w, OID_CLIENT_AUTH);
+    });
+
+    let files = [
+        (CA_FILE, pem("CERTIFICATE", &ca), false),
+        (SERVER_CERT_FILE, pem("CERTIFICATE", &server), false),
+        (SERVER_KEY_FILE, private_key_pem(&server_key)?, true),
+        (CLIENT_CERT_FILE, pem("CERTIFICATE", &client), false),
+        (CLIENT_KEY_FILE, private_key_pem(&client_key)?, true),
+    ];
+    Ok(files
+        .into_iter()
+        .map(|(name, contents, private)| DevCertFile {
+            name,
+            contents,
+            private,
+        })
+        .collect())
+}
+
+/// Extensions of an end-entity certificate used for `purpose` (server or client auth).
+fn leaf_extensions(w: &mut yasna::DERWriterSeq, purpose: &[u64]) {
+    extension(w.next(), OID_BASIC_CONSTRAINTS, true, |w| w.write_sequence(|_| {}));
+    // digitalSignature, the only usage an ECDSA TLS key needs.
+    extension(w.next(), OID_KEY_USAGE, true, |w| w.write_bitvec_bytes(&[0x80], 1));
+    extension(w.next(), OID_EXT_KEY_USAGE, false, |w| {
+        w.write_sequence(|w| w.next().write_oid(&ObjectIdentifier::from_slice(purpose)))
+    });
+}
+
+/// A DER-encoded X.509 v3 certificate for `subject`, signed by `issuer_key`.
+fn certificate(
+    subject: &str,
+    subject_key: &SecretKey,
+    issuer: &str,
+    issuer_key: &SecretKey,
+    extensions: impl FnOnce(&mut yasna::DERWriterSeq),
+) -> Vec<u8> {
+    let mut serial = [0u8; 16];
+    OsRng.fill_bytes(&mut serial);
+    serial[0] &= 0x7f;
+    let now = Utc::now();
+    let not_before = now - TimeDelta::hours(1);
+    let not_after = now + TimeDelta::days(VALIDITY_DAYS);
+    let public_key = subject_key.public_key().to_encoded_point(false);
+
+    let tbs = yasna::construct_der(|w| {
+        w.write_sequence(|w| {
+            w.next().write_tagged(Tag::context(0), |w| w.write_u8(2));
+            w.next().write_bigint_bytes(&serial, true);
+            signature_algorithm(w.next());
+            name(w.next(), issuer);
+            w.next().write_sequence(|w| {
+                utc_time(w.next(), &not_before.format("%y%m%d%H%M%SZ").to_string());
+                utc_time(w.next(), &not_after.format("%y%m%d%H%M%SZ").to_string());
+            });
+            name(w.next(), subject);
+            w.next().write_sequence(|w| {
+                w.next().write_sequence(|w| {
+                    w.next().write_oid(&ObjectIdentifier::from_slice(OID_EC_PUBLIC_KEY));
+                    w.next().write_oid(&ObjectIdentifier::from_slice(OID_PRIME256V1));
+                });
+                let point = public_key.as_bytes();
+                w.next().write_bitvec_bytes(point, point.len() * 8);
+            });
+            w.next().write_tagged(Tag::context(3), |w| w.write_sequence(extensions));
+        })
+    });
+
+    let signature: Signature = SigningKey::from(issuer_key).sign(&tbs);
+    let signature = signature.to_der();
+    yasna::construct_der(|w| {
+        w.write_sequence(|w| {
+            w.next().write_der(&tbs);
+            signature_algorithm(w.next());
+            w.next()
+                .write_bitvec_bytes(signature.as_bytes(), signature.as_bytes().len() * 8);
+        })
+    })
+}
+
+fn signature_algorithm(w: DERWriter) {
+    w.write_sequence(|w| w.next().write_oid(&ObjectIdentifier::from_slice(OID_ECDSA_WITH_SHA256)));
+}
+
+fn name(w: DERWriter, common_name: &str) {
+    w.write_sequence(|w| {
+        w.next().write_set(|w| {
+            w.next().write_sequence(|w| {
+                w.next().write_oid(&ObjectIdentifier::from_slice(OID_COMMON_NAME));
+                w.next().write_utf8_string(common_name);
+            })
+        })
+    });
+}
+
+fn utc_time(w: DERWriter, time: &str) {
+    w.write_tagged_implicit(TAG_UTCTIME, |w| w.write_bytes(time.as_bytes()));
+}
+
+fn extension(w: DERWriter, oid: &[u64], critical: bool, value: impl FnOnce(DERWriter)) {
+    w.write_sequence(|w| {
+        w.next().write_oid(&ObjectIdentifier::from_slice(oid));
+        if critical {
+            w.next().write_bool(true);
+        }
+        w.next().write_bytes(&yasna::construct_der(value));
+    });
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+fn private_key_pem(key: &SecretKey) -> Result<String> {
+    key.to_pkcs8_pem(LineEnding::LF)
+        .map(|pem| pem.to_string())
+        .map_err(|err| NatsForgeError::Backend(format!("Failed to encode private key: {}", err)))
+}
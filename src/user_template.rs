@@ -0,0 +1,69 @@
+use crate::{
+    config::{NatsConfig, UserConfig},
+    error::{NatsForgeError, Result},
+    tenant::substitute_strings,
+};
+
+impl NatsConfig {
+    /// Replaces every account's `user_template` with its users, appended to `users`.
+    /// [`NatsForge::from_config`](crate::NatsForge::from_config) does this after expanding
+    /// `account_templates`, so templated accounts may have user templates too.
+    pub fn expand_user_templates(&mut self) -> Result<()> {
+        for account in self.servers.iter_mut().flat_map(|server| &mut server.accounts) {
+            let Some(template) = account.user_template.take() else {
+                continue;
+            };
+            if template.count == 0 {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "The user_template of account {} needs a count of at least one",
+                    account.name
+                )));
+            }
+            let base = serde_json::to_value(&template.base).expect("UserConfig serializes");
+            for i in 1..=template.count {
+                let name = number(&template.name_pattern, i);
+                if account.users.iter().any(|user| user.name == name) {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "User {} from the user_template of account {} is already defined",
+                        name, account.name
+                    )));
+                }
+                let mut user = base.clone();
+                substitute_strings(&mut user, &|text| number(text, i));
+                let mut user: UserConfig = serde_json::from_value(user).expect("a numbered UserConfig deserializes");
+                user.name = name;
+                account.users.push(user);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `text` with every `{i}` replaced by `i`, and every `{i:0<width>}` by `i` zero-padded to
+/// `width` digits. Anything else in braces is kept as is.
+fn number(text: &str, i: u32) -> String {
+    let mut numbered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{i") {
+        numbered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find('}');
+        let width = match end.map(|end| &rest[2..end]) {
+            Some("") => Some(0),
+            Some(spec) => spec.strip_prefix(":0").and_then(|width| width.parse::<usize>().ok()),
+            None => None,
+        };
+        match (end, width) {
+            (Some(end), Some(width)) => {
+                numbered.push_str(&format!("{:0width$}", i, width = width));
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                numbered.push_str("{i");
+                rest = &rest[2..];
+            }
+        }
+    }
+    numbered.push_str(rest);
+    numbered
+}
@@ -0,0 +1,1660 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Months, NaiveDate, NaiveTime, TimeDelta, Utc};
+
+use crate::{
+    backend::is_user_public_key,
+    conf::ConfValue,
+    conf_auth,
+    config::{
+        AccountConfig, ArtifactMode, AuthCalloutConfig, AuthMode, BackendKind, ClusterConfig, ConfCredential,
+        ConsumerSpec, ExportConfig, GatewayConfig, KvSpec, LatencySampling, MappingTarget, MirrorSpec,
+        MonitoringConfig, MqttConfig, NatsConfig, OperatorRef, ResolverType, ServerConfig, StreamSpec, TlsConfig,
+        UserConfig, VerifyMode, DEFAULT_SYSTEM_ACCOUNT,
+    },
+    env,
+    error::{NatsForgeError, Result},
+    expand::INBOX,
+    net,
+    server::GENERATED_KEYS,
+    subject::{covers, overlaps, validate_mapping_destination, validate_subject, validate_transform},
+};
+
+impl NatsConfig {
+    /// Checks the configuration for problems that would otherwise surface late, inside nsc or
+    /// nats-server.
+    pub fn validate(&self) -> Result<()> {
+        env::validate(self)?;
+        if self.concurrency == Some(0) {
+            return Err(NatsForgeError::InvalidConfig(
+                "concurrency must be at least 1".to_string(),
+            ));
+        }
+        if let Some(timeout) = &self.nsc_timeout {
+            let timeout = parse_duration(timeout).map_err(prefixed("Invalid nsc_timeout".to_string()))?;
+            if timeout.is_zero() {
+                return Err(NatsForgeError::InvalidConfig(
+                    "nsc_timeout must be longer than zero".to_string(),
+                ));
+            }
+        }
+        if let Some(encryption) = &self.encryption {
+            if encryption.recipients.is_empty() == encryption.passphrase_env.is_none() {
+                return Err(NatsForgeError::InvalidConfig(
+                    "encryption needs either recipients or passphrase_env".to_string(),
+                ));
+            }
+            if !cfg!(feature = "encryption") {
+                return Err(NatsForgeError::InvalidConfig(
+                    "encryption needs natsforge built with the encryption feature".to_string(),
+                ));
+            }
+        }
+        if self.verify_configs != VerifyMode::Off && self.artifact_mode == ArtifactMode::InMemory {
+            return Err(NatsForgeError::InvalidConfig(
+                "verify_configs runs nats-server on the written configs, which in-memory mode doesn't write"
+                    .to_string(),
+            ));
+        }
+        if let Some(user) = &self.system_user {
+            if user.role.is_some() {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "System user {} has a role, but the system account has no signing keys",
+                    user.name
+                )));
+            }
+            for subject in permission_lists(user).into_iter().flatten() {
+                validate_subject(subject).map_err(prefixed(format!("System user {}", user.name)))?;
+            }
+            let configured_sys_users = self
+                .servers
+                .iter()
+                .flat_map(|server| &server.accounts)
+                .filter(|account| account.is_system_account)
+                .flat_map(|account| &account.users)
+                .chain(self.system_account.iter().flat_map(|account| &account.users));
+            for configured in configured_sys_users {
+                if configured.name == user.name {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "System user {} is also a user of the configured {} account",
+                        user.name,
+                        self.system_account_name()
+                    )));
+                }
+            }
+        }
+        if let Some(account) = &self.system_account {
+            check_identifier("System account name", &account.name)?;
+            if account.name.is_empty() || account.name == DEFAULT_SYSTEM_ACCOUNT {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "system_account needs a name other than {:?}; leave it out for the default {} account",
+                    account.name, DEFAULT_SYSTEM_ACCOUNT
+                )));
+            }
+            if !self.operators.is_empty() {
+                return Err(NatsForgeError::InvalidConfig(
+                    "system_account needs a single operator, but operators are configured".to_string(),
+                ));
+            }
+        }
+        for mirror in &self.mirrors {
+            self.check_mirror(mirror)
+                .map_err(prefixed(format!("Mirror {}", mirror.name)))?;
+        }
+        if let Some(first) = self.servers.first() {
+            if let Some(other) = self.servers.iter().find(|server| server.auth_mode != first.auth_mode) {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "Servers {} and {} have different auth_modes; every server of a setup needs the same",
+                    first.name, other.name
+                )));
+            }
+            if first.auth_mode == AuthMode::Conf {
+                let operator_only = [
+                    ("system_user", self.system_user.is_some()),
+                    ("system_account", self.system_account.is_some()),
+                    ("operators", !self.operators.is_empty()),
+                    ("write_manifest", self.write_manifest),
+                ];
+                if let Some((option, _)) = operator_only.iter().find(|(_, set)| *set) {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "{} needs operator mode, but the servers are in conf mode",
+                        option
+                    )));
+                }
+            } else {
+                // The operator has a single system account, which the backends create by name.
+                let misnamed = self
+                    .servers
+                    .iter()
+                    .flat_map(|server| &server.accounts)
+                    .find(|account| account.is_system_account && account.name != self.system_account_name());
+                if let Some(account) = misnamed {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Account {} is marked is_system_account, but the operator's system account is {}",
+                        account.name,
+                        self.system_account_name()
+                    )));
+                }
+            }
+        }
+        for operator in self.all_operators() {
+            check_identifier("Operator name", &operator.name)?;
+            if let Some(url) = &operator.account_server_url {
+                if !["http://", "https://", "nats://"]
+                    .iter()
+                    .any(|scheme| url.starts_with(scheme))
+                {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Operator account_server_url {} must be an http://, https:// or nats:// URL",
+                        url
+                    )));
+                }
+            }
+            if let Some(url) = operator
+                .service_urls
+                .iter()
+                .find(|url| !url.starts_with("nats://") && !url.starts_with("tls://"))
+            {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "Operator service URL {} must be a nats:// or tls:// URL",
+                    url
+                )));
+            }
+        }
+        self.check_operators()?;
+        if let Some(user) = &self.system_user {
+            check_identifier("User name", &user.name)?;
+        }
+        for server in &self.servers {
+            check_identifier("Server name", &server.name)?;
+            let names = [
+                ("Cluster name", server.cluster.as_ref().map(|cluster| &cluster.name)),
+                ("Gateway name", server.gateway.as_ref().map(|gateway| &gateway.name)),
+                ("JetStream domain", server.jetstream.domain.as_ref()),
+                (
+                    "MQTT JetStream domain",
+                    server.mqtt.as_ref().and_then(|mqtt| mqtt.jetstream_domain.as_ref()),
+                ),
+            ];
+            let remote_gateways = server.gateway.iter().flat_map(|gateway| &gateway.gateways);
+            let tags = server.server_tags.iter().map(|tag| ("Server tag", Some(tag)));
+            let accounts = server.accounts.iter().flat_map(|account| {
+                let users = account.users.iter().map(|user| ("User name", Some(&user.name)));
+                [("Account name", Some(&account.name))].into_iter().chain(users)
+            });
+            for (kind, name) in names
+                .into_iter()
+                .chain(remote_gateways.map(|remote| ("Gateway name", Some(&remote.name))))
+                .chain(tags)
+                .chain(accounts)
+            {
+                if let Some(name) = name {
+                    check_identifier(kind, name).map_err(prefixed(format!("Server {}", server.name)))?;
+                }
+            }
+            if let Some(host) = &server.host {
+                net::validate_host(host).map_err(prefixed(format!("Invalid host for server {}", server.name)))?;
+            }
+            let tls_configs = [
+                ("TLS", server.tls.as_ref()),
+                (
+                    "Monitoring TLS",
+                    server.monitoring.as_ref().and_then(|m| m.tls.as_ref()),
+                ),
+                ("Gateway TLS", server.gateway.as_ref().and_then(|g| g.tls.as_ref())),
+                ("MQTT TLS", server.mqtt.as_ref().and_then(|m| m.tls.as_ref())),
+                ("Leafnode TLS", server.leafnodes.tls.as_ref()),
+            ];
+            for (label, tls) in tls_configs {
+                if let Some(tls) = tls {
+                    check_tls(tls).map_err(prefixed(format!("{} of server {}", label, server.name)))?;
+                    if tls.dev && label != "TLS" {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "{} of server {}: dev certificates are only generated for the client tls block",
+                            label, server.name
+                        )));
+                    }
+                }
+            }
+            if let Some(advertise) = &server.client_advertise {
+                net::validate_advertise(advertise)
+                    .map_err(prefixed(format!("Invalid client_advertise for server {}", server.name)))?;
+            }
+            if let Some(advertise) = &server.leafnodes.advertise {
+                if server.leafnodes.port.is_none() {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} advertises a leafnode address but has no leafnode port",
+                        server.name
+                    )));
+                }
+                net::validate_advertise(advertise).map_err(prefixed(format!(
+                    "Invalid leafnode advertise for server {}",
+                    server.name
+                )))?;
+            }
+            if let Some(min_version) = &server.leafnodes.min_version {
+                check_leaf_min_version(min_version)
+                    .map_err(prefixed(format!("Leafnode min_version of server {}", server.name)))?;
+            }
+            if let Some(authorization) = &server.leafnodes.authorization {
+                if server.leafnodes.port.is_none() {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} has leafnode authorization but no leafnode port",
+                        server.name
+                    )));
+                }
+                if authorization.users.is_empty() {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Leafnode authorization of server {} lists no users",
+                        server.name
+                    )));
+                }
+                for (i, user) in authorization.users.iter().enumerate() {
+                    if user.user.is_empty() || authorization.users[..i].iter().any(|other| other.user == user.user) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode authorization of server {} has an empty or repeated user {:?}",
+                            server.name, user.user
+                        )));
+                    }
+                }
+                if let Some(account) = &authorization.account {
+                    if !self
+                        .servers
+                        .iter()
+                        .flat_map(|s| &s.accounts)
+                        .any(|a| &a.name == account)
+                    {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode authorization of server {} binds to unknown account {}",
+                            server.name, account
+                        )));
+                    }
+                }
+            }
+            for remote in &server.leafnodes.remotes {
+                if remote.credentials.is_empty() && remote.user.is_none() {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Leafnode remote {} of server {} needs credentials or a user",
+                        remote.url, server.name
+                    )));
+                }
+                // Only the encrypted creds are written, so the server can't find them unless
+                // told where they are decrypted to.
+                match (&self.encryption, &remote.decrypted_credentials) {
+                    (Some(_), None) => {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode remote {} of server {} needs decrypted_credentials, the path its creds are \
+                             decrypted to at deploy time, since encryption is set",
+                            remote.url, server.name
+                        )))
+                    }
+                    (None, Some(_)) => {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode remote {} of server {} sets decrypted_credentials without encryption",
+                            remote.url, server.name
+                        )))
+                    }
+                    _ => {}
+                }
+                if let Some(name) = &remote.server {
+                    if !self.servers.iter().any(|other| &other.name == name) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode remote {} of server {} takes creds from unknown server {}",
+                            remote.url, server.name, name
+                        )));
+                    }
+                }
+                net::normalize_url(&remote.url).map_err(prefixed(format!(
+                    "Invalid leafnode remote URL for server {}",
+                    server.name
+                )))?;
+                for subject in remote.deny_imports.iter().chain(&remote.deny_exports) {
+                    validate_subject(subject).map_err(prefixed(format!(
+                        "Leafnode remote {} of server {}",
+                        remote.url, server.name
+                    )))?;
+                }
+                if let Some(tls) = &remote.tls {
+                    if tls.cert_file.is_some() != tls.key_file.is_some() {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode remote {} of server {} needs both cert_file and key_file for TLS",
+                            remote.url, server.name
+                        )));
+                    }
+                }
+            }
+            check_mappings(&server.mappings).map_err(prefixed(format!("Mapping on server {}", server.name)))?;
+            if let Some(transform) = &server.jetstream.subject_transform {
+                validate_transform(&transform.src, &transform.dest).map_err(prefixed(format!(
+                    "JetStream subject_transform of server {}",
+                    server.name
+                )))?;
+            }
+            for republish in &server.jetstream.republish {
+                validate_transform(&republish.src, &republish.dest)
+                    .map_err(prefixed(format!("JetStream republish of server {}", server.name)))?;
+            }
+            check_ports(server)?;
+            for (field, duration) in [
+                ("write_deadline", &server.write_deadline),
+                ("ping_interval", &server.ping_interval),
+                ("lame_duck_duration", &server.lame_duck_duration),
+                ("lame_duck_grace_period", &server.lame_duck_grace_period),
+            ] {
+                if let Some(duration) = duration {
+                    parse_duration(duration)
+                        .map_err(prefixed(format!("Invalid {} for server {}", field, server.name)))?;
+                }
+            }
+            if let (Some(duration), Some(grace_period)) = (&server.lame_duck_duration, &server.lame_duck_grace_period) {
+                if parse_duration(grace_period)? >= parse_duration(duration)? {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} has a lame_duck_grace_period of {}, which must be shorter than its lame_duck_duration of {}",
+                        server.name, grace_period, duration
+                    )));
+                }
+            }
+            if let Some(size_limit) = server.logging.as_ref().and_then(|logging| logging.size_limit) {
+                if size_limit <= 0 {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} has a logfile size_limit of {}; it must be a positive number of bytes",
+                        server.name, size_limit
+                    )));
+                }
+            }
+            if let Some(server_max) = server.max_payload {
+                let larger = server.accounts.iter().find(|account| {
+                    account
+                        .max_message_payload
+                        .is_some_and(|max| max > i64::from(server_max))
+                });
+                if let Some(account) = larger {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Account {} allows a max_message_payload of {}, over the max_payload of {} of its server {}",
+                        account.name,
+                        account.max_message_payload.unwrap_or_default(),
+                        server_max,
+                        server.name
+                    )));
+                }
+            }
+            if let Some(monitoring) = &server.monitoring {
+                check_monitoring(server, monitoring)?;
+            }
+            check_resolver(&server.resolver).map_err(prefixed(format!("Resolver of server {}", server.name)))?;
+            if let Some(cluster) = &server.cluster {
+                check_cluster(cluster).map_err(prefixed(format!("Cluster of server {}", server.name)))?;
+            }
+            if let Some(gateway) = &server.gateway {
+                check_gateway(server, gateway).map_err(prefixed(format!("Gateway of server {}", server.name)))?;
+            }
+            if let Some(mqtt) = &server.mqtt {
+                check_mqtt(server, mqtt)?;
+            }
+            check_passthrough(server).map_err(prefixed(format!("Server {}", server.name)))?;
+            // nats-server resolves a relative operator path against its working directory.
+            if server.operator_reference == OperatorRef::File
+                && self.artifact_mode == ArtifactMode::InMemory
+                && !server.output_dir.is_absolute()
+            {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "Server {} refers to operator.jwt by path, so in-memory mode needs an absolute output_dir",
+                    server.name
+                )));
+            }
+            if let Some(no_auth_user) = &server.no_auth_user {
+                let referenced = server
+                    .accounts
+                    .iter()
+                    .flat_map(|account| account.users.iter().map(move |user| (account, user)))
+                    .find(|(account, user)| conf_auth::username(&account.name, &user.name) == *no_auth_user);
+                let Some((_, user)) = referenced else {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} has no_auth_user {}, which isn't one of its users; name it as <account>-<user>",
+                        server.name, no_auth_user
+                    )));
+                };
+                if server.auth_mode == AuthMode::Operator {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} sets no_auth_user, which nats-server doesn't allow with a trusted operator",
+                        server.name
+                    )));
+                }
+                if user.conf_credential == ConfCredential::Token {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} has no_auth_user {}, a token user, but only nkey and password users can be \
+                         logged in without credentials",
+                        server.name, no_auth_user
+                    )));
+                }
+            }
+            if let Some(permissions) = &server.default_permissions {
+                for subject in [&permissions.publish, &permissions.subscribe]
+                    .into_iter()
+                    .flat_map(|subjects| subjects.allow.iter().chain(&subjects.deny))
+                {
+                    validate_subject(subject)
+                        .map_err(prefixed(format!("default_permissions of server {}", server.name)))?;
+                }
+                if server.auth_mode == AuthMode::Operator {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} sets default_permissions, which only apply to users defined in nats.conf; in \
+                         operator mode users carry their permissions in their JWTs",
+                        server.name
+                    )));
+                }
+            }
+            if let Some(callout) = &server.auth_callout {
+                check_auth_callout(server, callout)
+                    .map_err(prefixed(format!("Auth callout of server {}", server.name)))?;
+            }
+            if server.auth_mode == AuthMode::Conf {
+                check_conf_mode(server).map_err(prefixed(format!("Server {} is in conf mode", server.name)))?;
+            }
+            for block in &server.split_includes {
+                if !GENERATED_KEYS.contains(&block.as_str()) && !server.extra_blocks.contains_key(block) {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} splits {} into an include, but neither natsforge nor its extra_blocks render a {} block",
+                        server.name, block, block
+                    )));
+                }
+            }
+            for (i, stream) in server.streams.iter().enumerate() {
+                self.check_stream(server, stream)
+                    .map_err(prefixed(format!("Stream {} on server {}", stream.name, server.name)))?;
+                if server.streams[..i]
+                    .iter()
+                    .any(|other| other.name == stream.name && other.account == stream.account)
+                {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} declares stream {} of account {} twice",
+                        server.name, stream.name, stream.account
+                    )));
+                }
+            }
+            for consumer in &server.consumers {
+                self.check_consumer(server, consumer).map_err(prefixed(format!(
+                    "Consumer {} on stream {} of server {}",
+                    consumer.name, consumer.stream, server.name
+                )))?;
+            }
+            for bucket in &server.kv_buckets {
+                self.check_kv_bucket(server, bucket).map_err(prefixed(format!(
+                    "KV bucket {} on server {}",
+                    bucket.bucket, server.name
+                )))?;
+            }
+            if server.push_accounts {
+                if !matches!(server.resolver, ResolverType::Url(_)) {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} sets push_accounts, which needs a URL resolver",
+                        server.name
+                    )));
+                }
+                if self.backend == BackendKind::Native {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} sets push_accounts, which needs the nsc backend",
+                        server.name
+                    )));
+                }
+            }
+            if !server.external_accounts.is_empty() && matches!(server.resolver, ResolverType::Url(_)) {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "Server {} sets external_accounts with a URL resolver; push them to the account server instead",
+                    server.name
+                )));
+            }
+            for issuer in &server.external_account_issuers {
+                if !issuer.starts_with('O') || nkeys::KeyPair::from_public_key(issuer).is_err() {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "External account issuer {} of server {} is not an operator public key",
+                        issuer, server.name
+                    )));
+                }
+            }
+            for account in &server.accounts {
+                let account_expiry = account
+                    .expiry
+                    .as_deref()
+                    .map(|expiry| parse_instant("expiry", expiry))
+                    .transpose()
+                    .map_err(prefixed(format!("Account {}", account.name)))?;
+                check_mappings(&account.mappings).map_err(prefixed(format!("Mapping in account {}", account.name)))?;
+                for export in &account.exports {
+                    check_export(export).map_err(prefixed(format!(
+                        "Export {} of account {}",
+                        export.subject, account.name
+                    )))?;
+                }
+                for import in &account.imports {
+                    validate_subject(&import.subject)
+                        .and_then(|_| import.local_subject.as_deref().map_or(Ok(()), validate_subject))
+                        .map_err(prefixed(format!(
+                            "Import from account {} in account {}",
+                            import.account, account.name
+                        )))?;
+                }
+                check_account_limits(account).map_err(prefixed(format!("Account {}", account.name)))?;
+                if let Some(import) = account
+                    .imports
+                    .iter()
+                    .find(|import| self.import_exporter(server, &import.account).is_none())
+                {
+                    return Err(self.unknown_import(server, &account.name, &import.account));
+                }
+                for user in &account.revocations {
+                    if !is_user_public_key(user) && !account.users.iter().any(|u| &u.name == user) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Account {} revokes {}, which is neither one of its users nor a user public key",
+                            account.name, user
+                        )));
+                    }
+                }
+                for role in &account.roles {
+                    let subjects = role.allowed_publishes.iter().chain(&role.allowed_subscribes).flatten();
+                    for subject in subjects {
+                        validate_subject(subject)
+                            .map_err(prefixed(format!("Role {} in account {}", role.name, account.name)))?;
+                    }
+                }
+                for user in &account.users {
+                    for subject in permission_lists(user).into_iter().flatten() {
+                        validate_subject(subject)
+                            .map_err(prefixed(format!("User {} in account {}", user.name, account.name)))?;
+                    }
+                    for cidr in &user.source_networks {
+                        net::validate_cidr(cidr).map_err(prefixed(format!(
+                            "Invalid source network for user {} in account {}",
+                            user.name, account.name
+                        )))?;
+                    }
+                    for (field, limit) in user_limits(user) {
+                        let Some(limit) = limit else { continue };
+                        if limit == 0 || limit < -1 {
+                            return Err(NatsForgeError::InvalidConfig(format!(
+                                "{} of user {} in account {} is {}; use a positive limit, or -1 for unlimited \
+                                 (nsc does not read 0 as a limit)",
+                                field, user.name, account.name, limit
+                            )));
+                        }
+                    }
+                    check_activation(user)
+                        .map_err(prefixed(format!("User {} in account {}", user.name, account.name)))?;
+                    if user.bearer && account.disallow_bearer {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "User {} is a bearer user, but account {} disallows bearer tokens",
+                            user.name, account.name
+                        )));
+                    }
+                    if let (Some(account_expiry), Some(expiry)) = (account_expiry, &user.expiry) {
+                        let expiry = parse_instant("expiry", expiry)
+                            .map_err(prefixed(format!("User {} in account {}", user.name, account.name)))?;
+                        if expiry > account_expiry {
+                            return Err(NatsForgeError::InvalidConfig(format!(
+                                "User {} expires at {}, after its account {} at {}",
+                                user.name,
+                                expiry.to_rfc3339(),
+                                account.name,
+                                account_expiry.to_rfc3339()
+                            )));
+                        }
+                    }
+                    if user.bearer && user.allow_pub_response.is_some() {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "User {} in account {} is a bearer user and can't also set allow_pub_response",
+                            user.name, account.name
+                        )));
+                    }
+                    if let Some(ttl) = &user.response_ttl {
+                        if user.allow_pub_response.is_none() {
+                            return Err(NatsForgeError::InvalidConfig(format!(
+                                "User {} in account {} sets response_ttl without allow_pub_response",
+                                user.name, account.name
+                            )));
+                        }
+                        parse_duration(ttl).map_err(prefixed(format!(
+                            "Invalid response_ttl for user {} in account {}",
+                            user.name, account.name
+                        )))?;
+                    }
+                    let Some(role) = &user.role else { continue };
+                    if !account.roles.iter().any(|r| &r.name == role) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "User {} in account {} has role {}, which the account does not define",
+                            user.name, account.name, role
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Problems that don't make the config invalid but likely need attention.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let account_server = self.servers.iter().find(|server| {
+            matches!(server.resolver, ResolverType::Full { .. } | ResolverType::Url(_))
+                && self.server_operator(server).account_server_url.is_none()
+        });
+        if let Some(server) = account_server {
+            warnings.push(format!(
+                "Server {} keeps account JWTs in a {} resolver, but the operator has no account_server_url; set it \
+                 so nsc push and the nats CLI find where to send account updates",
+                server.name,
+                if matches!(server.resolver, ResolverType::Url(_)) {
+                    "URL"
+                } else {
+                    "full"
+                }
+            ));
+        }
+        for server in &self.servers {
+            if let Some(no_auth_user) = &server.no_auth_user {
+                warnings.push(format!(
+                    "SECURITY: server {} logs in clients without credentials as {}; anyone who can reach port {} \
+                     gets its permissions",
+                    server.name, no_auth_user, server.port
+                ));
+            }
+            if server.logging.as_ref().is_some_and(|logging| !logging.pid_in_log) {
+                warnings.push(format!(
+                    "Server {} sets pid_in_log to false, but nats-server always logs its process ID",
+                    server.name
+                ));
+            }
+            for account in &server.accounts {
+                for user in account.users.iter().filter(|u| u.role.is_some() && has_own_scope(u)) {
+                    warnings.push(format!(
+                        "User {} in account {} has a role; its own permissions and limits are ignored",
+                        user.name, account.name
+                    ));
+                }
+                for user in account.users.iter().filter(|u| u.role.is_none()) {
+                    let directions = [
+                        (
+                            "publishing to",
+                            [&user.allow_pub, &user.allow_pubsub],
+                            [&user.deny_pub, &user.deny_pubsub],
+                        ),
+                        (
+                            "subscribing to",
+                            [&user.allow_sub, &user.allow_pubsub],
+                            [&user.deny_sub, &user.deny_pubsub],
+                        ),
+                    ];
+                    for (direction, allows, denies) in directions {
+                        let allows: Vec<&String> = allows.into_iter().flatten().collect();
+                        // Without allows the direction is unrestricted, so every deny matters.
+                        if allows.is_empty() {
+                            continue;
+                        }
+                        for deny in denies.into_iter().flatten() {
+                            if !allows.iter().any(|allow| overlaps(allow, deny)) {
+                                warnings.push(format!(
+                                    "User {} in account {} denies {} {}, which none of its allows match, so the \
+                                     deny has no effect",
+                                    user.name, account.name, direction, deny
+                                ));
+                            }
+                        }
+                    }
+                }
+                // Deny lists still apply to responses, so denying the inbox blocks most replies.
+                for user in account.users.iter().filter(|u| u.allow_pub_response.is_some()) {
+                    let denies = user.deny_pub.iter().chain(&user.deny_pubsub);
+                    if let Some(deny) = denies.into_iter().find(|deny| covers(deny, INBOX)) {
+                        warnings.push(format!(
+                            "User {} in account {} has allow_pub_response but denies publishing to {}, which \
+                             blocks responses to requests with inbox reply subjects",
+                            user.name, account.name, deny
+                        ));
+                    }
+                }
+            }
+            if let Some(cluster) = &server.cluster {
+                for other in &self.servers {
+                    let Some(other_cluster) = &other.cluster else { continue };
+                    if other_cluster.name != cluster.name && routes_to(cluster, other) {
+                        warnings.push(format!(
+                            "Server {} routes to server {}, but their cluster names differ ({} and {}), so the \
+                             route is rejected",
+                            server.name, other.name, cluster.name, other_cluster.name
+                        ));
+                    }
+                }
+            }
+            let Some(cap) = server.max_connections else {
+                continue;
+            };
+            let account_total: i64 = server
+                .accounts
+                .iter()
+                .filter_map(|a| a.max_connections)
+                .filter(|max| *max > 0)
+                .map(i64::from)
+                .sum();
+            if account_total > i64::from(cap) {
+                warnings.push(format!(
+                    "Accounts on server {} allow {} connections in total, more than the server's max_connections of {}",
+                    server.name, account_total, cap
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+fn check_export(export: &ExportConfig) -> Result<()> {
+    validate_subject(&export.subject)?;
+    if let Some(latency_subject) = &export.latency_subject {
+        validate_subject(latency_subject)?;
+    }
+    if export.response_type.is_some() && !export.is_service {
+        return Err(NatsForgeError::InvalidConfig(
+            "response_type only applies to service exports".to_string(),
+        ));
+    }
+    if export.latency_subject.is_some() && !export.is_service {
+        return Err(NatsForgeError::InvalidConfig(
+            "latency_subject only applies to service exports".to_string(),
+        ));
+    }
+    match export.latency_sampling {
+        Some(_) if export.latency_subject.is_none() => {
+            return Err(NatsForgeError::InvalidConfig(
+                "latency_sampling needs a latency_subject".to_string(),
+            ))
+        }
+        Some(LatencySampling::Percent(percent)) if !(1..=100).contains(&percent) => {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "latency_sampling {} is not between 1 and 100",
+                percent
+            )))
+        }
+        _ => {}
+    }
+    if let Some(position) = export.account_token_position {
+        let token = export.subject.split('.').nth((position as usize).wrapping_sub(1));
+        if token != Some("*") {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "account_token_position {} must point at a * token",
+                position
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the user's `times`, `start` and `expiry`.
+fn check_activation(user: &UserConfig) -> Result<()> {
+    for range in &user.times {
+        for time in [&range.start, &range.end] {
+            NaiveTime::parse_from_str(time, "%H:%M:%S")
+                .map_err(|_| NatsForgeError::InvalidConfig(format!("time {:?} is not in HH:MM:SS form", time)))?;
+        }
+    }
+    let start = user
+        .start
+        .as_deref()
+        .map(|start| parse_instant("start", start))
+        .transpose()?;
+    let expiry = user
+        .expiry
+        .as_deref()
+        .map(|expiry| parse_instant("expiry", expiry))
+        .transpose()?;
+    if let (Some(start), Some(expiry)) = (start, expiry) {
+        if start >= expiry {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "start {} is not before expiry {}",
+                user.start.as_deref().unwrap_or_default(),
+                user.expiry.as_deref().unwrap_or_default()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Checks the account's leafnode, export and import limits against what it declares.
+fn check_account_limits(account: &AccountConfig) -> Result<()> {
+    for (field, limit, declared) in [
+        ("max_leaf_connections", account.max_leaf_connections, None),
+        (
+            "max_exports",
+            account.max_exports,
+            Some(("exports", account.exports.len())),
+        ),
+        (
+            "max_imports",
+            account.max_imports,
+            Some(("imports", account.imports.len())),
+        ),
+    ] {
+        let Some(limit) = limit else { continue };
+        if limit < -1 {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "{} is {}; use 0 or more, or -1 for unlimited",
+                field, limit
+            )));
+        }
+        if let Some((kind, count)) = declared.filter(|(_, count)| limit >= 0 && *count > limit as usize) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "it declares {} {}, more than its {} of {}",
+                count, kind, field, limit
+            )));
+        }
+    }
+    if account.allow_wildcard_exports == Some(false) {
+        if let Some(export) = account
+            .exports
+            .iter()
+            .find(|export| export.subject.split('.').any(|token| token == "*" || token == ">"))
+        {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "it disallows wildcard exports, but exports {}",
+                export.subject
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn user_limits(user: &UserConfig) -> [(&'static str, Option<i64>); 3] {
+    [
+        ("max_subscriptions", user.max_subscriptions),
+        ("max_data", user.max_data),
+        ("max_payload", user.max_payload),
+    ]
+}
+
+fn permission_lists(user: &UserConfig) -> [&Vec<String>; 6] {
+    [
+        &user.allow_pub,
+        &user.allow_sub,
+        &user.allow_pubsub,
+        &user.deny_pub,
+        &user.deny_sub,
+        &user.deny_pubsub,
+    ]
+}
+
+/// Checks both sides of subject mappings and the weights of weighted ones, in a stable order.
+fn check_mappings(mappings: &HashMap<String, MappingTarget>) -> Result<()> {
+    let mut mappings: Vec<_> = mappings.iter().collect();
+    mappings.sort_by_key(|(source, _)| *source);
+    for (source, target) in mappings {
+        validate_subject(source)?;
+        let destinations = target.destinations();
+        if destinations.is_empty() {
+            return Err(NatsForgeError::InvalidConfig(format!("{} has no destinations", source)));
+        }
+        for (i, (destination, weight)) in destinations.iter().enumerate() {
+            validate_mapping_destination(destination)?;
+            if *weight == 0 || *weight > 100 {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "{} gives {} a weight of {}, not between 1 and 100",
+                    source, destination, weight
+                )));
+            }
+            if destinations[..i].iter().any(|(other, _)| other == destination) {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "{} repeats destination {}",
+                    source, destination
+                )));
+            }
+        }
+        let total: u32 = destinations.iter().map(|(_, weight)| u32::from(*weight)).sum();
+        if total != 100 {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "The weights of {} add up to {}, not 100",
+                source, total
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the user sets permissions or limits, which a role's scope replaces.
+fn has_own_scope(user: &UserConfig) -> bool {
+    permission_lists(user).iter().any(|subjects| !subjects.is_empty())
+        || user.allow_pub_response.is_some()
+        || user.response_ttl.is_some()
+        || user_limits(user).iter().any(|(_, limit)| limit.is_some())
+}
+
+/// Parses an `expiry` or `start`: an RFC 3339 timestamp, a date (`YYYY-MM-DD`, the start of that
+/// day in UTC) or a duration from now such as `30d`, in the units nsc takes: `m`inutes, `h`ours,
+/// `d`ays, `w`eeks, `M`onths and `y`ears.
+pub(crate) fn parse_instant(field: &str, text: &str) -> Result<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(instant) = DateTime::parse_from_rfc3339(text) {
+        return Ok(instant.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc());
+    }
+    relative_instant(text, Utc::now()).ok_or_else(|| {
+        NatsForgeError::InvalidConfig(format!(
+            "Invalid {} {:?}: expected an RFC 3339 timestamp, a date (YYYY-MM-DD) or a duration such as 30d or \
+             12h",
+            field, text
+        ))
+    })
+}
+
+fn relative_instant(text: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let unit = text.chars().last()?;
+    let count: u32 = text[..text.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        'm' => now.checked_add_signed(TimeDelta::try_minutes(count.into())?),
+        'h' => now.checked_add_signed(TimeDelta::try_hours(count.into())?),
+        'd' => now.checked_add_signed(TimeDelta::try_days(count.into())?),
+        'w' => now.checked_add_signed(TimeDelta::try_weeks(count.into())?),
+        'M' => now.checked_add_months(Months::new(count)),
+        'y' => now.checked_add_months(Months::new(count.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+/// Parses a Go duration such as `500ms`, `1.5s` or `1m30s`, the format nsc takes.
+pub(crate) fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || NatsForgeError::InvalidConfig(format!("{:?} is not a duration such as 5s or 1m30s", text));
+    let mut rest = text.trim();
+    // Like Go, a bare zero needs no unit.
+    if rest == "0" {
+        return Ok(Duration::ZERO);
+    }
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut total: u128 = 0;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (whole, fraction) = rest[..number_len].split_once('.').unwrap_or((&rest[..number_len], ""));
+        if (whole.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+            return Err(invalid());
+        }
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit: u128 = match &rest[..unit_len] {
+            "ns" => 1,
+            "us" | "µs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            _ => return Err(invalid()),
+        };
+        let whole: u128 = match whole {
+            "" => 0,
+            whole => whole.parse().map_err(|_| invalid())?,
+        };
+        // Digits beyond a nanosecond's precision can't change the result.
+        let fraction = &fraction[..fraction.len().min(18)];
+        let fraction_nanos = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<u128>().map_err(|_| invalid())? * unit / 10u128.pow(fraction.len() as u32)
+        };
+        total = whole
+            .checked_mul(unit)
+            .and_then(|nanos| nanos.checked_add(fraction_nanos))
+            .and_then(|nanos| total.checked_add(nanos))
+            .ok_or_else(invalid)?;
+        rest = &rest[unit_len..];
+    }
+    Ok(Duration::from_nanos(u64::try_from(total).map_err(|_| invalid())?))
+}
+
+impl NatsConfig {
+    /// Keeps every operator to its own servers and accounts: servers sharing a cluster or
+    /// gateways trust the same operator, accounts only import within theirs, and leafnode remotes
+    /// bind accounts of the server's operator, reaching another operator's account through its
+    /// creds alone.
+    fn check_operators(&self) -> Result<()> {
+        let operators: Vec<_> = self.all_operators().collect();
+        for (i, operator) in operators.iter().enumerate() {
+            if operators[..i].iter().any(|other| other.name == operator.name) {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "Operator {} is defined twice",
+                    operator.name
+                )));
+            }
+        }
+        for server in &self.servers {
+            if let Some(name) = &server.operator {
+                if !operators.iter().any(|operator| &operator.name == name) {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Server {} trusts unknown operator {}",
+                        server.name, name
+                    )));
+                }
+            }
+        }
+        if operators.len() < 2 {
+            return Ok(());
+        }
+
+        let operator_of = |server: &ServerConfig| self.server_operator(server).name.as_str();
+        for (i, server) in self.servers.iter().enumerate() {
+            for other in &self.servers[..i] {
+                if operator_of(server) == operator_of(other) {
+                    continue;
+                }
+                let link = match (&server.cluster, &other.cluster) {
+                    (Some(cluster), Some(other_cluster)) if cluster.name == other_cluster.name => {
+                        Some(format!("share cluster {}", cluster.name))
+                    }
+                    _ if server.gateway.is_some() && other.gateway.is_some() => {
+                        Some("are linked by gateways".to_string())
+                    }
+                    _ => None,
+                };
+                if let Some(link) = link {
+                    return Err(NatsForgeError::InvalidConfig(format!(
+                        "Servers {} and {} {} but trust different operators",
+                        other.name, server.name, link
+                    )));
+                }
+                for account in &server.accounts {
+                    if other.accounts.iter().any(|a| a.name == account.name) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Account {} is defined under operators {} and {}; account names have to differ \
+                             between operators",
+                            account.name,
+                            operator_of(other),
+                            operator_of(server)
+                        )));
+                    }
+                }
+            }
+        }
+
+        for server in &self.servers {
+            for account in &server.accounts {
+                for import in &account.imports {
+                    let exporter = self.account_operator(&import.account);
+                    if let Some(exporter) = exporter.filter(|exporter| exporter.name != operator_of(server)) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Account {} imports from account {} of operator {}; imports stay within an operator",
+                            account.name, import.account, exporter.name
+                        )));
+                    }
+                }
+            }
+            for remote in &server.leafnodes.remotes {
+                if let Some(bound) = self.account_operator(&remote.account) {
+                    if bound.name != operator_of(server) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode remote {} of server {} binds account {} of operator {}; it has to bind an \
+                             account of the server's own operator",
+                            remote.url, server.name, remote.account, bound.name
+                        )));
+                    }
+                }
+                let creds_server = remote
+                    .server
+                    .as_ref()
+                    .and_then(|name| self.servers.iter().find(|other| &other.name == name));
+                if let (Some(user), Some(creds_server)) = (&remote.user, creds_server) {
+                    if operator_of(creds_server) != operator_of(server) {
+                        return Err(NatsForgeError::InvalidConfig(format!(
+                            "Leafnode remote {} of server {} takes user {} from server {} of another operator; name \
+                             the creds with credentials instead",
+                            remote.url, server.name, user, creds_server.name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_cluster(cluster: &ClusterConfig) -> Result<()> {
+    if cluster.name.is_empty() {
+        return Err(NatsForgeError::InvalidConfig("name must not be empty".to_string()));
+    }
+    for route in &cluster.routes {
+        net::normalize_url(route)?;
+    }
+    if let Some(advertise) = &cluster.advertise {
+        net::validate_advertise(advertise).map_err(prefixed("Invalid advertise".to_string()))?;
+    }
+    Ok(())
+}
+
+// Gateways carry system account traffic between clusters; every generated server runs with the
+// operator's system account, so that needs no check here.
+fn check_gateway(server: &ServerConfig, gateway: &GatewayConfig) -> Result<()> {
+    if gateway.name.is_empty() {
+        return Err(NatsForgeError::InvalidConfig("name must not be empty".to_string()));
+    }
+    if let Some(cluster) = &server.cluster {
+        if cluster.name != gateway.name {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "name {} must match the cluster name {}",
+                gateway.name, cluster.name
+            )));
+        }
+    }
+    for (i, remote) in gateway.gateways.iter().enumerate() {
+        if gateway.gateways[..i].iter().any(|other| other.name == remote.name) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "gateway {} is listed twice",
+                remote.name
+            )));
+        }
+        if remote.urls.is_empty() {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "gateway {} needs at least one URL",
+                remote.name
+            )));
+        }
+        for url in &remote.urls {
+            net::normalize_url(url)?;
+        }
+    }
+    Ok(())
+}
+
+/// Rejects servers that would listen on one port for two things.
+fn check_ports(server: &ServerConfig) -> Result<()> {
+    let monitoring = server.monitoring.as_ref();
+    let ports = [
+        ("clients", Some(server.port)),
+        ("routes", server.cluster.as_ref().map(|cluster| cluster.port)),
+        ("gateways", server.gateway.as_ref().map(|gateway| gateway.port)),
+        ("leafnodes", server.leafnodes.port),
+        ("MQTT", server.mqtt.as_ref().map(|mqtt| mqtt.port)),
+        (
+            "HTTP monitoring",
+            monitoring.and_then(|monitoring| monitoring.http_port),
+        ),
+        (
+            "HTTPS monitoring",
+            monitoring.and_then(|monitoring| monitoring.https_port),
+        ),
+    ];
+    let ports: Vec<(&str, u16)> = ports
+        .into_iter()
+        .filter_map(|(use_, port)| Some((use_, port?)))
+        .collect();
+    for (i, (use_, port)) in ports.iter().enumerate() {
+        if let Some((other, _)) = ports[..i].iter().find(|(_, other_port)| other_port == port) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "Server {} uses port {} for both {} and {}",
+                server.name, port, other, use_
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// nats-server rejects leafnode `min_version`s older than 2.8.0, which introduced the setting.
+fn check_leaf_min_version(version: &str) -> Result<()> {
+    let parts: Vec<u32> = version
+        .split('.')
+        .map(|part| part.parse::<u32>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| NatsForgeError::InvalidConfig(format!("{:?} is not a version such as 2.10.0", version)))?;
+    if parts.len() != 3 {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "{:?} is not a version such as 2.10.0",
+            version
+        )));
+    }
+    if parts[..] < [2, 8, 0][..] {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "{} is older than 2.8.0, the first version that supports it",
+            version
+        )));
+    }
+    Ok(())
+}
+
+fn check_tls(tls: &TlsConfig) -> Result<()> {
+    if !tls.dev && (tls.cert_file.is_empty() || tls.key_file.is_empty()) {
+        return Err(NatsForgeError::InvalidConfig(
+            "cert_file and key_file are required unless dev is set".to_string(),
+        ));
+    }
+    if tls.verify_and_map && !tls.verify {
+        return Err(NatsForgeError::InvalidConfig(
+            "verify_and_map needs verify to be set as well".to_string(),
+        ));
+    }
+    if tls.verify && tls.ca_file.is_none() && !tls.dev {
+        return Err(NatsForgeError::InvalidConfig(
+            "verify needs a ca_file to check client certificates against".to_string(),
+        ));
+    }
+    if let Some(timeout) = tls.timeout {
+        if !timeout.is_finite() || timeout <= 0.0 {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "timeout {} is not a positive number of seconds",
+                timeout
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_monitoring(server: &ServerConfig, monitoring: &MonitoringConfig) -> Result<()> {
+    if monitoring.https_port.is_some() && monitoring.tls.is_none() && server.tls.is_none() {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "Server {} has an https_port but no TLS certificate to serve it with",
+            server.name
+        )));
+    }
+    if monitoring.tls.is_some() && server.tls.is_some() {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "Server {} sets TLS for both clients and monitoring; nats-server uses one certificate for both",
+            server.name
+        )));
+    }
+    Ok(())
+}
+
+fn check_mqtt(server: &ServerConfig, mqtt: &MqttConfig) -> Result<()> {
+    if !server.jetstream.enabled {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "Server {} has an MQTT listener but JetStream disabled; MQTT needs JetStream",
+            server.name
+        )));
+    }
+    if let Some(ack_wait) = &mqtt.ack_wait {
+        parse_duration(ack_wait).map_err(prefixed(format!("Invalid MQTT ack_wait for server {}", server.name)))?;
+    }
+    Ok(())
+}
+
+impl NatsConfig {
+    fn check_stream(&self, server: &ServerConfig, stream: &StreamSpec) -> Result<()> {
+        check_jetstream_enabled(server)?;
+        check_jetstream_name(&stream.name)?;
+        for subject in &stream.subjects {
+            validate_subject(subject)?;
+        }
+        if let Some(max_age) = &stream.max_age {
+            parse_duration(max_age).map_err(prefixed("Invalid max_age".to_string()))?;
+        }
+        check_replicas(stream.replicas)?;
+        self.check_jetstream_account(server, &stream.account)
+    }
+
+    fn check_consumer(&self, server: &ServerConfig, consumer: &ConsumerSpec) -> Result<()> {
+        check_jetstream_name(&consumer.name)?;
+        match server
+            .streams
+            .iter()
+            .filter(|stream| stream.name == consumer.stream)
+            .count()
+        {
+            0 => {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "stream {} isn't one of the server's streams",
+                    consumer.stream
+                )))
+            }
+            1 => {}
+            _ => {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "the server has more than one stream {}",
+                    consumer.stream
+                )))
+            }
+        }
+        if let Some(filter_subject) = &consumer.filter_subject {
+            validate_subject(filter_subject)?;
+        }
+        if consumer.max_deliver.is_some_and(|max| max == 0 || max < -1) {
+            return Err(NatsForgeError::InvalidConfig(
+                "max_deliver must be positive, or -1 for unlimited".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_kv_bucket(&self, server: &ServerConfig, bucket: &KvSpec) -> Result<()> {
+        check_jetstream_enabled(server)?;
+        if bucket.bucket.is_empty()
+            || !bucket
+                .bucket
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+        {
+            return Err(NatsForgeError::InvalidConfig(
+                "bucket names may only contain letters, digits, '_' and '-'".to_string(),
+            ));
+        }
+        if bucket.history.is_some_and(|history| !(1..=64).contains(&history)) {
+            return Err(NatsForgeError::InvalidConfig(
+                "history must be between 1 and 64".to_string(),
+            ));
+        }
+        if let Some(ttl) = &bucket.ttl {
+            parse_duration(ttl).map_err(prefixed("Invalid ttl".to_string()))?;
+        }
+        if bucket.max_value_size.is_some_and(|size| size == 0 || size < -1) {
+            return Err(NatsForgeError::InvalidConfig(
+                "max_value_size must be positive, or -1 for unlimited".to_string(),
+            ));
+        }
+        check_replicas(bucket.replicas)?;
+        self.check_jetstream_account(server, &bucket.account)
+    }
+
+    fn check_mirror(&self, mirror: &MirrorSpec) -> Result<()> {
+        check_jetstream_name(&mirror.name)?;
+        check_jetstream_name(&mirror.source_stream)
+            .map_err(prefixed(format!("source stream {}", mirror.source_stream)))?;
+        if mirror.source_domain == mirror.dest_domain {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "source and destination are both in domain {}",
+                mirror.dest_domain
+            )));
+        }
+        self.domain_server("source", &mirror.source_domain)?;
+        let server = self.domain_server("destination", &mirror.dest_domain)?;
+        self.check_jetstream_account(server, &mirror.account)
+    }
+
+    /// The first JetStream server of `domain`, the server a mirror's `side` is provisioned on.
+    fn domain_server(&self, side: &str, domain: &str) -> Result<&ServerConfig> {
+        if domain.is_empty() {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "the {} needs a JetStream domain",
+                side
+            )));
+        }
+        self.servers
+            .iter()
+            .find(|server| server.jetstream.enabled && server.jetstream.domain.as_deref() == Some(domain))
+            .ok_or_else(|| {
+                NatsForgeError::InvalidConfig(format!(
+                    "no server with JetStream enabled has the {} domain {}",
+                    side, domain
+                ))
+            })
+    }
+
+    /// Checks that `server` knows `account_name` and that the account can use JetStream.
+    fn check_jetstream_account(&self, server: &ServerConfig, account_name: &str) -> Result<()> {
+        let account = self
+            .servers
+            .iter()
+            .flat_map(|server| &server.accounts)
+            .find(|account| account.name == account_name)
+            .ok_or_else(|| NatsForgeError::InvalidConfig(format!("unknown account {}", account_name)))?;
+        if !account.jetstream {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "account {} doesn't enable jetstream",
+                account.name
+            )));
+        }
+        if account.users.is_empty() {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "account {} has no user to create it with",
+                account.name
+            )));
+        }
+        if self
+            .scoped_accounts(server)
+            .is_some_and(|scope| !scope.contains(account.name.as_str()))
+        {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "account {} isn't known to the server",
+                account.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn check_jetstream_enabled(server: &ServerConfig) -> Result<()> {
+    if !server.jetstream.enabled {
+        return Err(NatsForgeError::InvalidConfig(
+            "the server needs JetStream enabled".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Stream and consumer names end up as subject tokens.
+fn check_jetstream_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || matches!(c, '.' | '*' | '>' | '/' | '\\')) {
+        return Err(NatsForgeError::InvalidConfig(
+            "names must not be empty or contain whitespace, '.', '*', '>', '/' or '\\'".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn check_replicas(replicas: Option<usize>) -> Result<()> {
+    if replicas.is_some_and(|replicas| !(1..=5).contains(&replicas)) {
+        return Err(NatsForgeError::InvalidConfig(
+            "replicas must be between 1 and 5".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether one of the routes of `cluster` points at the cluster port of `other`.
+fn routes_to(cluster: &ClusterConfig, other: &ServerConfig) -> bool {
+    let Some(other_cluster) = &other.cluster else {
+        return false;
+    };
+    let other_host = net::client_host(other.host.as_deref());
+    cluster
+        .routes
+        .iter()
+        .filter_map(|route| net::url_host_port(route))
+        .any(|(host, port)| {
+            port == other_cluster.port
+                && (host == other_host || ["localhost", "127.0.0.1", "::1"].contains(&host.as_str()))
+        })
+}
+
+fn check_resolver(resolver: &ResolverType) -> Result<()> {
+    let (dir, field, duration) = match resolver {
+        ResolverType::Memory => return Ok(()),
+        ResolverType::Url(url) => {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                return Ok(());
+            }
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "{:?} is not an http or https URL",
+                url
+            )));
+        }
+        ResolverType::Full { dir, interval, .. } => (dir, "interval", interval),
+        ResolverType::Cache { dir, ttl } => (dir, "ttl", ttl),
+    };
+    if dir.as_os_str().is_empty() {
+        return Err(NatsForgeError::InvalidConfig("dir must not be empty".to_string()));
+    }
+    if let Some(duration) = duration {
+        parse_duration(duration).map_err(prefixed(format!("Invalid {}", field)))?;
+    }
+    Ok(())
+}
+
+/// Names are quoted in nats.conf, which could escape anything, but also end up in file names and
+/// log lines, where a newline or other control character is never what was meant.
+fn check_identifier(kind: &str, name: &str) -> Result<()> {
+    if name.contains(char::is_control) {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "{} {:?} contains a control character",
+            kind, name
+        )));
+    }
+    Ok(())
+}
+
+/// `extra_blocks` and `extra_config` come after the generated options, so nats-server would take
+/// theirs over the generated ones, e.g. another operator than the one that signed the accounts.
+/// Only unindented lines of `extra_config` are taken for top-level options.
+fn check_passthrough(server: &ServerConfig) -> Result<()> {
+    let mut names: Vec<_> = server.extra_blocks.keys().collect();
+    names.sort();
+    for name in names {
+        if GENERATED_KEYS.contains(&name.as_str()) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "extra_blocks sets {}, which natsforge generates",
+                name
+            )));
+        }
+        ConfValue::try_from(&server.extra_blocks[name]).map_err(prefixed(format!("extra_blocks {}", name)))?;
+    }
+    for line in server.extra_config.iter().flat_map(|extra_config| extra_config.lines()) {
+        let key = line
+            .split(|c: char| c.is_whitespace() || matches!(c, ':' | '=' | '{'))
+            .next()
+            .unwrap_or_default()
+            .trim_matches('"');
+        if GENERATED_KEYS.contains(&key) {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "extra_config sets {}, which natsforge generates",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Rejects what only account and user JWTs can express on a conf-mode server, and token users
+/// that share the server with other users.
+fn check_conf_mode(server: &ServerConfig) -> Result<()> {
+    let jwt_only = |what: String| {
+        Err(NatsForgeError::InvalidConfig(format!(
+            "{}, which needs operator mode",
+            what
+        )))
+    };
+    let server_options = [
+        ("leafnode remotes", !server.leafnodes.remotes.is_empty()),
+        ("push_accounts", server.push_accounts),
+        ("an operator", server.operator.is_some()),
+        ("external_accounts", !server.external_accounts.is_empty()),
+        ("a resolver", server.resolver != ResolverType::Memory),
+        (
+            "an operator_reference",
+            server.operator_reference != OperatorRef::Inline,
+        ),
+    ];
+    if let Some((option, _)) = server_options.iter().find(|(_, set)| *set) {
+        return jwt_only(format!("it sets {}", option));
+    }
+    for account in &server.accounts {
+        let account_options = [
+            ("max_connections", account.max_connections.is_some()),
+            ("max_payload", account.max_payload.is_some()),
+            ("max_message_payload", account.max_message_payload.is_some()),
+            ("max_leaf_connections", account.max_leaf_connections.is_some()),
+            ("max_exports", account.max_exports.is_some()),
+            ("max_imports", account.max_imports.is_some()),
+            ("allow_wildcard_exports", account.allow_wildcard_exports.is_some()),
+            ("signing_keys", account.signing_keys.is_some()),
+            ("roles", !account.roles.is_empty()),
+            ("expiry", account.expiry.is_some()),
+            ("disallow_bearer", account.disallow_bearer),
+            ("revocations", !account.revocations.is_empty()),
+        ];
+        if let Some((option, _)) = account_options.iter().find(|(_, set)| *set) {
+            return jwt_only(format!("account {} sets {}", account.name, option));
+        }
+        for user in &account.users {
+            let user_options = [
+                ("bearer", user.bearer),
+                ("role", user.role.is_some()),
+                ("expiry", user.expiry.is_some()),
+                ("start", user.start.is_some()),
+                ("times", !user.times.is_empty()),
+                ("locale", user.locale.is_some()),
+                ("source_networks", !user.source_networks.is_empty()),
+                ("max_subscriptions", user.max_subscriptions.is_some()),
+                ("max_data", user.max_data.is_some()),
+                ("max_payload", user.max_payload.is_some()),
+            ];
+            if let Some((option, _)) = user_options.iter().find(|(_, set)| *set) {
+                return jwt_only(format!(
+                    "user {} in account {} sets {}",
+                    user.name, account.name, option
+                ));
+            }
+            let users = server.accounts.iter().map(|account| account.users.len()).sum::<usize>();
+            if user.conf_credential == ConfCredential::Token && users > 1 {
+                return Err(NatsForgeError::InvalidConfig(format!(
+                    "user {} in account {} authenticates with a token, which nats-server only accepts when it \
+                     is the server's only user",
+                    user.name, account.name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_auth_callout(server: &ServerConfig, callout: &AuthCalloutConfig) -> Result<()> {
+    if server.auth_mode == AuthMode::Operator {
+        return Err(NatsForgeError::InvalidConfig(
+            "it needs conf mode; with a trusted operator, nats-server takes the callout from the issuer account's JWT"
+                .to_string(),
+        ));
+    }
+    let Some(issuer) = server
+        .accounts
+        .iter()
+        .find(|account| account.name == callout.issuer_account)
+    else {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "issuer_account {} isn't an account of the server",
+            callout.issuer_account
+        )));
+    };
+    if callout.auth_users.is_empty() {
+        return Err(NatsForgeError::InvalidConfig(
+            "it lists no auth_users, but the service needs one to connect as".to_string(),
+        ));
+    }
+    for name in &callout.auth_users {
+        let Some(user) = issuer.users.iter().find(|user| user.name == *name) else {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "auth user {} isn't a user of account {}",
+                name, issuer.name
+            )));
+        };
+        if user.conf_credential == ConfCredential::Token {
+            return Err(NatsForgeError::InvalidConfig(format!(
+                "auth user {} authenticates with a token, but nats-server only knows nkey and password users as \
+                 auth users",
+                name
+            )));
+        }
+    }
+    if let Some(account) = callout
+        .allowed_accounts
+        .iter()
+        .find(|account| !server.accounts.iter().any(|known| known.name == **account))
+    {
+        return Err(NatsForgeError::InvalidConfig(format!(
+            "allowed account {} isn't an account of the server",
+            account
+        )));
+    }
+    Ok(())
+}
+
+/// Prefixes an `InvalidConfig` message with where in the config the problem was found.
+fn prefixed(context: String) -> impl FnOnce(NatsForgeError) -> NatsForgeError {
+    move |err| match err {
+        NatsForgeError::InvalidConfig(message) => NatsForgeError::InvalidConfig(format!("{}: {}", context, message)),
+        other => other,
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::config::Durability;
+
+/// The file operations artifacts are persisted with.
+///
+/// The default [`FsSink`] goes straight to the filesystem; wrapping it is the way to observe or
+/// intercept what the forge writes.
+pub trait OutputSink: Send + Sync {
+    /// Creates or truncates `path` and writes `contents` to it.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Flushes a file or directory to stable storage.
+    fn fsync(&self, path: &Path) -> io::Result<()>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Like [`OutputSink::write`], for contents only the current user should be able to read,
+    /// such as `.creds` files. Sinks without a notion of permissions can keep the default.
+    fn write_private(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.write(path, contents)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsSink;
+
+impl OutputSink for FsSink {
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        write_all(File::create(path)?, contents)
+    }
+
+    fn fsync(&self, path: &Path) -> io::Result<()> {
+        File::open(path)?.sync_all()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    /// Creates the file with mode 0600 on Unix; elsewhere the default permissions apply.
+    fn write_private(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            let file = options.open(path)?;
+            // The mode only applies to new files; a leftover file keeps its permissions otherwise.
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+            write_all(file, contents)
+        }
+        #[cfg(not(unix))]
+        write_all(options.open(path)?, contents)
+    }
+}
+
+fn write_all(mut file: File, contents: &[u8]) -> io::Result<()> {
+    file.write_all(contents)?;
+    file.flush()
+}
+
+/// Drops everything; backs [`ArtifactMode::InMemory`](crate::config::ArtifactMode::InMemory).
+struct DiscardSink;
+
+impl OutputSink for DiscardSink {
+    fn write(&self, _path: &Path, _contents: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn fsync(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn rename(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes artifacts through an [`OutputSink`] so readers never see a partially written file.
+pub(crate) struct ArtifactWriter<'a> {
+    sink: &'a dyn OutputSink,
+    durability: Durability,
+}
+
+impl<'a> ArtifactWriter<'a> {
+    pub(crate) fn new(sink: &'a dyn OutputSink, durability: Durability) -> Self {
+        ArtifactWriter { sink, durability }
+    }
+
+    /// A writer that doesn't write anything.
+    pub(crate) fn discarding() -> ArtifactWriter<'static> {
+        ArtifactWriter {
+            sink: &DiscardSink,
+            durability: Durability::Fast,
+        }
+    }
+
+    /// Writes `contents` to a sibling temp file and renames it over `path`.
+    pub(crate) fn write(&self, path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        self.write_with(path, contents.as_ref(), false)
+    }
+
+    /// Like [`ArtifactWriter::write`], but only the current user may read the file.
+    pub(crate) fn write_private(&self, path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        self.write_with(path, contents.as_ref(), true)
+    }
+
+    fn write_with(&self, path: &Path, contents: &[u8], private: bool) -> io::Result<()> {
+        let tmp_path = temp_path(path);
+        if private {
+            self.sink.write_private(&tmp_path, contents)?;
+        } else {
+            self.sink.write(&tmp_path, contents)?;
+        }
+        if self.durability == Durability::Fsync {
+            self.sink.fsync(&tmp_path)?;
+        }
+        self.sink.rename(&tmp_path, path)?;
+        // Directories can only be opened for syncing on Unix.
+        #[cfg(unix)]
+        if self.durability == Durability::Fsync {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                self.sink.fsync(parent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates an output directory; with `private`, only the current user may access it (Unix only).
+pub(crate) fn create_output_dir(path: &Path, private: bool) -> io::Result<()> {
+    std::fs::create_dir_all(path)?;
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp", file_name))
+}
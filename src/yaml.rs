@@ -0,0 +1,115 @@
+//! Renders JSON values as block-style YAML, for the compose file and Kubernetes manifests.
+//!
+//! Strings are written as double-quoted scalars, which JSON string escaping produces, except for
+//! multi-line strings such as a `nats.conf`, which become literal blocks.
+
+use serde_json::Value;
+
+/// `value` as a YAML document.
+pub(crate) fn to_yaml(value: &Value) -> String {
+    let mut yaml = String::new();
+    match value {
+        Value::Object(map) if !map.is_empty() => write_map(&mut yaml, map, 0),
+        Value::Array(items) if !items.is_empty() => write_list(&mut yaml, items, 0),
+        _ => {
+            yaml.push_str(&scalar(value, 0));
+            yaml.push('\n');
+        }
+    }
+    yaml
+}
+
+fn write_map(yaml: &mut String, map: &serde_json::Map<String, Value>, indent: usize) {
+    for (key, value) in map {
+        yaml.push_str(&" ".repeat(indent));
+        yaml.push_str(&key_scalar(key));
+        yaml.push(':');
+        write_nested(yaml, value, indent);
+    }
+}
+
+fn write_list(yaml: &mut String, items: &[Value], indent: usize) {
+    for item in items {
+        yaml.push_str(&" ".repeat(indent));
+        yaml.push('-');
+        match item {
+            // The first key goes on the dash's line, the others line up with it.
+            Value::Object(map) if !map.is_empty() => {
+                let mut entry = String::new();
+                write_map(&mut entry, map, indent + 2);
+                yaml.push(' ');
+                yaml.push_str(&entry[indent + 2..]);
+            }
+            _ => write_nested(yaml, item, indent),
+        }
+    }
+}
+
+/// Writes what follows `key:` or `-`.
+fn write_nested(yaml: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            yaml.push('\n');
+            write_map(yaml, map, indent + 2);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            yaml.push('\n');
+            write_list(yaml, items, indent + 2);
+        }
+        _ => {
+            yaml.push(' ');
+            yaml.push_str(&scalar(value, indent + 2));
+            yaml.push('\n');
+        }
+    }
+}
+
+fn scalar(value: &Value, indent: usize) -> String {
+    match value {
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        Value::String(text) if is_block(text) => {
+            let chomping = if text.ends_with("\n\n") {
+                "+"
+            } else if text.ends_with('\n') {
+                ""
+            } else {
+                "-"
+            };
+            let mut block = format!("|{}", chomping);
+            for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+                block.push('\n');
+                if !line.is_empty() {
+                    block.push_str(&" ".repeat(indent));
+                    block.push_str(line);
+                }
+            }
+            block
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Whether a string is written as a literal block: it spans lines, and neither starts with a space,
+/// which would be read as more indentation, nor has characters a block can't hold.
+fn is_block(text: &str) -> bool {
+    text.contains('\n')
+        && !text.starts_with([' ', '\n'])
+        && !text
+            .chars()
+            .any(|c| (c.is_control() && c != '\n' && c != '\t') || matches!(c, '\u{2028}' | '\u{2029}'))
+        && !text.lines().any(|line| line.ends_with([' ', '\t']))
+}
+
+/// Keys like `app.kubernetes.io/name` are written plain, anything else quoted.
+fn key_scalar(key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+    if plain {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
@@ -0,0 +1,329 @@
+use base64::Engine;
+use natsforge::{config::NatsConfig, NatsForge};
+use serde_json::json;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn demo_config(output_dir: &str, account: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    let mut demo = json!({ "name": "DEMO", "users": [{ "name": "visitor" }] });
+    demo.as_object_mut()
+        .unwrap()
+        .extend(account.as_object().unwrap().clone());
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "account-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "account-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [demo]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_account_expiry_claim() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = demo_config(
+        output_dir.path().to_str().unwrap(),
+        json!({ "expiry": "2030-12-31T23:59:59Z" }),
+    )?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    assert_eq!(account["exp"], 1_924_991_999);
+
+    let output_dir = tempfile::TempDir::new()?;
+    let config = demo_config(output_dir.path().to_str().unwrap(), json!({ "expiry": "14d" }))?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    let exp = account["exp"].as_i64().unwrap();
+    assert!((now + 14 * 86_400..now + 14 * 86_400 + 60).contains(&exp), "{}", exp);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_disallow_bearer_claim() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = demo_config(output_dir.path().to_str().unwrap(), json!({ "disallow_bearer": true }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    assert_eq!(account["nats"]["limits"]["disallow_bearer"], true);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bearer_user_in_disallow_bearer_account_is_rejected() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let account = json!({ "disallow_bearer": true, "users": [{ "name": "kiosk", "bearer": true }] });
+    let mut config = demo_config(output_dir.path().to_str().unwrap(), account)?;
+    // Point the nsc backend at a binary that doesn't exist: the error must come before it runs.
+    config.backend = natsforge::config::BackendKind::Nsc;
+    config.nsc_path = Some("/nonexistent/nsc".into());
+    let err = NatsForge::from_config(config)?
+        .initialize()
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("User kiosk is a bearer user, but account DEMO disallows bearer tokens"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_user_expiring_after_account_is_rejected() -> anyhow::Result<()> {
+    let account = json!({
+        "expiry": "2030-06-30",
+        "users": [{ "name": "visitor", "expiry": "2030-07-01" }]
+    });
+    let err = demo_config("unused", account)?.validate().unwrap_err().to_string();
+    assert!(err.contains("User visitor expires at 2030-07-01"), "{}", err);
+    assert!(err.contains("after its account DEMO at 2030-06-30"), "{}", err);
+
+    let account = json!({ "expiry": "30d", "users": [{ "name": "visitor", "expiry": "7d" }] });
+    demo_config("unused", account)?.validate()?;
+    Ok(())
+}
+
+fn user_key(creds_path: &std::path::Path) -> anyhow::Result<String> {
+    let creds = std::fs::read_to_string(creds_path)?;
+    Ok(claims(creds.lines().nth(1).unwrap())?["sub"]
+        .as_str()
+        .unwrap()
+        .to_string())
+}
+
+#[tokio::test]
+async fn test_revoke_user_rewrites_account_jwt() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let account = json!({ "users": [{ "name": "visitor" }, { "name": "lost-laptop" }] });
+    let forge = NatsForge::from_config(demo_config(output_dir.path().to_str().unwrap(), account)?)?;
+    let err = forge.revoke_user("DEMO", "lost-laptop").await.unwrap_err();
+    assert!(err.to_string().contains("call initialize() first"), "{}", err);
+
+    let result = forge.initialize().await?;
+    let revoked = user_key(result.creds("DEMO", "lost-laptop").unwrap())?;
+    let kept = user_key(result.creds("DEMO", "visitor").unwrap())?;
+
+    let jwt = forge.revoke_user("DEMO", "lost-laptop").await?;
+    let revocations = &claims(&jwt)?["nats"]["revocations"];
+    assert!(revocations[&revoked].is_u64(), "{}", revocations);
+    assert!(revocations.get(&kept).is_none());
+
+    assert_eq!(std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?, jwt);
+    let server_config = std::fs::read_to_string(result.server_config("account-server").unwrap())?;
+    assert!(server_config.contains(&jwt));
+
+    let err = forge.revoke_user("DEMO", "nobody").await.unwrap_err();
+    assert!(err.to_string().contains("Account DEMO has no user nobody"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_declarative_revocations() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let outside = nkeys::KeyPair::new_user().public_key();
+    let account = json!({ "revocations": ["visitor", outside] });
+    let result = NatsForge::from_config(demo_config(output_dir.path().to_str().unwrap(), account)?)?
+        .initialize()
+        .await?;
+
+    let revoked = user_key(result.creds("DEMO", "visitor").unwrap())?;
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    let revocations = account["nats"]["revocations"].as_object().unwrap();
+    assert_eq!(revocations.len(), 2, "{:?}", revocations);
+    assert!(revocations.contains_key(&revoked) && revocations.contains_key(&outside));
+
+    let err = demo_config("unused", json!({ "revocations": ["stranger"] }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("revokes stranger"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_add_user_after_initialize() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(demo_config(output_dir.path().to_str().unwrap(), json!({}))?)?;
+    let latecomer: natsforge::config::UserConfig = serde_json::from_value(json!({
+        "name": "latecomer",
+        "allow_pub": ["demo.>"]
+    }))?;
+    let err = forge.add_user("DEMO", &latecomer).await.unwrap_err();
+    assert!(err.to_string().contains("call initialize() first"), "{}", err);
+
+    let result = forge.initialize().await?;
+    let creds_path = forge.add_user("DEMO", &latecomer).await?;
+    assert_eq!(
+        creds_path,
+        output_dir.path().canonicalize()?.join("DEMO-latecomer.creds")
+    );
+    assert_eq!(forge.creds("DEMO", "latecomer"), Some(creds_path.clone()));
+    assert_eq!(
+        forge.creds("DEMO", "visitor").as_deref(),
+        result.creds("DEMO", "visitor")
+    );
+
+    let creds = std::fs::read_to_string(&creds_path)?;
+    let user = claims(creds.lines().nth(1).unwrap())?;
+    assert_eq!(user["iss"], result.account_id("DEMO").unwrap());
+    assert_eq!(user["nats"]["pub"]["allow"], json!(["demo.>"]));
+
+    let err = forge.add_user("DEMO", &latecomer).await.unwrap_err();
+    assert!(err.to_string().contains("already has a user latecomer"), "{}", err);
+    let err = forge.add_user("NOPE", &latecomer).await.unwrap_err();
+    assert!(err.to_string().contains("Unknown account: NOPE"), "{}", err);
+
+    forge.revoke_user("DEMO", "latecomer").await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rotate_user_reissues_creds() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let account = json!({ "users": [{ "name": "visitor", "allow_sub": ["demo.>"] }] });
+    let forge = NatsForge::from_config(demo_config(output_dir.path().to_str().unwrap(), account)?)?;
+    let result = forge.initialize().await?;
+    let creds_path = result.creds("DEMO", "visitor").unwrap().to_path_buf();
+    let old_key = user_key(&creds_path)?;
+
+    let rotated = forge.rotate_user("DEMO", "visitor", true).await?;
+    assert_eq!(rotated.creds_path, creds_path);
+    assert_eq!(rotated.revoked_key.as_deref(), Some(old_key.as_str()));
+    let new_key = user_key(&creds_path)?;
+    assert_ne!(new_key, old_key);
+    let creds = std::fs::read_to_string(&creds_path)?;
+    assert_eq!(
+        claims(creds.lines().nth(1).unwrap())?["nats"]["sub"]["allow"],
+        json!(["demo.>"])
+    );
+
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    let revocations = account["nats"]["revocations"].as_object().unwrap();
+    assert!(revocations.contains_key(&old_key) && !revocations.contains_key(&new_key));
+
+    let kept = forge.rotate_user("DEMO", "visitor", false).await?;
+    assert!(kept.revoked_key.is_none());
+    assert_ne!(user_key(&creds_path)?, new_key);
+
+    let err = forge.rotate_user("DEMO", "nobody", true).await.unwrap_err();
+    assert!(err.to_string().contains("Account DEMO has no user nobody"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_account_mappings_claim() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = demo_config(
+        output_dir.path().to_str().unwrap(),
+        json!({ "mappings": {
+            "orders.new": "orders.v2.new",
+            "events.*": [
+                { "dest": "events.blue.{{wildcard(1)}}", "weight": 80 },
+                { "dest": "events.green.{{wildcard(1)}}", "weight": 20 }
+            ]
+        } }),
+    )?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    assert_eq!(
+        account["nats"]["mappings"],
+        json!({
+            "orders.new": [{ "subject": "orders.v2.new", "weight": 100 }],
+            "events.*": [
+                { "subject": "events.blue.{{wildcard(1)}}", "weight": 80 },
+                { "subject": "events.green.{{wildcard(1)}}", "weight": 20 }
+            ]
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_account_mapping_weights_are_validated() -> anyhow::Result<()> {
+    for (destinations, message) in [
+        (json!([]), "events.* has no destinations"),
+        (
+            json!([{ "dest": "a", "weight": 60 }, { "dest": "b", "weight": 30 }]),
+            "add up to 90, not 100",
+        ),
+        (
+            json!([{ "dest": "a", "weight": 0 }, { "dest": "b", "weight": 100 }]),
+            "weight of 0",
+        ),
+        (
+            json!([{ "dest": "a", "weight": 50 }, { "dest": "a", "weight": 50 }]),
+            "repeats destination a",
+        ),
+        (json!([{ "dest": "a b", "weight": 100 }]), "Invalid subject"),
+    ] {
+        let config = demo_config("/srv/demo", json!({ "mappings": { "events.*": destinations } }))?;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("Mapping in account DEMO"), "{}", err);
+        assert!(err.contains(message), "{}", err);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_account_limit_claims() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let account = json!({
+        "max_leaf_connections": 2,
+        "max_exports": 1,
+        "max_imports": 0,
+        "allow_wildcard_exports": false,
+        "max_payload": 1048576,
+        "max_message_payload": 4096,
+        "exports": [{ "subject": "demo.events" }]
+    });
+    let config = demo_config(output_dir.path().to_str().unwrap(), account)?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let account = claims(&std::fs::read_to_string(&result.account("DEMO").unwrap().jwt_path)?)?;
+    let limits = &account["nats"]["limits"];
+    assert_eq!(limits["leaf"], 2);
+    assert_eq!(limits["exports"], 1);
+    assert_eq!(limits["imports"], 0);
+    assert_eq!(limits["wildcards"], false);
+    assert_eq!(limits["data"], 1048576);
+    assert_eq!(limits["payload"], 4096);
+    Ok(())
+}
+
+#[test]
+fn test_account_limits_cover_its_declarations() -> anyhow::Result<()> {
+    for (account, message) in [
+        (
+            json!({ "max_exports": 1, "exports": [{ "subject": "a" }, { "subject": "b" }] }),
+            "Account DEMO: it declares 2 exports, more than its max_exports of 1",
+        ),
+        (
+            json!({ "max_imports": 0, "imports": [{ "subject": "a", "account": "OTHER" }] }),
+            "Account DEMO: it declares 1 imports, more than its max_imports of 0",
+        ),
+        (
+            json!({ "allow_wildcard_exports": false, "exports": [{ "subject": "demo.>" }] }),
+            "Account DEMO: it disallows wildcard exports, but exports demo.>",
+        ),
+        (
+            json!({ "max_leaf_connections": -2 }),
+            "Account DEMO: max_leaf_connections is -2; use 0 or more, or -1 for unlimited",
+        ),
+    ] {
+        let err = demo_config("/srv/demo", account)?.validate().unwrap_err().to_string();
+        assert!(err.contains(message), "{}", err);
+    }
+
+    let unlimited = json!({ "max_exports": -1, "exports": [{ "subject": "a" }, { "subject": "b" }] });
+    demo_config("/srv/demo", unlimited)?.validate()?;
+    Ok(())
+}
@@ -0,0 +1,365 @@
+//! Guards the public API against accidental changes.
+//!
+//! The public items of every source file are extracted into `tests/public-api.txt`. When a change to
+//! the API is intended, regenerate the snapshot with `NATSFORGE_UPDATE_API=1 cargo test --test
+//! api_tests` and review the diff.
+
+use std::path::Path;
+
+/// Every source file and how much its public items can be relied on. New files need an entry, so
+/// exposing a module is a deliberate decision.
+const STABILITY: &[(&str, &str)] = &[
+    ("lib.rs", "stable"),
+    ("prelude.rs", "stable"),
+    ("config.rs", "stable"),
+    ("error.rs", "stable"),
+    ("backend.rs", "stable"),
+    ("native.rs", "stable"),
+    ("nsc.rs", "stable; NscBackend is re-exported at the crate root"),
+    ("jwt.rs", "stable; extract_account_id is re-exported at the crate root"),
+    ("writer.rs", "stable"),
+    ("timing.rs", "stable"),
+    ("progress.rs", "stable"),
+    ("expand.rs", "stable"),
+    ("graph.rs", "stable"),
+    ("subject.rs", "stable"),
+    ("validate.rs", "stable; methods on NatsConfig"),
+    ("client.rs", "stable; methods on SetupResult, behind the client feature"),
+    ("cluster.rs", "stable; methods on NatsConfig"),
+    ("overlay.rs", "stable; methods on NatsConfig"),
+    ("tenant.rs", "stable; methods on NatsConfig"),
+    ("user_template.rs", "stable; methods on NatsConfig"),
+    ("compose.rs", "stable"),
+    ("conf.rs", "stable"),
+    ("diff.rs", "stable"),
+    ("encryption.rs", "stable; behind the encryption feature"),
+    ("kubernetes.rs", "stable"),
+    ("manifest.rs", "stable"),
+    ("prometheus.rs", "stable"),
+    ("secrets.rs", "stable"),
+    ("tls.rs", "stable"),
+    (
+        "env.rs",
+        "unstable: the child process environment may move into the backends",
+    ),
+    (
+        "net.rs",
+        "unstable: host and URL helpers may change with the config format",
+    ),
+    ("conf_auth.rs", "private"),
+    ("provision.rs", "private"),
+    ("server.rs", "private"),
+    ("yaml.rs", "private"),
+    ("main.rs", "binary, not part of the library API"),
+];
+
+/// Removes string literals and line comments, keeping the code around them.
+fn strip_code(line: &str) -> String {
+    let mut code = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                code.push_str("\"\"");
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => break,
+            _ => code.push(c),
+        }
+    }
+    code
+}
+
+/// Collapses whitespace and drops a trailing body or separator.
+fn normalize(signature: &str, keep_braces: bool) -> String {
+    let signature = signature.split_whitespace().collect::<Vec<_>>().join(" ");
+    let signature = if keep_braces {
+        signature.as_str()
+    } else {
+        signature.split(" {").next().unwrap().trim_end_matches('{').trim()
+    };
+    let signature = signature.trim_end_matches([';', ',']).trim();
+    signature
+        .replace("( ", "(")
+        .replace(", )", ")")
+        .replace(",)", ")")
+        .replace("{ ", "{")
+        .replace(", }", "}")
+        .replace(" }", "}")
+        .replace("(mut ", "(")
+        .replace(", mut ", ", ")
+}
+
+/// The name of the type an `impl`, `struct`, `enum` or `trait` header is about.
+fn item_name(header: &str) -> Option<String> {
+    let header = header.trim_start_matches("pub ");
+    let rest = ["struct ", "enum ", "trait ", "impl"]
+        .iter()
+        .find_map(|keyword| header.strip_prefix(keyword))?;
+    let mut rest = rest.trim_start();
+    if let Some(generics) = rest.strip_prefix('<') {
+        let mut depth = 1;
+        let end = generics
+            .char_indices()
+            .find(|(_, c)| {
+                match c {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })
+            .map(|(i, _)| i)?;
+        rest = generics[end + 1..].trim_start();
+    }
+    let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    Some(name)
+}
+
+fn item_name_of_variant(header: &str, owner: &str) -> Option<String> {
+    let name: String = header.chars().take_while(|c| c.is_alphanumeric()).collect();
+    Some(format!("{}.{}", owner, name))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Block {
+    /// Fields are public when marked `pub`.
+    Struct,
+    /// Every variant is public.
+    Enum,
+    /// Every field of a struct-like enum variant is public.
+    Variant,
+    /// Every method is public.
+    Trait,
+    /// Methods are public when marked `pub`.
+    Impl,
+    /// Anything else: function bodies, private types, trait impls.
+    Other,
+}
+
+/// Lists the public items, fields, variants and trait methods declared in `source`.
+fn public_items(source: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut blocks: Vec<(Block, String)> = Vec::new();
+    let mut pending: Option<(String, String)> = None;
+
+    for line in source.lines() {
+        let code = strip_code(line.trim());
+        let code = code.trim_end();
+        if code.is_empty() || code.starts_with("#[") || code.starts_with("#![") {
+            continue;
+        }
+
+        let (block, owner) = blocks.last().cloned().unwrap_or((Block::Impl, String::new()));
+        let declares = match block {
+            Block::Struct | Block::Impl => code.starts_with("pub ") || code.starts_with("pub("),
+            Block::Trait => ["fn ", "async fn ", "type ", "const "]
+                .iter()
+                .any(|k| code.starts_with(k)),
+            Block::Enum => code.starts_with(|c: char| c.is_ascii_uppercase()),
+            Block::Variant => code.starts_with(|c: char| c.is_ascii_lowercase()),
+            Block::Other => false,
+        };
+        if pending.is_none() && declares && !code.starts_with("pub(") {
+            let prefix = match block {
+                Block::Struct | Block::Enum | Block::Variant => format!("{}.", owner),
+                Block::Trait | Block::Impl if !owner.is_empty() => format!("{}::", owner),
+                _ => String::new(),
+            };
+            pending = Some((prefix, String::new()));
+        }
+        if let Some((_, signature)) = &mut pending {
+            signature.push(' ');
+            signature.push_str(code);
+            let complete = if signature.trim_start().starts_with("pub use ") {
+                code.ends_with(';')
+            } else {
+                let field = matches!(block, Block::Struct | Block::Variant) && code.ends_with(',');
+                code.contains('{') || code.ends_with(';') || field || block == Block::Enum
+            };
+            if complete {
+                let (prefix, signature) = pending.take().unwrap();
+                // `pub use` lists and single-line struct variants keep their braces.
+                let keep_braces =
+                    signature.trim_start().starts_with("pub use ") || (block == Block::Enum && signature.contains('}'));
+                let mut signature = normalize(&signature, keep_braces);
+                if signature.starts_with("pub const ") || signature.starts_with("pub static ") {
+                    signature = signature.split(" = ").next().unwrap().to_string();
+                }
+                items.push(format!("{}{}", prefix, signature));
+            }
+        }
+
+        for c in code.chars() {
+            match c {
+                '{' => {
+                    let header = code.trim();
+                    let public = header.starts_with("pub ");
+                    let opened = if block == Block::Enum && header.ends_with('{') {
+                        (Block::Variant, item_name_of_variant(header, &owner))
+                    } else if header.starts_with("pub struct ") {
+                        (Block::Struct, item_name(header))
+                    } else if header.starts_with("pub enum ") {
+                        (Block::Enum, item_name(header))
+                    } else if header.starts_with("pub trait ") {
+                        (Block::Trait, item_name(header))
+                    } else if header.starts_with("impl") && !header.contains(" for ") && blocks.is_empty() {
+                        (Block::Impl, item_name(header))
+                    } else {
+                        (Block::Other, None)
+                    };
+                    let opened = match opened {
+                        (kind, Some(name)) if public || kind == Block::Impl || kind == Block::Variant => (kind, name),
+                        _ => (Block::Other, String::new()),
+                    };
+                    blocks.push(opened);
+                }
+                '}' => {
+                    blocks.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    items
+}
+
+fn snapshot() -> String {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut files: Vec<String> = std::fs::read_dir(&src)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".rs"))
+        .collect();
+    files.sort();
+
+    let mut snapshot = String::from("# Public API of natsforge. Regenerate with NATSFORGE_UPDATE_API=1.\n");
+    for file in files {
+        let stability = STABILITY
+            .iter()
+            .find(|(name, _)| *name == file)
+            .map(|(_, stability)| *stability)
+            .unwrap_or_else(|| panic!("src/{} needs an entry in STABILITY", file));
+        if file == "main.rs" {
+            continue;
+        }
+        let source = std::fs::read_to_string(src.join(&file)).unwrap();
+        snapshot.push_str(&format!("\n## {} ({})\n", file, stability));
+        for item in public_items(&source) {
+            snapshot.push_str(&item);
+            snapshot.push('\n');
+        }
+    }
+    snapshot
+}
+
+#[test]
+fn test_public_api_matches_snapshot() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/public-api.txt");
+    let actual = snapshot();
+    if std::env::var_os("NATSFORGE_UPDATE_API").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    if expected != actual {
+        let changes: Vec<String> = diff(&expected, &actual);
+        panic!(
+            "The public API changed; if that's intended, rerun with NATSFORGE_UPDATE_API=1.\n{}",
+            changes.join("\n")
+        );
+    }
+}
+
+/// Lines only in `expected` (`-`) or only in `actual` (`+`).
+fn diff(expected: &str, actual: &str) -> Vec<String> {
+    let removed = expected
+        .lines()
+        .filter(|line| !actual.lines().any(|l| l == *line))
+        .map(|line| format!("- {}", line));
+    let added = actual
+        .lines()
+        .filter(|line| !expected.lines().any(|l| l == *line))
+        .map(|line| format!("+ {}", line));
+    removed.chain(added).collect()
+}
+
+#[test]
+fn test_extracts_public_items() {
+    let source = r#"
+pub struct Shown {
+    pub field: String, // trailing comment
+    hidden: u8,
+}
+
+pub(crate) struct Hidden {
+    pub field: String,
+}
+
+pub enum Kind {
+    /// Doc comment.
+    First,
+    Second(String),
+    #[error("{field}")]
+    Third { field: String },
+    Fourth {
+        /// Doc comment.
+        #[source]
+        source: Error,
+    },
+}
+
+pub use crate::{
+    a::{B, C},
+    D,
+};
+
+impl Shown {
+    pub fn new(
+        field: String,
+    ) -> Self {
+        let s = "pub fn fake() {";
+        Shown { field, hidden: 0 }
+    }
+
+    fn private(&self) {}
+}
+
+pub trait Backend: Send {
+    fn run(&self) -> u8;
+}
+
+impl Backend for Shown {
+    fn run(&self) -> u8 {
+        1
+    }
+}
+
+pub const LIMIT: usize = 4;
+"#;
+    assert_eq!(
+        public_items(source),
+        vec![
+            "pub struct Shown",
+            "Shown.pub field: String",
+            "pub enum Kind",
+            "Kind.First",
+            "Kind.Second(String)",
+            "Kind.Third {field: String}",
+            "Kind.Fourth",
+            "Kind.Fourth.source: Error",
+            "pub use crate::{a::{B, C}, D}",
+            "Shown::pub fn new(field: String) -> Self",
+            "pub trait Backend: Send",
+            "Backend::fn run(&self) -> u8",
+            "pub const LIMIT: usize",
+        ]
+    );
+}
@@ -0,0 +1,130 @@
+use std::path::Path;
+
+use natsforge::{
+    conf::{ConfObject, ConfValue},
+    config::{ArtifactMode, AuthMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+fn config(output_dir: &Path) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "unused" },
+        "backend": "nsc",
+        "durability": "fast",
+        "servers": [{
+            "name": "dev",
+            "port": 4222,
+            "output_dir": output_dir,
+            "auth_mode": "conf",
+            "auth_callout": {
+                "issuer_account": "AUTH",
+                "auth_users": ["callout", "admin"],
+                "allowed_accounts": ["APP"],
+                "xkey": true
+            },
+            "accounts": [
+                {
+                    "name": "AUTH",
+                    "users": [{ "name": "callout" }, { "name": "admin", "conf_credential": "password" }]
+                },
+                { "name": "APP" }
+            ]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_auth_callout_block() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(config(dir.path())?)?;
+    let result = forge.initialize().await?;
+    let document = forge.server_document("dev")?;
+
+    let keys = &result.auth_callout_keys["dev"];
+    assert!(keys.issuer_seed.ends_with("auth-callout-issuer.nk"));
+    let issuer = nkeys::KeyPair::from_seed(&std::fs::read_to_string(&keys.issuer_seed)?)?;
+    let xkey_seed = keys.xkey_seed.as_ref().expect("no xkey seed");
+    assert!(xkey_seed.ends_with("auth-callout.xk"));
+    let xkey = nkeys::XKey::from_seed(&std::fs::read_to_string(xkey_seed)?)?;
+    let callout_seed = std::fs::read_to_string(result.user_secret("AUTH", "callout").unwrap())?;
+    let callout_key = nkeys::KeyPair::from_seed(&callout_seed)?.public_key();
+
+    let authorization = document
+        .root
+        .get("authorization")
+        .and_then(ConfValue::as_object)
+        .expect("no authorization block");
+    assert_eq!(
+        authorization.get("auth_callout").and_then(ConfValue::as_object),
+        Some(
+            &ConfObject::new()
+                .with("issuer", issuer.public_key())
+                .with("account", "AUTH")
+                .with("auth_users", vec![callout_key, "AUTH-admin".to_string()])
+                .with("allowed_accounts", vec!["APP"])
+                .with("xkey", xkey.public_key())
+        ),
+        "{}",
+        document.render()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auth_callout_in_memory() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/dev"))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    config.servers[0].auth_callout.as_mut().unwrap().xkey = false;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let keys = &result.auth_callout_keys["dev"];
+    assert_eq!(keys.issuer_seed, Path::new("/srv/dev/auth-callout-issuer.nk"));
+    assert_eq!(keys.xkey_seed, None);
+    let artifacts = result.artifacts.as_ref().unwrap();
+    let seed = &artifacts.server_files["dev"][&keys.issuer_seed];
+    assert!(seed.starts_with("SA"), "{}", seed);
+    assert!(!artifacts.server_configs["dev"].contains("xkey"));
+    Ok(())
+}
+
+#[test]
+fn test_auth_callout_validation() -> anyhow::Result<()> {
+    let mut config = config(Path::new("unused"))?;
+    config.servers[0]
+        .auth_callout
+        .as_mut()
+        .unwrap()
+        .auth_users
+        .push("ghost".to_string());
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Auth callout of server dev: auth user ghost isn't a user of account AUTH"),
+        "{}",
+        err
+    );
+
+    let mut config = crate::config(Path::new("unused"))?;
+    config.servers[0].auth_callout.as_mut().unwrap().allowed_accounts = vec!["OTHER".to_string()];
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("allowed account OTHER isn't an account of the server"),
+        "{}",
+        err
+    );
+
+    let mut config = crate::config(Path::new("unused"))?;
+    config.servers[0].auth_mode = AuthMode::Operator;
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "Auth callout of server dev: it needs conf mode; with a trusted operator, nats-server takes the callout \
+             from the issuer account's JWT"
+        ),
+        "{}",
+        err
+    );
+    Ok(())
+}
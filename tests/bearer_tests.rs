@@ -0,0 +1,73 @@
+use base64::Engine;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::native_config;
+
+mod common;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(output_dir: &str) -> anyhow::Result<NatsConfig> {
+    native_config(json!([{
+        "name": "bearer-server",
+        "port": 4222,
+        "output_dir": output_dir,
+        "accounts": [{
+            "name": "WEB",
+            "users": [
+                { "name": "browser", "bearer": true, "allow_sub": ["ui.>"] },
+                { "name": "backend" }
+            ]
+        }]
+    }]))
+}
+
+#[tokio::test]
+async fn test_bearer_user_jwt_is_written() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap())?)?
+        .initialize()
+        .await?;
+
+    let jwt_path = result.bearer_jwt("WEB", "browser").unwrap();
+    assert_eq!(jwt_path, output_dir.path().canonicalize()?.join("WEB-browser.jwt"));
+    let jwt = std::fs::read_to_string(jwt_path)?;
+    assert!(std::fs::read_to_string(result.creds("WEB", "browser").unwrap())?.contains(&jwt));
+    assert_eq!(claims(&jwt)?["nats"]["bearer_token"], true);
+
+    assert!(result.bearer_jwt("WEB", "backend").is_none());
+    let creds = std::fs::read_to_string(result.creds("WEB", "backend").unwrap())?;
+    assert!(claims(creds.lines().nth(1).unwrap())?["nats"]
+        .get("bearer_token")
+        .is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bearer_user_jwt_in_memory() -> anyhow::Result<()> {
+    let mut config = config("unused")?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let artifacts = result.artifacts.as_ref().unwrap();
+    let jwt = artifacts.bearer_jwt("WEB", "browser").unwrap();
+    assert_eq!(claims(jwt)?["nats"]["bearer_token"], true);
+    assert!(!format!("{:?}", artifacts).contains(jwt));
+    Ok(())
+}
+
+#[test]
+fn test_bearer_user_rejects_pub_response() -> anyhow::Result<()> {
+    let mut config = config("unused")?;
+    config.servers[0].accounts[0].users[0].allow_pub_response = Some(1);
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("bearer user"), "{}", err);
+    Ok(())
+}
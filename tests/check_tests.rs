@@ -0,0 +1,166 @@
+#![cfg(unix)]
+
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use natsforge::{
+    config::{NatsConfig, VerifyMode},
+    NatsForge, NatsForgeError,
+};
+use serde_json::json;
+use tempfile::TempDir;
+
+/// Stands in for `nats-server -t -c <config>`: rejects configs containing `broken`.
+fn fake_nats_server(dir: &Path) -> anyhow::Result<PathBuf> {
+    let path = dir.join("nats-server");
+    std::fs::write(
+        &path,
+        "#!/bin/sh\n\
+         if grep -q broken \"$3\"; then echo \"nats-server: $3:12:1: unknown field 'broken'\" >&2; exit 1; fi\n\
+         echo \"nats-server: configuration file $3 is valid\"\n",
+    )?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
+
+fn config(dir: &Path, nats_server_path: Option<PathBuf>) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "check-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "nats_server_path": nats_server_path,
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4272,
+                "output_dir": dir.join("hub"),
+                "leafnodes": { "port": 7266 },
+                "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": 4273,
+                "output_dir": dir.join("leaf"),
+                "leafnodes": { "remotes": [{ "url": "nats://localhost:7266", "account": "APP", "user": "app-user" }] }
+            }
+        ]
+    }))?)
+}
+
+fn break_config(path: &Path) -> anyhow::Result<()> {
+    let mut conf = std::fs::read_to_string(path)?;
+    conf.push_str("broken {\n");
+    Ok(std::fs::write(path, conf)?)
+}
+
+#[tokio::test]
+async fn test_check_names_the_rejected_server() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let mut config = config(dir.path(), Some(fake_nats_server(dir.path())?))?;
+    config.verify_configs = VerifyMode::Required;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    assert!(forge.check(&result, true).await?.is_empty());
+
+    break_config(result.server_config("leaf").unwrap())?;
+    let err = forge.check(&result, true).await.unwrap_err();
+    match &err {
+        NatsForgeError::ConfigCheckFailed { server, output } => {
+            assert_eq!(server, "leaf");
+            assert!(output.contains(":12:1: unknown field 'broken'"), "{}", output);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_missing_nats_server() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let missing = dir.path().join("does-not-exist");
+
+    let mut lenient = config(dir.path(), Some(missing.clone()))?;
+    lenient.verify_configs = VerifyMode::IfAvailable;
+    let result = NatsForge::from_config(lenient)?.initialize().await?;
+    assert!(
+        result
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("configured via nats_server_path") && warning.contains("not checked")),
+        "{:?}",
+        result.warnings
+    );
+
+    let mut strict = config(dir.path(), Some(missing))?;
+    strict.verify_configs = VerifyMode::Required;
+    let err = NatsForge::from_config(strict)?.initialize().await.unwrap_err();
+    assert!(matches!(err, NatsForgeError::NatsServerNotFound { .. }), "{:?}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_verify_configs_needs_files() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let mut config = config(dir.path(), None)?;
+    config.verify_configs = VerifyMode::IfAvailable;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("in-memory"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nats_server_rejects_broken_config() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let forge = NatsForge::from_config(config(dir.path(), None)?)?;
+    let result = forge.initialize().await?;
+    forge.check(&result, true).await?;
+
+    break_config(result.server_config("hub").unwrap())?;
+    let err = forge.check(&result, true).await.unwrap_err();
+    assert!(
+        matches!(&err, NatsForgeError::ConfigCheckFailed { server, .. } if server == "hub"),
+        "{:?}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_escaped_config_parses() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    // Quotes, spaces and backslashes in paths are all legal on Unix.
+    let odd_dir = dir.path().join("o'brien \"quoted\" back\\slash");
+    let mut config = config(&odd_dir, None)?;
+    config.servers[0].name = "hub \"east\" O'Brien".to_string();
+    config.servers[0].jetstream.enabled = true;
+    config.servers[0].jetstream.domain = Some("east\\1".to_string());
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    forge.check(&result, true).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_passthrough_config_parses() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let mut config = config(dir.path(), None)?;
+    config.servers[0].extra_blocks = serde_json::from_value(json!({
+        "websocket": { "port": 8258, "no_tls": true, "compression": true },
+        "max_pending": 67108864
+    }))?;
+    config.servers[0].extra_config = Some("# tuned by hand\nmax_traced_msg_len: 1024\n".to_string());
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let conf = std::fs::read_to_string(result.server_config("hub").unwrap())?;
+    assert!(
+        conf.ends_with("# tuned by hand\nmax_traced_msg_len: 1024\n"),
+        "{}",
+        conf
+    );
+    forge.check(&result, true).await?;
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use natsforge::{
+    config::{
+        AccountConfig, BackendKind, Durability, JetStreamConfig, NatsConfig, OperatorConfig, ServerConfig, UserConfig,
+    },
+    NatsForge, NatsForgeError,
+};
+
+fn config(output_dir: &Path) -> NatsConfig {
+    NatsConfig {
+        operator: OperatorConfig {
+            name: "cleanup-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "cleanup-server".to_string(),
+            port: 4222,
+            output_dir: output_dir.to_path_buf(),
+            jetstream: JetStreamConfig {
+                enabled: true,
+                absolute_paths: true,
+                ..Default::default()
+            },
+            accounts: vec![AccountConfig {
+                name: "APP".to_string(),
+                users: vec![UserConfig {
+                    name: "app-user".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        backend: BackendKind::Native,
+        durability: Durability::Fast,
+        write_manifest: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_cleanup_removes_generated_files() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let output_dir = dir.path().join("out");
+    let forge = NatsForge::from_config(config(&output_dir))?;
+    let result = forge.initialize().await?;
+    let creds = result.creds("APP", "app-user").unwrap().to_path_buf();
+    let jetstream = result.jetstream_store_dirs["cleanup-server"].clone();
+    assert!(creds.exists() && jetstream.is_dir());
+    let unrelated = output_dir.join("notes.txt");
+    std::fs::write(&unrelated, "kept")?;
+
+    let report = forge.cleanup(&result, false).await?;
+    assert!(report.removed.contains(&creds), "{:#?}", report);
+    assert!(report.missing.is_empty(), "{:#?}", report);
+    for path in [
+        &creds,
+        &result.servers["cleanup-server"],
+        &result.operator_jwt_paths[0],
+        &jetstream,
+    ] {
+        assert!(!path.exists(), "{} was not removed", path.display());
+    }
+    assert!(!output_dir.join("manifest.json").exists());
+    assert!(unrelated.exists());
+
+    // Whatever is left goes with the output directory.
+    let report = forge.cleanup(&result, true).await?;
+    assert_eq!(report.removed, vec![std::fs::canonicalize(dir.path())?.join("out")]);
+    assert!(!output_dir.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cleanup_refuses_paths_outside_output_dirs() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let output_dir = dir.path().join("out");
+    let forge = NatsForge::from_config(config(&output_dir))?;
+    let mut result = forge.initialize().await?;
+    let outside = dir.path().join("outside.creds");
+    std::fs::write(&outside, "not generated")?;
+
+    // Neither a path elsewhere nor one escaping the output directory through `..` is removed.
+    for path in [outside.clone(), output_dir.join("..").join("outside.creds")] {
+        result
+            .sys_creds_paths
+            .insert("cleanup-server".to_string(), path.clone());
+        let err = forge.cleanup(&result, true).await.unwrap_err();
+        assert!(
+            matches!(&err, NatsForgeError::OutsideOutputDirs { path: refused } if *refused == path),
+            "{}",
+            err
+        );
+    }
+    assert!(outside.exists());
+    // The check comes first, so nothing of the setup was removed either.
+    assert!(result.creds("APP", "app-user").unwrap().exists());
+    assert!(output_dir.exists());
+
+    result.sys_creds_paths.clear();
+
+    result.sys_creds_paths.clear();
+
+    // Nor is the output directory itself, unless asked to.
+    result
+        .resolver_dirs
+        .insert("cleanup-server".to_string(), output_dir.clone());
+    let err = forge.cleanup(&result, false).await.unwrap_err();
+    assert!(matches!(err, NatsForgeError::OutsideOutputDirs { .. }), "{}", err);
+    assert!(result.creds("APP", "app-user").unwrap().exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cleanup_in_memory_removes_nothing() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path());
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    assert_eq!(forge.cleanup(&result, true).await?, Default::default());
+    assert!(dir.path().exists());
+    Ok(())
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "client")]
+
+use std::time::Duration;
+
+use natsforge::{config::NatsConfig, config::SetupResult, NatsForge, NatsForgeError};
+use serde_json::json;
+
+async fn setup() -> anyhow::Result<SetupResult> {
+    let config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "client-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "client-server",
+            "port": 4298,
+            "output_dir": "/srv/client",
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+        }]
+    }))?;
+    Ok(NatsForge::from_config(config)?.initialize().await?)
+}
+
+#[tokio::test]
+async fn test_connect_rejects_unknown_server_and_user() -> anyhow::Result<()> {
+    let result = setup().await?;
+
+    let err = result.connect("other-server", "APP", "app-user").await.unwrap_err();
+    assert!(err.to_string().contains("Unknown server: other-server"), "{}", err);
+
+    let err = result.connect("client-server", "APP", "nobody").await.unwrap_err();
+    assert!(
+        err.to_string().contains("No creds for user nobody in account APP"),
+        "{}",
+        err
+    );
+
+    let err = result
+        .connect("client-server", "BILLING", "app-user")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("account BILLING"), "{}", err);
+
+    let err = result.connect_sys("client-server").await.unwrap_err();
+    assert!(err.to_string().contains("SYS account has no users"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_gives_up_after_retry_duration() -> anyhow::Result<()> {
+    let result = setup().await?;
+    // Nothing listens on the server's port.
+    let err = result
+        .connect_within("client-server", "APP", "app-user", Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, NatsForgeError::Connect { server, .. } if server == "client-server"),
+        "{}",
+        err
+    );
+    Ok(())
+}
@@ -1,3 +1,8 @@
+// Not every test binary that declares `mod common` calls every helper here.
+#![allow(dead_code)]
+
+use natsforge::config::NatsConfig;
+use serde_json::{json, Value};
 use tokio::process::Child;
 
 pub struct ServerGuard(pub Child);
@@ -6,3 +11,14 @@ impl Drop for ServerGuard {
         let _ = self.0.start_kill();
     }
 }
+
+/// A config issuing `servers` with the native backend, without fsyncing what it writes. Tests set
+/// whatever else they exercise on the result.
+pub fn native_config(servers: Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": servers
+    }))?)
+}
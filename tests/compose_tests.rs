@@ -0,0 +1,124 @@
+use std::path::Path;
+
+use natsforge::{
+    compose::ComposeOptions,
+    config::{NatsConfig, SetupResult},
+    NatsForge, NatsForgeError,
+};
+use serde_json::{json, Value};
+
+fn config(hub_dir: &Path, leaf_dir: &Path, remote_url: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "compose-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [
+            {
+                "name": "Hub",
+                "port": 4222,
+                "host": "127.0.0.1",
+                "output_dir": hub_dir,
+                "leafnodes": { "port": 7422 },
+                "monitoring": { "http_port": 8222 },
+                "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": leaf_dir,
+                "leafnodes": { "remotes": [{ "url": remote_url, "account": "APP", "user": "app-user" }] }
+            }
+        ]
+    }))?)
+}
+
+async fn emit(remote_url: &str, options: &ComposeOptions) -> anyhow::Result<(Value, SetupResult, tempfile::TempDir)> {
+    let dir = tempfile::TempDir::new()?;
+    let (hub_dir, leaf_dir) = (dir.path().join("hub"), dir.path().join("leaf"));
+    let forge = NatsForge::from_config(config(&hub_dir, &leaf_dir, remote_url)?)?;
+    let result = forge.initialize().await?;
+    let compose_file = dir.path().join("docker-compose.yml");
+    forge.emit_docker_compose(&result, &compose_file, options)?;
+    let compose: Value = serde_yaml::from_str(&std::fs::read_to_string(&compose_file)?)?;
+    Ok((compose, result, dir))
+}
+
+#[tokio::test]
+async fn test_compose_services_ports_and_mounts() -> anyhow::Result<()> {
+    let (compose, result, _dir) = emit("nats://localhost:7422", &ComposeOptions::default()).await?;
+    let services = compose["services"].as_object().unwrap();
+    assert_eq!(services.keys().collect::<Vec<_>>(), ["hub", "leaf"]);
+
+    let hub = &services["hub"];
+    let hub_dir = result.server_config("Hub").unwrap().parent().unwrap();
+    assert_eq!(hub["image"], "nats:2.10-alpine");
+    assert_eq!(hub["command"], json!(["-c", "/etc/nats/nats.conf"]));
+    assert_eq!(hub["ports"], json!(["4222:4222", "7422:7422", "8222:8222"]));
+    assert_eq!(
+        hub["volumes"],
+        json!([
+            format!("{0}:{0}", hub_dir.display()),
+            format!("{}/nats.compose.conf:/etc/nats/nats.conf", hub_dir.display())
+        ])
+    );
+    assert!(hub.get("depends_on").is_none());
+
+    let leaf = &services["leaf"];
+    assert_eq!(leaf["ports"], json!(["4223:4223"]));
+    assert_eq!(leaf["depends_on"], json!(["hub"]));
+
+    // Containers listen on all interfaces and reach the hub by its service name.
+    let hub_conf = std::fs::read_to_string(hub_dir.join("nats.compose.conf"))?;
+    assert!(!hub_conf.contains("host:"), "{}", hub_conf);
+    let leaf_dir = result.server_config("leaf").unwrap().parent().unwrap();
+    let leaf_conf = std::fs::read_to_string(leaf_dir.join("nats.compose.conf"))?;
+    assert!(leaf_conf.contains("nats://hub:7422"), "{}", leaf_conf);
+    assert!(
+        leaf_conf.contains(&leaf_dir.join("APP-app-user.creds").display().to_string()),
+        "{}",
+        leaf_conf
+    );
+    // The config for running outside of compose is left alone.
+    let nats_conf = std::fs::read_to_string(result.server_config("leaf").unwrap())?;
+    assert!(nats_conf.contains("nats://localhost:7422"), "{}", nats_conf);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compose_hostname_mapping() -> anyhow::Result<()> {
+    let options = ComposeOptions {
+        hostnames: [("hub.internal".to_string(), "hub".to_string())].into(),
+        ..Default::default()
+    };
+    let (compose, result, _dir) = emit("tls://hub.internal:7422", &options).await?;
+    assert_eq!(compose["services"]["leaf"]["depends_on"], json!(["hub"]));
+    let leaf_dir = result.server_config("leaf").unwrap().parent().unwrap();
+    let leaf_conf = std::fs::read_to_string(leaf_dir.join("nats.compose.conf"))?;
+    assert!(leaf_conf.contains("tls://hub:7422"), "{}", leaf_conf);
+
+    // Remotes outside the deployment keep their host.
+    let (compose, result, _dir) = emit("nats://hub.example.com:7422", &ComposeOptions::default()).await?;
+    assert!(compose["services"]["leaf"].get("depends_on").is_none());
+    let leaf_dir = result.server_config("leaf").unwrap().parent().unwrap();
+    let leaf_conf = std::fs::read_to_string(leaf_dir.join("nats.compose.conf"))?;
+    assert!(leaf_conf.contains("nats://hub.example.com:7422"), "{}", leaf_conf);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compose_needs_files_mode() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(&dir.path().join("hub"), &dir.path().join("leaf"), "localhost:7422")?;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let err = forge
+        .emit_docker_compose(
+            &result,
+            &dir.path().join("docker-compose.yml"),
+            &ComposeOptions::default(),
+        )
+        .unwrap_err();
+    assert!(matches!(err, NatsForgeError::InvalidConfig(_)), "{}", err);
+    Ok(())
+}
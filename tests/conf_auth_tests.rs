@@ -0,0 +1,252 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use natsforge::{
+    conf::{ConfObject, ConfValue},
+    config::{ArtifactMode, ConfCredential, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn config(output_dir: &Path, port: u16) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "unused" },
+        "backend": "nsc",
+        "durability": "fast",
+        "servers": [{
+            "name": "dev",
+            "port": port,
+            "output_dir": output_dir,
+            "auth_mode": "conf",
+            "accounts": [
+                {
+                    "name": "APP",
+                    "jetstream": true,
+                    "exports": [
+                        { "subject": "app.events.>" },
+                        { "subject": "app.api", "is_service": true, "response_type": "Stream" }
+                    ],
+                    "users": [
+                        { "name": "worker", "allow_pub": ["app.>"], "deny_pub": ["app.secret.>"], "allow_pub_response": 1 },
+                        { "name": "reader", "conf_credential": "password", "allow_sub": ["app.>", "_INBOX.>"] }
+                    ]
+                },
+                {
+                    "name": "OPS",
+                    "imports": [
+                        { "subject": "app.events.>", "account": "APP", "local_subject": "ops.events.>" },
+                        { "subject": "app.api", "account": "APP", "service": true }
+                    ],
+                    "users": [{ "name": "admin" }]
+                }
+            ]
+        }]
+    }))?)
+}
+
+fn object<'a>(object: &'a ConfObject, key: &str) -> &'a ConfObject {
+    object
+        .get(key)
+        .and_then(ConfValue::as_object)
+        .unwrap_or_else(|| panic!("no {} block", key))
+}
+
+#[tokio::test]
+async fn test_conf_mode_defines_accounts() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    // The nsc backend is never run, so this passes without nsc installed.
+    let forge = NatsForge::from_config(config(dir.path(), 4222)?)?;
+    let result = forge.initialize().await?;
+    let document = forge.server_document("dev")?;
+
+    for key in ["operator", "system_account", "resolver", "resolver_preload"] {
+        assert!(document.root.get(key).is_none(), "{}", document.render());
+    }
+    assert!(result.operator_jwt_paths.is_empty());
+    assert!(result.creds("APP", "worker").is_none());
+
+    let accounts = object(&document.root, "accounts");
+    let app = object(accounts, "APP");
+    assert_eq!(app.get("jetstream"), Some(&ConfValue::Raw("enabled".to_string())));
+    let users = app.get("users").and_then(ConfValue::as_array).unwrap();
+    let worker = users[0].as_object().unwrap();
+    let seed = std::fs::read_to_string(result.user_secret("APP", "worker").unwrap())?;
+    let public_key = nkeys::KeyPair::from_seed(&seed)?.public_key();
+    assert_eq!(
+        worker.get("nkey").and_then(ConfValue::as_str),
+        Some(public_key.as_str())
+    );
+    let permissions = object(worker, "permissions");
+    assert_eq!(
+        object(permissions, "publish"),
+        &ConfObject::new()
+            .with("allow", vec!["app.>"])
+            .with("deny", vec!["app.secret.>"])
+    );
+    assert!(permissions.get("subscribe").is_none());
+    assert_eq!(
+        object(permissions, "allow_responses"),
+        &ConfObject::new().with("max", 1u32)
+    );
+
+    let reader = users[1].as_object().unwrap();
+    assert_eq!(reader.get("user").and_then(ConfValue::as_str), Some("APP-reader"));
+    let password = std::fs::read_to_string(result.user_secret("APP", "reader").unwrap())?;
+    assert_eq!(
+        reader.get("password").and_then(ConfValue::as_str),
+        Some(password.as_str())
+    );
+    assert!(result
+        .user_secret("APP", "reader")
+        .unwrap()
+        .ends_with("APP-reader.password"));
+
+    let rendered = std::fs::read_to_string(result.server_config("dev").unwrap())?;
+    assert!(
+        rendered.contains(
+            "        exports: [
+            { stream: \"app.events.>\" }
+            { service: \"app.api\", response_type: \"stream\" }
+        ]"
+        ),
+        "{}",
+        rendered
+    );
+    assert!(
+        rendered.contains(
+            "        imports: [
+            {
+                stream {
+                    account: \"APP\"
+                    subject: \"app.events.>\"
+                }
+                to: \"ops.events.>\"
+            }"
+        ),
+        "{}",
+        rendered
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conf_mode_in_memory() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/dev"), 4222)?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let artifacts = result.artifacts.as_ref().unwrap();
+    let seed = artifacts.user_secret("OPS", "admin").unwrap();
+    assert!(seed.starts_with("SU"), "{}", seed);
+    assert!(artifacts.server_configs["dev"].contains("accounts {"));
+    assert_eq!(
+        result.user_secret("OPS", "admin"),
+        Some(Path::new("/srv/dev/OPS-admin.nk"))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_user() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path(), 4222)?;
+    let server = &mut config.servers[0];
+    server.accounts.truncate(1);
+    server.accounts[0].users.truncate(1);
+    server.accounts[0].users[0].conf_credential = ConfCredential::Token;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let token = std::fs::read_to_string(result.user_secret("APP", "worker").unwrap())?;
+    let document = forge.server_document("dev")?;
+    let authorization = object(&document.root, "authorization");
+    assert_eq!(
+        authorization.get("token").and_then(ConfValue::as_str),
+        Some(token.as_str())
+    );
+    assert!(authorization.get("permissions").is_some());
+    assert!(object(object(&document.root, "accounts"), "APP").get("users").is_none());
+    Ok(())
+}
+
+#[test]
+fn test_conf_mode_rejects_jwt_only_settings() -> anyhow::Result<()> {
+    let mut config = config(Path::new("unused"), 4222)?;
+    config.servers[0].accounts[0].users[0].bearer = true;
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server dev is in conf mode: user worker in account APP sets bearer, which needs operator mode"),
+        "{}",
+        err
+    );
+
+    let mut config = crate::config(Path::new("unused"), 4222)?;
+    config.servers[0].accounts[1].users[0].conf_credential = ConfCredential::Token;
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("which nats-server only accepts when it is the server's only user"),
+        "{}",
+        err
+    );
+
+    let mut config = crate::config(Path::new("unused"), 4222)?;
+    let mut operator_server = config.servers[0].clone();
+    operator_server.name = "hub".to_string();
+    operator_server.auth_mode = Default::default();
+    config.servers.push(operator_server);
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Servers dev and hub have different auth_modes"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conf_mode_server_enforces_permissions() -> anyhow::Result<()> {
+    let port = 4276;
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path(), port)?)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("dev").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let url = format!("localhost:{}", port);
+    let seed = std::fs::read_to_string(result.user_secret("APP", "worker").unwrap())?;
+    let worker = async_nats::ConnectOptions::with_nkey(seed).connect(&url).await?;
+    let password = std::fs::read_to_string(result.user_secret("APP", "reader").unwrap())?;
+    let reader = async_nats::ConnectOptions::with_user_and_password("APP-reader".to_string(), password)
+        .connect(&url)
+        .await?;
+
+    let mut subscriber = reader.subscribe("app.>").await?;
+    reader.flush().await?;
+    // The deny rule drops the first message; the second one gets through.
+    worker.publish("app.secret.key", "denied".into()).await?;
+    worker.publish("app.status", "allowed".into()).await?;
+    worker.flush().await?;
+
+    let message = tokio::time::timeout(Duration::from_secs(5), subscriber.next())
+        .await?
+        .context("subscription closed")?;
+    assert_eq!(message.subject.as_str(), "app.status");
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use natsforge::conf::{ConfDocument, ConfObject, ConfValue};
+
+#[test]
+fn test_nested_blocks() {
+    let mut root = ConfObject::new()
+        .with("port", 4222u16)
+        .with("server_name", "hub")
+        .with("ping_interval", ConfValue::Duration("2m".to_string()));
+    root.object_mut("jetstream")
+        .set("store_dir", "/data")
+        .set("max_memory_store", 1024i64)
+        .set("limits", ConfObject::new().with("max_ha_assets", 4u32));
+    root.set("operator", "eyJ0eXAi.e30.c2ln");
+    root.set("resolver", ConfValue::Raw("MEMORY".to_string()));
+    root.set("tls", ConfObject::new().with("timeout", 2.5).with("verify", true));
+
+    let expected = "port: 4222
+server_name: \"hub\"
+ping_interval: \"2m\"
+
+jetstream {
+    store_dir: \"/data\"
+    max_memory_store: 1024
+    limits {
+        max_ha_assets: 4
+    }
+}
+
+operator: \"eyJ0eXAi.e30.c2ln\"
+resolver: MEMORY
+
+tls {
+    timeout: 2.5
+    verify: true
+}
+";
+    assert_eq!(ConfDocument::new(root).render(), expected);
+}
+
+#[test]
+fn test_arrays_of_objects() {
+    let mut leafnodes = ConfObject::new();
+    leafnodes.set(
+        "remotes",
+        vec![
+            ConfObject::new()
+                .with("url", "nats://hub:7422")
+                .with("account", "ACCOUNT"),
+            ConfObject::new()
+                .with("url", "tls://hub:7422")
+                .with("deny_imports", vec!["internal.>", "debug.*"])
+                .with("tls", ConfObject::new().with("insecure", true)),
+        ],
+    );
+    let root = ConfObject::new()
+        .with("server_tags", vec!["region:eu", "az:1"])
+        .with("leafnodes", leafnodes)
+        .with("empty", Vec::<ConfValue>::new());
+
+    let expected = "server_tags: [\"region:eu\", \"az:1\"]
+
+leafnodes {
+    remotes: [
+        { url: \"nats://hub:7422\", account: \"ACCOUNT\" }
+        {
+            url: \"tls://hub:7422\"
+            deny_imports: [\"internal.>\", \"debug.*\"]
+            tls {
+                insecure: true
+            }
+        }
+    ]
+}
+
+empty: []
+";
+    assert_eq!(ConfDocument::new(root).render(), expected);
+}
+
+#[test]
+fn test_escaping() {
+    let mappings: ConfObject = [
+        ("orders.\"new\"", "orders.v2"),
+        ("plain_key", "C:\\nats\\data"),
+        ("ACCOUNTID1", "tab\there\nbell\u{7}"),
+    ]
+    .into_iter()
+    .collect();
+    let root = ConfObject::new().with("mappings", mappings);
+
+    let expected = "mappings {
+    \"orders.\\\"new\\\"\": \"orders.v2\"
+    plain_key: \"C:\\\\nats\\\\data\"
+    ACCOUNTID1: \"tab\\there\\nbell\\x07\"
+}
+";
+    assert_eq!(ConfDocument::new(root).render(), expected);
+}
+
+#[test]
+fn test_set_keeps_the_key_in_place() {
+    let mut object = ConfObject::new().with("port", 4222u16).with("server_name", "hub");
+    object.set("port", 4223u16);
+    object.object_mut("leafnodes").set("port", 7422u16);
+    object.object_mut("leafnodes").set("remotes", Vec::<ConfObject>::new());
+
+    let keys: Vec<&str> = object.iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, ["port", "server_name", "leafnodes"]);
+    assert_eq!(object.get("port").and_then(ConfValue::as_i64), Some(4223));
+    assert_eq!(object.get("leafnodes").and_then(ConfValue::as_object).unwrap().len(), 2);
+    assert_eq!(object.remove("server_name"), Some(ConfValue::from("hub")));
+    assert_eq!(object.len(), 2);
+}
+
+#[test]
+fn test_split_leaves_an_include() {
+    let mut document = ConfDocument::new(
+        ConfObject::new()
+            .with("port", 4222u16)
+            .with("tls", ConfObject::new().with("cert_file", "server.pem"))
+            .with("resolver", ConfValue::Raw("MEMORY".to_string())),
+    );
+    let tls = document.split("tls", "tls.conf").unwrap();
+    assert!(document.split("cluster", "cluster.conf").is_none());
+
+    assert_eq!(
+        document.render(),
+        "port: 4222\n\ninclude \"tls.conf\"\n\nresolver: MEMORY\n"
+    );
+    assert_eq!(tls.render(), "tls {\n    cert_file: \"server.pem\"\n}\n");
+    assert_eq!(
+        document.root.get("tls"),
+        Some(&ConfValue::Include("tls.conf".to_string()))
+    );
+}
@@ -0,0 +1,740 @@
+use std::path::PathBuf;
+
+use natsforge::{
+    config::{
+        AccountConfig, BackendKind, ConnectionInfo, LeafNodeConfig, NatsConfig, OperatorConfig, RemoteConfig,
+        ServerConfig, UserConfig,
+    },
+    net, NatsForge,
+};
+
+fn single_server_config(server: ServerConfig) -> NatsConfig {
+    NatsConfig {
+        name: Some("config-test".to_string()),
+        operator: OperatorConfig {
+            name: "test-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![server],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_normalize_ipv6_urls() -> anyhow::Result<()> {
+    assert_eq!(net::normalize_url("[::1]:4222")?, "nats://[::1]:4222");
+    assert_eq!(net::normalize_url("nats://[::1]:4222")?, "nats://[::1]:4222");
+    assert_eq!(net::normalize_url("nats://::1")?, "nats://[::1]");
+    assert_eq!(net::normalize_url("tls://[fe80::1]:7422")?, "tls://[fe80::1]:7422");
+    assert_eq!(
+        net::normalize_url("nats://user:pass@[::1]:4222")?,
+        "nats://user:pass@[::1]:4222"
+    );
+    assert_eq!(net::normalize_url("localhost:4222")?, "nats://localhost:4222");
+    assert_eq!(net::normalize_url("nats://10.0.0.1:7422")?, "nats://10.0.0.1:7422");
+
+    assert!(net::normalize_url("nats://fe80::1::2:4222").is_err());
+    assert!(net::normalize_url("nats://[::1:4222").is_err());
+    assert!(net::normalize_url("nats://[::g]:4222").is_err());
+    assert!(net::normalize_url("nats://[::1]:notaport").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_host_rendering_forms() {
+    assert_eq!(net::host_port("::", 4222), "[::]:4222");
+    assert_eq!(net::host_port("[::1]", 4222), "[::1]:4222");
+    assert_eq!(net::host_port("127.0.0.1", 4222), "127.0.0.1:4222");
+    assert_eq!(net::conf_host("[2001:db8::1]"), "2001:db8::1");
+    assert_eq!(net::conf_host("::"), "::");
+    assert!(net::is_ipv6_literal("[::1]"));
+    assert!(!net::is_ipv6_literal("localhost"));
+}
+
+#[test]
+fn test_connection_info_brackets_ipv6() {
+    let mut server = ServerConfig {
+        name: "v6".to_string(),
+        port: 4222,
+        host: Some("::".to_string()),
+        output_dir: PathBuf::from("unused"),
+        ..Default::default()
+    };
+    assert_eq!(ConnectionInfo::for_server(&server).url, "nats://[::1]:4222");
+
+    server.host = Some("[2001:db8::5]".to_string());
+    assert_eq!(ConnectionInfo::for_server(&server).url, "nats://[2001:db8::5]:4222");
+
+    server.host = Some("0.0.0.0".to_string());
+    assert_eq!(ConnectionInfo::for_server(&server).url, "nats://127.0.0.1:4222");
+
+    server.host = None;
+    assert_eq!(ConnectionInfo::for_server(&server).url, "nats://localhost:4222");
+}
+
+#[test]
+fn test_validate_source_networks() -> anyhow::Result<()> {
+    for cidr in ["10.8.0.0/16", "127.0.0.1/32", "0.0.0.0/0", "2001:db8::/32", "::1/128"] {
+        assert!(net::validate_cidr(cidr).is_ok(), "{} should be accepted", cidr);
+    }
+    for cidr in [
+        "10.8.0.0",
+        "10.8.0.0/33",
+        "::1/129",
+        "10.8.0/16",
+        "10.8.0.0/+8",
+        "vpn/8",
+        "",
+    ] {
+        assert!(net::validate_cidr(cidr).is_err(), "{} should be rejected", cidr);
+    }
+
+    let config = single_server_config(ServerConfig {
+        name: "ops".to_string(),
+        port: 4222,
+        accounts: vec![AccountConfig {
+            name: "OPS".to_string(),
+            users: vec![UserConfig {
+                name: "oncall".to_string(),
+                source_networks: vec!["10.8.0.0/16".to_string(), "10.9.0.0/40".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("user oncall") && err.contains("10.9.0.0/40"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_validate_rejects_malformed_ipv6() {
+    for host in ["::", "[::1]", "fe80::1", "0.0.0.0", "nats.example.com"] {
+        let config = single_server_config(ServerConfig {
+            name: "ok".to_string(),
+            port: 4222,
+            host: Some(host.to_string()),
+            ..Default::default()
+        });
+        assert!(config.validate().is_ok(), "{} should be accepted", host);
+    }
+
+    for host in [":::1", "[::1", "fe80::zz", "[10.0.0.1]"] {
+        let config = single_server_config(ServerConfig {
+            name: "bad".to_string(),
+            port: 4222,
+            host: Some(host.to_string()),
+            ..Default::default()
+        });
+        assert!(config.validate().is_err(), "{} should be rejected", host);
+    }
+
+    let config = single_server_config(ServerConfig {
+        name: "leaf".to_string(),
+        port: 4222,
+        leafnodes: LeafNodeConfig {
+            remotes: vec![RemoteConfig {
+                url: "nats://[fe80::1::2]:7422".to_string(),
+                account: "APP".to_string(),
+                credentials: "APP-user.creds".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    assert!(config.validate().is_err());
+}
+
+fn capped_server(output_dir: PathBuf, account_limits: &[i32]) -> ServerConfig {
+    ServerConfig {
+        name: "capped".to_string(),
+        port: 4222,
+        output_dir,
+        max_connections: Some(10),
+        max_subscriptions: Some(100),
+        max_payload: Some(65536),
+        max_control_line: Some(8192),
+        write_deadline: Some("10s".to_string()),
+        ping_interval: Some("1m30s".to_string()),
+        ping_max: Some(3),
+        accounts: account_limits
+            .iter()
+            .enumerate()
+            .map(|(i, limit)| AccountConfig {
+                name: format!("APP{}", i),
+                max_connections: Some(*limit),
+                users: vec![UserConfig {
+                    name: "user".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_warns_when_account_limits_exceed_server_cap() {
+    let config = single_server_config(capped_server(PathBuf::from("unused"), &[4, 6]));
+    assert!(config.warnings().is_empty());
+
+    let config = single_server_config(capped_server(PathBuf::from("unused"), &[4, 6, 1, -1]));
+    let warnings = config.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(
+        warnings[0].contains("capped") && warnings[0].contains("11"),
+        "{}",
+        warnings[0]
+    );
+}
+
+#[test]
+fn test_validate_rejects_bad_hostnames() {
+    for host in ["localhost", "nats-1.internal", "10.0.0.1", "host.example.com."] {
+        assert!(net::validate_host(host).is_ok(), "{} should be accepted", host);
+    }
+    for host in [
+        "10.0.0.256",
+        "1.2.3",
+        "bad host",
+        "-nats.example.com",
+        "nats..example.com",
+        "nats_1",
+    ] {
+        assert!(net::validate_host(host).is_err(), "{} should be rejected", host);
+    }
+
+    for advertise in [
+        "203.0.113.7:4222",
+        "nats.example.com",
+        "[2001:db8::1]:4222",
+        "2001:db8::1",
+    ] {
+        assert!(
+            net::validate_advertise(advertise).is_ok(),
+            "{} should be accepted",
+            advertise
+        );
+    }
+    for advertise in [
+        "203.0.113.7:",
+        "nats.example.com:70000",
+        "[2001:db8::1]4222",
+        "2001:db8::1]:4222",
+    ] {
+        assert!(
+            net::validate_advertise(advertise).is_err(),
+            "{} should be rejected",
+            advertise
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_advertise_addresses_rendered() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "natted",
+        "host": "10.0.0.5",
+        "port": 4222,
+        "client_advertise": "203.0.113.7:4222",
+        "output_dir": "unused",
+        "leafnodes": { "port": 7422, "advertise": "203.0.113.7:7422" },
+        "cluster": { "name": "edge", "port": 6222, "advertise": "10.0.0.5:6222" }
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["natted"];
+    for line in [
+        "host: \"10.0.0.5\"\nport: 4222\nserver_name: \"natted\"\nclient_advertise: \"203.0.113.7:4222\"\n",
+        "leafnodes {\n    port: 7422\n    advertise: \"203.0.113.7:7422\"\n}\n",
+        "    advertise: \"10.0.0.5:6222\"\n",
+    ] {
+        assert!(conf.contains(line), "{:?} missing from {}", line, conf);
+    }
+
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "natted",
+        "port": 4222,
+        "client_advertise": "nats.example.com:99999",
+        "output_dir": "unused"
+    }))?);
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid client_advertise for server natted"),
+        "{}",
+        err
+    );
+    config.servers[0].client_advertise = None;
+    config.servers[0].leafnodes.advertise = Some("203.0.113.7:7422".to_string());
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("has no leafnode port"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_caps_rendered() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let mut config = single_server_config(capped_server(output_dir.path().to_path_buf(), &[20]));
+    config.backend = BackendKind::Native;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    assert_eq!(result.warnings.len(), 1);
+
+    let conf = std::fs::read_to_string(result.server_config("capped").unwrap())?;
+    assert!(conf.contains("\nmax_connections: 10\n"), "{}", conf);
+    assert!(conf.contains("\nmax_subscriptions: 100\n"), "{}", conf);
+    assert!(conf.contains("\nmax_payload: 65536\n"), "{}", conf);
+    assert!(conf.contains("\nmax_control_line: 8192\n"), "{}", conf);
+    assert!(conf.contains("\nwrite_deadline: \"10s\"\n"), "{}", conf);
+    assert!(conf.contains("\nping_interval: \"1m30s\"\n"), "{}", conf);
+    assert!(conf.contains("\nping_max: 3\n"), "{}", conf);
+
+    let mut config = single_server_config(ServerConfig {
+        name: "uncapped".to_string(),
+        port: 4222,
+        output_dir: output_dir.path().join("uncapped"),
+        ..Default::default()
+    });
+    config.backend = BackendKind::Native;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let conf = std::fs::read_to_string(result.server_config("uncapped").unwrap())?;
+    for option in [
+        "max_connections",
+        "max_control_line",
+        "write_deadline",
+        "ping_interval",
+        "ping_max",
+    ] {
+        assert!(!conf.contains(option), "{} in {}", option, conf);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_server_limits_validation() -> anyhow::Result<()> {
+    let mut config = single_server_config(capped_server(PathBuf::from("unused"), &[4]));
+    config.servers[0].ping_interval = Some("2 minutes".to_string());
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid ping_interval for server capped"),
+        "{}",
+        err
+    );
+
+    for duration in ["1.5s", "0", ".5m", "1h0.25m"] {
+        let mut config = single_server_config(capped_server(PathBuf::from("unused"), &[4]));
+        config.servers[0].ping_interval = Some(duration.to_string());
+        config.validate()?;
+    }
+    for duration in ["1..5s", ".s", "1.5"] {
+        let mut config = single_server_config(capped_server(PathBuf::from("unused"), &[4]));
+        config.servers[0].ping_interval = Some(duration.to_string());
+        assert!(config.validate().is_err(), "{}", duration);
+    }
+
+    let mut config = single_server_config(capped_server(PathBuf::from("unused"), &[4]));
+    config.servers[0].accounts[0].max_message_payload = Some(65536);
+    config.validate()?;
+    config.servers[0].accounts[0].max_message_payload = Some(1048576);
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Account APP0 allows a max_message_payload of 1048576, over the max_payload of 65536"),
+        "{}",
+        err
+    );
+    config.servers[0].accounts[0].max_message_payload = Some(-1);
+    config.validate()?;
+    // max_payload is the account's total data limit, which may well exceed a single message.
+    config.servers[0].accounts[0].max_payload = Some(10 * 1048576);
+    config.validate()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_monitoring_and_metadata_rendered() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "observed",
+        "port": 4222,
+        "output_dir": "unused",
+        "monitoring": {
+            "http_port": 8222,
+            "https_port": 8223,
+            "tls": { "cert_file": "server.pem", "key_file": "server-key.pem" }
+        },
+        "pid_file": "/var/run/nats.pid",
+        "ports_file_dir": "/var/run",
+        "server_tags": ["region:eu", "az:1"]
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["observed"];
+    for line in [
+        "\npid_file: \"/var/run/nats.pid\"\n",
+        "\nports_file_dir: \"/var/run\"\n",
+        "\nserver_tags: [\"region:eu\", \"az:1\"]\n",
+        "\nhttp_port: 8222\n",
+        "\nhttps_port: 8223\n",
+        "\ntls {\n    cert_file: \"server.pem\"\n",
+    ] {
+        assert!(conf.contains(line), "{:?} missing from {}", line, conf);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_logging_and_lame_duck_rendered() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "logged",
+        "port": 4222,
+        "output_dir": "/srv/nats",
+        "logging": { "logfile": "logs/nats.log", "size_limit": 10485760, "debug": true },
+        "lame_duck_duration": "2m",
+        "lame_duck_grace_period": "10s"
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["logged"];
+    let expected = "logfile: \"/srv/nats/logs/nats.log\"\nlogfile_size_limit: 10485760\ndebug: true\ntrace: false\n\
+                    logtime: true\nlame_duck_duration: \"2m\"\nlame_duck_grace_period: \"10s\"\n";
+    assert!(conf.contains(expected), "{}", conf);
+
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "quiet",
+        "port": 4222,
+        "output_dir": "unused",
+        "logging": { "logfile": "/var/log/nats.log", "logtime": false }
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["quiet"];
+    assert!(
+        conf.contains("\nlogfile: \"/var/log/nats.log\"\ndebug: false\ntrace: false\nlogtime: false\n"),
+        "{}",
+        conf
+    );
+    assert!(
+        !conf.contains("logfile_size_limit") && !conf.contains("lame_duck"),
+        "{}",
+        conf
+    );
+    Ok(())
+}
+
+#[test]
+fn test_logging_and_lame_duck_validation() -> anyhow::Result<()> {
+    let server = |options: serde_json::Value| -> anyhow::Result<NatsConfig> {
+        let mut server = serde_json::json!({ "name": "draining", "port": 4222, "output_dir": "unused" });
+        server
+            .as_object_mut()
+            .unwrap()
+            .extend(options.as_object().unwrap().clone());
+        Ok(single_server_config(serde_json::from_value(server)?))
+    };
+
+    let err = server(serde_json::json!({ "lame_duck_duration": "30s", "lame_duck_grace_period": "30s" }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("must be shorter than its lame_duck_duration"),
+        "{}",
+        err
+    );
+    let err = server(serde_json::json!({ "lame_duck_duration": "two minutes" }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Invalid lame_duck_duration for server draining"),
+        "{}",
+        err
+    );
+    let err = server(serde_json::json!({ "logging": { "size_limit": 0 } }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("logfile size_limit of 0"), "{}", err);
+
+    let config = server(serde_json::json!({ "logging": { "pid_in_log": false } }))?;
+    config.validate()?;
+    let warnings = config.warnings();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("always logs its process ID"), "{}", warnings[0]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mutual_tls_rendered() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "mtls",
+        "port": 4222,
+        "output_dir": "unused",
+        "tls": {
+            "cert_file": "server.pem",
+            "key_file": "server-key.pem",
+            "ca_file": "ca.pem",
+            "verify": true,
+            "verify_and_map": true,
+            "timeout": 2.5,
+            "cipher_suites": ["TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384", "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"],
+            "curve_preferences": ["X25519", "CurveP256"]
+        }
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["mtls"];
+    let expected = "tls {\n    cert_file: \"server.pem\"\n    key_file: \"server-key.pem\"\n    ca_file: \"ca.pem\"\n    \
+                    verify: true\n    verify_and_map: true\n    timeout: 2.5\n    \
+                    cipher_suites: [\"TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384\", \"TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384\"]\n    \
+                    curve_preferences: [\"X25519\", \"CurveP256\"]\n}\n";
+    assert!(conf.contains(expected), "{}", conf);
+    Ok(())
+}
+
+#[test]
+fn test_mutual_tls_validation() -> anyhow::Result<()> {
+    let server = |tls: serde_json::Value| -> anyhow::Result<NatsConfig> {
+        let mut tls_config = serde_json::json!({ "cert_file": "server.pem", "key_file": "server-key.pem" });
+        tls_config
+            .as_object_mut()
+            .unwrap()
+            .extend(tls.as_object().unwrap().clone());
+        Ok(single_server_config(serde_json::from_value(serde_json::json!({
+            "name": "mtls",
+            "port": 4222,
+            "output_dir": "unused",
+            "tls": tls_config
+        }))?))
+    };
+
+    server(serde_json::json!({}))?.validate()?;
+    server(serde_json::json!({ "ca_file": "ca.pem", "verify": true }))?.validate()?;
+    for (tls, message) in [
+        (
+            serde_json::json!({ "verify": true }),
+            "TLS of server mtls: verify needs a ca_file",
+        ),
+        (
+            serde_json::json!({ "ca_file": "ca.pem", "verify_and_map": true }),
+            "verify_and_map needs verify",
+        ),
+        (
+            serde_json::json!({ "timeout": 0.0 }),
+            "timeout 0 is not a positive number",
+        ),
+    ] {
+        let err = server(tls)?.validate().unwrap_err();
+        assert!(err.to_string().contains(message), "{}", err);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_server_ports_must_differ() -> anyhow::Result<()> {
+    let server = |options: serde_json::Value| -> anyhow::Result<NatsConfig> {
+        let mut server = serde_json::json!({ "name": "busy", "port": 4222, "output_dir": "unused" });
+        server
+            .as_object_mut()
+            .unwrap()
+            .extend(options.as_object().unwrap().clone());
+        Ok(single_server_config(serde_json::from_value(server)?))
+    };
+
+    server(serde_json::json!({ "monitoring": { "http_port": 8222 }, "leafnodes": { "port": 7422 } }))?.validate()?;
+    let err = server(serde_json::json!({ "monitoring": { "http_port": 7422 }, "leafnodes": { "port": 7422 } }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server busy uses port 7422 for both leafnodes and HTTP monitoring"),
+        "{}",
+        err
+    );
+
+    let err = server(serde_json::json!({ "monitoring": { "https_port": 8223 } }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("no TLS certificate"), "{}", err);
+    let both = serde_json::json!({
+        "tls": { "cert_file": "a.pem", "key_file": "a-key.pem" },
+        "monitoring": { "https_port": 8223, "tls": { "cert_file": "b.pem", "key_file": "b-key.pem" } }
+    });
+    let err = server(both)?.validate().unwrap_err();
+    assert!(err.to_string().contains("one certificate for both"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weighted_server_mappings_rendering() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "canary",
+        "port": 4222,
+        "output_dir": "/srv/nats",
+        "mappings": {
+            "orders.new": "orders.v2.new",
+            "api.>": [{ "dest": "api.stable.>", "weight": 90 }, { "dest": "api.canary.>", "weight": 10 }]
+        }
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["canary"];
+    let expected = "mappings {
+    \"api.>\": [
+        { destination: \"api.stable.>\", weight: 90% }
+        { destination: \"api.canary.>\", weight: 10% }
+    ]
+    \"orders.new\": \"orders.v2.new\"
+}
+";
+    assert!(conf.contains(expected), "{}", conf);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_quoted_values_escaped() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "edge \"west\" hub",
+        "port": 4222,
+        "output_dir": "/srv/nats\\edge",
+        "jetstream": { "enabled": true, "domain": "o'brien" },
+        "logging": { "logfile": "/var/log/nats \"edge\".log" },
+        "tls": { "cert_file": "C:\\certs\\server cert.pem", "key_file": "C:\\certs\\key.pem" },
+        "mappings": { "orders.\"quoted\"": "orders.plain" }
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["edge \"west\" hub"];
+    for line in [
+        "server_name: \"edge \\\"west\\\" hub\"\n",
+        "logfile: \"/var/log/nats \\\"edge\\\".log\"\n",
+        "domain: \"o'brien\"\n",
+        "cert_file: \"C:\\\\certs\\\\server cert.pem\"\n",
+        "    \"orders.\\\"quoted\\\"\": \"orders.plain\"\n",
+    ] {
+        assert!(conf.contains(line), "{:?} missing from {}", line, conf);
+    }
+    assert!(
+        conf.contains("store_dir: \"/srv/nats\\\\edge/jetstream\"\n"),
+        "{}",
+        conf
+    );
+    Ok(())
+}
+
+#[test]
+fn test_validate_rejects_control_characters_in_names() -> anyhow::Result<()> {
+    let server = |name: &str, account: &str| -> anyhow::Result<ServerConfig> {
+        Ok(serde_json::from_value(serde_json::json!({
+            "name": name,
+            "port": 4222,
+            "output_dir": "unused",
+            "accounts": [{ "name": account, "users": [{ "name": "app-user" }] }]
+        }))?)
+    };
+    single_server_config(server("with space's \"and quotes\"", "APP")?).validate()?;
+
+    let err = single_server_config(server("two\nlines", "APP")?)
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server name \"two\\nlines\" contains a control character"),
+        "{}",
+        err
+    );
+    let err = single_server_config(server("hub", "APP\t")?).validate().unwrap_err();
+    assert!(err.to_string().contains("Server hub: Account name"), "{}", err);
+
+    let mut config = single_server_config(server("hub", "APP")?);
+    config.servers[0].accounts[0].users[0].name = "bell\u{7}".to_string();
+    assert!(config.validate().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_passthrough_rendered_last() -> anyhow::Result<()> {
+    let mut config = single_server_config(serde_json::from_value(serde_json::json!({
+        "name": "tuned",
+        "port": 4222,
+        "output_dir": "/srv/nats",
+        "jetstream": { "enabled": true },
+        "extra_blocks": {
+            "websocket": { "port": 8080, "no_tls": true, "allowed_origins": ["https://app.example.com"] },
+            "max_pending": 67108864
+        },
+        "extra_config": "# tuned by hand\nmax_traced_msg_len: 1024"
+    }))?);
+    config.backend = BackendKind::Native;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["tuned"];
+    let expected = "resolver: MEMORY
+
+resolver_preload {
+";
+    let resolver = conf.find(expected).unwrap_or_else(|| panic!("{}", conf));
+    let extra = "
+max_pending: 67108864
+
+websocket {
+    allowed_origins: [\"https://app.example.com\"]
+    no_tls: true
+    port: 8080
+}
+
+# tuned by hand
+max_traced_msg_len: 1024
+";
+    assert!(conf.ends_with(extra), "{}", conf);
+    assert!(resolver < conf.len() - extra.len(), "{}", conf);
+    Ok(())
+}
+
+#[test]
+fn test_passthrough_cannot_set_generated_options() -> anyhow::Result<()> {
+    let server = |extra: serde_json::Value| -> anyhow::Result<NatsConfig> {
+        let mut server = serde_json::json!({ "name": "tuned", "port": 4222, "output_dir": "unused" });
+        server
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        Ok(single_server_config(serde_json::from_value(server)?))
+    };
+
+    let err = server(serde_json::json!({ "extra_blocks": { "resolver": { "type": "full" } } }))?
+        .validate()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid configuration: Server tuned: extra_blocks sets resolver, which natsforge generates"
+    );
+    let err = server(serde_json::json!({ "extra_config": "debug: true\noperator = \"eyJ0eXAi\"\n" }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("extra_config sets debug"), "{}", err);
+    let err = server(serde_json::json!({ "extra_blocks": { "websocket": { "port": null } } }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("extra_blocks websocket: null"), "{}", err);
+
+    // Options inside a block of its own are not top-level ones.
+    server(serde_json::json!({ "extra_config": "websocket {\n    port: 8080\n    no_tls: true\n}\n" }))?.validate()?;
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use natsforge::{
+    config::{
+        AccountConfig, BackendKind, Durability, JetStreamConfig, NatsConfig, OperatorConfig, ServerConfig, UserConfig,
+    },
+    diff::{Change, UserChange},
+    NatsForge,
+};
+
+fn server(name: &str, port: u16, output_dir: &Path) -> ServerConfig {
+    ServerConfig {
+        name: name.to_string(),
+        port,
+        output_dir: output_dir.join(name),
+        jetstream: JetStreamConfig {
+            enabled: true,
+            max_storage: Some(1_073_741_824),
+            ..Default::default()
+        },
+        accounts: vec![AccountConfig {
+            name: format!("APP-{}", name),
+            users: vec![UserConfig {
+                name: "app-user".to_string(),
+                allow_pubsub: vec!["app.>".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn config(servers: Vec<ServerConfig>) -> NatsConfig {
+    NatsConfig {
+        operator: OperatorConfig {
+            name: "diff-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers,
+        backend: BackendKind::Native,
+        durability: Durability::Fast,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_diff_of_an_unchanged_config_is_empty() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let servers = || vec![server("east", 4222, dir.path()), server("west", 4223, dir.path())];
+    NatsForge::from_config(config(servers()))?.initialize().await?;
+
+    let report = NatsForge::from_config(config(servers()))?.diff(dir.path()).await?;
+    assert!(report.is_empty(), "{}", report);
+    assert_eq!(report.to_string(), "No changes\n");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_diff_reports_an_added_user() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    NatsForge::from_config(config(vec![server("east", 4222, dir.path())]))?
+        .initialize()
+        .await?;
+
+    let mut changed = server("east", 4222, dir.path());
+    changed.accounts[0].users.push(UserConfig {
+        name: "new-user".to_string(),
+        ..Default::default()
+    });
+    let report = NatsForge::from_config(config(vec![changed]))?.diff(dir.path()).await?;
+
+    assert_eq!(
+        report.users,
+        vec![UserChange {
+            account: "APP-east".to_string(),
+            user: "new-user".to_string(),
+            change: Change::Added,
+            claims: vec![],
+        }]
+    );
+    assert!(report.accounts.is_empty(), "{}", report);
+    assert!(report.files.is_empty(), "{}", report);
+    assert!(
+        report.to_string().contains("Users:\n  + APP-east/new-user\n"),
+        "{}",
+        report
+    );
+    let json: serde_json::Value = serde_json::from_str(&report.to_json())?;
+    assert_eq!(json["users"][0]["change"], "added");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_diff_shows_a_changed_jetstream_limit_as_one_line() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    NatsForge::from_config(config(vec![server("east", 4222, dir.path())]))?
+        .initialize()
+        .await?;
+
+    let mut changed = server("east", 4222, dir.path());
+    changed.jetstream.max_storage = Some(2_147_483_648);
+    changed.accounts[0].users[0].allow_pubsub.push("orders.>".to_string());
+    let report = NatsForge::from_config(config(vec![changed]))?.diff(dir.path()).await?;
+
+    assert_eq!(report.files.len(), 1, "{}", report);
+    let file = &report.files[0];
+    assert_eq!(file.server, "east");
+    assert!(file.path.ends_with("east/nats.conf"), "{}", file.path.display());
+    let changed_lines: Vec<&str> = file
+        .diff
+        .lines()
+        .filter(|line| {
+            (line.starts_with('-') || line.starts_with('+')) && !line.starts_with("---") && !line.starts_with("+++")
+        })
+        .collect();
+    assert_eq!(
+        changed_lines,
+        vec!["-    max_file_store: 1073741824", "+    max_file_store: 2147483648"]
+    );
+    assert!(file.diff.contains("@@ -"), "{}", file.diff);
+    // The user's JWT changed with its permissions; the account's didn't.
+    assert!(report.accounts.is_empty(), "{}", report);
+    assert_eq!(report.users.len(), 1, "{}", report);
+    assert_eq!(report.users[0].change, Change::Modified);
+    assert!(
+        report.users[0].claims.contains(&"nats.pub".to_string()),
+        "{:?}",
+        report.users[0]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_diff_reports_added_and_removed_servers() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    NatsForge::from_config(config(vec![server("east", 4222, dir.path())]))?
+        .initialize()
+        .await?;
+
+    let servers = vec![server("west", 4223, dir.path())];
+    let report = NatsForge::from_config(config(servers))?.diff(dir.path()).await?;
+
+    assert_eq!(report.added_servers, vec!["west"]);
+    assert_eq!(report.removed_servers, vec!["east"]);
+    let accounts: Vec<(&str, Change)> = report
+        .accounts
+        .iter()
+        .map(|account| (account.account.as_str(), account.change))
+        .collect();
+    assert_eq!(accounts, vec![("APP-west", Change::Added)]);
+    Ok(())
+}
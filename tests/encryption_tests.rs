@@ -0,0 +1,188 @@
+#![cfg(feature = "encryption")]
+
+use std::path::Path;
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use natsforge::{
+    config::NatsConfig,
+    encryption::{decrypt_with_identity, decrypt_with_passphrase, generate_identity},
+    kubernetes::K8sOptions,
+    NatsForge, NatsForgeError,
+};
+use serde_json::{json, Value};
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn config(hub_dir: &Path, leaf_dir: &Path, port: u16, encryption: Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "encryption-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "encryption": encryption,
+        "servers": [
+            {
+                "name": "hub",
+                "port": port,
+                "output_dir": hub_dir,
+                "leafnodes": { "port": 7265 },
+                "accounts": [{
+                    "name": "APP",
+                    "users": [{ "name": "app-user" }, { "name": "browser", "bearer": true }]
+                }]
+            },
+            {
+                "name": "leaf",
+                "port": port + 1,
+                "output_dir": leaf_dir,
+                "leafnodes": { "remotes": [{
+                    "url": "nats://localhost:7265",
+                    "account": "APP",
+                    "user": "app-user",
+                    "decrypted_credentials": "/run/secrets/APP-app-user.creds"
+                }] }
+            }
+        ]
+    }))?)
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_creds_encrypted_to_recipients() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let (identity, recipient) = generate_identity();
+    let (hub_dir, leaf_dir) = (dir.path().join("hub"), dir.path().join("leaf"));
+    let forge = NatsForge::from_config(config(&hub_dir, &leaf_dir, 4222, json!({ "recipients": [recipient] }))?)?;
+    let result = forge.initialize().await?;
+    assert!(result.creds_encrypted);
+    assert!(result.manifest().creds_encrypted);
+
+    let creds_path = result.creds("APP", "app-user").unwrap();
+    assert_eq!(creds_path.file_name().unwrap(), "APP-app-user.creds.age");
+    let encrypted = std::fs::read(creds_path)?;
+    assert!(encrypted.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----"));
+    let creds = decrypt_with_identity(&encrypted, &identity)?;
+    assert!(creds.contains("BEGIN USER NKEY SEED"), "{}", creds);
+    let bearer_jwt = decrypt_with_identity(&std::fs::read(result.bearer_jwt("APP", "browser").unwrap())?, &identity)?;
+    assert!(bearer_jwt.starts_with("eyJ"));
+
+    // Another identity can't decrypt.
+    let (other_identity, _) = generate_identity();
+    assert!(decrypt_with_identity(&encrypted, &other_identity).is_err());
+
+    // No plaintext creds are left, and the leaf expects its creds where they are decrypted to.
+    for output_dir in [&hub_dir, &leaf_dir] {
+        for entry in std::fs::read_dir(output_dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            assert!(!name.ends_with(".creds"), "{}", name);
+        }
+    }
+    assert_eq!(
+        decrypt_with_identity(&std::fs::read(leaf_dir.join("APP-app-user.creds.age"))?, &identity)?,
+        creds
+    );
+    let leaf_conf = std::fs::read_to_string(result.server_config("leaf").unwrap())?;
+    assert!(
+        leaf_conf.contains("\"/run/secrets/APP-app-user.creds\""),
+        "{}",
+        leaf_conf
+    );
+
+    // What needs the plaintext says so instead of reading ciphertext.
+    let err = result.connect("hub", "APP", "app-user").await.unwrap_err();
+    assert!(matches!(err, NatsForgeError::InvalidConfig(_)), "{}", err);
+    let err = forge.emit_kubernetes(&result, K8sOptions::default()).unwrap_err();
+    assert!(matches!(err, NatsForgeError::InvalidConfig(_)), "{}", err);
+    let err = forge.rotate_user("APP", "app-user", false).await.unwrap_err();
+    assert!(matches!(err, NatsForgeError::InvalidConfig(_)), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_creds_encrypted_with_passphrase() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    std::env::set_var("NATSFORGE_TEST_CREDS_PASSPHRASE", "correct horse battery staple");
+    let config = config(
+        &dir.path().join("hub"),
+        &dir.path().join("leaf"),
+        4222,
+        json!({ "passphrase_env": "NATSFORGE_TEST_CREDS_PASSPHRASE" }),
+    )?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let encrypted = std::fs::read(result.creds("APP", "app-user").unwrap())?;
+    let creds = decrypt_with_passphrase(&encrypted, "correct horse battery staple")?;
+    assert!(creds.contains("BEGIN USER NKEY SEED"), "{}", creds);
+    assert!(decrypt_with_passphrase(&encrypted, "wrong").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_encryption_config_rejected() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let (hub_dir, leaf_dir) = (dir.path().join("hub"), dir.path().join("leaf"));
+    let (_, recipient) = generate_identity();
+
+    let mut missing_path = config(&hub_dir, &leaf_dir, 4222, json!({ "recipients": [recipient] }))?;
+    missing_path.servers[1].leafnodes.remotes[0].decrypted_credentials = None;
+    let err = missing_path.validate().unwrap_err();
+    assert!(err.to_string().contains("decrypted_credentials"), "{}", err);
+
+    let mut without_encryption = config(&hub_dir, &leaf_dir, 4222, json!(null))?;
+    assert!(without_encryption.validate().is_err());
+    without_encryption.servers[1].leafnodes.remotes[0].decrypted_credentials = None;
+    without_encryption.validate()?;
+
+    let both = config(
+        &hub_dir,
+        &leaf_dir,
+        4222,
+        json!({ "recipients": [recipient], "passphrase_env": "HOME" }),
+    )?;
+    assert!(both.validate().is_err());
+
+    let bad_recipient = config(&hub_dir, &leaf_dir, 4222, json!({ "recipients": ["age1nope"] }))?;
+    let err = NatsForge::from_config(bad_recipient)?.initialize().await.unwrap_err();
+    assert!(err.to_string().contains("age1nope"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_decrypted_creds_connect() -> anyhow::Result<()> {
+    let port = 4270;
+    let dir = tempfile::TempDir::new()?;
+    let (identity, recipient) = generate_identity();
+    let config = config(
+        &dir.path().join("hub"),
+        &dir.path().join("leaf"),
+        port,
+        json!({ "recipients": [recipient] }),
+    )?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("hub").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = decrypt_with_identity(&std::fs::read(result.creds("APP", "app-user").unwrap())?, &identity)?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+    let mut sub = client.subscribe("app.hello").await?;
+    client.publish("app.hello", "decrypted".into()).await?;
+    client.flush().await?;
+    let msg = tokio::time::timeout(tokio::time::Duration::from_secs(2), sub.next())
+        .await?
+        .context("No message received")?;
+    assert_eq!(msg.payload, "decrypted");
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use natsforge::{
+    config::{AccountConfig, BackendKind, ExportConfig, ImportConfig, NatsConfig, OperatorConfig, ServerConfig},
+    NatsForge, NatsForgeError,
+};
+
+fn account(name: &str, imports_from: &[&str]) -> AccountConfig {
+    AccountConfig {
+        name: name.to_string(),
+        exports: vec![ExportConfig {
+            subject: format!("{}.events", name.to_lowercase()),
+            is_service: false,
+            ..Default::default()
+        }],
+        imports: imports_from
+            .iter()
+            .map(|src| ImportConfig {
+                subject: format!("{}.events", src.to_lowercase()),
+                account: src.to_string(),
+                local_subject: None,
+                service: false,
+                share: false,
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn config(accounts: Vec<AccountConfig>, output_dir: &Path) -> NatsConfig {
+    NatsConfig {
+        operator: OperatorConfig {
+            name: "error-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "error-server".to_string(),
+            port: 4222,
+            output_dir: output_dir.to_path_buf(),
+            accounts,
+            ..Default::default()
+        }],
+        backend: BackendKind::Native,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_unknown_import_account() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(config(vec![account("APP", &["MISSING"])], output_dir.path()))?;
+    match forge.initialize().await {
+        Err(NatsForgeError::UnknownImportAccount {
+            account,
+            importer,
+            server,
+            suggestion,
+        }) => {
+            assert_eq!(account, "MISSING");
+            assert_eq!(importer, "APP");
+            assert_eq!(server, "error-server");
+            assert_eq!(suggestion, None);
+        }
+        other => panic!("expected UnknownImportAccount, got {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_unknown_import_account_suggestion() {
+    let accounts = vec![account("ORDERS", &[]), account("APP", &["ORDRES"])];
+    let err = config(accounts, Path::new("unused")).validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "account APP on server error-server imports from unknown account ORDRES; did you mean ORDERS?"
+    );
+}
+
+#[tokio::test]
+async fn test_circular_dependency() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let mut accounts = vec![
+        account("APP", &["BILLING"]),
+        account("BILLING", &["LEDGER"]),
+        account("LEDGER", &["APP"]),
+        account("C", &["APP"]),
+    ];
+    accounts[1].exports[0].private = true;
+    let forge = NatsForge::from_config(config(accounts, output_dir.path()))?;
+    match forge.initialize().await {
+        Err(err @ NatsForgeError::CircularDependency { .. }) => assert_eq!(
+            err.to_string(),
+            "circular import dependency: BILLING -> APP -> LEDGER -> BILLING"
+        ),
+        other => panic!("expected CircularDependency, got {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_missing_config_file() {
+    match NatsForge::from_json_file("does-not-exist.json", None) {
+        Err(NatsForgeError::Io { path, .. }) => assert_eq!(path, Path::new("does-not-exist.json")),
+        Err(other) => panic!("expected Io, got {:?}", other),
+        Ok(_) => panic!("expected Io, got a forge"),
+    }
+}
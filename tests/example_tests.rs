@@ -0,0 +1,79 @@
+//! Runs the programs in `examples/` and checks what they generate.
+
+use std::{collections::BTreeSet, path::Path};
+
+#[allow(dead_code)]
+#[path = "../examples/basic.rs"]
+mod basic;
+#[allow(dead_code)]
+#[path = "../examples/hub_leaf.rs"]
+mod hub_leaf;
+
+fn files(dir: &Path) -> anyhow::Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            names.insert(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    Ok(names)
+}
+
+fn set(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_basic_example() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = basic::run(output_dir.path()).await?;
+
+    assert_eq!(
+        files(output_dir.path())?,
+        set(&[
+            "operator.jwt",
+            "APP.jwt",
+            "APP-publisher.creds",
+            "APP-subscriber.creds",
+            "nats.conf"
+        ])
+    );
+    assert_eq!(result.account("APP").unwrap().creds.len(), 2);
+    assert_eq!(result.connections[0].url, "nats://localhost:4222");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hub_leaf_example() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = hub_leaf::run(output_dir.path()).await?;
+
+    assert_eq!(
+        files(&output_dir.path().join("hub"))?,
+        set(&["operator.jwt", "EDGE.jwt", "EDGE-leaf.creds", "nats.conf"])
+    );
+    assert_eq!(
+        files(&output_dir.path().join("leaf"))?,
+        set(&["operator.jwt", "EDGE.jwt", "EDGE-leaf.creds", "nats.conf"])
+    );
+    let leaf_conf = std::fs::read_to_string(result.server_config("leaf").unwrap())?;
+    assert!(leaf_conf.contains("nats://localhost:7422"), "{}", leaf_conf);
+    assert!(leaf_conf.contains("EDGE-leaf.creds"), "{}", leaf_conf);
+
+    let edge_id = result.account_id("EDGE").unwrap();
+    assert_eq!(edge_id, result.account("EDGE").unwrap().id);
+    assert!(
+        leaf_conf.contains(&format!("account: \"{}\"", edge_id)),
+        "{}",
+        leaf_conf
+    );
+    let sys_id = result.account_id("SYS").unwrap();
+    assert_eq!(sys_id.len(), 56);
+    assert!(
+        leaf_conf.contains(&format!("system_account: \"{}\"", sys_id)),
+        "{}",
+        leaf_conf
+    );
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use base64::Engine;
+use natsforge::{
+    config::{
+        AccountConfig, BackendKind, ExportConfig, ImportConfig, NatsConfig, OperatorConfig, ServerConfig, UserConfig,
+    },
+    expand::Grant,
+    NatsForge,
+};
+
+fn service_config(output_dir: PathBuf, grant_responses: bool) -> NatsConfig {
+    NatsConfig {
+        operator: OperatorConfig {
+            name: "grant-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "grant-server".to_string(),
+            port: 4222,
+            output_dir,
+            accounts: vec![
+                AccountConfig {
+                    name: "SVC".to_string(),
+                    users: vec![
+                        UserConfig {
+                            name: "responder".to_string(),
+                            allow_sub: vec!["svc.>".to_string()],
+                            allow_pub: vec!["svc.status".to_string()],
+                            ..Default::default()
+                        },
+                        UserConfig {
+                            name: "unrestricted".to_string(),
+                            ..Default::default()
+                        },
+                    ],
+                    exports: vec![ExportConfig {
+                        subject: "svc.echo".to_string(),
+                        is_service: true,
+                        grant_responses,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                AccountConfig {
+                    name: "CLIENT".to_string(),
+                    users: vec![
+                        UserConfig {
+                            name: "requester".to_string(),
+                            allow_pub: vec!["client.>".to_string()],
+                            allow_sub: vec!["_INBOX.>".to_string()],
+                            ..Default::default()
+                        },
+                        UserConfig {
+                            name: "muted".to_string(),
+                            deny_pub: vec!["echo".to_string()],
+                            ..Default::default()
+                        },
+                        UserConfig {
+                            name: "deaf".to_string(),
+                            allow_sub: vec!["client.>".to_string()],
+                            ..Default::default()
+                        },
+                    ],
+                    imports: vec![ImportConfig {
+                        subject: "svc.echo".to_string(),
+                        account: "SVC".to_string(),
+                        local_subject: Some("echo".to_string()),
+                        service: true,
+                        share: false,
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }],
+        backend: BackendKind::Native,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_derive_grants_for_service_export() {
+    let expansion = service_config(PathBuf::from("unused"), true).derive_grants();
+
+    let grants: Vec<(&str, &str, &Grant)> = expansion
+        .grants
+        .iter()
+        .map(|g| (g.account.as_str(), g.user.as_str(), &g.grant))
+        .collect();
+    assert_eq!(
+        grants,
+        vec![
+            ("SVC", "responder", &Grant::PubResponse),
+            ("CLIENT", "requester", &Grant::Publish("echo".to_string())),
+        ]
+    );
+
+    assert_eq!(expansion.warnings.len(), 2, "{:?}", expansion.warnings);
+    assert!(expansion.warnings[0].contains("muted") && expansion.warnings[0].contains("denied publishing"));
+    assert!(expansion.warnings[1].contains("deaf") && expansion.warnings[1].contains("_INBOX.>"));
+}
+
+#[test]
+fn test_no_grants_without_flag() {
+    let expansion = service_config(PathBuf::from("unused"), false).derive_grants();
+    assert!(expansion.grants.is_empty());
+    assert!(expansion.warnings.is_empty());
+}
+
+#[tokio::test]
+async fn test_derived_grants_in_user_jwts() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(service_config(output_dir.path().to_path_buf(), true))?;
+    let result = forge.initialize().await?;
+    assert_eq!(result.warnings.len(), 2);
+
+    let claims = |account: &str, user: &str| -> anyhow::Result<serde_json::Value> {
+        let path = result.creds(account, user).unwrap();
+        let creds = std::fs::read_to_string(path)?;
+        let jwt = creds.lines().nth(1).unwrap();
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+        Ok(serde_json::from_slice(&payload)?)
+    };
+
+    assert_eq!(claims("SVC", "responder")?["nats"]["resp"]["max"], 1);
+    assert!(claims("SVC", "unrestricted")?["nats"].get("resp").is_none());
+    let requester_pub = &claims("CLIENT", "requester")?["nats"]["pub"]["allow"];
+    assert!(requester_pub.as_array().unwrap().contains(&serde_json::json!("echo")));
+    Ok(())
+}
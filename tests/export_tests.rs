@@ -0,0 +1,221 @@
+use base64::Engine;
+use natsforge::{config::NatsConfig, NatsForge};
+use serde_json::json;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(output_dir: &str, export: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "export-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "export-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [{ "name": "API", "users": [{ "name": "echo" }], "exports": [export] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_service_export_settings() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let export = json!({
+        "subject": "api.*.echo",
+        "is_service": true,
+        "response_type": "Stream",
+        "description": "Echoes requests back, per tenant",
+        "account_token_position": 2
+    });
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap(), export)?)?
+        .initialize()
+        .await?;
+
+    let account = result.account("API").unwrap();
+    let claims = claims(&std::fs::read_to_string(&account.jwt_path)?)?;
+    let export = &claims["nats"]["exports"][0];
+    assert_eq!(export["subject"], "api.*.echo");
+    assert_eq!(export["type"], "service");
+    assert_eq!(export["response_type"], "Stream");
+    assert_eq!(export["description"], "Echoes requests back, per tenant");
+    assert_eq!(export["account_token_position"], 2);
+    Ok(())
+}
+
+#[test]
+fn test_export_settings_validation() -> anyhow::Result<()> {
+    let lowercase = json!({ "subject": "api.echo", "is_service": true, "response_type": "chunked" });
+    config("unused", lowercase)?.validate()?;
+
+    for (export, message) in [
+        (
+            json!({ "subject": "api.*.echo", "is_service": true, "account_token_position": 3 }),
+            "account_token_position 3 must point at a * token",
+        ),
+        (
+            json!({ "subject": "api.*.echo", "is_service": true, "account_token_position": 0 }),
+            "account_token_position 0",
+        ),
+        (
+            json!({ "subject": "events.>", "response_type": "Stream" }),
+            "response_type only applies to service exports",
+        ),
+    ] {
+        let err = config("unused", export)?.validate().unwrap_err().to_string();
+        assert!(err.contains(message), "{}", err);
+    }
+    Ok(())
+}
+
+fn private_config(output_dir: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "private-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "private-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [
+                {
+                    "name": "FEED",
+                    "users": [{ "name": "publisher" }],
+                    "exports": [{ "subject": "prices.>", "private": true }]
+                },
+                {
+                    "name": "DESK",
+                    "users": [{ "name": "trader" }],
+                    "imports": [{ "subject": "prices.fx", "account": "FEED" }]
+                }
+            ]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_private_export_import_carries_activation() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(private_config(output_dir.path().to_str().unwrap())?)?
+        .initialize()
+        .await?;
+
+    let feed = result.account("FEED").unwrap();
+    let desk = result.account("DESK").unwrap();
+    let feed_claims = claims(&std::fs::read_to_string(&feed.jwt_path)?)?;
+    assert_eq!(feed_claims["nats"]["exports"][0]["token_req"], true);
+
+    let import = &claims(&std::fs::read_to_string(&desk.jwt_path)?)?["nats"]["imports"][0];
+    let token = import["token"].as_str().unwrap();
+    let activation = claims(token)?;
+    assert_eq!(activation["iss"], feed.id.as_str());
+    assert_eq!(activation["sub"], desk.id.as_str());
+    assert_eq!(activation["nats"]["subject"], "prices.fx");
+    assert_eq!(activation["nats"]["type"], "activation");
+
+    let token_path = result.activation("DESK", "prices.fx").unwrap();
+    assert_eq!(
+        token_path,
+        output_dir.path().canonicalize()?.join("DESK-import-0.activation.jwt")
+    );
+    assert_eq!(std::fs::read_to_string(token_path)?, token);
+    assert!(result.account_graph().edges[0].private);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_public_export_import_has_no_activation() -> anyhow::Result<()> {
+    let mut config = private_config("unused")?;
+    config.artifact_mode = natsforge::config::ArtifactMode::InMemory;
+    config.servers[0].accounts[0].exports[0].private = false;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let artifacts = result.artifacts.as_ref().unwrap();
+    assert!(artifacts.activation("DESK", "prices.fx").is_none());
+    let import = &claims(&artifacts.account_jwts["DESK"])?["nats"]["imports"][0];
+    assert!(import.get("token").is_none());
+    assert!(!result.account_graph().edges[0].private);
+    Ok(())
+}
+
+fn latency_config(output_dir: &str, export: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "latency-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "latency-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [
+                { "name": "API", "users": [{ "name": "echo" }], "exports": [export] },
+                {
+                    "name": "CLIENT",
+                    "users": [{ "name": "caller" }],
+                    "imports": [{ "subject": "api.echo", "account": "API", "service": true, "share": true }]
+                }
+            ]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_export_latency_sampling() -> anyhow::Result<()> {
+    for (sampling, expected) in [(json!(25), json!(25)), (json!("headers"), json!("headers"))] {
+        let output_dir = tempfile::TempDir::new()?;
+        let export = json!({
+            "subject": "api.echo",
+            "is_service": true,
+            "latency_subject": "latency.api.echo",
+            "latency_sampling": sampling
+        });
+        let result = NatsForge::from_config(latency_config(output_dir.path().to_str().unwrap(), export)?)?
+            .initialize()
+            .await?;
+
+        let api = claims(&std::fs::read_to_string(&result.account("API").unwrap().jwt_path)?)?;
+        assert_eq!(
+            api["nats"]["exports"][0]["service_latency"],
+            json!({ "sampling": expected, "results": "latency.api.echo" })
+        );
+        let client = claims(&std::fs::read_to_string(&result.account("CLIENT").unwrap().jwt_path)?)?;
+        assert_eq!(client["nats"]["imports"][0]["share"], true);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_export_latency_validation() -> anyhow::Result<()> {
+    let defaulted = json!({ "subject": "api.echo", "is_service": true, "latency_subject": "latency.echo" });
+    latency_config("unused", defaulted)?.validate()?;
+
+    for (export, message) in [
+        (
+            json!({ "subject": "api.echo", "latency_subject": "latency.echo" }),
+            "latency_subject only applies to service exports",
+        ),
+        (
+            json!({ "subject": "api.echo", "is_service": true, "latency_subject": "latency.echo", "latency_sampling": 0 }),
+            "latency_sampling 0 is not between 1 and 100",
+        ),
+        (
+            json!({ "subject": "api.echo", "is_service": true, "latency_subject": "latency.echo", "latency_sampling": 101 }),
+            "latency_sampling 101",
+        ),
+        (
+            json!({ "subject": "api.echo", "is_service": true, "latency_sampling": 50 }),
+            "latency_sampling needs a latency_subject",
+        ),
+    ] {
+        let err = latency_config("unused", export)?.validate().unwrap_err().to_string();
+        assert!(err.contains(message), "{}", err);
+    }
+
+    let unknown =
+        json!({ "subject": "api.echo", "is_service": true, "latency_subject": "l", "latency_sampling": "all" });
+    assert!(latency_config("unused", unknown).is_err());
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use natsforge::{config::NatsConfig, NatsForgeError};
+
+fn user_names(config: &NatsConfig, server: usize, account: &str) -> Vec<String> {
+    let account = config.servers[server]
+        .accounts
+        .iter()
+        .find(|candidate| candidate.name == account)
+        .unwrap();
+    account.users.iter().map(|user| user.name.clone()).collect()
+}
+
+#[test]
+fn test_prod_overlay_merges_over_base() -> anyhow::Result<()> {
+    let config = NatsConfig::from_json_file("tests/prod-overlay.json", None)?;
+
+    assert_eq!(config.name.as_deref(), Some("shop-prod"));
+    assert_eq!(config.operator.name, "shop-operator");
+    assert!(config.extends.is_empty());
+    let servers: Vec<(&str, u16)> = config
+        .servers
+        .iter()
+        .map(|server| (server.name.as_str(), server.port))
+        .collect();
+    assert_eq!(servers, vec![("hub", 14222), ("edge", 4223)]);
+
+    let hub = &config.servers[0];
+    let tls = hub.tls.as_ref().expect("the overlay adds TLS");
+    assert_eq!(tls.cert_file, "/etc/nats/tls/hub.pem");
+    assert!(hub.jetstream.enabled, "the base's JetStream settings stay");
+    assert_eq!(hub.jetstream.max_storage, Some(107_374_182_400));
+    // Tags are not named, so the overlay's list replaces the base's.
+    assert_eq!(hub.server_tags, vec!["region:eu-west"]);
+
+    assert_eq!(user_names(&config, 0, "ORDERS"), vec!["order-service", "order-auditor"]);
+    let orders = &hub.accounts[0];
+    assert_eq!(orders.users[0].allow_pubsub, vec!["orders.>"]);
+    assert_eq!(orders.users[1].allow_sub, vec!["orders.>"]);
+    assert_eq!(orders.exports.len(), 1);
+    assert_eq!(user_names(&config, 0, "BILLING"), vec!["billing-service"]);
+    assert_eq!(user_names(&config, 1, "EDGE"), vec!["sensor"]);
+    Ok(())
+}
+
+#[test]
+fn test_base_loads_on_its_own() -> anyhow::Result<()> {
+    let config = NatsConfig::from_json_file("tests/base.json", None)?;
+
+    assert_eq!(config.name.as_deref(), Some("shop"));
+    assert_eq!(config.servers[0].port, 4222);
+    assert!(config.servers[0].tls.is_none());
+    assert_eq!(user_names(&config, 0, "ORDERS"), vec!["order-service"]);
+    Ok(())
+}
+
+#[test]
+fn test_later_files_win() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let write = |name: &str, json: &str| std::fs::write(dir.path().join(name), json);
+    std::fs::create_dir(dir.path().join("bases"))?;
+    write(
+        "bases/one.json",
+        r#"{ "name": "one", "nsc_retries": 1, "concurrency": 1 }"#,
+    )?;
+    write("bases/two.json", r#"{ "name": "two", "nsc_retries": 2 }"#)?;
+    write(
+        "env.json",
+        r#"{ "extends": ["bases/one.json", "bases/two.json"], "name": "env" }"#,
+    )?;
+
+    let config = NatsConfig::from_json_file(dir.path().join("env.json"), None)?;
+    assert_eq!(config.name.as_deref(), Some("env"));
+    assert_eq!(config.nsc_retries, Some(2));
+    assert_eq!(config.concurrency, Some(1));
+    Ok(())
+}
+
+#[test]
+fn test_extends_cycle_is_reported_with_its_chain() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let path = |name: &str| dir.path().join(name);
+    std::fs::write(path("a.json"), r#"{ "extends": ["b.json"] }"#)?;
+    std::fs::write(path("b.json"), r#"{ "extends": ["c.json"] }"#)?;
+    std::fs::write(path("c.json"), r#"{ "extends": ["b.json"] }"#)?;
+
+    let err = NatsConfig::from_json_file(path("a.json"), None).unwrap_err();
+    let NatsForgeError::ExtendsCycle { chain } = &err else {
+        panic!("expected ExtendsCycle, got {:?}", err);
+    };
+    let names: Vec<&str> = chain
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["b.json", "c.json", "b.json"]);
+    assert!(chain.iter().all(|path| path.is_absolute()));
+    assert!(err.to_string().contains("b.json -> "), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_missing_base_names_the_file() {
+    let err = NatsConfig::from_json_file(Path::new("tests/does-not-exist.json"), None).unwrap_err();
+    assert!(
+        matches!(&err, NatsForgeError::Io { path, .. } if path.ends_with("does-not-exist.json")),
+        "{:?}",
+        err
+    );
+}
@@ -0,0 +1,191 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Context;
+use base64::Engine;
+use natsforge::{
+    config::{ArtifactMode, BackendKind, NatsConfig},
+    error::NatsForgeError,
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn subject(jwt: &str) -> anyhow::Result<String> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.trim().split('.').nth(1).unwrap())?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload)?;
+    Ok(claims["sub"].as_str().unwrap().to_string())
+}
+
+fn config(operator: &str, account: &str, port: u16, output_dir: &Path) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": operator, "reuse_existing": false },
+        "backend": "native",
+        "naming": "exact",
+        "durability": "fast",
+        "servers": [{
+            "name": "hub",
+            "port": port,
+            "output_dir": output_dir,
+            "accounts": [{ "name": account, "users": [{ "name": "app-user" }] }]
+        }]
+    }))?)
+}
+
+/// Initializes a separate operator and returns the path of the `PARTNER` account JWT and a
+/// user's creds path.
+async fn partner_account(dir: &Path) -> anyhow::Result<(std::path::PathBuf, std::path::PathBuf)> {
+    let result = NatsForge::from_config(config("partner-operator", "PARTNER", 4222, dir)?)?
+        .initialize()
+        .await?;
+    let artifacts = &result.account_jwts["PARTNER"];
+    Ok((artifacts.jwt_path.clone(), artifacts.creds["app-user"].clone()))
+}
+
+#[tokio::test]
+async fn test_external_account_errors() -> anyhow::Result<()> {
+    let partner_dir = tempfile::TempDir::new()?;
+    let (partner_jwt, partner_creds) = partner_account(partner_dir.path()).await?;
+    let dir = tempfile::TempDir::new()?;
+
+    let hosting = |external_account| -> anyhow::Result<NatsConfig> {
+        let mut config = config("external-operator", "APP", 4222, dir.path())?;
+        config.servers[0].external_accounts = vec![external_account];
+        Ok(config)
+    };
+
+    let external_account = partner_dir.path().join("missing.jwt");
+    let err = NatsForge::from_config(hosting(external_account)?)?
+        .initialize()
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, NatsForgeError::Io { path, .. } if path.ends_with("missing.jwt")),
+        "{}",
+        err
+    );
+
+    let not_a_jwt = partner_dir.path().join("not-a-jwt.jwt");
+    std::fs::write(&not_a_jwt, "hello")?;
+    let external_account = not_a_jwt;
+    let err = NatsForge::from_config(hosting(external_account)?)?
+        .initialize()
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, NatsForgeError::NotAnAccountJwt { .. }), "{}", err);
+
+    // A creds file holds a user JWT, which decodes but is no account.
+    let user_jwt = partner_dir.path().join("user.jwt");
+    let creds = std::fs::read_to_string(&partner_creds)?;
+    std::fs::write(&user_jwt, creds.lines().nth(1).unwrap())?;
+    let external_account = user_jwt;
+    let err = NatsForge::from_config(hosting(external_account)?)?
+        .initialize()
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().ends_with("is not an account JWT: it is a user JWT"),
+        "{}",
+        err
+    );
+
+    let err = NatsForge::from_config(hosting(partner_jwt)?)?
+        .initialize()
+        .await
+        .unwrap_err();
+    assert!(matches!(&err, NatsForgeError::ExternalIssuerMismatch { .. }), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_external_account_from_allowed_issuer() -> anyhow::Result<()> {
+    let partner_dir = tempfile::TempDir::new()?;
+    let (partner_jwt_path, _) = partner_account(partner_dir.path()).await?;
+    let partner_jwt = std::fs::read_to_string(&partner_jwt_path)?;
+    let partner_operator = std::fs::read_to_string(partner_dir.path().join("operator.jwt"))?;
+    let issuer = subject(&partner_operator)?;
+
+    let mut config = config("external-operator", "APP", 4222, Path::new("/srv/hub"))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    config.servers[0].external_accounts = vec![partner_jwt_path];
+    config.servers[0].external_account_issuers = vec![issuer];
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let partner_id = result.account_id("PARTNER").unwrap();
+    let nats_conf = &result.artifacts.as_ref().unwrap().server_configs["hub"];
+    assert!(
+        nats_conf.contains(&format!("{}: \"{}\"", partner_id, partner_jwt.trim())),
+        "{}",
+        nats_conf
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_accepts_external_account() -> anyhow::Result<()> {
+    let port = 4278;
+    let store_dir = tempfile::TempDir::new()?;
+    let first_dir = tempfile::TempDir::new()?;
+    let second_dir = tempfile::TempDir::new()?;
+    let nsc_config = |account: &str, output_dir: &Path, reuse_existing: bool| -> anyhow::Result<NatsConfig> {
+        let mut config = config("shared-operator", account, port, output_dir)?;
+        config.backend = BackendKind::Nsc;
+        config.nsc_store_dir = Some(store_dir.path().to_path_buf());
+        config.operator.reuse_existing = reuse_existing;
+        Ok(config)
+    };
+
+    let first = NatsForge::from_config(nsc_config("PARTNER", first_dir.path(), false)?)?
+        .initialize()
+        .await?;
+    let mut config = nsc_config("APP", second_dir.path(), true)?;
+    config.servers[0].external_accounts = vec![first.account_jwts["PARTNER"].jwt_path.clone()];
+    let second = NatsForge::from_config(config)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(second.server_config("hub").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let client = async_nats::ConnectOptions::with_credentials_file(first.creds("PARTNER", "app-user").unwrap())
+        .await?
+        .connect(format!("localhost:{}", port))
+        .await?;
+    client.flush().await?;
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[test]
+fn test_external_account_validation() -> anyhow::Result<()> {
+    let mut config = config("external-operator", "APP", 4222, Path::new("/srv/hub"))?;
+    config.servers[0].external_account_issuers = vec!["not-a-key".to_string()];
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("External account issuer not-a-key of server hub is not an operator public key"),
+        "{}",
+        err
+    );
+
+    let mut config = crate::config("external-operator", "APP", 4222, Path::new("/srv/hub"))?;
+    config.servers[0].external_accounts = vec!["/srv/partner/PARTNER.jwt".into()];
+    config.servers[0].resolver =
+        serde_json::from_value(json!({ "url": "https://accounts.example.com/jwt/v1/accounts/" }))?;
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server hub sets external_accounts with a URL resolver; push them to the account server instead"),
+        "{}",
+        err
+    );
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use natsforge::{config::NatsConfig, NatsForge};
+use serde_json::json;
+
+fn config(gateway: serde_json::Value, cluster: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "gateway-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "eu-1",
+            "port": 4222,
+            "output_dir": "eu-1",
+            "cluster": cluster,
+            "gateway": gateway,
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_gateway_block() -> anyhow::Result<()> {
+    let gateway = json!({
+        "name": "eu",
+        "port": 7222,
+        "reject_unknown_cluster": true,
+        "tls": { "cert_file": "gw.pem", "key_file": "gw-key.pem", "ca_file": "ca.pem" },
+        "gateways": [
+            { "name": "eu", "urls": ["nats://localhost:7222"] },
+            { "name": "us", "urls": ["us-1.internal:7222", "nats://us-2.internal:7222"] }
+        ]
+    });
+    let result = NatsForge::from_config(config(gateway, json!({ "name": "eu", "port": 6222 }))?)?
+        .initialize()
+        .await?;
+
+    let server_config = &result.artifacts.as_ref().unwrap().server_configs["eu-1"];
+    let expected = "gateway {
+    name: \"eu\"
+    port: 7222
+    reject_unknown_cluster: true
+    tls {
+        cert_file: \"gw.pem\"
+        key_file: \"gw-key.pem\"
+        ca_file: \"ca.pem\"
+    }
+    gateways: [
+        {
+            name: \"eu\"
+            urls: [\"nats://localhost:7222\"]
+        }
+        {
+            name: \"us\"
+            urls: [\"nats://us-1.internal:7222\", \"nats://us-2.internal:7222\"]
+        }
+    ]
+}
+";
+    assert!(server_config.contains(expected), "{}", server_config);
+    Ok(())
+}
+
+#[test]
+fn test_gateway_validation() -> anyhow::Result<()> {
+    let remote = json!([{ "name": "us", "urls": ["us-1.internal:7222"] }]);
+    config(json!({ "name": "eu", "port": 7222, "gateways": remote }), json!(null))?.validate()?;
+
+    for (gateway, cluster, message) in [
+        (
+            json!({ "name": "eu", "port": 7222 }),
+            json!({ "name": "europe", "port": 6222 }),
+            "Gateway of server eu-1: name eu must match the cluster name europe",
+        ),
+        (
+            json!({ "name": "eu", "port": 6222 }),
+            json!({ "name": "eu", "port": 6222 }),
+            "uses port 6222 for both routes and gateways",
+        ),
+        (
+            json!({ "name": "eu", "port": 7222, "gateways": [{ "name": "us", "urls": [] }] }),
+            json!(null),
+            "gateway us needs at least one URL",
+        ),
+        (
+            json!({ "name": "eu", "port": 7222, "gateways": [remote[0], remote[0]] }),
+            json!(null),
+            "gateway us is listed twice",
+        ),
+    ] {
+        let err = config(gateway, cluster)?.validate().unwrap_err().to_string();
+        assert!(err.contains(message), "{}", err);
+    }
+    Ok(())
+}
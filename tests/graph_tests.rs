@@ -0,0 +1,204 @@
+use natsforge::{
+    config::NatsConfig,
+    graph::{AccountGraph, ImportKind},
+    NatsForge,
+};
+
+fn chain_config(output_dir: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "operator": { "name": "graph-operator", "reuse_existing": false },
+        "backend": "native",
+        "servers": [
+            {
+                "name": "upstream",
+                "port": 4222,
+                "output_dir": format!("{}/upstream", output_dir),
+                "accounts": [
+                    {
+                        "name": "SOURCE",
+                        "users": [{ "name": "producer" }],
+                        "exports": [{ "subject": "raw.>", "is_service": false }]
+                    },
+                    {
+                        "name": "ENRICH",
+                        "users": [{ "name": "worker" }],
+                        "exports": [{ "subject": "enrich.lookup", "is_service": true }],
+                        "imports": [{ "subject": "raw.>", "account": "SOURCE" }]
+                    }
+                ]
+            },
+            {
+                "name": "downstream",
+                "port": 4223,
+                "output_dir": format!("{}/downstream", output_dir),
+                "accounts": [
+                    {
+                        "name": "SINK",
+                        "users": [{ "name": "consumer" }],
+                        "imports": [{ "subject": "enrich.lookup", "account": "ENRICH", "service": true }]
+                    }
+                ]
+            }
+        ]
+    }))?)
+}
+
+/// Checks that `dot` is a digraph made only of `a [..];`, `a -> b [..];` and `key=value;` statements.
+fn assert_dot_grammar(dot: &str) {
+    let mut tokens = Vec::new();
+    let mut chars = dot.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '"' => {
+                let mut escaped = false;
+                loop {
+                    match chars.next().expect("unterminated string") {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+                tokens.push("ID".to_string());
+            }
+            '-' => {
+                assert_eq!(chars.next(), Some('>'), "dangling '-'");
+                tokens.push("->".to_string());
+            }
+            '{' | '}' | '[' | ']' | ';' | ',' | '=' => tokens.push(c.to_string()),
+            c if c.is_alphanumeric() || c == '_' => {
+                while chars.peek().is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                    chars.next();
+                }
+                tokens.push("ID".to_string());
+            }
+            other => panic!("unexpected character {:?} in DOT output", other),
+        }
+    }
+
+    assert!(dot.starts_with("digraph "));
+    let collected: Vec<&str> = tokens.iter().map(String::as_str).collect();
+    let expect = |i: &mut usize, token: &str| {
+        assert_eq!(collected.get(*i).copied(), Some(token), "at token {}", i);
+        *i += 1;
+    };
+    let mut i = 0;
+    expect(&mut i, "ID");
+    expect(&mut i, "ID");
+    expect(&mut i, "{");
+    while collected.get(i) != Some(&"}") {
+        expect(&mut i, "ID");
+        match collected.get(i).copied() {
+            Some("=") => {
+                i += 1;
+                expect(&mut i, "ID");
+            }
+            Some("->") => {
+                i += 1;
+                expect(&mut i, "ID");
+                expect(&mut i, "[");
+            }
+            Some("[") => i += 1,
+            other => panic!("unexpected {:?} at token {}", other, i),
+        }
+        if collected[i - 1] == "[" {
+            loop {
+                expect(&mut i, "ID");
+                expect(&mut i, "=");
+                expect(&mut i, "ID");
+                match collected.get(i).copied() {
+                    Some(",") => i += 1,
+                    Some("]") => {
+                        i += 1;
+                        break;
+                    }
+                    other => panic!("unexpected {:?} in attribute list at token {}", other, i),
+                }
+            }
+        }
+        expect(&mut i, ";");
+    }
+    assert_eq!(i + 1, collected.len(), "trailing tokens after closing brace");
+}
+
+fn edge<'a>(graph: &'a AccountGraph, exporter: &str, importer: &str) -> &'a natsforge::graph::ImportEdge {
+    graph
+        .edges
+        .iter()
+        .find(|e| e.exporter == exporter && e.importer == importer)
+        .unwrap_or_else(|| panic!("no edge {} -> {}", exporter, importer))
+}
+
+#[test]
+fn test_hub_leaf_graph() -> anyhow::Result<()> {
+    let config = serde_json::from_str::<NatsConfig>(include_str!("hub_leaf.json"))?;
+    let graph = config.account_graph();
+
+    assert_eq!(graph.nodes.len(), 2);
+    assert!(graph.edges.is_empty());
+    assert!(graph
+        .nodes
+        .iter()
+        .all(|n| n.id.is_none() && n.servers == ["central-hub"]));
+    assert_dot_grammar(&graph.to_dot());
+    Ok(())
+}
+
+#[test]
+fn test_chain_graph() -> anyhow::Result<()> {
+    let graph = chain_config("unused")?.account_graph();
+
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.edges.len(), 2);
+    let raw = edge(&graph, "SOURCE", "ENRICH");
+    assert_eq!(raw.kind, ImportKind::Stream);
+    assert!(!raw.cross_server && !raw.in_cycle && !raw.private);
+    let lookup = edge(&graph, "ENRICH", "SINK");
+    assert_eq!(lookup.kind, ImportKind::Service);
+    assert!(lookup.cross_server);
+
+    let dot = graph.to_dot();
+    assert_dot_grammar(&dot);
+    assert!(dot.contains("\"ENRICH\" -> \"SINK\" [label=\"enrich.lookup (service)\\ncross-server\", style=dashed];"));
+
+    let json: serde_json::Value = serde_json::from_str(&graph.to_json())?;
+    assert_eq!(json["nodes"].as_array().unwrap().len(), 3);
+    assert_eq!(json["edges"][1]["kind"], "service");
+    Ok(())
+}
+
+#[test]
+fn test_cycle_edges_are_marked() -> anyhow::Result<()> {
+    let mut config = chain_config("unused")?;
+    config.servers[0].accounts[0]
+        .imports
+        .push(serde_json::from_value(serde_json::json!({
+            "subject": "enrich.lookup", "account": "ENRICH", "service": true
+        }))?);
+    let graph = config.account_graph();
+
+    assert!(edge(&graph, "SOURCE", "ENRICH").in_cycle);
+    assert!(edge(&graph, "ENRICH", "SOURCE").in_cycle);
+    assert!(!edge(&graph, "ENRICH", "SINK").in_cycle);
+    let dot = graph.to_dot();
+    assert_dot_grammar(&dot);
+    assert_eq!(dot.matches(", color=red").count(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_setup_result_graph_has_ids() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = chain_config(output_dir.path().to_str().unwrap())?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let graph = result.account_graph();
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(graph.edges.len(), 2);
+    for node in &graph.nodes {
+        assert_eq!(node.id.as_deref(), result.account_id(&node.name));
+        assert!(node.id.is_some());
+    }
+    assert_dot_grammar(&graph.to_dot());
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use base64::Engine;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+#[tokio::test]
+async fn test_service_import_is_remapped_once() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "import-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "import-server",
+            "port": 4222,
+            "output_dir": output_dir.path(),
+            "accounts": [
+                {
+                    "name": "SVC",
+                    "users": [{ "name": "responder" }],
+                    "exports": [{ "subject": "svc.echo", "is_service": true }]
+                },
+                {
+                    "name": "CLIENT",
+                    "users": [{ "name": "requester" }],
+                    "imports": [{ "subject": "svc.echo", "account": "SVC", "local_subject": "echo", "service": true }]
+                }
+            ]
+        }]
+    }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let client = claims(&std::fs::read_to_string(&result.account("CLIENT").unwrap().jwt_path)?)?;
+    let imports = client["nats"]["imports"].as_array().unwrap();
+    assert_eq!(imports.len(), 1, "{:?}", imports);
+    assert_eq!(imports[0]["subject"], "svc.echo");
+    assert_eq!(imports[0]["local_subject"], "echo");
+    assert_eq!(imports[0]["type"], "service");
+    assert_eq!(imports[0]["account"], result.account_id("SVC").unwrap());
+    Ok(())
+}
+
+#[test]
+fn test_import_fields_default() -> anyhow::Result<()> {
+    let import: natsforge::config::ImportConfig = serde_json::from_value(json!({ "subject": "a.b", "account": "A" }))?;
+    assert_eq!(import.local_subject, None);
+    assert!(!import.service);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_from_account_on_another_server() -> anyhow::Result<()> {
+    let mut config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "import-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [
+            {
+                "name": "events",
+                "port": 4222,
+                "output_dir": "/srv/events",
+                "accounts": [{ "name": "EVENTS", "exports": [{ "subject": "events.>" }] }]
+            },
+            {
+                "name": "app",
+                "port": 4223,
+                "output_dir": "/srv/app",
+                "accounts": [{ "name": "APP", "imports": [{ "subject": "events.>", "account": "EVENTS" }] }]
+            }
+        ]
+    }))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    // The app server needs the exporter's JWT to route the import.
+    let events_id = result.account_id("EVENTS").unwrap();
+    let app_config = &result.artifacts.as_ref().unwrap().server_configs["app"];
+    assert!(app_config.contains(events_id), "{}", app_config);
+    let app = claims(&result.artifacts.as_ref().unwrap().account_jwts["APP"])?;
+    assert_eq!(app["nats"]["imports"][0]["account"], events_id);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_public_import_cycle_is_allowed() -> anyhow::Result<()> {
+    let account = |name: &str, imports_from: &str| {
+        json!({
+            "name": name,
+            "exports": [{ "subject": format!("{}.events", name.to_lowercase()) }],
+            "imports": [{ "subject": format!("{}.events", imports_from.to_lowercase()), "account": imports_from }]
+        })
+    };
+    let mut config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "import-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "import-server",
+            "port": 4222,
+            "output_dir": "/srv/import-server",
+            "accounts": [account("APP", "BILLING"), account("BILLING", "APP"), account("AUDIT", "APP")]
+        }]
+    }))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let account_jwts = &result.artifacts.as_ref().unwrap().account_jwts;
+    for (importer, exporter) in [("APP", "BILLING"), ("BILLING", "APP"), ("AUDIT", "APP")] {
+        let imports = &claims(&account_jwts[importer])?["nats"]["imports"];
+        assert_eq!(
+            imports[0]["account"],
+            result.account_id(exporter).unwrap(),
+            "{}",
+            importer
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_accounts_are_created_in_a_stable_order() -> anyhow::Result<()> {
+    // A diamond: LEFT and RIGHT import from TOP, BOTTOM from both.
+    let account = |name: &str, imports_from: &[&str]| {
+        let imports: Vec<_> = imports_from
+            .iter()
+            .map(|from| json!({ "subject": format!("{}.events", from.to_lowercase()), "account": from }))
+            .collect();
+        json!({
+            "name": name,
+            "exports": [{ "subject": format!("{}.events", name.to_lowercase()) }],
+            "imports": imports
+        })
+    };
+    let accounts = json!([
+        account("BOTTOM", &["LEFT", "RIGHT"]),
+        account("RIGHT", &["TOP"]),
+        account("LEFT", &["TOP"]),
+        account("TOP", &[])
+    ]);
+
+    for _ in 0..5 {
+        let output_dir = tempfile::TempDir::new()?;
+        let config: NatsConfig = serde_json::from_value(json!({
+            "operator": { "name": "import-operator", "reuse_existing": false },
+            "backend": "native",
+            "durability": "fast",
+            "servers": [{
+                "name": "import-server",
+                "port": 4222,
+                "output_dir": output_dir.path(),
+                "accounts": accounts
+            }]
+        }))?;
+        let result = NatsForge::from_config(config)?.initialize().await?;
+
+        // Exporters first, and accounts of the same layer in the order they are declared.
+        #[allow(deprecated)]
+        let created: Vec<_> = result
+            .account_jwt_paths
+            .iter()
+            .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(created, ["TOP", "RIGHT", "LEFT", "BOTTOM"]);
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use anyhow::Context;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn config(output_dir: &Path, port: u16) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "include-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "hub",
+            "port": port,
+            "output_dir": output_dir,
+            "split_resolver_preload": true,
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_split_layout() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path(), 4222)?;
+    config.servers[0].split_includes = vec!["tls".to_string(), "resolver_preload".to_string()];
+    config.servers[0].tls = Some(serde_json::from_value(
+        json!({ "cert_file": "server.pem", "key_file": "server-key.pem" }),
+    )?);
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let server_config = result.server_config("hub").unwrap();
+    let conf = std::fs::read_to_string(server_config)?;
+    assert!(conf.contains("\ninclude \"tls.conf\"\n"), "{}", conf);
+    assert!(
+        conf.ends_with("resolver: MEMORY\n\ninclude \"resolver_preload.conf\"\n"),
+        "{}",
+        conf
+    );
+
+    let output_dir = server_config.parent().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(output_dir.join("tls.conf"))?,
+        "tls {\n    cert_file: \"server.pem\"\n    key_file: \"server-key.pem\"\n}\n"
+    );
+    let preload = std::fs::read_to_string(output_dir.join("resolver_preload.conf"))?;
+    assert!(preload.starts_with("resolver_preload {\n"), "{}", preload);
+    assert!(preload.contains(result.account_id("APP").unwrap()), "{}", preload);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_split_layout_in_memory() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/hub"), 4222)?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let artifacts = result.artifacts.as_ref().unwrap();
+    assert!(artifacts.server_configs["hub"].contains("include \"resolver_preload.conf\""));
+    let preload = &artifacts.server_files["hub"][Path::new("/srv/hub/resolver_preload.conf")];
+    assert!(preload.contains(result.account_id("APP").unwrap()), "{}", preload);
+    Ok(())
+}
+
+#[test]
+fn test_split_includes_need_a_block() -> anyhow::Result<()> {
+    let mut config = config(Path::new("unused"), 4222)?;
+    config.servers[0].split_includes = vec!["websocket".to_string()];
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("neither natsforge nor its extra_blocks render a websocket block"),
+        "{}",
+        err
+    );
+
+    config.servers[0].extra_blocks = serde_json::from_value(json!({ "websocket": { "port": 8080 } }))?;
+    config.validate()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_starts_from_split_layout() -> anyhow::Result<()> {
+    let port = 4274;
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path(), port)?;
+    config.servers[0].jetstream.enabled = true;
+    config.servers[0].split_includes = vec!["jetstream".to_string()];
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    // Started from another directory, so only includes relative to nats.conf are found.
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("hub").unwrap())
+        .current_dir(std::env::temp_dir())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    // The APP account is only known to the server through the included preload.
+    let creds = std::fs::read_to_string(result.creds("APP", "app-user").unwrap())?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+    let jetstream = async_nats::jetstream::new(client);
+    jetstream.query_account().await?;
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use base64::Engine;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(jetstream: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "jetstream-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "js",
+            "port": 4222,
+            "output_dir": "/srv/js",
+            "jetstream": jetstream
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_subject_transform_and_republish_rendering() -> anyhow::Result<()> {
+    let config = config(json!({
+        "enabled": true,
+        "store_dir": "/var/lib/nats",
+        "domain": "edge",
+        "subject_transform": { "src": "orders.*.*", "dest": "orders.{{wildcard(2)}}.{{wildcard(1)}}" },
+        "republish": [
+            { "src": "events.>", "dest": "audit.events.>" },
+            { "src": "metrics.*", "dest": "mirror.metrics.*" }
+        ]
+    }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = &result.artifacts.as_ref().unwrap().server_configs["js"];
+    let expected = "jetstream {
+    store_dir: \"/var/lib/nats\"
+    domain: \"edge\"
+    subject_transform {
+        src: \"orders.*.*\"
+        dest: \"orders.{{wildcard(2)}}.{{wildcard(1)}}\"
+    }
+    republish: [
+        { src: \"events.>\", dest: \"audit.events.>\" }
+        { src: \"metrics.*\", dest: \"mirror.metrics.*\" }
+    ]
+}
+";
+    assert!(conf.contains(expected), "{}", conf);
+    Ok(())
+}
+
+#[test]
+fn test_transform_wildcard_arity_is_validated() -> anyhow::Result<()> {
+    for (src, dest) in [
+        ("orders.*", "orders.v2.*"),
+        ("orders.*.*", "by-region.$2.$1"),
+        ("orders.*.*", "orders.{{partition(4, 1, 2)}}"),
+        ("orders.>", "archive.orders.>"),
+    ] {
+        let config = config(json!({ "enabled": true, "subject_transform": { "src": src, "dest": dest } }))?;
+        assert!(config.validate().is_ok(), "{} -> {}", src, dest);
+    }
+
+    for (src, dest, message) in [
+        (
+            "orders.*",
+            "orders.{{wildcard(2)}}",
+            "refers to wildcard 2, but the source has 1",
+        ),
+        ("orders.*.*", "orders.*", "1 * tokens for the 2 of the source"),
+        ("orders.>", "archive.orders", "drops the trailing >"),
+        ("orders.new", "orders.>", "has a > the source doesn't"),
+        (
+            "orders.*",
+            "orders.*.{{wildcard(1)}}",
+            "mixes * tokens with mapping functions",
+        ),
+        ("orders.*", "$0.orders", "refers to wildcard 0"),
+    ] {
+        let config = config(json!({ "enabled": true, "subject_transform": { "src": src, "dest": dest } }))?;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("JetStream subject_transform of server js"), "{}", err);
+        assert!(err.contains(message), "{} -> {}: {}", src, dest, err);
+    }
+
+    let config = config(json!({ "enabled": true, "republish": [{ "src": "events.*", "dest": "audit.>" }] }))?;
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("JetStream republish of server js"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_relative_store_dir_is_made_absolute() -> anyhow::Result<()> {
+    let files_config = |output_dir: &tempfile::TempDir, jetstream| -> anyhow::Result<NatsConfig> {
+        let mut config = config(jetstream)?;
+        config.artifact_mode = ArtifactMode::Files;
+        config.servers[0].output_dir = output_dir.path().to_path_buf();
+        Ok(config)
+    };
+
+    let output_dir = tempfile::TempDir::new()?;
+    let config = files_config(&output_dir, json!({ "enabled": true, "store_dir": "data/js" }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = std::fs::read_to_string(result.server_config("js").unwrap())?;
+    let store_dir = output_dir.path().canonicalize()?.join("data/js");
+    assert!(store_dir.is_dir());
+    assert!(
+        conf.contains(&format!("    store_dir: \"{}\"\n", store_dir.display())),
+        "{}",
+        conf
+    );
+
+    let output_dir = tempfile::TempDir::new()?;
+    let config = files_config(&output_dir, json!({ "enabled": true, "absolute_paths": false }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let conf = std::fs::read_to_string(result.server_config("js").unwrap())?;
+    assert!(conf.contains("    store_dir: \"jetstream\"\n"), "{}", conf);
+    assert!(!output_dir.path().join("jetstream").exists());
+    Ok(())
+}
+
+fn stream_config(account_jetstream: bool, server_jetstream: bool) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "jetstream-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "js",
+            "port": 4299,
+            "output_dir": "/srv/js",
+            "jetstream": { "enabled": server_jetstream },
+            "accounts": [{ "name": "APP", "jetstream": account_jetstream, "users": [{ "name": "app-user" }] }],
+            "streams": [{ "name": "ORDERS", "subjects": ["orders.>"], "account": "APP" }]
+        }]
+    }))?)
+}
+
+#[test]
+fn test_streams_need_jetstream_on_server_and_account() -> anyhow::Result<()> {
+    assert!(stream_config(true, true)?.validate().is_ok());
+
+    let err = stream_config(false, true)?.validate().unwrap_err().to_string();
+    assert!(err.contains("Stream ORDERS on server js"), "{}", err);
+    assert!(err.contains("account APP doesn't enable jetstream"), "{}", err);
+
+    let err = stream_config(true, false)?.validate().unwrap_err().to_string();
+    assert!(err.contains("the server needs JetStream enabled"), "{}", err);
+
+    let mut config = stream_config(true, true)?;
+    config.servers[0].streams[0].name = "orders.v2".to_string();
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("must not be empty or contain"), "{}", err);
+
+    let mut config = stream_config(true, true)?;
+    config.servers[0].streams[0].account = "BILLING".to_string();
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("unknown account BILLING"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_jetstream_accounts_get_unlimited_storage() -> anyhow::Result<()> {
+    let result = NatsForge::from_config(stream_config(true, true)?)?.initialize().await?;
+    let claims = claims(&result.artifacts.as_ref().unwrap().account_jwts["APP"])?;
+    for limit in ["mem_storage", "disk_storage", "streams", "consumer"] {
+        assert_eq!(claims["nats"]["limits"][limit], -1, "{}", limit);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_provision_failure_names_server_and_stream() -> anyhow::Result<()> {
+    let forge = NatsForge::from_config(stream_config(true, true)?)?;
+    let result = forge.initialize().await?;
+    // Nothing listens on the server's port.
+    let err = forge.provision(&result).await.unwrap_err();
+    assert!(
+        matches!(&err, natsforge::NatsForgeError::Provision { server, resource, .. } if server == "js" && resource == "stream ORDERS"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_consumers_and_buckets_are_validated() -> anyhow::Result<()> {
+    let mut config = stream_config(true, true)?;
+    config.servers[0].consumers = serde_json::from_value(json!([{ "stream": "ORDERS", "name": "shipping" }]))?;
+    config.servers[0].kv_buckets = serde_json::from_value(json!([{ "bucket": "settings", "account": "APP" }]))?;
+    assert!(config.validate().is_ok());
+
+    config.servers[0].consumers[0].stream = "PAYMENTS".to_string();
+    let err = config.validate().unwrap_err().to_string();
+    assert!(
+        err.contains("Consumer shipping on stream PAYMENTS of server js"),
+        "{}",
+        err
+    );
+    assert!(err.contains("isn't one of the server's streams"), "{}", err);
+    config.servers[0].consumers.clear();
+
+    for (bucket, message) in [
+        (
+            json!({ "bucket": "app.settings", "account": "APP" }),
+            "may only contain",
+        ),
+        (
+            json!({ "bucket": "settings", "history": 65, "account": "APP" }),
+            "history must be between 1 and 64",
+        ),
+        (
+            json!({ "bucket": "settings", "ttl": "1 day", "account": "APP" }),
+            "Invalid ttl",
+        ),
+        (
+            json!({ "bucket": "settings", "account": "BILLING" }),
+            "unknown account BILLING",
+        ),
+    ] {
+        config.servers[0].kv_buckets = vec![serde_json::from_value(bucket)?];
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("KV bucket"), "{}", err);
+        assert!(err.contains(message), "{}", err);
+    }
+    Ok(())
+}
+
+fn mirror_config(leaf_domain: Option<&str>) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "jetstream-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4222,
+                "output_dir": "/srv/hub",
+                "jetstream": { "enabled": true, "domain": "hub" },
+                "leafnodes": { "port": 7422 },
+                "accounts": [{ "name": "APP", "jetstream": true, "users": [{ "name": "app-user" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": "/srv/leaf",
+                "jetstream": { "enabled": true, "domain": leaf_domain },
+                "leafnodes": { "remotes": [{ "url": "localhost:7422", "account": "APP", "user": "app-user" }] }
+            }
+        ],
+        "mirrors": [{
+            "name": "READINGS_MIRROR",
+            "source_stream": "READINGS",
+            "source_domain": "leaf",
+            "dest_domain": "hub",
+            "account": "APP"
+        }]
+    }))?)
+}
+
+#[test]
+fn test_mirrors_need_domains_on_both_sides() -> anyhow::Result<()> {
+    assert!(mirror_config(Some("leaf"))?.validate().is_ok());
+
+    let err = mirror_config(None)?.validate().unwrap_err().to_string();
+    assert!(err.contains("Mirror READINGS_MIRROR"), "{}", err);
+    assert!(err.contains("has the source domain leaf"), "{}", err);
+
+    let mut config = mirror_config(Some("leaf"))?;
+    config.servers[0].jetstream.domain = None;
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("has the destination domain hub"), "{}", err);
+
+    let mut config = mirror_config(Some("leaf"))?;
+    config.mirrors[0].source_domain = "hub".to_string();
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("both in domain hub"), "{}", err);
+    Ok(())
+}
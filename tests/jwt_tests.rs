@@ -0,0 +1,114 @@
+use base64::Engine;
+use natsforge::{
+    config::NatsConfig,
+    jwt::{self, SigningKey},
+    NatsForge, NatsForgeError,
+};
+use serde_json::{json, Value};
+
+fn config(output_dir: &std::path::Path) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "jwt-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "jwt",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [
+                {
+                    "name": "ORDERS",
+                    "max_connections": 100,
+                    "signing_keys": 1,
+                    "exports": [{ "subject": "orders.>" }, { "subject": "orders.lookup", "is_service": true }],
+                    "users": [{ "name": "writer", "allow_pub": ["orders.>"], "deny_sub": ["orders.secret"] }]
+                },
+                {
+                    "name": "BILLING",
+                    "imports": [{ "account": "ORDERS", "subject": "orders.>" }],
+                    "users": [{ "name": "reader", "allow_sub": ["orders.>"], "max_payload": 1024 }]
+                }
+            ]
+        }]
+    }))?)
+}
+
+/// The user JWT in a `.creds` file.
+fn creds_jwt(creds: &str) -> &str {
+    creds.lines().nth(1).unwrap()
+}
+
+#[tokio::test]
+async fn test_typed_claims_of_a_setup() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path())?)?.initialize().await?;
+
+    let operator = jwt::decode_operator_claims(&std::fs::read_to_string(&result.operator_jwt_path)?)?;
+    assert_eq!(operator.iss, operator.sub);
+    assert_eq!(operator.nats.system_account.as_deref(), result.account_id("SYS"));
+
+    let orders = jwt::decode_account_claims(&std::fs::read_to_string(&result.account_jwts["ORDERS"].jwt_path)?)?;
+    assert_eq!(orders.iss, operator.sub);
+    assert_eq!(orders.sub, result.account_jwts["ORDERS"].id);
+    assert_eq!(orders.nats.limits.conn, Some(100));
+    let exports: Vec<(&str, &str)> = orders
+        .nats
+        .exports
+        .iter()
+        .map(|export| (export.subject.as_str(), export.kind.as_str()))
+        .collect();
+    assert_eq!(exports, vec![("orders.>", "stream"), ("orders.lookup", "service")]);
+    let signing_keys: Vec<&str> = orders.nats.signing_keys.iter().map(SigningKey::key).collect();
+    assert_eq!(signing_keys, result.account_jwts["ORDERS"].signing_keys);
+    assert_eq!(orders.raw["nats"]["limits"]["conn"], 100);
+
+    let billing = jwt::decode_account_claims(&std::fs::read_to_string(&result.account_jwts["BILLING"].jwt_path)?)?;
+    assert_eq!(billing.nats.imports[0].account, orders.sub);
+    assert_eq!(billing.nats.imports[0].subject, "orders.>");
+
+    let writer = std::fs::read_to_string(result.creds("ORDERS", "writer").unwrap())?;
+    let writer = jwt::decode_user_claims(creds_jwt(&writer))?;
+    assert_eq!(writer.name, "writer");
+    assert_eq!(writer.nats.permissions.publish.allow, vec!["orders.>"]);
+    assert_eq!(writer.nats.permissions.subscribe.deny, vec!["orders.secret"]);
+    let reader = std::fs::read_to_string(result.creds("BILLING", "reader").unwrap())?;
+    assert_eq!(jwt::decode_user_claims(creds_jwt(&reader))?.nats.payload, Some(1024));
+    Ok(())
+}
+
+#[test]
+fn test_padded_and_unpadded_payloads_decode_alike() -> anyhow::Result<()> {
+    let claims = json!({ "sub": "UABC", "iss": "AXYZ", "name": "u", "nats": { "type": "user", "version": 2 } });
+    let payload = serde_json::to_vec(&claims)?;
+    let token = |payload: String| format!("eyJ0eXAiOiJKV1QifQ.{}.c2ln", payload);
+    let unpadded = token(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&payload));
+    let padded = token(base64::engine::general_purpose::URL_SAFE.encode(&payload));
+    let standard = token(base64::engine::general_purpose::STANDARD.encode(&payload));
+
+    for token in [unpadded, padded, standard] {
+        assert_eq!(jwt::decode_claims::<Value>(&token)?, claims);
+        assert_eq!(jwt::decode_user_claims(&token)?.sub, "UABC");
+        assert_eq!(natsforge::extract_account_id(&token)?, "UABC");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_malformed_and_mistyped_jwts_are_rejected() {
+    let err = jwt::decode_claims::<Value>("not.a-jwt").unwrap_err();
+    assert!(matches!(err, NatsForgeError::Jwt(_)), "{:?}", err);
+    assert!(err.to_string().contains("expected 3 parts, found 2"), "{}", err);
+
+    let user = format!(
+        "eyJ0eXAiOiJKV1QifQ.{}.c2ln",
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(r#"{ "sub": "UABC", "iss": "AXYZ", "nats": { "type": "user" } }"#)
+    );
+    let err = jwt::decode_account_claims(&user).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("expected a JWT of type account, found \"user\""),
+        "{}",
+        err
+    );
+}
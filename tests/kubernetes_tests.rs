@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, path::Path};
+
+use natsforge::{
+    config::{ArtifactMode, NatsConfig, SetupResult},
+    kubernetes::K8sOptions,
+    NatsForge,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+fn config(hub_dir: &Path, leaf_dir: &Path, hub: Value) -> anyhow::Result<NatsConfig> {
+    let mut hub_server = json!({
+        "name": "Hub",
+        "port": 4222,
+        "output_dir": hub_dir,
+        "leafnodes": { "port": 7422 },
+        "monitoring": { "http_port": 8222 },
+        "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+    });
+    for (key, value) in hub.as_object().unwrap() {
+        hub_server[key] = value.clone();
+    }
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "k8s-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [
+            hub_server,
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": leaf_dir,
+                "leafnodes": { "remotes": [{ "url": "nats://localhost:7422", "account": "APP", "user": "app-user" }] }
+            }
+        ]
+    }))?)
+}
+
+/// The documents of every written file, by server resource name.
+async fn emit(
+    mode: ArtifactMode,
+    hub: Value,
+) -> anyhow::Result<(BTreeMap<String, Vec<Value>>, SetupResult, tempfile::TempDir)> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(&dir.path().join("hub"), &dir.path().join("leaf"), hub)?;
+    config.artifact_mode = mode;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let options = K8sOptions {
+        output_dir: dir.path().join("k8s"),
+        namespace: "nats".to_string(),
+        labels: [("team".to_string(), "messaging".to_string())].into(),
+        ..Default::default()
+    };
+    let paths = forge.emit_kubernetes(&result, options)?;
+
+    let mut manifests = BTreeMap::new();
+    for path in paths {
+        let yaml = std::fs::read_to_string(&path)?;
+        let documents = serde_yaml::Deserializer::from_str(&yaml)
+            .map(Value::deserialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        manifests.insert(name, documents);
+    }
+    Ok((manifests, result, dir))
+}
+
+fn kind<'a>(documents: &'a [Value], wanted: &str) -> &'a Value {
+    documents.iter().find(|document| document["kind"] == wanted).unwrap()
+}
+
+#[tokio::test]
+async fn test_kubernetes_manifests() -> anyhow::Result<()> {
+    let (manifests, _result, _dir) = emit(ArtifactMode::Files, json!({ "jetstream": { "enabled": true } })).await?;
+    assert_eq!(manifests.keys().collect::<Vec<_>>(), ["hub", "leaf"]);
+    let hub = &manifests["hub"];
+    let kinds: Vec<&Value> = hub.iter().map(|document| &document["kind"]).collect();
+    assert_eq!(kinds, ["ConfigMap", "Secret", "StatefulSet", "Service"]);
+    for document in hub {
+        assert_eq!(document["metadata"]["namespace"], "nats");
+        assert_eq!(document["metadata"]["labels"]["team"], "messaging");
+        assert_eq!(document["metadata"]["labels"]["app.kubernetes.io/instance"], "hub");
+    }
+
+    let secrets = &kind(hub, "Secret")["stringData"];
+    assert!(secrets["APP.jwt"].as_str().unwrap().starts_with("eyJ"));
+    assert!(secrets["APP-app-user.creds"]
+        .as_str()
+        .unwrap()
+        .contains("BEGIN USER NKEY SEED"));
+
+    let stateful_set = kind(hub, "StatefulSet");
+    assert_eq!(stateful_set["metadata"]["name"], "hub");
+    let pod = &stateful_set["spec"]["template"]["spec"];
+    assert_eq!(pod["containers"][0]["args"], json!(["-c", "/etc/nats/nats.conf"]));
+    assert_eq!(
+        pod["containers"][0]["volumeMounts"],
+        json!([
+            { "name": "config", "mountPath": "/etc/nats/nats.conf", "subPath": "nats.conf" },
+            { "name": "secrets", "mountPath": "/etc/nats/secrets", "readOnly": true },
+            { "name": "data", "mountPath": "/data" }
+        ])
+    );
+    assert_eq!(pod["volumes"][0]["configMap"]["name"], "hub-config");
+    assert_eq!(pod["volumes"][1]["secret"]["secretName"], "hub-secrets");
+    // JetStream keeps its store on a volume claim.
+    assert_eq!(
+        stateful_set["spec"]["volumeClaimTemplates"][0]["metadata"]["name"],
+        "data"
+    );
+
+    let service = kind(hub, "Service");
+    assert_eq!(service["spec"]["selector"]["app.kubernetes.io/instance"], "hub");
+    assert_eq!(
+        service["spec"]["ports"],
+        json!([
+            { "name": "client", "port": 4222, "targetPort": "client" },
+            { "name": "leafnodes", "port": 7422, "targetPort": "leafnodes" },
+            { "name": "monitor", "port": 8222, "targetPort": "monitor" }
+        ])
+    );
+
+    let hub_conf = kind(hub, "ConfigMap")["data"]["nats.conf"].as_str().unwrap();
+    assert!(hub_conf.contains("store_dir: \"/data/jetstream\""), "{}", hub_conf);
+
+    let leaf = &manifests["leaf"];
+    let leaf_conf = kind(leaf, "ConfigMap")["data"]["nats.conf"].as_str().unwrap();
+    assert!(
+        leaf_conf.contains("nats://hub.nats.svc.cluster.local:7422"),
+        "{}",
+        leaf_conf
+    );
+    assert!(
+        leaf_conf.contains("/etc/nats/secrets/APP-app-user.creds"),
+        "{}",
+        leaf_conf
+    );
+    assert!(kind(leaf, "Secret")["stringData"]["APP-app-user.creds"].is_string());
+    let leaf_pod = &kind(leaf, "StatefulSet")["spec"]["template"]["spec"];
+    assert_eq!(leaf_pod["volumes"][2], json!({ "name": "data", "emptyDir": {} }));
+
+    // Creds only go into the Secrets.
+    for documents in manifests.values() {
+        let conf = kind(documents, "ConfigMap")["data"]["nats.conf"].as_str().unwrap();
+        assert!(!conf.contains("NKEY SEED"), "{}", conf);
+        for secret in kind(documents, "Secret")["stringData"].as_object().unwrap().values() {
+            let secret = secret.as_str().unwrap();
+            if secret.contains("NKEY SEED") {
+                let jwt = secret.lines().nth(1).unwrap();
+                assert!(!conf.contains(jwt), "{}", conf);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_kubernetes_full_resolver_in_memory() -> anyhow::Result<()> {
+    let (manifests, result, _dir) = emit(ArtifactMode::InMemory, json!({ "resolver": { "full": {} } })).await?;
+    let hub = &manifests["hub"];
+    let app_id = result.account_id("APP").unwrap();
+    let secrets = &kind(hub, "Secret")["stringData"];
+    assert!(secrets[format!("{}.jwt", app_id)].is_string());
+
+    let pod = &kind(hub, "StatefulSet")["spec"]["template"]["spec"];
+    let command = pod["initContainers"][0]["command"][2].as_str().unwrap();
+    assert!(command.starts_with("mkdir -p '/data/jwt' && cp "), "{}", command);
+    assert!(
+        command.contains(&format!("'/etc/nats/secrets/{}.jwt'", app_id)),
+        "{}",
+        command
+    );
+
+    let hub_conf = kind(hub, "ConfigMap")["data"]["nats.conf"].as_str().unwrap();
+    assert!(hub_conf.contains("\"/data/jwt\""), "{}", hub_conf);
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use std::path::Path;
+
+use base64::Engine;
+use natsforge::{config::NatsConfig, NatsForge};
+use serde_json::json;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(listener_tls: serde_json::Value, remote_tls: serde_json::Value, url: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "leafnode-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4222,
+                "output_dir": "/srv/hub",
+                "leafnodes": { "port": 7422, "tls": listener_tls },
+                "accounts": [{ "name": "APP", "users": [{ "name": "leaf" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": "/srv/leaf",
+                "leafnodes": {
+                    "remotes": [{ "url": url, "account": "APP", "credentials": "APP-leaf.creds", "tls": remote_tls }]
+                }
+            }
+        ]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_leafnode_tls_blocks() -> anyhow::Result<()> {
+    let listener_tls =
+        json!({ "cert_file": "hub.pem", "key_file": "hub-key.pem", "ca_file": "ca.pem", "verify": true });
+    let remote_tls = json!({ "ca_file": "ca.pem", "cert_file": "leaf.pem", "key_file": "leaf-key.pem" });
+    let result = NatsForge::from_config(config(listener_tls, remote_tls, "hub.internal:7422")?)?
+        .initialize()
+        .await?;
+
+    let configs = &result.artifacts.as_ref().unwrap().server_configs;
+    let expected = "leafnodes {
+    port: 7422
+    tls {
+        cert_file: \"hub.pem\"
+        key_file: \"hub-key.pem\"
+        ca_file: \"ca.pem\"
+        verify: true
+    }
+}
+";
+    assert!(configs["hub"].contains(expected), "{}", configs["hub"]);
+
+    let app_id = result.account_id("APP").unwrap();
+    let expected = format!(
+        "    remotes: [
+        {{
+            url: \"tls://hub.internal:7422\"
+            account: \"{}\"
+            credentials: \"/srv/leaf/APP-leaf.creds\"
+            tls {{
+                ca_file: \"ca.pem\"
+                cert_file: \"leaf.pem\"
+                key_file: \"leaf-key.pem\"
+            }}
+        }}
+    ]
+",
+        app_id
+    );
+    assert!(configs["leaf"].contains(&expected), "{}", configs["leaf"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_leafnode_remote_without_tls() -> anyhow::Result<()> {
+    let result = NatsForge::from_config(config(json!(null), json!(null), "hub.internal:7422")?)?
+        .initialize()
+        .await?;
+
+    let configs = &result.artifacts.as_ref().unwrap().server_configs;
+    assert!(
+        configs["hub"].contains("leafnodes {\n    port: 7422\n}\n"),
+        "{}",
+        configs["hub"]
+    );
+    assert!(
+        configs["leaf"].contains("{ url: \"nats://hub.internal:7422\", account: "),
+        "{}",
+        configs["leaf"]
+    );
+
+    let insecure = json!({ "insecure": true });
+    let result = NatsForge::from_config(config(json!(null), insecure, "tls://hub.internal:7422")?)?
+        .initialize()
+        .await?;
+    let leaf = &result.artifacts.as_ref().unwrap().server_configs["leaf"];
+    assert!(leaf.contains("url: \"tls://hub.internal:7422\"\n"), "{}", leaf);
+    assert!(
+        leaf.contains("            tls {\n                insecure: true\n            }\n"),
+        "{}",
+        leaf
+    );
+    Ok(())
+}
+
+#[test]
+fn test_leafnode_tls_validation() -> anyhow::Result<()> {
+    let half = json!({ "cert_file": "leaf.pem" });
+    let err = config(json!(null), half, "hub.internal:7422")?.validate().unwrap_err();
+    assert!(err.to_string().contains("needs both cert_file and key_file"), "{}", err);
+
+    let listener_tls = json!({ "cert_file": "hub.pem", "key_file": "hub-key.pem", "verify": true });
+    let err = config(listener_tls, json!(null), "hub.internal:7422")?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Leafnode TLS of server hub: verify needs a ca_file"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_leafnode_authorization_and_deny_lists() -> anyhow::Result<()> {
+    let mut config = config(json!(null), json!(null), "hub.internal:7422")?;
+    config.servers[0].leafnodes.min_version = Some("2.10.0".to_string());
+    config.servers[0].leafnodes.authorization = Some(serde_json::from_value(json!({
+        "users": [{ "user": "edge-1", "password": "s3cret" }, { "user": "edge-2", "password": "0ther" }],
+        "account": "APP"
+    }))?);
+    let remote = &mut config.servers[1].leafnodes.remotes[0];
+    remote.deny_imports = vec!["internal.>".to_string()];
+    remote.deny_exports = vec!["internal.>".to_string(), "debug.*".to_string()];
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let configs = &result.artifacts.as_ref().unwrap().server_configs;
+    let app_id = result.account_id("APP").unwrap();
+    let expected = format!(
+        "leafnodes {{
+    port: 7422
+    min_version: \"2.10.0\"
+    authorization {{
+        users: [
+            {{ user: \"edge-1\", password: \"s3cret\", account: \"{app_id}\" }}
+            {{ user: \"edge-2\", password: \"0ther\", account: \"{app_id}\" }}
+        ]
+    }}
+}}
+"
+    );
+    assert!(configs["hub"].contains(&expected), "{}", configs["hub"]);
+
+    let expected = format!(
+        "        {{
+            url: \"nats://hub.internal:7422\"
+            account: \"{app_id}\"
+            credentials: \"/srv/leaf/APP-leaf.creds\"
+            deny_imports: [\"internal.>\"]
+            deny_exports: [\"internal.>\", \"debug.*\"]
+        }}
+"
+    );
+    assert!(configs["leaf"].contains(&expected), "{}", configs["leaf"]);
+    Ok(())
+}
+
+#[test]
+fn test_leafnode_authorization_validation() -> anyhow::Result<()> {
+    let base = || config(json!(null), json!(null), "hub.internal:7422");
+
+    for (version, message) in [
+        ("2.7.4", "older than 2.8.0"),
+        ("2.10", "is not a version"),
+        ("v2.10.0", "is not a version"),
+    ] {
+        let mut config = base()?;
+        config.servers[0].leafnodes.min_version = Some(version.to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains(message), "{}: {}", version, err);
+    }
+
+    let mut config = base()?;
+    config.servers[0].leafnodes.authorization = Some(serde_json::from_value(json!({
+        "users": [{ "user": "edge", "password": "a" }],
+        "account": "NOPE"
+    }))?);
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("unknown account NOPE"), "{}", err);
+
+    let mut config = base()?;
+    config.servers[0].leafnodes.authorization = Some(serde_json::from_value(json!({
+        "users": [{ "user": "edge", "password": "a" }, { "user": "edge", "password": "b" }]
+    }))?);
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("repeated user \"edge\""), "{}", err);
+
+    let mut config = base()?;
+    config.servers[1].leafnodes.remotes[0].deny_exports = vec!["internal..secret".to_string()];
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Leafnode remote hub.internal:7422 of server leaf"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+fn twin_servers(edge_remote: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    let twin = |name: &str, port: u16| {
+        json!({
+            "name": name,
+            "port": port,
+            "output_dir": format!("/srv/{}", name),
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user", "allow_pub": [format!("{}.>", name)] }] }],
+            "leafnodes": {
+                "remotes": [{ "url": "hub.internal:7422", "account": "APP", "credentials": "APP-app-user.creds" }]
+            }
+        })
+    };
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "twin-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [
+            twin("east", 4222),
+            twin("west", 4223),
+            {
+                "name": "edge",
+                "port": 4224,
+                "output_dir": "/srv/edge",
+                "leafnodes": { "remotes": [edge_remote] }
+            }
+        ]
+    }))?)
+}
+
+/// The subjects the creds a server's remote connects with may publish to.
+fn remote_allow_pub(result: &natsforge::config::SetupResult, server: &str) -> anyhow::Result<serde_json::Value> {
+    let files = &result.artifacts.as_ref().unwrap().server_files[server];
+    let creds = &files[Path::new(&format!("/srv/{}/APP-app-user.creds", server))];
+    let jwt = creds.lines().find(|line| line.starts_with("eyJ")).unwrap();
+    Ok(claims(jwt)?["nats"]["pub"]["allow"].clone())
+}
+
+#[tokio::test]
+async fn test_remote_creds_come_from_own_account_instance() -> anyhow::Result<()> {
+    let edge_remote = json!({ "url": "hub.internal:7422", "account": "APP", "user": "app-user", "server": "west" });
+    let result = NatsForge::from_config(twin_servers(edge_remote)?)?.initialize().await?;
+
+    assert_eq!(remote_allow_pub(&result, "east")?, json!(["east.>"]));
+    assert_eq!(remote_allow_pub(&result, "west")?, json!(["west.>"]));
+    assert_eq!(remote_allow_pub(&result, "edge")?, json!(["west.>"]));
+    let edge = &result.artifacts.as_ref().unwrap().server_configs["edge"];
+    assert!(
+        edge.contains("credentials: \"/srv/edge/APP-app-user.creds\""),
+        "{}",
+        edge
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_servers_preload_their_own_account_of_a_shared_name() -> anyhow::Result<()> {
+    let edge_remote = json!({ "url": "hub.internal:7422", "account": "APP", "user": "app-user", "server": "west" });
+    let result = NatsForge::from_config(twin_servers(edge_remote)?)?.initialize().await?;
+
+    let east_id = &result.server_account("east", "APP").unwrap().id;
+    let west_id = &result.server_account("west", "APP").unwrap().id;
+    assert_ne!(east_id, west_id);
+    let configs = &result.artifacts.as_ref().unwrap().server_configs;
+    for (server, id) in [("east", east_id), ("west", west_id)] {
+        let conf = &configs[server];
+        assert!(conf.contains(&format!("{}: ", id)), "{}", conf);
+        assert!(conf.contains(&format!("account: \"{}\"", id)), "{}", conf);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ambiguous_remote_creds_are_rejected() -> anyhow::Result<()> {
+    let edge_remote = json!({ "url": "hub.internal:7422", "account": "APP", "credentials": "APP-app-user.creds" });
+    let err = NatsForge::from_config(twin_servers(edge_remote)?)?
+        .initialize()
+        .await
+        .unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("are ambiguous"), "{}", message);
+    assert!(message.contains("east/APP"), "{}", message);
+    assert!(message.contains("west/APP"), "{}", message);
+
+    let edge_remote = json!({ "url": "hub.internal:7422", "account": "APP", "server": "south", "user": "app-user" });
+    let err = twin_servers(edge_remote)?.validate().unwrap_err();
+    assert!(err.to_string().contains("unknown server south"), "{}", err);
+
+    let edge_remote = json!({ "url": "hub.internal:7422", "account": "APP" });
+    let err = twin_servers(edge_remote)?.validate().unwrap_err();
+    assert!(err.to_string().contains("needs credentials or a user"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_leaf_preloads_only_scoped_accounts() -> anyhow::Result<()> {
+    let with_billing = |include_all_accounts: bool| -> anyhow::Result<NatsConfig> {
+        let mut config = config(json!(null), json!(null), "hub.internal:7422")?;
+        config.servers[0]
+            .accounts
+            .push(serde_json::from_value(json!({ "name": "BILLING" }))?);
+        config.servers[1].include_all_accounts = include_all_accounts;
+        Ok(config)
+    };
+    let result = NatsForge::from_config(with_billing(false)?)?.initialize().await?;
+    let billing_id = result.account_id("BILLING").unwrap();
+    let configs = &result.artifacts.as_ref().unwrap().server_configs;
+    assert!(configs["hub"].contains(billing_id), "{}", configs["hub"]);
+    assert!(configs["leaf"].contains(result.account_id("APP").unwrap()));
+    assert!(!configs["leaf"].contains(billing_id), "{}", configs["leaf"]);
+
+    let result = NatsForge::from_config(with_billing(true)?)?.initialize().await?;
+    let leaf = &result.artifacts.as_ref().unwrap().server_configs["leaf"];
+    assert!(leaf.contains(result.account_id("BILLING").unwrap()), "{}", leaf);
+    Ok(())
+}
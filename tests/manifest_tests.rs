@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use natsforge::{
+    config::{ArtifactMode, NatsConfig},
+    manifest::{SetupManifest, MANIFEST_FILE, MANIFEST_VERSION},
+    NatsForge,
+};
+use serde_json::json;
+
+fn config(hub_dir: &Path, leaf_dir: &Path) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "manifest-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "write_manifest": true,
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4222,
+                "output_dir": hub_dir,
+                "jetstream": { "enabled": true },
+                "resolver": { "full": {} },
+                "accounts": [{
+                    "name": "APP",
+                    "users": [{ "name": "app-user" }, { "name": "bearer-user", "bearer": true }]
+                }]
+            },
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": leaf_dir,
+                "accounts": [{ "name": "EDGE", "users": [{ "name": "edge-user" }] }]
+            }
+        ]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_manifest_written_to_every_output_dir() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(config(&dir.path().join("hub"), &dir.path().join("leaf"))?)?;
+    let result = forge.initialize().await?;
+    let manifest = result.manifest();
+
+    for server in ["hub", "leaf"] {
+        let path = result.server_config(server).unwrap().with_file_name(MANIFEST_FILE);
+        let written: SetupManifest = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(written, manifest);
+    }
+
+    assert_eq!(manifest.version, MANIFEST_VERSION);
+    assert_eq!(manifest.operator_jwt_path, result.operator_jwt_path);
+    assert!(manifest.store_dir.is_dir());
+    assert_eq!(manifest.servers.keys().collect::<Vec<_>>(), ["hub", "leaf"]);
+    let hub = &manifest.servers["hub"];
+    assert_eq!(hub.config_path, result.server_config("hub").unwrap());
+    assert_eq!(hub.url, "nats://localhost:4222");
+    assert_eq!(hub.resolver_dir.as_deref(), result.resolver_dir("hub"));
+    assert!(hub.jetstream_store_dir.is_some());
+    assert_eq!(manifest.servers["leaf"].resolver_dir, None);
+    assert!(manifest.servers["leaf"]
+        .operator_jwt_path
+        .starts_with(dir.path().join("leaf")));
+
+    let app = &manifest.accounts["APP"];
+    assert_eq!(app.id, result.account_id("APP").unwrap());
+    assert_eq!(app.jwt_path.as_ref(), Some(&result.account("APP").unwrap().jwt_path));
+    assert_eq!(app.creds["app-user"], result.creds("APP", "app-user").unwrap());
+    assert_eq!(
+        app.bearer_jwts["bearer-user"],
+        result.bearer_jwt("APP", "bearer-user").unwrap()
+    );
+    assert_eq!(
+        manifest.accounts["EDGE"].creds["edge-user"],
+        result.creds("EDGE", "edge-user").unwrap()
+    );
+    assert_eq!(manifest.accounts["SYS"].id, result.account_id("SYS").unwrap());
+
+    // Only paths are listed, never key material.
+    let creds = std::fs::read_to_string(result.creds("APP", "app-user").unwrap())?;
+    let seed = creds.lines().find(|line| line.starts_with("SU")).unwrap();
+    assert!(!manifest.to_json().contains(seed));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_manifest_not_written_in_memory() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(&dir.path().join("hub"), &dir.path().join("leaf"))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    assert!(!dir.path().join("hub").join(MANIFEST_FILE).exists());
+    assert_eq!(
+        result.manifest().servers["hub"].config_path,
+        dir.path().join("hub/nats.conf")
+    );
+    Ok(())
+}
+
+/// Manifests written by this schema version keep deserializing.
+#[test]
+fn test_manifest_schema_stability() -> anyhow::Result<()> {
+    let manifest: SetupManifest = serde_json::from_value(json!({
+        "version": 1,
+        "operator_jwt_path": "/out/hub/operator.jwt",
+        "store_dir": "/tmp/store",
+        "servers": {
+            "hub": {
+                "config_path": "/out/hub/nats.conf",
+                "operator_jwt_path": "/out/hub/operator.jwt",
+                "url": "nats://127.0.0.1:4222",
+                "resolver_dir": "/out/hub/jwt",
+                "jetstream_store_dir": "/out/hub/jetstream"
+            },
+            "leaf": {
+                "config_path": "/out/leaf/nats.conf",
+                "operator_jwt_path": "/out/leaf/operator.jwt",
+                "url": "nats://127.0.0.1:4223"
+            }
+        },
+        "accounts": {
+            "APP": {
+                "id": "AAPP",
+                "jwt_path": "/out/hub/APP.jwt",
+                "creds": { "app-user": "/out/hub/APP-app-user.creds" },
+                "bearer_jwts": { "bearer-user": "/out/hub/APP-bearer-user.jwt" }
+            },
+            "SYS": { "id": "ASYS" }
+        }
+    }))?;
+    assert_eq!(manifest.version, 1);
+    assert_eq!(manifest.servers["leaf"].resolver_dir, None);
+    assert!(manifest.accounts["SYS"].creds.is_empty());
+
+    let round_trip: SetupManifest = serde_json::from_str(&manifest.to_json())?;
+    assert_eq!(round_trip, manifest);
+    Ok(())
+}
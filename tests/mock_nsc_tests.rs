@@ -0,0 +1,314 @@
+#![cfg(unix)]
+
+use natsforge::{
+    config::NatsConfig,
+    progress::{CancellationToken, SetupEvent},
+    timing::{PHASE_ACCOUNTS, PHASE_IMPORTS, PHASE_OPERATOR, PHASE_RENDER, PHASE_USERS, PHASE_WRITES},
+    NatsForge, NatsForgeError, NscBackend,
+};
+use serde_json::json;
+use tokio::sync::mpsc;
+
+mod support;
+
+use support::MockNscRunner;
+
+fn forge(config: serde_json::Value) -> anyhow::Result<(NatsForge, MockNscRunner)> {
+    let config: NatsConfig = serde_json::from_value(config)?;
+    let runner = MockNscRunner::default();
+    let backend = NscBackend::new(None).with_runner(Box::new(runner.clone()));
+    Ok((NatsForge::with_backend(config, Box::new(backend))?, runner))
+}
+
+#[tokio::test]
+async fn test_exporters_are_created_before_imports() -> anyhow::Result<()> {
+    let (forge, nsc) = forge(json!({
+        "operator": { "name": "mock-operator", "reuse_existing": false },
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [
+            {
+                "name": "app",
+                "port": 4223,
+                "output_dir": "/srv/app",
+                "accounts": [{
+                    "name": "APP",
+                    "imports": [
+                        { "subject": "events.>", "account": "EVENTS" },
+                        { "subject": "audit.>", "account": "EVENTS" }
+                    ]
+                }]
+            },
+            {
+                "name": "events",
+                "port": 4222,
+                "output_dir": "/srv/events",
+                "accounts": [{
+                    "name": "EVENTS",
+                    "exports": [{ "subject": "events.>" }, { "subject": "audit.>", "private": true }]
+                }]
+            }
+        ]
+    }))?;
+    let name = |account: &str| {
+        forge
+            .config()
+            .servers
+            .iter()
+            .flat_map(|server| &server.accounts)
+            .find(|a| a.name == account)
+            .map(|a| a.unique_name.clone())
+            .unwrap()
+    };
+    let (app, events) = (name("APP"), name("EVENTS"));
+    let result = forge.initialize().await?;
+
+    let add_events = nsc.position(&["add", "account"], &[("--name", &events)]).unwrap();
+    let add_app = nsc.position(&["add", "account"], &[("--name", &app)]).unwrap();
+    assert!(add_events < add_app, "{:#?}", nsc.calls());
+
+    let public = nsc
+        .position(
+            &["add", "import"],
+            &[
+                ("--account", &app),
+                ("--src-account", &events),
+                ("--remote-subject", "events.>"),
+            ],
+        )
+        .unwrap();
+    assert!(add_app < public, "{:#?}", nsc.calls());
+
+    // The private export needs an activation for APP, generated before the import uses it.
+    let app_id = result.account_id("APP").unwrap();
+    let activation = nsc
+        .position(
+            &["generate", "activation"],
+            &[
+                ("--account", &events),
+                ("--target-account", app_id),
+                ("--subject", "audit.>"),
+            ],
+        )
+        .unwrap();
+    let private = nsc
+        .position(
+            &["add", "import"],
+            &[("--account", &app), ("--remote-subject", "audit.>")],
+        )
+        .unwrap();
+    assert!(activation < private, "{:#?}", nsc.calls());
+    assert!(nsc.calls()[private].contains(&"--token".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_remote_creds_are_copied_to_the_leaf() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let (hub_dir, leaf_dir) = (output_dir.path().join("hub"), output_dir.path().join("leaf"));
+    let (forge, nsc) = forge(json!({
+        "operator": { "name": "mock-operator", "reuse_existing": false },
+        "durability": "fast",
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4222,
+                "output_dir": hub_dir,
+                "leafnodes": { "port": 7422 },
+                "accounts": [{ "name": "APP", "users": [{ "name": "leaf" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": leaf_dir,
+                "leafnodes": {
+                    "remotes": [{ "url": "nats-leaf://localhost:7422", "account": "APP", "credentials": "APP-leaf.creds" }]
+                }
+            }
+        ]
+    }))?;
+    let result = forge.initialize().await?;
+
+    assert!(nsc.position(&["generate", "creds"], &[("--name", "leaf")]).is_some());
+    let issued = std::fs::read_to_string(hub_dir.join("APP-leaf.creds"))?;
+    assert!(issued.contains("-----BEGIN USER NKEY SEED-----"), "{}", issued);
+    let leaf_dir = std::fs::canonicalize(&leaf_dir)?;
+    assert_eq!(std::fs::read_to_string(leaf_dir.join("APP-leaf.creds"))?, issued);
+    let leaf_config = std::fs::read_to_string(&result.servers["leaf"])?;
+    assert!(
+        leaf_config.contains(&leaf_dir.join("APP-leaf.creds").display().to_string()),
+        "{}",
+        leaf_config
+    );
+    Ok(())
+}
+
+fn progress_config() -> serde_json::Value {
+    json!({
+        "operator": { "name": "mock-operator", "reuse_existing": false },
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "concurrency": 1,
+        "servers": [{
+            "name": "app",
+            "port": 4222,
+            "output_dir": "/srv/app",
+            "accounts": [
+                {
+                    "name": "APP",
+                    "users": [{ "name": "web" }],
+                    "imports": [{ "subject": "events.>", "account": "EVENTS" }]
+                },
+                { "name": "EVENTS", "users": [{ "name": "feed" }], "exports": [{ "subject": "events.>" }] }
+            ]
+        }]
+    })
+}
+
+/// The nsc invocations that create something, one per [`SetupEvent`] before the server configs.
+fn creations(nsc: &MockNscRunner) -> usize {
+    nsc.calls()
+        .iter()
+        .filter(|call| {
+            let command: Vec<&str> = call.iter().take(2).map(String::as_str).collect();
+            matches!(
+                command.as_slice(),
+                ["init", ..] | ["add", "account" | "user" | "import"]
+            )
+        })
+        .count()
+}
+
+#[tokio::test]
+async fn test_progress_events_follow_creation() -> anyhow::Result<()> {
+    let (forge, nsc) = forge(progress_config())?;
+    let operator = forge.config().operator.name.clone();
+    let (tx, mut rx) = mpsc::channel(4);
+    let collect = async {
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+        events
+    };
+    let (result, events) = tokio::join!(forge.initialize_with_progress(tx), collect);
+    result?;
+
+    let user = |account: &str, user: &str| SetupEvent::UserCreated {
+        account: account.to_string(),
+        user: user.to_string(),
+    };
+    assert_eq!(
+        events,
+        vec![
+            SetupEvent::OperatorCreated { operator },
+            SetupEvent::AccountCreated {
+                name: "EVENTS".to_string()
+            },
+            user("EVENTS", "feed"),
+            SetupEvent::AccountCreated {
+                name: "APP".to_string()
+            },
+            user("APP", "web"),
+            SetupEvent::ImportAdded {
+                account: "APP".to_string(),
+                from: "EVENTS".to_string(),
+                subject: "events.>".to_string()
+            },
+            SetupEvent::ServerConfigWritten {
+                server: "app".to_string()
+            },
+        ]
+    );
+    assert_eq!(creations(&nsc), events.len() - 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancelling_stops_before_the_next_nsc_call() -> anyhow::Result<()> {
+    for cancel_after in 1..=5 {
+        let (forge, nsc) = forge(progress_config())?;
+        let cancel = CancellationToken::new();
+        let forge = forge.with_cancellation(cancel.clone());
+        let (tx, mut rx) = mpsc::channel(1);
+        let collect = async {
+            let mut events = Vec::new();
+            while let Some(event) = rx.recv().await {
+                events.push(event);
+                if events.len() == cancel_after {
+                    cancel.cancel();
+                }
+            }
+            events
+        };
+        let (result, events) = tokio::join!(forge.initialize_with_progress(tx), collect);
+
+        let Err(NatsForgeError::Cancelled { created }) = result else {
+            panic!("not cancelled after {} events: {:?}", cancel_after, result.map(|_| ()));
+        };
+        assert_eq!(created, events);
+        assert_eq!(created.len(), cancel_after);
+        assert_eq!(creations(&nsc), cancel_after, "{:#?}", nsc.calls());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cancelled_before_start_creates_nothing() -> anyhow::Result<()> {
+    let (forge, nsc) = forge(progress_config())?;
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let err = forge.with_cancellation(cancel).initialize().await.unwrap_err();
+
+    assert!(
+        matches!(&err, NatsForgeError::Cancelled { created } if created.is_empty()),
+        "{}",
+        err
+    );
+    assert_eq!(err.to_string(), "setup cancelled before creating anything");
+    assert_eq!(creations(&nsc), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_every_phase_and_item_is_timed() -> anyhow::Result<()> {
+    let (forge, _) = forge(progress_config())?;
+    let operator = forge.config().operator.name.clone();
+    let timings = forge.initialize().await?.timings;
+
+    for phase in [
+        PHASE_OPERATOR,
+        PHASE_ACCOUNTS,
+        PHASE_USERS,
+        PHASE_IMPORTS,
+        PHASE_RENDER,
+        PHASE_WRITES,
+    ] {
+        assert!(
+            timings.phase(phase).is_some_and(|d| !d.is_zero()),
+            "{}: {:#?}",
+            phase,
+            timings
+        );
+    }
+    let items = [
+        (PHASE_OPERATOR, operator.as_str()),
+        (PHASE_ACCOUNTS, "EVENTS"),
+        (PHASE_ACCOUNTS, "APP"),
+        (PHASE_USERS, "EVENTS/feed"),
+        (PHASE_USERS, "APP/web"),
+        (PHASE_IMPORTS, "APP: events.> from EVENTS"),
+    ];
+    for (phase, item) in items {
+        assert!(
+            timings.item(phase, item).is_some_and(|d| !d.is_zero()),
+            "{}: {:#?}",
+            item,
+            timings
+        );
+    }
+    assert_eq!(timings.items.len(), items.len());
+    assert!(!timings.processes.is_empty());
+    assert!(timings.breakdown_table().contains("slowest items"));
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use natsforge::{config::NatsConfig, NatsForge};
+use serde_json::json;
+
+fn config(jetstream: bool, mqtt: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "mqtt-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "iot",
+            "port": 4222,
+            "output_dir": "iot",
+            "jetstream": { "enabled": jetstream },
+            "mqtt": mqtt,
+            "accounts": [{ "name": "FLEET", "users": [{ "name": "sensor", "bearer": true }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_mqtt_block() -> anyhow::Result<()> {
+    let mqtt = json!({
+        "port": 1883,
+        "tls": { "cert_file": "mqtt.pem", "key_file": "mqtt-key.pem" },
+        "ack_wait": "30s",
+        "max_ack_pending": 100,
+        "jetstream_domain": "edge"
+    });
+    let result = NatsForge::from_config(config(true, mqtt)?)?.initialize().await?;
+
+    let server_config = &result.artifacts.as_ref().unwrap().server_configs["iot"];
+    let expected = "mqtt {
+    port: 1883
+    tls {
+        cert_file: \"mqtt.pem\"
+        key_file: \"mqtt-key.pem\"
+    }
+    ack_wait: \"30s\"
+    max_ack_pending: 100
+    js_domain: \"edge\"
+}
+";
+    assert!(server_config.contains(expected), "{}", server_config);
+
+    let result = NatsForge::from_config(config(true, json!({ "port": 1883 }))?)?
+        .initialize()
+        .await?;
+    let server_config = &result.artifacts.as_ref().unwrap().server_configs["iot"];
+    assert!(
+        server_config.contains("mqtt {\n    port: 1883\n}\n"),
+        "{}",
+        server_config
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mqtt_needs_jetstream() -> anyhow::Result<()> {
+    let err = config(false, json!({ "port": 1883 }))?.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server iot has an MQTT listener but JetStream disabled"),
+        "{}",
+        err
+    );
+
+    let err = config(true, json!({ "port": 4222 }))?.validate().unwrap_err();
+    assert!(
+        err.to_string().contains("uses port 4222 for both clients and MQTT"),
+        "{}",
+        err
+    );
+    let err = config(true, json!({ "port": 1883, "ack_wait": "a while" }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid MQTT ack_wait for server iot"),
+        "{}",
+        err
+    );
+    Ok(())
+}
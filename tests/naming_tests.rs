@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use natsforge::{
+    config::{NamingMode, NatsConfig},
+    NatsForge, NatsForgeError,
+};
+use serde_json::json;
+
+fn config(naming: NamingMode, backend: &str, store_dir: Option<&Path>, reuse: bool) -> anyhow::Result<NatsConfig> {
+    let mut config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "naming-operator", "reuse_existing": reuse },
+        "backend": backend,
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "naming-server",
+            "port": 4222,
+            "output_dir": "/srv/naming",
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+        }]
+    }))?;
+    config.naming = naming;
+    config.nsc_store_dir = store_dir.map(Path::to_path_buf);
+    Ok(config)
+}
+
+#[test]
+fn test_unique_naming_suffixes_store_names() -> anyhow::Result<()> {
+    let forge = NatsForge::from_config(config(NamingMode::Unique, "native", None, false)?)?;
+    let operator = &forge.config().operator.name;
+    assert!(operator.starts_with("naming-operator-"), "{}", operator);
+    let account = &forge.config().servers[0].accounts[0];
+    assert_eq!(account.name, "APP");
+    assert!(account.unique_name.starts_with("APP-"), "{}", account.unique_name);
+    Ok(())
+}
+
+#[test]
+fn test_exact_naming_keeps_names() -> anyhow::Result<()> {
+    let forge = NatsForge::from_config(config(NamingMode::Exact, "native", None, false)?)?;
+    assert_eq!(forge.config().operator.name, "naming-operator");
+    let account = &forge.config().servers[0].accounts[0];
+    assert_eq!((account.name.as_str(), account.unique_name.as_str()), ("APP", "APP"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exact_naming_rejects_existing_operator_native() -> anyhow::Result<()> {
+    let forge = NatsForge::from_config(config(NamingMode::Exact, "native", None, false)?)?;
+    forge.initialize().await?;
+    let err = forge.initialize().await.unwrap_err();
+    assert!(
+        matches!(&err, NatsForgeError::AlreadyInStore { kind: "operator", name } if name == "naming-operator"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exact_naming_reuses_persistent_store() -> anyhow::Result<()> {
+    let store_dir = tempfile::TempDir::new()?;
+    let first = NatsForge::from_config(config(NamingMode::Exact, "nsc", Some(store_dir.path()), false)?)?
+        .initialize()
+        .await?;
+    let second = NatsForge::from_config(config(NamingMode::Exact, "nsc", Some(store_dir.path()), true)?)?
+        .initialize()
+        .await?;
+    assert_eq!(first.account_id("APP"), second.account_id("APP"));
+    assert!(second.artifacts.as_ref().unwrap().creds("APP", "app-user").is_some());
+
+    let err = NatsForge::from_config(config(NamingMode::Exact, "nsc", Some(store_dir.path()), false)?)?
+        .initialize()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, NatsForgeError::AlreadyInStore { .. }), "{}", err);
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use natsforge::{
+    conf::ConfValue,
+    config::{AuthMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn config(no_auth_user: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "no-auth-operator", "reuse_existing": false },
+        "backend": "native",
+        "servers": [{
+            "name": "dev",
+            "port": 4222,
+            "output_dir": "unused",
+            "no_auth_user": no_auth_user,
+            "accounts": [
+                { "name": "APP", "users": [{ "name": "guest" }] },
+                { "name": "APP-guest", "users": [{ "name": "admin" }] }
+            ]
+        }]
+    }))?)
+}
+
+#[test]
+fn test_no_auth_user_must_be_generated() -> anyhow::Result<()> {
+    let err = config("guest")?.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server dev has no_auth_user guest, which isn't one of its users; name it as <account>-<user>"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_no_auth_user_needs_users_in_nats_conf() -> anyhow::Result<()> {
+    // Account names may contain dashes, so the reference is matched whole.
+    for no_auth_user in ["APP-guest", "APP-guest-admin"] {
+        let config = config(no_auth_user)?;
+        let err = config.validate().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Server dev sets no_auth_user, which nats-server doesn't allow with a trusted operator"),
+            "{}",
+            err
+        );
+        assert!(
+            config.warnings().iter().any(|warning| warning.starts_with(&format!(
+                "SECURITY: server dev logs in clients without credentials as {}",
+                no_auth_user
+            ))),
+            "{:?}",
+            config.warnings()
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_default_permissions_need_users_in_nats_conf() -> anyhow::Result<()> {
+    let mut config = config("APP-guest")?;
+    config.servers[0].no_auth_user = None;
+    config.servers[0].default_permissions = Some(serde_json::from_value(json!({
+        "publish": { "deny": ["orders..bad"] },
+        "subscribe": { "allow": ["_INBOX.>"] }
+    }))?);
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("default_permissions of server dev"), "{}", err);
+
+    config.servers[0].default_permissions.as_mut().unwrap().publish.deny = vec!["orders.>".to_string()];
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server dev sets default_permissions, which only apply to users defined in nats.conf"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_no_auth_user_in_conf_mode() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config("APP-guest")?;
+    config.servers[0].output_dir = dir.path().to_path_buf();
+    config.servers[0].auth_mode = AuthMode::Conf;
+    config.servers[0].default_permissions = Some(serde_json::from_value(json!({
+        "subscribe": { "deny": ["admin.>"] }
+    }))?);
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    // nats-server knows nkey users by their public key.
+    let seed = std::fs::read_to_string(result.user_secret("APP", "guest").unwrap())?;
+    let public_key = nkeys::KeyPair::from_seed(&seed)?.public_key();
+    let document = forge.server_document("dev")?;
+    assert_eq!(
+        document.root.get("no_auth_user").and_then(ConfValue::as_str),
+        Some(public_key.as_str())
+    );
+    assert!(
+        document
+            .render()
+            .contains("default_permissions {\n    subscribe {\n        deny: [\"admin.>\"]\n"),
+        "{}",
+        document.render()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_logs_in_no_auth_user() -> anyhow::Result<()> {
+    let port = 4277;
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config("APP-guest")?;
+    config.servers[0].port = port;
+    config.servers[0].output_dir = dir.path().to_path_buf();
+    config.servers[0].auth_mode = AuthMode::Conf;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("dev").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let url = format!("localhost:{}", port);
+    let seed = std::fs::read_to_string(result.user_secret("APP", "guest").unwrap())?;
+    let app = async_nats::ConnectOptions::with_nkey(seed).connect(&url).await?;
+    let seed = std::fs::read_to_string(result.user_secret("APP-guest", "admin").unwrap())?;
+    let other = async_nats::ConnectOptions::with_nkey(seed).connect(&url).await?;
+    let mut app_messages = app.subscribe("probe").await?;
+    let mut other_messages = other.subscribe("probe").await?;
+    app.flush().await?;
+    other.flush().await?;
+
+    // Only a client in APP sees what the anonymous client publishes.
+    let anonymous = async_nats::connect(&url).await?;
+    anonymous.publish("probe", "hello".into()).await?;
+    anonymous.flush().await?;
+    let message = tokio::time::timeout(Duration::from_secs(5), app_messages.next())
+        .await?
+        .context("subscription closed")?;
+    assert_eq!(message.payload.as_ref(), b"hello");
+    assert!(tokio::time::timeout(Duration::from_millis(500), other_messages.next())
+        .await
+        .is_err());
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
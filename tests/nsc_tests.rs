@@ -0,0 +1,334 @@
+#![cfg(unix)]
+
+use std::{
+    collections::HashMap,
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use natsforge::{
+    config::{AccountConfig, ImportConfig, NatsConfig},
+    env::{ProcessEnv, BASE_INHERITED_ENV},
+    CredentialBackend, NatsForge, NatsForgeError, NscBackend,
+};
+use tempfile::TempDir;
+
+fn fake_nsc(dir: &TempDir, version_output: &str) -> anyhow::Result<PathBuf> {
+    let path = dir.path().join("nsc");
+    std::fs::write(&path, format!("#!/bin/sh\necho \"{}\"\n", version_output))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(path)
+}
+
+#[tokio::test]
+async fn test_nsc_version_accepted() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    for output in ["nsc version 2.10.2", "nsc version v2.8.6"] {
+        let backend = NscBackend::new(Some(fake_nsc(&dir, output)?));
+        backend.preflight().await?;
+    }
+    let backend = NscBackend::new(Some(fake_nsc(&dir, "nsc version 2.11.0")?));
+    assert_eq!(backend.check_version().await?, "2.11.0");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nsc_version_too_old() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let backend = NscBackend::new(Some(fake_nsc(&dir, "nsc version 2.2.0")?));
+    let err = backend.preflight().await.unwrap_err();
+    assert!(matches!(err, NatsForgeError::NscVersionUnsupported { .. }), "{:?}", err);
+    let err = err.to_string();
+    assert!(err.contains("older than the minimum supported version"), "{}", err);
+    assert!(err.contains("configured via nsc_path"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nsc_missing() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let backend = NscBackend::new(Some(dir.path().join("does-not-exist")));
+    let err = backend.preflight().await.unwrap_err();
+    assert!(matches!(err, NatsForgeError::NscNotFound { .. }), "{:?}", err);
+    assert!(err.to_string().contains("nsc not found"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nsc_process_env() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let dump = dir.path().join("env.txt");
+    let path = dir.path().join("nsc");
+    std::fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\n/usr/bin/env > \"{}\"\necho \"nsc version 2.10.2\"\n",
+            dump.display()
+        ),
+    )?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+
+    std::env::set_var("NATSFORGE_TEST_INHERITED", "inherited");
+    std::env::set_var("NATSFORGE_TEST_SCRUBBED", "scrubbed");
+    let config = NatsConfig {
+        inherit_env: vec!["NATSFORGE_TEST_INHERITED".to_string()],
+        process_env: HashMap::from([("HTTPS_PROXY".to_string(), "http://proxy:3128".to_string())]),
+        ..Default::default()
+    };
+    config.validate()?;
+    NscBackend::new(Some(path))
+        .with_env(ProcessEnv::from_config(&config))
+        .preflight()
+        .await?;
+
+    let dumped = std::fs::read_to_string(&dump)?;
+    let vars: HashMap<&str, &str> = dumped.lines().filter_map(|l| l.split_once('=')).collect();
+    assert_eq!(vars.get("NATSFORGE_TEST_INHERITED"), Some(&"inherited"));
+    assert_eq!(vars.get("HTTPS_PROXY"), Some(&"http://proxy:3128"));
+    // The shell may export a few variables of its own.
+    let shell_vars = ["PWD", "OLDPWD", "SHLVL", "_"];
+    for name in vars.keys() {
+        assert!(
+            BASE_INHERITED_ENV.contains(name)
+                || shell_vars.contains(name)
+                || ["NATSFORGE_TEST_INHERITED", "HTTPS_PROXY"].contains(name),
+            "unexpected variable {} in nsc environment",
+            name
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_process_env_rejects_secrets() {
+    let seed = format!("SU{}", "A".repeat(56));
+    for (name, value) in [
+        ("NATS_TOKEN", "abc"),
+        ("NKEY", seed.as_str()),
+        ("CA_BUNDLE", "-----BEGIN USER NKEY SEED-----"),
+    ] {
+        let config = NatsConfig {
+            process_env: HashMap::from([(name.to_string(), value.to_string())]),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.validate(), Err(NatsForgeError::InvalidConfig(_))),
+            "{} accepted",
+            name
+        );
+    }
+
+    let config = NatsConfig {
+        inherit_env: vec!["NATS_USER_SEED".to_string()],
+        ..Default::default()
+    };
+    assert!(matches!(config.validate(), Err(NatsForgeError::InvalidConfig(_))));
+}
+
+#[tokio::test]
+async fn test_nsc_push_report() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("nsc");
+    let output = r#"server push results:
+[ OK ] push to nats-server "nats://localhost:4222" using system account "SYS":
+       [ OK ] push APP to nats-server with nats account resolver:
+              [ OK ] pushed "APP" to nats-server NDX: jwt updated
+              [ OK ] pushed to a total of 1 nats-server
+       [ X ] push LIMITS to nats-server with nats account resolver:
+              [ERR ] pushed "LIMITS" to nats-server NDX: jwt update resulted in error: not trusted"#;
+    std::fs::write(&path, format!("#!/bin/sh\ncat >&2 <<'EOF'\n{}\nEOF\nexit 1\n", output))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+
+    let backend = NscBackend::new(Some(path));
+    let report = backend.push_accounts("op", "nats://localhost:4222", dir.path()).await?;
+    assert_eq!(report.accounts.len(), 2, "{:?}", report);
+    assert_eq!(report.accounts[0].account, "APP");
+    assert_eq!(report.accounts[0].error, None);
+    let failures: Vec<_> = report.failures().collect();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].account, "LIMITS");
+    assert_eq!(
+        failures[0].error.as_deref(),
+        Some("jwt update resulted in error: not trusted")
+    );
+    assert!(!report.is_success());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nsc_home_under_store_dir() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let store_dir = TempDir::new()?;
+    let dump = dir.path().join("invocation.txt");
+    let path = dir.path().join("nsc");
+    std::fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\n{{ /usr/bin/env; echo \"ARGS=$*\"; }} > \"{}\"\n",
+            dump.display()
+        ),
+    )?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+
+    NscBackend::new(Some(path))
+        .push_accounts("op", "nats://localhost:4222", store_dir.path())
+        .await?;
+
+    let dumped = std::fs::read_to_string(&dump)?;
+    let vars: HashMap<&str, &str> = dumped.lines().filter_map(|l| l.split_once('=')).collect();
+    for name in ["NSC_HOME", "NKEYS_PATH", "XDG_DATA_HOME", "XDG_CONFIG_HOME"] {
+        let value = PathBuf::from(vars[name]);
+        assert!(value.starts_with(store_dir.path()), "{}={}", name, value.display());
+        assert!(value.is_dir(), "{} not created", value.display());
+    }
+    assert!(
+        vars["ARGS"].contains(&format!("--keystore-dir {}", vars["NKEYS_PATH"])),
+        "{}",
+        vars["ARGS"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_concurrent_forges_stay_in_their_stores() -> anyhow::Result<()> {
+    let home = TempDir::new()?;
+    let stores = [TempDir::new()?, TempDir::new()?];
+    let forges = stores
+        .iter()
+        .map(|store| {
+            let mut config: NatsConfig = serde_json::from_value(serde_json::json!({
+                "operator": { "name": "isolated-operator" },
+                "artifact_mode": "in_memory",
+                "servers": [{
+                    "name": "isolated-server",
+                    "port": 4222,
+                    "output_dir": "/srv/isolated",
+                    "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+                }]
+            }))?;
+            config.nsc_store_dir = Some(store.path().to_path_buf());
+            config.process_env = HashMap::from([("HOME".to_string(), home.path().display().to_string())]);
+            Ok(NatsForge::from_config(config)?)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let (first, second) = tokio::join!(forges[0].initialize(), forges[1].initialize());
+    first?;
+    second?;
+
+    let leaked: Vec<_> = std::fs::read_dir(home.path())?.collect::<std::io::Result<_>>()?;
+    assert!(leaked.is_empty(), "nsc wrote into HOME: {:?}", leaked);
+    for store in &stores {
+        let keys = store.path().join(".nsc-home/keys");
+        assert!(
+            std::fs::read_dir(&keys)?.next().is_some(),
+            "no keys in {}",
+            keys.display()
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nsc_timeout_kills_wedged_nsc() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let path = dir.path().join("nsc");
+    std::fs::write(&path, "#!/bin/sh\nexec sleep 30\n")?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+
+    let backend = NscBackend::new(Some(path))
+        .with_timeout(Duration::from_millis(200))
+        .with_retries(0);
+    let started = Instant::now();
+    let err = backend
+        .push_accounts("op", "nats://localhost:4222", dir.path())
+        .await
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(10), "{:?}", started.elapsed());
+    let NatsForgeError::NscTimedOut { command, argv, timeout } = &err else {
+        panic!("expected a timeout, got {:?}", err);
+    };
+    assert_eq!((command.as_str(), *timeout), ("push", Duration::from_millis(200)));
+    assert!(argv.iter().any(|arg| arg == "nats://localhost:4222"), "{:?}", argv);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nsc_failure_diagnostics_and_retries() -> anyhow::Result<()> {
+    let dir = TempDir::new()?;
+    let calls = dir.path().join("calls");
+    let path = dir.path().join("nsc");
+    std::fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\necho call >> \"{}\"\necho partial output\necho keystore locked >&2\nexit 3\n",
+            calls.display()
+        ),
+    )?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    let backend = NscBackend::new(Some(path)).with_retries(2);
+    let account = AccountConfig {
+        name: "APP".to_string(),
+        unique_name: "APP".to_string(),
+        ..Default::default()
+    };
+
+    // Generating an activation only reads the store, so it's retried.
+    let import: ImportConfig = serde_json::from_value(serde_json::json!({ "subject": "svc.>", "account": "SRC" }))?;
+    let err = backend
+        .generate_activation(&import, "SRC", "ADST", dir.path())
+        .await
+        .unwrap_err();
+    assert_eq!(std::fs::read_to_string(&calls)?.lines().count(), 3);
+    let NatsForgeError::NscCommandFailed {
+        command,
+        argv,
+        exit_code,
+        stdout,
+        stderr,
+    } = &err
+    else {
+        panic!("expected a failed command, got {:?}", err);
+    };
+    assert_eq!(command, "generate activation");
+    assert_eq!(*exit_code, Some(3));
+    assert_eq!(
+        (stdout.as_str(), stderr.as_str()),
+        ("partial output", "keystore locked")
+    );
+    assert!(argv.iter().any(|arg| arg == "--target-account"), "{:?}", argv);
+    let message = err.to_string();
+    assert!(
+        message.contains("exit code 3") && message.contains("partial output"),
+        "{}",
+        message
+    );
+
+    // Revoking changes the store, so it runs once; a seed never ends up in the error.
+    std::fs::remove_file(&calls)?;
+    let seed = format!("SU{}", "A".repeat(56));
+    let err = backend.revoke_user(&account, &seed, dir.path()).await.unwrap_err();
+    assert_eq!(std::fs::read_to_string(&calls)?.lines().count(), 1);
+    let NatsForgeError::NscCommandFailed { argv, .. } = &err else {
+        panic!("expected a failed command, got {:?}", err);
+    };
+    assert!(argv.iter().any(|arg| arg == "<redacted>"), "{:?}", argv);
+    assert!(!err.to_string().contains(&seed), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn test_nsc_timeout_validated() {
+    for timeout in ["0s", "soon"] {
+        let config = NatsConfig {
+            nsc_timeout: Some(timeout.to_string()),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.validate(), Err(NatsForgeError::InvalidConfig(_))),
+            "{} accepted",
+            timeout
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use anyhow::Context;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig, OperatorRef},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn config(output_dir: &Path, port: u16) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "operator-ref-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "hub",
+            "port": port,
+            "output_dir": output_dir,
+            "operator_reference": "file",
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_operator_inline_by_default() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path(), 4222)?;
+    config.servers[0].operator_reference = OperatorRef::Inline;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let conf = std::fs::read_to_string(result.server_config("hub").unwrap())?;
+    let operator_jwt = std::fs::read_to_string(&result.operator_jwt_path)?;
+    assert!(conf.contains(&format!("operator: \"{}\"\n", operator_jwt)), "{}", conf);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_operator_file() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path(), 4222)?)?.initialize().await?;
+
+    let conf = std::fs::read_to_string(result.server_config("hub").unwrap())?;
+    let operator_jwt_path = std::fs::canonicalize(dir.path())?.join("operator.jwt");
+    assert!(
+        conf.contains(&format!("operator: \"{}\"\n", operator_jwt_path.display())),
+        "{}",
+        conf
+    );
+    let operator_jwt = std::fs::read_to_string(&operator_jwt_path)?;
+    assert!(!conf.contains(&operator_jwt), "{}", conf);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_operator_file_in_memory() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/hub"), 4222)?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let artifacts = result.artifacts.as_ref().unwrap();
+    assert!(artifacts.server_configs["hub"].contains("operator: \"/srv/hub/operator.jwt\"\n"));
+    assert_eq!(
+        artifacts.server_files["hub"][Path::new("/srv/hub/operator.jwt")],
+        artifacts.operator_jwt
+    );
+    Ok(())
+}
+
+#[test]
+fn test_operator_file_in_memory_needs_an_absolute_path() -> anyhow::Result<()> {
+    let mut config = config(Path::new("hub"), 4222)?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server hub refers to operator.jwt by path, so in-memory mode needs an absolute output_dir"),
+        "{}",
+        err
+    );
+
+    // Written configs get the path made absolute.
+    config.artifact_mode = ArtifactMode::Files;
+    config.validate()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_starts_with_operator_file() -> anyhow::Result<()> {
+    let port = 4275;
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path(), port)?)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("hub").unwrap())
+        .current_dir(std::env::temp_dir())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "app-user").unwrap())?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+    client.flush().await?;
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::Engine;
+use futures_util::StreamExt;
+use natsforge::{
+    conf::ConfValue,
+    config::{ArtifactMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(output_dir: &str, operator: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    let mut named = json!({ "name": "url-operator", "reuse_existing": false });
+    named
+        .as_object_mut()
+        .unwrap()
+        .extend(operator.as_object().unwrap().clone());
+    Ok(serde_json::from_value(json!({
+        "operator": named,
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "hub",
+            "port": 4222,
+            "output_dir": output_dir,
+            "resolver": { "full": {} },
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_operator_urls_claims() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let operator = json!({
+        "account_server_url": "nats://hub.example.com:4222",
+        "service_urls": ["nats://hub.example.com:4222", "tls://hub.example.com:4443"]
+    });
+    let config = config(output_dir.path().to_str().unwrap(), operator)?;
+    assert!(config
+        .warnings()
+        .iter()
+        .all(|warning| !warning.contains("account_server_url")));
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let operator = claims(&std::fs::read_to_string(&result.operator_jwt_path)?)?;
+    assert_eq!(operator["nats"]["account_server_url"], "nats://hub.example.com:4222");
+    assert_eq!(
+        operator["nats"]["operator_service_urls"],
+        json!(["nats://hub.example.com:4222", "tls://hub.example.com:4443"])
+    );
+    Ok(())
+}
+
+#[test]
+fn test_operator_urls_are_validated() -> anyhow::Result<()> {
+    let err = config("/srv/hub", json!({ "account_server_url": "hub:4222" }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Operator account_server_url hub:4222 must be an http://, https:// or nats:// URL"),
+        "{}",
+        err
+    );
+
+    let err = config("/srv/hub", json!({ "service_urls": ["http://hub:4222"] }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Operator service URL http://hub:4222 must be a nats:// or tls:// URL"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_full_resolver_suggests_account_server_url() -> anyhow::Result<()> {
+    let config = config("/srv/hub", json!({}))?;
+    assert!(
+        config.warnings().iter().any(|warning| warning.starts_with(
+            "Server hub keeps account JWTs in a full resolver, but the operator has no account_server_url"
+        )),
+        "{:?}",
+        config.warnings()
+    );
+    Ok(())
+}
+
+/// A hub and a leaf in separate security domains, the leaf binding its LEAF account to the hub's
+/// HUB account through the creds of HUB's bridge user.
+fn two_operators(output_dir: &std::path::Path, ports: [u16; 3]) -> anyhow::Result<NatsConfig> {
+    let [hub_port, leaf_port, leafnode_port] = ports;
+    Ok(serde_json::from_value(json!({
+        "operators": [{ "name": "hub-operator" }, { "name": "leaf-operator" }],
+        "backend": "native",
+        "durability": "fast",
+        "servers": [
+            {
+                "name": "hub",
+                "port": hub_port,
+                "output_dir": output_dir.join("hub"),
+                "leafnodes": { "port": leafnode_port },
+                "accounts": [{ "name": "HUB", "users": [{ "name": "bridge" }, { "name": "listener" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": leaf_port,
+                "output_dir": output_dir.join("leaf"),
+                "operator": "leaf-operator",
+                "leafnodes": {
+                    "remotes": [{
+                        "url": format!("nats://localhost:{}", leafnode_port),
+                        "account": "LEAF",
+                        "credentials": "HUB-bridge.creds",
+                        "server": "hub"
+                    }]
+                },
+                "accounts": [{ "name": "LEAF", "users": [{ "name": "app" }] }]
+            }
+        ]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_servers_trust_their_own_operator() -> anyhow::Result<()> {
+    let mut config = two_operators(std::path::Path::new("/srv"), [4222, 4223, 7422])?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    config.servers[0].include_all_accounts = true;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let artifacts = result.artifacts.as_ref().unwrap();
+    assert_eq!(artifacts.operator_jwts.len(), 2);
+
+    let leaf_operator = &forge.config().operators[1].name;
+    assert!(leaf_operator.starts_with("leaf-operator-"), "{}", leaf_operator);
+    let leaf_operator = claims(&artifacts.operator_jwts[leaf_operator])?;
+    let leaf_account = claims(&artifacts.account_jwts["LEAF"])?;
+    assert_eq!(leaf_account["iss"], leaf_operator["sub"]);
+    let document = forge.server_document("leaf")?;
+    assert_eq!(
+        document.root.get("system_account").and_then(ConfValue::as_str),
+        leaf_operator["nats"]["system_account"].as_str()
+    );
+
+    // Even with every account included, the hub only preloads its own operator's.
+    let hub_config = &artifacts.server_configs["hub"];
+    assert!(
+        !hub_config.contains(result.account_id("LEAF").unwrap()),
+        "{}",
+        hub_config
+    );
+    assert!(hub_config.contains(result.account_id("HUB").unwrap()), "{}", hub_config);
+    let leaf_remote = &artifacts.server_files["leaf"];
+    assert!(leaf_remote.keys().any(|path| path.ends_with("HUB-bridge.creds")));
+    Ok(())
+}
+
+#[test]
+fn test_operators_stay_apart() -> anyhow::Result<()> {
+    let dir = std::path::Path::new("/srv");
+    let mut config = two_operators(dir, [4222, 4223, 7422])?;
+    config.servers[1].operator = Some("other-operator".to_string());
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Server leaf trusts unknown operator other-operator"),
+        "{}",
+        err
+    );
+
+    let mut config = two_operators(dir, [4222, 4223, 7422])?;
+    config.servers[1].accounts[0].imports = vec![serde_json::from_value(json!({
+        "subject": "hub.events", "account": "HUB"
+    }))?];
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "Account LEAF imports from account HUB of operator hub-operator; imports stay within an operator"
+        ),
+        "{}",
+        err
+    );
+
+    let mut config = two_operators(dir, [4222, 4223, 7422])?;
+    config.servers[1].accounts[0].name = "HUB".to_string();
+    config.servers[1].leafnodes.remotes[0].account = "HUB".to_string();
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Account HUB is defined under operators hub-operator and leaf-operator"),
+        "{}",
+        err
+    );
+
+    let mut config = two_operators(dir, [4222, 4223, 7422])?;
+    for server in &mut config.servers {
+        server.cluster = Some(serde_json::from_value(json!({ "name": "shared", "port": 6222 }))?);
+    }
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Servers hub and leaf share cluster shared but trust different operators"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_messages_cross_operators_over_leafnode() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let (hub_port, leaf_port) = (4279, 4280);
+    let result = NatsForge::from_config(two_operators(dir.path(), [hub_port, leaf_port, 7479])?)?
+        .initialize()
+        .await?;
+
+    let mut guards = Vec::new();
+    for server in ["hub", "leaf"] {
+        let process = tokio::process::Command::new("nats-server")
+            .arg("-c")
+            .arg(result.server_config(server).unwrap())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to start NATS server")?;
+        guards.push(ServerGuard(process));
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    let listener = async_nats::ConnectOptions::with_credentials_file(result.creds("HUB", "listener").unwrap())
+        .await?
+        .connect(format!("localhost:{}", hub_port))
+        .await?;
+    let mut messages = listener.subscribe("greetings").await?;
+    listener.flush().await?;
+    // Interest reaches the leaf over the leafnode connection.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let app = async_nats::ConnectOptions::with_credentials_file(result.creds("LEAF", "app").unwrap())
+        .await?
+        .connect(format!("localhost:{}", leaf_port))
+        .await?;
+    app.publish("greetings", "hello".into()).await?;
+    app.flush().await?;
+    let message = tokio::time::timeout(Duration::from_secs(5), messages.next())
+        .await?
+        .context("subscription closed")?;
+    assert_eq!(message.payload.as_ref(), b"hello");
+
+    for mut guard in guards {
+        guard.0.kill().await.context("Failed to kill NATS server")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use base64::Engine;
+use natsforge::{
+    config::{NatsConfig, UserConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+#[tokio::test]
+async fn test_publish_and_subscribe_permissions_are_split() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "permissions-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "permissions-server",
+            "port": 4222,
+            "output_dir": output_dir.path(),
+            "accounts": [{
+                "name": "APP",
+                "users": [{
+                    "name": "requester",
+                    "allow_pub": ["orders.create"],
+                    "allow_sub": ["_INBOX.>"],
+                    "allow_pubsub": ["health"],
+                    "deny_pub": ["orders.delete"],
+                    "deny_sub": ["orders.audit"],
+                    "deny_pubsub": ["admin.>"]
+                }]
+            }]
+        }]
+    }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "requester").unwrap())?;
+    let nats = &claims(creds.lines().nth(1).unwrap())?["nats"];
+    assert_eq!(nats["pub"]["allow"], json!(["health", "orders.create"]));
+    assert_eq!(nats["sub"]["allow"], json!(["health", "_INBOX.>"]));
+    assert_eq!(nats["pub"]["deny"], json!(["admin.>", "orders.delete"]));
+    assert_eq!(nats["sub"]["deny"], json!(["admin.>", "orders.audit"]));
+    Ok(())
+}
+
+#[test]
+fn test_old_permission_names_are_accepted() -> anyhow::Result<()> {
+    let user: UserConfig = serde_json::from_value(json!({
+        "name": "legacy",
+        "allowed_publishes": ["a"],
+        "allowed_subjects": ["b"],
+        "allowed_pubsub": ["c"],
+        "denied_publishes": ["d"],
+        "denied_subjects": ["e"],
+        "denied_pubsub": ["f"]
+    }))?;
+    assert_eq!(
+        [
+            user.allow_pub,
+            user.allow_sub,
+            user.allow_pubsub,
+            user.deny_pub,
+            user.deny_sub,
+            user.deny_pubsub
+        ],
+        [["a"], ["b"], ["c"], ["d"], ["e"], ["f"]]
+    );
+
+    let user: UserConfig = serde_json::from_value(json!({ "name": "unrestricted" }))?;
+    assert!(user.allow_pub.is_empty() && user.allow_sub.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_response_permissions_config() -> anyhow::Result<()> {
+    let user: UserConfig = serde_json::from_value(json!({ "name": "flag", "allow_pub_response": true }))?;
+    assert_eq!(user.allow_pub_response, Some(1));
+    let user: UserConfig = serde_json::from_value(json!({ "name": "off", "allow_pub_response": false }))?;
+    assert_eq!(user.allow_pub_response, None);
+    let user: UserConfig = serde_json::from_value(json!({ "name": "count", "allow_pub_response": 5 }))?;
+    assert_eq!(user.allow_pub_response, Some(5));
+
+    let config = |user: serde_json::Value| -> anyhow::Result<NatsConfig> {
+        Ok(serde_json::from_value(json!({
+            "operator": { "name": "response-operator", "reuse_existing": false },
+            "servers": [{
+                "name": "response-server",
+                "port": 4222,
+                "output_dir": "unused",
+                "accounts": [{ "name": "APP", "users": [user] }]
+            }]
+        }))?)
+    };
+
+    let ttl_alone = config(json!({ "name": "svc", "response_ttl": "5s" }))?;
+    assert!(ttl_alone.validate().is_err());
+    let bad_ttl = config(json!({ "name": "svc", "allow_pub_response": 1, "response_ttl": "soon" }))?;
+    let err = bad_ttl.validate().unwrap_err();
+    assert!(err.to_string().contains("Invalid response_ttl for user svc"), "{}", err);
+    let compound = config(json!({ "name": "svc", "allow_pub_response": 1, "response_ttl": "1m30s" }))?;
+    compound.validate()?;
+
+    let inbox_denied = config(json!({ "name": "svc", "allow_pub_response": 1, "deny_pub": ["_INBOX.>"] }))?;
+    let warnings = inbox_denied.warnings();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("denies publishing to _INBOX.>"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_source_networks_claim() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "network-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "network-server",
+            "port": 4222,
+            "output_dir": output_dir.path(),
+            "accounts": [{
+                "name": "OPS",
+                "users": [
+                    { "name": "oncall", "source_networks": ["10.8.0.0/16", "fd00::/8"] },
+                    { "name": "anywhere" }
+                ]
+            }]
+        }]
+    }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let creds = std::fs::read_to_string(result.creds("OPS", "oncall").unwrap())?;
+    let nats = &claims(creds.lines().nth(1).unwrap())?["nats"];
+    assert_eq!(nats["src"], json!(["10.8.0.0/16", "fd00::/8"]));
+
+    let creds = std::fs::read_to_string(result.creds("OPS", "anywhere").unwrap())?;
+    assert!(claims(creds.lines().nth(1).unwrap())?["nats"].get("src").is_none());
+    Ok(())
+}
+
+fn limits_config(output_dir: &str, limits: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    let mut user = json!({ "name": "tenant" });
+    user.as_object_mut()
+        .unwrap()
+        .extend(limits.as_object().unwrap().clone());
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "limits-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "limits-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [{ "name": "TENANT", "users": [user] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_user_limits_claims() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let limits = json!({ "max_subscriptions": 10, "max_data": -1, "max_payload": 1024 });
+    let config = limits_config(output_dir.path().to_str().unwrap(), limits)?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let creds = std::fs::read_to_string(result.creds("TENANT", "tenant").unwrap())?;
+    let nats = &claims(creds.lines().nth(1).unwrap())?["nats"];
+    assert_eq!(nats["subs"], 10);
+    assert_eq!(nats["data"], -1);
+    assert_eq!(nats["payload"], 1024);
+    Ok(())
+}
+
+#[test]
+fn test_zero_user_limit_is_rejected() -> anyhow::Result<()> {
+    let config = limits_config("unused", json!({ "max_payload": 0 }))?;
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("max_payload of user tenant"), "{}", err);
+
+    let config = limits_config("unused", json!({ "max_subscriptions": -2 }))?;
+    assert!(config.validate().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_activation_window_claims() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let window = json!({
+        "start": "2030-01-06T08:00:00Z",
+        "expiry": "2030-03-31",
+        "times": [{ "start": "08:00:00", "end": "18:00:00" }],
+        "locale": "Europe/Berlin"
+    });
+    let config = limits_config(output_dir.path().to_str().unwrap(), window)?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let creds = std::fs::read_to_string(result.creds("TENANT", "tenant").unwrap())?;
+    let claims = claims(creds.lines().nth(1).unwrap())?;
+    assert_eq!(claims["nbf"], 1_893_916_800);
+    assert_eq!(claims["exp"], 1_901_145_600);
+    assert_eq!(
+        claims["nats"]["times"],
+        json!([{ "start": "08:00:00", "end": "18:00:00" }])
+    );
+    assert_eq!(claims["nats"]["times_location"], "Europe/Berlin");
+    Ok(())
+}
+
+#[test]
+fn test_activation_window_validation() -> anyhow::Result<()> {
+    let bad_time = limits_config("unused", json!({ "times": [{ "start": "8:00", "end": "18:00:00" }] }))?;
+    let err = bad_time.validate().unwrap_err().to_string();
+    assert!(err.contains("User tenant") && err.contains("\"8:00\""), "{}", err);
+
+    let late_start = limits_config("unused", json!({ "start": "2030-04-01", "expiry": "2030-03-31" }))?;
+    let err = late_start.validate().unwrap_err().to_string();
+    assert!(err.contains("is not before expiry"), "{}", err);
+
+    let same_day = limits_config(
+        "unused",
+        json!({ "start": "2030-03-31T08:00:00Z", "expiry": "2030-03-31T18:00:00Z" }),
+    )?;
+    same_day.validate()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expiry_keeps_the_time() -> anyhow::Result<()> {
+    for (expiry, exp) in [
+        ("2030-12-31T23:59:59Z", 1_924_991_999),
+        ("2030-12-31T23:59:59+02:00", 1_924_984_799),
+        ("2030-12-31", 1_924_905_600),
+    ] {
+        let output_dir = tempfile::TempDir::new()?;
+        let config = limits_config(output_dir.path().to_str().unwrap(), json!({ "expiry": expiry }))?;
+        let result = NatsForge::from_config(config)?.initialize().await?;
+        let creds = std::fs::read_to_string(result.creds("TENANT", "tenant").unwrap())?;
+        assert_eq!(claims(creds.lines().nth(1).unwrap())?["exp"], exp, "{}", expiry);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_relative_expiry() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = limits_config(output_dir.path().to_str().unwrap(), json!({ "expiry": "12h" }))?;
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let after = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+
+    let creds = std::fs::read_to_string(result.creds("TENANT", "tenant").unwrap())?;
+    let exp = claims(creds.lines().nth(1).unwrap())?["exp"].as_i64().unwrap();
+    assert!((before + 43_200..=after + 43_200).contains(&exp), "{}", exp);
+
+    for expiry in ["12", "12s", "soon", "2030-13-01"] {
+        let config = limits_config("unused", json!({ "expiry": expiry }))?;
+        assert!(config.validate().is_err(), "{} should be rejected", expiry);
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use natsforge::{config::NatsConfig, NatsForgeError};
+
+fn servers(config: &NatsConfig) -> Vec<(&str, u16, &Path)> {
+    config
+        .servers
+        .iter()
+        .map(|server| (server.name.as_str(), server.port, server.output_dir.as_path()))
+        .collect()
+}
+
+#[test]
+fn test_profiles_give_different_ports_and_dirs() -> anyhow::Result<()> {
+    let dev = NatsConfig::from_json_file("tests/profiles.json", Some("dev"))?;
+    assert_eq!(servers(&dev), vec![("hub", 14222, Path::new("dev-output/hub"))]);
+    assert!(dev.servers[0].tls.is_none());
+
+    let prod = NatsConfig::from_json_file("tests/profiles.json", Some("prod"))?;
+    assert_eq!(
+        servers(&prod),
+        vec![
+            ("hub", 4222, Path::new("/srv/nats/hub")),
+            ("edge", 24223, Path::new("/srv/nats/edge")),
+        ]
+    );
+    let hub = &prod.servers[0];
+    assert_eq!(hub.tls.as_ref().unwrap().cert_file, "/etc/nats/tls/hub.pem");
+    assert!(hub.jetstream.enabled, "the limits merge into the rest of jetstream");
+    assert_eq!(hub.jetstream.max_storage, Some(107_374_182_400));
+    assert_eq!(hub.accounts[0].users[0].name, "order-service");
+    Ok(())
+}
+
+#[test]
+fn test_no_profile_leaves_the_config_as_written() -> anyhow::Result<()> {
+    let loaded = NatsConfig::from_json_file("tests/profiles.json", None)?;
+    let parsed: NatsConfig = serde_json::from_str(&std::fs::read_to_string("tests/profiles.json")?)?;
+
+    assert_eq!(serde_json::to_value(&loaded)?, serde_json::to_value(&parsed)?);
+    assert_eq!(
+        servers(&loaded),
+        vec![
+            ("hub", 4222, Path::new("shop-output/hub")),
+            ("edge", 4223, Path::new("shop-output/edge")),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_unknown_profile_lists_the_available_ones() {
+    let err = NatsConfig::from_json_file("tests/profiles.json", Some("staging")).unwrap_err();
+    let NatsForgeError::UnknownProfile { name, available } = &err else {
+        panic!("expected UnknownProfile, got {:?}", err);
+    };
+    assert_eq!(name, "staging");
+    assert_eq!(available, &vec!["dev".to_string(), "prod".to_string()]);
+    assert_eq!(err.to_string(), "unknown profile staging; available: dev, prod");
+}
+
+#[test]
+fn test_profile_overriding_an_unknown_server_is_rejected() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let path = dir.path().join("config.json");
+    std::fs::write(
+        &path,
+        r#"{ "servers": [], "profiles": { "prod": { "servers": { "hub": { "port": 4222 } } } } }"#,
+    )?;
+
+    let err = NatsConfig::from_json_file(&path, Some("prod")).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Profile prod overrides server hub, which isn't defined"),
+        "{}",
+        err
+    );
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use std::{collections::BTreeMap, path::Path};
+
+use natsforge::{
+    compose::ComposeOptions,
+    config::NatsConfig,
+    kubernetes::K8sOptions,
+    prometheus::{ExporterOptions, TargetGroup},
+    NatsForge, NatsForgeError,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+fn config(dir: &Path) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "prometheus-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4222,
+                "host": "0.0.0.0",
+                "output_dir": dir.join("hub"),
+                "monitoring": { "http_port": 8222 },
+                "cluster": { "name": "core", "port": 6222 },
+                "jetstream": { "enabled": true, "domain": "hub" },
+                "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+            },
+            {
+                "name": "secure",
+                "port": 4223,
+                "host": "nats.example.com",
+                "output_dir": dir.join("secure"),
+                "monitoring": { "https_port": 8223, "tls": { "cert_file": "cert.pem", "key_file": "key.pem" } }
+            },
+            {
+                "name": "quiet",
+                "port": 4224,
+                "output_dir": dir.join("quiet")
+            }
+        ]
+    }))?)
+}
+
+fn labels(labels: &[(&str, &str)]) -> BTreeMap<String, String> {
+    labels
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_targets_match_monitored_servers() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(config(dir.path())?)?;
+    let result = forge.initialize().await?;
+    let path = dir.path().join("targets.json");
+    forge.emit_prometheus_targets(&result, &path)?;
+
+    let groups: Vec<TargetGroup> = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    assert_eq!(
+        groups,
+        [
+            TargetGroup {
+                targets: vec!["127.0.0.1:8222".to_string()],
+                labels: labels(&[("server_name", "hub"), ("cluster", "core"), ("jetstream_domain", "hub")]),
+            },
+            TargetGroup {
+                targets: vec!["nats.example.com:8223".to_string()],
+                labels: labels(&[("server_name", "secure"), ("__scheme__", "https")]),
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_targets_need_a_monitored_server() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path())?;
+    for server in &mut config.servers {
+        server.monitoring = None;
+    }
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let path = dir.path().join("targets.json");
+    let err = forge.emit_prometheus_targets(&result, &path).unwrap_err();
+    assert!(matches!(err, NatsForgeError::InvalidConfig(_)), "{}", err);
+    assert!(err.to_string().contains("monitoring"), "{}", err);
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_exporter_alongside_servers() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let forge = NatsForge::from_config(config(dir.path())?)?;
+    let result = forge.initialize().await?;
+
+    let compose_file = dir.path().join("docker-compose.yml");
+    let options = ComposeOptions {
+        exporter: Some(ExporterOptions::default()),
+        ..Default::default()
+    };
+    forge.emit_docker_compose(&result, &compose_file, &options)?;
+    let compose: Value = serde_yaml::from_str(&std::fs::read_to_string(&compose_file)?)?;
+    let services = compose["services"].as_object().unwrap();
+    // Only servers with an http_port get one.
+    assert_eq!(
+        services.keys().collect::<Vec<_>>(),
+        ["hub", "hub-exporter", "quiet", "secure"]
+    );
+    let exporter = &services["hub-exporter"];
+    assert_eq!(exporter["image"], "natsio/prometheus-nats-exporter:0.15.0");
+    assert_eq!(
+        exporter["command"],
+        json!([
+            "-varz",
+            "-connz",
+            "-subz",
+            "-healthz",
+            "-routez",
+            "-jsz=all",
+            "-port",
+            "7777",
+            "http://hub:8222"
+        ])
+    );
+    assert_eq!(exporter["depends_on"], json!(["hub"]));
+    assert!(exporter.get("ports").is_none());
+
+    let paths = forge.emit_kubernetes(
+        &result,
+        K8sOptions {
+            output_dir: dir.path().join("k8s"),
+            exporter: Some(ExporterOptions {
+                port: 9100,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )?;
+    let yaml = std::fs::read_to_string(&paths[0])?;
+    let documents = serde_yaml::Deserializer::from_str(&yaml)
+        .map(Value::deserialize)
+        .collect::<Result<Vec<_>, _>>()?;
+    let kind = |wanted: &str| documents.iter().find(|document| document["kind"] == wanted).unwrap();
+    let containers = &kind("StatefulSet")["spec"]["template"]["spec"]["containers"];
+    assert_eq!(containers[1]["name"], "exporter");
+    let args = containers[1]["args"].as_array().unwrap();
+    assert_eq!(args.last().unwrap(), "http://localhost:8222");
+    assert_eq!(
+        containers[1]["ports"],
+        json!([{ "name": "metrics", "containerPort": 9100 }])
+    );
+    let service_ports = kind("Service")["spec"]["ports"].as_array().unwrap();
+    assert_eq!(
+        service_ports.last().unwrap(),
+        &json!({ "name": "metrics", "port": 9100, "targetPort": "metrics" })
+    );
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::{collections::BTreeMap, path::Path};
+
+use natsforge::{
+    config::{
+        AccountConfig, BackendKind, Durability, ExportConfig, ImportConfig, NatsConfig, OperatorConfig, ServerConfig,
+        UserConfig,
+    },
+    NatsForge, NatsForgeError,
+};
+
+fn config(output_dir: &Path, port: u16) -> NatsConfig {
+    NatsConfig {
+        operator: OperatorConfig {
+            name: "regenerate-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "regenerate-server".to_string(),
+            port,
+            output_dir: output_dir.to_path_buf(),
+            accounts: vec![
+                AccountConfig {
+                    name: "ORDERS".to_string(),
+                    users: vec![UserConfig {
+                        name: "orders-user".to_string(),
+                        ..Default::default()
+                    }],
+                    exports: vec![ExportConfig {
+                        subject: "orders.>".to_string(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                AccountConfig {
+                    name: "BILLING".to_string(),
+                    users: vec![UserConfig {
+                        name: "billing-user".to_string(),
+                        ..Default::default()
+                    }],
+                    imports: vec![ImportConfig {
+                        subject: "orders.>".to_string(),
+                        account: "ORDERS".to_string(),
+                        local_subject: None,
+                        service: false,
+                        share: false,
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }],
+        backend: BackendKind::Native,
+        durability: Durability::Fast,
+        ..Default::default()
+    }
+}
+
+/// The contents of every `.jwt` file in `dir`, by file name.
+fn jwt_files(dir: &Path) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+    let mut files = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "jwt") {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            files.insert(name, std::fs::read(&path)?);
+        }
+    }
+    Ok(files)
+}
+
+#[tokio::test]
+async fn test_regenerate_configs_keeps_jwts_and_changes_only_the_port() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let output_dir = dir.path().join("out");
+    let previous = NatsForge::from_config(config(&output_dir, 4222))?.initialize().await?;
+    let jwts = jwt_files(&output_dir)?;
+    assert!(
+        jwts.contains_key("operator.jwt") && jwts.contains_key("ORDERS.jwt"),
+        "{:?}",
+        jwts.keys()
+    );
+    let server_config_path = &previous.servers["regenerate-server"];
+    let before = std::fs::read_to_string(server_config_path)?;
+
+    // A new forge, so nothing issued by the first one is at hand but the files.
+    let forge = NatsForge::from_config(config(&output_dir, 4333))?;
+    let result = forge.regenerate_configs(&previous).await?;
+    let after = std::fs::read_to_string(&result.servers["regenerate-server"])?;
+
+    assert_eq!(jwt_files(&output_dir)?, jwts);
+    assert_eq!(result.account_ids, previous.account_ids);
+    let changed: Vec<(&str, &str)> = before
+        .lines()
+        .zip(after.lines())
+        .filter(|(old, new)| old != new)
+        .collect();
+    assert_eq!(before.lines().count(), after.lines().count());
+    assert_eq!(changed, vec![("port: 4222", "port: 4333")]);
+    assert_eq!(result.connections[0].url, "nats://localhost:4333");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_regenerate_configs_rejects_changed_accounts() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let output_dir = dir.path().join("out");
+    let previous = NatsForge::from_config(config(&output_dir, 4222))?.initialize().await?;
+    let before = std::fs::read_to_string(&previous.servers["regenerate-server"])?;
+
+    let mut changed = config(&output_dir, 4333);
+    let accounts = &mut changed.servers[0].accounts;
+    accounts.retain(|account| account.name == "ORDERS");
+    accounts.push(AccountConfig {
+        name: "SHIPPING".to_string(),
+        ..Default::default()
+    });
+    let err = NatsForge::from_config(changed)?
+        .regenerate_configs(&previous)
+        .await
+        .unwrap_err();
+
+    let NatsForgeError::InvalidConfig(message) = &err else {
+        panic!("expected InvalidConfig, got {:?}", err);
+    };
+    assert!(message.contains("not issued yet: SHIPPING"), "{}", message);
+    assert!(message.contains("no longer configured: BILLING"), "{}", message);
+    assert_eq!(std::fs::read_to_string(&previous.servers["regenerate-server"])?, before);
+    Ok(())
+}
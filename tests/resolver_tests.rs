@@ -0,0 +1,161 @@
+use natsforge::{
+    config::{ArtifactMode, NatsConfig, ResolverType},
+    NatsForge,
+};
+use serde_json::json;
+
+fn config(output_dir: &str, resolver: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "resolver-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "resolver-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "resolver": resolver,
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_memory_resolver_preloads_every_account() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap(), json!("memory"))?)?
+        .initialize()
+        .await?;
+
+    let server_config = std::fs::read_to_string(result.server_config("resolver-server").unwrap())?;
+    assert!(server_config.contains("resolver: MEMORY"));
+    assert!(server_config.contains(result.account_id("APP").unwrap()));
+    assert!(result.resolver_dir("resolver-server").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_full_resolver_writes_jwt_dir() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let resolver = json!({ "full": { "allow_delete": true, "interval": "2m" } });
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap(), resolver)?)?
+        .initialize()
+        .await?;
+
+    let resolver_dir = result.resolver_dir("resolver-server").unwrap();
+    assert_eq!(resolver_dir, output_dir.path().canonicalize()?.join("jwt"));
+    let app_id = result.account_id("APP").unwrap();
+    let sys_id = result.account_id("SYS").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(resolver_dir.join(format!("{}.jwt", app_id)))?,
+        std::fs::read_to_string(&result.account("APP").unwrap().jwt_path)?
+    );
+    assert!(resolver_dir.join(format!("{}.jwt", sys_id)).exists());
+
+    let server_config = std::fs::read_to_string(result.server_config("resolver-server").unwrap())?;
+    assert!(!server_config.contains("resolver: MEMORY"));
+    assert!(server_config.contains("resolver {\n    type: full\n"));
+    assert!(server_config.contains("    allow_delete: true\n    interval: \"2m\"\n"));
+    let preload = &server_config[server_config.find("resolver_preload").unwrap()..];
+    assert!(preload.contains(sys_id) && !preload.contains(app_id), "{}", preload);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_full_resolver_in_memory() -> anyhow::Result<()> {
+    let mut config = config("out", json!({ "full": { "dir": "accounts" } }))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let app_id = result.account_id("APP").unwrap();
+    let artifacts = result.artifacts.as_ref().unwrap();
+    let files = &artifacts.server_files["resolver-server"];
+    let app_jwt = &files[&std::path::Path::new("out/accounts").join(format!("{}.jwt", app_id))];
+    assert_eq!(app_jwt, &artifacts.account_jwts["APP"]);
+    assert!(artifacts.server_configs["resolver-server"].contains("dir: \"out/accounts\""));
+    Ok(())
+}
+
+#[test]
+fn test_resolver_validation() -> anyhow::Result<()> {
+    let resolver: ResolverType = serde_json::from_value(json!({ "full": {} }))?;
+    assert_eq!(
+        resolver,
+        ResolverType::Full {
+            dir: "jwt".into(),
+            allow_delete: false,
+            interval: None
+        }
+    );
+
+    let err = config("unused", json!({ "full": { "interval": "often" } }))?
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Resolver of server resolver-server: Invalid interval"),
+        "{}",
+        err
+    );
+    let err = config("unused", json!({ "full": { "dir": "" } }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("dir must not be empty"), "{}", err);
+    let err = config("unused", json!({ "cache": { "ttl": "1 hour" } }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid ttl"), "{}", err);
+    let err = config("unused", json!({ "url": "localhost:9090" }))?
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("not an http or https URL"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_url_resolver_preloads_only_the_system_account() -> anyhow::Result<()> {
+    let mut config = config("out", json!({ "url": "http://localhost:9090/jwt/v1/accounts/" }))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let server_config = &result.artifacts.as_ref().unwrap().server_configs["resolver-server"];
+    assert!(server_config.contains("resolver: URL(http://localhost:9090/jwt/v1/accounts/)\n"));
+    let preload = &server_config[server_config.find("resolver_preload").unwrap()..];
+    assert!(preload.contains(result.account_id("SYS").unwrap()));
+    assert!(!preload.contains(result.account_id("APP").unwrap()), "{}", preload);
+    assert!(result.resolver_dir("resolver-server").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_resolver_block() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let resolver = json!({ "cache": { "dir": "cache", "ttl": "1h" } });
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap(), resolver)?)?
+        .initialize()
+        .await?;
+
+    let resolver_dir = result.resolver_dir("resolver-server").unwrap();
+    assert!(resolver_dir
+        .join(format!("{}.jwt", result.account_id("APP").unwrap()))
+        .exists());
+    let server_config = std::fs::read_to_string(result.server_config("resolver-server").unwrap())?;
+    let block = format!(
+        "resolver {{\n    type: cache\n    dir: \"{}\"\n    ttl: \"1h\"\n}}\n",
+        output_dir.path().join("cache").display()
+    );
+    assert!(server_config.contains(&block), "{}", server_config);
+    Ok(())
+}
+
+#[test]
+fn test_push_accounts_needs_url_resolver_and_nsc() -> anyhow::Result<()> {
+    let mut config = config("unused", json!("memory"))?;
+    config.servers[0].push_accounts = true;
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("needs a URL resolver"), "{}", err);
+
+    config.servers[0].resolver = ResolverType::Url("http://localhost:9090/jwt/v1/accounts/".to_string());
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("needs the nsc backend"), "{}", err);
+    Ok(())
+}
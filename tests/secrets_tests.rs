@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig, UserConfig},
+    secrets::{EnvDirSink, FileSink, SecretSink},
+    NatsForge, NatsForgeError, Result,
+};
+use serde_json::json;
+
+/// The last value stored under each key, and how often the key was stored.
+type Stored = BTreeMap<String, (Vec<u8>, usize)>;
+
+#[derive(Default, Clone)]
+struct MemorySink {
+    stored: Arc<Mutex<Stored>>,
+}
+
+#[async_trait]
+impl SecretSink for MemorySink {
+    async fn store(&self, key: &str, value: &[u8]) -> Result<()> {
+        let mut stored = self.stored.lock().unwrap();
+        let entry = stored.entry(key.to_string()).or_default();
+        *entry = (value.to_vec(), entry.1 + 1);
+        Ok(())
+    }
+}
+
+impl MemorySink {
+    fn counts(&self) -> BTreeMap<String, usize> {
+        let stored = self.stored.lock().unwrap();
+        stored.iter().map(|(key, (_, count))| (key.clone(), *count)).collect()
+    }
+
+    fn value(&self, key: &str) -> String {
+        String::from_utf8(self.stored.lock().unwrap()[key].0.clone()).unwrap()
+    }
+}
+
+fn config(hub_dir: &Path, leaf_dir: &Path) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "secrets-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [
+            {
+                "name": "hub",
+                "port": 4222,
+                "output_dir": hub_dir,
+                "accounts": [
+                    { "name": "APP", "users": [{ "name": "app-user" }, { "name": "worker" }] },
+                    { "name": "OPS", "users": [{ "name": "ops-user" }] }
+                ]
+            },
+            {
+                "name": "leaf",
+                "port": 4223,
+                "output_dir": leaf_dir,
+                "accounts": [{ "name": "EDGE", "users": [{ "name": "edge-user" }] }]
+            }
+        ]
+    }))?)
+}
+
+fn expected_keys() -> BTreeMap<String, usize> {
+    [
+        "nats/hub/APP.jwt",
+        "nats/hub/APP/app-user.creds",
+        "nats/hub/APP/worker.creds",
+        "nats/hub/OPS.jwt",
+        "nats/hub/OPS/ops-user.creds",
+        "nats/leaf/EDGE.jwt",
+        "nats/leaf/EDGE/edge-user.creds",
+    ]
+    .into_iter()
+    .map(|key| (key.to_string(), 1))
+    .collect()
+}
+
+#[tokio::test]
+async fn test_every_secret_stored_once() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let sink = MemorySink::default();
+    let forge = NatsForge::from_config(config(&dir.path().join("hub"), &dir.path().join("leaf"))?)?
+        .with_secret_sink(Box::new(sink.clone()));
+    let result = forge.initialize().await?;
+
+    assert_eq!(sink.counts(), expected_keys());
+    let creds = std::fs::read_to_string(result.creds("APP", "app-user").unwrap())?;
+    assert_eq!(sink.value("nats/hub/APP/app-user.creds"), creds);
+    let account_jwt = std::fs::read_to_string(&result.account("EDGE").unwrap().jwt_path)?;
+    assert_eq!(sink.value("nats/leaf/EDGE.jwt"), account_jwt);
+
+    // Later users go to the sink too.
+    let new_user = UserConfig {
+        name: "late-user".to_string(),
+        ..Default::default()
+    };
+    let creds_path = forge.add_user("OPS", &new_user).await?;
+    assert_eq!(sink.counts()["nats/hub/OPS/late-user.creds"], 1);
+    assert_eq!(
+        sink.value("nats/hub/OPS/late-user.creds"),
+        std::fs::read_to_string(creds_path)?
+    );
+    forge.rotate_user("APP", "worker", true).await?;
+    assert_eq!(sink.counts()["nats/hub/APP/worker.creds"], 2);
+    assert_eq!(sink.counts()["nats/hub/APP.jwt"], 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_secrets_stored_in_memory_mode() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(&dir.path().join("hub"), &dir.path().join("leaf"))?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let sink = MemorySink::default();
+    let result = NatsForge::from_config(config)?
+        .with_secret_sink(Box::new(sink.clone()))
+        .initialize()
+        .await?;
+
+    assert_eq!(sink.counts(), expected_keys());
+    let artifacts = result.artifacts.as_ref().unwrap();
+    assert_eq!(
+        sink.value("nats/leaf/EDGE/edge-user.creds"),
+        artifacts.creds("EDGE", "edge-user").unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_file_and_env_dir_sinks() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let files = FileSink::new(dir.path().join("files"));
+    files.store("nats/hub/APP/app-user.creds", b"creds").await?;
+    let path = dir.path().join("files/nats/hub/APP/app-user.creds");
+    assert_eq!(std::fs::read_to_string(&path)?, "creds");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+    }
+    for key in ["../escape.creds", "/abs.creds", ""] {
+        let err = files.store(key, b"creds").await.unwrap_err();
+        assert!(matches!(err, NatsForgeError::InvalidConfig(_)), "{}", err);
+    }
+
+    let env_dir = dir.path().join("env");
+    let env = EnvDirSink::new(&env_dir);
+    env.store("nats/hub/APP/app-user.creds", b"creds").await?;
+    env.store("nats/hub/APP.jwt", b"jwt").await?;
+    assert_eq!(std::fs::read_to_string(env_dir.join("nats/hub/APP.jwt"))?, "jwt");
+    assert_eq!(
+        std::fs::read_to_string(env.env_file())?,
+        format!(
+            "NATS_HUB_APP_APP_USER_CREDS=\"{}\"\nNATS_HUB_APP_JWT=\"{}\"\n",
+            env_dir.join("nats/hub/APP/app-user.creds").display(),
+            env_dir.join("nats/hub/APP.jwt").display()
+        )
+    );
+    Ok(())
+}
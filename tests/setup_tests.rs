@@ -1,18 +1,29 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Context;
-use base64::Engine;
 use natsforge::{
+    conf::ConfValue,
     config::{
-        AccountConfig, ExportConfig, JetStreamConfig, LeafNodeConfig, NatsConfig, OperatorConfig, ServerConfig,
-        UserConfig,
+        AccountConfig, BackendKind, ConfCredential, Durability, ExportConfig, ImportConfig, JetStreamConfig,
+        LeafNodeConfig, NatsConfig, OperatorConfig, ServerConfig, UserConfig,
     },
-    NatsForge,
+    jwt, NatsForge,
 };
 
 #[tokio::test]
 async fn test_basic_setup_with_accounts() -> anyhow::Result<()> {
-    let output_dir = "test-output-basic";
+    basic_setup_with_accounts(BackendKind::Nsc, "test-output-basic").await
+}
+
+#[tokio::test]
+async fn test_basic_setup_with_accounts_native() -> anyhow::Result<()> {
+    basic_setup_with_accounts(BackendKind::Native, "test-output-basic-native").await
+}
+
+async fn basic_setup_with_accounts(backend: BackendKind, output_dir: &str) -> anyhow::Result<()> {
     let _ = std::fs::remove_dir_all(output_dir);
     std::fs::create_dir_all(output_dir)?;
 
@@ -21,6 +32,8 @@ async fn test_basic_setup_with_accounts() -> anyhow::Result<()> {
         operator: OperatorConfig {
             name: "test-operator".to_string(),
             reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
         },
         servers: vec![ServerConfig {
             name: "main-server".to_string(),
@@ -33,6 +46,7 @@ async fn test_basic_setup_with_accounts() -> anyhow::Result<()> {
                 max_storage: None,
                 subject_transform: None,
                 republish: vec![],
+                absolute_paths: true,
             },
             leafnodes: LeafNodeConfig::default(),
             accounts: vec![
@@ -40,100 +54,137 @@ async fn test_basic_setup_with_accounts() -> anyhow::Result<()> {
                     name: "SYS".to_string(),
                     unique_name: "".to_string(),
                     users: vec![],
+                    user_template: None,
                     is_system_account: true,
                     max_connections: None,
                     max_payload: None,
+                    max_message_payload: None,
+                    max_leaf_connections: None,
+                    max_exports: None,
+                    max_imports: None,
+                    allow_wildcard_exports: None,
                     exports: vec![],
                     imports: vec![],
                     mappings: HashMap::new(),
+                    signing_keys: None,
+                    roles: vec![],
+                    expiry: None,
+                    disallow_bearer: false,
+                    revocations: vec![],
+                    jetstream: false,
                 },
                 AccountConfig {
                     name: "APP".to_string(),
                     unique_name: "".to_string(),
                     users: vec![UserConfig {
                         name: "app-user".to_string(),
-                        allowed_pubsub: None,
-                        allowed_publishes: None,
-                        allowed_subjects: Some(vec!["app.>".to_string()]),
-                        denied_pubsub: None,
-                        denied_publishes: None,
-                        denied_subjects: None,
+                        allow_pubsub: vec![],
+                        allow_pub: vec![],
+                        allow_sub: vec!["app.>".to_string()],
+                        deny_pubsub: vec![],
+                        deny_pub: vec![],
+                        deny_sub: vec![],
                         allow_pub_response: None,
+                        response_ttl: None,
+                        source_networks: vec![],
+                        max_subscriptions: None,
+                        max_data: None,
+                        max_payload: None,
                         expiry: None,
+                        start: None,
+                        times: vec![],
+                        locale: None,
+                        role: None,
+                        bearer: false,
+                        conf_credential: ConfCredential::Nkey,
                     }],
+                    user_template: None,
                     is_system_account: false,
                     max_connections: Some(5),
                     max_payload: Some(1048576),
+                    max_message_payload: None,
+                    max_leaf_connections: None,
+                    max_exports: None,
+                    max_imports: None,
+                    allow_wildcard_exports: None,
                     exports: vec![ExportConfig {
                         subject: "app.data".to_string(),
                         is_service: false,
+                        ..Default::default()
                     }],
                     imports: vec![],
                     mappings: HashMap::new(),
+                    signing_keys: None,
+                    roles: vec![],
+                    expiry: None,
+                    disallow_bearer: false,
+                    revocations: vec![],
+                    jetstream: false,
                 },
             ],
             output_dir: PathBuf::from(output_dir),
             tls: None,
             mappings: HashMap::new(),
+            ..Default::default()
         }],
+        backend,
+        ..Default::default()
     };
 
     let forge = NatsForge::from_config(config)?;
     let result = forge.initialize().await?;
 
     assert!(result.operator_jwt_path.exists());
-    assert_eq!(result.account_jwt_paths.len(), 2);
-    assert_eq!(result.user_creds_paths.len(), 1);
-    assert!(result.server_config_path.exists());
-
-    let config_content = std::fs::read_to_string(&result.server_config_path)?;
+    assert_eq!(result.account_jwts.len(), 2);
+    assert!(result
+        .creds("APP", "app-user")
+        .context("Missing app-user creds")?
+        .exists());
+    let server_config = result.server_config("main-server").context("Missing server config")?;
+    assert!(server_config.exists());
+
+    let config_content = std::fs::read_to_string(server_config)?;
     assert!(config_content.contains("system_account:"));
     assert!(config_content.contains("port: 4222"));
     assert!(!config_content.contains("jetstream"));
 
-    let mut attempts = 0;
-    let max_attempts = 3;
-    while attempts < max_attempts {
-        match std::fs::remove_dir_all(output_dir) {
-            Ok(()) => break,
-            Err(e) if e.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
-                attempts += 1;
-                println!("Cleanup attempt {}/{} failed: {}", attempts, max_attempts, e);
-                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-            }
-            Err(e) => return Err(anyhow::anyhow!(e)),
-        }
-    }
-    if attempts == max_attempts {
-        return Err(anyhow::anyhow!(
-            "Failed to clean up {} after {} attempts",
-            output_dir,
-            max_attempts
-        ));
-    }
+    forge.cleanup(&result, true).await?;
+    assert!(!Path::new(output_dir).exists());
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_temp_setup_with_accounts() -> anyhow::Result<()> {
+    temp_setup_with_accounts(BackendKind::Nsc, "ignored").await
+}
+
+#[tokio::test]
+async fn test_temp_setup_with_accounts_native() -> anyhow::Result<()> {
+    temp_setup_with_accounts(BackendKind::Native, "ignored-native").await
+}
+
+async fn temp_setup_with_accounts(backend: BackendKind, output_dir: &str) -> anyhow::Result<()> {
     let config = NatsConfig {
         name: Some("temp-setup".to_string()),
         operator: OperatorConfig {
             name: "test-operator".to_string(),
             reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
         },
         servers: vec![ServerConfig {
             name: "main-server".to_string(),
             port: 4222,
             jetstream: JetStreamConfig {
                 enabled: true,
-                store_dir: Some("ignored/jetstream".to_string()),
+                store_dir: Some("jetstream".to_string()),
                 domain: Some("core".to_string()),
                 max_memory: Some(1024 * 1024 * 1024),       // 1GB
                 max_storage: Some(10 * 1024 * 1024 * 1024), // 10GB
                 subject_transform: None,
                 republish: vec![],
+                absolute_paths: true,
             },
             leafnodes: LeafNodeConfig::default(),
             accounts: vec![
@@ -141,69 +192,113 @@ async fn test_temp_setup_with_accounts() -> anyhow::Result<()> {
                     name: "SYS".to_string(),
                     unique_name: "".to_string(),
                     users: vec![], // No perms needed
+                    user_template: None,
                     is_system_account: true,
                     max_connections: None,
                     max_payload: None,
+                    max_message_payload: None,
+                    max_leaf_connections: None,
+                    max_exports: None,
+                    max_imports: None,
+                    allow_wildcard_exports: None,
                     exports: vec![],
                     imports: vec![],
                     mappings: HashMap::new(),
+                    signing_keys: None,
+                    roles: vec![],
+                    expiry: None,
+                    disallow_bearer: false,
+                    revocations: vec![],
+                    jetstream: false,
                 },
                 AccountConfig {
                     name: "APP".to_string(),
                     unique_name: "".to_string(),
                     users: vec![UserConfig {
                         name: "app-user".to_string(),
-                        allowed_pubsub: None,
-                        allowed_publishes: None,
-                        allowed_subjects: Some(vec!["app.>".to_string()]),
-                        denied_pubsub: None,
-                        denied_publishes: None,
-                        denied_subjects: Some(vec!["forbidden.>".to_string()]),
+                        allow_pubsub: vec![],
+                        allow_pub: vec![],
+                        allow_sub: vec!["app.>".to_string()],
+                        deny_pubsub: vec![],
+                        deny_pub: vec![],
+                        deny_sub: vec!["forbidden.>".to_string()],
                         allow_pub_response: None,
+                        response_ttl: None,
+                        source_networks: vec![],
+                        max_subscriptions: None,
+                        max_data: None,
+                        max_payload: None,
                         expiry: Some("2025-12-31T23:59:59Z".to_string()),
+                        start: None,
+                        times: vec![],
+                        locale: None,
+                        role: None,
+                        bearer: false,
+                        conf_credential: ConfCredential::Nkey,
                     }],
+                    user_template: None,
                     is_system_account: false,
                     max_connections: Some(10),
                     max_payload: Some(2097152),
+                    max_message_payload: None,
+                    max_leaf_connections: None,
+                    max_exports: None,
+                    max_imports: None,
+                    allow_wildcard_exports: None,
                     exports: vec![],
                     imports: vec![],
                     mappings: HashMap::new(),
+                    signing_keys: None,
+                    roles: vec![],
+                    expiry: None,
+                    disallow_bearer: false,
+                    revocations: vec![],
+                    jetstream: false,
                 },
             ],
-            output_dir: PathBuf::from("ignored"),
+            output_dir: PathBuf::from(output_dir),
             tls: None,
             mappings: HashMap::new(),
+            ..Default::default()
         }],
+        backend,
+        ..Default::default()
     };
 
     let forge = NatsForge::from_config(config)?;
     let result = forge.initialize().await?;
 
     assert!(result.operator_jwt_path.exists());
-    assert_eq!(result.account_jwt_paths.len(), 2);
-    assert_eq!(result.user_creds_paths.len(), 1);
-    assert!(result.server_config_path.exists());
-
-    let config_content = std::fs::read_to_string(&result.server_config_path)?;
+    assert_eq!(result.account_jwts.len(), 2);
+    assert!(result
+        .creds("APP", "app-user")
+        .context("Missing app-user creds")?
+        .exists());
+    let server_config = result.server_config("main-server").context("Missing server config")?;
+    assert!(server_config.exists());
+
+    let config_content = std::fs::read_to_string(server_config)?;
     assert!(config_content.contains("jetstream"));
     assert!(config_content.contains("domain: \"core\""));
 
-    std::fs::remove_dir_all("ignored")?;
+    std::fs::remove_dir_all(output_dir)?;
 
     Ok(())
 }
 
 #[tokio::test]
 async fn test_json_config() -> anyhow::Result<()> {
-    let forge = NatsForge::from_json_file("tests/example.json")?;
+    let forge = NatsForge::from_json_file("tests/example.json", None)?;
     let result = forge.initialize().await?;
 
     assert!(result.operator_jwt_path.exists());
-    assert_eq!(result.account_jwt_paths.len(), 2);
-    assert_eq!(result.user_creds_paths.len(), 2);
-    assert!(result.server_config_path.exists());
+    assert_eq!(result.account_jwts.len(), 2);
+    assert!(result.creds("APP1", "user1").context("Missing user1 creds")?.exists());
+    assert!(result.creds("APP1", "user2").context("Missing user2 creds")?.exists());
+    let server_config = result.server_config("main-server").context("Missing server config")?;
+    assert!(server_config.exists());
 
-    let config_content = std::fs::read_to_string(&result.server_config_path)?;
+    let config_content = std::fs::read_to_string(server_config)?;
     assert!(config_content.contains("system_account:"));
 
     std::fs::remove_dir_all("./nats-setup")?;
@@ -211,33 +306,97 @@ async fn test_json_config() -> anyhow::Result<()> {
 }
 
 #[tokio::test]
-async fn test_hub_leaf_json_config() -> anyhow::Result<()> {
-    let forge = NatsForge::from_json_file("tests/hub_leaf.json")?;
+async fn test_json_config_native() -> anyhow::Result<()> {
+    let output_dir = "nats-setup-native";
+    let mut config = serde_json::from_str::<NatsConfig>(include_str!("example.json"))?;
+    config.backend = BackendKind::Native;
+    config.servers[0].output_dir = PathBuf::from(output_dir);
+
+    let forge = NatsForge::from_config(config)?;
     let result = forge.initialize().await?;
 
     assert!(result.operator_jwt_path.exists());
-    assert_eq!(result.account_jwt_paths.len(), 2);
-    assert_eq!(result.user_creds_paths.len(), 2);
-    assert_eq!(result.server_config_paths.as_ref().unwrap().len(), 2);
+    assert_eq!(result.account_jwts.len(), 2);
+    assert!(result.creds("APP1", "user1").is_some());
+    assert!(result.creds("APP1", "user2").is_some());
 
-    let hub_config = std::fs::read_to_string(&result.server_config_paths.as_ref().unwrap()[0])?;
+    let config_content =
+        std::fs::read_to_string(result.server_config("main-server").context("Missing server config")?)?;
+    assert!(config_content.contains("system_account:"));
+
+    std::fs::remove_dir_all(output_dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hub_leaf_json_config() -> anyhow::Result<()> {
+    let forge = NatsForge::from_json_file("tests/hub_leaf.json", None)?;
+    hub_leaf_json_config(forge, "hub-output", "leaf-output").await
+}
+
+#[tokio::test]
+async fn test_hub_leaf_json_config_native() -> anyhow::Result<()> {
+    let (hub_dir, leaf_dir) = ("hub-output-native", "leaf-output-native");
+    let mut config = serde_json::from_str::<NatsConfig>(include_str!("hub_leaf.json"))?;
+    config.backend = BackendKind::Native;
+    config.servers[0].output_dir = PathBuf::from(hub_dir);
+    config.servers[1].output_dir = PathBuf::from(leaf_dir);
+    hub_leaf_json_config(NatsForge::from_config(config)?, hub_dir, leaf_dir).await
+}
+
+async fn hub_leaf_json_config(forge: NatsForge, hub_dir: &str, leaf_dir: &str) -> anyhow::Result<()> {
+    let result = forge.initialize().await?;
+
+    assert_eq!(
+        result.operator_jwt_paths,
+        vec![
+            PathBuf::from(hub_dir).join("operator.jwt"),
+            PathBuf::from(leaf_dir).join("operator.jwt")
+        ]
+    );
+    assert_eq!(result.operator_jwt_path, result.operator_jwt_paths[0]);
+    assert_eq!(
+        std::fs::read_to_string(&result.operator_jwt_paths[0])?,
+        std::fs::read_to_string(&result.operator_jwt_paths[1])?
+    );
+    assert_eq!(result.account_jwts.len(), 2);
+    assert!(result.creds("SYS", "admin").is_some());
+    assert!(result.creds("app-service", "service-user").is_some());
+    assert_eq!(result.servers.len(), 2);
+
+    let hub_config = std::fs::read_to_string(result.server_config("central-hub").context("Missing hub config")?)?;
     assert!(hub_config.contains("port: 4222"));
     assert!(hub_config.contains("leafnodes {\n    port: 4248\n}"));
 
-    let leaf_config = std::fs::read_to_string(&result.server_config_paths.as_ref().unwrap()[1])?;
+    let leaf_config = std::fs::read_to_string(result.server_config("edge-leaf").context("Missing leaf config")?)?;
     assert!(leaf_config.contains("port: 4223"));
 
-    if let Some(remote_section) = leaf_config.split("remotes = [").nth(1) {
+    // The leaf only preloads the account its remote binds to and the system account.
+    let document = forge.server_document("edge-leaf")?;
+    let system_account = document.root.get("system_account").and_then(ConfValue::as_str).unwrap();
+    let preload = document
+        .root
+        .get("resolver_preload")
+        .and_then(ConfValue::as_object)
+        .unwrap();
+    let mut preloaded: Vec<&str> = preload.iter().map(|(account_id, _)| account_id).collect();
+    preloaded.sort();
+    let mut expected = vec![result.account_id("app-service").unwrap(), system_account];
+    expected.sort();
+    assert_eq!(preloaded, expected);
+    assert!(!PathBuf::from(leaf_dir).join("SYS.jwt").exists());
+
+    if let Some(remote_section) = leaf_config.split("remotes: [").nth(1) {
         if let Some(remote_config) = remote_section.split("]").next() {
             println!("Remote configuration section:\n{}", remote_config);
         }
     }
 
-    assert!(leaf_config.contains("remotes = ["));
+    assert!(leaf_config.contains("remotes: ["));
     assert!(leaf_config.contains("url: \"nats://localhost:4248\""));
 
     let has_credentials = leaf_config.contains("credentials: \"app-service-service-user.creds\"")
-        || leaf_config.contains("credentials: \"leaf-output/app-service-service-user.creds\"");
+        || leaf_config.contains(&format!("credentials: \"{}/app-service-service-user.creds\"", leaf_dir));
     assert!(has_credentials, "Credentials path not found in expected format");
 
     let account_start = leaf_config.find("account: \"").unwrap() + 10;
@@ -252,13 +411,21 @@ async fn test_hub_leaf_json_config() -> anyhow::Result<()> {
         "Account ID should only contain uppercase letters and numbers"
     );
 
-    std::fs::remove_dir_all("hub-output")?;
-    std::fs::remove_dir_all("leaf-output")?;
+    std::fs::remove_dir_all(hub_dir)?;
+    std::fs::remove_dir_all(leaf_dir)?;
     Ok(())
 }
 #[tokio::test]
 async fn test_pub_sub_permissions() -> anyhow::Result<()> {
-    let output_dir = "test-output-pubsub";
+    pub_sub_permissions(BackendKind::Nsc, "test-output-pubsub").await
+}
+
+#[tokio::test]
+async fn test_pub_sub_permissions_native() -> anyhow::Result<()> {
+    pub_sub_permissions(BackendKind::Native, "test-output-pubsub-native").await
+}
+
+async fn pub_sub_permissions(backend: BackendKind, output_dir: &str) -> anyhow::Result<()> {
     let _ = std::fs::remove_dir_all(output_dir);
     std::fs::create_dir_all(output_dir)?;
     println!("Test output directory initialized: {}", output_dir);
@@ -268,18 +435,21 @@ async fn test_pub_sub_permissions() -> anyhow::Result<()> {
         operator: OperatorConfig {
             name: "test-operator".to_string(),
             reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
         },
         servers: vec![ServerConfig {
             name: "test-server".to_string(),
             port: 4222,
             jetstream: JetStreamConfig {
                 enabled: true,
-                store_dir: Some(format!("{}/jetstream", output_dir)),
+                store_dir: Some("jetstream".to_string()),
                 domain: Some("core".to_string()),
                 max_memory: None,
                 max_storage: None,
                 subject_transform: None,
                 republish: vec![],
+                absolute_paths: true,
             },
             leafnodes: LeafNodeConfig::default(),
             accounts: vec![
@@ -287,12 +457,24 @@ async fn test_pub_sub_permissions() -> anyhow::Result<()> {
                     name: "SYS".to_string(),
                     unique_name: "".to_string(),
                     users: vec![],
+                    user_template: None,
                     is_system_account: true,
                     max_connections: None,
                     max_payload: None,
+                    max_message_payload: None,
+                    max_leaf_connections: None,
+                    max_exports: None,
+                    max_imports: None,
+                    allow_wildcard_exports: None,
                     exports: vec![],
                     imports: vec![],
                     mappings: HashMap::new(),
+                    signing_keys: None,
+                    roles: vec![],
+                    expiry: None,
+                    disallow_bearer: false,
+                    revocations: vec![],
+                    jetstream: false,
                 },
                 AccountConfig {
                     name: "TEST".to_string(),
@@ -300,67 +482,125 @@ async fn test_pub_sub_permissions() -> anyhow::Result<()> {
                     users: vec![
                         UserConfig {
                             name: "sub-only".to_string(),
-                            allowed_pubsub: None,
-                            allowed_publishes: None,
-                            allowed_subjects: Some(vec!["test.sub.>".to_string()]),
-                            denied_pubsub: None,
-                            denied_publishes: None,
-                            denied_subjects: None,
+                            allow_pubsub: vec![],
+                            allow_pub: vec![],
+                            allow_sub: vec!["test.sub.>".to_string()],
+                            deny_pubsub: vec![],
+                            deny_pub: vec![],
+                            deny_sub: vec![],
                             allow_pub_response: None,
+                            response_ttl: None,
+                            source_networks: vec![],
+                            max_subscriptions: None,
+                            max_data: None,
+                            max_payload: None,
                             expiry: None,
+                            start: None,
+                            times: vec![],
+                            locale: None,
+                            role: None,
+                            bearer: false,
+                            conf_credential: ConfCredential::Nkey,
                         },
                         UserConfig {
                             name: "pub-only".to_string(),
-                            allowed_pubsub: None,
-                            allowed_publishes: Some(vec!["test.pub.>".to_string()]),
-                            allowed_subjects: None,
-                            denied_pubsub: None,
-                            denied_publishes: None,
-                            denied_subjects: None,
+                            allow_pubsub: vec![],
+                            allow_pub: vec!["test.pub.>".to_string()],
+                            allow_sub: vec![],
+                            deny_pubsub: vec![],
+                            deny_pub: vec![],
+                            deny_sub: vec![],
                             allow_pub_response: None,
+                            response_ttl: None,
+                            source_networks: vec![],
+                            max_subscriptions: None,
+                            max_data: None,
+                            max_payload: None,
                             expiry: None,
+                            start: None,
+                            times: vec![],
+                            locale: None,
+                            role: None,
+                            bearer: false,
+                            conf_credential: ConfCredential::Nkey,
                         },
                         UserConfig {
                             name: "both".to_string(),
-                            allowed_pubsub: None,
-                            allowed_publishes: Some(vec!["test.both.pub.>".to_string()]),
-                            allowed_subjects: Some(vec!["test.both.sub.>".to_string()]),
-                            denied_pubsub: None,
-                            denied_publishes: None,
-                            denied_subjects: None,
+                            allow_pubsub: vec![],
+                            allow_pub: vec!["test.both.pub.>".to_string()],
+                            allow_sub: vec!["test.both.sub.>".to_string()],
+                            deny_pubsub: vec![],
+                            deny_pub: vec![],
+                            deny_sub: vec![],
                             allow_pub_response: None,
+                            response_ttl: None,
+                            source_networks: vec![],
+                            max_subscriptions: None,
+                            max_data: None,
+                            max_payload: None,
                             expiry: None,
+                            start: None,
+                            times: vec![],
+                            locale: None,
+                            role: None,
+                            bearer: false,
+                            conf_credential: ConfCredential::Nkey,
                         },
                     ],
+                    user_template: None,
                     is_system_account: false,
                     max_connections: None,
                     max_payload: None,
+                    max_message_payload: None,
+                    max_leaf_connections: None,
+                    max_exports: None,
+                    max_imports: None,
+                    allow_wildcard_exports: None,
                     exports: vec![],
                     imports: vec![],
                     mappings: HashMap::new(),
+                    signing_keys: None,
+                    roles: vec![],
+                    expiry: None,
+                    disallow_bearer: false,
+                    revocations: vec![],
+                    jetstream: false,
                 },
             ],
             output_dir: PathBuf::from(output_dir),
             tls: None,
             mappings: HashMap::new(),
+            ..Default::default()
         }],
+        backend,
+        ..Default::default()
     };
 
     let forge = NatsForge::from_config(config)?;
     let result = forge.initialize().await?;
 
+    let test_account = result.account("TEST").context("Missing TEST account")?;
     println!("Post-initialize creds paths:");
-    for path in &result.user_creds_paths {
+    for path in test_account.creds.values() {
         println!(" - {} (exists: {})", path.display(), path.exists());
     }
     tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
     assert!(result.operator_jwt_path.exists());
-    assert_eq!(result.account_jwt_paths.len(), 2);
-    assert_eq!(result.user_creds_paths.len(), 3, "Expected 3 user creds files");
-    assert!(result.server_config_path.exists());
-
-    for creds_path in &result.user_creds_paths {
+    assert_eq!(result.account_jwts.len(), 2);
+    assert_eq!(test_account.creds.len(), 3, "Expected 3 user creds files");
+    assert!(result
+        .server_config("test-server")
+        .context("Missing server config")?
+        .exists());
+
+    let operator = jwt::decode_operator_claims(&std::fs::read_to_string(&result.operator_jwt_path)?)?;
+    let account = jwt::decode_account_claims(&std::fs::read_to_string(&test_account.jwt_path)?)?;
+    assert_eq!(account.iss, operator.sub);
+    assert_eq!(account.sub, test_account.id);
+    let test_account_id = &test_account.id;
+
+    for creds_path in test_account.creds.values() {
         println!(
             "Checking creds file: {} (exists: {})",
             creds_path.display(),
@@ -374,71 +614,202 @@ async fn test_pub_sub_permissions() -> anyhow::Result<()> {
             .take_while(|line| !line.contains("------END NATS USER JWT------"))
             .collect::<Vec<_>>()
             .join("\n");
-        let _decoded_jwt = natsforge::extract_account_id(&jwt)?;
-        let jwt_json: serde_json::Value = serde_json::from_str(
-            &String::from_utf8(
-                base64::engine::general_purpose::STANDARD_NO_PAD
-                    .decode(jwt.split('.').nth(1).unwrap())
-                    .context("Failed to decode JWT")?,
-            )
-            .context("Failed to convert to UTF-8")?,
-        )
-        .context("Failed to parse JSON")?;
-
-        let default_value = serde_json::Value::Object(serde_json::Map::new());
-        let nats_perms = jwt_json["nats"].get("pub").unwrap_or(&default_value);
-        let nats_subs = jwt_json["nats"].get("sub").unwrap_or(&default_value);
+        let claims = jwt::decode_user_claims(&jwt)?;
+        assert_eq!(&claims.iss, test_account_id);
+        let permissions = &claims.nats.permissions;
+        let (publish, subscribe) = (&permissions.publish.allow, &permissions.subscribe.allow);
 
         if creds_path.to_string_lossy().contains("sub-only") {
             assert!(
-                nats_subs.to_string().contains("test.sub.>"),
+                subscribe.contains(&"test.sub.>".to_string()),
                 "sub-only missing test.sub.>"
             );
             assert!(
-                !nats_perms.to_string().contains("test.sub.>"),
+                !publish.contains(&"test.sub.>".to_string()),
                 "sub-only has unexpected pub perm"
             );
         } else if creds_path.to_string_lossy().contains("pub-only") {
             assert!(
-                nats_perms.to_string().contains("test.pub.>"),
+                publish.contains(&"test.pub.>".to_string()),
                 "pub-only missing test.pub.>"
             );
             assert!(
-                !nats_subs.to_string().contains("test.pub.>"),
+                !subscribe.contains(&"test.pub.>".to_string()),
                 "pub-only has unexpected sub perm"
             );
         } else if creds_path.to_string_lossy().contains("both") {
             assert!(
-                nats_subs.to_string().contains("test.both.sub.>"),
+                subscribe.contains(&"test.both.sub.>".to_string()),
                 "both missing test.both.sub.>"
             );
             assert!(
-                nats_perms.to_string().contains("test.both.pub.>"),
+                publish.contains(&"test.both.pub.>".to_string()),
                 "both missing test.both.pub.>"
             );
         }
     }
 
-    let mut attempts = 0;
-    let max_attempts = 3;
-    while attempts < max_attempts {
-        match std::fs::remove_dir_all(output_dir) {
-            Ok(()) => break,
-            Err(e) if e.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
-                attempts += 1;
-                println!("Cleanup attempt {}/{} failed: {}", attempts, max_attempts, e);
-                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    forge.cleanup(&result, true).await?;
+    assert!(!Path::new(output_dir).exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_response_permissions() -> anyhow::Result<()> {
+    response_permissions(BackendKind::Nsc, "test-output-response").await
+}
+
+#[tokio::test]
+async fn test_response_permissions_native() -> anyhow::Result<()> {
+    response_permissions(BackendKind::Native, "test-output-response-native").await
+}
+
+async fn response_permissions(backend: BackendKind, output_dir: &str) -> anyhow::Result<()> {
+    let _ = std::fs::remove_dir_all(output_dir);
+    std::fs::create_dir_all(output_dir)?;
+
+    let config = NatsConfig {
+        name: Some("response-test".to_string()),
+        operator: OperatorConfig {
+            name: "test-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "test-server".to_string(),
+            port: 4222,
+            accounts: vec![AccountConfig {
+                name: "TEST".to_string(),
+                users: vec![UserConfig {
+                    name: "responder".to_string(),
+                    allow_sub: vec!["svc.>".to_string()],
+                    deny_pub: vec!["svc.>".to_string()],
+                    allow_pub_response: Some(3),
+                    response_ttl: Some("5s".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            output_dir: PathBuf::from(output_dir),
+            ..Default::default()
+        }],
+        backend,
+        ..Default::default()
+    };
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let creds_path = result.creds("TEST", "responder").context("Missing responder creds")?;
+    let creds_content = std::fs::read_to_string(creds_path)?;
+    let jwt = creds_content
+        .lines()
+        .skip_while(|line| !line.contains("-----BEGIN NATS USER JWT-----"))
+        .nth(1)
+        .context("Missing user JWT")?;
+    let permissions = jwt::decode_user_claims(jwt)?.nats.permissions;
+    let response = permissions.response.context("Missing response permission")?;
+    assert_eq!(response.max, 3);
+    assert_eq!(response.ttl, 5_000_000_000);
+    assert!(permissions.publish.deny.contains(&"svc.>".to_string()));
+    assert!(permissions.subscribe.allow.contains(&"svc.>".to_string()));
+
+    std::fs::remove_dir_all(output_dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_parallel_matches_serial_native() -> anyhow::Result<()> {
+    let many_accounts = |output_dir: PathBuf, concurrency: usize| {
+        let accounts = (0..20)
+            .map(|i| AccountConfig {
+                name: format!("ACC{}", i),
+                users: (0..3)
+                    .map(|u| UserConfig {
+                        name: format!("user{}", u),
+                        allow_pubsub: vec![format!("acc{}.user{}.>", i, u)],
+                        ..Default::default()
+                    })
+                    .collect(),
+                exports: vec![ExportConfig {
+                    subject: format!("acc{}.events", i),
+                    ..Default::default()
+                }],
+                imports: if i > 0 {
+                    vec![ImportConfig {
+                        subject: format!("acc{}.events", i - 1),
+                        account: format!("ACC{}", i - 1),
+                        local_subject: None,
+                        service: false,
+                        share: false,
+                    }]
+                } else {
+                    vec![]
+                },
+                ..Default::default()
+            })
+            .collect();
+        NatsConfig {
+            operator: OperatorConfig {
+                name: "parallel-operator".to_string(),
+                reuse_existing: false,
+                account_server_url: None,
+                service_urls: vec![],
+            },
+            servers: vec![ServerConfig {
+                name: "parallel-server".to_string(),
+                port: 4222,
+                output_dir,
+                accounts,
+                ..Default::default()
+            }],
+            backend: BackendKind::Native,
+            durability: Durability::Fast,
+            concurrency: Some(concurrency),
+            ..Default::default()
+        }
+    };
+
+    // Everything but keys, ids and timestamps, in the order it was produced.
+    let summarize = |result: &natsforge::config::SetupResult, root: &std::path::Path| -> anyhow::Result<Vec<String>> {
+        let mut summary = Vec::new();
+        let mut accounts: Vec<_> = result.account_jwts.iter().collect();
+        accounts.sort_by_key(|(name, _)| name.as_str());
+        let mut paths = Vec::new();
+        for (_, artifacts) in accounts {
+            let mut creds: Vec<_> = artifacts.creds.iter().collect();
+            creds.sort();
+            paths.push(&artifacts.jwt_path);
+            paths.extend(creds.into_iter().map(|(_, path)| path));
+        }
+        for path in paths {
+            let content = std::fs::read_to_string(path)?;
+            let jwt = content.lines().find(|l| l.starts_with("eyJ")).context("No JWT")?;
+            let claims: serde_json::Value = jwt::decode_claims(jwt)?;
+            let mut nats = claims["nats"].clone();
+            for import in nats["imports"].as_array_mut().into_iter().flatten() {
+                import["account"] = serde_json::Value::Null;
             }
-            Err(e) => return Err(anyhow::anyhow!(e)),
+            let name = claims["name"].as_str().unwrap_or_default();
+            let name = name.split_once("-").map(|(n, _)| n).unwrap_or(name);
+            summary.push(format!("{} {} {}", path.strip_prefix(root)?.display(), name, nats));
         }
-    }
-    if attempts == max_attempts {
-        return Err(anyhow::anyhow!(
-            "Failed to clean up {} after {} attempts",
-            output_dir,
-            max_attempts
-        ));
-    }
+        Ok(summary)
+    };
 
+    let serial_dir = tempfile::TempDir::new()?;
+    let serial_root = std::fs::canonicalize(serial_dir.path())?;
+    let serial = NatsForge::from_config(many_accounts(serial_dir.path().to_path_buf(), 1))?
+        .initialize()
+        .await?;
+    let parallel_dir = tempfile::TempDir::new()?;
+    let parallel_root = std::fs::canonicalize(parallel_dir.path())?;
+    let parallel = NatsForge::from_config(many_accounts(parallel_dir.path().to_path_buf(), 8))?
+        .initialize()
+        .await?;
+
+    assert_eq!(serial.account_jwts.values().map(|a| a.creds.len()).sum::<usize>(), 60);
+    assert_eq!(summarize(&serial, &serial_root)?, summarize(&parallel, &parallel_root)?);
     Ok(())
 }
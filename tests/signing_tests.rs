@@ -0,0 +1,117 @@
+use base64::Engine;
+use natsforge::{config::NatsConfig, NatsForge};
+
+fn claims(jwt: &str) -> anyhow::Result<serde_json::Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(output_dir: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "operator": { "name": "signing-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "signing-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [
+                { "name": "SIGNED", "signing_keys": 2, "users": [{ "name": "app" }] },
+                { "name": "PLAIN", "users": [{ "name": "app" }] }
+            ]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_users_are_issued_with_signing_key() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap())?)?
+        .initialize()
+        .await?;
+
+    let account = result.account("SIGNED").unwrap();
+    assert_eq!(account.signing_keys.len(), 2);
+    let account_claims = claims(&std::fs::read_to_string(&account.jwt_path)?)?;
+    assert_eq!(account_claims["nats"]["signing_keys"].as_array().unwrap().len(), 2);
+
+    let creds = std::fs::read_to_string(&account.creds["app"])?;
+    let user_claims = claims(creds.lines().nth(1).unwrap())?;
+    let issuer = user_claims["iss"].as_str().unwrap();
+    assert_ne!(issuer, account.id);
+    assert_eq!(issuer, account.signing_keys[0]);
+    assert_eq!(user_claims["nats"]["issuer_account"], account.id.as_str());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_accounts_without_signing_keys_issue_users_directly() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(output_dir.path().to_str().unwrap())?)?
+        .initialize()
+        .await?;
+
+    let account = result.account("PLAIN").unwrap();
+    assert!(account.signing_keys.is_empty());
+    let creds = std::fs::read_to_string(&account.creds["app"])?;
+    let user_claims = claims(creds.lines().nth(1).unwrap())?;
+    assert_eq!(user_claims["iss"], account.id.as_str());
+    assert!(user_claims["nats"].get("issuer_account").is_none());
+    Ok(())
+}
+
+fn roles_config(output_dir: &str, role: &str) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "operator": { "name": "roles-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "servers": [{
+            "name": "roles-server",
+            "port": 4222,
+            "output_dir": output_dir,
+            "accounts": [{
+                "name": "APP",
+                "roles": [{
+                    "name": "orders",
+                    "allowed_publishes": ["orders.>"],
+                    "allowed_subscribes": ["orders.>", "_INBOX.>"],
+                    "max_payload": 4096
+                }],
+                "users": [{ "name": "clerk", "role": role, "allow_pubsub": ["ignored.>"] }]
+            }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_role_users_get_scope_instead_of_permissions() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = roles_config(output_dir.path().to_str().unwrap(), "orders")?;
+    assert_eq!(config.warnings().len(), 1, "{:?}", config.warnings());
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let account = result.account("APP").unwrap();
+    let account_claims = claims(&std::fs::read_to_string(&account.jwt_path)?)?;
+    let scope = &account_claims["nats"]["signing_keys"][0];
+    assert_eq!(scope["kind"], "user_scope");
+    assert_eq!(scope["role"], "orders");
+    assert_eq!(scope["template"]["pub"]["allow"], serde_json::json!(["orders.>"]));
+    assert_eq!(scope["template"]["payload"], 4096);
+    assert_eq!(account.signing_keys, [scope["key"].as_str().unwrap()]);
+
+    let creds = std::fs::read_to_string(&account.creds["clerk"])?;
+    let user_claims = claims(creds.lines().nth(1).unwrap())?;
+    assert_eq!(user_claims["iss"], scope["key"]);
+    assert_eq!(user_claims["nats"]["issuer_account"], account.id.as_str());
+    assert_eq!(user_claims["nats"]["pub"], serde_json::json!({}));
+    assert_eq!(user_claims["nats"]["sub"], serde_json::json!({}));
+    Ok(())
+}
+
+#[test]
+fn test_unknown_role_is_rejected() -> anyhow::Result<()> {
+    let config = roles_config("unused", "auditors")?;
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("role auditors"), "{}", err);
+    Ok(())
+}
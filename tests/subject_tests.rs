@@ -0,0 +1,126 @@
+use natsforge::{
+    config::NatsConfig,
+    subject::{overlaps, validate_mapping_destination, validate_subject},
+};
+use serde_json::json;
+
+#[test]
+fn test_valid_subjects() {
+    for subject in [
+        "foo",
+        "foo.bar",
+        "foo.*",
+        "*.bar",
+        "foo.>",
+        ">",
+        "*",
+        "foo.*.>",
+        "_INBOX.>",
+        "$JS.API.>",
+    ] {
+        assert!(validate_subject(subject).is_ok(), "{} should be valid", subject);
+    }
+}
+
+#[test]
+fn test_invalid_subjects() {
+    for (subject, reason) in [
+        ("foo.>.bar", "> must be the last token"),
+        (">.foo", "> must be the last token"),
+        ("foo..bar", "empty token"),
+        ("", "empty token"),
+        (".foo", "empty token"),
+        ("foo.", "empty token"),
+        ("foo bar", "whitespace"),
+        ("foo.\tbar", "whitespace"),
+        ("foo*.bar", "wildcards must be a whole token"),
+        ("foo.b>", "wildcards must be a whole token"),
+        ("foo.**", "wildcards must be a whole token"),
+    ] {
+        let err = validate_subject(subject).unwrap_err().to_string();
+        assert!(err.contains(reason), "{:?}: {}", subject, err);
+        assert!(err.contains(&format!("{:?}", subject)), "{}", err);
+    }
+}
+
+#[test]
+fn test_mapping_destinations() {
+    for destination in [
+        "orders.{{wildcard(1)}}",
+        "bar.{{ partition(10,1,2) }}.baz",
+        "plain.subject",
+        "foo.$1",
+    ] {
+        assert!(validate_mapping_destination(destination).is_ok(), "{}", destination);
+    }
+    for destination in ["orders..{{wildcard(1)}}", "orders.{{wildcard(1)", "a b"] {
+        assert!(validate_mapping_destination(destination).is_err(), "{}", destination);
+    }
+}
+
+#[test]
+fn test_overlaps() {
+    assert!(overlaps("orders.>", "orders.create"));
+    assert!(overlaps("orders.create", "orders.*"));
+    assert!(overlaps("*.create", "orders.*"));
+    assert!(overlaps(">", "anything.at.all"));
+    assert!(!overlaps("orders.>", "orders"));
+    assert!(!overlaps("orders.*", "orders.a.b"));
+    assert!(!overlaps("admin.>", "orders.>"));
+}
+
+fn config(account: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "subject-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "subject-server",
+            "port": 4222,
+            "output_dir": "unused",
+            "accounts": [account]
+        }]
+    }))?)
+}
+
+#[test]
+fn test_validate_names_the_offending_subject() -> anyhow::Result<()> {
+    for (account, message) in [
+        (
+            json!({ "name": "APP", "users": [{ "name": "app", "allow_sub": ["orders.>.audit"] }] }),
+            "User app in account APP: Invalid subject \"orders.>.audit\"",
+        ),
+        (
+            json!({ "name": "APP", "exports": [{ "subject": "events..created" }] }),
+            "Export events..created of account APP: Invalid subject",
+        ),
+        (
+            json!({ "name": "APP", "imports": [{ "subject": "svc echo", "account": "SVC" }] }),
+            "Import from account SVC in account APP: Invalid subject \"svc echo\"",
+        ),
+        (
+            json!({ "name": "APP", "mappings": { "orders.*": "orders..{{wildcard(1)}}" } }),
+            "Mapping in account APP: Invalid subject",
+        ),
+    ] {
+        let err = config(account)?.validate().unwrap_err().to_string();
+        assert!(err.contains(message), "{}", err);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_unreachable_deny_warns() -> anyhow::Result<()> {
+    let config = config(json!({
+        "name": "APP",
+        "users": [{
+            "name": "app",
+            "allow_pub": ["orders.>"],
+            "deny_pub": ["orders.delete", "admin.>"],
+            "deny_sub": ["admin.>"]
+        }]
+    }))?;
+    config.validate()?;
+    let warnings = config.warnings();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("denies publishing to admin.>"), "{}", warnings[0]);
+    Ok(())
+}
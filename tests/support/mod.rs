@@ -0,0 +1,158 @@
+//! An [`NscRunner`] that stands in for nsc, so the nsc backend can be tested without nsc
+//! installed.
+
+// Each test binary that includes this module uses only part of it.
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use natsforge::{NatsForgeError, NscRunner};
+use nkeys::KeyPair;
+use serde_json::json;
+
+/// Records every invocation and answers it the way nsc would: writes the operator, account and
+/// activation JWTs into the store where nsc puts them, and prints generated keys and creds.
+/// Their JWTs carry the right subject and type, but aren't signed. Clones share what they
+/// recorded, so a test can keep one while the backend owns another.
+#[derive(Clone, Default)]
+pub struct MockNscRunner {
+    calls: Arc<Mutex<Vec<Vec<String>>>>,
+    /// The operator of each store, by data directory.
+    operators: Arc<Mutex<HashMap<PathBuf, String>>>,
+}
+
+impl MockNscRunner {
+    /// Every invocation so far, in order, without the `--keystore-dir` the backend adds.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The position of the first invocation that starts with `command` and has every flag of
+    /// `flags` set to the given value.
+    pub fn position(&self, command: &[&str], flags: &[(&str, &str)]) -> Option<usize> {
+        self.calls().iter().position(|call| {
+            call.starts_with(&command.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+                && flags.iter().all(|(flag, value)| flag_value(call, flag) == Some(*value))
+        })
+    }
+
+    fn answer(&self, args: &[String]) -> Result<String, String> {
+        let data_dir = flag_value(args, "--data-dir").map(PathBuf::from);
+        let command: Vec<&str> = args.iter().take(2).map(String::as_str).collect();
+        match command.as_slice() {
+            ["--version", ..] => Ok("nsc version 2.10.2".to_string()),
+            ["init", ..] => {
+                let (name, data_dir) = (required(args, "--name")?, data_dir.ok_or("no --data-dir")?);
+                let operator = KeyPair::new_operator().public_key();
+                write(
+                    &data_dir.join(name).join(format!("{}.jwt", name)),
+                    &jwt(&operator, &operator, name, "operator"),
+                )?;
+                let sys = KeyPair::new_account().public_key();
+                write(
+                    &data_dir.join(name).join("accounts/SYS/SYS.jwt"),
+                    &jwt(&sys, &operator, "SYS", "account"),
+                )?;
+                self.operators.lock().unwrap().insert(data_dir, name.to_string());
+                Ok(String::new())
+            }
+            ["add", "account"] => {
+                let (name, data_dir) = (required(args, "--name")?, data_dir.ok_or("no --data-dir")?);
+                let operator = self
+                    .operators
+                    .lock()
+                    .unwrap()
+                    .get(&data_dir)
+                    .cloned()
+                    .ok_or("no operator")?;
+                let account = KeyPair::new_account().public_key();
+                write(
+                    &data_dir
+                        .join(operator)
+                        .join("accounts")
+                        .join(name)
+                        .join(format!("{}.jwt", name)),
+                    &jwt(&account, "OMOCK", name, "account"),
+                )?;
+                Ok(String::new())
+            }
+            ["generate", "nkey"] => Ok(KeyPair::new_account().public_key()),
+            ["generate", "activation"] => {
+                let target = required(args, "--target-account")?;
+                let activation = jwt(target, "AMOCK", required(args, "--subject")?, "activation");
+                write(
+                    Path::new(required(args, "--output-file")?),
+                    &format!(
+                        "-----BEGIN NATS ACTIVATION JWT-----\n{}\n------END NATS ACTIVATION JWT------\n",
+                        activation
+                    ),
+                )?;
+                Ok(String::new())
+            }
+            ["generate", "creds"] => {
+                let user = KeyPair::new_user();
+                let user_jwt = jwt(&user.public_key(), "AMOCK", required(args, "--name")?, "user");
+                Ok(format!(
+                    "-----BEGIN NATS USER JWT-----\n{}\n------END NATS USER JWT------\n\n-----BEGIN USER NKEY \
+                     SEED-----\n{}\n------END USER NKEY SEED------\n",
+                    user_jwt,
+                    user.seed().map_err(|err| err.to_string())?
+                ))
+            }
+            _ => Ok(String::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl NscRunner for MockNscRunner {
+    async fn run(&self, args: &[String]) -> Result<Output, NatsForgeError> {
+        let keystore = args.iter().position(|arg| arg == "--keystore-dir");
+        let recorded = match keystore {
+            Some(i) => [&args[..i], &args[i + 2..]].concat(),
+            None => args.to_vec(),
+        };
+        self.calls.lock().unwrap().push(recorded);
+        let (code, stdout, stderr) = match self.answer(args) {
+            Ok(stdout) => (0, stdout, String::new()),
+            Err(stderr) => (1, String::new(), stderr),
+        };
+        Ok(Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.into_bytes(),
+            stderr: stderr.into_bytes(),
+        })
+    }
+}
+
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let i = args.iter().position(|arg| arg == flag)?;
+    args.get(i + 1).map(String::as_str)
+}
+
+fn required<'a>(args: &'a [String], flag: &str) -> Result<&'a str, String> {
+    flag_value(args, flag).ok_or_else(|| format!("missing {}", flag))
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::create_dir_all(path.parent().unwrap()).map_err(|err| err.to_string())?;
+    std::fs::write(path, contents).map_err(|err| err.to_string())
+}
+
+fn jwt(subject: &str, issuer: &str, name: &str, kind: &str) -> String {
+    let encode = |value: serde_json::Value| URL_SAFE_NO_PAD.encode(value.to_string());
+    format!(
+        "{}.{}.{}",
+        encode(json!({ "typ": "JWT", "alg": "ed25519-nkey" })),
+        encode(json!({ "sub": subject, "iss": issuer, "name": name, "nats": { "type": kind } })),
+        URL_SAFE_NO_PAD.encode("unsigned")
+    )
+}
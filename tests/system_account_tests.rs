@@ -0,0 +1,146 @@
+use std::path::Path;
+#[cfg(feature = "client")]
+use std::time::Duration;
+
+#[cfg(feature = "client")]
+use anyhow::Context;
+use base64::Engine;
+use natsforge::{config::NatsConfig, NatsForge};
+use serde_json::{json, Value};
+
+#[cfg(feature = "client")]
+use crate::common::ServerGuard;
+
+#[cfg(feature = "client")]
+mod common;
+
+fn claims(jwt: &str) -> anyhow::Result<Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn config(output_dir: &Path, port: u16) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "system-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "system_account": {
+            "name": "SYSTEM",
+            "users": [{ "name": "admin" }, { "name": "monitor", "allow_sub": ["$SYS.>", "_INBOX.>"] }],
+            "exports": [{ "subject": "$SYS.REQ.ACCOUNT.*.CLAIMS.LOOKUP", "type": "service" }]
+        },
+        "servers": [{
+            "name": "ops",
+            "port": port,
+            "output_dir": output_dir,
+            "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+        }]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_renamed_system_account_is_the_operators() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path(), 4222)?)?.initialize().await?;
+
+    assert_eq!(result.system_account, "SYSTEM");
+    let system_id = result.account_id("SYSTEM").unwrap();
+    assert!(result.account_id("SYS").is_none());
+
+    let nats_conf = std::fs::read_to_string(result.server_config("ops").unwrap())?;
+    assert!(
+        nats_conf.contains(&format!("system_account: \"{}\"", system_id)),
+        "{}",
+        nats_conf
+    );
+    let operator_jwt = std::fs::read_to_string(&result.operator_jwt_path)?;
+    assert_eq!(claims(&operator_jwt)?["nats"]["system_account"], system_id);
+
+    let account_claims = claims(&std::fs::read_to_string(&result.account_jwts["SYSTEM"].jwt_path)?)?;
+    assert_eq!(account_claims["name"], "SYSTEM");
+    assert_eq!(
+        account_claims["nats"]["exports"][0]["subject"],
+        "$SYS.REQ.ACCOUNT.*.CLAIMS.LOOKUP"
+    );
+    let creds = std::fs::read_to_string(result.creds("SYSTEM", "monitor").unwrap())?;
+    assert_eq!(claims(creds.lines().nth(1).unwrap())?["iss"], system_id);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_without_system_account_the_operator_keeps_sys() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let mut config = config(dir.path(), 4222)?;
+    config.system_account = None;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    assert_eq!(result.system_account, "SYS");
+    let operator_jwt = std::fs::read_to_string(&result.operator_jwt_path)?;
+    assert_eq!(
+        claims(&operator_jwt)?["nats"]["system_account"],
+        result.account_id("SYS").unwrap()
+    );
+    assert!(!result.account_jwts.contains_key("SYSTEM"));
+    Ok(())
+}
+
+#[test]
+fn test_misnamed_system_account_is_rejected() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/ops"), 4222)?;
+    config.servers[0].accounts[0].is_system_account = true;
+
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Account APP is marked is_system_account, but the operator's system account is SYSTEM"),
+        "{}",
+        err
+    );
+
+    config.servers[0].accounts[0].is_system_account = false;
+    config.system_account.as_mut().unwrap().name = "SYS".to_string();
+    let err = config.validate().unwrap_err();
+    assert!(
+        err.to_string().contains("system_account needs a name other than"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_monitoring_through_the_renamed_system_account() -> anyhow::Result<()> {
+    let port = 4282;
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path(), port)?)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("ops").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let client = async_nats::ConnectOptions::with_credentials_file(result.creds("SYSTEM", "monitor").unwrap())
+        .await?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+    let reply = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.request("$SYS.REQ.SERVER.PING", "".into()),
+    )
+    .await??;
+    let statsz: Value = serde_json::from_slice(&reply.payload)?;
+    assert!(statsz["statsz"].is_object(), "{}", statsz);
+
+    // connect_sys falls back to the first user of the renamed account.
+    let client = result.connect_sys("ops").await?;
+    client.flush().await?;
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
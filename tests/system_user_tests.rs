@@ -0,0 +1,163 @@
+use std::path::Path;
+
+#[cfg(feature = "client")]
+use anyhow::Context;
+use base64::Engine;
+use natsforge::{
+    config::{ArtifactMode, NatsConfig},
+    NatsForge,
+};
+use serde_json::{json, Value};
+
+#[cfg(feature = "client")]
+use crate::common::ServerGuard;
+
+#[cfg(feature = "client")]
+mod common;
+
+fn claims(jwt: &str) -> anyhow::Result<Value> {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+/// The user JWT in a `.creds` file.
+fn creds_jwt(creds: &str) -> &str {
+    creds.lines().nth(1).unwrap()
+}
+
+fn config(hub_dir: &Path, leaf_dir: &Path, port: u16) -> anyhow::Result<NatsConfig> {
+    Ok(serde_json::from_value(json!({
+        "operator": { "name": "sys-operator", "reuse_existing": false },
+        "backend": "native",
+        "durability": "fast",
+        "system_user": { "name": "sys" },
+        "servers": [
+            {
+                "name": "hub",
+                "port": port,
+                "output_dir": hub_dir,
+                "accounts": [{ "name": "APP", "users": [{ "name": "app-user" }] }]
+            },
+            {
+                "name": "leaf",
+                "port": port + 1,
+                "output_dir": leaf_dir,
+                "accounts": [{ "name": "EDGE", "users": [{ "name": "edge-user" }] }]
+            }
+        ]
+    }))?)
+}
+
+#[tokio::test]
+async fn test_sys_creds_in_every_output_dir() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let (hub_dir, leaf_dir) = (dir.path().join("hub"), dir.path().join("leaf"));
+    let mut config = config(&hub_dir, &leaf_dir, 4222)?;
+    config.write_manifest = true;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let hub_creds_path = result.sys_creds("hub").unwrap();
+    assert_eq!(hub_creds_path, std::fs::canonicalize(&hub_dir)?.join("SYS-sys.creds"));
+    let creds = std::fs::read_to_string(hub_creds_path)?;
+    assert_eq!(std::fs::read_to_string(leaf_dir.join("SYS-sys.creds"))?, creds);
+    assert!(result.sys_creds("other").is_none());
+
+    // Issued by the SYS account the operator names as its system account.
+    let sys_id = result.account_id("SYS").unwrap();
+    let operator_jwt = std::fs::read_to_string(&result.operator_jwt_path)?;
+    assert_eq!(claims(&operator_jwt)?["nats"]["system_account"], sys_id);
+    let user_claims = claims(creds_jwt(&creds))?;
+    assert_eq!(user_claims["name"], "sys");
+    assert_eq!(user_claims["iss"], sys_id);
+
+    let manifest = result.manifest();
+    assert_eq!(
+        manifest.servers["leaf"].sys_creds_path.as_deref(),
+        result.sys_creds("leaf")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sys_creds_in_memory() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/hub"), Path::new("/srv/leaf"), 4222)?;
+    config.artifact_mode = ArtifactMode::InMemory;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    assert_eq!(result.sys_creds("hub").unwrap(), Path::new("/srv/hub/SYS-sys.creds"));
+    let artifacts = result.artifacts.as_ref().unwrap();
+    let creds = artifacts.creds("SYS", "sys").unwrap();
+    assert_eq!(claims(creds_jwt(creds))?["iss"], result.account_id("SYS").unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_system_user_rejected() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let (hub_dir, leaf_dir) = (dir.path().join("hub"), dir.path().join("leaf"));
+
+    let mut with_role = config(&hub_dir, &leaf_dir, 4222)?;
+    with_role.system_user.as_mut().unwrap().role = Some("monitor".to_string());
+    let err = with_role.validate().unwrap_err();
+    assert!(err.to_string().contains("signing keys"), "{}", err);
+
+    let mut clashing: NatsConfig = serde_json::from_value(json!({
+        "operator": { "name": "sys-operator", "reuse_existing": false },
+        "backend": "native",
+        "system_user": { "name": "sys" },
+        "servers": [{
+            "name": "hub",
+            "port": 4222,
+            "output_dir": hub_dir,
+            "accounts": [{ "name": "SYS", "is_system_account": true, "users": [{ "name": "sys" }] }]
+        }]
+    }))?;
+    let err = clashing.validate().unwrap_err();
+    assert!(
+        err.to_string().contains("also a user of the configured SYS account"),
+        "{}",
+        err
+    );
+    clashing.system_user.as_mut().unwrap().name = "monitor".to_string();
+    clashing.validate()?;
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_sys_user_pings_servers() -> anyhow::Result<()> {
+    let port = 4271;
+    let dir = tempfile::TempDir::new()?;
+    let config = config(&dir.path().join("hub"), &dir.path().join("leaf"), port)?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("hub").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.sys_creds("hub").unwrap())?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+    let reply = tokio::time::timeout(
+        tokio::time::Duration::from_secs(2),
+        client.request("$SYS.REQ.SERVER.PING", "".into()),
+    )
+    .await??;
+    let statsz: Value = serde_json::from_slice(&reply.payload)?;
+    assert!(statsz["statsz"].is_object(), "{}", statsz);
+    assert!(statsz["server"]["id"].is_string(), "{}", statsz);
+
+    // connect_sys picks the system user without any configured SYS account.
+    let client = result.connect_sys("hub").await?;
+    client.flush().await?;
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use natsforge::{
+    config::{AccountConfig, BackendKind, Durability, NatsConfig, OperatorConfig, ServerConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+use crate::common::ServerGuard;
+
+mod common;
+
+const TENANTS: [&str; 5] = ["acme", "globex", "initech", "umbrella", "hooli"];
+
+fn config(port: u16, output_dir: &Path) -> anyhow::Result<NatsConfig> {
+    let server: ServerConfig = serde_json::from_value(json!({
+        "name": "saas",
+        "port": port,
+        "output_dir": output_dir,
+        "accounts": [{ "name": "OPS", "users": [{ "name": "ops" }] }],
+        "account_templates": [{
+            "template": {
+                "name": "TENANT-{tenant}",
+                "users": [{ "name": "{tenant}-app", "allow_pubsub": ["{customer}.>", "_INBOX.>"] }],
+                "exports": [{ "subject": "{customer}.events.>" }],
+                "mappings": { "{customer}.legacy.>": "{customer}.orders.>" }
+            },
+            "instances": TENANTS,
+            "subject_prefix_var": "customer"
+        }]
+    }))?;
+    Ok(NatsConfig {
+        operator: OperatorConfig {
+            name: "saas-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![server],
+        backend: BackendKind::Native,
+        durability: Durability::Fast,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_template_expands_per_tenant() -> anyhow::Result<()> {
+    let mut config = config(4222, Path::new("/srv/saas"))?;
+    config.expand_account_templates()?;
+
+    let server = &config.servers[0];
+    assert!(server.account_templates.is_empty());
+    let names: Vec<&str> = server.accounts.iter().map(|account| account.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "OPS",
+            "TENANT-acme",
+            "TENANT-globex",
+            "TENANT-initech",
+            "TENANT-umbrella",
+            "TENANT-hooli"
+        ]
+    );
+    for (account, tenant) in server.accounts[1..].iter().zip(TENANTS) {
+        let user = &account.users[0];
+        assert_eq!(user.name, format!("{}-app", tenant));
+        assert_eq!(user.allow_pubsub, vec![format!("{}.>", tenant), "_INBOX.>".to_string()]);
+        assert_eq!(account.exports[0].subject, format!("{}.events.>", tenant));
+        let (source, target) = account.mappings.iter().next().unwrap();
+        assert_eq!(*source, format!("{}.legacy.>", tenant));
+        assert_eq!(
+            target.destinations(),
+            vec![(format!("{}.orders.>", tenant).as_str(), 100)]
+        );
+    }
+    config.validate()?;
+    Ok(())
+}
+
+#[test]
+fn test_template_colliding_with_an_account_is_rejected() -> anyhow::Result<()> {
+    let mut config = config(4222, Path::new("/srv/saas"))?;
+    config.servers[0].accounts.push(AccountConfig {
+        name: "TENANT-initech".to_string(),
+        ..Default::default()
+    });
+
+    let err = NatsForge::from_config(config).err().expect("the template collides");
+    assert!(
+        err.to_string()
+            .contains("Account TENANT-initech from template TENANT-{tenant} is already defined"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tenant_cannot_publish_on_another_tenants_prefix() -> anyhow::Result<()> {
+    let port = 4281;
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(port, dir.path())?)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("saas").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(Duration::from_secs(2)).await;
+
+    let url = format!("localhost:{}", port);
+    let (errors_tx, mut errors) = tokio::sync::mpsc::unbounded_channel();
+    let acme = async_nats::ConnectOptions::with_credentials_file(result.creds("TENANT-acme", "acme-app").unwrap())
+        .await?
+        .event_callback(move |event| {
+            let errors_tx = errors_tx.clone();
+            async move {
+                if let async_nats::Event::ServerError(error) = event {
+                    let _ = errors_tx.send(error.to_string());
+                }
+            }
+        })
+        .connect(&url)
+        .await?;
+    let globex =
+        async_nats::ConnectOptions::with_credentials_file(result.creds("TENANT-globex", "globex-app").unwrap())
+            .await?
+            .connect(&url)
+            .await?;
+
+    let mut subscriber = globex.subscribe("globex.>").await?;
+    globex.flush().await?;
+    acme.publish("globex.orders", "intruder".into()).await?;
+    acme.flush().await?;
+
+    let error = tokio::time::timeout(Duration::from_secs(5), errors.recv())
+        .await?
+        .context("event callback dropped")?;
+    assert!(
+        error
+            .to_lowercase()
+            .contains("permissions violation for publish to \"globex.orders\""),
+        "{}",
+        error
+    );
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), subscriber.next())
+            .await
+            .is_err(),
+        "globex received acme's message"
+    );
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
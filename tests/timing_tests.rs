@@ -0,0 +1,238 @@
+use std::{path::Path, time::Duration};
+
+use async_trait::async_trait;
+use natsforge::{
+    config::{AccountConfig, ImportConfig, NatsConfig, OperatorConfig, ServerConfig, UserConfig},
+    timing::{PHASE_ACCOUNTS, PHASE_EDITS, PHASE_EXPORTS, PHASE_OPERATOR, PHASE_OTHER, PHASE_USERS},
+    CredentialBackend, NativeBackend, NatsForge,
+};
+
+#[cfg(unix)]
+mod support;
+
+/// Native backend that sleeps before every account it creates.
+struct SlowAccounts {
+    inner: NativeBackend,
+    delay: Duration,
+}
+
+#[async_trait]
+impl CredentialBackend for SlowAccounts {
+    async fn create_operator(&self, operator: &OperatorConfig, store_dir: &Path) -> natsforge::Result<String> {
+        self.inner.create_operator(operator, store_dir).await
+    }
+
+    async fn system_account_jwt(&self, operator_name: &str, store_dir: &Path) -> natsforge::Result<String> {
+        self.inner.system_account_jwt(operator_name, store_dir).await
+    }
+
+    async fn create_account(
+        &self,
+        account: &AccountConfig,
+        operator_name: &str,
+        store_dir: &Path,
+    ) -> natsforge::Result<String> {
+        tokio::time::sleep(self.delay).await;
+        self.inner.create_account(account, operator_name, store_dir).await
+    }
+
+    async fn generate_activation(
+        &self,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        target_account_id: &str,
+        store_dir: &Path,
+    ) -> natsforge::Result<String> {
+        self.inner
+            .generate_activation(import, src_unique_name, target_account_id, store_dir)
+            .await
+    }
+
+    async fn add_import(
+        &self,
+        account: &AccountConfig,
+        import_name: &str,
+        import: &ImportConfig,
+        src_unique_name: &str,
+        activation: Option<&str>,
+        store_dir: &Path,
+    ) -> natsforge::Result<()> {
+        self.inner
+            .add_import(account, import_name, import, src_unique_name, activation, store_dir)
+            .await
+    }
+
+    async fn revoke_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> natsforge::Result<()> {
+        self.inner.revoke_user(account, user, store_dir).await
+    }
+
+    async fn delete_user(&self, account: &AccountConfig, user: &str, store_dir: &Path) -> natsforge::Result<()> {
+        self.inner.delete_user(account, user, store_dir).await
+    }
+
+    async fn account_jwt(
+        &self,
+        account: &AccountConfig,
+        operator_name: &str,
+        store_dir: &Path,
+    ) -> natsforge::Result<String> {
+        self.inner.account_jwt(account, operator_name, store_dir).await
+    }
+
+    async fn create_user(
+        &self,
+        account: &AccountConfig,
+        user: &UserConfig,
+        store_dir: &Path,
+    ) -> natsforge::Result<String> {
+        self.inner.create_user(account, user, store_dir).await
+    }
+}
+
+#[tokio::test]
+async fn test_setup_timings_breakdown() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let config = NatsConfig {
+        operator: OperatorConfig {
+            name: "timing-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "timing-server".to_string(),
+            port: 4222,
+            output_dir: output_dir.path().to_path_buf(),
+            accounts: vec![AccountConfig {
+                name: "APP".to_string(),
+                unique_name: "APP".to_string(),
+                users: vec![UserConfig {
+                    name: "app-user".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let delay = Duration::from_millis(200);
+    let forge = NatsForge::with_backend(
+        config,
+        Box::new(SlowAccounts {
+            inner: NativeBackend::new(),
+            delay,
+        }),
+    )?;
+    let result = forge.initialize().await?;
+    let timings = &result.timings;
+
+    let sum: Duration = timings.phases.iter().map(|p| p.duration).sum();
+    assert_eq!(sum, timings.total);
+    assert!(timings.phase(PHASE_ACCOUNTS).unwrap() >= delay);
+    assert!(timings.phase(PHASE_OPERATOR).unwrap() < delay);
+    assert!(timings.phase(PHASE_USERS).unwrap() < delay);
+    assert!(timings.phase(PHASE_OTHER).unwrap() < delay);
+    assert!(timings.processes.is_empty());
+
+    let table = timings.breakdown_table();
+    assert!(table.lines().nth(1).unwrap().starts_with(PHASE_ACCOUNTS), "{}", table);
+    Ok(())
+}
+
+/// Mock nsc that sleeps before every account edit and export.
+#[cfg(unix)]
+struct SlowEdits {
+    inner: support::MockNscRunner,
+    delay: Duration,
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl natsforge::NscRunner for SlowEdits {
+    async fn run(&self, args: &[String]) -> natsforge::Result<std::process::Output> {
+        let command: Vec<&str> = args.iter().take(2).map(String::as_str).collect();
+        if command == ["edit", "account"] || command == ["add", "export"] {
+            tokio::time::sleep(self.delay).await;
+        }
+        self.inner.run(args).await
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_nsc_edits_and_exports_are_timed() -> anyhow::Result<()> {
+    let config: NatsConfig = serde_json::from_value(serde_json::json!({
+        "operator": { "name": "timing-operator", "reuse_existing": false },
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "servers": [{
+            "name": "timing-server",
+            "port": 4222,
+            "output_dir": "/srv/timing",
+            "accounts": [{ "name": "APP", "max_connections": 10, "exports": [{ "subject": "app.>" }] }]
+        }]
+    }))?;
+
+    let delay = Duration::from_millis(200);
+    let backend = natsforge::NscBackend::new(None).with_runner(Box::new(SlowEdits {
+        inner: support::MockNscRunner::default(),
+        delay,
+    }));
+    let result = NatsForge::with_backend(config, Box::new(backend))?.initialize().await?;
+    let timings = &result.timings;
+
+    let sum: Duration = timings.phases.iter().map(|p| p.duration).sum();
+    assert_eq!(sum, timings.total);
+    assert!(timings.step(PHASE_EDITS).unwrap() >= delay, "{:#?}", timings);
+    assert!(timings.step(PHASE_EXPORTS).unwrap() >= delay, "{:#?}", timings);
+    assert!(timings.phase(PHASE_ACCOUNTS).unwrap() >= delay * 2, "{:#?}", timings);
+    assert!(timings.phase(PHASE_EDITS).is_none(), "{:#?}", timings);
+
+    let process = |name: &str| timings.processes.iter().find(|p| p.name == name).map(|p| p.duration);
+    assert!(process("nsc edit account").unwrap() >= delay, "{:#?}", timings);
+    assert!(process("nsc add export").unwrap() >= delay, "{:#?}", timings);
+    assert!(timings.external_total() >= delay * 2);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_concurrent_account_steps_stay_within_the_total() -> anyhow::Result<()> {
+    let accounts: Vec<_> = (1..=4)
+        .map(|i| {
+            serde_json::json!({
+                "name": format!("APP{}", i),
+                "max_connections": 10,
+                "exports": [{ "subject": format!("app{}.>", i) }]
+            })
+        })
+        .collect();
+    let config: NatsConfig = serde_json::from_value(serde_json::json!({
+        "operator": { "name": "timing-operator", "reuse_existing": false },
+        "durability": "fast",
+        "artifact_mode": "in_memory",
+        "concurrency": 4,
+        "servers": [{ "name": "timing-server", "port": 4222, "output_dir": "/srv/timing", "accounts": accounts }]
+    }))?;
+
+    let delay = Duration::from_millis(50);
+    let backend = natsforge::NscBackend::new(None).with_runner(Box::new(SlowEdits {
+        inner: support::MockNscRunner::default(),
+        delay,
+    }));
+    let forge = NatsForge::with_backend(config, Box::new(backend))?;
+    let started = std::time::Instant::now();
+    let result = forge.initialize().await?;
+    let wall_time = started.elapsed();
+    let timings = &result.timings;
+
+    assert!(timings.total <= wall_time, "{:?} > {:?}", timings.total, wall_time);
+    let sum: Duration = timings.phases.iter().map(|p| p.duration).sum();
+    assert_eq!(sum, timings.total);
+    assert!(timings.phase(PHASE_ACCOUNTS).unwrap() >= delay * 4, "{:#?}", timings);
+    assert!(timings.step(PHASE_EDITS).unwrap() >= delay * 4, "{:#?}", timings);
+    assert!(timings.step(PHASE_EXPORTS).unwrap() >= delay * 4, "{:#?}", timings);
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use natsforge::{
+    config::{AccountConfig, BackendKind, Durability, NatsConfig, OperatorConfig, ServerConfig, UserConfig},
+    NatsForge,
+};
+use serde_json::json;
+
+fn config(output_dir: &Path, account: serde_json::Value) -> anyhow::Result<NatsConfig> {
+    Ok(NatsConfig {
+        operator: OperatorConfig {
+            name: "load-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "load".to_string(),
+            port: 4222,
+            output_dir: output_dir.to_path_buf(),
+            accounts: vec![serde_json::from_value(account)?],
+            ..Default::default()
+        }],
+        backend: BackendKind::Native,
+        durability: Durability::Fast,
+        concurrency: Some(16),
+        ..Default::default()
+    })
+}
+
+fn load_account() -> serde_json::Value {
+    json!({
+        "name": "LOAD",
+        "users": [{ "name": "controller" }],
+        "user_template": {
+            "name_pattern": "load-{i:03}",
+            "count": 500,
+            "base": { "allow_pub": ["load.{i}.>"], "allow_sub": ["_INBOX.>"], "max_payload": 1024 }
+        }
+    })
+}
+
+#[test]
+fn test_user_template_expands_into_numbered_users() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/load"), load_account())?;
+    config.expand_user_templates()?;
+
+    let account = &config.servers[0].accounts[0];
+    assert!(account.user_template.is_none());
+    assert_eq!(account.users.len(), 501);
+    assert_eq!(account.users[0].name, "controller");
+    let user = |name: &str| account.users.iter().find(|user| user.name == name).unwrap();
+    assert_eq!(user("load-001").allow_pub, vec!["load.1.>"]);
+    assert_eq!(user("load-042").allow_pub, vec!["load.42.>"]);
+    assert_eq!(user("load-500").allow_sub, vec!["_INBOX.>"]);
+    assert_eq!(user("load-500").max_payload, Some(1024));
+    assert!(account
+        .users
+        .iter()
+        .all(|user| user.name != "load-000" && user.name != "load-501"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_setup_result_has_creds_for_every_templated_user() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::from_config(config(dir.path(), load_account())?)?
+        .initialize()
+        .await?;
+
+    let creds = &result.account_jwts["LOAD"].creds;
+    assert_eq!(creds.len(), 501);
+    for i in 1..=500 {
+        let path = result
+            .creds("LOAD", &format!("load-{:03}", i))
+            .unwrap_or_else(|| panic!("no creds for load-{:03}", i));
+        assert!(path.is_file(), "{}", path.display());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_user_template_colliding_with_a_user_is_rejected() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/load"), load_account())?;
+    let account: &mut AccountConfig = &mut config.servers[0].accounts[0];
+    account.users.push(UserConfig {
+        name: "load-007".to_string(),
+        ..Default::default()
+    });
+
+    let err = NatsForge::from_config(config).err().expect("the template collides");
+    assert!(
+        err.to_string()
+            .contains("User load-007 from the user_template of account LOAD is already defined"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_new_expands_user_templates() -> anyhow::Result<()> {
+    let dir = tempfile::TempDir::new()?;
+    let result = NatsForge::new(config(dir.path(), load_account())?).initialize().await?;
+
+    assert_eq!(result.account_jwts["LOAD"].creds.len(), 501);
+    assert!(result.creds("LOAD", "load-500").is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_new_leaves_a_colliding_template_to_initialize() -> anyhow::Result<()> {
+    let mut config = config(Path::new("/srv/load"), load_account())?;
+    config.servers[0].accounts[0].users.push(UserConfig {
+        name: "load-007".to_string(),
+        ..Default::default()
+    });
+
+    let forge = NatsForge::new(config);
+    for _ in 0..2 {
+        let err = forge.initialize().await.unwrap_err();
+        assert!(err.to_string().contains("User load-007"), "{}", err);
+    }
+    Ok(())
+}
@@ -1,20 +1,24 @@
 use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::Context;
+use base64::Engine;
 use futures_util::StreamExt;
+#[cfg(feature = "client")]
+use natsforge::config::{
+    AccountConfig, ConfCredential, ExportConfig, JetStreamConfig, LeafNodeConfig, OperatorConfig, ServerConfig,
+};
 use natsforge::{
-    config::{
-        AccountConfig, ExportConfig, JetStreamConfig, LeafNodeConfig, NatsConfig, OperatorConfig, ServerConfig,
-        UserConfig,
-    },
+    config::{NatsConfig, UserConfig},
     NatsForge,
 };
-use tokio::{self, io::AsyncBufReadExt};
+#[cfg(feature = "client")]
+use tokio::io::AsyncBufReadExt;
 
 use crate::common::ServerGuard;
 
 mod common;
 
+#[cfg(feature = "client")]
 #[tokio::test]
 async fn test_setup_validation() -> anyhow::Result<()> {
     let validation_port = 4223;
@@ -40,18 +44,21 @@ async fn test_setup_validation() -> anyhow::Result<()> {
         operator: OperatorConfig {
             name: "test-operator".to_string(),
             reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
         },
         servers: vec![ServerConfig {
             name: "validation-server".to_string(),
             port: validation_port,
             jetstream: JetStreamConfig {
                 enabled: true,
-                store_dir: Some("test-output-validation/jetstream".to_string()),
+                store_dir: Some("jetstream".to_string()),
                 domain: Some("test".to_string()),
                 max_memory: None,
                 max_storage: None,
                 subject_transform: None,
                 republish: vec![],
+                absolute_paths: true,
             },
             leafnodes: LeafNodeConfig::default(),
             accounts: vec![AccountConfig {
@@ -59,29 +66,55 @@ async fn test_setup_validation() -> anyhow::Result<()> {
                 unique_name: "APP".to_string(),
                 users: vec![UserConfig {
                     name: "app-user".to_string(),
-                    allowed_pubsub: None,
-                    allowed_publishes: None,
-                    allowed_subjects: Some(vec!["test.>".to_string()]),
-                    denied_pubsub: None,
-                    denied_publishes: None,
-                    denied_subjects: Some(vec!["forbidden.>".to_string()]),
+                    allow_pubsub: vec![],
+                    allow_pub: vec![],
+                    allow_sub: vec!["test.>".to_string()],
+                    deny_pubsub: vec![],
+                    deny_pub: vec![],
+                    deny_sub: vec!["forbidden.>".to_string()],
                     allow_pub_response: None,
+                    response_ttl: None,
+                    source_networks: vec![],
+                    max_subscriptions: None,
+                    max_data: None,
+                    max_payload: None,
                     expiry: Some("2025-12-31T23:59:59Z".to_string()),
+                    start: None,
+                    times: vec![],
+                    locale: None,
+                    role: None,
+                    bearer: false,
+                    conf_credential: ConfCredential::Nkey,
                 }],
+                user_template: None,
                 is_system_account: false,
                 max_connections: Some(1),
                 max_payload: Some(1024),
+                max_message_payload: None,
+                max_leaf_connections: None,
+                max_exports: None,
+                max_imports: None,
+                allow_wildcard_exports: None,
                 exports: vec![ExportConfig {
                     subject: "test.data".to_string(),
                     is_service: false,
+                    ..Default::default()
                 }],
                 imports: vec![],
                 mappings: HashMap::new(),
+                signing_keys: None,
+                roles: vec![],
+                expiry: None,
+                disallow_bearer: false,
+                revocations: vec![],
+                jetstream: false,
             }],
             output_dir: PathBuf::from("test-output-validation"),
             tls: None,
             mappings: HashMap::new(),
+            ..Default::default()
         }],
+        ..Default::default()
     };
 
     let forge = NatsForge::from_config(config)?;
@@ -91,7 +124,7 @@ async fn test_setup_validation() -> anyhow::Result<()> {
 
     let server = tokio::process::Command::new("nats-server")
         .arg("-c")
-        .arg(&result.server_config_path)
+        .arg(result.server_config("validation-server").unwrap())
         .arg("-DV")
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -101,32 +134,7 @@ async fn test_setup_validation() -> anyhow::Result<()> {
 
     tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
 
-    let app_user_creds = result
-        .user_creds_paths
-        .iter()
-        .find(|path| path.to_string_lossy().contains("app-user"))
-        .context("Failed to find app user credentials")?;
-
-    let creds = std::fs::read_to_string(app_user_creds).context("Failed to read user creds")?;
-
-    let mut retry_count = 0;
-    let max_retries = 3;
-    let client = loop {
-        match async_nats::ConnectOptions::with_credentials(&creds)
-            .context("Failed to parse credentials")?
-            .connect(&format!("localhost:{}", validation_port))
-            .await
-        {
-            Ok(client) => break client,
-            Err(e) => {
-                retry_count += 1;
-                if retry_count >= max_retries {
-                    return Err(e.into());
-                }
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-        }
-    };
+    let client = result.connect("validation-server", "APP", "app-user").await?;
 
     let sub_result = client.subscribe("forbidden.bar").await?;
     let mut sub = sub_result;
@@ -136,9 +144,8 @@ async fn test_setup_validation() -> anyhow::Result<()> {
         "Should not receive messages on denied subject"
     );
 
-    let second_client_result = async_nats::ConnectOptions::with_credentials(&creds)
-        .context("Failed to parse credentials")?
-        .connect(&format!("localhost:{}", validation_port))
+    let second_client_result = result
+        .connect_within("validation-server", "APP", "app-user", std::time::Duration::ZERO)
         .await;
     assert!(
         second_client_result.is_err(),
@@ -150,6 +157,7 @@ async fn test_setup_validation() -> anyhow::Result<()> {
 
     Ok(())
 }
+#[cfg(feature = "client")]
 #[tokio::test]
 async fn test_hub_leaf_validation() -> anyhow::Result<()> {
     let hub_port = 4232;
@@ -186,14 +194,25 @@ async fn test_hub_leaf_validation() -> anyhow::Result<()> {
     let result = forge.initialize().await?;
 
     // Log configs for inspection
-    let hub_config = std::fs::read_to_string(&result.server_config_paths.as_ref().unwrap()[0])?;
-    let leaf_config = std::fs::read_to_string(&result.server_config_paths.as_ref().unwrap()[1])?;
+    let hub_config_path = result.server_config("central-hub").context("Missing hub config")?;
+    let leaf_config_path = result.server_config("edge-leaf").context("Missing leaf config")?;
+    let hub_config = std::fs::read_to_string(hub_config_path)?;
+    let leaf_config = std::fs::read_to_string(leaf_config_path)?;
     println!("Hub config:\n{}", hub_config);
     println!("Leaf config:\n{}", leaf_config);
 
+    let app_service_id = result.account_id("app-service").context("Missing app-service ID")?;
+    assert_eq!(app_service_id.len(), 56);
+    assert!(app_service_id.starts_with('A'));
+    assert!(
+        leaf_config.contains(&format!("account: \"{}\"", app_service_id)),
+        "leafnode remote should bind to app-service"
+    );
+    assert_eq!(result.account_id("SYS").map(str::len), Some(56));
+
     let hub_server = tokio::process::Command::new("nats-server")
         .arg("-c")
-        .arg(&result.server_config_paths.as_ref().unwrap()[0])
+        .arg(hub_config_path)
         .arg("-DV")
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -217,7 +236,7 @@ async fn test_hub_leaf_validation() -> anyhow::Result<()> {
 
     let leaf_server = tokio::process::Command::new("nats-server")
         .arg("-c")
-        .arg(&result.server_config_paths.as_ref().unwrap()[1])
+        .arg(leaf_config_path)
         .arg("-DV")
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -239,60 +258,8 @@ async fn test_hub_leaf_validation() -> anyhow::Result<()> {
 
     tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
 
-    let service_user_creds = result
-        .user_creds_paths
-        .iter()
-        .find(|path| path.to_string_lossy().contains("service-user"))
-        .ok_or_else(|| anyhow::anyhow!("Failed to find service-user credentials"))?;
-    let creds = std::fs::read_to_string(service_user_creds)?;
-    println!("Using creds from: {}", service_user_creds.display());
-
-    let mut retry_count = 0;
-    let max_retries = 5;
-
-    let leaf_client = loop {
-        println!("Attempting to connect to leaf (retry {}/{})", retry_count, max_retries);
-        match async_nats::ConnectOptions::with_credentials(&creds)
-            .map_err(|e| anyhow::anyhow!("Failed to parse credentials: {}", e))?
-            .connect(&format!("localhost:{}", leaf_port))
-            .await
-        {
-            Ok(client) => break client,
-            Err(e) => {
-                retry_count += 1;
-                if retry_count >= max_retries {
-                    return Err(anyhow::anyhow!(
-                        "Failed to connect to leaf after {} retries: {}",
-                        max_retries,
-                        e
-                    ));
-                }
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-        }
-    };
-
-    let hub_client = loop {
-        println!("Attempting to connect to hub (retry {}/{})", retry_count, max_retries);
-        match async_nats::ConnectOptions::with_credentials(&creds)
-            .map_err(|e| anyhow::anyhow!("Failed to parse credentials: {}", e))?
-            .connect(&format!("localhost:{}", hub_port))
-            .await
-        {
-            Ok(client) => break client,
-            Err(e) => {
-                retry_count += 1;
-                if retry_count >= max_retries {
-                    return Err(anyhow::anyhow!(
-                        "Failed to connect to hub after {} retries: {}",
-                        max_retries,
-                        e
-                    ));
-                }
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-        }
-    };
+    let leaf_client = result.connect("edge-leaf", "app-service", "service-user").await?;
+    let hub_client = result.connect("central-hub", "app-service", "service-user").await?;
 
     let mut sub = hub_client
         .subscribe("events.test")
@@ -324,3 +291,1642 @@ async fn test_hub_leaf_validation() -> anyhow::Result<()> {
 
     Ok(())
 }
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_ipv6_loopback_server() -> anyhow::Result<()> {
+    let ipv6_port = 4243;
+    let output_dir = "test-output-ipv6";
+
+    if tokio::net::TcpListener::bind(("::1", ipv6_port)).await.is_err() {
+        println!("IPv6 loopback unavailable, skipping");
+        return Ok(());
+    }
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = NatsConfig {
+        name: Some("ipv6-test".to_string()),
+        operator: OperatorConfig {
+            name: "test-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "ipv6-server".to_string(),
+            port: ipv6_port,
+            host: Some("::1".to_string()),
+            accounts: vec![AccountConfig {
+                name: "APP".to_string(),
+                unique_name: "".to_string(),
+                users: vec![UserConfig {
+                    name: "app-user".to_string(),
+                    allow_pubsub: vec!["v6.>".to_string()],
+                    allow_pub: vec![],
+                    allow_sub: vec![],
+                    deny_pubsub: vec![],
+                    deny_pub: vec![],
+                    deny_sub: vec![],
+                    allow_pub_response: None,
+                    response_ttl: None,
+                    source_networks: vec![],
+                    max_subscriptions: None,
+                    max_data: None,
+                    max_payload: None,
+                    expiry: None,
+                    start: None,
+                    times: vec![],
+                    locale: None,
+                    role: None,
+                    bearer: false,
+                    conf_credential: ConfCredential::Nkey,
+                }],
+                user_template: None,
+                is_system_account: false,
+                max_connections: None,
+                max_payload: None,
+                max_message_payload: None,
+                max_leaf_connections: None,
+                max_exports: None,
+                max_imports: None,
+                allow_wildcard_exports: None,
+                exports: vec![],
+                imports: vec![],
+                mappings: HashMap::new(),
+                signing_keys: None,
+                roles: vec![],
+                expiry: None,
+                disallow_bearer: false,
+                revocations: vec![],
+                jetstream: false,
+            }],
+            output_dir: PathBuf::from(output_dir),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let config_content = std::fs::read_to_string(result.server_config("ipv6-server").unwrap())?;
+    assert!(config_content.contains("host: \"::1\""));
+    assert_eq!(result.connections[0].url, format!("nats://[::1]:{}", ipv6_port));
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("ipv6-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let client = result.connect("ipv6-server", "APP", "app-user").await?;
+
+    let mut sub = client.subscribe("v6.test").await?;
+    client.publish("v6.test", "over ipv6".into()).await?;
+    client.flush().await?;
+    let msg = tokio::time::timeout(tokio::time::Duration::from_secs(2), sub.next())
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No message received"))?;
+    assert_eq!(msg.payload, "over ipv6");
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_service_import_request_reply() -> anyhow::Result<()> {
+    let service_port = 4244;
+    let output_dir = "test-output-service";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "service-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "service-server",
+            "port": service_port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [
+                {
+                    "name": "SVC",
+                    "users": [{ "name": "responder", "allow_pubsub": ["svc.>"] }],
+                    "exports": [{ "subject": "svc.echo", "is_service": true, "grant_responses": true }]
+                },
+                {
+                    "name": "CLIENT",
+                    "users": [{ "name": "requester", "allow_pubsub": ["_INBOX.>"] }],
+                    "imports": [{ "subject": "svc.echo", "account": "SVC", "local_subject": "echo", "service": true }]
+                }
+            ]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("service-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let responder = result.connect("service-server", "SVC", "responder").await?;
+    let requester = result.connect("service-server", "CLIENT", "requester").await?;
+
+    let mut requests = responder.subscribe("svc.echo").await?;
+    responder.flush().await?;
+    let responder_task = tokio::spawn(async move {
+        if let Some(request) = requests.next().await {
+            if let Some(reply) = request.reply {
+                responder.publish(reply, request.payload).await?;
+                responder.flush().await?;
+            }
+        }
+        anyhow::Ok(())
+    });
+
+    let response = tokio::time::timeout(
+        tokio::time::Duration::from_secs(2),
+        requester.request("echo", "ping".into()),
+    )
+    .await??;
+    assert_eq!(response.payload, "ping");
+    responder_task.await??;
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_max_connections_cap() -> anyhow::Result<()> {
+    let capped_port = 4245;
+    let output_dir = "test-output-capped";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "capped-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "capped-server",
+            "port": capped_port,
+            "max_connections": 1,
+            "output_dir": output_dir,
+            "accounts": [{
+                "name": "APP",
+                "max_connections": 5,
+                "users": [{ "name": "app-user" }]
+            }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    assert_eq!(result.warnings.len(), 1, "{:?}", result.warnings);
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("capped-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "app-user").unwrap())?;
+    let url = format!("localhost:{}", capped_port);
+    let first = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&url)
+        .await?;
+    first.flush().await?;
+
+    let second = tokio::time::timeout(
+        tokio::time::Duration::from_secs(2),
+        async_nats::ConnectOptions::with_credentials(&creds)?.connect(&url),
+    )
+    .await;
+    assert!(
+        !matches!(second, Ok(Ok(_))),
+        "the server cap should reject a second connection"
+    );
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_role_scoped_user() -> anyhow::Result<()> {
+    let scoped_port = 4246;
+    let output_dir = "test-output-scoped";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "scoped-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "scoped-server",
+            "port": scoped_port,
+            "output_dir": output_dir,
+            "accounts": [{
+                "name": "APP",
+                "roles": [{
+                    "name": "orders",
+                    "allowed_publishes": ["orders.>"],
+                    "allowed_subscribes": ["orders.>"]
+                }],
+                "users": [
+                    { "name": "clerk", "role": "orders" },
+                    { "name": "admin" }
+                ]
+            }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let clerk_creds = std::fs::read_to_string(result.creds("APP", "clerk").unwrap())?;
+    let jwt = clerk_creds.lines().nth(1).context("creds without a JWT")?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(jwt.split('.').nth(1).unwrap())?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload)?;
+    assert_eq!(claims["nats"]["pub"], serde_json::json!({}));
+    assert_eq!(claims["nats"]["sub"], serde_json::json!({}));
+    assert_eq!(claims["nats"]["issuer_account"], result.account_id("APP").unwrap());
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("scoped-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let url = format!("localhost:{}", scoped_port);
+    let admin_creds = std::fs::read_to_string(result.creds("APP", "admin").unwrap())?;
+    let admin = async_nats::ConnectOptions::with_credentials(&admin_creds)?
+        .connect(&url)
+        .await?;
+    let clerk = async_nats::ConnectOptions::with_credentials(&clerk_creds)?
+        .connect(&url)
+        .await?;
+
+    let mut orders = clerk.subscribe("orders.>").await?;
+    let mut secrets = clerk.subscribe("secrets.>").await?;
+    clerk.flush().await?;
+    admin.publish("orders.new", "order".into()).await?;
+    admin.publish("secrets.key", "secret".into()).await?;
+    admin.flush().await?;
+
+    let order = tokio::time::timeout(tokio::time::Duration::from_secs(2), orders.next()).await?;
+    assert_eq!(order.context("no order received")?.payload.as_ref(), b"order");
+    let secret = tokio::time::timeout(tokio::time::Duration::from_secs(1), secrets.next()).await;
+    assert!(
+        secret.is_err() || secret.unwrap().is_none(),
+        "the role's scope should keep the clerk from subscribing to secrets"
+    );
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_source_network_restriction() -> anyhow::Result<()> {
+    let restricted_port = 4247;
+    let output_dir = "test-output-source-network";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "source-network-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "restricted-server",
+            "port": restricted_port,
+            "output_dir": output_dir,
+            "accounts": [{
+                "name": "OPS",
+                "users": [
+                    { "name": "vpn-only", "source_networks": ["10.99.0.0/16"] },
+                    { "name": "local", "source_networks": ["127.0.0.0/8"] }
+                ]
+            }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("restricted-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let url = format!("127.0.0.1:{}", restricted_port);
+    let local_creds = std::fs::read_to_string(result.creds("OPS", "local").unwrap())?;
+    let local = async_nats::ConnectOptions::with_credentials(&local_creds)?
+        .connect(&url)
+        .await?;
+    local.flush().await?;
+
+    let vpn_creds = std::fs::read_to_string(result.creds("OPS", "vpn-only").unwrap())?;
+    let vpn = tokio::time::timeout(
+        tokio::time::Duration::from_secs(2),
+        async_nats::ConnectOptions::with_credentials(&vpn_creds)?.connect(&url),
+    )
+    .await;
+    assert!(
+        !matches!(vpn, Ok(Ok(_))),
+        "a connection from 127.0.0.1 should be rejected for a user limited to 10.99.0.0/16"
+    );
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_private_export_activation() -> anyhow::Result<()> {
+    let private_port = 4248;
+    let output_dir = "test-output-private-export";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "private-export-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "private-server",
+            "port": private_port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [
+                {
+                    "name": "FEED",
+                    "users": [{ "name": "publisher" }],
+                    "exports": [{ "subject": "prices.>", "private": true }]
+                },
+                {
+                    "name": "DESK",
+                    "users": [{ "name": "trader" }],
+                    "imports": [{ "subject": "prices.fx", "account": "FEED" }]
+                }
+            ]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let desk_jwt = std::fs::read_to_string(&result.account("DESK").unwrap().jwt_path)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(desk_jwt.split('.').nth(1).unwrap())?;
+    let desk_claims: serde_json::Value = serde_json::from_slice(&payload)?;
+    assert!(desk_claims["nats"]["imports"][0]["token"].is_string());
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("private-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let publisher = result.connect("private-server", "FEED", "publisher").await?;
+    let trader = result.connect("private-server", "DESK", "trader").await?;
+
+    let mut prices = trader.subscribe("prices.fx").await?;
+    trader.flush().await?;
+    publisher.publish("prices.fx", "1.0842".into()).await?;
+    publisher.flush().await?;
+
+    let msg = tokio::time::timeout(tokio::time::Duration::from_secs(2), prices.next())
+        .await?
+        .context("No message received through the private export")?;
+    assert_eq!(msg.payload, "1.0842");
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_add_user_to_running_server() -> anyhow::Result<()> {
+    let port = 4249;
+    let output_dir = "test-output-add-user";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "add-user-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "add-user-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [{ "name": "APP", "users": [{ "name": "first" }] }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("add-user-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    // The account JWT the server loaded doesn't change, so the new user works right away.
+    let creds_path = forge
+        .add_user(
+            "APP",
+            &UserConfig {
+                name: "second".to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+    let creds = std::fs::read_to_string(creds_path)?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+    let mut sub = client.subscribe("app.hello").await?;
+    client.publish("app.hello", "late but here".into()).await?;
+    client.flush().await?;
+    let msg = tokio::time::timeout(tokio::time::Duration::from_secs(2), sub.next())
+        .await?
+        .context("No message received")?;
+    assert_eq!(msg.payload, "late but here");
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rotated_user_old_creds_stop_working() -> anyhow::Result<()> {
+    let port = 4250;
+    let output_dir = "test-output-rotate-user";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "rotate-user-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "rotate-user-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let old_creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    forge.rotate_user("APP", "worker", true).await?;
+    let new_creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+
+    // Starting after the rotation is the same as a reload: the server has the revoking account JWT.
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("rotate-user-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let old = async_nats::ConnectOptions::with_credentials(&old_creds)?
+        .connect(&format!("localhost:{}", port))
+        .await;
+    assert!(old.is_err(), "the old creds still connect");
+    async_nats::ConnectOptions::with_credentials(&new_creds)?
+        .connect(&format!("localhost:{}", port))
+        .await?;
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_full_resolver_picks_up_changes_on_reload() -> anyhow::Result<()> {
+    let port = 4251;
+    let output_dir = "test-output-full-resolver";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "full-resolver-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "full-resolver-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "resolver": { "full": {} },
+            "accounts": [{ "name": "APP", "users": [{ "name": "kept" }, { "name": "lost" }] }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let kept_creds = std::fs::read_to_string(result.creds("APP", "kept").unwrap())?;
+    let lost_creds = std::fs::read_to_string(result.creds("APP", "lost").unwrap())?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("full-resolver-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    let url = format!("localhost:{}", port);
+    async_nats::ConnectOptions::with_credentials(&lost_creds)?
+        .connect(&url)
+        .await?;
+
+    // The account is only in the resolver dir, so the revocation lands there and a reload applies it.
+    forge.revoke_user("APP", "lost").await?;
+    let pid = server_guard.0.id().context("NATS server has exited")?;
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &pid.to_string()])
+        .status()?;
+    assert!(status.success());
+    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+    let lost = async_nats::ConnectOptions::with_credentials(&lost_creds)?
+        .connect(&url)
+        .await;
+    assert!(lost.is_err(), "the revoked creds still connect");
+    async_nats::ConnectOptions::with_credentials(&kept_creds)?
+        .connect(&url)
+        .await?;
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_push_accounts_to_running_server() -> anyhow::Result<()> {
+    let port = 4252;
+    let output_dir = "test-output-push-accounts";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "push-accounts-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "push-accounts-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "resolver": { "full": {} },
+            "accounts": [{ "name": "APP", "users": [{ "name": "kept" }, { "name": "lost" }] }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let kept_creds = std::fs::read_to_string(result.creds("APP", "kept").unwrap())?;
+    let lost_creds = std::fs::read_to_string(result.creds("APP", "lost").unwrap())?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("push-accounts-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    let url = format!("nats://localhost:{}", port);
+
+    // The pushed account JWT applies right away, without a reload.
+    forge.revoke_user("APP", "lost").await?;
+    let report = forge.push_accounts(&url).await?;
+    assert!(report.is_success(), "{:?}", report);
+    assert!(
+        report.accounts.iter().any(|pushed| pushed.account == "APP"),
+        "{:?}",
+        report
+    );
+
+    let lost = async_nats::ConnectOptions::with_credentials(&lost_creds)?
+        .connect(&url)
+        .await;
+    assert!(lost.is_err(), "the revoked creds still connect");
+    async_nats::ConnectOptions::with_credentials(&kept_creds)?
+        .connect(&url)
+        .await?;
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clustered_servers_share_messages() -> anyhow::Result<()> {
+    let (east_port, west_port) = (4253, 4254);
+    let output_dir = "test-output-cluster";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "cluster-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [
+            {
+                "name": "cluster-east",
+                "port": east_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/east", output_dir),
+                "cluster": { "name": "forge", "port": 6253, "routes": ["localhost:6254"] },
+                "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+            },
+            {
+                "name": "cluster-west",
+                "port": west_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/west", output_dir),
+                "cluster": { "name": "forge", "port": 6254, "routes": ["localhost:6253"] }
+            }
+        ]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let mut guards = Vec::new();
+    for server in ["cluster-east", "cluster-west"] {
+        let child = tokio::process::Command::new("nats-server")
+            .arg("-c")
+            .arg(result.server_config(server).unwrap())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to start NATS server")?;
+        guards.push(ServerGuard(child));
+    }
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let west = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", west_port))
+        .await?;
+    let mut sub = west.subscribe("cluster.hello").await?;
+    west.flush().await?;
+    // Give the subscription time to propagate over the route.
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let east = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", east_port))
+        .await?;
+    east.publish("cluster.hello", "across the route".into()).await?;
+    east.flush().await?;
+    let msg = tokio::time::timeout(tokio::time::Duration::from_secs(2), sub.next())
+        .await?
+        .context("No message received")?;
+    assert_eq!(msg.payload, "across the route");
+
+    std::fs::remove_dir_all(output_dir)?;
+    for mut guard in guards {
+        guard.0.kill().await.context("Failed to kill NATS server")?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_gateway_request_answered_in_other_region() -> anyhow::Result<()> {
+    let (east_port, west_port) = (4255, 4256);
+    let output_dir = "test-output-gateway";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let gateways = serde_json::json!([
+        { "name": "east", "urls": ["localhost:7255"] },
+        { "name": "west", "urls": ["localhost:7256"] }
+    ]);
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "gateway-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [
+            {
+                "name": "gateway-east",
+                "port": east_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/east", output_dir),
+                "gateway": { "name": "east", "port": 7255, "gateways": gateways },
+                "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+            },
+            {
+                "name": "gateway-west",
+                "port": west_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/west", output_dir),
+                "gateway": { "name": "west", "port": 7256, "gateways": gateways }
+            }
+        ]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let mut guards = Vec::new();
+    for server in ["gateway-east", "gateway-west"] {
+        let child = tokio::process::Command::new("nats-server")
+            .arg("-c")
+            .arg(result.server_config(server).unwrap())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to start NATS server")?;
+        guards.push(ServerGuard(child));
+    }
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let west = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", west_port))
+        .await?;
+    let mut requests = west.subscribe("region.echo").await?;
+    west.flush().await?;
+    tokio::spawn(async move {
+        while let Some(request) = requests.next().await {
+            if let Some(reply) = request.reply {
+                let _ = west.publish(reply, request.payload).await;
+            }
+        }
+    });
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let east = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", east_port))
+        .await?;
+    let response = tokio::time::timeout(
+        tokio::time::Duration::from_secs(5),
+        east.request("region.echo", "from the east".into()),
+    )
+    .await??;
+    assert_eq!(response.payload, "from the east");
+
+    std::fs::remove_dir_all(output_dir)?;
+    for mut guard in guards {
+        guard.0.kill().await.context("Failed to kill NATS server")?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_monitoring_endpoint_serves_varz() -> anyhow::Result<()> {
+    let port = 4257;
+    let http_port = 8257;
+    let output_dir = "test-output-monitoring";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "monitoring-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "monitored-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "monitoring": { "http_port": http_port },
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("monitored-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", http_port)).await?;
+    stream
+        .write_all(b"GET /varz HTTP/1.0\r\nHost: localhost\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let (head, body) = response.split_once("\r\n\r\n").context("No HTTP body")?;
+    assert!(
+        head.starts_with("HTTP/1.0 200") || head.starts_with("HTTP/1.1 200"),
+        "{}",
+        head
+    );
+    let varz: serde_json::Value = serde_json::from_str(body)?;
+    assert_eq!(varz["server_name"], "monitored-server");
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+/// The address this host uses to reach other machines. Connecting a UDP socket sends nothing.
+fn lan_ip() -> Option<std::net::IpAddr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    Some(socket.local_addr().ok()?.ip()).filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+}
+
+#[tokio::test]
+async fn test_server_binds_only_its_host() -> anyhow::Result<()> {
+    let port = 4258;
+    let output_dir = "test-output-bind-host";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "bind-host-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "loopback-server",
+            "host": "127.0.0.1",
+            "port": port,
+            "client_advertise": format!("localhost:{}", port),
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("loopback-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("127.0.0.1:{}", port))
+        .await?;
+    client.flush().await?;
+
+    match lan_ip() {
+        Some(ip) => {
+            let lan = tokio::time::timeout(
+                tokio::time::Duration::from_secs(2),
+                tokio::net::TcpStream::connect((ip, port)),
+            )
+            .await;
+            assert!(
+                !matches!(lan, Ok(Ok(_))),
+                "server bound to 127.0.0.1 accepted a connection on {}",
+                ip
+            );
+        }
+        None => eprintln!("No LAN address; only checked the loopback connection"),
+    }
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+/// Creates a CA plus a server certificate for localhost and a client certificate in `dir` with the
+/// openssl CLI, returning the absolute path of each file by name.
+fn openssl_certs(dir: &std::path::Path) -> anyhow::Result<HashMap<&'static str, String>> {
+    let openssl = |args: &[&str]| -> anyhow::Result<()> {
+        let status = std::process::Command::new("openssl")
+            .args(args)
+            .current_dir(dir)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .context("Failed to run openssl")?;
+        anyhow::ensure!(status.success(), "openssl {} failed", args.join(" "));
+        Ok(())
+    };
+    openssl(&[
+        "req",
+        "-x509",
+        "-newkey",
+        "rsa:2048",
+        "-nodes",
+        "-days",
+        "1",
+        "-subj",
+        "/CN=natsforge test CA",
+        "-keyout",
+        "ca-key.pem",
+        "-out",
+        "ca.pem",
+    ])?;
+    std::fs::write(dir.join("server.ext"), "subjectAltName=DNS:localhost,IP:127.0.0.1\n")?;
+    std::fs::write(dir.join("client.ext"), "extendedKeyUsage=clientAuth\n")?;
+    for name in ["server", "client"] {
+        let (key, csr, cert, ext) = (
+            format!("{}-key.pem", name),
+            format!("{}.csr", name),
+            format!("{}.pem", name),
+            format!("{}.ext", name),
+        );
+        openssl(&[
+            "req",
+            "-newkey",
+            "rsa:2048",
+            "-nodes",
+            "-subj",
+            &format!("/CN={}", name),
+            "-keyout",
+            &key,
+            "-out",
+            &csr,
+        ])?;
+        openssl(&[
+            "x509",
+            "-req",
+            "-days",
+            "1",
+            "-in",
+            &csr,
+            "-CA",
+            "ca.pem",
+            "-CAkey",
+            "ca-key.pem",
+            "-CAcreateserial",
+            "-extfile",
+            &ext,
+            "-out",
+            &cert,
+        ])?;
+    }
+    let dir = dir.canonicalize()?;
+    Ok(
+        ["ca.pem", "server.pem", "server-key.pem", "client.pem", "client-key.pem"]
+            .into_iter()
+            .map(|name| (name, dir.join(name).to_string_lossy().into_owned()))
+            .collect(),
+    )
+}
+
+#[tokio::test]
+async fn test_mutual_tls_requires_client_certificate() -> anyhow::Result<()> {
+    let port = 4259;
+    let output_dir = "test-output-mutual-tls";
+    let _ = std::fs::remove_dir_all(output_dir);
+    let cert_dir = tempfile::TempDir::new()?;
+    let certs = openssl_certs(cert_dir.path())?;
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "mutual-tls-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "mtls-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "tls": {
+                "cert_file": certs["server.pem"],
+                "key_file": certs["server-key.pem"],
+                "ca_file": certs["ca.pem"],
+                "verify": true
+            },
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("mtls-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let url = format!("tls://localhost:{}", port);
+    let without_cert = async_nats::ConnectOptions::with_credentials(&creds)?
+        .add_root_certificates(PathBuf::from(&certs["ca.pem"]))
+        .require_tls(true)
+        .connect(&url)
+        .await;
+    assert!(without_cert.is_err(), "a client without a certificate connected");
+
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .add_root_certificates(PathBuf::from(&certs["ca.pem"]))
+        .add_client_certificate(
+            PathBuf::from(&certs["client.pem"]),
+            PathBuf::from(&certs["client-key.pem"]),
+        )
+        .require_tls(true)
+        .connect(&url)
+        .await?;
+    client.flush().await?;
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hub_leaf_over_tls() -> anyhow::Result<()> {
+    let hub_port = 4260;
+    let leaf_port = 4261;
+    let leafnode_port = 7260;
+    let output_dir = "test-output-leaf-tls";
+    let _ = std::fs::remove_dir_all(output_dir);
+    let cert_dir = tempfile::TempDir::new()?;
+    let certs = openssl_certs(cert_dir.path())?;
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "leaf-tls-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [
+            {
+                "name": "tls-hub",
+                "port": hub_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/hub", output_dir),
+                "leafnodes": {
+                    "port": leafnode_port,
+                    "tls": {
+                        "cert_file": certs["server.pem"],
+                        "key_file": certs["server-key.pem"],
+                        "ca_file": certs["ca.pem"],
+                        "verify": true
+                    }
+                },
+                "accounts": [{ "name": "APP", "users": [{ "name": "leaf" }, { "name": "worker" }] }]
+            },
+            {
+                "name": "tls-leaf",
+                "port": leaf_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/leaf", output_dir),
+                "leafnodes": {
+                    "remotes": [{
+                        "url": format!("localhost:{}", leafnode_port),
+                        "account": "APP",
+                        "credentials": "APP-leaf.creds",
+                        "tls": {
+                            "ca_file": certs["ca.pem"],
+                            "cert_file": certs["client.pem"],
+                            "key_file": certs["client-key.pem"]
+                        }
+                    }]
+                }
+            }
+        ]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let mut guards = Vec::new();
+    for name in ["tls-hub", "tls-leaf"] {
+        let server = tokio::process::Command::new("nats-server")
+            .arg("-c")
+            .arg(result.server_config(name).unwrap())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to start NATS server")?;
+        guards.push(ServerGuard(server));
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    }
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let hub = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", hub_port))
+        .await?;
+    let mut events = hub.subscribe("events.>").await?;
+    hub.flush().await?;
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let leaf = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", leaf_port))
+        .await?;
+    leaf.publish("events.edge", "over tls".into()).await?;
+    leaf.flush().await?;
+    let message = tokio::time::timeout(tokio::time::Duration::from_secs(5), events.next())
+        .await?
+        .context("Subscription closed")?;
+    assert_eq!(message.payload, "over tls");
+
+    std::fs::remove_dir_all(output_dir)?;
+    for mut guard in guards {
+        guard.0.kill().await.context("Failed to kill NATS server")?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_dev_tls_server_accepts_pinned_ca() -> anyhow::Result<()> {
+    let port = 4262;
+    let output_dir = "test-output-dev-tls";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let mut config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "dev-tls-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [{
+            "name": "dev-tls-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [{ "name": "APP", "users": [{ "name": "worker" }] }]
+        }]
+    }))?;
+    config.servers[0].tls = Some(natsforge::config::TlsConfig::dev());
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("dev-tls-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let certs = natsforge::tls::DevCerts::in_dir(std::path::Path::new(output_dir));
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let client = async_nats::ConnectOptions::with_credentials(&creds)?
+        .add_root_certificates(certs.ca_file)
+        .require_tls(true)
+        .connect(&format!("tls://localhost:{}", port))
+        .await?;
+    client.flush().await?;
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_leaf_deny_exports_stay_local() -> anyhow::Result<()> {
+    let hub_port = 4263;
+    let leaf_port = 4264;
+    let leafnode_port = 7263;
+    let output_dir = "test-output-leaf-deny";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "leaf-deny-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "servers": [
+            {
+                "name": "deny-hub",
+                "port": hub_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/hub", output_dir),
+                "leafnodes": { "port": leafnode_port },
+                "accounts": [{ "name": "APP", "users": [{ "name": "leaf" }, { "name": "worker" }] }]
+            },
+            {
+                "name": "deny-leaf",
+                "port": leaf_port,
+                "jetstream": { "enabled": false },
+                "output_dir": format!("{}/leaf", output_dir),
+                "leafnodes": {
+                    "remotes": [{
+                        "url": format!("localhost:{}", leafnode_port),
+                        "account": "APP",
+                        "credentials": "APP-leaf.creds",
+                        "deny_exports": ["internal.>"]
+                    }]
+                }
+            }
+        ]
+    }))?;
+
+    let result = NatsForge::from_config(config)?.initialize().await?;
+    let mut guards = Vec::new();
+    for name in ["deny-hub", "deny-leaf"] {
+        let server = tokio::process::Command::new("nats-server")
+            .arg("-c")
+            .arg(result.server_config(name).unwrap())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to start NATS server")?;
+        guards.push(ServerGuard(server));
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    }
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let creds = std::fs::read_to_string(result.creds("APP", "worker").unwrap())?;
+    let hub = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", hub_port))
+        .await?;
+    let mut internal = hub.subscribe("internal.>").await?;
+    let mut events = hub.subscribe("events.>").await?;
+    hub.flush().await?;
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    let leaf = async_nats::ConnectOptions::with_credentials(&creds)?
+        .connect(&format!("localhost:{}", leaf_port))
+        .await?;
+    leaf.publish("internal.secret", "stays on the leaf".into()).await?;
+    leaf.publish("events.edge", "crosses the link".into()).await?;
+    leaf.flush().await?;
+
+    let message = tokio::time::timeout(tokio::time::Duration::from_secs(5), events.next())
+        .await?
+        .context("Subscription closed")?;
+    assert_eq!(message.payload, "crosses the link");
+    let leaked = tokio::time::timeout(tokio::time::Duration::from_secs(1), internal.next()).await;
+    assert!(leaked.is_err(), "internal.secret reached the hub");
+
+    std::fs::remove_dir_all(output_dir)?;
+    for mut guard in guards {
+        guard.0.kill().await.context("Failed to kill NATS server")?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_account_mapping_reroutes_messages() -> anyhow::Result<()> {
+    let port = 4265;
+    let output_dir = "test-output-mapping";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "mapping-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "backend": "native",
+        "servers": [{
+            "name": "mapping-server",
+            "port": port,
+            "output_dir": output_dir,
+            "accounts": [{
+                "name": "APP",
+                "mappings": { "orders.new": "orders.v2.new" },
+                "users": [{ "name": "app-user" }]
+            }]
+        }]
+    }))?;
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("mapping-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    let client = result.connect("mapping-server", "APP", "app-user").await?;
+    let mut mapped = client.subscribe("orders.v2.new").await?;
+    client.flush().await?;
+    client.publish("orders.new", "order-1".into()).await?;
+    client.flush().await?;
+
+    let message = tokio::time::timeout(tokio::time::Duration::from_secs(2), mapped.next())
+        .await?
+        .context("subscription closed")?;
+    assert_eq!(message.payload.as_ref(), b"order-1");
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_provisioned_stream_stores_messages() -> anyhow::Result<()> {
+    let port = 4266;
+    let output_dir = "test-output-provision";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "provision-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "backend": "native",
+        "servers": [{
+            "name": "provision-server",
+            "port": port,
+            "output_dir": output_dir,
+            "jetstream": { "enabled": true },
+            "accounts": [{
+                "name": "APP",
+                "jetstream": true,
+                "users": [{ "name": "app-user" }]
+            }],
+            "streams": [{
+                "name": "ORDERS",
+                "subjects": ["orders.>"],
+                "max_age": "1h",
+                "account": "APP"
+            }]
+        }]
+    }))?;
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("provision-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    // The second run finds the stream in place.
+    forge.provision(&result).await?;
+    forge.provision(&result).await?;
+
+    let client = result.connect("provision-server", "APP", "app-user").await?;
+    let jetstream = async_nats::jetstream::new(client);
+    jetstream.publish("orders.new", "order-1".into()).await?.await?;
+
+    let stream = jetstream.get_stream("ORDERS").await?;
+    assert_eq!(
+        stream.cached_info().config.max_age,
+        std::time::Duration::from_secs(3600)
+    );
+    let consumer = stream
+        .create_consumer(async_nats::jetstream::consumer::pull::Config {
+            durable_name: Some("reader".to_string()),
+            ..Default::default()
+        })
+        .await?;
+    let mut messages = consumer.fetch().max_messages(1).messages().await?;
+    let message = tokio::time::timeout(tokio::time::Duration::from_secs(2), messages.next())
+        .await?
+        .context("no message in the stream")?
+        .map_err(|err| anyhow::anyhow!(err))?;
+    assert_eq!(message.payload.as_ref(), b"order-1");
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_provisioned_consumer_and_kv_bucket() -> anyhow::Result<()> {
+    let port = 4267;
+    let output_dir = "test-output-provision-kv";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = |history: u8| {
+        serde_json::from_value::<NatsConfig>(serde_json::json!({
+            "name": "provision-kv-test",
+            "operator": { "name": "test-operator", "reuse_existing": false },
+            "backend": "native",
+            "servers": [{
+                "name": "provision-kv-server",
+                "port": port,
+                "output_dir": output_dir,
+                "jetstream": { "enabled": true, "domain": "edge" },
+                "accounts": [{
+                    "name": "APP",
+                    "jetstream": true,
+                    "users": [{ "name": "app-user" }]
+                }],
+                "streams": [{ "name": "EVENTS", "subjects": ["events.>"], "account": "APP" }],
+                "consumers": [{ "stream": "EVENTS", "name": "audit", "filter_subject": "events.audit" }],
+                "kv_buckets": [{ "bucket": "settings", "history": history, "account": "APP" }]
+            }]
+        }))
+    };
+    let forge = NatsForge::from_config(config(1)?)?;
+    let result = forge.initialize().await?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("provision-kv-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    forge.provision(&result).await?;
+    forge.provision(&result).await?;
+
+    let client = result.connect("provision-kv-server", "APP", "app-user").await?;
+    let jetstream = async_nats::jetstream::with_domain(client, "edge");
+
+    let settings = jetstream.get_key_value("settings").await?;
+    settings.put("mode", "maintenance".into()).await?;
+    assert_eq!(settings.get("mode").await?.as_deref(), Some(&b"maintenance"[..]));
+
+    jetstream.publish("events.audit", "login".into()).await?.await?;
+    let consumer = jetstream
+        .get_stream("EVENTS")
+        .await?
+        .get_consumer::<async_nats::jetstream::consumer::pull::Config>("audit")
+        .await
+        .map_err(|err| anyhow::anyhow!(err))?;
+    let mut messages = consumer.fetch().max_messages(1).messages().await?;
+    let message = tokio::time::timeout(tokio::time::Duration::from_secs(2), messages.next())
+        .await?
+        .context("no message for the consumer")?
+        .map_err(|err| anyhow::anyhow!(err))?;
+    assert_eq!(message.payload.as_ref(), b"login");
+
+    // A changed spec updates the bucket in place.
+    NatsForge::from_config(config(5)?)?.provision(&result).await?;
+    let settings = jetstream.get_key_value("settings").await?;
+    assert_eq!(settings.status().await?.history(), 5);
+
+    std::fs::remove_dir_all(output_dir)?;
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_hub_mirrors_leaf_stream() -> anyhow::Result<()> {
+    let hub_port = 4268;
+    let leaf_port = 4269;
+    let leafnode_port = 7264;
+    let output_dir = "test-output-mirror";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "mirror-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "backend": "native",
+        "servers": [
+            {
+                "name": "mirror-hub",
+                "port": hub_port,
+                "jetstream": { "enabled": true, "domain": "hub" },
+                "output_dir": format!("{}/hub", output_dir),
+                "leafnodes": { "port": leafnode_port },
+                "accounts": [{ "name": "APP", "jetstream": true, "users": [{ "name": "app-user" }] }]
+            },
+            {
+                "name": "mirror-leaf",
+                "port": leaf_port,
+                "jetstream": { "enabled": true, "domain": "leaf" },
+                "output_dir": format!("{}/leaf", output_dir),
+                "leafnodes": {
+                    "remotes": [{ "url": format!("localhost:{}", leafnode_port), "account": "APP", "user": "app-user" }]
+                },
+                "streams": [{ "name": "READINGS", "subjects": ["sensors.>"], "account": "APP" }]
+            }
+        ],
+        "mirrors": [{
+            "name": "READINGS_MIRROR",
+            "source_stream": "READINGS",
+            "source_domain": "leaf",
+            "dest_domain": "hub",
+            "account": "APP"
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let mut guards = Vec::new();
+    for name in ["mirror-hub", "mirror-leaf"] {
+        let server = tokio::process::Command::new("nats-server")
+            .arg("-c")
+            .arg(result.server_config(name).unwrap())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .context("Failed to start NATS server")?;
+        guards.push(ServerGuard(server));
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+    }
+    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+    forge.provision(&result).await?;
+
+    let leaf = result.connect("mirror-leaf", "APP", "app-user").await?;
+    async_nats::jetstream::with_domain(leaf, "leaf")
+        .publish("sensors.temp", "21.5".into())
+        .await?
+        .await?;
+
+    let hub = result.connect("mirror-hub", "APP", "app-user").await?;
+    let mirror = async_nats::jetstream::with_domain(hub, "hub")
+        .get_stream("READINGS_MIRROR")
+        .await?;
+    let mut message = None;
+    for _ in 0..50 {
+        if let Ok(stored) = mirror.get_raw_message(1).await {
+            message = Some(stored);
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let message = message.context("the mirror never received the leaf's message")?;
+    assert_eq!(message.payload.as_ref(), b"21.5");
+
+    std::fs::remove_dir_all(output_dir)?;
+    for mut guard in guards {
+        guard.0.kill().await.context("Failed to kill NATS server")?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_reload_applies_revocation_to_running_server() -> anyhow::Result<()> {
+    let port = 4279;
+    let output_dir = "test-output-reload";
+    let _ = std::fs::remove_dir_all(output_dir);
+
+    let config = serde_json::from_value::<NatsConfig>(serde_json::json!({
+        "name": "reload-test",
+        "operator": { "name": "test-operator", "reuse_existing": false },
+        "system_user": { "name": "sys" },
+        "servers": [{
+            "name": "reload-server",
+            "port": port,
+            "jetstream": { "enabled": false },
+            "output_dir": output_dir,
+            "accounts": [{ "name": "APP", "users": [{ "name": "kept" }, { "name": "lost" }] }]
+        }]
+    }))?;
+
+    let forge = NatsForge::from_config(config)?;
+    let result = forge.initialize().await?;
+    let kept_creds = std::fs::read_to_string(result.creds("APP", "kept").unwrap())?;
+    let lost_creds = std::fs::read_to_string(result.creds("APP", "lost").unwrap())?;
+
+    let server = tokio::process::Command::new("nats-server")
+        .arg("-c")
+        .arg(result.server_config("reload-server").unwrap())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .context("Failed to start NATS server")?;
+    let mut server_guard = ServerGuard(server);
+
+    let url = format!("localhost:{}", port);
+    result.connect("reload-server", "APP", "lost").await?;
+
+    // The memory resolver only sees the revocation in the preloads of the reloaded config.
+    forge.revoke_user("APP", "lost").await?;
+    let report = forge.reload(&result).await?;
+    assert!(report.is_success(), "{:?}", report);
+    assert_eq!(report.servers.len(), 1);
+
+    let lost = async_nats::ConnectOptions::with_credentials(&lost_creds)?
+        .connect(&url)
+        .await;
+    assert!(lost.is_err(), "the revoked creds still connect");
+    async_nats::ConnectOptions::with_credentials(&kept_creds)?
+        .connect(&url)
+        .await?;
+
+    server_guard.0.kill().await.context("Failed to kill NATS server")?;
+    forge.cleanup(&result, true).await?;
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use natsforge::{
+    config::{
+        AccountConfig, ArtifactMode, BackendKind, Durability, NatsConfig, OperatorConfig, ServerConfig, UserConfig,
+    },
+    writer::{FsSink, OutputSink},
+    NatsForge,
+};
+
+#[derive(Default)]
+struct Recorded {
+    renamed: Vec<PathBuf>,
+    fsynced: Vec<PathBuf>,
+}
+
+/// Passes everything through to the filesystem and remembers what was renamed and synced.
+#[derive(Clone, Default)]
+struct RecordingSink(Arc<Mutex<Recorded>>);
+
+impl OutputSink for RecordingSink {
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        FsSink.write(path, contents)
+    }
+
+    fn fsync(&self, path: &Path) -> io::Result<()> {
+        self.0.lock().unwrap().fsynced.push(path.to_path_buf());
+        FsSink.fsync(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut recorded = self.0.lock().unwrap();
+        // An artifact must have been synced under its temp name before it becomes visible.
+        if !recorded.fsynced.is_empty() {
+            assert_eq!(recorded.fsynced.last().map(PathBuf::as_path), Some(from));
+        }
+        recorded.renamed.push(to.to_path_buf());
+        FsSink.rename(from, to)
+    }
+}
+
+fn config(durability: Durability, output_dir: &Path) -> NatsConfig {
+    NatsConfig {
+        operator: OperatorConfig {
+            name: "durable-operator".to_string(),
+            reuse_existing: false,
+            account_server_url: None,
+            service_urls: vec![],
+        },
+        servers: vec![ServerConfig {
+            name: "durable-server".to_string(),
+            port: 4222,
+            output_dir: output_dir.to_path_buf(),
+            accounts: vec![AccountConfig {
+                name: "APP".to_string(),
+                users: vec![UserConfig {
+                    name: "app-user".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        backend: BackendKind::Native,
+        durability,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_fsync_durability_syncs_every_artifact() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let sink = RecordingSink::default();
+    let forge =
+        NatsForge::from_config(config(Durability::Fsync, output_dir.path()))?.with_output_sink(Box::new(sink.clone()));
+    let result = forge.initialize().await?;
+
+    let recorded = sink.0.lock().unwrap();
+    let app = result.account("APP").unwrap();
+    let artifacts = [
+        result.server_config("durable-server").unwrap(),
+        result.creds("APP", "app-user").unwrap(),
+        app.jwt_path.as_path(),
+    ];
+    for artifact in artifacts {
+        assert!(
+            recorded.renamed.iter().any(|p| p == artifact),
+            "{} not written",
+            artifact.display()
+        );
+    }
+    // One sync for the file, one for its directory.
+    assert_eq!(
+        recorded.fsynced.len(),
+        recorded.renamed.len() * if cfg!(unix) { 2 } else { 1 }
+    );
+    assert!(!output_dir
+        .path()
+        .read_dir()?
+        .any(|e| e.unwrap().file_name().to_string_lossy().ends_with(".tmp")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fast_durability_skips_fsync() -> anyhow::Result<()> {
+    let output_dir = tempfile::TempDir::new()?;
+    let sink = RecordingSink::default();
+    let forge =
+        NatsForge::from_config(config(Durability::Fast, output_dir.path()))?.with_output_sink(Box::new(sink.clone()));
+    let result = forge.initialize().await?;
+
+    let recorded = sink.0.lock().unwrap();
+    let server_config = result.server_config("durable-server").unwrap();
+    assert!(recorded.renamed.iter().any(|p| p == server_config));
+    assert!(recorded.fsynced.is_empty());
+    assert!(std::fs::read_to_string(server_config)?.contains("operator"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_in_memory_mode_writes_nothing() -> anyhow::Result<()> {
+    let root = tempfile::TempDir::new()?;
+    let output_dir = root.path().join("never-created");
+    let mut config = config(Durability::Fsync, &output_dir);
+    config.artifact_mode = ArtifactMode::InMemory;
+    let sink = RecordingSink::default();
+    let forge = NatsForge::from_config(config)?.with_output_sink(Box::new(sink.clone()));
+    let result = forge.initialize().await?;
+
+    assert!(!output_dir.exists());
+    let recorded = sink.0.lock().unwrap();
+    assert!(recorded.renamed.is_empty() && recorded.fsynced.is_empty());
+
+    let artifacts = result.artifacts.as_ref().expect("in-memory artifacts");
+    assert!(artifacts.operator_jwt.starts_with("eyJ"));
+    assert!(artifacts.account_jwts.contains_key("APP"));
+    let server_config = &artifacts.server_configs["durable-server"];
+    assert!(server_config.contains(&artifacts.operator_jwt));
+    assert_eq!(
+        result.server_config("durable-server"),
+        Some(output_dir.join("nats.conf").as_path())
+    );
+
+    let creds = artifacts.creds("APP", "app-user").unwrap();
+    async_nats::ConnectOptions::with_credentials(creds)?;
+    let seed = creds.lines().find(|line| line.starts_with("SU")).unwrap();
+    assert!(
+        !format!("{:?}", result).contains(seed),
+        "seeds must not show up in debug output"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_in_memory_leafnode_credentials() -> anyhow::Result<()> {
+    let root = tempfile::TempDir::new()?;
+    let mut config = serde_json::from_str::<NatsConfig>(include_str!("hub_leaf.json"))?;
+    config.backend = BackendKind::Native;
+    config.artifact_mode = ArtifactMode::InMemory;
+    config.servers[0].output_dir = root.path().join("hub");
+    config.servers[1].output_dir = PathBuf::from("/etc/nats/leaf");
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    assert!(!root.path().join("hub").exists());
+    let artifacts = result.artifacts.as_ref().unwrap();
+    let leaf_files = &artifacts.server_files["edge-leaf"];
+    let creds_path = PathBuf::from("/etc/nats/leaf/app-service-service-user.creds");
+    assert_eq!(leaf_files.len(), 1);
+    assert_eq!(
+        leaf_files[&creds_path],
+        artifacts.creds("app-service", "service-user").unwrap()
+    );
+    async_nats::ConnectOptions::with_credentials(&leaf_files[&creds_path])?;
+    assert!(artifacts.server_configs["edge-leaf"].contains(&format!("credentials: \"{}\"", creds_path.display())));
+    assert!(!artifacts.server_files.contains_key("central-hub"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_creds_are_private() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = |path: &Path| -> anyhow::Result<u32> { Ok(std::fs::metadata(path)?.permissions().mode() & 0o777) };
+
+    let root = tempfile::TempDir::new()?;
+    let mut config = serde_json::from_str::<NatsConfig>(include_str!("hub_leaf.json"))?;
+    config.backend = BackendKind::Native;
+    config.durability = Durability::Fast;
+    config.private_output_dirs = true;
+    config.servers[0].output_dir = root.path().join("hub");
+    config.servers[1].output_dir = root.path().join("leaf");
+    let result = NatsForge::from_config(config)?.initialize().await?;
+
+    let mut creds_paths: Vec<PathBuf> = result
+        .account_jwts
+        .values()
+        .flat_map(|account| account.creds.values().cloned())
+        .collect();
+    assert!(!creds_paths.is_empty());
+    // The leafnode remote's copy of the creds.
+    creds_paths.push(root.path().join("leaf/app-service-service-user.creds"));
+    for path in &creds_paths {
+        assert_eq!(mode(path)?, 0o600, "{}", path.display());
+    }
+    for dir in ["hub", "leaf"] {
+        assert_eq!(mode(&root.path().join(dir))?, 0o700, "{}", dir);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nested_relative_output_dir_is_created() -> anyhow::Result<()> {
+    let root = tempfile::TempDir::new()?;
+    let relative = PathBuf::from(format!("test-output-nested-{}", std::process::id()));
+    let mut config = serde_json::from_str::<NatsConfig>(include_str!("hub_leaf.json"))?;
+    config.backend = BackendKind::Native;
+    config.durability = Durability::Fast;
+    config.servers[0].output_dir = root.path().join("hub");
+    // The second server's directory doesn't exist yet, nor do its parents.
+    config.servers[1].output_dir = relative.join("a/b/c/leaf");
+    let result = NatsForge::from_config(config)?.initialize().await;
+    let leaf_dir = std::fs::canonicalize(relative.join("a/b/c/leaf"));
+    std::fs::remove_dir_all(&relative)?;
+    let result = result?;
+
+    let leaf_dir = leaf_dir?;
+    let hub_dir = std::fs::canonicalize(root.path())?.join("hub");
+    assert_eq!(result.servers["edge-leaf"], leaf_dir.join("nats.conf"));
+    assert!(result.account_jwts["app-service"]
+        .creds
+        .values()
+        .all(|path| path.starts_with(hub_dir.as_path())));
+    Ok(())
+}